@@ -0,0 +1,301 @@
+//! Bundling the configuration and state into a portable archive.
+//!
+//! `monitorbot export` writes a zstd-compressed tar file containing the
+//! configuration file as `config.toml` and everything in the state directory
+//! under `state/`. `monitorbot restore` restores it, possibly on another
+//! machine with different paths. History files are named after their URLs, so
+//! they still match the `[[url]]` entries in the configuration.
+
+use anyhow::{Context, bail};
+use std::fs;
+use std::io::{self, Read, Write};
+use std::path::{Component, Path, PathBuf};
+
+/// Name of the configuration file within the archive.
+const CONFIG_NAME: &str = "config.toml";
+
+/// Directory containing the state within the archive.
+const STATE_DIR: &str = "state";
+
+/// What was restored from an archive.
+#[derive(Debug, Default)]
+pub struct Restored {
+    /// Whether the configuration file was restored.
+    pub config: bool,
+
+    /// The number of files restored into the state directory.
+    pub files: usize,
+}
+
+/// Write an archive of the configuration file and state directory.
+///
+/// The configuration file is skipped if it doesn’t exist. Paths in `exclude`
+/// are left out of the state; the configuration file is always left out of it
+/// so that it isn’t archived twice when it’s in the state directory.
+///
+/// # Errors
+///
+/// Returns an error if the state directory couldn’t be read or the archive
+/// couldn’t be written.
+pub fn create<W: Write>(
+    writer: W,
+    state_dir: &Path,
+    config_path: &Path,
+    exclude: &[PathBuf],
+) -> anyhow::Result<W> {
+    let mut builder = tar::Builder::new(zstd::Encoder::new(writer, 0)?);
+    builder.follow_symlinks(false);
+
+    match fs::File::open(config_path) {
+        Ok(mut file) => builder.append_file(CONFIG_NAME, &mut file)?,
+        Err(error) if error.kind() == io::ErrorKind::NotFound => {}
+        Err(error) => {
+            return Err(error).with_context(|| {
+                format!("could not read {}", config_path.display())
+            });
+        }
+    }
+
+    let exclude: Vec<PathBuf> = exclude
+        .iter()
+        .map(PathBuf::as_path)
+        .chain([config_path])
+        .filter_map(|path| fs::canonicalize(path).ok())
+        .collect();
+    append_dir(&mut builder, state_dir, Path::new(STATE_DIR), &exclude)
+        .with_context(|| {
+            format!("could not archive {}", state_dir.display())
+        })?;
+
+    Ok(builder.into_inner()?.finish()?)
+}
+
+/// Add the contents of a directory to an archive, sorted by name.
+///
+/// # Errors
+///
+/// Returns an error if the directory couldn’t be read.
+fn append_dir<W: Write>(
+    builder: &mut tar::Builder<W>,
+    dir: &Path,
+    name: &Path,
+    exclude: &[PathBuf],
+) -> io::Result<()> {
+    let mut entries = fs::read_dir(dir)?.collect::<io::Result<Vec<_>>>()?;
+    entries.sort_by_key(fs::DirEntry::file_name);
+
+    builder.append_dir(name, dir)?;
+    for entry in entries {
+        let path = entry.path();
+        let entry_name = name.join(entry.file_name());
+        let file_type = entry.file_type()?;
+        if file_type.is_dir() {
+            append_dir(builder, &path, &entry_name, exclude)?;
+        } else if file_type.is_symlink()
+            || !fs::canonicalize(&path)
+                .is_ok_and(|canonical| exclude.contains(&canonical))
+        {
+            builder.append_path_with_name(&path, &entry_name)?;
+        }
+    }
+    Ok(())
+}
+
+/// Restore an archive written by [`create()`].
+///
+/// Unless `overwrite` is set, this refuses to replace existing history or a
+/// configuration file with different contents. Entries that would be written
+/// outside of the state directory are rejected.
+///
+/// # Errors
+///
+/// Returns an error if the archive is invalid, or if restoring it would
+/// overwrite something and `overwrite` isn’t set.
+pub fn extract<R: Read>(
+    reader: R,
+    state_dir: &Path,
+    config_path: &Path,
+    overwrite: bool,
+) -> anyhow::Result<Restored> {
+    if !overwrite && has_history(state_dir)? {
+        bail!(
+            "{} already contains history; use --overwrite to replace it",
+            state_dir.display()
+        );
+    }
+
+    let mut archive = tar::Archive::new(zstd::Decoder::new(reader)?);
+    let mut restored = Restored::default();
+    for entry in archive.entries()? {
+        let mut entry = entry?;
+        let path = entry.path()?.into_owned();
+        if path == Path::new(CONFIG_NAME) {
+            let mut contents = Vec::new();
+            entry.read_to_end(&mut contents)?;
+            restore_config(config_path, &contents, overwrite)?;
+            restored.config = true;
+            continue;
+        }
+
+        let Some(relative) = path
+            .strip_prefix(STATE_DIR)
+            .ok()
+            .filter(|relative| is_plain(relative))
+        else {
+            bail!("unexpected path in archive: {}", path.display());
+        };
+
+        let entry_type = entry.header().entry_type();
+        if entry_type.is_symlink() {
+            // Redirects are links to another file in the same directory.
+            let target = entry.link_name()?.unwrap_or_default();
+            if target.components().count() != 1 || !is_plain(&target) {
+                bail!(
+                    "unexpected link in archive: {} -> {}",
+                    path.display(),
+                    target.display()
+                );
+            }
+        } else if !entry_type.is_file() && !entry_type.is_dir() {
+            bail!("unexpected entry in archive: {}", path.display());
+        }
+
+        let destination = state_dir.join(relative);
+        if let Some(parent) = destination.parent() {
+            fs::create_dir_all(parent)?;
+        }
+        entry.unpack(&destination).with_context(|| {
+            format!("could not write {}", destination.display())
+        })?;
+        if !entry_type.is_dir() {
+            restored.files = restored.files.saturating_add(1);
+        }
+    }
+
+    Ok(restored)
+}
+
+/// Write the configuration file from an archive.
+///
+/// # Errors
+///
+/// Returns an error if a different configuration file exists and `overwrite`
+/// isn’t set, or if it couldn’t be written.
+fn restore_config(
+    config_path: &Path,
+    contents: &[u8],
+    overwrite: bool,
+) -> anyhow::Result<()> {
+    match fs::read(config_path) {
+        Ok(existing) if existing == contents => return Ok(()),
+        Ok(_) if !overwrite => bail!(
+            "{} already exists; use --overwrite to replace it",
+            config_path.display()
+        ),
+        Ok(_) => {}
+        Err(error) if error.kind() == io::ErrorKind::NotFound => {}
+        Err(error) => {
+            return Err(error).with_context(|| {
+                format!("could not read {}", config_path.display())
+            });
+        }
+    }
+
+    if let Some(parent) = config_path.parent() {
+        fs::create_dir_all(parent)?;
+    }
+    fs::write(config_path, contents)
+        .with_context(|| format!("could not write {}", config_path.display()))
+}
+
+/// Check if the state directory contains any history.
+///
+/// # Errors
+///
+/// Returns an error if the directory exists but couldn’t be read.
+fn has_history(state_dir: &Path) -> io::Result<bool> {
+    let entries = match fs::read_dir(state_dir) {
+        Ok(entries) => entries,
+        Err(error) if error.kind() == io::ErrorKind::NotFound => {
+            return Ok(false);
+        }
+        Err(error) => return Err(error),
+    };
+    for entry in entries {
+        if entry?.path().extension().is_some_and(|ext| ext == "ron") {
+            return Ok(true);
+        }
+    }
+    Ok(false)
+}
+
+/// Check if a path is relative and only contains normal components, i.e. no
+/// `..`.
+fn is_plain(path: &Path) -> bool {
+    path.components()
+        .all(|component| matches!(component, Component::Normal(_)))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use assert2::check;
+
+    /// Create a unique empty directory for a test.
+    fn test_dir(name: &str) -> PathBuf {
+        let dir = std::env::temp_dir()
+            .join(format!("monitorbot-{}-{name}", std::process::id()));
+        let _ = fs::remove_dir_all(&dir);
+        fs::create_dir_all(&dir).unwrap();
+        dir
+    }
+
+    #[test]
+    fn test_round_trip() {
+        let from = test_dir("archive-from");
+        fs::create_dir(from.join("bodies")).unwrap();
+        fs::write(from.join("bodies/abc"), "body").unwrap();
+        fs::write(from.join("https:||example.com|.ron"), "history").unwrap();
+        fs::write(from.join("config.toml"), "[[url]]").unwrap();
+        fs::write(from.join("key.txt"), "secret").unwrap();
+        #[cfg(unix)]
+        std::os::unix::fs::symlink(
+            "https:||example.com|.ron",
+            from.join("http:||example.com|.ron"),
+        )
+        .unwrap();
+
+        let archive = create(
+            Vec::new(),
+            &from,
+            &from.join("config.toml"),
+            &[from.join("key.txt")],
+        )
+        .unwrap();
+
+        let to = test_dir("archive-to");
+        let config_path = to.join("config/config.toml");
+        let state_dir = to.join("state");
+        let restored =
+            extract(&archive[..], &state_dir, &config_path, false).unwrap();
+        check!(restored.config);
+        check!(fs::read_to_string(&config_path).unwrap() == "[[url]]");
+        check!(
+            fs::read_to_string(state_dir.join("bodies/abc")).unwrap() == "body"
+        );
+        check!(!state_dir.join("config.toml").exists());
+        check!(!state_dir.join("key.txt").exists());
+        #[cfg(unix)]
+        check!(
+            fs::read_link(state_dir.join("http:||example.com|.ron")).unwrap()
+                == Path::new("https:||example.com|.ron")
+        );
+
+        // Don’t clobber existing history unless asked to.
+        check!(extract(&archive[..], &state_dir, &config_path, false).is_err());
+        check!(extract(&archive[..], &state_dir, &config_path, true).is_ok());
+
+        fs::remove_dir_all(from).unwrap();
+        fs::remove_dir_all(to).unwrap();
+    }
+}
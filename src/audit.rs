@@ -0,0 +1,568 @@
+//! Tamper-evident log of checks.
+//!
+//! With `[audit_log]` in the configuration file, every check of a URL is
+//! appended to a log with one JSON entry per line. Each entry includes the
+//! hash of the previous one, so editing, removing, or reordering entries
+//! breaks the chain, which `monitorbot audit verify` detects. Entries can also
+//! be signed with a [minisign] key so that the log can’t be rewritten by
+//! someone without the key:
+//!
+//! ```toml
+//! [audit_log]
+//! file = "audit.jsonl"              # Default: audit.jsonl in the state dir.
+//! signing_key = "monitorbot.key"    # Relative to the config file.
+//! password = { env = "MONITORBOT_KEY_PASSWORD" }
+//! ```
+//!
+//! [minisign]: https://jedisct1.github.io/minisign/
+
+use anyhow::{Context, bail};
+use chrono::{DateTime, Utc};
+use std::fs;
+use std::io::{self, BufRead, Write as _};
+use std::path::{Path, PathBuf};
+use url::Url;
+
+use crate::progress::Outcome;
+use crate::secret::Secret;
+use crate::state::{Revision, sha256_hex};
+
+/// Configuration for the audit log.
+#[derive(Clone, Debug, Default, serde::Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct AuditConfig {
+    /// Where to write the log. Defaults to `audit.jsonl` in the state
+    /// directory.
+    #[serde(default)]
+    pub file: Option<PathBuf>,
+
+    /// minisign secret key to sign entries with.
+    #[serde(default)]
+    pub signing_key: Option<PathBuf>,
+
+    /// Password for `signing_key`, if it’s encrypted.
+    #[serde(default)]
+    pub password: Option<Secret>,
+}
+
+impl AuditConfig {
+    /// Make relative paths relative to `base_dir`.
+    pub fn resolve_paths(&mut self, base_dir: &Path) {
+        for path in [&mut self.file, &mut self.signing_key]
+            .into_iter()
+            .flatten()
+        {
+            if path.is_relative() {
+                *path = base_dir.join(&*path);
+            }
+        }
+    }
+
+    /// Get the path to the log.
+    #[must_use]
+    pub fn path(&self, state_dir: &Path) -> PathBuf {
+        self.file
+            .clone()
+            .unwrap_or_else(|| state_dir.join("audit.jsonl"))
+    }
+}
+
+/// What happened in a check, as recorded in the log.
+#[derive(Clone, Debug, Eq, PartialEq, serde::Deserialize, serde::Serialize)]
+pub struct Record {
+    /// Position of the entry in the log, starting from 1.
+    pub seq: u64,
+
+    /// When the entry was written.
+    pub time: DateTime<Utc>,
+
+    /// The URL that was checked.
+    pub url: Url,
+
+    /// What happened, e.g. `changed` or `error`.
+    pub outcome: String,
+
+    /// HTTP status of the response, if there was one.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub status: Option<u16>,
+
+    /// SHA-256 of the content that was compared, if there was a response.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub content_sha256: Option<String>,
+
+    /// The error, if the check failed.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub error: Option<String>,
+
+    /// Hash of the previous entry, or `None` for the first entry.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub previous: Option<String>,
+}
+
+impl Record {
+    /// Get the SHA-256 of the record as JSON.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if the record couldn’t be serialized.
+    pub fn hash(&self) -> serde_json::Result<String> {
+        Ok(sha256_hex(&serde_json::to_vec(self)?))
+    }
+}
+
+/// An entry in the log.
+#[derive(Clone, Debug, serde::Deserialize, serde::Serialize)]
+pub struct Entry {
+    /// What happened.
+    #[serde(flatten)]
+    pub record: Record,
+
+    /// Hash of `record`; see [`Record::hash()`].
+    pub hash: String,
+
+    /// minisign signature of `hash`, if the log is signed.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub signature: Option<String>,
+}
+
+/// The audit log, open for appending.
+#[derive(Debug)]
+pub struct AuditLog {
+    /// Path to the log.
+    path: PathBuf,
+
+    /// Key to sign entries with, if any.
+    key: Option<SigningKey>,
+
+    /// Sequence number and hash of the last entry, if there is one.
+    last: Option<(u64, String)>,
+}
+
+impl AuditLog {
+    /// Open the log, loading the signing key if there is one.
+    ///
+    /// This only checks the last entry; use [`verify()`] to check the whole
+    /// log.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if the key couldn’t be loaded, or the log couldn’t be
+    /// read or its last entry is invalid.
+    pub fn open(
+        config: &AuditConfig,
+        state_dir: &Path,
+    ) -> anyhow::Result<Self> {
+        let path = config.path(state_dir);
+        let key = config
+            .signing_key
+            .as_deref()
+            .map(|key| {
+                let password = config.password.as_ref().map(Secret::expose);
+                load_key(key, password.transpose()?).with_context(|| {
+                    format!("could not load signing key {}", key.display())
+                })
+            })
+            .transpose()?;
+
+        let last = match fs::File::open(&path) {
+            Ok(file) => last_entry(file).with_context(|| {
+                format!("invalid audit log {}", path.display())
+            })?,
+            Err(error) if error.kind() == io::ErrorKind::NotFound => None,
+            Err(error) => {
+                return Err(error).with_context(|| {
+                    format!("could not read {}", path.display())
+                });
+            }
+        };
+
+        Ok(Self { path, key, last })
+    }
+
+    /// Get the path to the log.
+    #[must_use]
+    pub fn path(&self) -> &Path {
+        &self.path
+    }
+
+    /// Append an entry for a check of `url`.
+    ///
+    /// `revision` is the latest revision stored for the URL, if there is one.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if the entry couldn’t be signed or written.
+    pub fn append(
+        &mut self,
+        url: &Url,
+        outcome: Outcome,
+        revision: Option<&Revision>,
+        error: Option<&str>,
+    ) -> anyhow::Result<()> {
+        let record = Record {
+            seq: self
+                .last
+                .as_ref()
+                .map_or(1, |(seq, _)| seq.saturating_add(1)),
+            time: Utc::now(),
+            url: url.clone(),
+            outcome: outcome.to_string(),
+            status: revision.map(|revision| revision.status.as_u16()),
+            content_sha256: revision
+                .map(|revision| revision.content_sha256.clone()),
+            error: error.map(str::to_owned),
+            previous: self.last.as_ref().map(|(_, hash)| hash.clone()),
+        };
+        let hash = record.hash()?;
+        let signature =
+            self.key.as_ref().map(|key| sign(key, &hash)).transpose()?;
+        let seq = record.seq;
+        let mut line = serde_json::to_string(&Entry {
+            record,
+            hash: hash.clone(),
+            signature,
+        })?;
+        line.push('\n');
+
+        if let Some(parent) = self.path.parent() {
+            fs::create_dir_all(parent)?;
+        }
+        let mut file = fs::OpenOptions::new()
+            .create(true)
+            .append(true)
+            .open(&self.path)
+            .with_context(|| {
+                format!("could not open {}", self.path.display())
+            })?;
+        file.write_all(line.as_bytes())?;
+        file.sync_data()?;
+
+        self.last = Some((seq, hash));
+        Ok(())
+    }
+}
+
+/// The result of verifying a log.
+#[derive(Clone, Copy, Debug, Default, Eq, PartialEq)]
+pub struct Verified {
+    /// Number of entries.
+    pub entries: usize,
+
+    /// Number of signed entries.
+    pub signed: usize,
+
+    /// Whether signatures were verified.
+    pub signatures_verified: bool,
+}
+
+/// Verify the hash chain of a log, and its signatures if `public_key` is
+/// passed.
+///
+/// # Errors
+///
+/// Returns an error if the log couldn’t be read, or if any entry is invalid,
+/// out of order, doesn’t match its hash, or doesn’t have a valid signature.
+pub fn verify<R: io::Read>(
+    reader: R,
+    public_key: Option<&Path>,
+) -> anyhow::Result<Verified> {
+    let public_key = public_key
+        .map(|path| {
+            load_public_key(path).with_context(|| {
+                format!("could not load public key {}", path.display())
+            })
+        })
+        .transpose()?;
+
+    let mut verified = Verified {
+        signatures_verified: public_key.is_some(),
+        ..Verified::default()
+    };
+    let mut previous: Option<String> = None;
+    for (i, line) in io::BufReader::new(reader).lines().enumerate() {
+        let number = i.saturating_add(1);
+        let line = line?;
+        let entry: Entry = serde_json::from_str(&line)
+            .with_context(|| format!("line {number}: invalid entry"))?;
+        let expected_seq = u64::try_from(number).unwrap_or(u64::MAX);
+        if entry.record.seq != expected_seq {
+            bail!(
+                "line {number}: expected entry {expected_seq}, found {}",
+                entry.record.seq,
+            );
+        }
+        if entry.record.previous != previous {
+            bail!("line {number}: previous hash doesn’t match line {i}");
+        }
+        if entry.record.hash()? != entry.hash {
+            bail!("line {number}: hash doesn’t match contents");
+        }
+        match (&public_key, &entry.signature) {
+            (Some(key), Some(signature)) => {
+                verify_signature(key, signature, &entry.hash).with_context(
+                    || format!("line {number}: invalid signature"),
+                )?;
+            }
+            (Some(_), None) => bail!("line {number}: entry isn’t signed"),
+            (None, _) => {}
+        }
+
+        verified.entries = number;
+        if entry.signature.is_some() {
+            verified.signed = verified.signed.saturating_add(1);
+        }
+        previous = Some(entry.hash);
+    }
+    Ok(verified)
+}
+
+/// Find the sequence number and hash of the last entry in a log.
+///
+/// # Errors
+///
+/// Returns an error if the log couldn’t be read, or the last entry couldn’t be
+/// parsed or doesn’t match its hash.
+fn last_entry<R: io::Read>(reader: R) -> anyhow::Result<Option<(u64, String)>> {
+    let mut last = None;
+    for line in io::BufReader::new(reader).lines() {
+        let line = line?;
+        if !line.trim().is_empty() {
+            last = Some(line);
+        }
+    }
+    let Some(line) = last else {
+        return Ok(None);
+    };
+    let entry: Entry = serde_json::from_str(&line)?;
+    if entry.record.hash()? != entry.hash {
+        bail!("last entry doesn’t match its hash");
+    }
+    Ok(Some((entry.record.seq, entry.hash)))
+}
+
+/// A minisign secret key.
+#[cfg(feature = "minisign")]
+type SigningKey = minisign::SecretKey;
+
+/// Signing isn’t supported without the `minisign` feature.
+#[cfg(not(feature = "minisign"))]
+type SigningKey = std::convert::Infallible;
+
+/// A minisign public key.
+#[cfg(feature = "minisign")]
+type PublicKey = minisign::PublicKey;
+
+/// Signing isn’t supported without the `minisign` feature.
+#[cfg(not(feature = "minisign"))]
+type PublicKey = std::convert::Infallible;
+
+/// Load a minisign secret key, decrypting it with `password` if passed.
+///
+/// # Errors
+///
+/// Returns an error if the key couldn’t be read or decrypted.
+#[cfg(feature = "minisign")]
+fn load_key(path: &Path, password: Option<&str>) -> anyhow::Result<SigningKey> {
+    let key_box =
+        minisign::SecretKeyBox::from_string(&fs::read_to_string(path)?)?;
+    // Without a password, minisign would prompt for one.
+    Ok(match password {
+        Some(password) => key_box.into_secret_key(Some(password.to_owned()))?,
+        None => key_box.into_unencrypted_secret_key()?,
+    })
+}
+
+/// Load a minisign secret key.
+///
+/// # Errors
+///
+/// Always returns an error.
+#[cfg(not(feature = "minisign"))]
+fn load_key(
+    _path: &Path,
+    _password: Option<&str>,
+) -> anyhow::Result<SigningKey> {
+    bail!("support for signing with minisign is not enabled")
+}
+
+/// Load a minisign public key.
+///
+/// # Errors
+///
+/// Returns an error if the key couldn’t be read or parsed.
+#[cfg(feature = "minisign")]
+fn load_public_key(path: &Path) -> anyhow::Result<PublicKey> {
+    Ok(minisign::PublicKey::from_file(path)?)
+}
+
+/// Load a minisign public key.
+///
+/// # Errors
+///
+/// Always returns an error.
+#[cfg(not(feature = "minisign"))]
+fn load_public_key(_path: &Path) -> anyhow::Result<PublicKey> {
+    bail!("support for signing with minisign is not enabled")
+}
+
+/// Sign the hash of an entry.
+///
+/// # Errors
+///
+/// Returns an error if the signature couldn’t be created.
+#[cfg(feature = "minisign")]
+fn sign(key: &SigningKey, hash: &str) -> anyhow::Result<String> {
+    Ok(minisign::sign(None, key, hash.as_bytes(), None, None)?.into_string())
+}
+
+/// Sign the hash of an entry.
+///
+/// # Errors
+///
+/// Always returns an error, though keys can’t be loaded in the first place.
+#[cfg(not(feature = "minisign"))]
+#[expect(
+    clippy::trivially_copy_pass_by_ref,
+    reason = "matches the minisign version"
+)]
+fn sign(_key: &SigningKey, _hash: &str) -> anyhow::Result<String> {
+    bail!("support for signing with minisign is not enabled")
+}
+
+/// Verify the signature of the hash of an entry.
+///
+/// # Errors
+///
+/// Returns an error if the signature is invalid.
+#[cfg(feature = "minisign")]
+fn verify_signature(
+    key: &PublicKey,
+    signature: &str,
+    hash: &str,
+) -> anyhow::Result<()> {
+    let signature = minisign::SignatureBox::from_string(signature)?;
+    minisign::verify(
+        key,
+        &signature,
+        io::Cursor::new(hash.as_bytes()),
+        true,
+        false,
+        false,
+    )?;
+    Ok(())
+}
+
+/// Verify the signature of the hash of an entry.
+///
+/// # Errors
+///
+/// Always returns an error, though keys can’t be loaded in the first place.
+#[cfg(not(feature = "minisign"))]
+#[expect(
+    clippy::trivially_copy_pass_by_ref,
+    reason = "matches the minisign version"
+)]
+fn verify_signature(
+    _key: &PublicKey,
+    _signature: &str,
+    _hash: &str,
+) -> anyhow::Result<()> {
+    bail!("support for signing with minisign is not enabled")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use assert2::check;
+
+    #[test]
+    fn test_audit_log() {
+        let dir = std::env::temp_dir()
+            .join(format!("monitorbot-{}-audit", std::process::id()));
+        let _ = fs::remove_dir_all(&dir);
+        let config = AuditConfig::default();
+        let url: Url = "https://example.com/".parse().unwrap();
+
+        let mut log = AuditLog::open(&config, &dir).unwrap();
+        log.append(&url, Outcome::New, None, None).unwrap();
+        log.append(&url, Outcome::Error, None, Some("timed out"))
+            .unwrap();
+        drop(log);
+        let mut log = AuditLog::open(&config, &dir).unwrap();
+        log.append(&url, Outcome::Unchanged, None, None).unwrap();
+
+        let path = dir.join("audit.jsonl");
+        let contents = fs::read_to_string(&path).unwrap();
+        check!(
+            verify(contents.as_bytes(), None).unwrap()
+                == Verified {
+                    entries: 3,
+                    signed: 0,
+                    signatures_verified: false
+                }
+        );
+
+        // Editing an entry breaks its hash.
+        let edited = contents.replace("timed out", "fine");
+        let error = verify(edited.as_bytes(), None).unwrap_err();
+        check!(error.to_string() == "line 2: hash doesn’t match contents");
+
+        // Removing an entry breaks the chain.
+        let lines: Vec<_> = contents.lines().collect();
+        let removed = format!("{}\n{}\n", lines[0], lines[2]);
+        let error = verify(removed.as_bytes(), None).unwrap_err();
+        check!(error.to_string() == "line 2: expected entry 2, found 3");
+
+        fs::remove_dir_all(dir).unwrap();
+    }
+
+    #[cfg(feature = "minisign")]
+    #[test]
+    fn test_signatures() {
+        let dir = std::env::temp_dir()
+            .join(format!("monitorbot-{}-audit-signed", std::process::id()));
+        let _ = fs::remove_dir_all(&dir);
+        fs::create_dir_all(&dir).unwrap();
+        let key_pair =
+            minisign::KeyPair::generate_unencrypted_keypair().unwrap();
+        fs::write(
+            dir.join("key"),
+            key_pair.sk.to_box(None).unwrap().into_string(),
+        )
+        .unwrap();
+        fs::write(
+            dir.join("key.pub"),
+            key_pair.pk.to_box().unwrap().into_string(),
+        )
+        .unwrap();
+
+        let config = AuditConfig {
+            signing_key: Some(dir.join("key")),
+            ..AuditConfig::default()
+        };
+        let mut log = AuditLog::open(&config, &dir).unwrap();
+        let url: Url = "https://example.com/".parse().unwrap();
+        log.append(&url, Outcome::Changed, None, None).unwrap();
+
+        let contents = fs::read_to_string(log.path()).unwrap();
+        let public_key = dir.join("key.pub");
+        check!(
+            verify(contents.as_bytes(), Some(&public_key)).unwrap()
+                == Verified {
+                    entries: 1,
+                    signed: 1,
+                    signatures_verified: true
+                }
+        );
+
+        // Rewriting the entry and its hash is detected by the signature.
+        let mut entry: Entry = serde_json::from_str(contents.trim()).unwrap();
+        entry.record.outcome = "unchanged".to_owned();
+        entry.hash = entry.record.hash().unwrap();
+        let forged = serde_json::to_string(&entry).unwrap();
+        check!(verify(forged.as_bytes(), None).is_ok());
+        check!(verify(forged.as_bytes(), Some(&public_key)).is_err());
+
+        fs::remove_dir_all(dir).unwrap();
+    }
+}
@@ -0,0 +1,119 @@
+//! Backing off from URLs that keep failing (`[backoff]`).
+//!
+//! With `--daemon`, a URL that has failed several times in a row is checked
+//! less often: the time between checks doubles with each failure after the
+//! first, up to `max`.
+//!
+//! If `disable_after` is set, a URL that has been failing for that long is
+//! disabled, with a notification. Disabled URLs are skipped until they’re
+//! checked successfully with `--force`.
+
+use chrono::{DateTime, Utc};
+use std::time::Duration;
+
+use crate::state::Reliability;
+
+/// Longest time between checks of a failing URL if `max` isn’t set.
+pub const DEFAULT_MAX: Duration = Duration::from_hours(24);
+
+/// How to back off from URLs that keep failing.
+#[derive(Clone, Debug, Default, serde::Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct Backoff {
+    /// Longest time between checks of a failing URL, e.g. `"6h"`; defaults
+    /// to [`DEFAULT_MAX`].
+    #[serde(default, with = "humantime_serde")]
+    pub max: Option<Duration>,
+
+    /// Disable URLs that have been failing for this long, e.g. `"7d"`.
+    #[serde(default, with = "humantime_serde")]
+    pub disable_after: Option<Duration>,
+}
+
+impl Backoff {
+    /// Get how long to wait after a failure before checking a URL again.
+    ///
+    /// `interval` is how often the URL is usually checked, and `failures` is
+    /// how many times in a row it has failed. The delay is never shorter than
+    /// `interval`.
+    #[must_use]
+    pub fn delay(&self, interval: Duration, failures: u64) -> Duration {
+        let max = self.max.unwrap_or(DEFAULT_MAX);
+        let doublings = failures.saturating_sub(1);
+        u32::try_from(doublings)
+            .ok()
+            .and_then(|doublings| 2_u32.checked_pow(doublings))
+            .and_then(|factor| interval.checked_mul(factor))
+            .map_or(max, |delay| delay.min(max))
+            .max(interval)
+    }
+
+    /// Get when a failing URL should next be checked, if it’s failing.
+    #[must_use]
+    pub fn next_check(
+        &self,
+        interval: Duration,
+        reliability: &Reliability,
+    ) -> Option<DateTime<Utc>> {
+        let delay = self.delay(interval, reliability.consecutive_errors);
+        reliability
+            .last_failure?
+            .checked_add_signed(chrono::Duration::from_std(delay).ok()?)
+    }
+
+    /// Check if a URL has been failing for long enough to be disabled.
+    #[must_use]
+    pub fn should_disable(
+        &self,
+        reliability: &Reliability,
+        now: DateTime<Utc>,
+    ) -> bool {
+        let (Some(disable_after), Some(failing_since), None) = (
+            self.disable_after,
+            reliability.failing_since,
+            reliability.disabled_at,
+        ) else {
+            return false;
+        };
+        now.signed_duration_since(failing_since)
+            .to_std()
+            .is_ok_and(|failing| failing >= disable_after)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use assert2::check;
+
+    #[test]
+    fn test_backoff() {
+        let minutes = |minutes: u64| Duration::from_mins(minutes);
+        let backoff = Backoff {
+            max: Some(minutes(60)),
+            disable_after: Some(minutes(24 * 60)),
+        };
+        check!(backoff.delay(minutes(15), 0) == minutes(15));
+        check!(backoff.delay(minutes(15), 1) == minutes(15));
+        check!(backoff.delay(minutes(15), 2) == minutes(30));
+        check!(backoff.delay(minutes(15), 3) == minutes(60));
+        check!(backoff.delay(minutes(15), 4) == minutes(60));
+        check!(backoff.delay(minutes(15), u64::MAX) == minutes(60));
+        check!(backoff.delay(minutes(120), 5) == minutes(120));
+
+        let time = |seconds| DateTime::from_timestamp(seconds, 0).unwrap();
+        let mut reliability = Reliability::default();
+        check!(backoff.next_check(minutes(15), &reliability) == None);
+        reliability.record(false, time(0));
+        reliability.record(false, time(900));
+        check!(
+            backoff.next_check(minutes(15), &reliability) == Some(time(2700))
+        );
+
+        check!(!backoff.should_disable(&reliability, time(3600)));
+        check!(backoff.should_disable(&reliability, time(86_400)));
+        check!(!Backoff::default().should_disable(&reliability, time(86_400)));
+        reliability.disabled_at = Some(time(86_400));
+        check!(!backoff.should_disable(&reliability, time(90_000)));
+    }
+}
@@ -0,0 +1,105 @@
+//! Removing cookie banners and consent dialogs from pages.
+//!
+//! Consent management platforms inject markup with random IDs and timestamps,
+//! which shows up as a change on every check. With `strip_boilerplate`, that
+//! markup is removed before the page is rendered.
+
+use scraper::{Html, Selector};
+use std::sync::LazyLock;
+
+/// Selectors for consent dialogs from common consent management platforms,
+/// and for overlays hidden from assistive technology.
+static SELECTORS: &[&str] = &[
+    // OneTrust
+    "#onetrust-consent-sdk",
+    "#onetrust-banner-sdk",
+    "#onetrust-pc-sdk",
+    ".optanon-alert-box-wrapper",
+    // Cookiebot
+    "#CybotCookiebotDialog",
+    "#CybotCookiebotDialogBodyUnderlay",
+    "#CookiebotWidget",
+    // Quantcast Choice
+    "#qc-cmp2-container",
+    ".qc-cmp2-container",
+    // TrustArc
+    "#truste-consent-track",
+    "#consent_blackbar",
+    ".truste_overlay",
+    ".truste_box_overlay",
+    // Usercentrics
+    "#usercentrics-root",
+    "#usercentrics-cmp-ui",
+    // Didomi
+    "#didomi-host",
+    // Osano
+    ".osano-cm-window",
+    // Complianz
+    "#cmplz-cookiebanner-container",
+    ".cmplz-cookiebanner",
+    // CookieYes and Cookie Law Info
+    ".cky-consent-container",
+    "#cookie-law-info-bar",
+    // iubenda
+    "#iubenda-cs-banner",
+    // Klaro
+    ".klaro",
+    // Sourcepoint
+    "[id^=\"sp_message_container\"]",
+    // Generic consent banners and dialogs.
+    "[id*=\"cookie-consent\" i]",
+    "[class*=\"cookie-consent\" i]",
+    "[id*=\"cookie-banner\" i]",
+    "[class*=\"cookie-banner\" i]",
+    // Overlays hidden from assistive technology.
+    "[aria-hidden=\"true\"][class*=\"overlay\" i]",
+    "[aria-hidden=\"true\"][class*=\"backdrop\" i]",
+    "[aria-hidden=\"true\"][id*=\"overlay\" i]",
+];
+
+/// [`SELECTORS`] combined into one selector.
+static SELECTOR: LazyLock<Selector> = LazyLock::new(|| {
+    Selector::parse(&SELECTORS.join(", ")).expect("selectors are valid")
+});
+
+/// Remove cookie banners, consent dialogs, and hidden overlays from an HTML
+/// document.
+#[must_use]
+pub fn strip(html: &str) -> String {
+    let mut document = Html::parse_document(html);
+    let ids: Vec<_> = document
+        .select(&SELECTOR)
+        .map(|element| element.id())
+        .collect();
+    for id in ids {
+        if let Some(mut node) = document.tree.get_mut(id) {
+            node.detach();
+        }
+    }
+    document.html()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use assert2::check;
+
+    #[test]
+    fn test_strip() {
+        let html = r#"<html><head></head><body>
+            <p>Content</p>
+            <div id="onetrust-consent-sdk"><div id="ot-123">Accept</div></div>
+            <div class="site-Cookie-Banner">We use cookies</div>
+            <div class="modal-overlay" aria-hidden="true"></div>
+            <span aria-hidden="true">★</span>
+            <div id="CybotCookiebotDialog" data-ts="1700000000">Allow</div>
+        </body></html>"#;
+        let stripped = strip(html);
+        check!(stripped.contains("<p>Content</p>"));
+        check!(stripped.contains("<span aria-hidden=\"true\">★</span>"));
+        check!(!stripped.contains("Accept"));
+        check!(!stripped.contains("cookies"));
+        check!(!stripped.contains("overlay"));
+        check!(!stripped.contains("Allow"));
+    }
+}
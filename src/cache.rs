@@ -0,0 +1,193 @@
+//! Honoring HTTP caching headers.
+//!
+//! When a server says how long a response stays fresh, with `Cache-Control:
+//! max-age` or `Expires`, checking the URL again before then would just get
+//! the same response. monitorbot skips fetching the URL until the latest
+//! stored response is stale, which saves requests when running as a daemon.
+//!
+//! Responses with `Cache-Control: no-store` or `no-cache`, or `Vary: *`, are
+//! never reused, though they’re still stored so they can be compared to the
+//! next response. Responses with other `Vary` headers are only reused if the
+//! request headers they name are the same. Responses without an explicit
+//! lifetime are always fetched again. Pass `--no-cache` to ignore caching
+//! headers entirely.
+
+use chrono::{DateTime, TimeDelta, Utc};
+use http::HeaderMap;
+use http::header::{AGE, CACHE_CONTROL, DATE, EXPIRES, VARY};
+use std::collections::BTreeMap;
+
+use crate::state::Revision;
+
+/// Get the values of the request headers named by a response’s `Vary`, to
+/// store with the response.
+///
+/// Headers that weren’t sent have empty values. Returns `None` if the
+/// response varies on everything (`Vary: *`).
+#[must_use]
+pub fn varied_headers(
+    response_headers: &HeaderMap,
+    request_headers: &HeaderMap,
+) -> Option<BTreeMap<String, String>> {
+    let mut varied = BTreeMap::new();
+    for name in list(response_headers, VARY) {
+        if name == "*" {
+            return None;
+        }
+        let value = request_headers
+            .get_all(name.as_str())
+            .iter()
+            .map(|value| String::from_utf8_lossy(value.as_bytes()))
+            .collect::<Vec<_>>()
+            .join(", ");
+        varied.insert(name, value);
+    }
+    Some(varied)
+}
+
+/// Find when a stored response stops being fresh, if it’s still fresh at
+/// `now` and can be reused for a request with `request_headers`.
+#[must_use]
+pub fn fresh_until(
+    revision: &Revision,
+    request_headers: &HeaderMap,
+    now: DateTime<Utc>,
+) -> Option<DateTime<Utc>> {
+    if revision.status != http::StatusCode::OK
+        || varied_headers(&revision.headers, request_headers).as_ref()
+            != Some(&revision.vary)
+    {
+        return None;
+    }
+
+    let headers = &revision.headers;
+    let mut max_age = None;
+    for directive in list(headers, CACHE_CONTROL) {
+        let (name, value) = directive
+            .split_once('=')
+            .map_or((directive.as_str(), None), |(name, value)| {
+                (name.trim(), Some(value.trim().trim_matches('"')))
+            });
+        match name {
+            "no-store" | "no-cache" => return None,
+            "max-age" => max_age = value.and_then(|value| value.parse().ok()),
+            _ => {}
+        }
+    }
+
+    let fetched_at = revision.last_fetched();
+    let date = http_date(headers, DATE).unwrap_or(fetched_at);
+    let lifetime = if let Some(seconds) = max_age {
+        TimeDelta::try_seconds(seconds)?
+    } else {
+        http_date(headers, EXPIRES)?.signed_duration_since(date)
+    };
+
+    // The response might have been in another cache for a while already.
+    let age = headers
+        .get(AGE)
+        .and_then(|age| age.to_str().ok()?.trim().parse().ok())
+        .and_then(TimeDelta::try_seconds)
+        .unwrap_or_default()
+        .max(fetched_at.signed_duration_since(date));
+
+    let expires = fetched_at
+        .checked_add_signed(lifetime)?
+        .checked_sub_signed(age)?;
+    (now < expires).then_some(expires)
+}
+
+/// Get the lowercased items in a comma-separated header.
+fn list(headers: &HeaderMap, name: http::HeaderName) -> Vec<String> {
+    headers
+        .get_all(name)
+        .iter()
+        .filter_map(|value| value.to_str().ok())
+        .flat_map(|value| value.split(','))
+        .map(|item| item.trim().to_ascii_lowercase())
+        .filter(|item| !item.is_empty())
+        .collect()
+}
+
+/// Parse a header containing an HTTP date.
+fn http_date(
+    headers: &HeaderMap,
+    name: http::HeaderName,
+) -> Option<DateTime<Utc>> {
+    let value = headers.get(name)?.to_str().ok()?;
+    DateTime::parse_from_rfc2822(value.trim())
+        .ok()
+        .map(|date| date.with_timezone(&Utc))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::response::Response;
+    use assert2::check;
+
+    /// Make a revision fetched at 1,000 seconds with `headers`.
+    fn revision(headers: &[(&'static str, &str)]) -> Revision {
+        let mut header_map = HeaderMap::new();
+        for (name, value) in headers {
+            header_map.append(*name, value.parse().unwrap());
+        }
+        let response = Response {
+            url: "https://example.com/".parse().unwrap(),
+            version: http::Version::HTTP_11,
+            status: http::StatusCode::OK,
+            headers: header_map,
+            body: bytes::Bytes::new(),
+            spooled: None,
+            certificate_sha256: None,
+            peer_address: None,
+        };
+        let mut revision = Revision::new(&response, b"");
+        revision.fetched_at = at(1_000);
+        revision
+    }
+
+    /// Get a time in seconds since the epoch.
+    fn at(seconds: i64) -> DateTime<Utc> {
+        DateTime::from_timestamp(seconds, 0).unwrap()
+    }
+
+    #[test]
+    fn test_fresh_until() {
+        let none = HeaderMap::new();
+        let cached = revision(&[("cache-control", "public, max-age=60")]);
+        check!(fresh_until(&cached, &none, at(1_059)) == Some(at(1_060)));
+        check!(fresh_until(&cached, &none, at(1_060)) == None);
+
+        let aged = revision(&[("cache-control", "max-age=60"), ("age", "50")]);
+        check!(fresh_until(&aged, &none, at(1_005)) == Some(at(1_010)));
+
+        // 1,000 seconds is 00:16:40.
+        let expires = revision(&[
+            ("date", "Thu, 01 Jan 1970 00:16:40 GMT"),
+            ("expires", "Thu, 01 Jan 1970 00:18:20 GMT"),
+        ]);
+        check!(fresh_until(&expires, &none, at(1_050)) == Some(at(1_100)));
+        check!(
+            fresh_until(&revision(&[("expires", "0")]), &none, at(0)) == None
+        );
+
+        let no_store = revision(&[("cache-control", "max-age=60, no-store")]);
+        check!(fresh_until(&no_store, &none, at(1_000)) == None);
+        check!(fresh_until(&revision(&[]), &none, at(1_000)) == None);
+
+        let mut vary = revision(&[
+            ("cache-control", "max-age=60"),
+            ("vary", "Accept-Language"),
+        ]);
+        let mut german = HeaderMap::new();
+        german.insert("accept-language", "de".parse().unwrap());
+        vary.vary = varied_headers(&vary.headers, &german).unwrap();
+        check!(fresh_until(&vary, &german, at(1_000)).is_some());
+        check!(fresh_until(&vary, &none, at(1_000)) == None);
+
+        let star = revision(&[("cache-control", "max-age=60"), ("vary", "*")]);
+        check!(varied_headers(&star.headers, &none) == None);
+        check!(fresh_until(&star, &none, at(1_000)) == None);
+    }
+}
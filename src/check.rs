@@ -0,0 +1,3467 @@
+//! Checking URLs for changes.
+
+use chrono::{DateTime, Local, Utc};
+use std::borrow::Cow;
+use std::collections::HashMap;
+use std::fs;
+use std::io;
+use std::net::IpAddr;
+use std::path::PathBuf;
+use std::time::Instant;
+use termcolor::{NoColor, WriteColor};
+use tracing::Instrument as _;
+use url::Url;
+
+use crate::audit::AuditLog;
+use crate::cache;
+use crate::checksums::{self, Hasher};
+use crate::config::{Config, DiffMode, OnNew, UrlConfig};
+use crate::csv_diff::{self, Table};
+use crate::dashboard::Dashboard;
+use crate::dns::{self, AddressFamily};
+use crate::dom_diff;
+use crate::encoding;
+use crate::error::MonitorError;
+use crate::expect;
+use crate::export::Export;
+use crate::extract::Filter;
+use crate::feed::Feed;
+use crate::git::GitHistory;
+use crate::graphql;
+use crate::hook;
+use crate::image;
+use crate::items;
+use crate::notify::{ChangeKind, Event, Notification, Notifier, Outbox};
+use crate::oauth::TokenCache;
+use crate::output::{
+    DiffView, format_size, print_diff, print_heading, print_pretty_diff,
+    print_side_by_side, terminal_width,
+};
+use crate::paginate::{self, Paginate};
+use crate::params::Params;
+use crate::progress::{Outcome, Progress};
+use crate::refresh;
+use crate::render::render;
+use crate::request::{self, ClientOptions};
+use crate::response::{DownloadError, Response, head_summary, watched_headers};
+use crate::robots::RobotsCache;
+use crate::soft_error;
+use crate::spool::Spool;
+use crate::state::{History, Removal, Revision, State, sha256_hex};
+use crate::stream;
+use crate::timing::{self, Recorder, Timing};
+use crate::trace::{Exchange, HttpTrace};
+use crate::translate;
+use crate::tunnel::Tunnels;
+use crate::values;
+use crate::vantage;
+use crate::wayback;
+
+/// Everything needed to check URLs.
+#[derive(Debug)]
+pub struct Checker<'a> {
+    /// Parameters passed on the command line.
+    pub params: &'a Params,
+
+    /// Configuration file.
+    pub config: &'a Config,
+
+    /// HTTP client.
+    pub client: reqwest::Client,
+
+    /// HTTP clients for URLs with options that need their own client, by
+    /// their options.
+    pub clients: HashMap<ClientOptions, reqwest::Client>,
+
+    /// State directory.
+    pub state: State,
+
+    /// Cache of robots.txt files, if `--respect-robots` was passed.
+    pub robots: Option<RobotsCache>,
+
+    /// Feed of changes, if `--feed-output` was passed.
+    pub feed: Option<Feed>,
+
+    /// Notifications about changes found so far in this run.
+    pub notifications: Vec<Notification>,
+
+    /// Whether any URL has failed its expectations in this run.
+    pub failed: bool,
+
+    /// Records timings for the URL currently being checked.
+    pub recorder: Recorder,
+
+    /// Timings for each URL checked so far in this run.
+    pub timings: Vec<Timing>,
+
+    /// Progress through the URLs being checked.
+    pub progress: Progress,
+
+    /// Where to write changes, if `--output-dir` was passed.
+    pub export: Option<Export>,
+
+    /// OAuth 2.0 access tokens for URLs with `oauth2`.
+    pub tokens: TokenCache,
+
+    /// Dashboard to record results in, if `--listen` was passed.
+    pub dashboard: Option<&'a Dashboard<'a>>,
+
+    /// Tamper-evident log to record checks in, if `[audit_log]` is set.
+    pub audit_log: Option<AuditLog>,
+
+    /// Git repository to commit rendered snapshots to, if `[git]` is set.
+    pub git: Option<GitHistory>,
+
+    /// The status of the response for the URL being checked, if any, for
+    /// notifications.
+    pub status: Option<http::StatusCode>,
+
+    /// Where to write requests and responses, if `--trace-http` was passed.
+    pub trace: Option<HttpTrace>,
+
+    /// SSH tunnels opened for URLs with `ssh_tunnel`.
+    pub tunnels: Tunnels,
+}
+
+/// Check if a stored response can stand in for a new one, either because
+/// it’s still fresh or because the server says the URL wasn’t modified.
+///
+/// Later pages of a paginated URL might change even if the first page
+/// doesn’t, so the first page’s response can’t stand in for them. GraphQL
+/// requests are POSTs, which aren’t cacheable or conditional. Checking for
+/// stale content and comparing vantage points need a response for every
+/// check.
+fn can_reuse(params: &Params, url_config: &UrlConfig) -> bool {
+    !params.force
+        && url_config.paginate.is_none()
+        && url_config.graphql.is_none()
+        && url_config.stale_after.is_none()
+        && url_config.vantages.is_empty()
+        && !url_config.is_stream()
+}
+
+/// Render a response for diffing.
+///
+/// If the URL has `checksums`, HTML pages are rendered as the list of
+/// artifacts they link to. If it’s only fetched with `HEAD`, the headers that
+/// show whether it changed are rendered. Redirects that weren’t followed are
+/// rendered as where they go.
+///
+/// # Errors
+///
+/// Returns an error if the response couldn’t be decoded or rendered.
+pub fn render_url(
+    url_config: &UrlConfig,
+    response: &Response,
+    filter: Filter<'_>,
+) -> anyhow::Result<String> {
+    if let Some(summary) = response.redirect_summary() {
+        return Ok(summary);
+    }
+    if url_config.is_head() {
+        return Ok(head_summary(&response.headers));
+    }
+    match &url_config.checksums {
+        Some(checksums) if response.is_html()? => Ok(checksums::manifest(
+            &checksums.find(&response.text()?, &response.url)?,
+        )),
+        _ => render(response, url_config.tables.unwrap_or_default(), filter),
+    }
+}
+
+/// Get the content of a response to compare between revisions.
+///
+/// This is what gets diffed: the page after `select`, `filters`, and the like
+/// are applied, so that changes to parts of the page that aren’t monitored
+/// don’t count. Binary bodies, and pages compared as items or CSV, are
+/// compared as they are. If the page can’t be rendered, its body is used, and
+/// the error is reported when it’s diffed.
+fn content<'a>(
+    params: &Params,
+    url_config: &UrlConfig,
+    response: &'a Response,
+) -> Cow<'a, [u8]> {
+    let summarized =
+        url_config.is_head() || response.redirect_target().is_some();
+    if !summarized
+        && (response.spooled.is_some()
+            || response.is_binary().unwrap_or(true)
+            || url_config.items_selector.is_some()
+            || csv_diff::delimiter(url_config.csv.as_ref(), response)
+                .map_or(true, |delimiter| delimiter.is_some()))
+    {
+        return Cow::Borrowed(&response.body);
+    }
+    let rendered = Filter::new(params, url_config).and_then(|filter| {
+        if params.diff_mode(url_config) == DiffMode::Html
+            && url_config.checksums.is_none()
+            && !summarized
+            && response.is_html()?
+        {
+            Ok(filter.apply(response.text()?)?.into_owned())
+        } else {
+            render_url(url_config, response, filter)
+        }
+    });
+    rendered.map_or(Cow::Borrowed(&response.body), |text| {
+        Cow::Owned(text.into_bytes())
+    })
+}
+
+/// Look up the addresses of the host a response came from.
+///
+/// Errors are logged, and result in no addresses. Names of URLs fetched
+/// through an SSH tunnel are resolved on the far side, so they aren’t looked
+/// up.
+async fn lookup(
+    params: &Params,
+    url_config: &UrlConfig,
+    response: &Response,
+) -> Vec<IpAddr> {
+    if url_config.ssh_tunnel.is_some() {
+        return Vec::new();
+    }
+    dns::lookup(
+        &response.url,
+        &params.resolve,
+        params.url_address_family(url_config),
+    )
+    .await
+    .unwrap_or_else(|error| {
+        tracing::warn!(
+            "Could not resolve host for {}: {error}",
+            url_config.label(),
+        );
+        Vec::new()
+    })
+}
+
+/// Check if a URL may be checked now according to its `schedule`.
+fn is_scheduled(url_config: &UrlConfig) -> bool {
+    let scheduled = url_config
+        .schedule
+        .as_ref()
+        .is_none_or(|schedule| schedule.contains(Local::now()));
+    if !scheduled {
+        tracing::info!(
+            "Skipping {}: outside of its schedule",
+            url_config.label(),
+        );
+    }
+    scheduled
+}
+
+/// Describe a change that isn’t just an edit to the content, e.g. “Page removed
+/// (404 Not Found)”.
+fn describe_change(
+    kind: ChangeKind,
+    old: &Response,
+    new: &Response,
+) -> Option<String> {
+    match kind {
+        ChangeKind::Removed => Some(format!("Page removed ({})", new.status)),
+        ChangeKind::Restored => Some(format!("Page restored ({})", new.status)),
+        ChangeKind::Redirect => Some(format!(
+            "Redirect changed: {} → {}",
+            old.destination(),
+            new.destination(),
+        )),
+        _ => None,
+    }
+}
+
+/// Log the encoding used to decode a text response, and warn if it’s not the
+/// one the response was labeled with.
+fn log_encoding(url_config: &UrlConfig, response: &Response) {
+    if response.spooled.is_some() || response.is_binary().unwrap_or(true) {
+        return;
+    }
+    let (Ok(declared), Ok(actual)) =
+        (response.encoding(), response.actual_encoding())
+    else {
+        return;
+    };
+    if declared == actual {
+        tracing::debug!(
+            "{} is encoded in {}",
+            url_config.label(),
+            actual.name()
+        );
+    } else {
+        tracing::warn!(
+            "{} is labeled as {} but is encoded in {}; decoding it as {}",
+            url_config.label(),
+            declared.name(),
+            actual.name(),
+            actual.name(),
+        );
+    }
+}
+
+/// Find when the content of a URL with `flap_window` was last seen, if it
+/// changed back to content seen within the window.
+fn flapped(
+    url_config: &UrlConfig,
+    old_history: Option<&History>,
+    revision: &Revision,
+) -> Option<DateTime<Utc>> {
+    old_history?.seen_within(
+        &revision.content_sha256,
+        Utc::now(),
+        url_config.flap_window?,
+    )
+}
+
+/// Check if a URL with `stale_after` has had the same content for too long.
+///
+/// Returns a failure message if it has. The message only depends on when the
+/// content last changed, so the failure is only notified once.
+fn stale(
+    url_config: &UrlConfig,
+    old_history: Option<&History>,
+    revision: &Revision,
+) -> Option<String> {
+    let stale_after = url_config.stale_after?;
+    let since = old_history?.unchanged_since(&revision.content_sha256)?;
+    let age = revision
+        .fetched_at
+        .signed_duration_since(since)
+        .to_std()
+        .ok()?;
+    (age >= stale_after).then(|| {
+        format!(
+            "Content hasn’t changed since {} (stale_after is {})",
+            since.with_timezone(&Local).to_rfc2822(),
+            humantime::format_duration(stale_after),
+        )
+    })
+}
+
+/// Build the request for a URL.
+///
+/// # Errors
+///
+/// Returns an error if the GraphQL request body couldn’t be built, or if the
+/// URL has `graphql` and is fetched with `HEAD`.
+fn build_request(
+    client: &reqwest::Client,
+    url_config: &UrlConfig,
+    headers: http::HeaderMap,
+) -> anyhow::Result<reqwest::RequestBuilder> {
+    let request = match (&url_config.graphql, url_config.is_head()) {
+        (Some(_), true) => anyhow::bail!("graphql can’t be used with HEAD"),
+        (Some(graphql), false) => client
+            .post(url_config.url.clone())
+            .json(&graphql.body(Utc::now())?),
+        (None, true) => client.head(url_config.url.clone()),
+        (None, false) => client.get(url_config.url.clone()),
+    };
+    Ok(request.headers(headers))
+}
+
+/// Translate the lines added in a change, if `translate` is set and the
+/// content isn’t in the target language.
+///
+/// Returns the translation with a heading. Translation errors are logged, so
+/// the change is still reported.
+async fn translate(
+    dry_run: bool,
+    url_config: &UrlConfig,
+    change: &Change,
+) -> Option<String> {
+    let translate = url_config.translate.as_ref()?;
+    // Without the old and new content, use the added lines of the diff.
+    let (added, language) = if let Some((old, new)) = &change.sides {
+        (translate::added_lines(old, new), translate::detect(new))
+    } else {
+        let added = change
+            .text
+            .lines()
+            .filter_map(|line| line.strip_prefix('+'))
+            .collect::<Vec<_>>()
+            .join("\n");
+        let language = translate::detect(&added);
+        (added, language)
+    };
+    let language = language?;
+    if added.trim().is_empty() || !translate.wants(language) {
+        return None;
+    }
+
+    if dry_run {
+        eprintln!("Dry run: would run {:?}", translate.command);
+        return None;
+    }
+    match translate.run(&url_config.url, language, &added).await {
+        Ok(translation) => Some(format!(
+            "Translation ({language} → {}):\n{}",
+            translate.to,
+            translation.trim_end(),
+        )),
+        Err(error) => {
+            tracing::warn!(
+                "Could not translate change to {}: {error:#}",
+                url_config.label(),
+            );
+            None
+        }
+    }
+}
+
+/// Save a snapshot of a changed page to the Wayback Machine.
+///
+/// Returns the URL of the snapshot. Failing to save the snapshot is only
+/// logged.
+async fn save_to_wayback(
+    client: &reqwest::Client,
+    dry_run: bool,
+    url_config: &UrlConfig,
+    url: &Url,
+) -> Option<Url> {
+    if dry_run {
+        eprintln!("Dry run: would save {url} to the Wayback Machine");
+        return None;
+    }
+    wayback::save(client, url)
+        .await
+        .inspect_err(|error| {
+            tracing::warn!(
+                "Could not archive {}: {error:#}",
+                url_config.label(),
+            );
+        })
+        .ok()
+}
+
+/// Download the body of a response, stopping if it’s larger than `max` bytes,
+/// and add the bytes downloaded to `recorder`.
+///
+/// Large bodies are written to disk as they’re downloaded; see [`Spool`]. If
+/// the request was written for `--trace-http`, the response is written next to
+/// it.
+///
+/// # Errors
+///
+/// Returns an error if the body couldn’t be downloaded or was too large.
+async fn download(
+    recorder: &Recorder,
+    max: Option<u64>,
+    spool: &Spool,
+    (response, exchange): (reqwest::Response, Option<Exchange>),
+) -> Result<Response, DownloadError> {
+    let result = Response::from_reqwest(response, max, spool).await;
+    recorder.add_bytes(match &result {
+        Ok(response) => response.body.len(),
+        Err(error) => error.received(),
+    });
+    if let (Ok(response), Some(exchange)) = (&result, exchange) {
+        exchange.response(response).unwrap_or_else(|error| {
+            tracing::warn!(
+                "Could not write response for --trace-http: {error}"
+            );
+        });
+    }
+    result
+}
+
+/// Send a request, writing it to the `--trace-http` directory if there is
+/// one.
+///
+/// # Errors
+///
+/// Returns an error if the request couldn’t be built or sent.
+async fn send(
+    trace: Option<&HttpTrace>,
+    client: &reqwest::Client,
+    request: reqwest::RequestBuilder,
+) -> reqwest::Result<(reqwest::Response, Option<Exchange>)> {
+    let request = request.build()?;
+    let exchange = trace.and_then(|trace| {
+        trace
+            .request(&request)
+            .inspect_err(|error| {
+                tracing::warn!(
+                    "Could not write request for --trace-http: {error}"
+                );
+            })
+            .ok()
+    });
+    Ok((client.execute(request).await?, exchange))
+}
+
+/// Download an artifact and hash it with the same algorithm as `checksum`.
+///
+/// Downloading stops if the artifact is larger than `max` bytes. The bytes
+/// downloaded are added to `recorder`.
+///
+/// # Errors
+///
+/// Returns an error if the artifact couldn’t be downloaded or was too large,
+/// or the algorithm isn’t supported.
+async fn hash_artifact(
+    client: &reqwest::Client,
+    recorder: &Recorder,
+    max: Option<u64>,
+    url: &Url,
+    checksum: &str,
+) -> anyhow::Result<String> {
+    let mut hasher = Hasher::for_checksum(checksum)?;
+    let mut response =
+        client.get(url.clone()).send().await?.error_for_status()?;
+    let mut received = 0_usize;
+    while let Some(chunk) = response.chunk().await? {
+        recorder.add_bytes(chunk.len());
+        received = received.saturating_add(chunk.len());
+        if let Some(max) = max
+            && u64::try_from(received).unwrap_or(u64::MAX) > max
+        {
+            return Err(DownloadError::TooLarge { max, received }.into());
+        }
+        hasher.update(&chunk);
+    }
+    Ok(hasher.finish())
+}
+
+/// Output about a change to a URL.
+#[derive(Clone, Debug)]
+struct Change {
+    /// The output as plain text.
+    text: String,
+
+    /// The old and new rendered content, if the output is a diff of them.
+    sides: Option<(String, String)>,
+}
+
+impl From<String> for Change {
+    fn from(text: String) -> Self {
+        Self { text, sides: None }
+    }
+}
+
+impl Checker<'_> {
+    /// Check a URL for changes and output them.
+    ///
+    /// Errors checking the URL are output rather than returned so that the
+    /// rest of the URLs can still be checked.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if an error checking the URL couldn’t be output.
+    pub async fn check(&mut self, url_config: &UrlConfig) -> io::Result<()> {
+        self.progress.start(url_config.label());
+        self.recorder.start(&url_config.url);
+        self.status = None;
+        let notified = self.notifications.len();
+        let span = tracing::info_span!(
+            "check",
+            url = %url_config.url,
+            status = tracing::field::Empty,
+            outcome = tracing::field::Empty,
+            duration_ms = tracing::field::Empty,
+        );
+        let result = self.check_url(url_config).instrument(span.clone()).await;
+        let mut timing = self.recorder.finish();
+        timing.notifications = self
+            .notifications
+            .get(notified..)
+            .unwrap_or_default()
+            .iter()
+            .map(|notification| notification.text.clone())
+            .collect();
+
+        let outcome = match result {
+            Ok(outcome) => outcome,
+            Err(error) => {
+                timing.error = Some(error.report());
+                self.progress.clear();
+                self.params.warn(format!(
+                    "Error checking {}: {error:#}\n",
+                    url_config.label(),
+                ))?;
+                self.failed = true;
+                Outcome::Error
+            }
+        };
+        if let Some(status) = self.status {
+            span.record("status", status.as_u16());
+        }
+        span.record("outcome", tracing::field::display(outcome));
+        span.record(
+            "duration_ms",
+            u64::try_from(timing.total.as_millis()).unwrap_or(u64::MAX),
+        );
+        let _entered = span.enter();
+        tracing::info!("{timing}");
+        if outcome != Outcome::Skipped {
+            let error = timing.error.as_ref().map(|error| &*error.message);
+            if let Err(error) = self.audit(url_config, outcome, error) {
+                self.params.warn(format!(
+                    "Error writing audit log for {}: {error:#}\n",
+                    url_config.label(),
+                ))?;
+                self.failed = true;
+            }
+            if let Err(error) = self.record_reliability(url_config, error) {
+                self.params.warn(format!(
+                    "Error recording reliability for {}: {error:#}\n",
+                    url_config.label(),
+                ))?;
+                self.failed = true;
+            }
+        }
+        if let Some(dashboard) = self.dashboard {
+            dashboard.record(
+                url_config,
+                outcome,
+                timing.error.as_ref().map(|error| error.message.clone()),
+            );
+        }
+        self.progress.finish(
+            url_config.label(),
+            outcome,
+            timing.total,
+            timing.bytes,
+        );
+        timing.outcome = Some(outcome);
+        self.timings.push(timing);
+        Ok(())
+    }
+
+    /// Record a check in the audit log, if there is one.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if the entry couldn’t be written.
+    fn audit(
+        &mut self,
+        url_config: &UrlConfig,
+        outcome: Outcome,
+        error: Option<&str>,
+    ) -> anyhow::Result<()> {
+        let Some(audit_log) = &mut self.audit_log else {
+            return Ok(());
+        };
+        if self.params.dry_run {
+            eprintln!(
+                "Dry run: would record {outcome} for {} in {}",
+                url_config.url,
+                audit_log.path().display(),
+            );
+            return Ok(());
+        }
+
+        let history = if outcome == Outcome::Error {
+            None
+        } else {
+            self.state.load_history(&url_config.url, true)?
+        };
+        audit_log.append(
+            &url_config.url,
+            outcome,
+            history.as_ref().and_then(History::latest),
+            error,
+        )
+    }
+
+    /// Count a check in the URL’s [`Reliability`](crate::state::Reliability),
+    /// and notify if it has now failed `alert_after_failures` times in a row,
+    /// or if it recovered after that.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if the history couldn’t be loaded or saved, or the
+    /// notification couldn’t be output.
+    fn record_reliability(
+        &mut self,
+        url_config: &UrlConfig,
+        error: Option<&str>,
+    ) -> anyhow::Result<()> {
+        let mut history = self
+            .state
+            .load_history(&url_config.url, self.params.dry_run)?
+            .unwrap_or_default();
+        let failures = history.reliability.consecutive_errors;
+        let was_disabled = history.reliability.disabled_at.is_some();
+        let now = Utc::now();
+        history.reliability.record(error.is_none(), now);
+        let disable = error.is_some()
+            && self.config.backoff.as_ref().is_some_and(|backoff| {
+                backoff.should_disable(&history.reliability, now)
+            });
+        if disable {
+            history.reliability.disabled_at = Some(now);
+        }
+        if !self.params.dry_run {
+            let url = history
+                .latest()
+                .map_or(&url_config.url, |latest| &latest.url);
+            self.state.save_history(url, &history)?;
+        }
+
+        if let (true, Some(error), Some(since)) =
+            (disable, error, history.reliability.failing_since)
+        {
+            let text = self.output(url_config, |out| {
+                writeln!(
+                    out,
+                    "Disabled after failing since {}: {error}\n\
+                    Check it with --force to enable it again",
+                    since.with_timezone(&Local).to_rfc2822(),
+                )
+            })?;
+            self.notify(url_config, Event::Failed, None, text);
+        } else if was_disabled && error.is_none() {
+            let text = self.output(url_config, |out| {
+                writeln!(out, "Enabled again after checking successfully")
+            })?;
+            self.notify(url_config, Event::Recovered, None, text);
+        }
+
+        let Some(limit) =
+            url_config.alert_after_failures.filter(|&limit| limit > 0)
+        else {
+            return Ok(());
+        };
+        match error {
+            Some(error) if history.reliability.consecutive_errors == limit => {
+                let text = self.output(url_config, |out| {
+                    writeln!(
+                        out,
+                        "Couldn’t be checked {limit} times in a row: {error}"
+                    )
+                })?;
+                self.notify(url_config, Event::Failed, None, text);
+            }
+            None if failures >= limit => {
+                let text = self.output(url_config, |out| {
+                    writeln!(
+                        out,
+                        "Checked successfully after {failures} failures"
+                    )
+                })?;
+                self.notify(url_config, Event::Recovered, None, text);
+            }
+            _ => {}
+        }
+        Ok(())
+    }
+
+    /// Check a URL for changes and output them without recording timings.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if the URL could not be fetched, or if its state could
+    /// not be saved. The error is classified by what went wrong; see
+    /// [`MonitorError`].
+    async fn check_url(
+        &mut self,
+        url_config: &UrlConfig,
+    ) -> Result<Outcome, MonitorError> {
+        let request_url = &url_config.url;
+
+        if !is_scheduled(url_config) {
+            return Ok(Outcome::Skipped);
+        }
+
+        let old_history = self.load_old_history(url_config);
+        let old_revision =
+            old_history.as_ref().and_then(History::latest).cloned();
+
+        if self.is_fresh(url_config, old_history.as_ref())
+            || self.is_held_back(url_config, old_history.as_ref())
+        {
+            return Ok(Outcome::Skipped);
+        }
+
+        let client = self
+            .client_for(url_config)
+            .await
+            .map_err(MonitorError::Config)?;
+        if !self.robots_allow(&client, request_url).await {
+            tracing::warn!(
+                "Skipping {}: disallowed by robots.txt",
+                url_config.label(),
+            );
+            return Ok(Outcome::Skipped);
+        }
+
+        let response = self.fetch(url_config, old_revision.as_ref()).await?;
+        if response.status == http::StatusCode::NOT_MODIFIED {
+            tracing::info!("{} not modified", url_config.label());
+            self.save_not_modified(old_history)
+                .map_err(MonitorError::Storage)?;
+            return Ok(Outcome::Unchanged);
+        }
+
+        let rejected = expect::reject(url_config, &response)
+            .map_err(MonitorError::Config)?;
+        if !rejected.is_empty() {
+            self.report_rejected(url_config, old_revision.is_some(), &rejected)
+                .map_err(|error| MonitorError::Render(error.into()))?;
+            return Ok(Outcome::Failed);
+        }
+
+        self.recorder
+            .set_response(response.version, response.peer_address);
+        log_encoding(url_config, &response);
+        let mut revision = Revision::new(
+            &response,
+            &content(self.params, url_config, &response),
+        );
+        revision.addresses = lookup(self.params, url_config, &response).await;
+        self.check_revision(
+            url_config,
+            &response,
+            old_history.as_ref(),
+            &mut revision,
+        )
+        .await?;
+
+        // Compare against the last revision that wasn’t a soft error, so that
+        // recovering from one doesn’t produce a giant diff.
+        let base_revision = old_history
+            .as_ref()
+            .and_then(History::latest_valid)
+            .cloned();
+        let removal = old_history.as_ref().and_then(History::removal);
+        let baseline =
+            old_history.as_ref().and_then(History::baseline).cloned();
+        let flapped = flapped(url_config, old_history.as_ref(), &revision);
+
+        self.save(url_config, old_history, &response, &revision)
+            .map_err(MonitorError::Storage)?;
+        self.commit_snapshot(
+            url_config,
+            old_revision.as_ref(),
+            (&revision, &response),
+        )
+        .map_err(MonitorError::Storage)?;
+
+        if let Some(old_revision) = &old_revision {
+            self.report_connection(url_config, old_revision, &revision)
+                .map_err(MonitorError::Render)?;
+        }
+
+        if removal.is_some() || revision.is_removed() && base_revision.is_some()
+        {
+            return self
+                .compare_removal(url_config, removal, (&revision, &response))
+                .map_err(MonitorError::Render);
+        }
+        if let Some(baseline) = baseline {
+            return self
+                .compare_baseline(
+                    url_config,
+                    baseline,
+                    old_revision,
+                    (&revision, &response),
+                    flapped,
+                )
+                .await
+                .map_err(MonitorError::Render);
+        }
+        self.compare(url_config, base_revision, (&revision, &response), flapped)
+            .await
+            .map_err(MonitorError::Render)
+    }
+
+    /// Check if robots.txt allows fetching a URL, if `--respect-robots` was
+    /// passed, and wait for its `Crawl-delay` if it does.
+    async fn robots_allow(
+        &mut self,
+        client: &reqwest::Client,
+        url: &Url,
+    ) -> bool {
+        let Some(robots) = &mut self.robots else {
+            return true;
+        };
+        if !robots.is_allowed(client, url).await {
+            return false;
+        }
+        robots.wait(url).await;
+        true
+    }
+
+    /// Check a new revision for failed expectations, stale content, and soft
+    /// errors, and report any changes in them.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if an artifact couldn’t be downloaded, the response
+    /// couldn’t be checked, or the failures couldn’t be output.
+    async fn check_revision(
+        &mut self,
+        url_config: &UrlConfig,
+        response: &Response,
+        old_history: Option<&History>,
+        revision: &mut Revision,
+    ) -> Result<(), MonitorError> {
+        let old_revision = old_history.and_then(History::latest);
+        if let Ok(headers) = request::headers(self.params, url_config) {
+            revision.vary = cache::varied_headers(&response.headers, &headers)
+                .unwrap_or_default();
+        }
+        revision.failures = expect::check(url_config, response)
+            .map_err(MonitorError::Config)?;
+        if let Some(failure) = stale(url_config, old_history, revision) {
+            revision.failures.push(failure);
+        }
+        if url_config.watch_encoding
+            && let Some(format) = encoding::double_encoded(response)
+                .map_err(MonitorError::Decode)?
+        {
+            revision.failures.push(format!(
+                "Body is still {format} compressed after decoding \
+                Content-Encoding: {}",
+                encoding::describe(&response.headers),
+            ));
+        }
+        let failures = self
+            .verify_checksums(url_config, response, old_revision, revision)
+            .await
+            .map_err(MonitorError::Network)?;
+        revision.failures.extend(failures);
+        self.report_failures(url_config, old_revision, revision)
+            .map_err(|error| MonitorError::Render(error.into()))?;
+        revision.values = values::extract(&url_config.extract, response)
+            .map_err(MonitorError::Config)?;
+        self.report_alerts(url_config, old_revision, revision)
+            .map_err(|error| MonitorError::Render(error.into()))?;
+        if url_config.detect_soft_errors
+            && !url_config.is_head()
+            && response.spooled.is_none()
+        {
+            revision.soft_error =
+                soft_error::detect(&self.config.soft_errors, response)
+                    .map_err(MonitorError::Decode)?;
+        }
+        self.report_soft_error(url_config, old_revision, revision)
+            .map_err(|error| MonitorError::Render(error.into()))?;
+        self.compare_vantages(url_config, response, old_revision, revision)
+            .await
+            .map_err(MonitorError::Render)
+    }
+
+    /// Fetch the URL through each of its `vantages`, and output and notify
+    /// about content that differs from `response`.
+    ///
+    /// Each vantage point is only reported when what it sees changes, and
+    /// once more when they all agree again. Vantage points that can’t be
+    /// fetched through are logged and skipped.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if a response couldn’t be rendered or output.
+    async fn compare_vantages(
+        &mut self,
+        url_config: &UrlConfig,
+        response: &Response,
+        old_revision: Option<&Revision>,
+        revision: &mut Revision,
+    ) -> anyhow::Result<()> {
+        if url_config.vantages.is_empty()
+            || response.spooled.is_some()
+            || response.is_binary()?
+        {
+            return Ok(());
+        }
+        let filter = Filter::new(self.params, url_config)?;
+        let text = render_url(url_config, response, filter)?;
+
+        let mut differences = Vec::new();
+        for name in &url_config.vantages {
+            let other = match self.fetch_through(url_config, name).await {
+                Ok(other) => render_url(url_config, &other, filter)?,
+                Err(error) => {
+                    tracing::warn!(
+                        "Could not fetch {} through {name}: {error:#}",
+                        url_config.label(),
+                    );
+                    continue;
+                }
+            };
+            if other == text {
+                continue;
+            }
+            let sha256 = sha256_hex(other.as_bytes());
+            let seen = old_revision
+                .and_then(|old| old.vantages.get(name))
+                .is_some_and(|old| *old == sha256);
+            revision.vantages.insert(name.clone(), sha256);
+            if !seen {
+                differences.push((name, other));
+            }
+        }
+
+        let context = self.params.diff_context();
+        if !differences.is_empty() {
+            let text = self.output(url_config, |out| {
+                for (name, other) in &differences {
+                    writeln!(out, "Content seen through {name} differs:")?;
+                    print_pretty_diff(out, &text, other, context);
+                }
+                Ok(())
+            })?;
+            self.notify(url_config, Event::Failed, None, text);
+        } else if revision.vantages.is_empty()
+            && old_revision.is_some_and(|old| !old.vantages.is_empty())
+        {
+            let text = self.output(url_config, |out| {
+                writeln!(out, "All vantage points see the same content")
+            })?;
+            self.notify(url_config, Event::Recovered, None, text);
+        }
+        Ok(())
+    }
+
+    /// Fetch a URL through a vantage point.
+    ///
+    /// Only the first page is fetched, and client-side redirects aren’t
+    /// followed.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if the vantage point isn’t defined, or the URL
+    /// couldn’t be fetched.
+    async fn fetch_through(
+        &mut self,
+        url_config: &UrlConfig,
+        name: &str,
+    ) -> anyhow::Result<Response> {
+        let vantage = vantage::find(&self.config.vantages, name)
+            .ok_or_else(|| anyhow::anyhow!("unknown vantage {name:?}"))?;
+        let mut options = ClientOptions::new(self.params, Some(url_config));
+        options.proxy = Some(vantage.proxy.clone());
+        let client = self.client_with(options)?;
+
+        let mut headers = request::headers(self.params, url_config)?;
+        if let Some(oauth2) = &url_config.oauth2 {
+            headers.insert(
+                http::header::AUTHORIZATION,
+                self.tokens.authorization(&client, oauth2).await?,
+            );
+        }
+        let request = build_request(&client, url_config, headers)?;
+        let response = send(self.trace.as_ref(), &client, request).await?;
+        Ok(download(
+            &self.recorder,
+            self.max_download_size(url_config),
+            &self.spool(),
+            response,
+        )
+        .await?)
+    }
+
+    /// Compare a new revision to a pinned baseline.
+    ///
+    /// Changes are only reported if the content differs from the last
+    /// revision, but they’re shown relative to the baseline.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if a response couldn’t be loaded or rendered, or the
+    /// change couldn’t be exported.
+    async fn compare_baseline(
+        &mut self,
+        url_config: &UrlConfig,
+        baseline: Revision,
+        old_revision: Option<Revision>,
+        new: (&Revision, &Response),
+        flapped: Option<DateTime<Utc>>,
+    ) -> anyhow::Result<Outcome> {
+        let (revision, _) = new;
+        let changed = old_revision
+            .as_ref()
+            .is_none_or(|old| old.content_sha256 != revision.content_sha256);
+        if !changed || revision.soft_error.is_some() {
+            return self.compare(url_config, old_revision, new, flapped).await;
+        }
+
+        if baseline.content_sha256 == revision.content_sha256 {
+            let text = self.output(url_config, |out| {
+                writeln!(out, "Matches the pinned baseline again")
+            })?;
+            self.notify(
+                url_config,
+                Event::Changed,
+                Some(ChangeKind::Restored),
+                text,
+            );
+            return Ok(if revision.failures.is_empty() {
+                Outcome::Changed
+            } else {
+                Outcome::Failed
+            });
+        }
+
+        self.compare(url_config, Some(baseline), new, flapped).await
+    }
+
+    /// Report a page being removed (404 Not Found or 410 Gone) or restored.
+    ///
+    /// The error page isn’t diffed, and nothing is reported while the page
+    /// stays removed. A restored page is compared to its content from before
+    /// it was removed, unless that was longer ago than its `restore_window`.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if the old response couldn’t be loaded, either
+    /// response couldn’t be rendered, or the change couldn’t be exported.
+    fn compare_removal(
+        &mut self,
+        url_config: &UrlConfig,
+        removal: Option<Removal>,
+        (revision, response): (&Revision, &Response),
+    ) -> anyhow::Result<Outcome> {
+        let outcome = if revision.failures.is_empty() {
+            Outcome::Changed
+        } else {
+            Outcome::Failed
+        };
+        let Some(removal) = removal else {
+            let text = self.output(url_config, |out| {
+                writeln!(out, "Page removed ({})", response.status)
+            })?;
+            self.export(url_config, &Change::from(text.clone()))?;
+            self.notify(
+                url_config,
+                Event::Changed,
+                Some(ChangeKind::Removed),
+                text,
+            );
+            return Ok(outcome);
+        };
+        if revision.is_removed() {
+            return Ok(if revision.failures.is_empty() {
+                Outcome::Unchanged
+            } else {
+                Outcome::Failed
+            });
+        }
+        // A login wall or the like doesn’t mean the page is back.
+        if revision.soft_error.is_some() {
+            return Ok(Outcome::Failed);
+        }
+
+        let within_window = url_config.restore_window.is_none_or(|window| {
+            (Utc::now().signed_duration_since(removal.removed_at))
+                .to_std()
+                .is_ok_and(|removed_for| removed_for <= window)
+        });
+        let before = removal.before.filter(|_| within_window);
+        let mut text = self.output(url_config, |out| {
+            writeln!(out, "Page restored ({})", response.status)
+        })?;
+        let change = match &before {
+            Some(before)
+                if before.content_sha256 == revision.content_sha256 =>
+            {
+                text.push_str(&self.output(url_config, |out| {
+                    writeln!(
+                        out,
+                        "Content is the same as before it was removed"
+                    )
+                })?);
+                None
+            }
+            _ => {
+                let old_response = before
+                    .map(|before| {
+                        self.state
+                            .load_response(&before, self.params.max_body_memory)
+                    })
+                    .transpose()?;
+                self.report(url_config, old_response.as_ref(), response)?
+            }
+        };
+        let change = if let Some(mut change) = change {
+            change.text.insert_str(0, &text);
+            change
+        } else {
+            Change::from(text)
+        };
+        self.export(url_config, &change)?;
+        self.notify(
+            url_config,
+            Event::Changed,
+            Some(ChangeKind::Restored),
+            change.text,
+        );
+        Ok(outcome)
+    }
+
+    /// Compare a new revision to the last one, and output and notify about
+    /// any changes.
+    ///
+    /// If the new content was `flapped` back to content seen recently, the
+    /// change is output but not notified.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if the old response couldn’t be loaded, either
+    /// response couldn’t be rendered, or the change couldn’t be exported.
+    async fn compare(
+        &mut self,
+        url_config: &UrlConfig,
+        base_revision: Option<Revision>,
+        (revision, response): (&Revision, &Response),
+        flapped: Option<DateTime<Utc>>,
+    ) -> anyhow::Result<Outcome> {
+        let failed =
+            !revision.failures.is_empty() || revision.soft_error.is_some();
+        if revision.soft_error.is_some() {
+            return Ok(Outcome::Failed);
+        }
+
+        let old_response =
+            if let Some(old_revision) = base_revision {
+                // Shortcut
+                if old_revision.content_sha256 == revision.content_sha256 {
+                    return Ok(if failed {
+                        Outcome::Failed
+                    } else {
+                        Outcome::Unchanged
+                    });
+                }
+
+                Some(self.state.load_response(
+                    &old_revision,
+                    self.params.max_body_memory,
+                )?)
+            } else {
+                None
+            };
+
+        let on_new = self.params.on_new(url_config);
+        let change = if old_response.is_none() && on_new == OnNew::Silent {
+            None
+        } else {
+            self.report(url_config, old_response.as_ref(), response)?
+        };
+
+        let outcome = if failed {
+            Outcome::Failed
+        } else if old_response.is_none() {
+            Outcome::New
+        } else if change.is_some() {
+            Outcome::Changed
+        } else {
+            Outcome::Unchanged
+        };
+
+        // Only notify about URLs that haven’t been checked before if asked.
+        if outcome == Outcome::New && on_new == OnNew::Notify {
+            self.announce(url_config, change.as_ref())?;
+        }
+        if let Some(mut change) = change
+            && let Some(old_response) = &old_response
+        {
+            let kind = ChangeKind::between(old_response, response);
+            if let Some(description) =
+                describe_change(kind, old_response, response)
+            {
+                let line = self
+                    .output(url_config, |out| writeln!(out, "{description}"))?;
+                change.text.insert_str(0, &line);
+            }
+            if url_config.wayback
+                && flapped.is_none()
+                && kind != ChangeKind::Removed
+            {
+                let snapshot = save_to_wayback(
+                    &self.client,
+                    self.params.dry_run,
+                    url_config,
+                    &response.url,
+                )
+                .await;
+                if let Some(snapshot) = snapshot {
+                    let line = self.output(url_config, |out| {
+                        writeln!(out, "Archived: {snapshot}")
+                    })?;
+                    change.text.push_str(&line);
+                }
+            }
+            let translation =
+                translate(self.params.dry_run, url_config, &change).await;
+            if let Some(translation) = translation {
+                let text = self
+                    .output(url_config, |out| writeln!(out, "{translation}"))?;
+                change.text.push_str(&text);
+            }
+            self.export(url_config, &change)?;
+            if let Some(seen_at) = flapped {
+                self.output(url_config, |out| {
+                    writeln!(
+                        out,
+                        "Not notifying: content matches a response from {}",
+                        seen_at.with_timezone(&Local).to_rfc2822(),
+                    )
+                })?;
+            } else {
+                self.notify(
+                    url_config,
+                    Event::Changed,
+                    Some(kind),
+                    change.text,
+                );
+            }
+        }
+
+        Ok(outcome)
+    }
+
+    /// Notify that a URL is now being monitored, with its initial content if
+    /// it was output.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if the notice couldn’t be output.
+    fn announce(
+        &mut self,
+        url_config: &UrlConfig,
+        change: Option<&Change>,
+    ) -> io::Result<()> {
+        let mut text = self.output(url_config, |out| {
+            writeln!(out, "Now monitoring; initial content captured")
+        })?;
+        if let Some(change) = change {
+            text.push_str(&change.text);
+        }
+        self.notify(url_config, Event::New, None, text);
+        Ok(())
+    }
+
+    /// Write a change to the `--output-dir` export, if there is one.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if the change couldn’t be written.
+    fn export(
+        &mut self,
+        url_config: &UrlConfig,
+        change: &Change,
+    ) -> io::Result<()> {
+        if let Some(export) = &mut self.export {
+            if self.params.dry_run {
+                eprintln!(
+                    "Dry run: would write change to {}",
+                    export.dir().display(),
+                );
+            } else {
+                export.add(url_config, &change.text, change.sides.as_ref())?;
+            }
+        }
+        Ok(())
+    }
+
+    /// Check if the URL was checked more recently than `--max-age` or its
+    /// `interval`, or if the latest response is still fresh according to its
+    /// caching headers, so it doesn’t need to be fetched.
+    fn is_fresh(
+        &self,
+        url_config: &UrlConfig,
+        old_history: Option<&History>,
+    ) -> bool {
+        if !self.params.no_cache
+            && can_reuse(self.params, url_config)
+            && let Some(latest) = old_history.and_then(History::latest)
+            && let Ok(headers) = request::headers(self.params, url_config)
+            && let Some(expires) =
+                cache::fresh_until(latest, &headers, Utc::now())
+        {
+            tracing::info!(
+                "Skipping {}: cached response is fresh until {expires}",
+                url_config.label(),
+            );
+            return true;
+        }
+
+        let (Some(max_age), Some(checked_at)) = (
+            self.params.max_age.or(url_config.interval),
+            old_history.and_then(History::last_checked),
+        ) else {
+            return false;
+        };
+
+        let fresh = Utc::now()
+            .signed_duration_since(checked_at)
+            .to_std()
+            .is_ok_and(|age| age < max_age);
+        if fresh {
+            tracing::info!(
+                "Skipping {}: checked at {checked_at}",
+                url_config.label(),
+            );
+        }
+        fresh
+    }
+
+    /// Load the history of a URL before checking it.
+    ///
+    /// Errors are logged, and the URL is treated as new.
+    fn load_old_history(&self, url_config: &UrlConfig) -> Option<History> {
+        self.state
+            .load_history(&url_config.url, self.params.dry_run)
+            .unwrap_or_else(|error| {
+                tracing::warn!(
+                    "Could not read old response for {}: {error:?}",
+                    url_config.label(),
+                );
+                None
+            })
+    }
+
+    /// Check if a URL was disabled for failing too long, or if it’s failing
+    /// and it’s too soon to check it again; see [`crate::backoff`].
+    ///
+    /// `--force` checks URLs regardless.
+    fn is_held_back(
+        &self,
+        url_config: &UrlConfig,
+        old_history: Option<&History>,
+    ) -> bool {
+        let Some(reliability) = old_history.map(|history| history.reliability)
+        else {
+            return false;
+        };
+        if self.params.force {
+            return false;
+        }
+        if let Some(disabled_at) = reliability.disabled_at {
+            tracing::info!(
+                "Skipping {}: disabled at {disabled_at} for failing too long",
+                url_config.label(),
+            );
+            return true;
+        }
+
+        let (Some(backoff), Some(period)) =
+            (&self.config.backoff, self.params.daemon)
+        else {
+            return false;
+        };
+        let interval = url_config.interval.map_or(period, |i| i.max(period));
+        match backoff.next_check(interval, &reliability) {
+            Some(next) if Utc::now() < next => {
+                tracing::info!(
+                    "Skipping {}: backing off after {} failures until {next}",
+                    url_config.label(),
+                    reliability.consecutive_errors,
+                );
+                true
+            }
+            _ => false,
+        }
+    }
+
+    /// Record that a URL was checked and wasn’t modified.
+    ///
+    /// If `--dry-run` was passed, this does nothing.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if the history could not be saved.
+    fn save_not_modified(
+        &self,
+        old_history: Option<History>,
+    ) -> anyhow::Result<()> {
+        let Some(mut history) = old_history else {
+            return Ok(());
+        };
+        let Some(url) = history.latest().map(|latest| latest.url.clone())
+        else {
+            return Ok(());
+        };
+        if !self.params.dry_run {
+            history.checked_at = Some(Utc::now());
+            self.state.save_history(&url, &history)?;
+        }
+        Ok(())
+    }
+
+    /// Output failed expectations, and notify if they’ve changed since the
+    /// last revision.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if the output couldn’t be written.
+    fn report_failures(
+        &mut self,
+        url_config: &UrlConfig,
+        old_revision: Option<&Revision>,
+        revision: &Revision,
+    ) -> io::Result<()> {
+        let old_failures =
+            old_revision.map_or(&[][..], |old| old.failures.as_slice());
+        if revision.failures.is_empty() {
+            if !old_failures.is_empty() {
+                let text = self.output(url_config, |out| {
+                    writeln!(out, "All expectations passed")
+                })?;
+                self.notify(url_config, Event::Recovered, None, text);
+            }
+            return Ok(());
+        }
+
+        self.failed = true;
+        let text = self.output(url_config, |out| {
+            for failure in &revision.failures {
+                writeln!(out, "{failure}")?;
+            }
+            Ok(())
+        })?;
+        if revision.failures != old_failures {
+            self.notify(url_config, Event::Failed, None, text);
+        }
+        Ok(())
+    }
+
+    /// Output and notify about `alert` conditions on extracted values that
+    /// started to hold, and thresholds that values crossed.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if the alerts couldn’t be output.
+    fn report_alerts(
+        &mut self,
+        url_config: &UrlConfig,
+        old_revision: Option<&Revision>,
+        revision: &Revision,
+    ) -> io::Result<()> {
+        let alerts = values::alerts(
+            url_config,
+            old_revision.map(|old| &old.values),
+            &revision.values,
+        );
+        if alerts.is_empty() {
+            return Ok(());
+        }
+        let text = self.output(url_config, |out| {
+            for alert in &alerts {
+                writeln!(out, "Alert: {alert}")?;
+            }
+            Ok(())
+        })?;
+        self.notify(url_config, Event::Changed, Some(ChangeKind::Value), text);
+        Ok(())
+    }
+
+    /// Download the artifacts linked from a page and verify their checksums,
+    /// if the URL has `checksums` with `verify` set.
+    ///
+    /// Returns a message for each artifact that failed verification. Nothing is
+    /// downloaded if the page hasn’t changed since a check that passed.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if the page couldn’t be decoded, or if the `checksums`
+    /// configuration is invalid.
+    async fn verify_checksums(
+        &mut self,
+        url_config: &UrlConfig,
+        response: &Response,
+        old_revision: Option<&Revision>,
+        revision: &Revision,
+    ) -> anyhow::Result<Vec<String>> {
+        let Some(checksums) = url_config
+            .checksums
+            .as_ref()
+            .filter(|checksums| checksums.verify)
+        else {
+            return Ok(Vec::new());
+        };
+        if old_revision.is_some_and(|old| {
+            old.content_sha256 == revision.content_sha256
+                && old.failures.is_empty()
+        }) || !response.is_html()?
+        {
+            return Ok(Vec::new());
+        }
+
+        let client = self.client_for(url_config).await?;
+        let mut failures = Vec::new();
+        for artifact in checksums.find(&response.text()?, &response.url)? {
+            let Some(checksum) = &artifact.checksum else {
+                continue;
+            };
+            if !self.robots_allow(&client, &artifact.url).await {
+                tracing::warn!(
+                    "Not verifying {}: disallowed by robots.txt",
+                    artifact.url,
+                );
+                continue;
+            }
+
+            tracing::debug!("Verifying checksum of {}", artifact.url);
+            let hashed = hash_artifact(
+                &client,
+                &self.recorder,
+                self.max_download_size(url_config),
+                &artifact.url,
+                checksum,
+            );
+            match hashed.await {
+                Ok(actual) if actual == *checksum => {}
+                Ok(actual) => failures.push(format!(
+                    "Checksum mismatch for {}: expected {checksum}, got {actual}",
+                    artifact.url,
+                )),
+                Err(error) => failures.push(format!(
+                    "Could not verify {}: {error:#}",
+                    artifact.url,
+                )),
+            }
+        }
+        Ok(failures)
+    }
+
+    /// Output the reasons a response was ignored.
+    ///
+    /// The response isn’t saved, so the previous snapshot (if any) stays the
+    /// baseline for the next check.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if the output couldn’t be written.
+    fn report_rejected(
+        &mut self,
+        url_config: &UrlConfig,
+        has_snapshot: bool,
+        reasons: &[String],
+    ) -> io::Result<()> {
+        self.failed = true;
+        self.output(url_config, |out| {
+            if has_snapshot {
+                writeln!(out, "Ignoring response; keeping previous snapshot:")?;
+            } else {
+                writeln!(out, "Ignoring response:")?;
+            }
+            for reason in reasons {
+                writeln!(out, "  {reason}")?;
+            }
+            Ok(())
+        })?;
+        Ok(())
+    }
+
+    /// Output soft errors, and notify if they’ve changed since the last
+    /// revision.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if the output couldn’t be written.
+    fn report_soft_error(
+        &mut self,
+        url_config: &UrlConfig,
+        old_revision: Option<&Revision>,
+        revision: &Revision,
+    ) -> io::Result<()> {
+        let old_soft_error = old_revision.and_then(|old| old.soft_error);
+        if let Some(soft_error) = revision.soft_error {
+            let text = self.output(url_config, |out| {
+                writeln!(out, "Looks like {soft_error}; not comparing content")
+            })?;
+            if old_soft_error != Some(soft_error) {
+                self.notify(url_config, Event::SoftError, None, text);
+            }
+        } else if let Some(old_soft_error) = old_soft_error {
+            let text = self.output(url_config, |out| {
+                writeln!(out, "No longer looks like {old_soft_error}")
+            })?;
+            self.notify(url_config, Event::Recovered, None, text);
+        }
+        Ok(())
+    }
+
+    /// Output and notify about changes in HTTP version, watched headers, or
+    /// resolved addresses, if `watch_http_version`, `watch_headers`, or
+    /// `watch_dns` is set, and about changes in the language of the content.
+    ///
+    /// A change in `Content-Language` is always reported, since it probably
+    /// means that the content changed because a different language was served.
+    /// Set `accept_language` to ask for the same language every time.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if the output couldn’t be written.
+    fn report_connection(
+        &mut self,
+        url_config: &UrlConfig,
+        old_revision: &Revision,
+        revision: &Revision,
+    ) -> anyhow::Result<()> {
+        if url_config.watch_http_version
+            && old_revision.version != revision.version
+        {
+            let text = self.output(url_config, |out| {
+                writeln!(
+                    out,
+                    "HTTP version changed: {:?} → {:?}",
+                    old_revision.version, revision.version,
+                )
+            })?;
+            self.notify(
+                url_config,
+                Event::Changed,
+                Some(ChangeKind::Connection),
+                text,
+            );
+        }
+
+        let language = |revision: &Revision| {
+            revision
+                .headers
+                .get(http::header::CONTENT_LANGUAGE)
+                .cloned()
+        };
+        if let (Some(old), Some(new)) =
+            (language(old_revision), language(revision))
+            && !old.as_bytes().eq_ignore_ascii_case(new.as_bytes())
+        {
+            let text = self.output(url_config, |out| {
+                writeln!(
+                    out,
+                    "Warning: language changed: {} → {}",
+                    String::from_utf8_lossy(old.as_bytes()),
+                    String::from_utf8_lossy(new.as_bytes()),
+                )
+            })?;
+            self.notify(
+                url_config,
+                Event::Changed,
+                Some(ChangeKind::Language),
+                text,
+            );
+        }
+
+        let old_headers =
+            watched_headers(&old_revision.headers, &url_config.watch_headers);
+        let new_headers =
+            watched_headers(&revision.headers, &url_config.watch_headers);
+        if old_headers != new_headers {
+            let text = self.output(url_config, |out| {
+                writeln!(out, "Headers changed:")?;
+                print_pretty_diff(
+                    out,
+                    old_headers.trim_end(),
+                    new_headers.trim_end(),
+                    None,
+                );
+                Ok(())
+            })?;
+            self.notify(
+                url_config,
+                Event::Changed,
+                Some(ChangeKind::Headers),
+                text,
+            );
+        }
+
+        self.report_certificate(url_config, old_revision, revision)?;
+        self.report_encoding(url_config, old_revision, revision)?;
+        self.report_address_family(url_config, old_revision, revision)?;
+
+        // Lookups fail sometimes; that’s not a change.
+        if old_revision.addresses.is_empty()
+            || revision.addresses.is_empty()
+            || old_revision.addresses == revision.addresses
+        {
+            return Ok(());
+        }
+
+        let join = |addresses: &[IpAddr]| {
+            addresses
+                .iter()
+                .map(ToString::to_string)
+                .collect::<Vec<_>>()
+                .join(", ")
+        };
+        let old = join(&old_revision.addresses);
+        let new = join(&revision.addresses);
+        if url_config.watch_dns {
+            let text = self.output(url_config, |out| {
+                writeln!(out, "Addresses changed: {old} → {new}")
+            })?;
+            self.notify(
+                url_config,
+                Event::Changed,
+                Some(ChangeKind::Connection),
+                text,
+            );
+        } else {
+            tracing::info!(
+                "Addresses for {} changed: {old} → {new}",
+                url_config.label(),
+            );
+        }
+        Ok(())
+    }
+
+    /// Output and notify about a change in the server’s TLS certificate if
+    /// `watch_certificate` is set, or just log it otherwise.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if the output couldn’t be written.
+    fn report_certificate(
+        &mut self,
+        url_config: &UrlConfig,
+        old_revision: &Revision,
+        revision: &Revision,
+    ) -> anyhow::Result<()> {
+        let (Some(old), Some(new)) = (
+            &old_revision.certificate_sha256,
+            &revision.certificate_sha256,
+        ) else {
+            return Ok(());
+        };
+        if old == new {
+            return Ok(());
+        }
+
+        if url_config.watch_certificate {
+            let text = self.output(url_config, |out| {
+                writeln!(out, "Certificate changed: SHA-256 {old} → {new}")
+            })?;
+            self.notify(
+                url_config,
+                Event::Changed,
+                Some(ChangeKind::Certificate),
+                text,
+            );
+        } else {
+            tracing::info!(
+                "Certificate for {} changed: SHA-256 {old} → {new}",
+                url_config.label(),
+            );
+        }
+        Ok(())
+    }
+
+    /// Output and notify about a change in the `Content-Encoding` of the
+    /// response if `watch_encoding` is set, or just log it otherwise.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if the output couldn’t be written.
+    fn report_encoding(
+        &mut self,
+        url_config: &UrlConfig,
+        old_revision: &Revision,
+        revision: &Revision,
+    ) -> anyhow::Result<()> {
+        let old = encoding::describe(&old_revision.headers);
+        let new = encoding::describe(&revision.headers);
+        if old == new {
+            return Ok(());
+        }
+
+        if url_config.watch_encoding {
+            let text = self.output(url_config, |out| {
+                writeln!(out, "Content-Encoding changed: {old} → {new}")
+            })?;
+            self.notify(
+                url_config,
+                Event::Changed,
+                Some(ChangeKind::Connection),
+                text,
+            );
+        } else {
+            tracing::info!(
+                "Content-Encoding for {} changed: {old} → {new}",
+                url_config.label(),
+            );
+        }
+        Ok(())
+    }
+
+    /// Finish the run by printing a summary, delivering notifications, and
+    /// writing the feed and the index of changes.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if the feed, the index, or the outbox could not be
+    /// written.
+    pub async fn finish(&mut self) -> anyhow::Result<()> {
+        self.progress.print_summary()?;
+        self.deliver_notifications().await?;
+
+        if let Some(export) = &self.export
+            && !self.params.dry_run
+        {
+            export.finish()?;
+        }
+
+        if let Some(git) = &self.git {
+            if self.params.dry_run {
+                if let Some(remote) = git.push_remote() {
+                    eprintln!(
+                        "Dry run: would push {} to {remote}",
+                        git.dir().display(),
+                    );
+                }
+            } else if let Err(error) = git.push() {
+                tracing::warn!("Could not push git history: {error:#}");
+                self.failed = true;
+            }
+        }
+
+        for report in &self.params.report {
+            if let Some(path) = &report.path {
+                timing::write_report(path, report.format, &self.timings)?;
+            } else {
+                timing::print_report(
+                    &mut io::stderr(),
+                    report.format,
+                    &self.timings,
+                )?;
+            }
+        }
+
+        if let (Some(feed), Some(output)) =
+            (&self.feed, &self.params.feed_output)
+        {
+            if self.params.dry_run {
+                eprintln!("Dry run: would write feed to {}", output.display());
+            } else {
+                feed.save(self.state.dir(), output)?;
+            }
+        }
+        Ok(())
+    }
+
+    /// Fetch a URL, including any following pages if it’s paginated.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if the URL could not be fetched.
+    async fn fetch(
+        &mut self,
+        url_config: &UrlConfig,
+        old_revision: Option<&Revision>,
+    ) -> Result<Response, MonitorError> {
+        if url_config.url.scheme() == "file" {
+            return self.read_file(url_config);
+        }
+
+        let client = self
+            .client_for(url_config)
+            .await
+            .map_err(MonitorError::Config)?;
+        let mut headers = request::headers(self.params, url_config)
+            .map_err(MonitorError::Config)?;
+        if can_reuse(self.params, url_config)
+            && let Some(old_revision) = old_revision
+        {
+            headers.extend(request::conditional_headers(old_revision));
+        }
+
+        if let Some(oauth2) = &url_config.oauth2 {
+            headers.insert(
+                http::header::AUTHORIZATION,
+                self.tokens
+                    .authorization(&client, oauth2)
+                    .await
+                    .map_err(MonitorError::Network)?,
+            );
+        }
+
+        if url_config.is_stream() {
+            return self.capture_stream(url_config, &client, headers).await;
+        }
+
+        let started = Instant::now();
+        let request = build_request(&client, url_config, headers.clone())
+            .map_err(MonitorError::Config)?;
+        let mut response = send(self.trace.as_ref(), &client, request).await?;
+        if let Some(oauth2) = &url_config.oauth2
+            && response.0.status() == http::StatusCode::UNAUTHORIZED
+        {
+            // The token might have been revoked, so try once with a new one.
+            self.tokens.invalidate(oauth2);
+            headers.insert(
+                http::header::AUTHORIZATION,
+                self.tokens
+                    .authorization(&client, oauth2)
+                    .await
+                    .map_err(MonitorError::Network)?,
+            );
+            let request = build_request(&client, url_config, headers.clone())
+                .map_err(MonitorError::Config)?;
+            response = send(self.trace.as_ref(), &client, request).await?;
+        }
+        if let Some(credentials) = &url_config.credentials
+            && response.0.status() == http::StatusCode::UNAUTHORIZED
+            && let Some(authorization) = credentials
+                .digest(
+                    url_config.http_method(),
+                    &url_config.url,
+                    response.0.headers(),
+                )
+                .map_err(MonitorError::Config)?
+        {
+            headers.insert(http::header::AUTHORIZATION, authorization);
+            let request = build_request(&client, url_config, headers.clone())
+                .map_err(MonitorError::Config)?;
+            response = send(self.trace.as_ref(), &client, request).await?;
+        }
+        self.recorder.add(|t| &mut t.ttfb, started.elapsed());
+
+        let started = Instant::now();
+        let mut response = download(
+            &self.recorder,
+            self.max_download_size(url_config),
+            &self.spool(),
+            response,
+        )
+        .await?;
+        if !url_config.is_head() && url_config.graphql.is_none() {
+            response = self
+                .follow_refreshes(url_config, &client, &headers, response)
+                .await?;
+        }
+        // Keep the fragment through redirects like browsers do. It isn’t sent,
+        // but it identifies the part of the page being monitored.
+        if response.url.fragment().is_none() {
+            response.url.set_fragment(url_config.url.fragment());
+        }
+        if url_config.graphql.is_some() {
+            graphql::check_errors(&response).map_err(MonitorError::Decode)?;
+        }
+        if let Some(paginate) = &url_config.paginate
+            && !url_config.is_head()
+            && response.spooled.is_none()
+            && response.status.is_success()
+            && (response.is_html()? || response.is_json()?)
+        {
+            self.fetch_pages(url_config, paginate, headers, &mut response)
+                .await?;
+        }
+        self.recorder.add(|t| &mut t.download, started.elapsed());
+        self.status = Some(response.status);
+        Ok(response)
+    }
+
+    /// Collect messages from a WebSocket or server-sent event stream.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if the stream couldn’t be read.
+    async fn capture_stream(
+        &mut self,
+        url_config: &UrlConfig,
+        client: &reqwest::Client,
+        headers: http::HeaderMap,
+    ) -> Result<Response, MonitorError> {
+        let started = Instant::now();
+        let response = stream::capture(
+            client,
+            &url_config.url,
+            headers,
+            &url_config.stream.clone().unwrap_or_default(),
+            self.max_download_size(url_config),
+            &self.spool(),
+        )
+        .await;
+        self.recorder.add(|t| &mut t.download, started.elapsed());
+        let response = response?;
+        self.recorder.add_bytes(response.body_len());
+        self.status = Some(response.status);
+        Ok(response)
+    }
+
+    /// Read a local file for a `file://` URL.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if the file couldn’t be read.
+    fn read_file(
+        &mut self,
+        url_config: &UrlConfig,
+    ) -> Result<Response, MonitorError> {
+        let started = Instant::now();
+        let response = Response::from_file(
+            &url_config.url,
+            self.max_download_size(url_config),
+            &self.spool(),
+        );
+        self.recorder.add(|t| &mut t.download, started.elapsed());
+        self.recorder.add_bytes(match &response {
+            Ok(response) => response.body_len(),
+            Err(error) => error.received(),
+        });
+        let response = response?;
+        self.status = Some(response.status);
+        Ok(response)
+    }
+
+    /// Follow client-side redirects from a response, if the URL has
+    /// `follow_refresh`.
+    ///
+    /// Returns the final response. If redirects aren’t being followed, this
+    /// just logs that the response redirects somewhere.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if a response couldn’t be decoded, or a redirect
+    /// couldn’t be fetched.
+    async fn follow_refreshes(
+        &mut self,
+        url_config: &UrlConfig,
+        client: &reqwest::Client,
+        headers: &http::HeaderMap,
+        mut response: Response,
+    ) -> Result<Response, MonitorError> {
+        // Validators for the original URL don’t apply to the target.
+        let mut headers = headers.clone();
+        headers.remove(http::header::IF_NONE_MATCH);
+        headers.remove(http::header::IF_MODIFIED_SINCE);
+
+        for hop in 0.. {
+            if !response.status.is_success() {
+                break;
+            }
+            let Some(target) =
+                refresh::target(&response).map_err(MonitorError::Decode)?
+            else {
+                break;
+            };
+            if !self.params.follow_refresh && !url_config.follow_refresh {
+                tracing::info!(
+                    "{} redirects to {target} on the client side; set \
+                    follow_refresh to follow it",
+                    url_config.label(),
+                );
+                break;
+            }
+            if hop >= self.params.max_refresh_hops {
+                tracing::warn!(
+                    "Not following refresh to {target}: more than {hop} \
+                    client-side redirects",
+                );
+                break;
+            }
+            if let Some(robots) = &mut self.robots {
+                if !robots.is_allowed(client, &target).await {
+                    tracing::warn!(
+                        "Not following refresh to {target}: disallowed by \
+                        robots.txt",
+                    );
+                    break;
+                }
+                robots.wait(&target).await;
+            }
+
+            tracing::debug!(
+                "Following refresh from {} to {target}",
+                response.url
+            );
+            let headers =
+                request::follow_up_headers(&headers, url_config, &target);
+            let next = send(
+                self.trace.as_ref(),
+                client,
+                client.get(target).headers(headers),
+            )
+            .await?;
+            response = download(
+                &self.recorder,
+                self.max_download_size(url_config),
+                &self.spool(),
+                next,
+            )
+            .await?;
+        }
+        Ok(response)
+    }
+
+    /// Report if a response was served over a different address family than
+    /// the last one, e.g. because happy eyeballs fell back to IPv4.
+    ///
+    /// This is only output if `watch_dns` is set; otherwise it’s logged.
+    fn report_address_family(
+        &mut self,
+        url_config: &UrlConfig,
+        old_revision: &Revision,
+        revision: &Revision,
+    ) -> anyhow::Result<()> {
+        let (Some(old), Some(new)) =
+            (old_revision.peer_address, revision.peer_address)
+        else {
+            return Ok(());
+        };
+        let (old_family, new_family) =
+            (AddressFamily::of(old), AddressFamily::of(new));
+        if old_family == new_family {
+            return Ok(());
+        }
+
+        if url_config.watch_dns {
+            let text = self.output(url_config, |out| {
+                writeln!(
+                    out,
+                    "Served over {old_family} → {new_family} ({old} → {new})",
+                )
+            })?;
+            self.notify(
+                url_config,
+                Event::Changed,
+                Some(ChangeKind::Connection),
+                text,
+            );
+        } else {
+            tracing::info!(
+                "{} was served over {old_family} → {new_family} \
+                ({old} → {new})",
+                url_config.label(),
+            );
+        }
+        Ok(())
+    }
+
+    /// Get the HTTP client to use for a URL.
+    ///
+    /// reqwest configures TLS, DNS resolution, redirects, and proxies for a
+    /// whole client, so URLs with `tls`, `address_family`, redirect options,
+    /// or `ssh_tunnel` get their own client. It’s shared with other URLs with
+    /// the same options.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if a certificate or key couldn’t be loaded, or an SSH
+    /// tunnel couldn’t be opened.
+    async fn client_for(
+        &mut self,
+        url_config: &UrlConfig,
+    ) -> anyhow::Result<reqwest::Client> {
+        let mut options = ClientOptions::new(self.params, Some(url_config));
+        if let Some(destination) = &url_config.ssh_tunnel {
+            options.proxy = Some(self.tunnels.proxy(destination).await?);
+        }
+        self.client_with(options)
+    }
+
+    /// Get an HTTP client with particular options.
+    ///
+    /// Clients are reused, so that connections can be too.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if a new client couldn’t be built.
+    fn client_with(
+        &mut self,
+        options: ClientOptions,
+    ) -> anyhow::Result<reqwest::Client> {
+        if options == ClientOptions::new(self.params, None) {
+            return Ok(self.client.clone());
+        }
+        if let Some(client) = self.clients.get(&options) {
+            return Ok(client.clone());
+        }
+        let client = request::client(self.params, &self.recorder, &options)?;
+        self.clients.insert(options, client.clone());
+        Ok(client)
+    }
+
+    /// Get where and when to write bodies to disk while downloading them.
+    fn spool(&self) -> Spool {
+        Spool {
+            dir: self.state.tmp_dir(),
+            max_memory: self.params.max_body_memory,
+        }
+    }
+
+    /// Get the maximum size of a download for a URL, if any.
+    fn max_download_size(&self, url_config: &UrlConfig) -> Option<u64> {
+        self.params
+            .max_download_size
+            .or(url_config.max_download_size)
+    }
+
+    /// Fetch the following pages of a paginated URL and combine their bodies
+    /// with the response’s.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if a page could not be fetched.
+    async fn fetch_pages(
+        &mut self,
+        url_config: &UrlConfig,
+        paginate: &Paginate,
+        headers: http::HeaderMap,
+        response: &mut Response,
+    ) -> Result<(), MonitorError> {
+        let selector = paginate.selector().map_err(MonitorError::Config)?;
+        let client = self
+            .client_for(url_config)
+            .await
+            .map_err(MonitorError::Config)?;
+        let mut pages = paginate::Pages::new(paginate, &selector, response)
+            .map_err(MonitorError::Decode)?;
+
+        for _ in 1..paginate.max_pages {
+            let Some(next_url) = pages.next_url().cloned() else {
+                break;
+            };
+
+            if let Some(robots) = &mut self.robots {
+                if !robots.is_allowed(&client, &next_url).await {
+                    tracing::warn!(
+                        "Not following pagination to {next_url}: disallowed \
+                        by robots.txt",
+                    );
+                    break;
+                }
+                robots.wait(&next_url).await;
+            }
+
+            tracing::debug!("Following pagination to {next_url}");
+            let headers =
+                request::follow_up_headers(&headers, url_config, &next_url);
+            let next = send(
+                self.trace.as_ref(),
+                &client,
+                client.get(next_url.clone()).headers(headers),
+            )
+            .await?;
+            let next = download(
+                &self.recorder,
+                self.max_download_size(url_config),
+                &self.spool(),
+                next,
+            )
+            .await?;
+            if !next.status.is_success() {
+                tracing::warn!(
+                    "Not following pagination to {next_url}: got status {}",
+                    next.status,
+                );
+                break;
+            }
+            pages.add(&next).map_err(MonitorError::Decode)?;
+        }
+
+        response.body = pages.finish().map_err(MonitorError::Decode)?;
+        Ok(())
+    }
+
+    /// Save a new response to the state directory.
+    ///
+    /// If `--dry-run` was passed, this just prints what it would have done.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if the response could not be saved.
+    fn save(
+        &self,
+        url_config: &UrlConfig,
+        old_history: Option<History>,
+        response: &Response,
+        revision: &Revision,
+    ) -> anyhow::Result<()> {
+        let request_url = &url_config.url;
+
+        if self.params.dry_run {
+            eprintln!(
+                "Dry run: would save state to {}",
+                self.state.history_path(&response.url).display(),
+            );
+            if response.url != *request_url {
+                eprintln!(
+                    "Dry run: would record that {request_url} redirects to {}",
+                    response.url,
+                );
+            }
+            return Ok(());
+        }
+
+        let mut history = if response.url == *request_url {
+            old_history.unwrap_or_default()
+        } else {
+            self.state
+                .load_final_history(&response.url, false)
+                .ok()
+                .flatten()
+                .unwrap_or_default()
+        };
+        if history.push(revision.clone()) {
+            self.state.save_response_body(response)?;
+        }
+        if revision.status.is_success() || revision.status.is_redirection() {
+            history.checked_at = Some(revision.fetched_at);
+        }
+        self.state.save_history(&response.url, &history)?;
+
+        self.state.record_redirect(request_url, &response.url)
+    }
+
+    /// Commit the rendered content of a response to the git repository, if
+    /// `[git]` is set and the content changed since `old_revision`.
+    ///
+    /// Binary content is recorded as its type, size, and hash.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if the response couldn’t be rendered, or the snapshot
+    /// couldn’t be committed.
+    fn commit_snapshot(
+        &mut self,
+        url_config: &UrlConfig,
+        old_revision: Option<&Revision>,
+        (revision, response): (&Revision, &Response),
+    ) -> anyhow::Result<()> {
+        let Some(git) = &mut self.git else {
+            return Ok(());
+        };
+        if old_revision
+            .is_some_and(|old| old.content_sha256 == revision.content_sha256)
+        {
+            return Ok(());
+        }
+        if self.params.dry_run {
+            eprintln!(
+                "Dry run: would commit {} to {}",
+                GitHistory::file_name(&url_config.url),
+                git.dir().display(),
+            );
+            git.would_commit();
+            return Ok(());
+        }
+
+        let rendered = if response.spooled.is_some() || response.is_binary()? {
+            format!(
+                "{} ({}, SHA-256 {})\n",
+                response.media_type()?.unwrap_or_default(),
+                format_size(response.body_len()),
+                revision.body_sha256,
+            )
+        } else {
+            render_url(
+                url_config,
+                response,
+                Filter::new(self.params, url_config)?,
+            )?
+        };
+        let label = url_config.label().to_owned();
+        self.git.as_mut().map_or(Ok(()), |git| {
+            git.record(&label, &url_config.url, &rendered, revision.fetched_at)
+        })
+    }
+
+    /// Output the changes between an old response and a new one.
+    ///
+    /// Returns the output as plain text, or `None` if there was no output.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if either response could not be decoded or rendered.
+    fn report(
+        &self,
+        url_config: &UrlConfig,
+        old_response: Option<&Response>,
+        response: &Response,
+    ) -> anyhow::Result<Option<Change>> {
+        let params = self.params;
+
+        let spooled = response.spooled.is_some()
+            || old_response.is_some_and(|old| old.spooled.is_some());
+        // These are rendered as a summary rather than from the body.
+        let summarized = url_config.is_head()
+            || response.redirect_target().is_some()
+            || old_response.is_some_and(|old| old.redirect_target().is_some());
+        if (spooled || response.is_binary()?) && !summarized {
+            return Ok(self
+                .report_binary(url_config, old_response, response)?
+                .map(Change::from));
+        }
+
+        if !params.no_diff
+            && !summarized
+            && let Some(source) = &url_config.items_selector
+            && let Some(old_response) = old_response
+            && response.is_html()?
+        {
+            return self.report_items(
+                url_config,
+                old_response,
+                response,
+                source,
+            );
+        }
+
+        if !params.no_diff
+            && !summarized
+            && let Some(old_response) = old_response
+            && let Some(delimiter) =
+                csv_diff::delimiter(url_config.csv.as_ref(), response)?
+            && csv_diff::delimiter(url_config.csv.as_ref(), old_response)?
+                .is_some()
+        {
+            return self.report_csv(
+                url_config,
+                old_response,
+                response,
+                delimiter,
+            );
+        }
+
+        let filter = Filter::new(params, url_config)?;
+        if !params.no_diff
+            && params.diff_mode(url_config) == DiffMode::Html
+            && url_config.checksums.is_none()
+            && !summarized
+            && response.is_html()?
+        {
+            return self.report_dom(url_config, old_response, response, filter);
+        }
+
+        let (old_md, new_md) = self.recorder.time(
+            |t| &mut t.render,
+            || -> anyhow::Result<_> {
+                let old_md = old_response
+                    .map(|old| render_url(url_config, old, filter))
+                    .transpose()?
+                    .unwrap_or_default();
+                Ok((old_md, render_url(url_config, response, filter)?))
+            },
+        )?;
+        if params.no_diff {
+            Ok(Some(
+                self.output(url_config, |out| writeln!(out, "{new_md}"))?
+                    .into(),
+            ))
+        } else if new_md != old_md {
+            let unified = |out: &mut dyn WriteColor| {
+                print_diff(
+                    out,
+                    &old_md,
+                    &new_md,
+                    params.diff_context(),
+                    params.diff_granularity,
+                )
+            };
+            let text = self.recorder.time(
+                |t| &mut t.diff,
+                || match params.diff_view {
+                    DiffView::Unified => self.output(url_config, unified),
+                    DiffView::SideBySide => self.output_split(
+                        url_config,
+                        |out| {
+                            let granularity = params.diff_granularity;
+                            print_side_by_side(
+                                out,
+                                &granularity.prepare(&old_md),
+                                &granularity.prepare(&new_md),
+                                params.diff_context(),
+                                terminal_width(),
+                            )
+                        },
+                        unified,
+                    ),
+                },
+            )?;
+            Ok(Some(Change { text, sides: Some((old_md, new_md)) }))
+        } else {
+            Ok(None)
+        }
+    }
+
+    /// Output the structural changes between two versions of an HTML page.
+    ///
+    /// Returns `None` if the DOM didn’t change.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if either response couldn’t be decoded or filtered.
+    fn report_dom(
+        &self,
+        url_config: &UrlConfig,
+        old_response: Option<&Response>,
+        response: &Response,
+        filter: Filter<'_>,
+    ) -> anyhow::Result<Option<Change>> {
+        let old_text = old_response
+            .map(Response::text)
+            .transpose()?
+            .unwrap_or_default();
+        let new_text = response.text()?;
+        let (old_text, new_text) = (
+            filter.apply_text(filter.apply(old_text)?.into_owned()),
+            filter.apply_text(filter.apply(new_text)?.into_owned()),
+        );
+        let changes = self
+            .recorder
+            .time(|t| &mut t.diff, || dom_diff::diff(&old_text, &new_text));
+        if changes.is_empty() {
+            return Ok(None);
+        }
+        let text = self.recorder.time(
+            |t| &mut t.diff,
+            || {
+                self.output(url_config, |out| {
+                    dom_diff::print_changes(out, &changes);
+                    Ok(())
+                })
+            },
+        )?;
+        Ok(Some(text.into()))
+    }
+
+    /// Output the changes between two versions of a CSV or TSV file, row by
+    /// row.
+    ///
+    /// Returns `None` if no rows changed, e.g. if they were just reordered.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if either response couldn’t be decoded or parsed, or
+    /// doesn’t have the `key` column.
+    fn report_csv(
+        &self,
+        url_config: &UrlConfig,
+        old_response: &Response,
+        response: &Response,
+        delimiter: u8,
+    ) -> anyhow::Result<Option<Change>> {
+        let key = url_config.csv.as_ref().and_then(|csv| csv.key.as_deref());
+        let changes = self.recorder.time(
+            |t| &mut t.diff,
+            || -> anyhow::Result<_> {
+                csv_diff::diff(
+                    &Table::parse(&old_response.text()?, delimiter)?,
+                    &Table::parse(&response.text()?, delimiter)?,
+                    key,
+                )
+            },
+        )?;
+        if changes.is_empty() {
+            return Ok(None);
+        }
+        let text = self.output(url_config, |out| {
+            csv_diff::print_changes(out, &changes);
+            Ok(())
+        })?;
+        Ok(Some(text.into()))
+    }
+
+    /// Output the items on a list-like page that weren’t on it before, for
+    /// URLs with `items_selector`.
+    ///
+    /// Returns `None` if there are no new items.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if either response couldn’t be decoded, or the
+    /// selector is invalid.
+    fn report_items(
+        &self,
+        url_config: &UrlConfig,
+        old_response: &Response,
+        response: &Response,
+        source: &str,
+    ) -> anyhow::Result<Option<Change>> {
+        let selector = items::selector(source)?;
+        let (old, new) = self.recorder.time(
+            |t| &mut t.diff,
+            || -> anyhow::Result<_> {
+                Ok((
+                    items::extract(
+                        &old_response.text()?,
+                        &old_response.url,
+                        &selector,
+                    ),
+                    items::extract(&response.text()?, &response.url, &selector),
+                ))
+            },
+        )?;
+        let new_items = items::new_items(&old, &new);
+        if new_items.is_empty() {
+            return Ok(None);
+        }
+        let text = self.output(url_config, |out| {
+            items::print_items(out, &new_items);
+            Ok(())
+        })?;
+        Ok(Some(text.into()))
+    }
+
+    /// Output a change in binary content.
+    ///
+    /// Binary content isn’t rendered; this just reports the size and hash, and
+    /// for images, the metadata that changed. If `--download-dir` was passed,
+    /// this saves the new content there. This is also used for bodies too
+    /// large to keep in memory.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if the content could not be saved.
+    fn report_binary(
+        &self,
+        url_config: &UrlConfig,
+        old_response: Option<&Response>,
+        response: &Response,
+    ) -> anyhow::Result<Option<String>> {
+        let media_type = response.media_type()?.unwrap_or_default();
+        let new_sha256 = response.body_sha256();
+        let what = if response.is_binary()? {
+            "Binary content"
+        } else {
+            "Content"
+        };
+        let mut message = if let Some(old_response) = old_response {
+            format!(
+                "{what} changed ({media_type}, {} → {}, SHA-256 {} → \
+                {new_sha256})",
+                format_size(old_response.body_len()),
+                format_size(response.body_len()),
+                old_response.body_sha256(),
+            )
+        } else {
+            format!(
+                "{what} ({media_type}, {}, SHA-256 {new_sha256})",
+                format_size(response.body_len()),
+            )
+        };
+
+        // Spooled bodies are too large to read metadata from in memory.
+        if media_type.starts_with("image/") && response.spooled.is_none() {
+            let old_body = old_response
+                .filter(|old| old.spooled.is_none())
+                .map(|old| &old.body[..]);
+            if let Some(report) = image::report(old_body, &response.body) {
+                message.push('\n');
+                message.push_str(report.trim_end());
+            }
+        }
+
+        if let Some(download_dir) = &self.params.download_dir {
+            let path = download_dir.join(download_file_name(response));
+            if self.params.dry_run {
+                eprintln!("Dry run: would save content to {}", path.display());
+            } else {
+                fs::create_dir_all(download_dir)?;
+                if response.spooled.is_some() {
+                    // The body was moved into the state when it was saved.
+                    fs::copy(self.state.body_path(&new_sha256), &path)?;
+                } else {
+                    fs::write(&path, &response.body)?;
+                }
+                message.push_str("\nSaved to ");
+                message.push_str(&path.display().to_string());
+            }
+        }
+
+        Ok(Some(
+            self.output(url_config, |out| writeln!(out, "{message}"))?,
+        ))
+    }
+
+    /// Print output about a URL to the terminal under a heading.
+    ///
+    /// Returns the output (without the heading) as plain text.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if `write` returns an error.
+    fn output<F>(&self, url_config: &UrlConfig, write: F) -> io::Result<String>
+    where
+        F: Fn(&mut dyn WriteColor) -> io::Result<()>,
+    {
+        self.output_split(url_config, &write, &write)
+    }
+
+    /// Output information about a URL to the terminal with `write_terminal`,
+    /// and return the plain text written by `write_plain` for notifications.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if the output couldn’t be written.
+    fn output_split<F, G>(
+        &self,
+        url_config: &UrlConfig,
+        write_terminal: F,
+        write_plain: G,
+    ) -> io::Result<String>
+    where
+        F: FnOnce(&mut dyn WriteColor) -> io::Result<()>,
+        G: FnOnce(&mut dyn WriteColor) -> io::Result<()>,
+    {
+        self.progress.clear();
+        let mut out = self.params.out_stream();
+        print_heading(&mut out, url_config.label());
+        write_terminal(&mut out)?;
+
+        let mut plain = NoColor::new(Vec::new());
+        write_plain(&mut plain)?;
+        Ok(String::from_utf8_lossy(&plain.into_inner()).into_owned())
+    }
+
+    /// Record a change to be sent to notifiers at the end of the run, add it
+    /// to the feed if there is one, and run the `--exec` hook.
+    fn notify(
+        &mut self,
+        url_config: &UrlConfig,
+        event: Event,
+        kind: Option<ChangeKind>,
+        text: String,
+    ) {
+        let detected_at = Utc::now();
+        let notification = Notification {
+            label: url_config.label().to_owned(),
+            url: url_config.url.clone(),
+            diff_path: self.save_diff(url_config, &text, detected_at),
+            text,
+            detected_at,
+            event,
+            kind,
+            tags: url_config.tags.clone(),
+            severity: url_config.severity.unwrap_or_default(),
+            status: self.status.map(|status| status.as_u16()),
+        };
+
+        if let Some(feed) = &mut self.feed {
+            feed.add(&notification);
+        }
+
+        if let Some(command) = &self.params.exec {
+            if self.params.dry_run {
+                eprintln!("Dry run: would run {command:?}");
+            } else if let Err(error) = hook::run(command, &notification) {
+                tracing::warn!(
+                    "Error running hook for {}: {error:#}",
+                    notification.label,
+                );
+            }
+        }
+
+        self.notifications.push(notification);
+    }
+
+    /// Save the full text of a notification if it’s too long for any of the
+    /// notifiers, so that the shortened notifications can point to it.
+    ///
+    /// Returns the path to the saved file, if it was saved.
+    fn save_diff(
+        &self,
+        url_config: &UrlConfig,
+        text: &str,
+        detected_at: DateTime<Utc>,
+    ) -> Option<PathBuf> {
+        let too_long =
+            self.config.notifiers.iter().any(|notifier| {
+                !notifier.limits(&self.config.notify).fits(text)
+            });
+        if !too_long {
+            return None;
+        }
+
+        let path = self.state.diff_path(&url_config.url, detected_at);
+        if self.params.dry_run {
+            eprintln!("Dry run: would write {}", path.display());
+        } else if let Err(error) = self.state.save_diff(&path, text) {
+            tracing::warn!("Error saving {}: {error}", path.display());
+            return None;
+        }
+        Some(path)
+    }
+
+    /// Deliver notifications to every configured notifier.
+    ///
+    /// Notifications that can’t be sent yet because of quiet hours or batching
+    /// are saved in the outbox for a later run. Errors sending notifications
+    /// are logged rather than returned so that one failing notifier doesn’t
+    /// stop the others; the notifications stay in the outbox to be retried.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if the outbox could not be read or written.
+    async fn deliver_notifications(&mut self) -> anyhow::Result<()> {
+        let notifications = std::mem::take(&mut self.notifications);
+
+        let for_notifier = |notifier: &Notifier| -> Vec<Notification> {
+            notifications
+                .iter()
+                .filter(|notification| notifier.accepts(notification))
+                .cloned()
+                .collect()
+        };
+
+        if self.params.dry_run {
+            for notifier in &self.config.notifiers {
+                let count = for_notifier(notifier).len();
+                if count > 0 {
+                    eprintln!(
+                        "Dry run: would send {count} notification(s) to {}",
+                        notifier.name(),
+                    );
+                }
+            }
+            return Ok(());
+        }
+
+        if self.config.notifiers.is_empty() {
+            return Ok(());
+        }
+
+        let mut outbox = Outbox::load(self.state.dir())?;
+        for notifier in &self.config.notifiers {
+            if let Err(error) = outbox
+                .deliver(
+                    &self.client,
+                    notifier,
+                    &self.config.notify,
+                    for_notifier(notifier),
+                )
+                .await
+            {
+                tracing::warn!(
+                    "Could not send notifications to {}: {error:#}",
+                    notifier.name(),
+                );
+            }
+        }
+        outbox.save(self.state.dir())
+    }
+}
+
+/// Choose a file name for downloaded content.
+///
+/// This is the last segment of the URL path prefixed with the current time, so
+/// that downloads of different versions don’t overwrite each other.
+fn download_file_name(response: &Response) -> String {
+    let name = response
+        .url
+        .path_segments()
+        .and_then(|mut segments| segments.next_back())
+        .filter(|name| !name.is_empty() && *name != "." && *name != "..")
+        .unwrap_or("index");
+    format!("{}-{name}", Utc::now().format("%Y%m%dT%H%M%SZ"))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use assert2::check;
+    use chrono::TimeDelta;
+    use std::path::Path;
+    use std::sync::{Arc, Mutex};
+    use std::time::Duration;
+    use tokio::io::{AsyncBufReadExt, AsyncWriteExt, BufReader};
+    use tokio::net::TcpListener;
+
+    /// A local web server whose response can be changed between checks.
+    #[derive(Clone)]
+    struct Site(Arc<Mutex<String>>);
+
+    impl Site {
+        /// Start serving `body` as HTML on a local port.
+        async fn start(body: &str) -> (Self, Url) {
+            let site = Self(Arc::new(Mutex::new(String::new())));
+            site.set(200, body);
+            let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+            let address = listener.local_addr().unwrap();
+            let reply = site.clone();
+            tokio::spawn(async move {
+                loop {
+                    let (socket, _) = listener.accept().await.unwrap();
+                    let mut socket = BufReader::new(socket);
+                    let mut request = String::new();
+                    while !request.ends_with("\r\n\r\n") {
+                        match socket.read_line(&mut request).await {
+                            Ok(0) | Err(_) => break,
+                            Ok(_) => {}
+                        }
+                    }
+                    let reply = reply.0.lock().unwrap().clone();
+                    let _ =
+                        socket.into_inner().write_all(reply.as_bytes()).await;
+                }
+            });
+            (site, format!("http://{address}/").parse().unwrap())
+        }
+
+        /// Respond with `status` and `body` as HTML.
+        fn set(&self, status: u16, body: &str) {
+            self.reply(&format!(
+                "HTTP/1.1 {status} Status\r\nContent-Type: text/html\r\n\
+                Content-Length: {}\r\nConnection: close\r\n\r\n{body}",
+                body.len(),
+            ));
+        }
+
+        /// Respond with a raw HTTP response.
+        fn reply(&self, reply: &str) {
+            reply.clone_into(&mut self.0.lock().unwrap());
+        }
+    }
+
+    /// Get an empty temporary directory for a test.
+    fn temp_dir(name: &str) -> PathBuf {
+        let dir = std::env::temp_dir()
+            .join(format!("monitorbot-{}-{name}", std::process::id()));
+        let _ = fs::remove_dir_all(&dir);
+        dir
+    }
+
+    /// Parse the command line, with the state directory set to `dir`.
+    fn params(dir: &Path, args: &[&str]) -> Params {
+        let state_dir = dir.to_str().unwrap();
+        <Params as clap::Parser>::parse_from(
+            ["monitorbot", "--state-dir", state_dir, "--no-progress"]
+                .into_iter()
+                .chain(args.iter().copied()),
+        )
+    }
+
+    /// Make a checker like `monitorbot check` does.
+    fn checker<'a>(params: &'a Params, config: &'a Config) -> Checker<'a> {
+        let state = State::new(params.state_dir_path());
+        state.create().unwrap();
+        let recorder = Recorder::default();
+        let options = ClientOptions::new(params, None);
+        Checker {
+            params,
+            config,
+            client: request::client(params, &recorder, &options).unwrap(),
+            clients: HashMap::new(),
+            state,
+            robots: None,
+            feed: None,
+            notifications: Vec::new(),
+            failed: false,
+            recorder,
+            timings: Vec::new(),
+            progress: Progress::new(params, 1),
+            export: None,
+            tokens: TokenCache::default(),
+            dashboard: None,
+            audit_log: None,
+            git: None,
+            status: None,
+            trace: None,
+            tunnels: Tunnels::default(),
+        }
+    }
+
+    /// Check a URL, and get the outcome and the events notified.
+    async fn check_url(
+        checker: &mut Checker<'_>,
+        url_config: &UrlConfig,
+    ) -> (Outcome, Vec<Event>) {
+        let notified = checker.notifications.len();
+        let outcome = checker.check_url(url_config).await.unwrap();
+        let events = checker
+            .notifications
+            .get(notified..)
+            .unwrap_or_default()
+            .iter()
+            .map(|notification| notification.event)
+            .collect();
+        (outcome, events)
+    }
+
+    /// Make an HTML response.
+    fn html(body: &'static str) -> Response {
+        let mut headers = http::HeaderMap::new();
+        headers.insert(
+            http::header::CONTENT_TYPE,
+            http::HeaderValue::from_static("text/html; charset=utf-8"),
+        );
+        Response {
+            url: "https://example.com/".parse().unwrap(),
+            version: http::Version::HTTP_11,
+            status: http::StatusCode::OK,
+            headers,
+            body: bytes::Bytes::from_static(body.as_bytes()),
+            spooled: None,
+            certificate_sha256: None,
+            peer_address: None,
+        }
+    }
+
+    #[test]
+    fn test_content() {
+        let params = <Params as clap::Parser>::parse_from(["monitorbot"]);
+        let mut url_config =
+            UrlConfig::new("https://example.com/".parse().unwrap());
+        url_config.select = Some("#price".to_owned());
+
+        let old = html("<p id=price>$5</p><p>Visitors: 1</p>");
+        let new = html("<p id=price>$5</p><p>Visitors: 2</p>");
+        let changed = html("<p id=price>$6</p><p>Visitors: 2</p>");
+        let content = |response| content(&params, &url_config, response);
+        check!(content(&old) == content(&new));
+        check!(content(&old) != content(&changed));
+        check!(content(&old).as_ref() == b"$5".as_slice());
+    }
+
+    /// Collects log output in memory.
+    #[derive(Clone, Default)]
+    struct Buffer(Arc<Mutex<Vec<u8>>>);
+
+    impl io::Write for Buffer {
+        fn write(&mut self, bytes: &[u8]) -> io::Result<usize> {
+            self.0.lock().unwrap().extend_from_slice(bytes);
+            Ok(bytes.len())
+        }
+
+        fn flush(&mut self) -> io::Result<()> {
+            Ok(())
+        }
+    }
+
+    #[test]
+    fn test_log_encoding() {
+        let url_config =
+            UrlConfig::new("https://example.com/".parse().unwrap());
+        let log = |content_type, body: &'static [u8]| {
+            let mut response = html("");
+            response.headers.insert(
+                http::header::CONTENT_TYPE,
+                http::HeaderValue::from_static(content_type),
+            );
+            response.body = bytes::Bytes::from_static(body);
+            let buffer = Buffer::default();
+            let writer = buffer.clone();
+            let subscriber = tracing_subscriber::fmt()
+                .with_max_level(tracing::Level::DEBUG)
+                .with_ansi(false)
+                .without_time()
+                .with_writer(move || writer.clone())
+                .finish();
+            tracing::subscriber::with_default(subscriber, || {
+                log_encoding(&url_config, &response);
+            });
+            let output = buffer.0.lock().unwrap().clone();
+            String::from_utf8(output).unwrap().trim_start().to_owned()
+        };
+
+        check!(
+            log("text/html; charset=utf-8", "café".as_bytes())
+                == format!(
+                    "DEBUG monitorbot::check: {} is encoded in UTF-8\n",
+                    url_config.label(),
+                )
+        );
+        check!(
+            log("text/html; charset=utf-8", b"caf\xe9")
+                == format!(
+                    "WARN monitorbot::check: {} is labeled as UTF-8 but is \
+                    encoded in windows-1252; decoding it as windows-1252\n",
+                    url_config.label(),
+                )
+        );
+        check!(log("image/png", b"\x89PNG\r\n\x1a\n\xe9").is_empty());
+    }
+
+    /// Move a URL’s history back in time.
+    fn backdate(checker: &Checker<'_>, url: &Url, by: TimeDelta) {
+        let mut history =
+            checker.state.load_history(url, false).unwrap().unwrap();
+        for revision in &mut history.revisions {
+            revision.fetched_at =
+                revision.fetched_at.checked_sub_signed(by).unwrap();
+            revision.last_seen = revision
+                .last_seen
+                .map(|last_seen| last_seen.checked_sub_signed(by).unwrap());
+        }
+        checker.state.save_history(url, &history).unwrap();
+    }
+
+    #[tokio::test]
+    async fn test_removal() {
+        let hour = TimeDelta::hours(1);
+        let minute = TimeDelta::minutes(1);
+        // How long the page was removed for, whether it looked like a login
+        // page while removed, and whether it should be compared to the page
+        // from before it was removed when it’s restored.
+        let cases = [
+            (TimeDelta::zero(), false, true),
+            (TimeDelta::zero(), true, true),
+            (hour.checked_sub(&minute).unwrap(), false, true),
+            (hour.checked_add(&minute).unwrap(), false, false),
+        ];
+        for (i, (removed_for, login, compared)) in cases.into_iter().enumerate()
+        {
+            let (site, url) = Site::start("<p>Price: $5</p>").await;
+            let dir = temp_dir(&format!("removal-{i}"));
+            let params = params(&dir, &[]);
+            let config = Config::default();
+            let mut checker = checker(&params, &config);
+            let mut url_config = UrlConfig::new(url.clone());
+            url_config.restore_window = Some(Duration::from_hours(1));
+
+            check!(
+                check_url(&mut checker, &url_config).await.0 == Outcome::New
+            );
+            site.set(404, "<p>Gone</p>");
+            check!(
+                check_url(&mut checker, &url_config).await
+                    == (Outcome::Changed, vec![Event::Changed])
+            );
+            check!(
+                checker.notifications.last().unwrap().kind
+                    == Some(ChangeKind::Removed)
+            );
+            check!(
+                check_url(&mut checker, &url_config).await
+                    == (Outcome::Unchanged, vec![])
+            );
+            if login {
+                site.set(200, "<title>Log in</title><input type=password>");
+                check!(
+                    check_url(&mut checker, &url_config).await
+                        == (Outcome::Failed, vec![Event::SoftError])
+                );
+            }
+            backdate(&checker, &url, removed_for);
+
+            site.set(200, "<p>Price: $6</p>");
+            let (outcome, events) = check_url(&mut checker, &url_config).await;
+            check!(outcome == Outcome::Changed);
+            check!(events.last() == Some(&Event::Changed));
+            let restored = checker.notifications.last().unwrap();
+            check!(restored.kind == Some(ChangeKind::Restored));
+            check!(restored.text.contains("Page restored (200 OK)"));
+            check!(restored.text.contains("+Price: $6"));
+            check!(restored.text.contains("-Price: $5") == compared);
+            fs::remove_dir_all(&dir).unwrap();
+        }
+    }
+
+    #[tokio::test]
+    async fn test_stale() {
+        let (site, url) = Site::start("<p>Updated today</p>").await;
+        let dir = temp_dir("stale");
+        let params = params(&dir, &[]);
+        let config = Config::default();
+        let mut checker = checker(&params, &config);
+        let mut url_config = UrlConfig::new(url.clone());
+        url_config.stale_after = Some(Duration::from_hours(1));
+
+        check!(check_url(&mut checker, &url_config).await.0 == Outcome::New);
+        backdate(&checker, &url, TimeDelta::minutes(50));
+        check!(
+            check_url(&mut checker, &url_config).await
+                == (Outcome::Unchanged, vec![])
+        );
+
+        // An error doesn’t count as a change.
+        site.set(503, "<p>Down for maintenance</p>");
+        check_url(&mut checker, &url_config).await;
+        backdate(&checker, &url, TimeDelta::minutes(20));
+        site.set(200, "<p>Updated today</p>");
+        let (outcome, events) = check_url(&mut checker, &url_config).await;
+        check!(outcome == Outcome::Failed);
+        check!(events.contains(&Event::Failed));
+        check!(checker.notifications.iter().any(|notification| {
+            notification.text.contains("Content hasn’t changed since")
+        }));
+        check!(
+            check_url(&mut checker, &url_config).await
+                == (Outcome::Failed, vec![])
+        );
+
+        site.set(200, "<p>Updated tomorrow</p>");
+        check!(
+            check_url(&mut checker, &url_config).await
+                == (Outcome::Changed, vec![Event::Recovered, Event::Changed])
+        );
+        fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[tokio::test]
+    async fn test_flap() {
+        let (site, url) = Site::start("<p>A</p>").await;
+        let dir = temp_dir("flap");
+        let params = params(&dir, &[]);
+        let config = Config::default();
+        let mut checker = checker(&params, &config);
+        let mut url_config = UrlConfig::new(url);
+        url_config.flap_window = Some(Duration::from_hours(1));
+
+        let mut check = async |body| {
+            site.set(200, body);
+            check_url(&mut checker, &url_config).await
+        };
+        check!(check("<p>A</p>").await == (Outcome::New, vec![]));
+        check!(
+            check("<p>B</p>").await == (Outcome::Changed, vec![Event::Changed])
+        );
+        // Back to A: the change is output, but not notified.
+        check!(check("<p>A</p>").await == (Outcome::Changed, vec![]));
+        check!(check("<p>A</p>").await == (Outcome::Unchanged, vec![]));
+        check!(
+            check("<p>C</p>").await == (Outcome::Changed, vec![Event::Changed])
+        );
+        fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[tokio::test]
+    async fn test_baseline() {
+        let (site, url) = Site::start("<p>Price: $5</p>").await;
+        let dir = temp_dir("baseline");
+        let params = params(&dir, &[]);
+        let config = Config::default();
+        let mut checker = checker(&params, &config);
+        let url_config = UrlConfig::new(url.clone());
+
+        check!(check_url(&mut checker, &url_config).await.0 == Outcome::New);
+        site.set(200, "<p>Price: $6</p>");
+        check!(
+            check_url(&mut checker, &url_config).await.0 == Outcome::Changed
+        );
+
+        let baseline = |rev: &str| {
+            let params = self::params(
+                &dir,
+                &["baseline", "set", url.as_str(), "--rev", rev],
+            );
+            let Some(crate::params::Command::Baseline(command)) =
+                &params.command
+            else {
+                unreachable!();
+            };
+            crate::commands::baseline::run(&params, command)
+        };
+        check!(
+            baseline("3").unwrap_err().to_string()
+                == "Revision 3 does not exist; there are 2 revisions"
+        );
+        check!(baseline("0").is_err());
+        baseline("1").unwrap();
+
+        // Changes are shown relative to the baseline.
+        site.set(200, "<p>Price: $7</p>");
+        check!(
+            check_url(&mut checker, &url_config).await
+                == (Outcome::Changed, vec![Event::Changed])
+        );
+        let changed = checker.notifications.last().unwrap();
+        check!(changed.text.contains("-Price: $5"));
+        check!(changed.text.contains("+Price: $7"));
+        check!(!changed.text.contains("Price: $6"));
+        check!(
+            check_url(&mut checker, &url_config).await
+                == (Outcome::Unchanged, vec![])
+        );
+
+        site.set(200, "<p>Price: $5</p>");
+        check!(
+            check_url(&mut checker, &url_config).await
+                == (Outcome::Changed, vec![Event::Changed])
+        );
+        let restored = checker.notifications.last().unwrap();
+        check!(restored.kind == Some(ChangeKind::Restored));
+        check!(restored.text.contains("Matches the pinned baseline again"));
+        fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[tokio::test]
+    async fn test_max_download_size() {
+        let big = format!("<p>{}</p>", "x".repeat(1000));
+        let (site, url) = Site::start(&big).await;
+        let dir = temp_dir("max-download-size");
+        // The command line overrides the config.
+        let params = params(&dir, &["--max-download-size", "100"]);
+        let config = Config::default();
+        let mut checker = checker(&params, &config);
+        let mut url_config = UrlConfig::new(url);
+        url_config.max_download_size = Some(10_000);
+
+        checker.check(&url_config).await.unwrap();
+        let timing = checker.timings.last().unwrap();
+        check!(timing.outcome == Some(Outcome::Error));
+        check!(timing.bytes > 100);
+        check!(timing.bytes <= big.len());
+        let error = timing.error.as_ref().unwrap();
+        check!(error.kind == crate::error::ErrorKind::Network);
+        check!(!error.retryable);
+        check!(
+            error.message
+                == format!(
+                    "stopped downloading after {} bytes: larger than \
+                    max_download_size of 100 bytes",
+                    timing.bytes,
+                )
+        );
+
+        // Bytes are counted for each URL separately.
+        site.set(200, "<p>Small</p>");
+        checker.check(&url_config).await.unwrap();
+        let timing = checker.timings.last().unwrap();
+        check!(timing.outcome == Some(Outcome::New));
+        check!(timing.bytes == "<p>Small</p>".len());
+        fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[tokio::test]
+    async fn test_redirects() {
+        let (target, target_url) = Site::start("<p>Moved here</p>").await;
+        let (site, url) = Site::start("").await;
+        let redirect = |location: &str| {
+            format!(
+                "HTTP/1.1 302 Found\r\nLocation: {location}\r\n\
+                Content-Length: 0\r\nConnection: close\r\n\r\n"
+            )
+        };
+        let dir = temp_dir("redirects");
+        let config = Config::default();
+        let status = |checker: &Checker<'_>| {
+            let history =
+                checker.state.load_history(&url, false).unwrap().unwrap();
+            history.latest().unwrap().status
+        };
+
+        // Redirects to other origins are followed by default.
+        site.reply(&redirect(target_url.as_str()));
+        let params = params(&dir, &[]);
+        let mut checker = checker(&params, &config);
+        let mut url_config = UrlConfig::new(url.clone());
+        check!(check_url(&mut checker, &url_config).await.0 == Outcome::New);
+        check!(status(&checker) == http::StatusCode::OK);
+
+        // If they aren’t, the redirect itself is compared.
+        url_config.follow_cross_origin_redirects = false;
+        target.set(200, "<p>Moved again</p>");
+        check!(
+            check_url(&mut checker, &url_config).await
+                == (Outcome::Changed, vec![Event::Changed])
+        );
+        check!(status(&checker) == http::StatusCode::FOUND);
+        check!(
+            checker
+                .notifications
+                .last()
+                .unwrap()
+                .text
+                .contains("302 Found")
+        );
+
+        // Redirects within the origin are still followed.
+        site.reply(&redirect("/again"));
+        let error = checker.check_url(&url_config).await.unwrap_err();
+        check!(let MonitorError::Redirect(_) = error);
+        check!(format!("{error:#}").contains("redirect loop"));
+        drop(checker);
+
+        // `--max-redirects 0` stops at the first redirect.
+        let params = self::params(&dir, &["--max-redirects", "0"]);
+        let mut checker = self::checker(&params, &config);
+        url_config.follow_cross_origin_redirects = true;
+        check!(
+            check_url(&mut checker, &url_config).await.0 == Outcome::Changed
+        );
+        check!(status(&checker) == http::StatusCode::FOUND);
+        check!(
+            checker
+                .notifications
+                .last()
+                .unwrap()
+                .text
+                .contains("/again")
+        );
+        fs::remove_dir_all(&dir).unwrap();
+    }
+}
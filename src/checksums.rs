@@ -0,0 +1,257 @@
+//! Verifying the checksums published on download pages.
+//!
+//! A URL with a `checksums` table is rendered as a list of the artifacts it
+//! links to and their published checksums, so that new releases and changed
+//! checksums show up in the diff. With `verify = true`, the artifacts are also
+//! downloaded and checked against the published checksums.
+
+use anyhow::{anyhow, bail};
+use regex::Regex;
+use scraper::{Html, Selector};
+use sha2::{Digest, Sha256, Sha512};
+use std::collections::HashMap;
+use std::fmt::Write as _;
+use url::Url;
+
+/// Pattern for checksums in the format used by `sha256sum`, i.e. a SHA-256 or
+/// SHA-512 hash followed by a file name.
+static DEFAULT_PATTERN: &str =
+    r"(?i)\b(?P<hash>[0-9a-f]{128}|[0-9a-f]{64})\b\s+\*?(?P<file>[^\s<>]+)";
+
+/// Selector for all links.
+static DEFAULT_LINKS: &str = "a[href]";
+
+/// How to find and verify the checksums on a page.
+#[derive(Clone, Debug, serde::Deserialize, serde::Serialize)]
+#[serde(deny_unknown_fields)]
+pub struct Checksums {
+    /// CSS selector for links to artifacts (default: links to files with a
+    /// published checksum).
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub links: Option<String>,
+
+    /// Regular expression for checksums in the text of the page, with named
+    /// groups `hash` and `file` (default: `sha256sum` format).
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub pattern: Option<String>,
+
+    /// Download the artifacts and verify their checksums.
+    #[serde(default, skip_serializing_if = "std::ops::Not::not")]
+    pub verify: bool,
+}
+
+/// A file linked from a page.
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub struct Artifact {
+    /// Where to download the file.
+    pub url: Url,
+
+    /// The published checksum in lowercase hex, if one was found.
+    pub checksum: Option<String>,
+}
+
+impl Checksums {
+    /// Find the artifacts linked from an HTML page and their checksums.
+    ///
+    /// Links are matched to checksums by file name. Relative links are
+    /// resolved against `base_url`. If `links` isn’t set, only links with a
+    /// checksum are returned.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if `links` or `pattern` is invalid.
+    pub fn find(
+        &self,
+        html: &str,
+        base_url: &Url,
+    ) -> anyhow::Result<Vec<Artifact>> {
+        let source = self.links.as_deref().unwrap_or(DEFAULT_LINKS);
+        let selector = Selector::parse(source).map_err(|error| {
+            anyhow!("invalid checksums links {source:?}: {error}")
+        })?;
+        let source = self.pattern.as_deref().unwrap_or(DEFAULT_PATTERN);
+        let pattern = Regex::new(source).map_err(|error| {
+            anyhow!("invalid checksums pattern {source:?}: {error}")
+        })?;
+        for group in ["hash", "file"] {
+            if !pattern.capture_names().flatten().any(|name| name == group) {
+                bail!("checksums pattern {source:?} has no group {group:?}");
+            }
+        }
+
+        let document = Html::parse_document(html);
+        let text = document.root_element().text().collect::<Vec<_>>().join(" ");
+        let mut checksums = HashMap::new();
+        for captures in pattern.captures_iter(&text) {
+            if let (Some(hash), Some(file)) =
+                (captures.name("hash"), captures.name("file"))
+            {
+                checksums
+                    .entry(base_name(file.as_str()).to_owned())
+                    .or_insert_with(|| hash.as_str().to_ascii_lowercase());
+            }
+        }
+
+        let mut artifacts: Vec<Artifact> = Vec::new();
+        for element in document.select(&selector) {
+            let Some(url) = element
+                .attr("href")
+                .and_then(|href| base_url.join(href).ok())
+                .filter(|url| matches!(url.scheme(), "http" | "https"))
+            else {
+                continue;
+            };
+            if artifacts.iter().any(|artifact| artifact.url == url) {
+                continue;
+            }
+            let checksum = checksums.get(base_name(url.path())).cloned();
+            if checksum.is_some() || self.links.is_some() {
+                artifacts.push(Artifact { url, checksum });
+            }
+        }
+        Ok(artifacts)
+    }
+}
+
+/// Render artifacts as one line each, for diffing.
+#[must_use]
+pub fn manifest(artifacts: &[Artifact]) -> String {
+    let mut out = String::new();
+    for artifact in artifacts {
+        let _ = match &artifact.checksum {
+            Some(checksum) => writeln!(
+                out,
+                "{} {}:{checksum}",
+                artifact.url,
+                algorithm(checksum).unwrap_or("unknown"),
+            ),
+            None => writeln!(out, "{} (no checksum)", artifact.url),
+        };
+    }
+    out.trim_end().to_owned()
+}
+
+/// Hashes data with the algorithm that matches a published checksum.
+#[derive(Clone, Debug)]
+pub enum Hasher {
+    /// SHA-256.
+    Sha256(Sha256),
+
+    /// SHA-512.
+    Sha512(Sha512),
+}
+
+impl Hasher {
+    /// Get a hasher for the algorithm that produces `checksum`, based on its
+    /// length.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if the checksum isn’t a SHA-256 or SHA-512 hash.
+    pub fn for_checksum(checksum: &str) -> anyhow::Result<Self> {
+        match algorithm(checksum) {
+            Some("sha256") => Ok(Self::Sha256(Sha256::new())),
+            Some("sha512") => Ok(Self::Sha512(Sha512::new())),
+            _ => bail!("unsupported checksum {checksum:?}"),
+        }
+    }
+
+    /// Add data to the hash.
+    pub fn update(&mut self, data: &[u8]) {
+        match self {
+            Self::Sha256(hasher) => hasher.update(data),
+            Self::Sha512(hasher) => hasher.update(data),
+        }
+    }
+
+    /// Get the hash in lowercase hex.
+    #[must_use]
+    pub fn finish(self) -> String {
+        let hash = match self {
+            Self::Sha256(hasher) => hasher.finalize().to_vec(),
+            Self::Sha512(hasher) => hasher.finalize().to_vec(),
+        };
+        hash.iter().fold(String::new(), |mut out, byte| {
+            let _ = write!(out, "{byte:02x}");
+            out
+        })
+    }
+}
+
+/// Guess the algorithm used to produce a checksum from its length.
+const fn algorithm(checksum: &str) -> Option<&'static str> {
+    match checksum.len() {
+        64 => Some("sha256"),
+        128 => Some("sha512"),
+        _ => None,
+    }
+}
+
+/// Get the last component of a path.
+fn base_name(path: &str) -> &str {
+    path.rsplit('/').next().unwrap_or(path)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use assert2::check;
+
+    #[test]
+    fn test_find() {
+        let base: Url = "https://example.com/releases/".parse().unwrap();
+        let sha256 = "ab".repeat(32);
+        let html = format!(
+            r#"<a href="/">Home</a>
+            <a href="v1/tool-1.0.tar.gz">tool-1.0.tar.gz</a>
+            <a href="v1/tool-1.0.zip">tool-1.0.zip</a>
+            <pre>{}  ./tool-1.0.tar.gz</pre>"#,
+            sha256.to_ascii_uppercase(),
+        );
+        let artifact = |path: &str, checksum: Option<&str>| Artifact {
+            url: base.join(path).unwrap(),
+            checksum: checksum.map(ToOwned::to_owned),
+        };
+
+        let default = Checksums { links: None, pattern: None, verify: false };
+        let artifacts = default.find(&html, &base).unwrap();
+        check!(artifacts == [artifact("v1/tool-1.0.tar.gz", Some(&sha256))]);
+        check!(
+            manifest(&artifacts)
+                == format!(
+                    "https://example.com/releases/v1/tool-1.0.tar.gz \
+                    sha256:{sha256}"
+                )
+        );
+
+        let links = Checksums {
+            links: Some("a[href^=v1]".to_owned()),
+            ..default.clone()
+        };
+        check!(
+            links.find(&html, &base).unwrap()
+                == [
+                    artifact("v1/tool-1.0.tar.gz", Some(&sha256)),
+                    artifact("v1/tool-1.0.zip", None),
+                ]
+        );
+
+        let pattern = Checksums {
+            pattern: Some("(?P<hash>[0-9a-f]+)".to_owned()),
+            ..default
+        };
+        check!(pattern.find(&html, &base).is_err());
+    }
+
+    #[test]
+    fn test_hasher() {
+        let sha256 =
+            "2cf24dba5fb0a30e26e83b2ac5b9e29e1b161e5c1fa7425e73043362938b9824";
+        let mut hasher = Hasher::for_checksum(sha256).unwrap();
+        hasher.update(b"hel");
+        hasher.update(b"lo");
+        check!(hasher.finish() == sha256);
+        check!(Hasher::for_checksum(&"0".repeat(128)).is_ok());
+        check!(Hasher::for_checksum("abc").is_err());
+    }
+}
@@ -0,0 +1,20 @@
+//! Subcommands.
+
+pub mod add;
+pub mod audit;
+pub mod baseline;
+pub mod completions;
+pub mod digest;
+pub mod export;
+pub mod fsck;
+pub mod history;
+pub mod import;
+pub mod list;
+pub mod manpage;
+pub mod note;
+pub mod profile;
+pub mod remove;
+pub mod restore;
+pub mod review;
+pub mod show;
+pub mod values;
@@ -0,0 +1,58 @@
+//! The `add` subcommand.
+
+use anyhow::bail;
+use std::process::ExitCode;
+
+use crate::config::UrlConfig;
+use crate::config_file::ConfigFile;
+use crate::params::{AddParams, Params};
+use crate::state::State;
+
+/// Add URLs to the configuration file.
+///
+/// URLs that are already in the file are skipped with a warning.
+///
+/// # Errors
+///
+/// Returns an error if the configuration file couldn’t be read or written, or
+/// `--name` was passed with more than one URL.
+pub fn run(params: &Params, add: &AddParams) -> anyhow::Result<ExitCode> {
+    if add.name.is_some() && add.urls.len() > 1 {
+        bail!("--name can only be used when adding one URL");
+    }
+
+    let mut file = ConfigFile::open(&params.config_path())?;
+    let mut exit_code = ExitCode::SUCCESS;
+    let mut added = Vec::new();
+    for url in &add.urls {
+        let mut url_config = UrlConfig::new(url.clone());
+        url_config.name.clone_from(&add.name);
+        url_config.select.clone_from(&add.select);
+        url_config.tags.clone_from(&add.tag);
+        match file.add(&url_config) {
+            Ok(()) => added.push(url),
+            Err(error) => {
+                params.warn(format!("Skipping: {error}\n"))?;
+                exit_code = ExitCode::FAILURE;
+            }
+        }
+    }
+    if added.is_empty() {
+        return Ok(exit_code);
+    }
+
+    let path = file.path().display();
+    if params.dry_run {
+        for url in added {
+            eprintln!("Dry run: would add {url} to {path}");
+        }
+        return Ok(exit_code);
+    }
+
+    file.save()?;
+    State::new(params.state_dir_path()).create()?;
+    for url in added {
+        println!("Added {url} to {path}");
+    }
+    Ok(exit_code)
+}
@@ -0,0 +1,55 @@
+//! The `audit` subcommand.
+
+use anyhow::{Context, bail};
+use std::fs;
+use std::process::ExitCode;
+
+use crate::audit;
+use crate::params::{AuditCommand, Params};
+
+/// Check the audit log.
+///
+/// # Errors
+///
+/// Returns an error if no log is configured, or if it couldn’t be read or is
+/// invalid.
+pub fn run(
+    params: &Params,
+    command: &AuditCommand,
+) -> anyhow::Result<ExitCode> {
+    match command {
+        AuditCommand::Verify(verify) => {
+            let path = if let Some(file) = &verify.file {
+                file.clone()
+            } else {
+                let Some(audit_log) = params.load_config()?.audit_log else {
+                    bail!(
+                        "no audit log is configured in {}",
+                        params.config_path().display(),
+                    );
+                };
+                audit_log.path(&params.state_dir_path())
+            };
+
+            let file = fs::File::open(&path).with_context(|| {
+                format!("could not read {}", path.display())
+            })?;
+            let verified = audit::verify(file, verify.public_key.as_deref())
+                .with_context(|| format!("{} is invalid", path.display()))?;
+            println!(
+                "Verified {} entries in {}",
+                verified.entries,
+                path.display(),
+            );
+            if verified.signatures_verified {
+                println!("All entries have valid signatures");
+            } else if verified.signed > 0 {
+                println!(
+                    "{} entries are signed; pass --public-key to verify them",
+                    verified.signed,
+                );
+            }
+            Ok(ExitCode::SUCCESS)
+        }
+    }
+}
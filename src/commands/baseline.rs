@@ -0,0 +1,85 @@
+//! The `baseline` subcommand.
+
+use anyhow::anyhow;
+use std::process::ExitCode;
+
+use crate::params::{BaselineCommand, Params};
+use crate::state::{History, State};
+
+/// Pin, unpin, or show the baseline for a URL.
+///
+/// # Errors
+///
+/// Returns an error if there is no stored response for the URL, if the
+/// revision doesn’t exist, or if the history couldn’t be saved.
+pub fn run(
+    params: &Params,
+    command: &BaselineCommand,
+) -> anyhow::Result<ExitCode> {
+    let state = State::new(params.state_dir_path());
+    let url = match command {
+        BaselineCommand::Set(set) => &set.url,
+        BaselineCommand::Clear(baseline) | BaselineCommand::Show(baseline) => {
+            &baseline.url
+        }
+    };
+    let mut history = state
+        .load_history(url, params.dry_run)?
+        .ok_or_else(|| anyhow!("No stored responses for {url}"))?;
+
+    match command {
+        BaselineCommand::Set(set) => {
+            let index = history.index(set.rev)?;
+            history.baseline = Some(index);
+            save(params, &state, &history)?;
+            println!(
+                "Pinned revision {} as the baseline for {url}",
+                index.saturating_add(1),
+            );
+        }
+        BaselineCommand::Clear(_) => {
+            if history.baseline.take().is_some() {
+                save(params, &state, &history)?;
+                println!("Unpinned the baseline for {url}");
+            } else {
+                println!("No baseline pinned for {url}");
+            }
+        }
+        BaselineCommand::Show(_) => match history.baseline() {
+            Some(revision) => println!(
+                "Revision {} (fetched {}) is pinned as the baseline for {url}",
+                history.baseline.unwrap_or_default().saturating_add(1),
+                revision.fetched_at.to_rfc3339(),
+            ),
+            None => println!("No baseline pinned for {url}"),
+        },
+    }
+
+    Ok(ExitCode::SUCCESS)
+}
+
+/// Save a URL’s history.
+///
+/// It’s saved under the URL of the latest response, like after checking the
+/// URL, so that redirects still point to it.
+///
+/// # Errors
+///
+/// Returns an error if the history couldn’t be saved.
+fn save(
+    params: &Params,
+    state: &State,
+    history: &History,
+) -> anyhow::Result<()> {
+    let Some(latest) = history.latest() else {
+        return Ok(());
+    };
+    if params.dry_run {
+        eprintln!(
+            "Dry run: would save {}",
+            state.history_path(&latest.url).display(),
+        );
+        return Ok(());
+    }
+    state.save_history(&latest.url, history)
+}
@@ -0,0 +1,45 @@
+//! The `completions` subcommand.
+
+use clap::CommandFactory;
+use std::io::{self, Write};
+use std::process::ExitCode;
+
+use crate::params::{CompletionsParams, Params};
+
+/// Print a shell completion script generated from the command line options.
+///
+/// # Errors
+///
+/// Returns an error if stdout couldn’t be written.
+pub fn run(completions: &CompletionsParams) -> anyhow::Result<ExitCode> {
+    let mut command = Params::command();
+    let name = command.get_name().to_owned();
+    // `generate()` panics if it can’t write, e.g. to a closed pipe.
+    let mut script = Vec::new();
+    clap_complete::generate(completions.shell, &mut command, name, &mut script);
+    io::stdout().lock().write_all(&script)?;
+    Ok(ExitCode::SUCCESS)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use assert2::check;
+    use clap::ValueEnum;
+
+    #[test]
+    fn test_completions() {
+        Params::command().debug_assert();
+        for &shell in clap_complete::Shell::value_variants() {
+            let mut out = Vec::new();
+            clap_complete::generate(
+                shell,
+                &mut Params::command(),
+                "monitorbot",
+                &mut out,
+            );
+            let script = String::from_utf8(out).unwrap();
+            check!(script.contains("max-age"), "{shell}");
+        }
+    }
+}
@@ -0,0 +1,210 @@
+//! The `digest` subcommand.
+
+use anyhow::Context;
+use chrono::{DateTime, Utc};
+use std::fs;
+use std::process::ExitCode;
+
+use crate::config::UrlConfig;
+use crate::digest::{self, Record};
+use crate::export::html_page;
+use crate::notify::{ChangeKind, Event, Notification};
+use crate::params::{DigestParams, Params};
+use crate::request::{self, ClientOptions};
+use crate::review::{render_diff, unreviewed_since};
+use crate::state::State;
+use crate::summarize::Limits;
+use crate::timing::Recorder;
+
+/// Summarize the changes since the last digest in one message.
+///
+/// The digest is printed, and optionally written as HTML and sent to the
+/// notifiers. Then the time is recorded, so the next digest starts from here.
+///
+/// # Errors
+///
+/// Returns an error if the configuration couldn’t be loaded, the digest
+/// couldn’t be written or sent, or the time couldn’t be recorded.
+pub async fn run(
+    params: &Params,
+    digest: &DigestParams,
+) -> anyhow::Result<ExitCode> {
+    let config = params.load_config()?;
+    let state = State::new(params.state_dir_path());
+    let now = Utc::now();
+    let since = match digest.since {
+        Some(since) => chrono::Duration::from_std(since)
+            .ok()
+            .and_then(|since| now.checked_sub_signed(since))
+            .context("--since is too long")?,
+        None => match Record::load(state.dir())
+            .context("could not load the time of the last digest")?
+        {
+            Some(record) => record.made_at,
+            None => now
+                .checked_sub_signed(chrono::Duration::days(1))
+                .unwrap_or(now),
+        },
+    };
+
+    let changes = params
+        .url_configs(&config)
+        .iter()
+        .filter_map(|url_config| change(params, &state, url_config, since))
+        .collect::<Vec<_>>();
+
+    let message = digest::message(&changes, since, Limits::default());
+    print!("{}", message.text);
+    if let Some(path) = &digest.html {
+        fs::write(path, html_page(&message.title, &message.html))
+            .with_context(|| format!("could not write {}", path.display()))?;
+    }
+
+    if digest.notify && !changes.is_empty() {
+        let client = request::client(
+            params,
+            &Recorder::default(),
+            &ClientOptions::new(params, None),
+        )?;
+        for notifier in &config.notifiers {
+            let accepted = changes
+                .iter()
+                .filter(|change| notifier.accepts(change))
+                .cloned()
+                .collect::<Vec<_>>();
+            if accepted.is_empty() {
+                continue;
+            }
+            if params.dry_run {
+                eprintln!("Dry run: would send digest to {}", notifier.name());
+                continue;
+            }
+            let limits = notifier.limits(&config.notify);
+            let message = digest::message(&accepted, since, limits);
+            notifier
+                .backend
+                .send(&client, &message)
+                .await
+                .with_context(|| {
+                    format!("could not send digest to {}", notifier.name())
+                })?;
+        }
+    }
+
+    if params.dry_run {
+        eprintln!("Dry run: would record digest at {}", now.to_rfc3339());
+    } else {
+        state.create()?;
+        Record { made_at: now }.save(state.dir())?;
+    }
+    Ok(ExitCode::SUCCESS)
+}
+
+/// Get how a URL changed since `since`, if it did, leaving out changes that
+/// were acknowledged with `review`.
+///
+/// Problems are logged rather than returned, so that one URL can’t keep the
+/// others out of the digest.
+fn change(
+    params: &Params,
+    state: &State,
+    url_config: &UrlConfig,
+    since: DateTime<Utc>,
+) -> Option<Notification> {
+    let history =
+        state
+            .load_history(&url_config.url, true)
+            .unwrap_or_else(|error| {
+                tracing::warn!(
+                    "Could not read history for {}: {error:#}",
+                    url_config.label(),
+                );
+                None
+            })?;
+    let changes = unreviewed_since(&history, since)?;
+    let text =
+        render_diff(params, state, url_config, changes.base, changes.latest)
+            .map_or_else(
+                |error| format!("Could not render: {error:#}\n"),
+                |lines| format!("{}\n", lines.join("\n")),
+            );
+    Some(Notification {
+        label: url_config.label().to_owned(),
+        url: url_config.url.clone(),
+        text,
+        detected_at: changes.latest.fetched_at,
+        event: Event::Changed,
+        kind: Some(ChangeKind::Content),
+        tags: url_config.tags.clone(),
+        severity: url_config.severity.unwrap_or_default(),
+        diff_path: None,
+        status: None,
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::response::Response;
+    use crate::review::acknowledge;
+    use crate::state::{History, Revision};
+    use assert2::check;
+
+    #[test]
+    fn test_change() {
+        let dir = std::env::temp_dir()
+            .join(format!("monitorbot-{}-digest", std::process::id()));
+        let _ = fs::remove_dir_all(&dir);
+        let params = <Params as clap::Parser>::parse_from([
+            "monitorbot",
+            "--state-dir",
+            dir.to_str().unwrap(),
+        ]);
+        let state = State::new(params.state_dir_path());
+        state.create().unwrap();
+        let url_config =
+            UrlConfig::new("https://example.com/".parse().unwrap());
+        let time = |seconds| DateTime::from_timestamp(seconds, 0).unwrap();
+        let revision = |body: &'static str, seconds| {
+            let response = Response {
+                url: url_config.url.clone(),
+                version: http::Version::HTTP_11,
+                status: http::StatusCode::OK,
+                headers: http::HeaderMap::new(),
+                body: body.into(),
+                spooled: None,
+                certificate_sha256: None,
+                peer_address: None,
+            };
+            state.save_response_body(&response).unwrap();
+            let mut revision = Revision::new(&response, body.as_bytes());
+            revision.fetched_at = time(seconds);
+            revision
+        };
+
+        let mut history = History {
+            revisions: vec![
+                revision("one", 1_000),
+                revision("two", 2_000),
+                revision("three", 3_000),
+            ],
+            ..History::default()
+        };
+        state.save_history(&url_config.url, &history).unwrap();
+        let change = |since| change(&params, &state, &url_config, time(since));
+        let text = change(500).unwrap().text;
+        check!(text.contains("-one"));
+        check!(text.contains("+three"));
+
+        // Reviewed changes are left out.
+        acknowledge(&mut history, time(2_000));
+        state.save_history(&url_config.url, &history).unwrap();
+        let text = change(500).unwrap().text;
+        check!(text.contains("-two"));
+        check!(!text.contains("one"));
+        acknowledge(&mut history, time(3_000));
+        state.save_history(&url_config.url, &history).unwrap();
+        check!(change(500).is_none());
+        fs::remove_dir_all(&dir).unwrap();
+    }
+}
@@ -0,0 +1,70 @@
+//! The `export` subcommand.
+//!
+//! This writes the configuration file and state directory to an archive that
+//! can be restored with `restore`.
+
+use anyhow::Context;
+use std::fs;
+use std::io::{self, Write};
+use std::path::{Path, PathBuf};
+use std::process::ExitCode;
+
+use crate::archive;
+use crate::credentials;
+use crate::params::{ExportParams, Params};
+
+/// Write the configuration file and state directory to an archive.
+///
+/// The age identity from `[secrets]` and the credentials file are left out in
+/// case they’re in the state directory; they should be copied separately.
+///
+/// # Errors
+///
+/// Returns an error if the state couldn’t be read or the archive couldn’t be
+/// written.
+pub fn run(params: &Params, export: &ExportParams) -> anyhow::Result<ExitCode> {
+    let state_dir = params.state_dir_path();
+    let config_path = params.config_path();
+    if params.dry_run {
+        eprintln!(
+            "Dry run: would write {} and {} to {}",
+            config_path.display(),
+            state_dir.display(),
+            export.output.display(),
+        );
+        return Ok(ExitCode::SUCCESS);
+    }
+
+    let mut exclude: Vec<PathBuf> =
+        secrets_identity(&config_path).into_iter().collect();
+    exclude.extend(
+        config_path
+            .parent()
+            .map(|dir| dir.join(credentials::FILE_NAME)),
+    );
+    let file = fs::File::create(&export.output).with_context(|| {
+        format!("could not create {}", export.output.display())
+    })?;
+    archive::create(
+        io::BufWriter::new(file),
+        &state_dir,
+        &config_path,
+        &exclude,
+    )
+    .and_then(|mut writer| Ok(writer.flush()?))
+    .with_context(|| format!("could not write {}", export.output.display()))?;
+
+    eprintln!("Wrote {}", export.output.display());
+    Ok(ExitCode::SUCCESS)
+}
+
+/// Get the path to the age identity in the configuration file, if any.
+///
+/// This reads the file directly rather than loading the configuration, since
+/// loading it would decrypt secrets.
+fn secrets_identity(config_path: &Path) -> Option<PathBuf> {
+    let config: toml::Table =
+        fs::read_to_string(config_path).ok()?.parse().ok()?;
+    let identity = config.get("secrets")?.get("identity")?.as_str()?;
+    Some(config_path.parent()?.join(identity))
+}
@@ -0,0 +1,60 @@
+//! The `fsck` subcommand.
+
+use std::process::ExitCode;
+
+use crate::fsck;
+use crate::params::{FsckParams, Params};
+use crate::state::State;
+
+/// Check the state directory and print the problems found.
+///
+/// With `--quarantine`, damaged files are moved aside. Returns failure if there
+/// are problems left.
+///
+/// # Errors
+///
+/// Returns an error if the state directory couldn’t be read, or a file
+/// couldn’t be moved.
+pub fn run(params: &Params, fsck: &FsckParams) -> anyhow::Result<ExitCode> {
+    let state = State::new(params.state_dir_path());
+    if !state.dir().exists() {
+        println!("{} does not exist", state.dir().display());
+        return Ok(ExitCode::SUCCESS);
+    }
+
+    let problems = fsck::check(&state)?;
+    for problem in &problems {
+        println!("{}: {}", problem.path.display(), problem.description);
+    }
+
+    let damaged = problems.iter().filter(|problem| problem.damaged);
+    let mut remaining = problems.len();
+    if fsck.quarantine && damaged.clone().next().is_some() {
+        let now = chrono::Utc::now();
+        if params.dry_run {
+            let dir = fsck::quarantine_dir(&state, now);
+            for problem in damaged {
+                eprintln!(
+                    "Dry run: would move {} to {}",
+                    problem.path.display(),
+                    dir.display(),
+                );
+            }
+        } else {
+            for (from, to) in fsck::quarantine(&state, &problems, now)? {
+                println!("Moved {} to {}", from.display(), to.display());
+            }
+            remaining =
+                problems.iter().filter(|problem| !problem.damaged).count();
+        }
+    }
+
+    if problems.is_empty() {
+        println!("No problems found in {}", state.dir().display());
+    }
+    Ok(if remaining == 0 {
+        ExitCode::SUCCESS
+    } else {
+        ExitCode::FAILURE
+    })
+}
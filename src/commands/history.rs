@@ -0,0 +1,54 @@
+//! The `history` subcommand.
+
+use anyhow::anyhow;
+use chrono::Local;
+use std::io::{self, Write};
+use std::process::ExitCode;
+
+use crate::params::{HistoryParams, Params};
+use crate::state::State;
+
+/// List the stored responses for a URL, oldest first.
+///
+/// Each line has the revision number, when it was fetched, its status,
+/// whether the content changed, and its note, if any.
+///
+/// # Errors
+///
+/// Returns an error if there is no stored response for the URL, or the output
+/// couldn’t be written.
+pub fn run(
+    params: &Params,
+    history: &HistoryParams,
+) -> anyhow::Result<ExitCode> {
+    let state = State::new(params.state_dir_path());
+    let url = &history.url;
+    let history = state
+        .load_history(url, true)?
+        .ok_or_else(|| anyhow!("No stored responses for {url}"))?;
+
+    let mut out = io::stdout().lock();
+    let mut previous = None;
+    for (index, revision) in history.revisions.iter().enumerate() {
+        let changed = previous
+            .is_none_or(|previous: &str| previous != revision.content_sha256);
+        previous = Some(&revision.content_sha256);
+        let mark = match (history.baseline == Some(index), changed) {
+            (true, _) => "baseline",
+            (false, true) => "changed",
+            (false, false) => "",
+        };
+        let line = format!(
+            "{:>4}  {}  {}  {mark:8}  {}",
+            index.saturating_add(1),
+            revision
+                .fetched_at
+                .with_timezone(&Local)
+                .format("%Y-%m-%d %H:%M:%S"),
+            revision.status.as_u16(),
+            revision.note.as_deref().unwrap_or_default(),
+        );
+        writeln!(out, "{}", line.trim_end())?;
+    }
+    Ok(ExitCode::SUCCESS)
+}
@@ -0,0 +1,557 @@
+//! The `import` subcommand.
+//!
+//! This converts watches from other tools into `[[url]]` entries for the
+//! configuration file. Filters that select part of the page become `select`,
+//! `xpath`, or `filters`, check intervals become `interval`, and the request
+//! method becomes `method`. Settings that monitorbot doesn’t support, like
+//! JSON filters and `POST` requests, are dropped with a warning.
+
+use anyhow::Context;
+use serde_json::Value as JsonValue;
+use serde_yaml_ng::Value as YamlValue;
+use std::collections::BTreeMap;
+use std::fs;
+use std::process::ExitCode;
+use std::time::Duration;
+use url::Url;
+
+use crate::config::{Method, UrlConfig};
+use crate::filters::Pipeline;
+use crate::params::{ImportFormat, ImportParams, Params};
+
+/// URLs converted from another tool’s configuration.
+#[derive(Debug, Default)]
+pub struct Import {
+    /// The converted URLs.
+    pub urls: Vec<UrlConfig>,
+
+    /// Warnings about settings that couldn’t be converted.
+    pub warnings: Vec<String>,
+}
+
+/// The converted configuration as it will be output.
+#[derive(Debug, serde::Serialize)]
+struct Output<'a> {
+    /// The converted URLs.
+    #[serde(rename = "url")]
+    urls: &'a [UrlConfig],
+}
+
+/// Convert another tool’s configuration and print it as TOML.
+///
+/// # Errors
+///
+/// Returns an error if the file couldn’t be read or parsed.
+pub fn run(params: &Params, import: &ImportParams) -> anyhow::Result<ExitCode> {
+    let source = fs::read_to_string(&import.path)
+        .with_context(|| format!("could not read {}", import.path.display()))?;
+    let imported = match import.from {
+        ImportFormat::Urlwatch => from_urlwatch(&source),
+        ImportFormat::Changedetection => from_changedetection(&source),
+    }
+    .with_context(|| format!("could not parse {}", import.path.display()))?;
+
+    for warning in &imported.warnings {
+        params.warn(format!("Warning: {warning}\n"))?;
+    }
+
+    print!("{}", toml::to_string(&Output { urls: &imported.urls })?);
+    Ok(ExitCode::SUCCESS)
+}
+
+/// A job from a urlwatch `urls.yaml` file.
+#[derive(Debug, serde::Deserialize)]
+struct UrlwatchJob {
+    /// Name of the job.
+    #[serde(default)]
+    name: Option<String>,
+
+    /// URL to fetch.
+    #[serde(default)]
+    url: Option<String>,
+
+    /// URL to load in a browser.
+    #[serde(default)]
+    navigate: Option<String>,
+
+    /// Command to run.
+    #[serde(default)]
+    command: Option<String>,
+
+    /// Headers to send.
+    #[serde(default)]
+    headers: BTreeMap<String, String>,
+
+    /// Filters to apply to the page, in order.
+    #[serde(default)]
+    filter: Option<YamlValue>,
+
+    /// HTTP method to use.
+    #[serde(default)]
+    method: Option<String>,
+
+    /// Everything else.
+    #[serde(flatten)]
+    other: BTreeMap<String, YamlValue>,
+}
+
+/// Convert a urlwatch `urls.yaml` file.
+///
+/// # Errors
+///
+/// Returns an error if the YAML couldn’t be parsed.
+pub fn from_urlwatch(source: &str) -> anyhow::Result<Import> {
+    let mut import = Import::default();
+
+    for document in serde_yaml_ng::Deserializer::from_str(source) {
+        let job: Option<UrlwatchJob> =
+            serde::Deserialize::deserialize(document)?;
+        let Some(job) = job else {
+            continue; // Empty document.
+        };
+
+        let label = job
+            .name
+            .clone()
+            .or_else(|| job.url.clone())
+            .or_else(|| job.navigate.clone())
+            .or_else(|| job.command.clone())
+            .unwrap_or_default();
+
+        let url = if let Some(url) = &job.url {
+            url
+        } else if let Some(url) = &job.navigate {
+            import.warnings.push(format!(
+                "{label}: JavaScript is not supported; fetching the page \
+                without a browser",
+            ));
+            url
+        } else {
+            import
+                .warnings
+                .push(format!("{label}: skipping job that isn’t a URL"));
+            continue;
+        };
+
+        let Some(url) = parse_url(&mut import.warnings, &label, url) else {
+            continue;
+        };
+
+        for key in job.other.keys() {
+            import.warnings.push(unsupported(&label, key));
+        }
+
+        let mut url_config = UrlConfig::new(url);
+        url_config.name = job.name;
+        url_config.headers = job
+            .headers
+            .into_iter()
+            .map(|(name, value)| (name, value.into()))
+            .collect();
+        if let Some(filter) = &job.filter {
+            url_config.filters =
+                urlwatch_filters(&mut import.warnings, &label, filter);
+        }
+        if let Some(method) = &job.method {
+            url_config.method =
+                convert_method(&mut import.warnings, &label, method);
+        }
+        import.urls.push(url_config);
+    }
+
+    Ok(import)
+}
+
+/// Convert a changedetection.io `url-watches.json` file from a backup.
+///
+/// # Errors
+///
+/// Returns an error if the JSON couldn’t be parsed.
+pub fn from_changedetection(source: &str) -> anyhow::Result<Import> {
+    let mut import = Import::default();
+    let root: JsonValue = serde_json::from_str(source)?;
+    let tag_titles = root
+        .pointer("/settings/application/tags")
+        .and_then(JsonValue::as_object);
+
+    let watches = root
+        .get("watching")
+        .and_then(JsonValue::as_object)
+        .context("missing \"watching\" object")?;
+
+    for watch in watches.values() {
+        let string = |key| watch.get(key).and_then(JsonValue::as_str);
+
+        let Some(url) = string("url") else {
+            import
+                .warnings
+                .push("skipping watch without URL".to_owned());
+            continue;
+        };
+        let name = string("title").filter(|title| !title.is_empty());
+        let label = name.unwrap_or(url);
+
+        if watch.get("paused").and_then(JsonValue::as_bool) == Some(true) {
+            import
+                .warnings
+                .push(format!("{label}: skipping paused watch"));
+            continue;
+        }
+
+        let Some(url) = parse_url(&mut import.warnings, label, url) else {
+            continue;
+        };
+
+        let mut url_config = UrlConfig::new(url);
+        url_config.name = name.map(str::to_owned);
+
+        // Newer versions refer to tags by UUID; older versions used a comma
+        // separated string.
+        if let Some(tags) = watch.get("tags").and_then(JsonValue::as_array) {
+            url_config.tags = tags
+                .iter()
+                .filter_map(JsonValue::as_str)
+                .map(|tag| {
+                    tag_titles
+                        .and_then(|titles| titles.get(tag))
+                        .and_then(|tag| tag.get("title"))
+                        .and_then(JsonValue::as_str)
+                        .unwrap_or(tag)
+                        .to_owned()
+                })
+                .collect();
+        } else if let Some(tags) = string("tag") {
+            url_config.tags = tags
+                .split(',')
+                .map(str::trim)
+                .filter(|tag| !tag.is_empty())
+                .map(str::to_owned)
+                .collect();
+        }
+
+        if let Some(headers) =
+            watch.get("headers").and_then(JsonValue::as_object)
+        {
+            url_config.headers = headers
+                .iter()
+                .filter_map(|(name, value)| {
+                    Some((name.clone(), value.as_str()?.to_owned().into()))
+                })
+                .collect();
+        }
+
+        if let Some(filters) =
+            watch.get("include_filters").and_then(JsonValue::as_array)
+        {
+            include_filters(
+                &mut import.warnings,
+                label,
+                &mut url_config,
+                filters.iter().filter_map(JsonValue::as_str),
+            );
+        }
+        if watch.get("time_between_check_use_default")
+            != Some(&JsonValue::Bool(true))
+        {
+            url_config.interval = watch
+                .get("time_between_check")
+                .and_then(JsonValue::as_object)
+                .and_then(interval);
+        }
+        if string("fetch_backend").is_some_and(|backend| {
+            backend == "html_webdriver" || backend.starts_with("extra_browser_")
+        }) {
+            import.warnings.push(format!(
+                "{label}: JavaScript is not supported; fetching the page \
+                without a browser",
+            ));
+        }
+        if let Some(method) = string("method") {
+            url_config.method =
+                convert_method(&mut import.warnings, label, method);
+        }
+
+        import.urls.push(url_config);
+    }
+
+    Ok(import)
+}
+
+/// Convert a urlwatch `filter` to `filters` steps.
+///
+/// The filter is either a comma separated string, e.g. `css:main,html2text`,
+/// or a list of filters, each either a string or a map from the name of the
+/// filter to its argument. Both apply the filters in order, like `filters`.
+fn urlwatch_filters(
+    warnings: &mut Vec<String>,
+    label: &str,
+    filter: &YamlValue,
+) -> Pipeline {
+    let filters: Vec<(String, Option<&YamlValue>)> = match filter {
+        YamlValue::String(filters) => filters
+            .split(',')
+            .map(|filter| (filter.to_owned(), None))
+            .collect(),
+        YamlValue::Sequence(filters) => filters
+            .iter()
+            .filter_map(|filter| match filter {
+                YamlValue::String(filter) => Some((filter.clone(), None)),
+                YamlValue::Mapping(filter) => {
+                    filter.iter().next().and_then(|(name, arg)| {
+                        Some((name.as_str()?.to_owned(), Some(arg)))
+                    })
+                }
+                _ => None,
+            })
+            .collect(),
+        _ => Vec::new(),
+    };
+
+    let mut steps = Vec::new();
+    for (filter, args) in filters {
+        let (name, inline) = filter
+            .split_once(':')
+            .map_or((filter.as_str(), None), |(name, arg)| (name, Some(arg)));
+        // Arguments in maps are either a string or a map of named arguments.
+        let arg = |key| {
+            inline.map(str::to_owned).or_else(|| {
+                args.and_then(|args| args.get(key).unwrap_or(args).as_str())
+                    .map(str::to_owned)
+            })
+        };
+        let step = match name.trim() {
+            "html2text" | "strip" => continue, // Rendering already does this.
+            "css" => arg("selector").map(|css| format!("selector:{css}")),
+            "xpath" => arg("path").map(|xpath| format!("xpath:{xpath}")),
+            "re.sub"
+                if args.and_then(|args| args.get("repl")).is_none_or(
+                    |repl| repl.as_str().is_some_and(str::is_empty),
+                ) =>
+            {
+                arg("pattern").map(|regex| format!("strip-regex:{regex}"))
+            }
+            _ => None,
+        };
+        match step {
+            Some(step) => steps.push(step),
+            None => warnings.push(format!(
+                "{label}: ignoring unsupported filter {:?}",
+                filter.trim(),
+            )),
+        }
+    }
+
+    Pipeline::try_from(steps).unwrap_or_else(|error| {
+        warnings.push(format!("{label}: ignoring filters: {error}"));
+        Pipeline::new()
+    })
+}
+
+/// Convert changedetection.io `include_filters`, which keep everything that
+/// matches any of a list of CSS selectors and `XPath` expressions.
+fn include_filters<'a>(
+    warnings: &mut Vec<String>,
+    label: &str,
+    url_config: &mut UrlConfig,
+    filters: impl Iterator<Item = &'a str>,
+) {
+    let mut css = Vec::new();
+    let mut xpath = Vec::new();
+    for filter in filters {
+        if let Some(expression) = filter
+            .strip_prefix("xpath:")
+            .or_else(|| filter.strip_prefix("xpath1:"))
+        {
+            xpath.push(expression);
+        } else if filter.starts_with('/') {
+            xpath.push(filter);
+        } else if filter.starts_with("json:") || filter.starts_with("jq") {
+            warnings.push(format!(
+                "{label}: ignoring unsupported JSON filter {filter:?}"
+            ));
+        } else {
+            css.push(filter);
+        }
+    }
+
+    if !css.is_empty() {
+        url_config.select = Some(css.join(", "));
+        if !xpath.is_empty() {
+            warnings.push(format!(
+                "{label}: ignoring XPath filters {xpath:?}, which can’t be \
+                combined with CSS filters",
+            ));
+        }
+    } else if !xpath.is_empty() {
+        url_config.xpath = Some(xpath.join(" | "));
+    }
+}
+
+/// Convert a changedetection.io `time_between_check`, e.g. `{"hours": 3}`.
+///
+/// Returns `None` if it’s zero, which means to use the default.
+fn interval(time: &serde_json::Map<String, JsonValue>) -> Option<Duration> {
+    let units = [
+        ("weeks", 604_800),
+        ("days", 86_400),
+        ("hours", 3_600),
+        ("minutes", 60),
+        ("seconds", 1),
+    ];
+    let seconds = units.iter().try_fold(0_u64, |total, (unit, seconds)| {
+        let count = time.get(*unit).and_then(JsonValue::as_u64).unwrap_or(0);
+        total.checked_add(count.checked_mul(*seconds)?)
+    })?;
+    (seconds > 0).then(|| Duration::from_secs(seconds))
+}
+
+/// Convert an HTTP method, adding a warning if it isn’t supported.
+fn convert_method(
+    warnings: &mut Vec<String>,
+    label: &str,
+    method: &str,
+) -> Option<Method> {
+    match method.to_uppercase().as_str() {
+        "GET" => None,
+        "HEAD" => Some(Method::Head),
+        other => {
+            warnings.push(format!(
+                "{label}: {other} requests are not supported; using GET"
+            ));
+            None
+        }
+    }
+}
+
+/// Parse a URL, adding a warning if it’s invalid.
+fn parse_url(
+    warnings: &mut Vec<String>,
+    label: &str,
+    url: &str,
+) -> Option<Url> {
+    url.parse()
+        .map_err(|error| {
+            warnings.push(format!("{label}: skipping invalid URL: {error}"));
+        })
+        .ok()
+}
+
+/// Make a warning about an unsupported setting.
+fn unsupported(label: &str, key: &str) -> String {
+    format!("{label}: ignoring unsupported setting {key:?}")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use assert2::check;
+
+    #[test]
+    fn test_from_urlwatch() {
+        let import = from_urlwatch(
+            "name: Example\n\
+            url: https://example.com/\n\
+            headers:\n  Accept-Language: fr\n\
+            filter: css:main,html2text\n\
+            method: HEAD\n\
+            max_tries: 3\n\
+            ---\n\
+            command: ls\n\
+            ---\n\
+            navigate: https://example.org/\n\
+            method: POST\n\
+            filter:\n\
+            - xpath: //div\n\
+            - re.sub: '\\d+'\n\
+            - re.sub: {pattern: a, repl: b}\n\
+            - shellpipe: sort\n",
+        )
+        .unwrap();
+
+        check!(import.urls.len() == 2);
+        check!(import.urls[0].name.as_deref() == Some("Example"));
+        check!(
+            import.urls[0].headers["Accept-Language"].expose().unwrap() == "fr"
+        );
+        check!(
+            serde_json::to_string(&import.urls[0].filters).unwrap()
+                == "[\"selector:main\"]"
+        );
+        check!(import.urls[0].method == Some(Method::Head));
+        check!(import.urls[1].url.as_str() == "https://example.org/");
+        check!(
+            serde_json::to_string(&import.urls[1].filters).unwrap()
+                == r#"["xpath://div","strip-regex:\\d+"]"#
+        );
+        check!(import.urls[1].method == None);
+        check!(
+            import.warnings
+                == [
+                    "Example: ignoring unsupported setting \"max_tries\"",
+                    "ls: skipping job that isn’t a URL",
+                    "https://example.org/: JavaScript is not supported; \
+                    fetching the page without a browser",
+                    "https://example.org/: ignoring unsupported filter \
+                    \"re.sub\"",
+                    "https://example.org/: ignoring unsupported filter \
+                    \"shellpipe\"",
+                    "https://example.org/: POST requests are not supported; \
+                    using GET",
+                ]
+        );
+    }
+
+    #[test]
+    fn test_from_changedetection() {
+        let import = from_changedetection(
+            r##"{
+                "settings": {"application": {"tags": {
+                    "1234": {"title": "work"}
+                }}},
+                "watching": {
+                    "a": {
+                        "url": "https://example.com/",
+                        "title": "Example",
+                        "tags": ["1234"],
+                        "headers": {"X-Foo": "bar"},
+                        "include_filters": ["#main", ".price"],
+                        "time_between_check": {"hours": 3, "minutes": null},
+                        "method": "HEAD"
+                    },
+                    "b": {
+                        "url": "https://example.org/",
+                        "tag": "a, b",
+                        "include_filters": ["xpath://main", "/html/body/p"],
+                        "time_between_check": {"hours": 3},
+                        "time_between_check_use_default": true,
+                        "method": "POST"
+                    },
+                    "c": {"url": "https://example.net/", "paused": true}
+                }
+            }"##,
+        )
+        .unwrap();
+
+        check!(import.urls.len() == 2);
+        check!(import.urls[0].name.as_deref() == Some("Example"));
+        check!(import.urls[0].tags == ["work"]);
+        check!(import.urls[0].headers["X-Foo"].expose().unwrap() == "bar");
+        check!(import.urls[0].select.as_deref() == Some("#main, .price"));
+        check!(import.urls[0].interval == Some(Duration::from_hours(3)));
+        check!(import.urls[0].method == Some(Method::Head));
+        check!(import.urls[1].tags == ["a", "b"]);
+        check!(
+            import.urls[1].xpath.as_deref() == Some("//main | /html/body/p")
+        );
+        check!(import.urls[1].interval == None);
+        check!(import.urls[1].method == None);
+        check!(
+            import.warnings
+                == [
+                    "https://example.org/: POST requests are not supported; \
+                    using GET",
+                    "https://example.net/: skipping paused watch",
+                ]
+        );
+    }
+}
@@ -0,0 +1,72 @@
+//! The `list` subcommand.
+
+use chrono::Local;
+use std::io::{self, Write};
+use std::process::ExitCode;
+
+use crate::params::Params;
+use crate::state::State;
+
+/// Print the configured URLs with how reliably they’ve been checked.
+///
+/// # Errors
+///
+/// Returns an error if the configuration couldn’t be loaded or the output
+/// couldn’t be written.
+pub fn run(params: &Params) -> anyhow::Result<ExitCode> {
+    let config = params.load_config()?;
+    let state = State::new(params.state_dir_path());
+    let mut out = io::stdout().lock();
+    for url_config in params.url_configs(&config) {
+        let history = state
+            .load_history(&url_config.url, true)
+            .unwrap_or_else(|error| {
+                tracing::warn!(
+                    "Could not read history for {}: {error:#}",
+                    url_config.label(),
+                );
+                None
+            })
+            .unwrap_or_default();
+
+        writeln!(out, "{}", url_config.label())?;
+        if url_config.name.is_some() {
+            writeln!(out, "  {}", url_config.url)?;
+        }
+        let last_checked = history.last_checked().map_or_else(
+            || "never".to_owned(),
+            |time| {
+                time.with_timezone(&Local)
+                    .format("%Y-%m-%d %H:%M:%S")
+                    .to_string()
+            },
+        );
+        writeln!(out, "  Last checked: {last_checked}")?;
+
+        let reliability = history.reliability;
+        if let Some(uptime) = reliability.uptime() {
+            writeln!(
+                out,
+                "  Uptime: {uptime:.1}% of {} checks",
+                reliability.checks,
+            )?;
+        }
+        if reliability.consecutive_errors > 0 {
+            writeln!(
+                out,
+                "  Failing: {} checks in a row",
+                reliability.consecutive_errors,
+            )?;
+        }
+        if let Some(disabled_at) = reliability.disabled_at {
+            writeln!(
+                out,
+                "  Disabled: {} for failing too long",
+                disabled_at
+                    .with_timezone(&Local)
+                    .format("%Y-%m-%d %H:%M:%S"),
+            )?;
+        }
+    }
+    Ok(ExitCode::SUCCESS)
+}
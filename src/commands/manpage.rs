@@ -0,0 +1,18 @@
+//! The `manpage` subcommand.
+
+use clap::CommandFactory;
+use std::io;
+use std::process::ExitCode;
+
+use crate::params::Params;
+
+/// Print a man page generated from the command line options.
+///
+/// # Errors
+///
+/// Returns an error if stdout couldn’t be written.
+pub fn run() -> anyhow::Result<ExitCode> {
+    clap_mangen::Man::new(Params::command())
+        .render(&mut io::stdout().lock())?;
+    Ok(ExitCode::SUCCESS)
+}
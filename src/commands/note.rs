@@ -0,0 +1,59 @@
+//! The `note` subcommand.
+
+use anyhow::anyhow;
+use std::process::ExitCode;
+
+use crate::params::{NoteParams, Params};
+use crate::state::State;
+
+/// Add, show, or delete the note on a stored response.
+///
+/// # Errors
+///
+/// Returns an error if there is no stored response for the URL, if the
+/// revision doesn’t exist, or if the history couldn’t be saved.
+pub fn run(params: &Params, note: &NoteParams) -> anyhow::Result<ExitCode> {
+    let state = State::new(params.state_dir_path());
+    let url = &note.url;
+    let mut history = state
+        .load_history(url, params.dry_run)?
+        .ok_or_else(|| anyhow!("No stored responses for {url}"))?;
+    let index = history.index(note.rev)?;
+    let rev = index.saturating_add(1);
+    let Some(revision) = history.revisions.get_mut(index) else {
+        return Err(anyhow!("No stored responses for {url}"));
+    };
+
+    let message = if note.delete {
+        if revision.note.take().is_none() {
+            println!("No note on revision {rev} of {url}");
+            return Ok(ExitCode::SUCCESS);
+        }
+        format!("Deleted the note on revision {rev} of {url}")
+    } else if let Some(text) = &note.text {
+        revision.note = Some(text.clone());
+        format!("Noted revision {rev} of {url}")
+    } else {
+        match &revision.note {
+            Some(text) => println!("{text}"),
+            None => println!("No note on revision {rev} of {url}"),
+        }
+        return Ok(ExitCode::SUCCESS);
+    };
+
+    // Save under the URL of the latest response, like after checking the
+    // URL, so that redirects still point to it.
+    let Some(latest) = history.latest() else {
+        return Ok(ExitCode::SUCCESS);
+    };
+    if params.dry_run {
+        eprintln!(
+            "Dry run: would save {}",
+            state.history_path(&latest.url).display(),
+        );
+    } else {
+        state.save_history(&latest.url, &history)?;
+        println!("{message}");
+    }
+    Ok(ExitCode::SUCCESS)
+}
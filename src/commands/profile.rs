@@ -0,0 +1,42 @@
+//! The `profile` subcommand.
+
+use std::process::ExitCode;
+
+use crate::params::{Params, ProfileCommand, default_dir};
+use crate::profile;
+
+/// Manage profiles.
+///
+/// # Errors
+///
+/// Returns an error if the profiles couldn’t be listed.
+pub fn run(
+    params: &Params,
+    command: &ProfileCommand,
+) -> anyhow::Result<ExitCode> {
+    match command {
+        ProfileCommand::List => {
+            let default_dir = default_dir();
+            let names = profile::list(&default_dir)?;
+            if names.is_empty() {
+                params.warn(format!(
+                    "No profiles in {}; create one by running with \
+                    --profile NAME\n",
+                    profile::profiles_dir(&default_dir).display(),
+                ))?;
+            }
+            for name in names {
+                let current = if params.profile.as_ref() == Some(&name) {
+                    " (current)"
+                } else {
+                    ""
+                };
+                println!(
+                    "{name}{current}\t{}",
+                    profile::dir(&default_dir, &name).display()
+                );
+            }
+        }
+    }
+    Ok(ExitCode::SUCCESS)
+}
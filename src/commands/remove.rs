@@ -0,0 +1,65 @@
+//! The `remove` subcommand.
+
+use std::process::ExitCode;
+
+use crate::config_file::ConfigFile;
+use crate::params::{Params, RemoveParams};
+use crate::state::State;
+
+/// Remove URLs from the configuration file and delete their history.
+///
+/// URLs that aren’t in the file are skipped with a warning, but their history
+/// is still deleted.
+///
+/// # Errors
+///
+/// Returns an error if the configuration file couldn’t be read or written, or
+/// the history couldn’t be deleted.
+pub fn run(params: &Params, remove: &RemoveParams) -> anyhow::Result<ExitCode> {
+    let mut file = ConfigFile::open(&params.config_path())?;
+    let mut exit_code = ExitCode::SUCCESS;
+    let mut removed = Vec::new();
+    for url in &remove.urls {
+        if file.remove(url) {
+            removed.push(url);
+        } else {
+            params
+                .warn(format!("{url} is not in {}\n", file.path().display()))?;
+            exit_code = ExitCode::FAILURE;
+        }
+    }
+
+    let path = file.path().display();
+    let state = State::new(params.state_dir_path());
+    if params.dry_run {
+        for url in removed {
+            eprintln!("Dry run: would remove {url} from {path}");
+        }
+        if !remove.keep_history {
+            for url in &remove.urls {
+                eprintln!(
+                    "Dry run: would delete the history for {url} in {}",
+                    state.dir().display(),
+                );
+            }
+        }
+        return Ok(exit_code);
+    }
+
+    if !removed.is_empty() {
+        file.save()?;
+    }
+    for url in removed {
+        println!("Removed {url} from {path}");
+    }
+    if !remove.keep_history {
+        for url in &remove.urls {
+            for deleted in
+                state.remove_history(url, |url| file.contains(url))?
+            {
+                println!("Deleted {}", deleted.display());
+            }
+        }
+    }
+    Ok(exit_code)
+}
@@ -0,0 +1,55 @@
+//! The `restore` subcommand.
+//!
+//! This restores the configuration file and state directory from an archive
+//! written by the `export` subcommand.
+
+use anyhow::Context;
+use std::fs;
+use std::io;
+use std::process::ExitCode;
+
+use crate::archive;
+use crate::params::{Params, RestoreParams};
+
+/// Restore the configuration file and state directory from an archive.
+///
+/// # Errors
+///
+/// Returns an error if the archive couldn’t be read or restored.
+pub fn run(
+    params: &Params,
+    restore: &RestoreParams,
+) -> anyhow::Result<ExitCode> {
+    let path = &restore.archive;
+    let state_dir = params.state_dir_path();
+    let config_path = params.config_path();
+    if params.dry_run {
+        eprintln!(
+            "Dry run: would restore {} to {} and {}",
+            path.display(),
+            config_path.display(),
+            state_dir.display(),
+        );
+        return Ok(ExitCode::SUCCESS);
+    }
+
+    let file = fs::File::open(path)
+        .with_context(|| format!("could not read {}", path.display()))?;
+    let restored = archive::extract(
+        io::BufReader::new(file),
+        &state_dir,
+        &config_path,
+        restore.overwrite,
+    )
+    .with_context(|| format!("could not restore {}", path.display()))?;
+
+    if restored.config {
+        eprintln!("Restored {}", config_path.display());
+    }
+    eprintln!(
+        "Restored {} files to {}",
+        restored.files,
+        state_dir.display()
+    );
+    Ok(ExitCode::SUCCESS)
+}
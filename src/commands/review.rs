@@ -0,0 +1,392 @@
+//! The `review` subcommand.
+
+use anyhow::{anyhow, bail};
+use chrono::{DateTime, Local, Utc};
+use ratatui::crossterm::event::{self, Event, KeyCode, KeyEventKind};
+use ratatui::layout::{Constraint, Layout};
+use ratatui::style::{Color, Style, Stylize};
+use ratatui::text::{Line, Text};
+use ratatui::widgets::{Block, List, ListItem, ListState, Paragraph};
+use ratatui::{DefaultTerminal, Frame};
+use std::io::{self, IsTerminal, Write};
+use std::process::{Command, ExitCode, Stdio};
+use url::Url;
+
+use crate::config::UrlConfig;
+use crate::params::{Params, ReviewParams};
+use crate::review::{acknowledge, render_diff, unreviewed};
+use crate::state::State;
+
+/// Help shown at the bottom of the screen.
+const KEYS: &str = "↑/↓ select  j/k/PgUp/PgDn scroll  a acknowledge  \
+    o open in browser  q quit";
+
+/// A URL with changes to review.
+#[derive(Debug)]
+struct Entry {
+    /// The URL’s configuration.
+    url_config: UrlConfig,
+
+    /// Number of times the content changed since it was last reviewed.
+    count: usize,
+
+    /// When the content last reviewed was fetched.
+    since: DateTime<Utc>,
+
+    /// When the latest response was fetched. This is what’s recorded when
+    /// the changes are acknowledged.
+    latest_at: DateTime<Utc>,
+
+    /// The diff from the content last reviewed to the latest content.
+    diff: Vec<String>,
+
+    /// Whether the changes have been acknowledged.
+    acknowledged: bool,
+}
+
+/// Review changes since they were last acknowledged.
+///
+/// # Errors
+///
+/// Returns an error if the configuration couldn’t be loaded, or if the
+/// terminal or output couldn’t be written.
+pub fn run(params: &Params, review: &ReviewParams) -> anyhow::Result<ExitCode> {
+    let config = params.load_config()?;
+    let state = State::new(params.state_dir_path());
+    let mut entries = Vec::new();
+    for url_config in params.url_configs(&config) {
+        if let Some(entry) = load_entry(params, &state, url_config) {
+            entries.push(entry);
+        }
+    }
+
+    if review.list {
+        print_list(&entries)?;
+        return Ok(ExitCode::SUCCESS);
+    }
+    if entries.is_empty() {
+        println!("No changes to review.");
+        return Ok(ExitCode::SUCCESS);
+    }
+    if !io::stdout().is_terminal() {
+        bail!("review must be run in a terminal; pass --list to print changes");
+    }
+
+    let mut app = App::new(params, &state, entries);
+    let mut terminal = ratatui::init();
+    let result = app.run(&mut terminal);
+    ratatui::restore();
+    result?;
+
+    let acknowledged = app
+        .entries
+        .iter()
+        .filter(|entry| entry.acknowledged)
+        .count();
+    println!("Acknowledged {acknowledged} of {} URLs.", app.entries.len());
+    Ok(ExitCode::SUCCESS)
+}
+
+/// Load the unreviewed changes to a URL, if there are any.
+///
+/// Problems are logged rather than returned, so that one URL can’t keep the
+/// others from being reviewed.
+fn load_entry(
+    params: &Params,
+    state: &State,
+    url_config: UrlConfig,
+) -> Option<Entry> {
+    let history =
+        state
+            .load_history(&url_config.url, true)
+            .unwrap_or_else(|error| {
+                tracing::warn!(
+                    "Could not read history for {}: {error:#}",
+                    url_config.label(),
+                );
+                None
+            })?;
+    let changes = unreviewed(&history)?;
+    let diff =
+        render_diff(params, state, &url_config, changes.base, changes.latest)
+            .unwrap_or_else(|error| {
+                vec![format!("Could not render: {error:#}")]
+            });
+    Some(Entry {
+        count: changes.count,
+        since: changes.base.fetched_at,
+        latest_at: changes.latest.fetched_at,
+        diff,
+        acknowledged: false,
+        url_config,
+    })
+}
+
+/// Print the URLs with unreviewed changes.
+///
+/// # Errors
+///
+/// Returns an error if the output couldn’t be written.
+fn print_list(entries: &[Entry]) -> io::Result<()> {
+    let mut out = io::stdout().lock();
+    for entry in entries {
+        writeln!(out, "{}", entry.url_config.label())?;
+        if entry.url_config.name.is_some() {
+            writeln!(out, "  {}", entry.url_config.url)?;
+        }
+        let noun = if entry.count == 1 {
+            "change"
+        } else {
+            "changes"
+        };
+        writeln!(
+            out,
+            "  {} {noun} since {}",
+            entry.count,
+            format_time(entry.since),
+        )?;
+    }
+    Ok(())
+}
+
+/// Format a time in the local time zone.
+fn format_time(time: DateTime<Utc>) -> String {
+    time.with_timezone(&Local)
+        .format("%Y-%m-%d %H:%M:%S")
+        .to_string()
+}
+
+/// Open a URL in the default browser.
+///
+/// # Errors
+///
+/// Returns an error if the browser couldn’t be started.
+fn open_in_browser(url: &Url) -> anyhow::Result<()> {
+    let program = if cfg!(target_os = "macos") {
+        "open"
+    } else {
+        "xdg-open"
+    };
+    let status = Command::new(program)
+        .arg(url.as_str())
+        .stdin(Stdio::null())
+        .stdout(Stdio::null())
+        .stderr(Stdio::null())
+        .status()
+        .map_err(|error| anyhow!("Could not run {program}: {error}"))?;
+    if !status.success() {
+        bail!("{program} failed: {status}");
+    }
+    Ok(())
+}
+
+/// State of the review screen.
+struct App<'a> {
+    /// Global parameters.
+    params: &'a Params,
+
+    /// Where histories are stored.
+    state: &'a State,
+
+    /// URLs with changes to review.
+    entries: Vec<Entry>,
+
+    /// Which entry is selected.
+    list: ListState,
+
+    /// How many lines the diff is scrolled down.
+    scroll: u16,
+
+    /// Height of the diff the last time it was drawn, for scrolling by
+    /// pages.
+    page: u16,
+
+    /// Message shown above the key help, e.g. after an error.
+    message: String,
+
+    /// Whether to exit.
+    quit: bool,
+}
+
+impl<'a> App<'a> {
+    /// Start reviewing `entries`.
+    fn new(params: &'a Params, state: &'a State, entries: Vec<Entry>) -> Self {
+        Self {
+            params,
+            state,
+            entries,
+            list: ListState::default().with_selected(Some(0)),
+            scroll: 0,
+            page: 0,
+            message: String::new(),
+            quit: false,
+        }
+    }
+
+    /// Draw the screen and handle keys until the user quits.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if the terminal couldn’t be used.
+    fn run(&mut self, terminal: &mut DefaultTerminal) -> io::Result<()> {
+        while !self.quit {
+            terminal.draw(|frame| self.draw(frame))?;
+            if let Event::Key(key) = event::read()?
+                && key.kind == KeyEventKind::Press
+            {
+                self.handle_key(key.code);
+            }
+        }
+        Ok(())
+    }
+
+    /// Get the selected entry.
+    fn selected(&self) -> Option<&Entry> {
+        self.entries.get(self.list.selected()?)
+    }
+
+    /// Draw the screen.
+    fn draw(&mut self, frame: &mut Frame<'_>) {
+        let [main, message, keys] = Layout::vertical([
+            Constraint::Min(1),
+            Constraint::Length(1),
+            Constraint::Length(1),
+        ])
+        .areas(frame.area());
+        let [list_area, diff_area] = Layout::horizontal([
+            Constraint::Percentage(30),
+            Constraint::Percentage(70),
+        ])
+        .areas(main);
+
+        let items: Vec<ListItem<'_>> = self
+            .entries
+            .iter()
+            .map(|entry| {
+                let label = entry.url_config.label();
+                if entry.acknowledged {
+                    ListItem::new(format!("✓ {label}")).dim()
+                } else {
+                    ListItem::new(format!("  {label} ({})", entry.count))
+                }
+            })
+            .collect();
+        let list = List::new(items)
+            .block(Block::bordered().title("Changes"))
+            .highlight_style(Style::new().reversed());
+        frame.render_stateful_widget(list, list_area, &mut self.list);
+
+        self.page = diff_area.height.saturating_sub(2);
+        if let Some(entry) = self.selected() {
+            let lines: Vec<Line<'_>> = entry
+                .diff
+                .iter()
+                .map(|line| match line.chars().next() {
+                    Some('-') => Line::styled(line.as_str(), Color::Red),
+                    Some('+') => Line::styled(line.as_str(), Color::Green),
+                    _ => Line::raw(line.as_str()),
+                })
+                .collect();
+            let noun = if entry.count == 1 {
+                "change"
+            } else {
+                "changes"
+            };
+            let title = format!(
+                "{} — {} {noun} since {}",
+                entry.url_config.url,
+                entry.count,
+                format_time(entry.since),
+            );
+            let diff = Paragraph::new(Text::from(lines))
+                .block(Block::bordered().title(title))
+                .scroll((self.scroll, 0));
+            frame.render_widget(diff, diff_area);
+        }
+
+        frame.render_widget(Line::raw(self.message.as_str()), message);
+        frame.render_widget(Line::raw(KEYS).dim(), keys);
+    }
+
+    /// Handle a key press.
+    fn handle_key(&mut self, code: KeyCode) {
+        match code {
+            KeyCode::Char('q') | KeyCode::Esc => self.quit = true,
+            KeyCode::Up => self.select(ListState::select_previous),
+            KeyCode::Down => self.select(ListState::select_next),
+            KeyCode::Char('k') => self.scroll = self.scroll.saturating_sub(1),
+            KeyCode::Char('j') => self.scroll_down(1),
+            KeyCode::PageUp => {
+                self.scroll = self.scroll.saturating_sub(self.page);
+            }
+            KeyCode::PageDown | KeyCode::Char(' ') => {
+                self.scroll_down(self.page);
+            }
+            KeyCode::Home => self.scroll = 0,
+            KeyCode::Char('a') => {
+                self.message = match self.acknowledge() {
+                    Ok(message) => message,
+                    Err(error) => format!("Error: {error:#}"),
+                };
+            }
+            KeyCode::Char('o') => {
+                if let Some(entry) = self.selected() {
+                    self.message = match open_in_browser(&entry.url_config.url)
+                    {
+                        Ok(()) => format!("Opened {}", entry.url_config.url),
+                        Err(error) => format!("Error: {error:#}"),
+                    };
+                }
+            }
+            _ => {}
+        }
+    }
+
+    /// Change the selected entry, and scroll to the top of its diff.
+    fn select(&mut self, change: impl FnOnce(&mut ListState)) {
+        change(&mut self.list);
+        self.scroll = 0;
+    }
+
+    /// Scroll the diff down, but not past its end.
+    fn scroll_down(&mut self, lines: u16) {
+        let len = self.selected().map_or(0, |entry| entry.diff.len());
+        let max = u16::try_from(len).unwrap_or(u16::MAX).saturating_sub(1);
+        self.scroll = self.scroll.saturating_add(lines).min(max);
+    }
+
+    /// Acknowledge the changes to the selected URL, and select the next one.
+    ///
+    /// The history is loaded again in case it was updated by a check while
+    /// reviewing.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if the history couldn’t be loaded or saved.
+    fn acknowledge(&mut self) -> anyhow::Result<String> {
+        let Some(index) = self.list.selected() else {
+            return Ok(String::new());
+        };
+        let Some(entry) = self.entries.get_mut(index) else {
+            return Ok(String::new());
+        };
+        let url = &entry.url_config.url;
+        let mut history = self
+            .state
+            .load_history(url, self.params.dry_run)?
+            .ok_or_else(|| anyhow!("No stored responses for {url}"))?;
+        acknowledge(&mut history, entry.latest_at);
+
+        let message = if self.params.dry_run {
+            format!("Dry run: would acknowledge changes to {url}")
+        } else {
+            if let Some(latest) = history.latest() {
+                self.state.save_history(&latest.url, &history)?;
+            }
+            format!("Acknowledged changes to {url}")
+        };
+        entry.acknowledged = true;
+        self.select(ListState::select_next);
+        Ok(message)
+    }
+}
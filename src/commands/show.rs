@@ -0,0 +1,77 @@
+//! The `show` subcommand.
+
+use anyhow::{anyhow, bail};
+use std::fs;
+use std::io::{self, Write};
+use std::process::ExitCode;
+
+use crate::extract::Filter;
+use crate::params::{Params, ShowParams};
+use crate::render::{TableMode, render};
+use crate::state::State;
+
+/// Print a stored response without fetching it.
+///
+/// # Errors
+///
+/// Returns an error if there is no stored response for the URL, or if it
+/// couldn’t be read or rendered.
+pub fn run(params: &Params, show: &ShowParams) -> anyhow::Result<ExitCode> {
+    let state = State::new(params.state_dir_path());
+    let history = state
+        .load_history(&show.url, true)?
+        .ok_or_else(|| anyhow!("No stored responses for {}", show.url))?;
+
+    let revision = history
+        .revisions
+        .get(history.index(show.rev)?)
+        .ok_or_else(|| anyhow!("No stored responses for {}", show.url))?;
+
+    if let Some(note) = &revision.note {
+        eprintln!("Note: {note}");
+    }
+
+    let mut out = io::stdout().lock();
+    if show.format.headers {
+        write!(
+            out,
+            "{:?} {}  [fetched {}",
+            revision.version,
+            revision.status,
+            revision.fetched_at.to_rfc3339(),
+        )?;
+        if let Some(address) = revision.peer_address {
+            write!(out, " from {address}")?;
+        }
+        writeln!(out, "]")?;
+        for (name, value) in &revision.headers {
+            writeln!(
+                out,
+                "{name}: {}",
+                String::from_utf8_lossy(value.as_bytes())
+            )?;
+        }
+    } else {
+        let response = state.load_response(revision, params.max_body_memory)?;
+        if show.format.raw {
+            if let Some(spooled) = &response.spooled {
+                io::copy(&mut fs::File::open(spooled.path())?, &mut out)?;
+            } else {
+                out.write_all(&response.body)?;
+            }
+        } else if response.spooled.is_some() {
+            bail!(
+                "Body is {} bytes, too large to render; pass --raw",
+                response.body_len(),
+            );
+        } else {
+            writeln!(
+                out,
+                "{}",
+                render(&response, TableMode::default(), Filter::default())?
+            )?;
+        }
+    }
+
+    Ok(ExitCode::SUCCESS)
+}
@@ -0,0 +1,114 @@
+//! The `values` subcommand.
+
+use anyhow::{anyhow, bail};
+use chrono::Local;
+use std::collections::BTreeSet;
+use std::io::{self, Write};
+use std::iter;
+use std::process::ExitCode;
+
+use crate::params::{Params, ValuesParams};
+use crate::state::State;
+use crate::values::Value;
+
+/// Print the values extracted from each stored response for a URL.
+///
+/// # Errors
+///
+/// Returns an error if there is no stored response for the URL, a requested
+/// value was never extracted, or the output couldn’t be written.
+pub fn run(params: &Params, values: &ValuesParams) -> anyhow::Result<ExitCode> {
+    let state = State::new(params.state_dir_path());
+    let history = state
+        .load_history(&values.url, true)?
+        .ok_or_else(|| anyhow!("No stored responses for {}", values.url))?;
+
+    let found = history
+        .revisions
+        .iter()
+        .flat_map(|revision| revision.values.keys())
+        .collect::<BTreeSet<_>>();
+    let names = if values.name.is_empty() {
+        found.into_iter().cloned().collect::<Vec<_>>()
+    } else {
+        if let Some(missing) =
+            values.name.iter().find(|name| !found.contains(name))
+        {
+            bail!(
+                "No value named {missing:?} was extracted from {}",
+                values.url
+            );
+        }
+        values.name.clone()
+    };
+    if names.is_empty() {
+        bail!("No values were extracted from {}", values.url);
+    }
+
+    let rows = history
+        .revisions
+        .iter()
+        .filter(|revision| {
+            names.iter().any(|name| revision.values.contains_key(name))
+        })
+        .map(|revision| {
+            let cells = names.iter().map(|name| {
+                revision.values.get(name).map_or_else(String::new, cell)
+            });
+            (revision.fetched_at, cells.collect::<Vec<_>>())
+        });
+
+    let mut out = io::stdout().lock();
+    if values.csv {
+        let mut writer = csv::Writer::from_writer(out);
+        writer.write_record(
+            iter::once("fetched_at").chain(names.iter().map(String::as_str)),
+        )?;
+        for (fetched_at, cells) in rows {
+            writer.write_record(
+                iter::once(fetched_at.to_rfc3339()).chain(cells),
+            )?;
+        }
+        writer.flush()?;
+        return Ok(ExitCode::SUCCESS);
+    }
+
+    let rows = rows.collect::<Vec<_>>();
+    let widths = names
+        .iter()
+        .enumerate()
+        .map(|(i, name)| {
+            rows.iter()
+                .filter_map(|(_, cells)| cells.get(i))
+                .map(|cell| cell.chars().count())
+                .chain([name.chars().count()])
+                .max()
+                .unwrap_or_default()
+        })
+        .collect::<Vec<_>>();
+    write!(out, "{:19}", "fetched")?;
+    for (name, width) in names.iter().zip(&widths) {
+        write!(out, "  {name:>width$}")?;
+    }
+    writeln!(out)?;
+    for (fetched_at, cells) in rows {
+        write!(
+            out,
+            "{}",
+            fetched_at.with_timezone(&Local).format("%Y-%m-%d %H:%M:%S"),
+        )?;
+        for (cell, width) in cells.iter().zip(&widths) {
+            write!(out, "  {cell:>width$}")?;
+        }
+        writeln!(out)?;
+    }
+    Ok(ExitCode::SUCCESS)
+}
+
+/// Format a value for a table cell.
+fn cell(value: &Value) -> String {
+    match value {
+        Value::Number(number) => number.to_string(),
+        Value::Text(text) => text.clone(),
+    }
+}
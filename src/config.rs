@@ -0,0 +1,792 @@
+//! Configuration file.
+
+use anyhow::Context;
+use serde::Deserialize as _;
+use std::collections::BTreeMap;
+use std::fs;
+use std::io;
+use std::path::Path;
+use std::time::Duration;
+use url::Url;
+
+use crate::audit::AuditConfig;
+use crate::backoff::Backoff;
+use crate::checksums::Checksums;
+use crate::credentials::{self, Credentials, Store};
+use crate::csv_diff::CsvConfig;
+use crate::dns::AddressFamily;
+use crate::filters::Pipeline;
+use crate::git::GitConfig;
+use crate::graphql::GraphQl;
+use crate::inline_json::InlineJson;
+use crate::notify::{Notifier, Schedule, Severity};
+use crate::oauth::OAuth2;
+use crate::paginate::Paginate;
+use crate::render::TableMode;
+use crate::request::Impersonate;
+use crate::schedule::Windows;
+use crate::secret::{Resolver, Secret, SecretsConfig};
+use crate::soft_error::SoftErrorConfig;
+use crate::stream::{self, Stream};
+use crate::tls::Tls;
+use crate::translate::Translate;
+use crate::tunnel::Destination;
+use crate::values::{Condition, ValueRule};
+use crate::vantage::{self, Vantage};
+
+/// Configuration loaded from a TOML file.
+#[derive(Debug, Default, serde::Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct Config {
+    /// URLs to monitor.
+    #[serde(default, rename = "url")]
+    pub urls: Vec<UrlConfig>,
+
+    /// Where to send notifications about changes.
+    #[serde(default, rename = "notifier")]
+    pub notifiers: Vec<Notifier>,
+
+    /// When to deliver notifications, unless overridden by a notifier.
+    #[serde(default)]
+    pub notify: Schedule,
+
+    /// Where to find the age-encrypted secrets file.
+    #[serde(default)]
+    pub secrets: Option<SecretsConfig>,
+
+    /// How to detect soft errors, like “not found” pages that return 200.
+    #[serde(default)]
+    pub soft_errors: SoftErrorConfig,
+
+    /// Where to keep a tamper-evident log of checks, if anywhere.
+    #[serde(default)]
+    pub audit_log: Option<AuditConfig>,
+
+    /// Git repository to commit rendered snapshots to, if any.
+    #[serde(default)]
+    pub git: Option<GitConfig>,
+
+    /// Proxies to fetch URLs through for comparison.
+    #[serde(default, rename = "vantage")]
+    pub vantages: Vec<Vantage>,
+
+    /// How to back off from URLs that keep failing, if at all.
+    #[serde(default)]
+    pub backoff: Option<Backoff>,
+
+    /// SSH destination to tunnel requests for every URL through, unless the
+    /// URL has its own `ssh_tunnel`.
+    #[serde(default)]
+    pub ssh_tunnel: Option<Destination>,
+
+    /// How to translate changes to every URL, unless the URL has its own
+    /// `translate`.
+    #[serde(default)]
+    pub translate: Option<Translate>,
+}
+
+impl Config {
+    /// Load configuration from a TOML file.
+    ///
+    /// If `required` is false and the file does not exist, this returns the
+    /// default (empty) configuration.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if the file could not be read or parsed.
+    pub fn load(path: &Path, required: bool) -> anyhow::Result<Self> {
+        let source = match fs::read_to_string(path) {
+            Ok(source) => source,
+            Err(error)
+                if !required && error.kind() == io::ErrorKind::NotFound =>
+            {
+                return Ok(Self::default());
+            }
+            Err(error) => {
+                return Err(error).with_context(|| {
+                    format!("could not read config {}", path.display())
+                });
+            }
+        };
+
+        let mut config: Self = toml::from_str(&source).with_context(|| {
+            format!("could not parse config {}", path.display())
+        })?;
+        vantage::validate(&config.vantages, &config.urls)
+            .with_context(|| format!("invalid config {}", path.display()))?;
+        for url_config in &mut config.urls {
+            if url_config.ssh_tunnel.is_none() {
+                url_config.ssh_tunnel.clone_from(&config.ssh_tunnel);
+            }
+            if url_config.translate.is_none() {
+                url_config.translate.clone_from(&config.translate);
+            }
+        }
+        let base_dir = path.parent().unwrap_or_else(|| Path::new("."));
+        let mut store = Store::load(&base_dir.join(credentials::FILE_NAME))?;
+        // Only resolve passwords that will be used.
+        store.hosts.retain(|credentials| {
+            config.urls.iter().any(|url_config| {
+                url_config.wants_credentials()
+                    && credentials.matches(&url_config.url)
+            })
+        });
+        config.resolve_secrets(base_dir, &mut store)?;
+        for url_config in &mut config.urls {
+            if url_config.wants_credentials() {
+                url_config.credentials = store.find(&url_config.url).cloned();
+            }
+        }
+        for tls in config.urls.iter_mut().filter_map(|url| url.tls.as_mut()) {
+            tls.resolve_paths(base_dir);
+        }
+        if let Some(audit_log) = &mut config.audit_log {
+            audit_log.resolve_paths(base_dir);
+        }
+        if let Some(git) = &mut config.git {
+            git.resolve_paths(base_dir);
+        }
+        Ok(config)
+    }
+
+    /// Resolve all secrets in the configuration and the credentials file.
+    ///
+    /// Relative paths in `[secrets]` are relative to `base_dir`.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if a secret couldn’t be resolved.
+    fn resolve_secrets(
+        &mut self,
+        base_dir: &Path,
+        store: &mut Store,
+    ) -> anyhow::Result<()> {
+        let mut resolver = Resolver::new(self.secrets.as_ref(), base_dir);
+        store.resolve_secrets(&mut resolver)?;
+        for url_config in &mut self.urls {
+            for (name, value) in &mut url_config.headers {
+                value.resolve(&mut resolver).with_context(|| {
+                    format!("header {name} for {}", url_config.url)
+                })?;
+            }
+            if let Some(oauth2) = &mut url_config.oauth2 {
+                oauth2.client_secret.resolve(&mut resolver).with_context(
+                    || format!("oauth2 client_secret for {}", url_config.url),
+                )?;
+            }
+            if let Some(password) = url_config
+                .tls
+                .as_mut()
+                .and_then(|tls| tls.password.as_mut())
+            {
+                password.resolve(&mut resolver).with_context(|| {
+                    format!("tls password for {}", url_config.url)
+                })?;
+            }
+        }
+        if let Some(password) = self
+            .audit_log
+            .as_mut()
+            .and_then(|audit_log| audit_log.password.as_mut())
+        {
+            password
+                .resolve(&mut resolver)
+                .context("audit_log password")?;
+        }
+        for notifier in &mut self.notifiers {
+            let name = notifier.name();
+            for secret in notifier.backend.secrets_mut() {
+                secret
+                    .resolve(&mut resolver)
+                    .with_context(|| format!("notifier {name}"))?;
+            }
+        }
+        Ok(())
+    }
+
+    /// Find the configuration for a URL, if there is one.
+    #[must_use]
+    pub fn find_url(&self, url: &Url) -> Option<&UrlConfig> {
+        self.urls.iter().find(|config| config.url == *url)
+    }
+}
+
+/// Configuration for a single URL.
+#[derive(Clone, Debug, serde::Deserialize, serde::Serialize)]
+#[serde(deny_unknown_fields)]
+#[expect(
+    clippy::struct_excessive_bools,
+    reason = "configuration options are naturally bools"
+)]
+pub struct UrlConfig {
+    /// The URL to monitor. This may also be an absolute path to a local file,
+    /// which is converted to a `file://` URL.
+    #[serde(deserialize_with = "deserialize_url")]
+    pub url: Url,
+
+    /// Human readable name to use in output instead of the URL.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub name: Option<String>,
+
+    /// Tags used to select subsets of URLs to check with `--tag`, and to route
+    /// notifications.
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub tags: Vec<String>,
+
+    /// How important changes to the URL are, for routing notifications.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub severity: Option<Severity>,
+
+    /// How to compare old and new versions of the page.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub diff_mode: Option<DiffMode>,
+
+    /// What to do the first time the URL is checked.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub on_new: Option<OnNew>,
+
+    /// Browser to impersonate when requesting the page.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub impersonate: Option<Impersonate>,
+
+    /// User agent to send when requesting the page.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub user_agent: Option<String>,
+
+    /// Skip the URL if it was checked successfully less than this long ago,
+    /// e.g. "6h". This is overridden by `--max-age`.
+    #[serde(
+        default,
+        with = "humantime_serde",
+        skip_serializing_if = "Option::is_none"
+    )]
+    pub interval: Option<Duration>,
+
+    /// Don’t notify about changes back to content that was seen less than
+    /// this long ago, e.g. "1d", so that pages that flap between versions
+    /// don’t cause a notification on every check.
+    #[serde(
+        default,
+        with = "humantime_serde",
+        skip_serializing_if = "Option::is_none"
+    )]
+    pub flap_window: Option<Duration>,
+
+    /// How long a removed page (404 Not Found or 410 Gone) may stay removed
+    /// and still be compared to its content from before when it comes back,
+    /// e.g. "30d". If it comes back later, its content is shown as new. By
+    /// default there’s no limit.
+    #[serde(
+        default,
+        with = "humantime_serde",
+        skip_serializing_if = "Option::is_none"
+    )]
+    pub restore_window: Option<Duration>,
+
+    /// Report a failure if the content hasn’t changed for this long, e.g. for
+    /// a status page that’s supposed to update regularly.
+    #[serde(
+        default,
+        with = "humantime_serde",
+        skip_serializing_if = "Option::is_none"
+    )]
+    pub stale_after: Option<Duration>,
+
+    /// Notify once the URL has failed to be fetched this many times in a row,
+    /// and again when it recovers.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub alert_after_failures: Option<u64>,
+
+    /// Names of `[[vantage]]` proxies to also fetch the URL through. If any of
+    /// them see different content, that’s reported.
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub vantages: Vec<String>,
+
+    /// SSH destination to tunnel requests through, e.g. `user@bastion`, for
+    /// pages that are only reachable from inside a network.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub ssh_tunnel: Option<Destination>,
+
+    /// When the URL may be checked, e.g. "Mon-Fri 09:00-18:00". Outside of
+    /// this, the URL is skipped.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub schedule: Option<Windows>,
+
+    /// CSS selector for the part of the page to monitor.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub select: Option<String>,
+
+    /// `XPath` expression for the part of the page to monitor, as an
+    /// alternative to `select`.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub xpath: Option<String>,
+
+    /// CSS selector for the items on a list-like page. Only new items are
+    /// reported; see [`crate::items`].
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub items_selector: Option<String>,
+
+    /// Compare JSON embedded in the page instead of its rendered HTML; see
+    /// [`crate::inline_json`].
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub inline_json: Option<InlineJson>,
+
+    /// Steps to normalize the content before comparing it, e.g.
+    /// `["selector:#main", "lowercase"]`.
+    #[serde(default, skip_serializing_if = "Pipeline::is_empty")]
+    pub filters: Pipeline,
+
+    /// Remove cookie banners and consent dialogs before comparing the page.
+    #[serde(default, skip_serializing_if = "std::ops::Not::not")]
+    pub strip_boilerplate: bool,
+
+    /// How to render tables.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub tables: Option<TableMode>,
+
+    /// Languages to ask for in the `Accept-Language` header, e.g. `"en-US"`.
+    ///
+    /// This overrides the header sent for `impersonate`.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub accept_language: Option<String>,
+
+    /// Ask for the response without compression, i.e. send
+    /// `Accept-Encoding: identity`.
+    #[serde(default, skip_serializing_if = "std::ops::Not::not")]
+    pub identity_encoding: bool,
+
+    /// The HTTP method to use. With `HEAD`, only the headers that show whether
+    /// a file changed (`Content-Length`, `Last-Modified`, and `ETag`) are
+    /// compared, and the body is never downloaded.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub method: Option<Method>,
+
+    /// Extra headers to send when requesting the page.
+    #[serde(default, skip_serializing_if = "BTreeMap::is_empty")]
+    pub headers: BTreeMap<String, Secret>,
+
+    /// Get an access token with OAuth 2.0 client credentials and send it with
+    /// the request.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub oauth2: Option<OAuth2>,
+
+    /// Credentials for the URL’s host from the credentials file; see
+    /// [`crate::credentials`].
+    #[serde(skip)]
+    pub credentials: Option<Credentials>,
+
+    /// Client certificate, CA certificates, and certificate verification.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub tls: Option<Tls>,
+
+    /// Only connect to the host over this address family.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub address_family: Option<AddressFamily>,
+
+    /// Follow “next page” links and monitor all the pages together.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub paginate: Option<Paginate>,
+
+    /// Follow redirects done with a `Refresh` header, a meta refresh tag, or
+    /// a simple script, like HTTP redirects.
+    #[serde(default, skip_serializing_if = "std::ops::Not::not")]
+    pub follow_refresh: bool,
+
+    /// Follow HTTP redirects. If this is false, the redirect response itself
+    /// is compared, so changes to where it goes are reported.
+    #[serde(default = "default_true", skip_serializing_if = "is_true")]
+    pub follow_redirects: bool,
+
+    /// Maximum number of HTTP redirects to follow; defaults to
+    /// [`crate::request::DEFAULT_MAX_REDIRECTS`].
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub max_redirects: Option<usize>,
+
+    /// Follow HTTP redirects to other origins. If this is false, redirects to
+    /// other origins are compared rather than followed.
+    #[serde(default = "default_true", skip_serializing_if = "is_true")]
+    pub follow_cross_origin_redirects: bool,
+
+    /// Send a GraphQL query to the URL instead of fetching it.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub graphql: Option<GraphQl>,
+
+    /// Monitor the artifacts linked from the page and their published
+    /// checksums, rather than the page itself.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub checksums: Option<Checksums>,
+
+    /// How to collect messages from a WebSocket or server-sent event stream.
+    /// `ws://` and `wss://` URLs are always streams; see [`crate::stream`].
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub stream: Option<Stream>,
+
+    /// How to compare CSV and TSV content row by row.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub csv: Option<CsvConfig>,
+
+    /// Report when the HTTP version of the response changes, e.g. from
+    /// HTTP/2 to HTTP/1.1.
+    #[serde(default, skip_serializing_if = "std::ops::Not::not")]
+    pub watch_http_version: bool,
+
+    /// Report when the addresses the host resolves to change.
+    #[serde(default, skip_serializing_if = "std::ops::Not::not")]
+    pub watch_dns: bool,
+
+    /// Report when the server’s TLS certificate changes.
+    #[serde(default, skip_serializing_if = "std::ops::Not::not")]
+    pub watch_certificate: bool,
+
+    /// Report when the `Content-Encoding` of the response changes, and when
+    /// the body is still compressed after decoding it.
+    #[serde(default, skip_serializing_if = "std::ops::Not::not")]
+    pub watch_encoding: bool,
+
+    /// Save a snapshot of the page to the Wayback Machine when it changes.
+    #[serde(default, skip_serializing_if = "std::ops::Not::not")]
+    pub wayback: bool,
+
+    /// Translate changes that aren’t in the target language before notifying
+    /// about them; see [`crate::translate`].
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub translate: Option<Translate>,
+
+    /// Response headers to compare, e.g. `Content-Security-Policy`. Other
+    /// headers are stored but not compared.
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub watch_headers: Vec<String>,
+
+    /// Detect responses that look like “not found” pages, login pages, or
+    /// parked domains, and report them instead of diffing them.
+    #[serde(default = "default_true", skip_serializing_if = "is_true")]
+    pub detect_soft_errors: bool,
+
+    /// Status codes the response is expected to have.
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub expect_status: Vec<u16>,
+
+    /// Text the page is expected to contain.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub expect_contains: Option<String>,
+
+    /// CSS selector the page is expected to have a match for.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub expect_selector: Option<String>,
+
+    /// Rules for extracting values, like a price or version, to store with
+    /// each response.
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub extract: Vec<ValueRule>,
+
+    /// Conditions on extracted values to notify about when they start to
+    /// hold, e.g. "price < 100".
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub alert: Vec<Condition>,
+
+    /// Ignore responses smaller than this many bytes, keeping the previous
+    /// snapshot.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub min_size: Option<u64>,
+
+    /// Ignore responses larger than this many bytes, keeping the previous
+    /// snapshot.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub max_size: Option<u64>,
+
+    /// Stop downloading responses larger than this many bytes and report an
+    /// error, rather than using up a metered connection.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub max_download_size: Option<u64>,
+
+    /// Ignore responses without a match for this CSS selector, keeping the
+    /// previous snapshot.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub require_selector: Option<String>,
+}
+
+impl UrlConfig {
+    /// Configuration for a URL that only appears on the command line.
+    #[must_use]
+    pub const fn new(url: Url) -> Self {
+        Self {
+            url,
+            name: None,
+            tags: Vec::new(),
+            severity: None,
+            diff_mode: None,
+            on_new: None,
+            impersonate: None,
+            user_agent: None,
+            interval: None,
+            schedule: None,
+            flap_window: None,
+            restore_window: None,
+            stale_after: None,
+            alert_after_failures: None,
+            vantages: Vec::new(),
+            ssh_tunnel: None,
+            select: None,
+            xpath: None,
+            items_selector: None,
+            inline_json: None,
+            filters: Pipeline::new(),
+            strip_boilerplate: false,
+            tables: None,
+            accept_language: None,
+            identity_encoding: false,
+            method: None,
+            headers: BTreeMap::new(),
+            oauth2: None,
+            credentials: None,
+            tls: None,
+            address_family: None,
+            paginate: None,
+            follow_refresh: false,
+            follow_redirects: true,
+            max_redirects: None,
+            follow_cross_origin_redirects: true,
+            graphql: None,
+            checksums: None,
+            stream: None,
+            csv: None,
+            watch_http_version: false,
+            watch_dns: false,
+            watch_certificate: false,
+            watch_encoding: false,
+            wayback: false,
+            translate: None,
+            watch_headers: Vec::new(),
+            detect_soft_errors: true,
+            expect_status: Vec::new(),
+            expect_contains: None,
+            expect_selector: None,
+            extract: Vec::new(),
+            alert: Vec::new(),
+            min_size: None,
+            max_size: None,
+            max_download_size: None,
+            require_selector: None,
+        }
+    }
+
+    /// Whether the URL is a WebSocket or server-sent event stream.
+    #[must_use]
+    pub fn is_stream(&self) -> bool {
+        self.stream.is_some() || stream::is_websocket(&self.url)
+    }
+
+    /// Whether only the headers of the URL are fetched.
+    #[must_use]
+    pub fn is_head(&self) -> bool {
+        self.method == Some(Method::Head)
+    }
+
+    /// Get the HTTP method the URL is requested with.
+    #[must_use]
+    pub fn http_method(&self) -> &'static str {
+        if self.graphql.is_some() {
+            "POST"
+        } else if self.is_head() {
+            "HEAD"
+        } else {
+            "GET"
+        }
+    }
+
+    /// Check if credentials from the credentials file should be used for
+    /// this URL, i.e. it doesn’t already authenticate some other way.
+    #[must_use]
+    pub fn wants_credentials(&self) -> bool {
+        self.url.username().is_empty()
+            && self.oauth2.is_none()
+            && !self.headers.keys().any(|name| {
+                name.eq_ignore_ascii_case(http::header::AUTHORIZATION.as_str())
+            })
+    }
+
+    /// The label to use for this URL in output.
+    #[must_use]
+    pub fn label(&self) -> &str {
+        self.name.as_deref().unwrap_or_else(|| self.url.as_str())
+    }
+
+    /// Whether this URL has any of `tags`.
+    ///
+    /// Returns true if `tags` is empty.
+    #[must_use]
+    pub fn matches_tags(&self, tags: &[String]) -> bool {
+        tags.is_empty() || tags.iter().any(|tag| self.tags.contains(tag))
+    }
+}
+
+/// Default for boolean settings that are on unless turned off.
+const fn default_true() -> bool {
+    true
+}
+
+/// Whether a boolean setting is on, so it can be skipped when serializing.
+#[expect(
+    clippy::trivially_copy_pass_by_ref,
+    reason = "serde passes a reference"
+)]
+const fn is_true(value: &bool) -> bool {
+    *value
+}
+
+/// How to compare old and new versions of a page.
+#[derive(
+    Clone,
+    Copy,
+    Debug,
+    Default,
+    Eq,
+    PartialEq,
+    clap::ValueEnum,
+    serde::Deserialize,
+    serde::Serialize,
+)]
+#[serde(rename_all = "kebab-case")]
+pub enum DiffMode {
+    /// Render the page as Markdown and diff the lines.
+    #[default]
+    Markdown,
+
+    /// Compare the HTML DOM trees and report changed elements.
+    Html,
+}
+
+/// What to do the first time a URL is checked, when there’s nothing to compare
+/// it to.
+#[derive(
+    Clone,
+    Copy,
+    Debug,
+    Default,
+    Eq,
+    PartialEq,
+    clap::ValueEnum,
+    serde::Deserialize,
+    serde::Serialize,
+)]
+#[serde(rename_all = "kebab-case")]
+pub enum OnNew {
+    /// Save the response without output.
+    Silent,
+
+    /// Output the content as if it had all been added.
+    #[default]
+    Render,
+
+    /// Output the content and send a notification that the URL is now being
+    /// monitored.
+    Notify,
+}
+
+/// The HTTP method to use to fetch a URL.
+#[derive(
+    Clone,
+    Copy,
+    Debug,
+    Default,
+    Eq,
+    PartialEq,
+    serde::Deserialize,
+    serde::Serialize,
+)]
+#[serde(rename_all = "UPPERCASE")]
+pub enum Method {
+    /// Fetch the whole response.
+    #[default]
+    Get,
+
+    /// Only fetch the headers.
+    Head,
+}
+
+/// Parse a URL, or a path to a local file as a `file://` URL.
+///
+/// Relative paths are relative to the current directory.
+///
+/// # Errors
+///
+/// Returns an error if `source` is neither a valid URL nor a path.
+pub fn parse_url(source: &str) -> anyhow::Result<Url> {
+    match Url::parse(source) {
+        Err(url::ParseError::RelativeUrlWithoutBase) => {
+            let path = std::path::absolute(source)?;
+            Url::from_file_path(&path).map_err(|()| {
+                anyhow::anyhow!("invalid path {}", path.display())
+            })
+        }
+        result => Ok(result?),
+    }
+}
+
+/// Deserialize a URL, or an absolute path to a local file as a `file://` URL.
+///
+/// Relative paths aren’t allowed, since it wouldn’t be clear what they’re
+/// relative to.
+fn deserialize_url<'de, D>(deserializer: D) -> Result<Url, D::Error>
+where
+    D: serde::Deserializer<'de>,
+{
+    let source = String::deserialize(deserializer)?;
+    if matches!(
+        Url::parse(&source),
+        Err(url::ParseError::RelativeUrlWithoutBase)
+    ) && !Path::new(&source).is_absolute()
+    {
+        return Err(serde::de::Error::custom(format!(
+            "{source:?} is not a URL or an absolute path"
+        )));
+    }
+    parse_url(&source).map_err(serde::de::Error::custom)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use assert2::check;
+
+    #[test]
+    fn test_parse_urls() {
+        let config: Config = toml::from_str(
+            r#"
+            [[url]]
+            url = "https://example.com/"
+            name = "Example"
+            tags = ["work", "security"]
+
+            [[url]]
+            url = "https://example.org/"
+
+            [[url]]
+            url = "/var/lib/reports/daily.csv"
+            "#,
+        )
+        .unwrap();
+
+        check!(config.urls.len() == 3);
+        check!(
+            config.urls[2].url.as_str() == "file:///var/lib/reports/daily.csv"
+        );
+        check!(config.urls[0].label() == "Example");
+        check!(config.urls[1].label() == "https://example.org/");
+        check!(config.urls[0].matches_tags(&["work".to_owned()]));
+        check!(!config.urls[1].matches_tags(&["work".to_owned()]));
+        check!(config.urls[1].matches_tags(&[]));
+
+        check!(
+            toml::from_str::<Config>("[[url]]\nurl = \"reports/daily.csv\"")
+                .is_err()
+        );
+        check!(
+            parse_url("reports/daily.csv").unwrap()
+                == Url::from_file_path(
+                    std::env::current_dir().unwrap().join("reports/daily.csv")
+                )
+                .unwrap()
+        );
+    }
+}
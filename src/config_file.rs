@@ -0,0 +1,242 @@
+//! Editing the configuration file.
+//!
+//! The `add` and `remove` subcommands change the `[[url]]` entries in the
+//! configuration file. The rest of the file, including comments and
+//! formatting, is left as it was.
+
+use anyhow::{Context, anyhow};
+use std::fs;
+use std::io;
+use std::path::{Path, PathBuf};
+use toml_edit::{ArrayOfTables, DocumentMut, Item, Table};
+use url::Url;
+
+use crate::config::{Config, UrlConfig};
+use crate::state::atomic_write;
+
+/// A configuration file being edited.
+#[derive(Debug)]
+pub struct ConfigFile {
+    /// Where the file is.
+    path: PathBuf,
+
+    /// The parsed file.
+    document: DocumentMut,
+}
+
+impl ConfigFile {
+    /// Open a configuration file for editing. It’s empty if it doesn’t exist.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if the file couldn’t be read or parsed.
+    pub fn open(path: &Path) -> anyhow::Result<Self> {
+        let source = match fs::read_to_string(path) {
+            Ok(source) => source,
+            Err(error) if error.kind() == io::ErrorKind::NotFound => {
+                String::new()
+            }
+            Err(error) => {
+                return Err(error).with_context(|| {
+                    format!("could not read config {}", path.display())
+                });
+            }
+        };
+        Self::parse(path, &source)
+    }
+
+    /// Parse the source of a configuration file.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if the source couldn’t be parsed.
+    pub fn parse(path: &Path, source: &str) -> anyhow::Result<Self> {
+        let document = source.parse().with_context(|| {
+            format!("could not parse config {}", path.display())
+        })?;
+        Ok(Self { path: path.to_owned(), document })
+    }
+
+    /// Get the path to the file.
+    #[must_use]
+    pub fn path(&self) -> &Path {
+        &self.path
+    }
+
+    /// Check if the file has an entry for a URL.
+    #[must_use]
+    pub fn contains(&self, url: &Url) -> bool {
+        self.urls()
+            .is_some_and(|urls| urls.iter().any(|table| is_for(table, url)))
+    }
+
+    /// Add an entry for a URL at the end of the file.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if the file already has an entry for the URL, or
+    /// `[url]` isn’t an array of tables.
+    pub fn add(&mut self, url_config: &UrlConfig) -> anyhow::Result<()> {
+        if self.contains(&url_config.url) {
+            return Err(anyhow!(
+                "{} is already in {}",
+                url_config.url,
+                self.path.display(),
+            ));
+        }
+
+        let entry: DocumentMut = toml::to_string(url_config)?.parse()?;
+        self.document
+            .entry("url")
+            .or_insert_with(|| Item::ArrayOfTables(ArrayOfTables::new()))
+            .as_array_of_tables_mut()
+            .ok_or_else(|| anyhow!("url is not an array of tables"))?
+            .push(entry.as_table().clone());
+        Ok(())
+    }
+
+    /// Remove the entries for a URL.
+    ///
+    /// Comments directly above an entry are removed with it, but comments
+    /// separated from it by a blank line, like a header at the top of the
+    /// file, are kept.
+    ///
+    /// Returns `false` if there weren’t any.
+    pub fn remove(&mut self, url: &Url) -> bool {
+        let Some(urls) = self
+            .document
+            .get_mut("url")
+            .and_then(Item::as_array_of_tables_mut)
+        else {
+            return false;
+        };
+
+        let count = urls.len();
+        let mut kept = String::new();
+        let mut index = 0;
+        while let Some(table) = urls.get_mut(index) {
+            let prefix = table
+                .decor()
+                .prefix()
+                .and_then(|prefix| prefix.as_str())
+                .unwrap_or_default();
+            if is_for(table, url) {
+                kept.push_str(detached(prefix));
+                urls.remove(index);
+            } else {
+                if !kept.is_empty() {
+                    let prefix = format!("{kept}{prefix}");
+                    table.decor_mut().set_prefix(prefix);
+                    kept.clear();
+                }
+                index = index.saturating_add(1);
+            }
+        }
+
+        let removed = urls.len() != count;
+        if urls.is_empty() {
+            self.document.remove("url");
+        }
+        if !kept.is_empty() {
+            let trailing =
+                self.document.trailing().as_str().unwrap_or_default();
+            let trailing = format!("{trailing}{kept}");
+            self.document.set_trailing(trailing);
+        }
+        removed
+    }
+
+    /// Save the file atomically.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if the edited configuration isn’t valid, or the file
+    /// couldn’t be written.
+    pub fn save(&self) -> anyhow::Result<()> {
+        let source = self.document.to_string();
+        toml::from_str::<Config>(&source)
+            .context("edited configuration is invalid")?;
+        if let Some(parent) = self.path.parent() {
+            fs::create_dir_all(parent)?;
+        }
+        atomic_write(&self.path, source.as_bytes()).with_context(|| {
+            format!("could not write config {}", self.path.display())
+        })
+    }
+
+    /// Get the `[[url]]` entries, if there are any.
+    fn urls(&self) -> Option<&ArrayOfTables> {
+        self.document.get("url").and_then(Item::as_array_of_tables)
+    }
+}
+
+impl std::fmt::Display for ConfigFile {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        self.document.fmt(f)
+    }
+}
+
+/// Check if a `[[url]]` entry is for a URL.
+fn is_for(table: &Table, url: &Url) -> bool {
+    table
+        .get("url")
+        .and_then(Item::as_str)
+        .and_then(|value| Url::parse(value).ok())
+        .is_some_and(|value| value == *url)
+}
+
+/// Get the part of the whitespace and comments above an entry that isn’t
+/// about the entry, i.e. everything up to the last blank line.
+fn detached(prefix: &str) -> &str {
+    prefix
+        .rfind("\n\n")
+        .and_then(|end| prefix.get(..end.saturating_add(2)))
+        .unwrap_or_default()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use assert2::check;
+
+    #[test]
+    fn test_add_remove() {
+        let mut file = ConfigFile::parse(
+            Path::new("config.toml"),
+            "# Pages to watch.\n\n\
+            [[url]]\n\
+            url = \"https://example.com\"  # The home page.\n",
+        )
+        .unwrap();
+        let url: Url = "https://example.com/".parse().unwrap();
+        check!(file.contains(&url));
+        check!(file.add(&UrlConfig::new(url.clone())).is_err());
+
+        let mut url_config =
+            UrlConfig::new("https://example.com/news".parse().unwrap());
+        url_config.select = Some("main".to_owned());
+        url_config.tags = vec!["news".to_owned()];
+        file.add(&url_config).unwrap();
+        check!(
+            file.to_string()
+                == "# Pages to watch.\n\n\
+                    [[url]]\n\
+                    url = \"https://example.com\"  # The home page.\n\n\
+                    [[url]]\n\
+                    url = \"https://example.com/news\"\n\
+                    tags = [\"news\"]\n\
+                    select = \"main\"\n"
+        );
+
+        check!(file.remove(&url));
+        check!(!file.remove(&url));
+        check!(
+            file.to_string()
+                == "# Pages to watch.\n\n\
+                    [[url]]\n\
+                    url = \"https://example.com/news\"\n\
+                    tags = [\"news\"]\n\
+                    select = \"main\"\n"
+        );
+    }
+}
@@ -0,0 +1,534 @@
+//! HTTP authentication credentials by host (`credentials.toml`).
+//!
+//! Rather than repeating the same `Authorization` header for every URL on a
+//! server, credentials can be listed by host in `credentials.toml`, next to
+//! the configuration file:
+//!
+//! ```toml
+//! [[host]]
+//! host = "intranet.example.com"
+//! username = "monitor"
+//! password = { env = "INTRANET_PASSWORD" }
+//!
+//! [[host]]
+//! host = "*.example.org:8443"  # Any subdomain, on port 8443 only.
+//! username = "monitor"
+//! password = { age = "example_org" }
+//! scheme = "digest"
+//! ```
+//!
+//! The first entry matching a URL is used. Passwords can be any kind of
+//! [`Secret`]. Since the file usually contains passwords, it must not be
+//! accessible by other users, and `export` leaves it out.
+//!
+//! Basic credentials are sent with every request to the URL’s origin, but not
+//! to other origins when following pagination or refreshes. Digest credentials
+//! ([RFC 7616]) are sent in answer to a `401 Unauthorized` challenge.
+//! Credentials aren’t used for URLs that include a username, set an
+//! `Authorization` header, or use `oauth2`.
+//!
+//! [RFC 7616]: https://www.rfc-editor.org/rfc/rfc7616
+
+use anyhow::{Context, bail};
+use base64::Engine as _;
+use base64::engine::general_purpose::STANDARD as BASE64;
+use http::header::{self, HeaderMap, HeaderValue};
+use md5::Digest as _;
+use sha2::Digest as _;
+use std::fmt::{self, Write as _};
+use std::fs;
+use std::io;
+use std::path::Path;
+use url::Url;
+
+use crate::secret::{Resolver, Secret};
+use crate::state::hex;
+
+/// Name of the credentials file, in the same directory as the configuration
+/// file.
+pub const FILE_NAME: &str = "credentials.toml";
+
+/// The credentials file.
+#[derive(Debug, Default, serde::Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct Store {
+    /// Credentials in the order they’re listed.
+    #[serde(default, rename = "host")]
+    pub hosts: Vec<Credentials>,
+}
+
+impl Store {
+    /// Load the credentials file, if it exists.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if the file couldn’t be read or parsed, or other users
+    /// can access it.
+    pub fn load(path: &Path) -> anyhow::Result<Self> {
+        let source = match fs::read_to_string(path) {
+            Ok(source) => source,
+            Err(error) if error.kind() == io::ErrorKind::NotFound => {
+                return Ok(Self::default());
+            }
+            Err(error) => {
+                return Err(error).with_context(|| {
+                    format!("could not read {}", path.display())
+                });
+            }
+        };
+        check_permissions(path)?;
+        toml::from_str(&source)
+            .with_context(|| format!("could not parse {}", path.display()))
+    }
+
+    /// Resolve the passwords.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if a password couldn’t be resolved.
+    pub fn resolve_secrets(
+        &mut self,
+        resolver: &mut Resolver,
+    ) -> anyhow::Result<()> {
+        for credentials in &mut self.hosts {
+            credentials.password.resolve(resolver).with_context(|| {
+                format!("password for {} in {FILE_NAME}", credentials.host)
+            })?;
+        }
+        Ok(())
+    }
+
+    /// Find the credentials for a URL.
+    #[must_use]
+    pub fn find(&self, url: &Url) -> Option<&Credentials> {
+        self.hosts
+            .iter()
+            .find(|credentials| credentials.matches(url))
+    }
+}
+
+/// Credentials for the URLs on a host.
+#[derive(Clone, Debug, serde::Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct Credentials {
+    /// Host the credentials are for, optionally with a port, e.g.
+    /// `example.com:8443`. `*.example.com` matches any subdomain of
+    /// `example.com`.
+    pub host: String,
+
+    /// User name.
+    pub username: String,
+
+    /// Password.
+    pub password: Secret,
+
+    /// Authentication scheme.
+    #[serde(default)]
+    pub scheme: Scheme,
+}
+
+/// An HTTP authentication scheme.
+#[derive(Clone, Copy, Debug, Default, Eq, PartialEq, serde::Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum Scheme {
+    /// Send the password with every request.
+    #[default]
+    Basic,
+
+    /// Answer challenges without sending the password.
+    Digest,
+}
+
+impl Credentials {
+    /// Check if the credentials are for a URL.
+    #[must_use]
+    pub fn matches(&self, url: &Url) -> bool {
+        let Some(host) = url.host_str() else {
+            return false;
+        };
+        let pattern = match self.host.rsplit_once(':') {
+            Some((pattern, port))
+                if port.parse().ok() == url.port_or_known_default() =>
+            {
+                pattern
+            }
+            Some((_, port)) if port.parse::<u16>().is_ok() => return false,
+            _ => &self.host,
+        };
+        match pattern.strip_prefix("*.") {
+            Some(domain) => host
+                .len()
+                .checked_sub(domain.len())
+                .and_then(|index| index.checked_sub(1))
+                .and_then(|index| host.get(index..))
+                .and_then(|suffix| suffix.strip_prefix('.'))
+                .is_some_and(|suffix| suffix.eq_ignore_ascii_case(domain)),
+            None => host.eq_ignore_ascii_case(pattern),
+        }
+    }
+
+    /// Get the `Authorization` header to send with every request, if the
+    /// scheme is Basic.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if the password hasn’t been resolved, or the header
+    /// value is invalid.
+    pub fn basic(&self) -> anyhow::Result<Option<HeaderValue>> {
+        if self.scheme != Scheme::Basic {
+            return Ok(None);
+        }
+        let encoded = BASE64.encode(format!(
+            "{}:{}",
+            self.username,
+            self.password.expose()?
+        ));
+        sensitive(format!("Basic {encoded}")).map(Some)
+    }
+
+    /// Get the `Authorization` header answering the Digest challenge in a
+    /// `401 Unauthorized` response, if there is one and the scheme is Digest.
+    ///
+    /// `method` and `url` are the method and URL of the request that will
+    /// send the header.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if the password hasn’t been resolved, or the header
+    /// value is invalid.
+    pub fn digest(
+        &self,
+        method: &str,
+        url: &Url,
+        headers: &HeaderMap,
+    ) -> anyhow::Result<Option<HeaderValue>> {
+        if self.scheme != Scheme::Digest {
+            return Ok(None);
+        }
+        let Some(challenge) = headers
+            .get_all(header::WWW_AUTHENTICATE)
+            .iter()
+            .find_map(|value| Challenge::parse(value.to_str().ok()?))
+        else {
+            return Ok(None);
+        };
+        let mut cnonce = [0_u8; 16];
+        getrandom::fill(&mut cnonce)?;
+        let mut uri = url.path().to_owned();
+        if let Some(query) = url.query() {
+            uri.push('?');
+            uri.push_str(query);
+        }
+        self.answer(&challenge, method, &uri, &hex(&cnonce))
+            .map(Some)
+    }
+
+    /// Answer a Digest challenge.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if the password hasn’t been resolved, or the header
+    /// value is invalid.
+    fn answer(
+        &self,
+        challenge: &Challenge,
+        method: &str,
+        uri: &str,
+        cnonce: &str,
+    ) -> anyhow::Result<HeaderValue> {
+        let hash = |data: String| challenge.algorithm.hash(&data);
+        let Challenge { realm, nonce, .. } = challenge;
+        let mut secret = hash(format!(
+            "{}:{realm}:{}",
+            self.username,
+            self.password.expose()?
+        ));
+        if challenge.algorithm.session {
+            secret = hash(format!("{secret}:{nonce}:{cnonce}"));
+        }
+        let request = hash(format!("{method}:{uri}"));
+        let nc = "00000001";
+        let response = if challenge.qop {
+            hash(format!("{secret}:{nonce}:{nc}:{cnonce}:auth:{request}"))
+        } else {
+            hash(format!("{secret}:{nonce}:{request}"))
+        };
+
+        let mut value = format!(
+            "Digest username={}, realm={}, nonce={}, uri={}, algorithm={}, \
+                response=\"{response}\"",
+            quote(&self.username),
+            quote(realm),
+            quote(nonce),
+            quote(uri),
+            challenge.algorithm,
+        );
+        if challenge.qop {
+            let _ = write!(value, ", qop=auth, nc={nc}, cnonce=\"{cnonce}\"");
+        }
+        if let Some(opaque) = &challenge.opaque {
+            let _ = write!(value, ", opaque={}", quote(opaque));
+        }
+        sensitive(value)
+    }
+}
+
+/// A Digest challenge from a `WWW-Authenticate` header.
+#[derive(Debug)]
+struct Challenge {
+    /// The realm.
+    realm: String,
+
+    /// The server’s nonce.
+    nonce: String,
+
+    /// Data to pass back to the server unchanged.
+    opaque: Option<String>,
+
+    /// The hash algorithm.
+    algorithm: Algorithm,
+
+    /// Whether the server supports `qop=auth`. If it doesn’t send `qop`, the
+    /// older [RFC 2069] scheme is used.
+    ///
+    /// [RFC 2069]: https://www.rfc-editor.org/rfc/rfc2069
+    qop: bool,
+}
+
+impl Challenge {
+    /// Parse a `WWW-Authenticate` header value.
+    ///
+    /// Returns `None` if it isn’t a Digest challenge, or it requires
+    /// something that isn’t supported, like `qop=auth-int`.
+    fn parse(value: &str) -> Option<Self> {
+        let (scheme, params) = value.trim().split_once(' ')?;
+        if !scheme.eq_ignore_ascii_case("digest") {
+            return None;
+        }
+        let params = parse_params(params);
+        let get = |name: &str| {
+            params
+                .iter()
+                .find(|(other, _)| other.eq_ignore_ascii_case(name))
+                .map(|(_, value)| value.clone())
+        };
+        let qop = match get("qop") {
+            Some(qop) => qop
+                .split(',')
+                .any(|qop| qop.trim().eq_ignore_ascii_case("auth"))
+                .then_some(true)?,
+            None => false,
+        };
+        Some(Self {
+            realm: get("realm").unwrap_or_default(),
+            nonce: get("nonce")?,
+            opaque: get("opaque"),
+            algorithm: Algorithm::parse(
+                get("algorithm").as_deref().unwrap_or("MD5"),
+            )?,
+            qop,
+        })
+    }
+}
+
+/// A Digest hash algorithm.
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+struct Algorithm {
+    /// Use SHA-256 rather than MD5.
+    sha256: bool,
+
+    /// Hash the nonces into the secret (the `-sess` variants).
+    session: bool,
+}
+
+impl Algorithm {
+    /// Parse an algorithm name, e.g. `SHA-256-sess`.
+    fn parse(name: &str) -> Option<Self> {
+        let (name, session) = match name.len().checked_sub(5) {
+            Some(index)
+                if name.get(index..).is_some_and(|suffix| {
+                    suffix.eq_ignore_ascii_case("-sess")
+                }) =>
+            {
+                (name.get(..index)?, true)
+            }
+            _ => (name, false),
+        };
+        if name.eq_ignore_ascii_case("md5") {
+            Some(Self { sha256: false, session })
+        } else if name.eq_ignore_ascii_case("sha-256") {
+            Some(Self { sha256: true, session })
+        } else {
+            None
+        }
+    }
+
+    /// Hash data, returning lowercase hex.
+    fn hash(self, data: &str) -> String {
+        if self.sha256 {
+            hex(&sha2::Sha256::digest(data))
+        } else {
+            hex(&md5::Md5::digest(data))
+        }
+    }
+}
+
+impl fmt::Display for Algorithm {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(if self.sha256 { "SHA-256" } else { "MD5" })?;
+        if self.session {
+            f.write_str("-sess")?;
+        }
+        Ok(())
+    }
+}
+
+/// Parse comma-separated `name=value` parameters, where values may be quoted.
+fn parse_params(source: &str) -> Vec<(String, String)> {
+    let mut params = Vec::new();
+    let mut chars = source.chars().peekable();
+    loop {
+        let name: String = chars
+            .by_ref()
+            .skip_while(|c| *c == ',' || c.is_whitespace())
+            .take_while(|c| *c != '=')
+            .collect();
+        if name.is_empty() {
+            return params;
+        }
+        let mut value = String::new();
+        if chars.peek() == Some(&'"') {
+            chars.next();
+            while let Some(c) = chars.next() {
+                match c {
+                    '"' => break,
+                    '\\' => value.extend(chars.next()),
+                    c => value.push(c),
+                }
+            }
+        } else {
+            value = chars.by_ref().take_while(|c| *c != ',').collect();
+        }
+        params.push((name.trim().to_owned(), value.trim().to_owned()));
+    }
+}
+
+/// Quote a value for a Digest header.
+fn quote(value: &str) -> String {
+    format!("\"{}\"", value.replace('\\', "\\\\").replace('"', "\\\""))
+}
+
+/// Make a header value that shouldn’t be logged.
+///
+/// # Errors
+///
+/// Returns an error if the value isn’t a valid header value.
+fn sensitive(value: String) -> anyhow::Result<HeaderValue> {
+    let mut value =
+        HeaderValue::try_from(value).context("invalid credentials")?;
+    value.set_sensitive(true);
+    Ok(value)
+}
+
+/// Check that other users can’t access the credentials file.
+///
+/// # Errors
+///
+/// Returns an error if they can, or the permissions couldn’t be read.
+#[cfg(unix)]
+fn check_permissions(path: &Path) -> anyhow::Result<()> {
+    use std::os::unix::fs::PermissionsExt as _;
+
+    let mode = fs::metadata(path)
+        .with_context(|| format!("could not read {}", path.display()))?
+        .permissions()
+        .mode();
+    if mode & 0o077 != 0 {
+        bail!(
+            "{} is accessible by other users; run `chmod 600 {0}`",
+            path.display()
+        );
+    }
+    Ok(())
+}
+
+/// Check that other users can’t access the credentials file.
+///
+/// Permissions aren’t checked on this platform.
+#[cfg(not(unix))]
+fn check_permissions(_path: &Path) -> anyhow::Result<()> {
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use assert2::check;
+
+    #[test]
+    fn test_credentials() {
+        let credentials = |host: &str, scheme| Credentials {
+            host: host.to_owned(),
+            username: "Mufasa".to_owned(),
+            password: Secret::from("Circle of Life".to_owned()),
+            scheme,
+        };
+        let url = |url: &str| url.parse::<Url>().unwrap();
+
+        let exact = credentials("Example.com", Scheme::Basic);
+        check!(exact.matches(&url("https://example.com/a")));
+        check!(!exact.matches(&url("https://www.example.com/")));
+        let wildcard = credentials("*.example.com:8443", Scheme::Basic);
+        check!(wildcard.matches(&url("https://a.b.example.com:8443/")));
+        check!(!wildcard.matches(&url("https://a.example.com/")));
+        check!(!wildcard.matches(&url("https://example.com:8443/")));
+        check!(!wildcard.matches(&url("https://badexample.com:8443/")));
+        check!(
+            exact.basic().unwrap().unwrap()
+                == "Basic TXVmYXNhOkNpcmNsZSBvZiBMaWZl"
+        );
+
+        // The example from RFC 7616 § 3.9.1.
+        let digest = credentials("example.org", Scheme::Digest);
+        check!(digest.basic().unwrap() == None);
+        let challenge = |algorithm| {
+            Challenge::parse(&format!(
+                "Digest realm=\"http-auth@example.org\", \
+                    qop=\"auth, auth-int\", algorithm={algorithm}, \
+                    nonce=\"7ypf/xlj9XXwfDPEoM4URrv/xwf94BcCAzFZH4GiTo0v\", \
+                    opaque=\"FQhe/qaU925kfnzjCev0ciny7QMkPqMAFRtzCUYo5tdS\""
+            ))
+            .unwrap()
+        };
+        let answer = |algorithm| {
+            digest
+                .answer(
+                    &challenge(algorithm),
+                    "GET",
+                    "/dir/index.html",
+                    "f2/wE4q74E6zIJEtWaHKaf5wv/H5QzzpXusqGemxURZJ",
+                )
+                .unwrap()
+        };
+        check!(
+            answer("MD5")
+                == "Digest username=\"Mufasa\", \
+                    realm=\"http-auth@example.org\", \
+                    nonce=\"7ypf/xlj9XXwfDPEoM4URrv/xwf94BcCAzFZH4GiTo0v\", \
+                    uri=\"/dir/index.html\", algorithm=MD5, \
+                    response=\"8ca523f5e9506fed4657c9700eebdbec\", \
+                    qop=auth, nc=00000001, \
+                    cnonce=\"f2/wE4q74E6zIJEtWaHKaf5wv/H5QzzpXusqGemxURZJ\", \
+                    opaque=\"FQhe/qaU925kfnzjCev0ciny7QMkPqMAFRtzCUYo5tdS\""
+        );
+        check!(answer("SHA-256").to_str().unwrap().contains(
+            "response=\"753927fa0e85d155564e2e272a28d180\
+                2ca10daf4496794697cf8db5856cb6c1\""
+        ));
+        check!(Challenge::parse("Basic realm=\"x\"").is_none());
+        check!(Challenge::parse("Digest nonce=\"n\", qop=auth-int").is_none());
+        check!(
+            Challenge::parse("Digest nonce=\"n\", algorithm=SHA-512").is_none()
+        );
+    }
+}
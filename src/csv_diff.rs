@@ -0,0 +1,410 @@
+//! Row-wise diffing of CSV and TSV files.
+//!
+//! Line diffs of tables break down when rows are reordered, and don’t say
+//! which column changed. This parses both versions and matches rows up by a
+//! key column, like `id`, and reports rows that were added, removed, or
+//! changed. Without a key column, rows are compared as a whole regardless of
+//! their order.
+//!
+//! ```toml
+//! [[url]]
+//! url = "https://example.com/prices.csv"
+//! csv.key = "sku"
+//! ```
+
+use anyhow::{anyhow, bail};
+use std::collections::HashMap;
+use std::fmt;
+use std::io;
+use termcolor::{Color, ColorSpec};
+
+use crate::response::Response;
+
+/// Maximum length of a row included in a change summary.
+const SUMMARY_LEN: usize = 100;
+
+/// Options for CSV and TSV content.
+///
+/// Setting these treats the response as CSV even if its content type isn’t
+/// `text/csv`.
+#[derive(Clone, Debug, Default, serde::Deserialize, serde::Serialize)]
+#[serde(deny_unknown_fields)]
+pub struct CsvConfig {
+    /// Name of the column that identifies each row.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub key: Option<String>,
+
+    /// Character between fields. Defaults to a tab for TSV and a comma
+    /// otherwise.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub delimiter: Option<char>,
+}
+
+/// Get the delimiter to parse a response with, if it should be handled as
+/// CSV or TSV.
+///
+/// # Errors
+///
+/// Returns an error if the `Content-Type` header is invalid, or the configured
+/// delimiter isn’t ASCII.
+pub fn delimiter(
+    config: Option<&CsvConfig>,
+    response: &Response,
+) -> anyhow::Result<Option<u8>> {
+    let detected = match response.media_type()?.as_deref() {
+        Some("text/csv" | "application/csv") => Some(b','),
+        Some("text/tab-separated-values") => Some(b'\t'),
+        _ => None,
+    };
+    let Some(config) = config else {
+        return Ok(detected);
+    };
+    match config.delimiter {
+        Some(delimiter) => u8::try_from(delimiter)
+            .ok()
+            .filter(u8::is_ascii)
+            .map(Some)
+            .ok_or_else(|| anyhow!("csv.delimiter {delimiter:?} isn’t ASCII")),
+        None => Ok(Some(detected.unwrap_or(b','))),
+    }
+}
+
+/// A parsed CSV file.
+#[derive(Clone, Debug, Default, Eq, PartialEq)]
+pub struct Table {
+    /// Names of the columns, from the first row.
+    pub headers: Vec<String>,
+
+    /// The other rows.
+    pub rows: Vec<Vec<String>>,
+}
+
+impl Table {
+    /// Parse CSV text. Rows may have different numbers of fields.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if the CSV is malformed.
+    pub fn parse(text: &str, delimiter: u8) -> anyhow::Result<Self> {
+        let mut reader = csv::ReaderBuilder::new()
+            .delimiter(delimiter)
+            .flexible(true)
+            .from_reader(text.as_bytes());
+        let headers = reader.headers()?.iter().map(str::to_owned).collect();
+        let rows = reader
+            .records()
+            .map(|record| Ok(record?.iter().map(str::to_owned).collect()))
+            .collect::<Result<_, csv::Error>>()?;
+        Ok(Self { headers, rows })
+    }
+
+    /// Get the index of a column by name.
+    fn column(&self, name: &str) -> Option<usize> {
+        self.headers.iter().position(|header| header == name)
+    }
+
+    /// A short description of a row, e.g. `id=1, name=Widget`.
+    fn summary(&self, row: &[String]) -> String {
+        let summary = row
+            .iter()
+            .enumerate()
+            .map(|(i, value)| match self.headers.get(i) {
+                Some(header) => format!("{header}={value}"),
+                None => value.clone(),
+            })
+            .collect::<Vec<_>>()
+            .join(", ");
+        truncate(summary)
+    }
+}
+
+/// The kind of change made to a row.
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub enum ChangeKind {
+    /// The row was added.
+    Added(String),
+
+    /// The row was removed.
+    Removed(String),
+
+    /// A value changed from the first value to the second.
+    Changed(String, String),
+}
+
+/// A change to the table.
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub struct Change {
+    /// The row or column that changed, e.g. `row id=42, price`.
+    pub path: String,
+
+    /// What changed.
+    pub kind: ChangeKind,
+}
+
+impl fmt::Display for Change {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match &self.kind {
+            ChangeKind::Added(summary) => {
+                write!(f, "+ {}: {summary}", self.path)
+            }
+            ChangeKind::Removed(summary) => {
+                write!(f, "- {}: {summary}", self.path)
+            }
+            ChangeKind::Changed(old, new) => {
+                write!(f, "~ {}: {old} → {new}", self.path)
+            }
+        }
+    }
+}
+
+/// Compare two tables and return the changes between them.
+///
+/// Rows are matched by the value in the `key` column. If there’s no `key`,
+/// rows are only added or removed, and moving a row isn’t a change.
+///
+/// # Errors
+///
+/// Returns an error if the `key` column is missing from either table.
+pub fn diff(
+    old: &Table,
+    new: &Table,
+    key: Option<&str>,
+) -> anyhow::Result<Vec<Change>> {
+    let mut changes = Vec::new();
+    if old.headers != new.headers {
+        changes.push(Change {
+            path: "columns".to_owned(),
+            kind: ChangeKind::Changed(
+                old.headers.join(", "),
+                new.headers.join(", "),
+            ),
+        });
+    }
+    match key {
+        Some(key) => diff_keyed(&mut changes, old, new, key)?,
+        None => diff_unkeyed(&mut changes, old, new),
+    }
+    Ok(changes)
+}
+
+/// Compare rows matched up by the value in the `key` column.
+///
+/// If several rows have the same key, they’re matched up in order.
+fn diff_keyed(
+    changes: &mut Vec<Change>,
+    old: &Table,
+    new: &Table,
+    key: &str,
+) -> anyhow::Result<()> {
+    let (Some(old_key), Some(new_key)) = (old.column(key), new.column(key))
+    else {
+        bail!("CSV has no column {key:?}");
+    };
+    let path = |value: &str, occurrence: usize| {
+        if occurrence == 0 {
+            format!("row {key}={value}")
+        } else {
+            format!("row {key}={value} (#{})", occurrence.saturating_add(1))
+        }
+    };
+
+    let mut old_rows: HashMap<&str, Vec<&[String]>> = HashMap::new();
+    for row in &old.rows {
+        let value = row.get(old_key).map_or("", String::as_str);
+        old_rows.entry(value).or_default().push(row);
+    }
+
+    // Columns in both tables, by their indexes in each.
+    let common: Vec<(&str, usize, usize)> = new
+        .headers
+        .iter()
+        .enumerate()
+        .filter_map(|(new_i, name)| {
+            Some((name.as_str(), old.column(name)?, new_i))
+        })
+        .collect();
+
+    let mut seen: HashMap<&str, usize> = HashMap::new();
+    for row in &new.rows {
+        let value = row.get(new_key).map_or("", String::as_str);
+        let occurrence = seen.entry(value).or_default();
+        let old_row =
+            old_rows.get(value).and_then(|rows| rows.get(*occurrence));
+        let row_path = path(value, *occurrence);
+        *occurrence = occurrence.saturating_add(1);
+
+        let Some(old_row) = old_row else {
+            changes.push(Change {
+                path: row_path,
+                kind: ChangeKind::Added(new.summary(row)),
+            });
+            continue;
+        };
+        for &(name, old_i, new_i) in &common {
+            let old_value = old_row.get(old_i).map_or("", String::as_str);
+            let new_value = row.get(new_i).map_or("", String::as_str);
+            if old_value != new_value {
+                changes.push(Change {
+                    path: format!("{row_path}, {name}"),
+                    kind: ChangeKind::Changed(
+                        truncate(old_value.to_owned()),
+                        truncate(new_value.to_owned()),
+                    ),
+                });
+            }
+        }
+    }
+
+    // Old rows that weren’t matched were removed.
+    let mut occurrences: HashMap<&str, usize> = HashMap::new();
+    for row in &old.rows {
+        let value = row.get(old_key).map_or("", String::as_str);
+        let occurrence = occurrences.entry(value).or_default();
+        if *occurrence >= seen.get(value).copied().unwrap_or_default() {
+            changes.push(Change {
+                path: path(value, *occurrence),
+                kind: ChangeKind::Removed(old.summary(row)),
+            });
+        }
+        *occurrence = occurrence.saturating_add(1);
+    }
+    Ok(())
+}
+
+/// Compare rows as a whole, ignoring their order.
+///
+/// Rows are identified by their position in the table they’re in.
+fn diff_unkeyed(changes: &mut Vec<Change>, old: &Table, new: &Table) {
+    let mut unmatched = row_counts(new);
+    for (i, row) in old.rows.iter().enumerate() {
+        match unmatched.get_mut(&row[..]) {
+            Some(count) if *count > 0 => *count = count.saturating_sub(1),
+            _ => changes.push(Change {
+                path: format!("row {}", i.saturating_add(1)),
+                kind: ChangeKind::Removed(old.summary(row)),
+            }),
+        }
+    }
+
+    let mut unmatched = row_counts(old);
+    for (i, row) in new.rows.iter().enumerate() {
+        match unmatched.get_mut(&row[..]) {
+            Some(count) if *count > 0 => *count = count.saturating_sub(1),
+            _ => changes.push(Change {
+                path: format!("row {}", i.saturating_add(1)),
+                kind: ChangeKind::Added(new.summary(row)),
+            }),
+        }
+    }
+}
+
+/// Count how many times each row appears in a table.
+fn row_counts(table: &Table) -> HashMap<&[String], usize> {
+    let mut counts: HashMap<&[String], usize> = HashMap::new();
+    for row in &table.rows {
+        let count = counts.entry(row).or_default();
+        *count = count.saturating_add(1);
+    }
+    counts
+}
+
+/// Truncate text to [`SUMMARY_LEN`] characters.
+fn truncate(mut text: String) -> String {
+    if let Some((index, _)) = text.char_indices().nth(SUMMARY_LEN) {
+        text.truncate(index);
+        text.push('…');
+    }
+    text
+}
+
+/// Print a list of changes.
+///
+/// # Panics
+///
+/// Panics if the output couldn’t be written, like `println!`.
+pub fn print_changes<S>(out: &mut S, changes: &[Change])
+where
+    S: termcolor::WriteColor + io::Write + ?Sized,
+{
+    let mut removed_color = ColorSpec::new();
+    removed_color.set_fg(Some(Color::Red)).set_intense(true);
+    let mut added_color = ColorSpec::new();
+    added_color.set_fg(Some(Color::Green)).set_intense(true);
+    let mut changed_color = ColorSpec::new();
+    changed_color.set_fg(Some(Color::Yellow)).set_intense(true);
+
+    for change in changes {
+        let color = match change.kind {
+            ChangeKind::Added(_) => &added_color,
+            ChangeKind::Removed(_) => &removed_color,
+            ChangeKind::Changed(_, _) => &changed_color,
+        };
+        // Use `unwrap()` here because these would be IO errors, so we may as
+        // well act like `println!`.
+        out.set_color(color).unwrap();
+        writeln!(out, "{change}").unwrap();
+        out.reset().unwrap();
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use assert2::check;
+
+    /// Shortcut to diff CSV and format changes as strings.
+    fn d(old: &str, new: &str, key: Option<&str>) -> Vec<String> {
+        let old = Table::parse(old, b',').unwrap();
+        let new = Table::parse(new, b',').unwrap();
+        diff(&old, &new, key)
+            .unwrap()
+            .iter()
+            .map(ToString::to_string)
+            .collect()
+    }
+
+    #[test]
+    fn test_diff() {
+        let old =
+            "id,name,price\n1,Widget,3\n2,Gadget,5\n3,\"Thing, large\",9\n";
+        check!(d(old, old, Some("id")).is_empty());
+
+        // Reordering rows isn’t a change.
+        let reordered =
+            "id,name,price\n3,\"Thing, large\",9\n1,Widget,3\n2,Gadget,5\n";
+        check!(d(old, reordered, Some("id")).is_empty());
+        check!(d(old, reordered, None).is_empty());
+
+        let new = "id,name,price\n2,Gadget,6\n1,Widget,3\n4,Doohickey,1\n";
+        check!(
+            d(old, new, Some("id"))
+                == [
+                    "~ row id=2, price: 5 → 6",
+                    "+ row id=4: id=4, name=Doohickey, price=1",
+                    "- row id=3: id=3, name=Thing, large, price=9",
+                ]
+        );
+        check!(
+            d(old, new, None)
+                == [
+                    "- row 2: id=2, name=Gadget, price=5",
+                    "- row 3: id=3, name=Thing, large, price=9",
+                    "+ row 1: id=2, name=Gadget, price=6",
+                    "+ row 3: id=4, name=Doohickey, price=1",
+                ]
+        );
+
+        // Only columns in both versions are compared.
+        check!(
+            d(old, "price,id\n3,1\n5,2\n9,3\n", Some("id"))
+                == ["~ columns: id, name, price → price, id"]
+        );
+
+        check!(
+            d("id\n1\n1\n", "id\n1\n", Some("id")) == ["- row id=1 (#2): id=1"]
+        );
+
+        let table = Table::parse("a\n1\n", b',').unwrap();
+        check!(diff(&table, &table, Some("b")).is_err());
+    }
+}
@@ -0,0 +1,127 @@
+//! Running continuously, e.g. as a systemd service.
+
+use anyhow::bail;
+use chrono::Local;
+use std::process::ExitCode;
+use std::time::{Duration, Instant};
+
+use crate::systemd::Systemd;
+
+/// Check URLs every `interval` until stopped with `SIGTERM` or `SIGINT`.
+///
+/// `check` checks the URLs once and returns whether any of them failed. Errors
+/// checking individual URLs are reported by `check`, so an error returned by
+/// it is fatal; under systemd, the unit is then restarted according to its
+/// `Restart` setting.
+///
+/// # Errors
+///
+/// Returns an error if `interval` is zero, if signal handlers couldn’t be
+/// installed, or if `check` returns an error.
+pub async fn run<F, Fut>(
+    interval: Duration,
+    systemd: &Systemd,
+    mut check: F,
+) -> anyhow::Result<ExitCode>
+where
+    F: FnMut() -> Fut,
+    Fut: Future<Output = anyhow::Result<bool>>,
+{
+    if interval.is_zero() {
+        bail!("--daemon interval must be greater than zero");
+    }
+    let mut shutdown = Shutdown::new()?;
+    systemd.ready();
+
+    loop {
+        let started = Instant::now();
+        systemd.status("Checking URLs");
+        let failed = tokio::select! {
+            result = check() => result?,
+            () = shutdown.wait() => break,
+        };
+        systemd.ping();
+
+        let next = started.checked_add(interval).unwrap_or(started);
+        let status = if failed {
+            "Some checks failed"
+        } else {
+            "All checks passed"
+        };
+        systemd.status(&format!(
+            "{status} at {}; next check in {}",
+            Local::now().format("%Y-%m-%d %H:%M:%S"),
+            humantime::format_duration(Duration::from_secs(
+                next.saturating_duration_since(Instant::now()).as_secs()
+            )),
+        ));
+        tracing::info!("{status}; waiting until next check");
+
+        if shutdown.sleep_until(next, systemd).await {
+            break;
+        }
+    }
+
+    tracing::info!("Stopping");
+    systemd.stopping();
+    Ok(ExitCode::SUCCESS)
+}
+
+/// Waits for signals to shut down.
+#[derive(Debug)]
+struct Shutdown {
+    /// Receives `SIGTERM`, which systemd sends to stop the service.
+    #[cfg(unix)]
+    terminate: tokio::signal::unix::Signal,
+}
+
+impl Shutdown {
+    /// Install signal handlers.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if the handlers couldn’t be installed.
+    fn new() -> std::io::Result<Self> {
+        Ok(Self {
+            #[cfg(unix)]
+            terminate: tokio::signal::unix::signal(
+                tokio::signal::unix::SignalKind::terminate(),
+            )?,
+        })
+    }
+
+    /// Wait for `SIGTERM` or `SIGINT`.
+    async fn wait(&mut self) {
+        #[cfg(unix)]
+        tokio::select! {
+            _ = tokio::signal::ctrl_c() => {}
+            _ = self.terminate.recv() => {}
+        }
+        #[cfg(not(unix))]
+        let _ = tokio::signal::ctrl_c().await;
+    }
+
+    /// Sleep until `deadline`, pinging the systemd watchdog as needed.
+    ///
+    /// Returns `true` if a signal to shut down was received.
+    async fn sleep_until(
+        &mut self,
+        deadline: Instant,
+        systemd: &Systemd,
+    ) -> bool {
+        loop {
+            let wake = systemd
+                .watchdog_interval()
+                .and_then(|interval| Instant::now().checked_add(interval))
+                .map_or(deadline, |wake| wake.min(deadline));
+            tokio::select! {
+                () = tokio::time::sleep_until(wake.into()) => {}
+                () = self.wait() => return true,
+            }
+            if wake >= deadline {
+                return false;
+            }
+            systemd.ping();
+        }
+    }
+}
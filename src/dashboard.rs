@@ -0,0 +1,442 @@
+//! A read-only web page showing results in daemon mode.
+//!
+//! With `--listen`, the daemon serves an index of the monitored URLs with the
+//! result of their last check and when they last changed. Each URL has a page
+//! listing its changes, and each change can be viewed as a side by side diff
+//! rendered from the stored history.
+//!
+//! The server only handles simple `GET` requests, one at a time. It’s meant
+//! to be used on localhost or a trusted network; there’s no authentication.
+
+use chrono::{DateTime, Local, Utc};
+use http::StatusCode;
+use std::collections::HashMap;
+use std::fmt::Write as _;
+use std::sync::{Mutex, PoisonError};
+use std::time::Duration;
+use tokio::io::{AsyncReadExt, AsyncWriteExt};
+use tokio::net::{TcpListener, TcpStream};
+use url::Url;
+
+use crate::check::render_url;
+use crate::config::UrlConfig;
+use crate::export::{html_page, side_by_side};
+use crate::extract::Filter;
+use crate::output::escape_html;
+use crate::params::Params;
+use crate::progress::Outcome;
+use crate::state::{History, Revision, State};
+
+/// Maximum size of a request’s headers.
+const MAX_REQUEST_SIZE: usize = 8192;
+
+/// How long to wait for a client to send its request.
+const REQUEST_TIMEOUT: Duration = Duration::from_secs(10);
+
+/// The dashboard for a daemon.
+#[derive(Debug)]
+pub struct Dashboard<'a> {
+    /// Parameters passed on the command line.
+    params: &'a Params,
+
+    /// The URLs being monitored.
+    url_configs: &'a [UrlConfig],
+
+    /// State directory.
+    state: State,
+
+    /// The result of the last check of each URL in this process.
+    checks: Mutex<HashMap<Url, LastCheck>>,
+}
+
+/// The result of the last check of a URL.
+#[derive(Clone, Debug)]
+struct LastCheck {
+    /// When the check finished.
+    at: DateTime<Utc>,
+
+    /// What happened.
+    outcome: Outcome,
+
+    /// The error, if there was one.
+    error: Option<String>,
+}
+
+impl<'a> Dashboard<'a> {
+    /// Create a dashboard for `url_configs`.
+    #[must_use]
+    pub fn new(params: &'a Params, url_configs: &'a [UrlConfig]) -> Self {
+        Self {
+            params,
+            url_configs,
+            state: State::new(params.state_dir_path()),
+            checks: Mutex::new(HashMap::new()),
+        }
+    }
+
+    /// Record the result of checking a URL.
+    pub fn record(
+        &self,
+        url_config: &UrlConfig,
+        outcome: Outcome,
+        error: Option<String>,
+    ) {
+        self.checks
+            .lock()
+            .unwrap_or_else(PoisonError::into_inner)
+            .insert(
+                url_config.url.clone(),
+                LastCheck { at: Utc::now(), outcome, error },
+            );
+    }
+
+    /// Get the result of the last check of a URL, if it’s been checked.
+    fn last_check(&self, url_config: &UrlConfig) -> Option<LastCheck> {
+        self.checks
+            .lock()
+            .unwrap_or_else(PoisonError::into_inner)
+            .get(&url_config.url)
+            .cloned()
+    }
+
+    /// Serve the dashboard on `listener` forever.
+    ///
+    /// Errors handling individual requests are logged.
+    pub async fn serve(&self, listener: TcpListener) -> ! {
+        loop {
+            let stream = match listener.accept().await {
+                Ok((stream, _)) => stream,
+                Err(error) => {
+                    tracing::warn!(
+                        "Error accepting dashboard connection: {error}"
+                    );
+                    continue;
+                }
+            };
+            let handled =
+                tokio::time::timeout(REQUEST_TIMEOUT, self.handle(stream));
+            match handled.await {
+                Ok(Ok(())) => {}
+                Ok(Err(error)) => {
+                    tracing::warn!("Error handling dashboard request: {error}");
+                }
+                Err(_) => tracing::debug!("Dashboard request timed out"),
+            }
+        }
+    }
+
+    /// Read a request from a connection and respond to it.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if the connection failed.
+    async fn handle(&self, mut stream: TcpStream) -> std::io::Result<()> {
+        let mut request = Vec::new();
+        let mut buffer = [0; 1024];
+        while !request.windows(4).any(|window| window == b"\r\n\r\n") {
+            let read = stream.read(&mut buffer).await?;
+            if read == 0 {
+                return Ok(());
+            }
+            request.extend_from_slice(buffer.get(..read).unwrap_or_default());
+            if request.len() > MAX_REQUEST_SIZE {
+                break;
+            }
+        }
+
+        let request = String::from_utf8_lossy(&request);
+        let mut words = request.lines().next().unwrap_or_default().split(' ');
+        let method = words.next().unwrap_or_default();
+        let target = words.next().unwrap_or_default();
+        tracing::debug!("Dashboard request: {method} {target}");
+        let (status, body) = match method {
+            "GET" | "HEAD" => self.page(target),
+            _ => error_page(StatusCode::METHOD_NOT_ALLOWED),
+        };
+
+        let mut response = format!(
+            "HTTP/1.1 {status}\r\nContent-Type: text/html; charset=utf-8\r\n\
+            Content-Length: {}\r\nConnection: close\r\n\r\n",
+            body.len(),
+        );
+        if method != "HEAD" {
+            response.push_str(&body);
+        }
+        stream.write_all(response.as_bytes()).await?;
+        stream.shutdown().await
+    }
+
+    /// Generate the page for a request target, e.g. `/url/0`.
+    fn page(&self, target: &str) -> (StatusCode, String) {
+        let path = target.split(['?', '#']).next().unwrap_or_default();
+        let segments: Vec<&str> = path
+            .split('/')
+            .filter(|segment| !segment.is_empty())
+            .collect();
+        let url_config = |index: &str| {
+            index
+                .parse::<usize>()
+                .ok()
+                .and_then(|i| self.url_configs.get(i))
+        };
+        let page = match segments.as_slice() {
+            [] => Some(self.index()),
+            ["url", index] => url_config(index)
+                .map(|url_config| self.url_page(index, url_config)),
+            ["url", index, rev] => url_config(index).and_then(|url_config| {
+                self.change_page(url_config, rev.parse().ok()?)
+            }),
+            _ => None,
+        };
+        page.map_or_else(
+            || error_page(StatusCode::NOT_FOUND),
+            |page| (StatusCode::OK, page),
+        )
+    }
+
+    /// Generate the index of all URLs.
+    fn index(&self) -> String {
+        let mut body = String::from(
+            "<h1>monitorbot</h1>\n<table>\n<tr><th>URL</th><th>Last check</th>\
+            <th>Status</th><th>Checked</th><th>Changed</th></tr>\n",
+        );
+        for (i, url_config) in self.url_configs.iter().enumerate() {
+            let history = self.history(url_config);
+            let latest = history.as_ref().and_then(History::latest);
+            let check = self.last_check(url_config);
+            let check = check.as_ref();
+            let _ = writeln!(
+                body,
+                "<tr><td><a href=\"/url/{i}\">{}</a></td><td{}>{}</td>\
+                <td>{}</td><td>{}</td><td>{}</td></tr>",
+                escape_html(url_config.label()),
+                check
+                    .and_then(|check| check.error.as_deref())
+                    .map(|error| format!(" title=\"{}\"", escape_html(error)))
+                    .unwrap_or_default(),
+                check.map_or_else(
+                    || "not checked yet".to_owned(),
+                    |check| check.outcome.to_string(),
+                ),
+                latest
+                    .map(|revision| revision.status.as_str().to_owned())
+                    .unwrap_or_default(),
+                check
+                    .map(|check| check.at)
+                    .or_else(|| latest.map(|revision| revision.fetched_at))
+                    .map(format_time)
+                    .unwrap_or_default(),
+                history
+                    .as_ref()
+                    .and_then(|history| history.changes().last().copied())
+                    .map(|(_, revision)| format_time(revision.fetched_at))
+                    .unwrap_or_default(),
+            );
+        }
+        body.push_str("</table>\n");
+        html_page("monitorbot", &body)
+    }
+
+    /// Generate the page for a URL, listing its changes.
+    fn url_page(&self, index: &str, url_config: &UrlConfig) -> String {
+        let label = url_config.label();
+        let mut body = format!(
+            "<p><a href=\"/\">All URLs</a></p>\n<h1>{}</h1>\n\
+            <p><a href=\"{url}\">{url}</a></p>\n",
+            escape_html(label),
+            url = escape_html(url_config.url.as_str()),
+        );
+        if let Some(error) =
+            self.last_check(url_config).and_then(|check| check.error)
+        {
+            let _ =
+                writeln!(body, "<p>Last error: {}</p>", escape_html(&error));
+        }
+
+        let history = self.history(url_config).unwrap_or_default();
+        let changes = history.changes();
+        if changes.is_empty() {
+            body.push_str("<p>No responses stored yet.</p>\n");
+        } else {
+            body.push_str("<ul>\n");
+            for (rev, revision) in changes.into_iter().rev() {
+                let _ = writeln!(
+                    body,
+                    "<li><a href=\"/url/{index}/{rev}\">{}</a> ({})</li>",
+                    format_time(revision.fetched_at),
+                    revision.status,
+                );
+            }
+            body.push_str("</ul>\n");
+        }
+        html_page(label, &body)
+    }
+
+    /// Generate the page showing the change in a revision.
+    ///
+    /// `rev` is numbered from 1 for the oldest revision, like `show --rev`.
+    /// Returns `None` if there is no such revision.
+    fn change_page(
+        &self,
+        url_config: &UrlConfig,
+        rev: usize,
+    ) -> Option<String> {
+        let history = self.history(url_config)?;
+        let index = rev.checked_sub(1)?;
+        let revision = history.revisions.get(index)?;
+        let old = index
+            .checked_sub(1)
+            .and_then(|old| history.revisions.get(old));
+
+        let label = url_config.label();
+        let diff =
+            self.render(url_config, old, revision)
+                .unwrap_or_else(|error| {
+                    format!(
+                        "<p>Could not render: {}</p>",
+                        escape_html(&format!("{error:#}"))
+                    )
+                });
+        Some(html_page(
+            label,
+            &format!(
+                "<p><a href=\"/\">All URLs</a></p>\n<h1>{}</h1>\n\
+                <p><a href=\"{url}\">{url}</a> at {} ({})</p>\n{diff}\n",
+                escape_html(label),
+                format_time(revision.fetched_at),
+                revision.status,
+                url = escape_html(revision.url.as_str()),
+            ),
+        ))
+    }
+
+    /// Render two revisions side by side.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if either response couldn’t be loaded or rendered.
+    fn render(
+        &self,
+        url_config: &UrlConfig,
+        old: Option<&Revision>,
+        new: &Revision,
+    ) -> anyhow::Result<String> {
+        let filter = Filter::new(self.params, url_config)?;
+        let render = |revision: &Revision| -> anyhow::Result<String> {
+            let response = self
+                .state
+                .load_response(revision, self.params.max_body_memory)?;
+            if response.spooled.is_some() {
+                anyhow::bail!("body is too large to render");
+            }
+            render_url(url_config, &response, filter)
+        };
+        let old = old.map(render).transpose()?.unwrap_or_default();
+        Ok(side_by_side(&old, &render(new)?))
+    }
+
+    /// Load the history of a URL, logging any error.
+    fn history(&self, url_config: &UrlConfig) -> Option<History> {
+        self.state
+            .load_history(&url_config.url, true)
+            .unwrap_or_else(|error| {
+                tracing::warn!(
+                    "Could not read history for {}: {error:#}",
+                    url_config.label(),
+                );
+                None
+            })
+    }
+}
+
+/// Generate a page for an error status.
+fn error_page(status: StatusCode) -> (StatusCode, String) {
+    let title = status.to_string();
+    let body = format!(
+        "<p><a href=\"/\">All URLs</a></p>\n<h1>{}</h1>\n",
+        escape_html(&title),
+    );
+    (status, html_page(&title, &body))
+}
+
+/// Format a time in the local time zone.
+fn format_time(time: DateTime<Utc>) -> String {
+    time.with_timezone(&Local)
+        .format("%Y-%m-%d %H:%M:%S")
+        .to_string()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::params::Parser;
+    use crate::response::Response;
+    use assert2::check;
+
+    #[test]
+    fn test_pages() {
+        let dir = std::env::temp_dir()
+            .join(format!("monitorbot-{}-dashboard", std::process::id()));
+        let _ = std::fs::remove_dir_all(&dir);
+        let params = Params::parse_from([
+            "monitorbot".as_ref(),
+            "--state-dir".as_ref(),
+            dir.as_os_str(),
+        ]);
+        let url: Url = "https://example.com/".parse().unwrap();
+        let url_configs = [UrlConfig::new(url.clone())];
+        let dashboard = Dashboard::new(&params, &url_configs);
+        dashboard.state.create().unwrap();
+
+        let mut history = History::default();
+        for body in ["<p>old</p>", "<p>old</p>", "<p>new</p>"] {
+            let response = Response {
+                url: url.clone(),
+                version: http::Version::HTTP_11,
+                status: StatusCode::OK,
+                headers: http::HeaderMap::new(),
+                body: body.into(),
+                spooled: None,
+                certificate_sha256: None,
+                peer_address: None,
+            };
+            dashboard.state.save_body(&response.body).unwrap();
+            history
+                .revisions
+                .push(Revision::new(&response, &response.body));
+        }
+        dashboard.state.save_history(&url, &history).unwrap();
+        check!(
+            history
+                .changes()
+                .iter()
+                .map(|(rev, _)| *rev)
+                .collect::<Vec<_>>()
+                == [1, 3]
+        );
+
+        dashboard.record(&url_configs[0], Outcome::Changed, None);
+        let (status, index) = dashboard.page("/");
+        check!(status == StatusCode::OK);
+        check!(index.contains("<a href=\"/url/0\">https://example.com/</a>"));
+        check!(index.contains("<td>changed</td><td>200</td>"));
+
+        let (_, page) = dashboard.page("/url/0");
+        check!(page.contains("<a href=\"/url/0/3\">"));
+        check!(page.contains("<a href=\"/url/0/1\">"));
+        check!(!page.contains("<a href=\"/url/0/2\">"));
+
+        let (_, diff) = dashboard.page("/url/0/3?x");
+        check!(
+            diff.contains(
+                "<td class=\"del\">old</td><td class=\"ins\">new</td>"
+            )
+        );
+
+        check!(dashboard.page("/url/1").0 == StatusCode::NOT_FOUND);
+        check!(dashboard.page("/url/0/4").0 == StatusCode::NOT_FOUND);
+        check!(dashboard.page("/other").0 == StatusCode::NOT_FOUND);
+
+        std::fs::remove_dir_all(dir).unwrap();
+    }
+}
@@ -0,0 +1,192 @@
+//! Digests of changes (`monitorbot digest`).
+//!
+//! A digest summarizes how each URL changed since the last digest in one
+//! message, grouped by tag, rather than one notification per change. It’s
+//! meant to be run once a day, e.g. late at night from cron.
+//!
+//! When the last digest was made is kept in `digest.ron` in the state
+//! directory.
+
+use chrono::{DateTime, Local, Utc};
+use std::collections::BTreeMap;
+use std::fmt::Write as _;
+use std::fs;
+use std::io;
+use std::path::{Path, PathBuf};
+
+use crate::notify::{Message, Notification};
+use crate::output::escape_html;
+use crate::state::atomic_write;
+use crate::summarize::Limits;
+
+/// Heading for URLs without tags.
+const UNTAGGED: &str = "Untagged";
+
+/// When the last digest was made.
+#[derive(Clone, Copy, Debug, serde::Serialize, serde::Deserialize)]
+pub struct Record {
+    /// When the last digest was made. The next one covers changes since then.
+    pub made_at: DateTime<Utc>,
+}
+
+impl Record {
+    /// Get the path to the record within a state directory.
+    #[must_use]
+    pub fn path(state_dir: &Path) -> PathBuf {
+        state_dir.join("digest.ron")
+    }
+
+    /// Load the record from the state directory.
+    ///
+    /// Returns `None` if no digest has been made.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if the record exists but couldn’t be read or parsed.
+    pub fn load(state_dir: &Path) -> anyhow::Result<Option<Self>> {
+        match fs::read(Self::path(state_dir)) {
+            Ok(source) => Ok(Some(ron::de::from_bytes(&source)?)),
+            Err(error) if error.kind() == io::ErrorKind::NotFound => Ok(None),
+            Err(error) => Err(error.into()),
+        }
+    }
+
+    /// Save the record to the state directory.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if the record could not be written.
+    pub fn save(&self, state_dir: &Path) -> anyhow::Result<()> {
+        let contents = ron::ser::to_string_pretty(
+            self,
+            ron::ser::PrettyConfig::default(),
+        )?;
+        atomic_write(&Self::path(state_dir), contents.as_bytes())?;
+        Ok(())
+    }
+}
+
+/// Group changes by tag.
+///
+/// Changes with several tags are in each of their groups. Changes without tags
+/// are in a last group, [`UNTAGGED`].
+#[must_use]
+pub fn by_tag(changes: &[Notification]) -> Vec<(&str, Vec<&Notification>)> {
+    let mut groups: BTreeMap<&str, Vec<&Notification>> = BTreeMap::new();
+    let mut untagged = Vec::new();
+    for change in changes {
+        if change.tags.is_empty() {
+            untagged.push(change);
+        }
+        for tag in &change.tags {
+            groups.entry(tag).or_default().push(change);
+        }
+    }
+    let mut groups = groups.into_iter().collect::<Vec<_>>();
+    if !untagged.is_empty() {
+        groups.push((UNTAGGED, untagged));
+    }
+    groups
+}
+
+/// Make a digest message of the changes since `since`.
+///
+/// Each diff is shortened to fit within `limits`.
+#[must_use]
+pub fn message(
+    changes: &[Notification],
+    since: DateTime<Utc>,
+    limits: Limits,
+) -> Message {
+    let since = since.with_timezone(&Local).format("%Y-%m-%d %H:%M");
+    let title = match changes.len() {
+        0 => format!("No changes since {since}"),
+        1 => format!("1 URL changed since {since}"),
+        n => format!("{n} URLs changed since {since}"),
+    };
+    let mut text = format!("{title}\n");
+    let mut html = format!("<h1>{}</h1>", escape_html(&title));
+    for (tag, changes) in by_tag(changes) {
+        let _ = write!(text, "\n## {tag}\n");
+        let _ = write!(html, "<h2>{}</h2>", escape_html(tag));
+        for change in changes {
+            let summary = change.summarized_text(limits);
+            let _ = write!(
+                text,
+                "\n=== {} ({})\n{summary}",
+                change.label, change.url,
+            );
+            let _ = write!(
+                html,
+                "<p><a href=\"{}\">{}</a></p>\
+                <pre><code class=\"language-diff\">{}</code></pre>",
+                escape_html(change.url.as_str()),
+                escape_html(&change.label),
+                escape_html(&summary),
+            );
+        }
+    }
+
+    let severity = changes
+        .iter()
+        .map(|change| change.severity)
+        .max()
+        .unwrap_or_default();
+    Message { title, text, html, severity }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::notify::{ChangeKind, Event, Severity};
+    use assert2::check;
+
+    #[test]
+    fn test_message() {
+        let change = |label: &str, tags: &[&str], severity| Notification {
+            label: label.to_owned(),
+            url: format!("https://example.com/{label}").parse().unwrap(),
+            text: "-old\n+new\n".to_owned(),
+            detected_at: Utc::now(),
+            event: Event::Changed,
+            kind: Some(ChangeKind::Content),
+            tags: tags.iter().map(|&tag| tag.to_owned()).collect(),
+            severity,
+            diff_path: None,
+            status: None,
+        };
+        let changes = [
+            change("a", &[], Severity::Normal),
+            change("b", &["security", "docs"], Severity::High),
+            change("c", &["docs"], Severity::Low),
+        ];
+
+        let groups = by_tag(&changes)
+            .into_iter()
+            .map(|(tag, changes)| {
+                let labels = changes
+                    .iter()
+                    .map(|change| change.label.as_str())
+                    .collect::<Vec<_>>();
+                (tag, labels)
+            })
+            .collect::<Vec<_>>();
+        check!(
+            groups
+                == [
+                    ("docs", vec!["b", "c"]),
+                    ("security", vec!["b"]),
+                    ("Untagged", vec!["a"]),
+                ]
+        );
+
+        let since = DateTime::from_timestamp(0, 0).unwrap();
+        let message = message(&changes, since, Limits::default());
+        check!(message.title.starts_with("3 URLs changed since "));
+        check!(message.severity == Severity::High);
+        check!(message.text.contains(
+            "\n## docs\n\n=== b (https://example.com/b)\n+1 -1 lines\n-old\n+new\n"
+        ));
+        check!(message.html.contains("<h2>Untagged</h2>"));
+    }
+}
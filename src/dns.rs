@@ -0,0 +1,286 @@
+//! Resolving and pinning host names.
+//!
+//! The addresses a host resolves to are recorded with each revision so that
+//! changes (e.g. moving to a new CDN) can be reported. `--resolve` pins a host
+//! to specific addresses, which is useful for monitoring an origin server
+//! behind a CDN.
+//!
+//! `--ipv4` and `--ipv6`, or `address_family` for a URL, restrict connections
+//! to one address family. This is useful for dual-stacked services, where the
+//! IPv4 and IPv6 servers might serve different content. The address that
+//! served each response is recorded, so the family can be reported either way.
+
+use std::collections::BTreeMap;
+use std::fmt;
+use std::io;
+use std::net::{IpAddr, SocketAddr};
+use std::str::FromStr;
+use url::Url;
+
+/// An IP address family.
+#[derive(
+    Clone,
+    Copy,
+    Debug,
+    Eq,
+    Hash,
+    PartialEq,
+    serde::Deserialize,
+    serde::Serialize,
+)]
+#[serde(rename_all = "lowercase")]
+pub enum AddressFamily {
+    /// IPv4.
+    Ipv4,
+
+    /// IPv6.
+    Ipv6,
+}
+
+impl AddressFamily {
+    /// Get the family of an address.
+    #[must_use]
+    pub const fn of(addr: IpAddr) -> Self {
+        match addr {
+            IpAddr::V4(_) => Self::Ipv4,
+            IpAddr::V6(_) => Self::Ipv6,
+        }
+    }
+
+    /// Check if an address is in this family.
+    #[must_use]
+    pub fn contains(self, addr: IpAddr) -> bool {
+        Self::of(addr) == self
+    }
+}
+
+impl fmt::Display for AddressFamily {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(match self {
+            Self::Ipv4 => "IPv4",
+            Self::Ipv6 => "IPv6",
+        })
+    }
+}
+
+/// A host pinned to an address with `--resolve`.
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub struct Pin {
+    /// The host name, in lowercase.
+    pub host: String,
+
+    /// The port.
+    pub port: u16,
+
+    /// The address to connect to.
+    pub addr: IpAddr,
+}
+
+impl FromStr for Pin {
+    type Err = String;
+
+    /// Parse `HOST:PORT:ADDR`, like curl’s `--resolve`. IPv6 addresses may be
+    /// in brackets.
+    fn from_str(source: &str) -> Result<Self, Self::Err> {
+        let mut parts = source.splitn(3, ':');
+        let (Some(host), Some(port), Some(addr)) =
+            (parts.next(), parts.next(), parts.next())
+        else {
+            return Err("expected HOST:PORT:ADDR".to_owned());
+        };
+        if host.is_empty() {
+            return Err("host must not be empty".to_owned());
+        }
+
+        let port = port
+            .parse()
+            .map_err(|error| format!("invalid port {port:?}: {error}"))?;
+        let addr = addr
+            .strip_prefix('[')
+            .and_then(|addr| addr.strip_suffix(']'))
+            .unwrap_or(addr);
+        let addr = addr
+            .parse()
+            .map_err(|error| format!("invalid address {addr:?}: {error}"))?;
+        Ok(Self { host: host.to_ascii_lowercase(), port, addr })
+    }
+}
+
+impl fmt::Display for Pin {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self.addr {
+            IpAddr::V4(addr) => write!(f, "{}:{}:{addr}", self.host, self.port),
+            IpAddr::V6(addr) => {
+                write!(f, "{}:{}:[{addr}]", self.host, self.port)
+            }
+        }
+    }
+}
+
+/// Group pins by host, as they’re passed to [`reqwest::ClientBuilder`].
+///
+/// Only pins in `family` are included, if it’s passed.
+#[must_use]
+pub fn pins_by_host(
+    pins: &[Pin],
+    family: Option<AddressFamily>,
+) -> BTreeMap<&str, Vec<SocketAddr>> {
+    let mut by_host = BTreeMap::<&str, Vec<SocketAddr>>::new();
+    for pin in pins
+        .iter()
+        .filter(|pin| family.is_none_or(|family| family.contains(pin.addr)))
+    {
+        by_host
+            .entry(&pin.host)
+            .or_default()
+            .push(SocketAddr::new(pin.addr, pin.port));
+    }
+    by_host
+}
+
+/// Look up the addresses for the host in a URL.
+///
+/// Pinned hosts return their pinned addresses without a lookup. Only addresses
+/// in `family` are returned, if it’s passed. The result is sorted and has no
+/// duplicates. URLs without a host return nothing.
+///
+/// # Errors
+///
+/// Returns an error if the host couldn’t be resolved.
+pub async fn lookup(
+    url: &Url,
+    pins: &[Pin],
+    family: Option<AddressFamily>,
+) -> io::Result<Vec<IpAddr>> {
+    let mut addrs = match url.host() {
+        None => Vec::new(),
+        Some(url::Host::Ipv4(addr)) => vec![IpAddr::V4(addr)],
+        Some(url::Host::Ipv6(addr)) => vec![IpAddr::V6(addr)],
+        Some(url::Host::Domain(domain)) => {
+            let domain = domain.to_ascii_lowercase();
+            let pinned: Vec<_> = pins
+                .iter()
+                .filter(|pin| pin.host == domain)
+                .map(|pin| pin.addr)
+                .collect();
+            if pinned.is_empty() {
+                tokio::net::lookup_host((domain, 0))
+                    .await?
+                    .map(|addr| addr.ip())
+                    .collect()
+            } else {
+                pinned
+            }
+        }
+    };
+    addrs.retain(|addr| family.is_none_or(|family| family.contains(*addr)));
+    addrs.sort_unstable();
+    addrs.dedup();
+    Ok(addrs)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use assert2::check;
+
+    #[test]
+    fn test_pin() {
+        let pin: Pin = "Example.com:443:192.0.2.1".parse().unwrap();
+        check!(pin.host == "example.com");
+        check!(pin.port == 443);
+        check!(pin.to_string() == "example.com:443:192.0.2.1");
+
+        let pin: Pin = "example.com:80:[2001:db8::1]".parse().unwrap();
+        check!(pin.addr == "2001:db8::1".parse::<IpAddr>().unwrap());
+        check!(pin.to_string() == "example.com:80:[2001:db8::1]");
+
+        check!("example.com:443".parse::<Pin>().is_err());
+        check!("example.com:https:192.0.2.1".parse::<Pin>().is_err());
+        check!("example.com:443:example.net".parse::<Pin>().is_err());
+        check!(":443:192.0.2.1".parse::<Pin>().is_err());
+    }
+
+    #[test]
+    fn test_address_family() {
+        let v4: IpAddr = "192.0.2.1".parse().unwrap();
+        let v6: IpAddr = "2001:db8::1".parse().unwrap();
+        let mapped: IpAddr = "::ffff:192.0.2.1".parse().unwrap();
+        check!(AddressFamily::of(v4) == AddressFamily::Ipv4);
+        check!(AddressFamily::of(v6) == AddressFamily::Ipv6);
+        check!(AddressFamily::of(mapped) == AddressFamily::Ipv6);
+        check!(AddressFamily::Ipv4.contains(v4));
+        check!(!AddressFamily::Ipv4.contains(v6));
+        check!(AddressFamily::Ipv6.to_string() == "IPv6");
+
+        // The command line overrides the config.
+        let config: crate::config::Config = toml::from_str(
+            "[[url]]\nurl = \"https://example.com/\"\n\
+            address_family = \"ipv6\"\n\
+            [[url]]\nurl = \"https://example.net/\"",
+        )
+        .unwrap();
+        let [ipv6, any] = &config.urls[..] else {
+            panic!("expected 2 URLs");
+        };
+        let family = |args: &[&str]| {
+            let params = <crate::params::Params as clap::Parser>::parse_from(
+                std::iter::once("monitorbot").chain(args.iter().copied()),
+            );
+            (
+                params.url_address_family(ipv6),
+                params.url_address_family(any),
+            )
+        };
+        check!(family(&[]) == (Some(AddressFamily::Ipv6), None));
+        check!(
+            family(&["-4"])
+                == (Some(AddressFamily::Ipv4), Some(AddressFamily::Ipv4))
+        );
+        check!(
+            family(&["--ipv6"])
+                == (Some(AddressFamily::Ipv6), Some(AddressFamily::Ipv6))
+        );
+        check!(
+            toml::from_str::<crate::config::Config>(
+                "[[url]]\nurl = \"https://example.com/\"\n\
+            address_family = \"ipx\""
+            )
+            .is_err()
+        );
+    }
+
+    #[tokio::test]
+    async fn test_lookup() {
+        let pins = ["example.com:443:192.0.2.2", "example.com:80:192.0.2.1"]
+            .map(|pin| pin.parse::<Pin>().unwrap());
+        let lookup = async |url: &str, family| {
+            lookup(&url.parse().unwrap(), &pins, family)
+                .await
+                .unwrap()
+                .iter()
+                .map(ToString::to_string)
+                .collect::<Vec<_>>()
+        };
+
+        check!(
+            lookup("https://EXAMPLE.com/", None).await
+                == ["192.0.2.1", "192.0.2.2"]
+        );
+        check!(
+            lookup("http://[2001:db8::1]:8080/", None).await == ["2001:db8::1"]
+        );
+        check!(lookup("http://127.0.0.1/", None).await == ["127.0.0.1"]);
+
+        let v6 = Some(AddressFamily::Ipv6);
+        check!(lookup("https://example.com/", v6).await.is_empty());
+        check!(
+            lookup("http://[2001:db8::1]:8080/", v6).await == ["2001:db8::1"]
+        );
+        check!(
+            pins_by_host(&pins, Some(AddressFamily::Ipv4))["example.com"].len()
+                == 2
+        );
+        check!(pins_by_host(&pins, v6).is_empty());
+    }
+}
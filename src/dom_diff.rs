@@ -0,0 +1,475 @@
+//! Structural diffing of HTML documents.
+//!
+//! This compares DOM trees rather than rendered Markdown, and reports changed
+//! elements by their CSS path.
+
+use std::fmt::{self, Write as _};
+use std::io;
+use termcolor::{Color, ColorSpec};
+
+/// Elements that are skipped entirely when comparing documents.
+const SKIPPED_ELEMENTS: &[&str] = &["script", "style", "noscript", "template"];
+
+/// Maximum length of text included in a change summary.
+const SUMMARY_LEN: usize = 60;
+
+/// A simplified DOM node.
+#[derive(Clone, Debug, Eq, PartialEq)]
+enum Node {
+    /// An element with its attributes and children.
+    Element {
+        /// Tag name.
+        name: String,
+
+        /// Attributes, sorted by name.
+        attrs: Vec<(String, String)>,
+
+        /// Child nodes.
+        children: Vec<Self>,
+    },
+
+    /// Text with whitespace collapsed.
+    Text(String),
+}
+
+impl Node {
+    /// Convert a [`scraper`] node into a simplified node.
+    ///
+    /// Returns `None` for nodes that should be ignored.
+    fn from_scraper(
+        node: ego_tree::NodeRef<'_, scraper::Node>,
+    ) -> Option<Self> {
+        match node.value() {
+            scraper::Node::Element(element) => {
+                let name = element.name().to_owned();
+                if SKIPPED_ELEMENTS.contains(&name.as_str()) {
+                    return None;
+                }
+                let mut attrs: Vec<(String, String)> = element
+                    .attrs()
+                    .map(|(key, value)| (key.to_owned(), value.to_owned()))
+                    .collect();
+                attrs.sort();
+                let children =
+                    node.children().filter_map(Self::from_scraper).collect();
+                Some(Self::Element { name, attrs, children })
+            }
+            scraper::Node::Text(text) => {
+                let text = collapse_whitespace(text);
+                (!text.is_empty()).then_some(Self::Text(text))
+            }
+            _ => None,
+        }
+    }
+
+    /// Get the name of the element, if this is an element.
+    fn name(&self) -> Option<&str> {
+        match self {
+            Self::Element { name, .. } => Some(name),
+            Self::Text(_) => None,
+        }
+    }
+
+    /// Get an attribute of the element, if this is an element.
+    fn attr(&self, key: &str) -> Option<&str> {
+        match self {
+            Self::Element { attrs, .. } => attrs
+                .iter()
+                .find(|(name, _)| name == key)
+                .map(|(_, value)| value.as_str()),
+            Self::Text(_) => None,
+        }
+    }
+
+    /// Get the text content of the node with whitespace collapsed.
+    fn text(&self) -> String {
+        match self {
+            Self::Text(text) => text.clone(),
+            Self::Element { children, .. } => children
+                .iter()
+                .map(Self::text)
+                .filter(|text| !text.is_empty())
+                .collect::<Vec<_>>()
+                .join(" "),
+        }
+    }
+
+    /// A short description of the node for reports.
+    fn summary(&self) -> String {
+        let text = truncate(&self.text());
+        match self {
+            Self::Text(_) => format!("{text:?}"),
+            Self::Element { name, .. } if text.is_empty() => {
+                format!("<{name}>")
+            }
+            Self::Element { name, .. } => format!("<{name}> {text:?}"),
+        }
+    }
+}
+
+/// The kind of change made to an element.
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub enum ChangeKind {
+    /// The element or text was added.
+    Added(String),
+
+    /// The element or text was removed.
+    Removed(String),
+
+    /// The element or text changed from the first value to the second.
+    Changed(String, String),
+}
+
+/// A change to the document.
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub struct Change {
+    /// CSS path to the element that changed (or that contains the change).
+    pub path: String,
+
+    /// What changed.
+    pub kind: ChangeKind,
+}
+
+impl fmt::Display for Change {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match &self.kind {
+            ChangeKind::Added(summary) => {
+                write!(f, "+ {}: {summary}", self.path)
+            }
+            ChangeKind::Removed(summary) => {
+                write!(f, "- {}: {summary}", self.path)
+            }
+            ChangeKind::Changed(old, new) => {
+                write!(f, "~ {}: {old} → {new}", self.path)
+            }
+        }
+    }
+}
+
+/// Compare two HTML documents and return the changes between them.
+#[must_use]
+pub fn diff(old_html: &str, new_html: &str) -> Vec<Change> {
+    let old = parse(old_html);
+    let new = parse(new_html);
+    let mut changes = Vec::new();
+    diff_children(&mut changes, "", &old, &new);
+    changes
+}
+
+/// Parse an HTML document into a list of top level nodes.
+fn parse(html: &str) -> Vec<Node> {
+    if html.is_empty() {
+        return Vec::new();
+    }
+    let document = scraper::Html::parse_document(html);
+    document
+        .tree
+        .root()
+        .children()
+        .filter_map(Node::from_scraper)
+        .collect()
+}
+
+/// Compare the children of two elements at `path`.
+fn diff_children(
+    changes: &mut Vec<Change>,
+    path: &str,
+    old: &[Node],
+    new: &[Node],
+) {
+    let mut removed: Vec<(usize, &Node)> = Vec::new();
+    let mut added: Vec<(usize, &Node)> = Vec::new();
+    let mut old_index = 0_usize;
+    let mut new_index = 0_usize;
+
+    for result in diff::slice(old, new) {
+        match result {
+            diff::Result::Left(node) => {
+                removed.push((old_index, node));
+                old_index = old_index.saturating_add(1);
+            }
+            diff::Result::Right(node) => {
+                added.push((new_index, node));
+                new_index = new_index.saturating_add(1);
+            }
+            diff::Result::Both(_, _) => {
+                pair_changes(changes, path, old, new, &removed, &added);
+                removed.clear();
+                added.clear();
+                old_index = old_index.saturating_add(1);
+                new_index = new_index.saturating_add(1);
+            }
+        }
+    }
+    pair_changes(changes, path, old, new, &removed, &added);
+}
+
+/// Report a run of removed and added nodes.
+///
+/// Removed and added nodes are paired up in order. Pairs of elements with the
+/// same tag and id are compared recursively; pairs of text nodes are reported
+/// as changed text. Anything left over is reported as added or removed.
+fn pair_changes(
+    changes: &mut Vec<Change>,
+    path: &str,
+    old_siblings: &[Node],
+    new_siblings: &[Node],
+    removed: &[(usize, &Node)],
+    added: &[(usize, &Node)],
+) {
+    let mut removed = removed.iter().peekable();
+    let mut added = added.iter().peekable();
+
+    loop {
+        match (removed.peek(), added.peek()) {
+            (Some((old_index, old)), Some((new_index, new))) => {
+                if let (Node::Text(old_text), Node::Text(new_text)) = (old, new)
+                {
+                    changes.push(Change {
+                        path: display_path(path),
+                        kind: ChangeKind::Changed(
+                            format!("{:?}", truncate(old_text)),
+                            format!("{:?}", truncate(new_text)),
+                        ),
+                    });
+                } else if old.name().is_some()
+                    && old.name() == new.name()
+                    && old.attr("id") == new.attr("id")
+                {
+                    let child_path =
+                        element_path(path, new_siblings, *new_index);
+                    diff_element(changes, &child_path, old, new);
+                } else {
+                    changes.push(removed_change(
+                        path,
+                        old_siblings,
+                        *old_index,
+                        old,
+                    ));
+                    changes.push(added_change(
+                        path,
+                        new_siblings,
+                        *new_index,
+                        new,
+                    ));
+                }
+                removed.next();
+                added.next();
+            }
+            (Some((old_index, old)), None) => {
+                changes.push(removed_change(
+                    path,
+                    old_siblings,
+                    *old_index,
+                    old,
+                ));
+                removed.next();
+            }
+            (None, Some((new_index, new))) => {
+                changes.push(added_change(path, new_siblings, *new_index, new));
+                added.next();
+            }
+            (None, None) => break,
+        }
+    }
+}
+
+/// Compare two elements with the same tag.
+fn diff_element(changes: &mut Vec<Change>, path: &str, old: &Node, new: &Node) {
+    let (
+        Node::Element { attrs: old_attrs, children: old_children, .. },
+        Node::Element { attrs: new_attrs, children: new_children, .. },
+    ) = (old, new)
+    else {
+        return;
+    };
+
+    if old_attrs != new_attrs {
+        changes.push(Change {
+            path: path.to_owned(),
+            kind: ChangeKind::Changed(
+                format_attrs(old_attrs),
+                format_attrs(new_attrs),
+            ),
+        });
+    }
+
+    diff_children(changes, path, old_children, new_children);
+}
+
+/// Build a change for a removed node.
+fn removed_change(
+    path: &str,
+    siblings: &[Node],
+    index: usize,
+    node: &Node,
+) -> Change {
+    Change {
+        path: node_path(path, siblings, index, node),
+        kind: ChangeKind::Removed(node.summary()),
+    }
+}
+
+/// Build a change for an added node.
+fn added_change(
+    path: &str,
+    siblings: &[Node],
+    index: usize,
+    node: &Node,
+) -> Change {
+    Change {
+        path: node_path(path, siblings, index, node),
+        kind: ChangeKind::Added(node.summary()),
+    }
+}
+
+/// Get the path to a node, which is the path to its parent for text.
+fn node_path(
+    path: &str,
+    siblings: &[Node],
+    index: usize,
+    node: &Node,
+) -> String {
+    if node.name().is_some() {
+        element_path(path, siblings, index)
+    } else {
+        display_path(path)
+    }
+}
+
+/// Get the CSS path to the element at `index` in `siblings`.
+///
+/// Elements are identified by id if they have one. Otherwise, they are
+/// identified by tag name and classes, with `:nth-of-type()` added if there
+/// are other siblings with the same tag name.
+fn element_path(parent: &str, siblings: &[Node], index: usize) -> String {
+    let Some(node) = siblings.get(index) else {
+        return display_path(parent);
+    };
+    let name = node.name().unwrap_or_default();
+
+    let mut segment = name.to_owned();
+    if let Some(id) = node.attr("id") {
+        segment.push('#');
+        segment.push_str(id);
+    } else {
+        if let Some(classes) = node.attr("class") {
+            for class in classes.split_whitespace() {
+                segment.push('.');
+                segment.push_str(class);
+            }
+        }
+
+        let same_type: Vec<usize> = siblings
+            .iter()
+            .enumerate()
+            .filter(|(_, sibling)| sibling.name() == Some(name))
+            .map(|(i, _)| i)
+            .collect();
+        if same_type.len() > 1
+            && let Some(position) = same_type.iter().position(|&i| i == index)
+        {
+            let _ =
+                write!(segment, ":nth-of-type({})", position.saturating_add(1));
+        }
+    }
+
+    if parent.is_empty() {
+        segment
+    } else {
+        format!("{parent} > {segment}")
+    }
+}
+
+/// Display a path, using `:root` for the empty path.
+fn display_path(path: &str) -> String {
+    if path.is_empty() {
+        ":root".to_owned()
+    } else {
+        path.to_owned()
+    }
+}
+
+/// Format attributes for display.
+fn format_attrs(attrs: &[(String, String)]) -> String {
+    let attrs: Vec<String> = attrs
+        .iter()
+        .map(|(key, value)| format!("{key}={value:?}"))
+        .collect();
+    format!("[{}]", attrs.join(" "))
+}
+
+/// Collapse runs of whitespace into single spaces and trim.
+fn collapse_whitespace(text: &str) -> String {
+    text.split_whitespace().collect::<Vec<_>>().join(" ")
+}
+
+/// Truncate text to [`SUMMARY_LEN`] characters.
+fn truncate(text: &str) -> String {
+    match text.char_indices().nth(SUMMARY_LEN) {
+        Some((index, _)) => format!("{}…", &text[..index]),
+        None => text.to_owned(),
+    }
+}
+
+/// Print a list of changes.
+///
+/// # Panics
+///
+/// Panics if the output couldn’t be written, like `println!`.
+pub fn print_changes<S>(out: &mut S, changes: &[Change])
+where
+    S: termcolor::WriteColor + io::Write + ?Sized,
+{
+    let mut removed_color = ColorSpec::new();
+    removed_color.set_fg(Some(Color::Red)).set_intense(true);
+    let mut added_color = ColorSpec::new();
+    added_color.set_fg(Some(Color::Green)).set_intense(true);
+    let mut changed_color = ColorSpec::new();
+    changed_color.set_fg(Some(Color::Yellow)).set_intense(true);
+
+    for change in changes {
+        let color = match change.kind {
+            ChangeKind::Added(_) => &added_color,
+            ChangeKind::Removed(_) => &removed_color,
+            ChangeKind::Changed(_, _) => &changed_color,
+        };
+        // Use `unwrap()` here because these would be IO errors, so we may as
+        // well act like `println!`.
+        out.set_color(color).unwrap();
+        writeln!(out, "{change}").unwrap();
+        out.reset().unwrap();
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use assert2::check;
+
+    /// Shortcut to diff and format changes as strings.
+    fn d(old: &str, new: &str) -> Vec<String> {
+        diff(old, new).iter().map(ToString::to_string).collect()
+    }
+
+    #[test]
+    fn test_diff() {
+        check!(d("<p>a</p>", "<p>a</p>").is_empty());
+        check!(
+            d("<p>a</p><p>b</p>", "<p>a</p><p>c</p>")
+                == [r#"~ html > body > p:nth-of-type(2): "b" → "c""#]
+        );
+        check!(
+            d("<ul><li>a</li></ul>", "<ul><li>a</li><li>b</li></ul>")
+                == [r#"+ html > body > ul > li:nth-of-type(2): <li> "b""#]
+        );
+        check!(
+            d(r#"<div id="x"><b>a</b></div>"#, r#"<div id="x"></div>"#)
+                == [r#"- html > body > div#x > b: <b> "a""#]
+        );
+        check!(
+            d(r#"<a href="/a">a</a>"#, r#"<a href="/b">a</a>"#)
+                == [r#"~ html > body > a: [href="/a"] → [href="/b"]"#]
+        );
+    }
+}
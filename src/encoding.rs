@@ -0,0 +1,279 @@
+//! Decoding `Content-Encoding`.
+//!
+//! monitorbot decodes compressed responses itself rather than leaving it to
+//! the HTTP library, so that the `Content-Encoding` a server used is kept with
+//! the response. With `watch_encoding = true`, changes to it are reported, as
+//! are bodies that are still compressed after decoding, which usually means a
+//! server or proxy compressed them twice.
+
+use std::io::{self, Write};
+
+use crate::response::Response;
+
+/// Value of `Accept-Encoding` for the encodings that can be decoded.
+pub const ACCEPT: &str = "zstd, br, gzip, deflate";
+
+/// A decoder for one content coding.
+enum Coding {
+    /// `gzip`.
+    Gzip(flate2::write::GzDecoder<Vec<u8>>),
+
+    /// `deflate`, which is actually the zlib format.
+    Deflate(flate2::write::ZlibDecoder<Vec<u8>>),
+
+    /// `br`.
+    Brotli(Box<brotli_decompressor::DecompressorWriter<Vec<u8>>>),
+
+    /// `zstd`.
+    Zstd(zstd::stream::write::Decoder<'static, Vec<u8>>),
+}
+
+impl Coding {
+    /// Start decoding a coding.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if the coding isn’t supported.
+    fn new(name: &str) -> io::Result<Self> {
+        Ok(match name {
+            "gzip" | "x-gzip" => {
+                Self::Gzip(flate2::write::GzDecoder::new(Vec::new()))
+            }
+            "deflate" => {
+                Self::Deflate(flate2::write::ZlibDecoder::new(Vec::new()))
+            }
+            "br" => Self::Brotli(Box::new(
+                brotli_decompressor::DecompressorWriter::new(Vec::new(), 4096),
+            )),
+            "zstd" => {
+                Self::Zstd(zstd::stream::write::Decoder::new(Vec::new())?)
+            }
+            _ => {
+                return Err(io::Error::new(
+                    io::ErrorKind::Unsupported,
+                    format!("unsupported Content-Encoding {name:?}"),
+                ));
+            }
+        })
+    }
+
+    /// Decode a chunk, and return what was decoded so far.
+    fn decode(&mut self, chunk: &[u8]) -> io::Result<Vec<u8>> {
+        let output = match self {
+            Self::Gzip(decoder) => {
+                decoder.write_all(chunk)?;
+                decoder.get_mut()
+            }
+            Self::Deflate(decoder) => {
+                decoder.write_all(chunk)?;
+                decoder.get_mut()
+            }
+            Self::Brotli(decoder) => {
+                decoder.write_all(chunk)?;
+                decoder.get_mut()
+            }
+            Self::Zstd(decoder) => {
+                decoder.write_all(chunk)?;
+                decoder.get_mut()
+            }
+        };
+        Ok(std::mem::take(output))
+    }
+
+    /// Finish decoding, and return the rest of the output.
+    fn finish(self) -> io::Result<Vec<u8>> {
+        match self {
+            Self::Gzip(decoder) => decoder.finish(),
+            Self::Deflate(decoder) => decoder.finish(),
+            Self::Brotli(mut decoder) => {
+                decoder.close()?;
+                decoder.into_inner().map_err(|_| {
+                    io::Error::new(
+                        io::ErrorKind::InvalidData,
+                        "brotli stream is incomplete",
+                    )
+                })
+            }
+            Self::Zstd(mut decoder) => {
+                decoder.flush()?;
+                Ok(decoder.into_inner())
+            }
+        }
+    }
+}
+
+/// Decodes a body according to its `Content-Encoding`, chunk by chunk.
+pub struct Decoder {
+    /// Decoders for each coding, in the order they have to be applied.
+    codings: Vec<Coding>,
+
+    /// Whether any of the body has been decoded. An empty body isn’t a valid
+    /// compressed stream, but it’s what `HEAD` and `304` responses have.
+    started: bool,
+}
+
+impl Decoder {
+    /// Start decoding a body with the `Content-Encoding` in `headers`.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if any of the codings isn’t supported.
+    pub fn new(headers: &http::HeaderMap) -> io::Result<Self> {
+        let codings = content_encoding(headers)
+            .iter()
+            .rev()
+            .map(|name| Coding::new(name))
+            .collect::<io::Result<_>>()?;
+        Ok(Self { codings, started: false })
+    }
+
+    /// Decode a chunk of the body, and return what was decoded so far.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if the body isn’t valid for its encoding.
+    pub fn decode(&mut self, chunk: &[u8]) -> io::Result<Vec<u8>> {
+        self.started |= !chunk.is_empty();
+        let mut output = chunk.to_vec();
+        for coding in &mut self.codings {
+            output = coding.decode(&output)?;
+        }
+        Ok(output)
+    }
+
+    /// Finish decoding, and return the rest of the body.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if the body was truncated.
+    pub fn finish(self) -> io::Result<Vec<u8>> {
+        if !self.started {
+            return Ok(Vec::new());
+        }
+        let mut output = Vec::new();
+        for mut coding in self.codings {
+            let mut rest = coding.decode(&output)?;
+            rest.extend(coding.finish()?);
+            output = rest;
+        }
+        Ok(output)
+    }
+}
+
+/// Get the codings in `Content-Encoding` in the order they were applied,
+/// without `identity`.
+#[must_use]
+pub fn content_encoding(headers: &http::HeaderMap) -> Vec<String> {
+    headers
+        .get_all(http::header::CONTENT_ENCODING)
+        .iter()
+        .filter_map(|value| value.to_str().ok())
+        .flat_map(|value| value.split(','))
+        .map(|name| name.trim().to_ascii_lowercase())
+        .filter(|name| !name.is_empty() && name != "identity")
+        .collect()
+}
+
+/// Describe the `Content-Encoding` in `headers`, e.g. `gzip` or `identity`.
+#[must_use]
+pub fn describe(headers: &http::HeaderMap) -> String {
+    let codings = content_encoding(headers);
+    if codings.is_empty() {
+        "identity".to_owned()
+    } else {
+        codings.join(", ")
+    }
+}
+
+/// Check if a decoded body is still compressed, and return the format if it
+/// is.
+///
+/// Bodies whose content type says they’re compressed files are expected to
+/// be compressed, so they’re ignored.
+///
+/// # Errors
+///
+/// Returns an error if the `Content-Type` is invalid.
+pub fn double_encoded(
+    response: &Response,
+) -> anyhow::Result<Option<&'static str>> {
+    if matches!(
+        response.media_type()?.as_deref(),
+        Some(
+            "application/gzip"
+                | "application/x-gzip"
+                | "application/zstd"
+                | "application/octet-stream"
+        )
+    ) {
+        return Ok(None);
+    }
+    Ok(if response.body.starts_with(&[0x1f, 0x8b, 0x08]) {
+        Some("gzip")
+    } else if response.body.starts_with(&[0x28, 0xb5, 0x2f, 0xfd]) {
+        Some("zstd")
+    } else {
+        None
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use assert2::check;
+
+    /// Compress with gzip.
+    fn gzip(data: &[u8]) -> Vec<u8> {
+        let mut encoder = flate2::write::GzEncoder::new(
+            Vec::new(),
+            flate2::Compression::default(),
+        );
+        encoder.write_all(data).unwrap();
+        encoder.finish().unwrap()
+    }
+
+    /// Decode a body in chunks of `size` bytes.
+    fn decode(encoding: &str, body: &[u8], size: usize) -> io::Result<Vec<u8>> {
+        let mut headers = http::HeaderMap::new();
+        headers
+            .insert(http::header::CONTENT_ENCODING, encoding.parse().unwrap());
+        let mut decoder = Decoder::new(&headers)?;
+        let mut output = Vec::new();
+        for chunk in body.chunks(size) {
+            output.extend(decoder.decode(chunk)?);
+        }
+        output.extend(decoder.finish()?);
+        Ok(output)
+    }
+
+    #[test]
+    fn test_decoder() {
+        let text = "Hello, world! ".repeat(100);
+        let gzipped = gzip(text.as_bytes());
+        check!(decode("gzip", &gzipped, 7).unwrap() == text.as_bytes());
+        check!(decode("identity", b"plain", 2).unwrap() == b"plain");
+        check!(decode("gzip", b"", 2).unwrap() == b"");
+
+        // Codings are listed in the order they were applied.
+        let zstd = zstd::encode_all(&gzipped[..], 0).unwrap();
+        check!(decode("gzip, zstd", &zstd, 5).unwrap() == text.as_bytes());
+
+        let truncated = &gzipped[..20];
+        check!(decode("gzip", truncated, 7).is_err());
+        check!(decode("compress", b"", 1).is_err());
+
+        let mut response = Response {
+            url: "https://example.com/".parse().unwrap(),
+            version: http::Version::HTTP_11,
+            status: http::StatusCode::OK,
+            headers: http::HeaderMap::new(),
+            body: gzipped.into(),
+            spooled: None,
+            certificate_sha256: None,
+            peer_address: None,
+        };
+        check!(double_encoded(&response).unwrap() == Some("gzip"));
+        response.body = text.into();
+        check!(double_encoded(&response).unwrap() == None);
+    }
+}
@@ -0,0 +1,219 @@
+//! Errors checking a URL.
+//!
+//! Errors from the core of checking a URL are classified by what went wrong,
+//! so that callers can tell a temporary network failure that’s worth retrying
+//! from a problem with the configuration that will fail the same way every
+//! time. Each variant keeps the full chain of context for its message.
+
+use std::fmt;
+use std::io;
+use thiserror::Error;
+
+use crate::request::RedirectError;
+use crate::response::{DownloadError, ResponseError};
+
+/// An error checking a URL.
+#[derive(Error, Debug)]
+pub enum MonitorError {
+    /// The URL, or something needed to fetch it, couldn’t be downloaded.
+    #[error(transparent)]
+    Network(anyhow::Error),
+
+    /// The response couldn’t be interpreted, e.g. it had an invalid charset.
+    #[error(transparent)]
+    Decode(anyhow::Error),
+
+    /// The state directory couldn’t be read or written.
+    #[error(transparent)]
+    Storage(anyhow::Error),
+
+    /// The response couldn’t be rendered, compared, or output.
+    #[error(transparent)]
+    Render(anyhow::Error),
+
+    /// The configuration for the URL is invalid.
+    #[error(transparent)]
+    Config(anyhow::Error),
+
+    /// HTTP redirects looped, or there were more than `max_redirects`. The
+    /// chain includes a [`RedirectError`] with every URL requested.
+    #[error(transparent)]
+    Redirect(anyhow::Error),
+}
+
+impl MonitorError {
+    /// Get the kind of error.
+    #[must_use]
+    pub const fn kind(&self) -> ErrorKind {
+        match self {
+            Self::Network(_) => ErrorKind::Network,
+            Self::Decode(_) => ErrorKind::Decode,
+            Self::Storage(_) => ErrorKind::Storage,
+            Self::Render(_) => ErrorKind::Render,
+            Self::Config(_) => ErrorKind::Config,
+            Self::Redirect(_) => ErrorKind::Redirect,
+        }
+    }
+
+    /// Check if the same check might succeed if it’s tried again later.
+    ///
+    /// Only network errors are retryable, and not when the response was
+    /// larger than `max_download_size`, since it will probably still be too
+    /// large.
+    #[must_use]
+    pub fn is_retryable(&self) -> bool {
+        let Self::Network(error) = self else {
+            return false;
+        };
+        !error.chain().any(|source| {
+            matches!(
+                source.downcast_ref::<DownloadError>(),
+                Some(DownloadError::TooLarge { .. })
+            ) || source
+                .downcast_ref::<reqwest::Error>()
+                .is_some_and(reqwest::Error::is_builder)
+        })
+    }
+
+    /// Summarize the error for machine-readable output.
+    #[must_use]
+    pub fn report(&self) -> ErrorReport {
+        ErrorReport {
+            kind: self.kind(),
+            retryable: self.is_retryable(),
+            message: format!("{self:#}"),
+        }
+    }
+}
+
+impl From<reqwest::Error> for MonitorError {
+    fn from(error: reqwest::Error) -> Self {
+        let error = anyhow::Error::from(error);
+        if error
+            .chain()
+            .any(<dyn std::error::Error>::is::<RedirectError>)
+        {
+            Self::Redirect(error)
+        } else {
+            Self::Network(error)
+        }
+    }
+}
+
+impl From<DownloadError> for MonitorError {
+    fn from(error: DownloadError) -> Self {
+        match error {
+            DownloadError::Spool(_) => Self::Storage(error.into()),
+            DownloadError::Decode(_) => Self::Decode(error.into()),
+            DownloadError::Request(_)
+            | DownloadError::TooLarge { .. }
+            | DownloadError::File { .. } => Self::Network(error.into()),
+        }
+    }
+}
+
+impl From<ResponseError> for MonitorError {
+    fn from(error: ResponseError) -> Self {
+        Self::Decode(error.into())
+    }
+}
+
+impl From<io::Error> for MonitorError {
+    fn from(error: io::Error) -> Self {
+        Self::Storage(error.into())
+    }
+}
+
+/// The kind of a [`MonitorError`].
+#[derive(Clone, Copy, Debug, Eq, PartialEq, serde::Serialize)]
+#[serde(rename_all = "lowercase")]
+pub enum ErrorKind {
+    /// See [`MonitorError::Network`].
+    Network,
+
+    /// See [`MonitorError::Decode`].
+    Decode,
+
+    /// See [`MonitorError::Storage`].
+    Storage,
+
+    /// See [`MonitorError::Render`].
+    Render,
+
+    /// See [`MonitorError::Config`].
+    Config,
+
+    /// See [`MonitorError::Redirect`].
+    Redirect,
+}
+
+impl ErrorKind {
+    /// Get the name of the kind, as used in JSON output.
+    #[must_use]
+    pub const fn as_str(self) -> &'static str {
+        match self {
+            Self::Network => "network",
+            Self::Decode => "decode",
+            Self::Storage => "storage",
+            Self::Render => "render",
+            Self::Config => "config",
+            Self::Redirect => "redirect",
+        }
+    }
+}
+
+impl fmt::Display for ErrorKind {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(self.as_str())
+    }
+}
+
+/// A [`MonitorError`] summarized for machine-readable output.
+#[derive(Clone, Debug, serde::Serialize)]
+pub struct ErrorReport {
+    /// The kind of error.
+    pub kind: ErrorKind,
+
+    /// Whether the check might succeed if it’s tried again later.
+    pub retryable: bool,
+
+    /// The error message, including its causes.
+    pub message: String,
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use assert2::check;
+
+    #[test]
+    fn test_classification() {
+        let error = MonitorError::from(DownloadError::TooLarge {
+            max: 10,
+            received: 20,
+        });
+        check!(error.kind() == ErrorKind::Network);
+        check!(!error.is_retryable());
+
+        let error = MonitorError::Network(anyhow::anyhow!("connection reset"));
+        check!(error.is_retryable());
+        let report = error.report();
+        check!(report.retryable);
+        check!(report.message == "connection reset");
+
+        let error = MonitorError::from(DownloadError::Spool(io::Error::other(
+            "disk full",
+        )));
+        check!(error.kind() == ErrorKind::Storage);
+        check!(!error.is_retryable());
+
+        let error = MonitorError::from(ResponseError::InvalidCharset(
+            "klingon".to_owned(),
+        ));
+        check!(error.kind() == ErrorKind::Decode);
+        check!(
+            serde_json::to_value(error.report()).unwrap()["kind"] == "decode"
+        );
+        check!(!MonitorError::Config(anyhow::anyhow!("bad")).is_retryable());
+    }
+}
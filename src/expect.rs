@@ -0,0 +1,205 @@
+//! Checking responses against expectations and sanity checks in the
+//! configuration.
+
+use scraper::{Html, Selector};
+
+use crate::config::UrlConfig;
+use crate::response::Response;
+
+/// Check a response against the expectations for its URL.
+///
+/// Returns a message for each expectation that failed.
+///
+/// # Errors
+///
+/// Returns an error if `expect_selector` is invalid, or if the response
+/// couldn’t be decoded.
+pub fn check(
+    url_config: &UrlConfig,
+    response: &Response,
+) -> anyhow::Result<Vec<String>> {
+    let mut failures = Vec::new();
+
+    if !url_config.expect_status.is_empty()
+        && !url_config.expect_status.contains(&response.status.as_u16())
+    {
+        failures.push(format!(
+            "Expected status {}, got {}",
+            url_config
+                .expect_status
+                .iter()
+                .map(u16::to_string)
+                .collect::<Vec<_>>()
+                .join(" or "),
+            response.status,
+        ));
+    }
+
+    if url_config.expect_contains.is_none()
+        && url_config.expect_selector.is_none()
+    {
+        return Ok(failures);
+    }
+
+    if response.spooled.is_some() {
+        failures.push(format!(
+            "Body is {} bytes, too large to check expect_contains or \
+            expect_selector",
+            response.body_len(),
+        ));
+        return Ok(failures);
+    }
+
+    let text = response.text()?;
+    if let Some(expected) = &url_config.expect_contains
+        && !text.contains(expected.as_str())
+    {
+        failures.push(format!("Expected content to contain {expected:?}"));
+    }
+
+    if let Some(source) = &url_config.expect_selector
+        && !has_match(&text, source, "expect_selector")?
+    {
+        failures.push(format!("Expected an element matching {source:?}"));
+    }
+
+    Ok(failures)
+}
+
+/// Check a response against the sanity checks for its URL.
+///
+/// Responses that fail `min_size`, `max_size`, or `require_selector` are
+/// probably error pages or interstitials, so they shouldn’t replace the
+/// previous snapshot. Returns a reason for each check that failed.
+///
+/// # Errors
+///
+/// Returns an error if `require_selector` is invalid, or if the response
+/// couldn’t be decoded.
+pub fn reject(
+    url_config: &UrlConfig,
+    response: &Response,
+) -> anyhow::Result<Vec<String>> {
+    let mut reasons = Vec::new();
+    let size = u64::try_from(response.body_len()).unwrap_or(u64::MAX);
+
+    if let Some(min_size) = url_config.min_size
+        && size < min_size
+    {
+        reasons.push(format!(
+            "Body is {size} bytes, less than min_size of {min_size}"
+        ));
+    }
+
+    if let Some(max_size) = url_config.max_size
+        && size > max_size
+    {
+        reasons.push(format!(
+            "Body is {size} bytes, more than max_size of {max_size}"
+        ));
+    }
+
+    if url_config.require_selector.is_some() && response.spooled.is_some() {
+        reasons.push(format!(
+            "Body is {size} bytes, too large to check require_selector"
+        ));
+    } else if let Some(source) = &url_config.require_selector
+        && !has_match(&response.text()?, source, "require_selector")?
+    {
+        reasons.push(format!("No element matching {source:?}"));
+    }
+
+    Ok(reasons)
+}
+
+/// Check if an HTML document has an element matching a selector.
+///
+/// # Errors
+///
+/// Returns an error if the selector is invalid. `option` is the name of the
+/// configuration option it came from, for the error message.
+fn has_match(html: &str, source: &str, option: &str) -> anyhow::Result<bool> {
+    let selector = Selector::parse(source).map_err(|error| {
+        anyhow::anyhow!("invalid {option} {source:?}: {error}")
+    })?;
+    Ok(Html::parse_document(html)
+        .select(&selector)
+        .next()
+        .is_some())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use assert2::check;
+
+    #[test]
+    fn test_check() {
+        let url = "https://example.com/".parse().unwrap();
+        let mut url_config = UrlConfig::new(url);
+        let mut response = Response {
+            url: url_config.url.clone(),
+            version: http::Version::HTTP_11,
+            status: http::StatusCode::OK,
+            headers: http::HeaderMap::new(),
+            body: r#"<div id="main">© 2025</div>"#.into(),
+            spooled: None,
+            certificate_sha256: None,
+            peer_address: None,
+        };
+        check!(check(&url_config, &response).unwrap().is_empty());
+
+        url_config.expect_status = vec![200, 204];
+        url_config.expect_contains = Some("© 2025".to_owned());
+        url_config.expect_selector = Some("#main".to_owned());
+        check!(check(&url_config, &response).unwrap().is_empty());
+
+        response.status = http::StatusCode::NOT_FOUND;
+        response.body = "<p>Not found</p>".into();
+        check!(
+            check(&url_config, &response).unwrap()
+                == [
+                    "Expected status 200 or 204, got 404 Not Found",
+                    "Expected content to contain \"© 2025\"",
+                    "Expected an element matching \"#main\"",
+                ]
+        );
+
+        url_config.expect_selector = Some("[".to_owned());
+        check!(check(&url_config, &response).is_err());
+    }
+
+    #[test]
+    fn test_reject() {
+        let url = "https://example.com/".parse().unwrap();
+        let mut url_config = UrlConfig::new(url);
+        let response = Response {
+            url: url_config.url.clone(),
+            version: http::Version::HTTP_11,
+            status: http::StatusCode::OK,
+            headers: http::HeaderMap::new(),
+            body: "<p>Please wait…</p>".into(),
+            spooled: None,
+            certificate_sha256: None,
+            peer_address: None,
+        };
+        check!(reject(&url_config, &response).unwrap().is_empty());
+
+        url_config.min_size = Some(1000);
+        url_config.max_size = Some(10);
+        url_config.require_selector = Some("#main".to_owned());
+        check!(
+            reject(&url_config, &response).unwrap()
+                == [
+                    "Body is 21 bytes, less than min_size of 1000",
+                    "Body is 21 bytes, more than max_size of 10",
+                    "No element matching \"#main\"",
+                ]
+        );
+
+        url_config.min_size = Some(21);
+        url_config.max_size = Some(21);
+        url_config.require_selector = Some("p".to_owned());
+        check!(reject(&url_config, &response).unwrap().is_empty());
+    }
+}
@@ -0,0 +1,309 @@
+//! Writing changes to files for archiving and sharing.
+//!
+//! Each run gets its own directory inside `--output-dir`, named after the time
+//! the run started. Each change is written as a Markdown file and an HTML
+//! report, and `index.html` links to all of them.
+
+use chrono::{DateTime, Utc};
+use std::fmt::Write as _;
+use std::fs;
+use std::io;
+use std::path::{Path, PathBuf};
+use url::Url;
+
+use crate::config::UrlConfig;
+use crate::output::escape_html;
+
+/// Styles for the HTML reports.
+static STYLE: &str = "\
+body { font-family: sans-serif; margin: 2em; }
+table.diff { border-collapse: collapse; width: 100%; table-layout: fixed; }
+table.diff td { font-family: monospace; white-space: pre-wrap;
+  word-wrap: break-word; vertical-align: top; padding: 0 0.5em;
+  border-left: 1px solid #ccc; }
+td.del { background: #fdd; }
+td.ins { background: #dfd; }
+pre { background: #f6f6f6; padding: 1em; overflow-x: auto; }";
+
+/// Writes changes found during a run to a directory.
+#[derive(Debug)]
+pub struct Export {
+    /// The directory for this run.
+    dir: PathBuf,
+
+    /// When the run started.
+    started: DateTime<Utc>,
+
+    /// Changes written so far.
+    entries: Vec<Entry>,
+}
+
+/// A change that was written.
+#[derive(Debug)]
+struct Entry {
+    /// The label for the URL.
+    label: String,
+
+    /// The URL that changed.
+    url: Url,
+
+    /// File name of the change without an extension.
+    stem: String,
+}
+
+impl Export {
+    /// Prepare to write changes to a new directory inside `output_dir`.
+    ///
+    /// Nothing is created until a change is added.
+    #[must_use]
+    pub fn new(output_dir: &Path) -> Self {
+        let started = Utc::now();
+        Self {
+            dir: output_dir.join(started.format("%Y%m%dT%H%M%SZ").to_string()),
+            started,
+            entries: Vec::new(),
+        }
+    }
+
+    /// The directory for this run.
+    #[must_use]
+    pub fn dir(&self) -> &Path {
+        &self.dir
+    }
+
+    /// Write a change to Markdown and HTML files.
+    ///
+    /// `sides` is the old and new rendered content, if the change is a diff
+    /// of them. It’s used to show them side by side in the HTML report.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if the files could not be written.
+    pub fn add(
+        &mut self,
+        url_config: &UrlConfig,
+        text: &str,
+        sides: Option<&(String, String)>,
+    ) -> io::Result<()> {
+        fs::create_dir_all(&self.dir)?;
+
+        let label = url_config.label();
+        let stem = format!(
+            "{:02}-{}",
+            self.entries.len().saturating_add(1),
+            slug(label),
+        );
+        let now = Utc::now();
+
+        let fence = fence(text);
+        fs::write(
+            self.dir.join(format!("{stem}.md")),
+            format!(
+                "# {label}\n\n<{}> changed at {}.\n\n{fence}diff\n{text}\n\
+                {fence}\n",
+                url_config.url,
+                now.to_rfc3339(),
+                text = text.trim_end_matches('\n'),
+            ),
+        )?;
+
+        let body = if let Some((old, new)) = sides {
+            side_by_side(old, new)
+        } else {
+            format!("<pre>{}</pre>", escape_html(text))
+        };
+        fs::write(
+            self.dir.join(format!("{stem}.html")),
+            html_page(
+                label,
+                &format!(
+                    "<h1>{}</h1>\n<p><a href=\"{}\">{}</a> changed at {}.</p>\n\
+                    {body}\n",
+                    escape_html(label),
+                    escape_html(url_config.url.as_str()),
+                    escape_html(url_config.url.as_str()),
+                    now.to_rfc3339(),
+                ),
+            ),
+        )?;
+
+        self.entries.push(Entry {
+            label: label.to_owned(),
+            url: url_config.url.clone(),
+            stem,
+        });
+        Ok(())
+    }
+
+    /// Write the index page, if there were any changes.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if the index could not be written.
+    pub fn finish(&self) -> io::Result<()> {
+        if self.entries.is_empty() {
+            return Ok(());
+        }
+
+        let title = format!("Changes at {}", self.started.to_rfc3339());
+        let mut body = format!(
+            "<h1>{}</h1>\n<p>{} URL(s) changed.</p>\n<ul>\n",
+            escape_html(&title),
+            self.entries.len(),
+        );
+        for entry in &self.entries {
+            let _ = writeln!(
+                body,
+                "<li><a href=\"{stem}.html\">{}</a> (<a href=\"{}\">page</a>, \
+                <a href=\"{stem}.md\">Markdown</a>)</li>",
+                escape_html(&entry.label),
+                escape_html(entry.url.as_str()),
+                stem = escape_html(&entry.stem),
+            );
+        }
+        body.push_str("</ul>\n");
+
+        fs::write(self.dir.join("index.html"), html_page(&title, &body))
+    }
+}
+
+/// Wrap HTML in a complete page.
+#[must_use]
+pub fn html_page(title: &str, body: &str) -> String {
+    format!(
+        "<!DOCTYPE html>\n<html>\n<head>\n<meta charset=\"utf-8\">\n\
+        <title>{}</title>\n<style>\n{STYLE}\n</style>\n</head>\n<body>\n\
+        {body}</body>\n</html>\n",
+        escape_html(title),
+    )
+}
+
+/// Make a string safe to use in a file name.
+fn slug(label: &str) -> String {
+    let slug = label
+        .chars()
+        .map(|c| {
+            if c.is_ascii_alphanumeric() {
+                c.to_ascii_lowercase()
+            } else {
+                '-'
+            }
+        })
+        .collect::<String>();
+    let slug = slug
+        .split('-')
+        .filter(|part| !part.is_empty())
+        .collect::<Vec<_>>()
+        .join("-");
+    slug.chars().take(60).collect()
+}
+
+/// Choose a Markdown code fence that doesn’t appear in `text`.
+fn fence(text: &str) -> String {
+    let longest = text
+        .split(|c| c != '`')
+        .map(str::len)
+        .max()
+        .unwrap_or_default();
+    "`".repeat(longest.saturating_add(1).max(3))
+}
+
+/// Lines in a side by side diff: the old line and the new line.
+///
+/// `None` means there is no corresponding line on that side.
+type Row<'a> = (Option<&'a str>, Option<&'a str>);
+
+/// Pair up old and new lines for a side by side diff.
+///
+/// Runs of removed and added lines are shown next to each other.
+fn side_by_side_rows<'a>(old: &'a str, new: &'a str) -> Vec<Row<'a>> {
+    /// Add pending removed and added lines as rows.
+    fn flush<'a>(
+        rows: &mut Vec<Row<'a>>,
+        removed: &mut Vec<&'a str>,
+        added: &mut Vec<&'a str>,
+    ) {
+        let count = removed.len().max(added.len());
+        for i in 0..count {
+            rows.push((removed.get(i).copied(), added.get(i).copied()));
+        }
+        removed.clear();
+        added.clear();
+    }
+
+    // Avoid an empty row at the end for the final newline.
+    let old = old.strip_suffix('\n').unwrap_or(old);
+    let new = new.strip_suffix('\n').unwrap_or(new);
+
+    let mut rows = Vec::new();
+    let mut removed = Vec::new();
+    let mut added = Vec::new();
+    for result in diff::lines(old, new) {
+        match result {
+            diff::Result::Left(line) => removed.push(line),
+            diff::Result::Right(line) => added.push(line),
+            diff::Result::Both(line, _) => {
+                flush(&mut rows, &mut removed, &mut added);
+                rows.push((Some(line), Some(line)));
+            }
+        }
+    }
+    flush(&mut rows, &mut removed, &mut added);
+    rows
+}
+
+/// Generate an HTML table showing old and new content side by side.
+#[must_use]
+pub fn side_by_side(old: &str, new: &str) -> String {
+    let mut html = String::from(
+        "<table class=\"diff\">\n<tr><th>Old</th><th>New</th></tr>\n",
+    );
+    for (old_line, new_line) in side_by_side_rows(old, new) {
+        let changed = old_line != new_line;
+        let cell = |line: Option<&str>, class: &str| {
+            if changed {
+                format!(
+                    "<td class=\"{class}\">{}</td>",
+                    escape_html(line.unwrap_or_default()),
+                )
+            } else {
+                format!("<td>{}</td>", escape_html(line.unwrap_or_default()))
+            }
+        };
+        let _ = writeln!(
+            html,
+            "<tr>{}{}</tr>",
+            cell(old_line, "del"),
+            cell(new_line, "ins"),
+        );
+    }
+    html.push_str("</table>");
+    html
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use assert2::check;
+
+    #[test]
+    fn test_side_by_side_rows() {
+        check!(
+            side_by_side_rows("a\nb\nc\nd\n", "a\nB\nB2\nc\n")
+                == [
+                    (Some("a"), Some("a")),
+                    (Some("b"), Some("B")),
+                    (None, Some("B2")),
+                    (Some("c"), Some("c")),
+                    (Some("d"), None),
+                ]
+        );
+    }
+
+    #[test]
+    fn test_slug_and_fence() {
+        check!(slug("https://example.com/a?b=c") == "https-example-com-a-b-c");
+        check!(fence("no fences") == "```");
+        check!(fence("```rust\n````\n") == "`````");
+    }
+}
@@ -0,0 +1,338 @@
+//! Extracting the part of a page to monitor.
+//!
+//! A URL can set either `select`, a CSS selector, or `xpath`, an `XPath`
+//! expression. Only the matching parts of the page are rendered and compared.
+//! If it sets neither but has a fragment, e.g. `#pricing`, only the element
+//! with that ID, or the section under the heading it refers to, is used.
+//! Before that, cookie banners and consent dialogs can be removed with
+//! `strip_boilerplate`.
+
+use anyhow::{anyhow, bail};
+use scraper::{ElementRef, Html, Selector};
+use skyscraper::xpath::grammar::data_model::XpathItem;
+use skyscraper::xpath::grammar::{DisplayFormatting, XpathItemTreeNode};
+use std::borrow::Cow;
+
+use crate::boilerplate;
+use crate::config::UrlConfig;
+use crate::filters::Pipeline;
+use crate::inline_json::InlineJson;
+use crate::output::escape_html;
+use crate::params::Params;
+
+/// How to filter HTML pages before rendering and comparing them.
+#[derive(Clone, Copy, Debug, Default)]
+pub struct Filter<'a> {
+    /// Remove cookie banners and consent dialogs.
+    pub strip_boilerplate: bool,
+
+    /// Only keep the matching parts of the page.
+    pub extract: Option<Extract<'a>>,
+
+    /// Further steps from the URL’s `filters`.
+    pub pipeline: Option<&'a Pipeline>,
+
+    /// Render JSON embedded in the page instead of the page itself.
+    pub inline_json: Option<&'a InlineJson>,
+}
+
+impl<'a> Filter<'a> {
+    /// Get the filter for a URL.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if both `select` and `xpath` are set.
+    pub fn new(
+        params: &Params,
+        url_config: &'a UrlConfig,
+    ) -> anyhow::Result<Self> {
+        Ok(Self {
+            strip_boilerplate: params.strip_boilerplate
+                || url_config.strip_boilerplate,
+            extract: Extract::from_config(url_config)?,
+            pipeline: Some(&url_config.filters),
+            inline_json: url_config.inline_json.as_ref(),
+        })
+    }
+
+    /// Filter an HTML document.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if the extraction failed; see [`Extract::apply()`].
+    pub fn apply(self, html: Cow<'_, str>) -> anyhow::Result<Cow<'_, str>> {
+        let html = if self.strip_boilerplate {
+            boilerplate::strip(&html).into()
+        } else {
+            html
+        };
+        let html = match self.extract {
+            Some(extract) => extract.apply(&html)?.into(),
+            None => html,
+        };
+        match self.pipeline {
+            Some(pipeline) => pipeline.apply_html(html),
+            None => Ok(html),
+        }
+    }
+
+    /// Filter rendered text with the text steps from `filters`.
+    #[must_use]
+    pub fn apply_text(self, text: String) -> String {
+        match self.pipeline {
+            Some(pipeline) => pipeline.apply_text(text),
+            None => text,
+        }
+    }
+}
+
+/// How to find the part of a page to monitor.
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+pub enum Extract<'a> {
+    /// A CSS selector.
+    Css(&'a str),
+
+    /// An `XPath` expression.
+    Xpath(&'a str),
+
+    /// The fragment of the URL, without the `#`.
+    ///
+    /// This matches the element with the ID, or the section under a heading
+    /// with the ID or with text that would be turned into the ID. If nothing
+    /// matches, the whole page is used.
+    Fragment(&'a str),
+}
+
+impl<'a> Extract<'a> {
+    /// Get the extraction configured for a URL, if any.
+    ///
+    /// If neither `select` nor `xpath` is set, and `filters` doesn’t select
+    /// part of the page, the fragment of the URL is used, unless it looks
+    /// like a route in a single page app (e.g. `#/about`).
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if both `select` and `xpath` are set.
+    pub fn from_config(
+        url_config: &'a UrlConfig,
+    ) -> anyhow::Result<Option<Self>> {
+        match (&url_config.select, &url_config.xpath) {
+            (Some(_), Some(_)) => {
+                bail!("only one of select and xpath may be set")
+            }
+            (Some(source), None) => Ok(Some(Self::Css(source))),
+            (None, Some(source)) => Ok(Some(Self::Xpath(source))),
+            (None, None) if url_config.filters.has_extract() => Ok(None),
+            (None, None) => Ok(url_config
+                .url
+                .fragment()
+                .filter(|fragment| {
+                    !fragment.is_empty() && !fragment.starts_with(['/', '!'])
+                })
+                .map(Self::Fragment)),
+        }
+    }
+
+    /// Extract the matching parts of an HTML document as HTML.
+    ///
+    /// Matches are separated by newlines. Matches that aren’t elements, e.g.
+    /// text nodes, attributes, or the result of `string()`, are included as
+    /// escaped text.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if the selector or expression is invalid, or couldn’t
+    /// be evaluated.
+    pub fn apply(self, html: &str) -> anyhow::Result<String> {
+        match self {
+            Self::Css(source) => {
+                let selector = Selector::parse(source).map_err(|error| {
+                    anyhow!("invalid select {source:?}: {error}")
+                })?;
+                Ok(Html::parse_document(html)
+                    .select(&selector)
+                    .map(|element| element.html())
+                    .collect::<Vec<_>>()
+                    .join("\n"))
+            }
+            Self::Xpath(source) => {
+                let xpath =
+                    skyscraper::xpath::parse(source).map_err(|error| {
+                        anyhow!("invalid xpath {source:?}: {error}")
+                    })?;
+                let tree = skyscraper::html::parse(html).map_err(|error| {
+                    anyhow!("could not parse HTML: {error}")
+                })?;
+                let items = xpath.apply(&tree).map_err(|error| {
+                    anyhow!("could not evaluate xpath {source:?}: {error}")
+                })?;
+                Ok(items
+                    .into_iter()
+                    .filter_map(|item| match item {
+                        XpathItem::Node(XpathItemTreeNode::AttributeNode(
+                            attribute,
+                        )) => Some(escape_html(&attribute.value)),
+                        XpathItem::Node(XpathItemTreeNode::TextNode(text)) => {
+                            Some(escape_html(&text.content))
+                        }
+                        XpathItem::Node(node) => {
+                            Some(node.display(&tree, DisplayFormatting::Raw, 0))
+                        }
+                        XpathItem::AnyAtomicType(value) => {
+                            Some(escape_html(&value.to_string()))
+                        }
+                        XpathItem::Function(_) => None,
+                    })
+                    .collect::<Vec<_>>()
+                    .join("\n"))
+            }
+            Self::Fragment(fragment) => Ok(section(html, fragment)
+                .unwrap_or_else(|| {
+                    tracing::info!(
+                        "Nothing matches #{fragment}; using the whole page"
+                    );
+                    html.to_owned()
+                })),
+        }
+    }
+}
+
+/// Find the part of an HTML document that a fragment refers to.
+///
+/// This is the element with the fragment as its ID (or an `<a>` with it as its
+/// name), or if there isn’t one, the heading with text that [`slug()`] turns
+/// into the fragment. If the match is a heading or is inside one, this returns
+/// the heading and everything after it up to the next heading of the same or
+/// a higher level.
+fn section(html: &str, fragment: &str) -> Option<String> {
+    let document = Html::parse_document(html);
+    let elements = || {
+        document
+            .root_element()
+            .descendants()
+            .filter_map(ElementRef::wrap)
+    };
+    let target = elements()
+        .find(|element| {
+            let value = element.value();
+            value.id() == Some(fragment)
+                || (value.name() == "a" && value.attr("name") == Some(fragment))
+        })
+        .or_else(|| {
+            let fragment = fragment.to_lowercase();
+            elements().find(|element| {
+                heading_level(*element).is_some()
+                    && slug(&element.text().collect::<String>()) == fragment
+            })
+        })?;
+
+    let heading = std::iter::once(target)
+        .chain(target.ancestors().filter_map(ElementRef::wrap))
+        .find_map(|element| Some((element, heading_level(element)?)));
+    let Some((heading, level)) = heading else {
+        return Some(target.html());
+    };
+
+    let mut section = heading.html();
+    for sibling in heading.next_siblings() {
+        if let Some(element) = ElementRef::wrap(sibling) {
+            if heading_level(element).is_some_and(|other| other <= level) {
+                break;
+            }
+            section.push_str(&element.html());
+        } else if let Some(text) = sibling.value().as_text() {
+            section.push_str(&escape_html(text));
+        }
+    }
+    Some(section)
+}
+
+/// Get the level of a heading element, e.g. 2 for `<h2>`.
+fn heading_level(element: ElementRef<'_>) -> Option<u8> {
+    match element.value().name() {
+        "h1" => Some(1),
+        "h2" => Some(2),
+        "h3" => Some(3),
+        "h4" => Some(4),
+        "h5" => Some(5),
+        "h6" => Some(6),
+        _ => None,
+    }
+}
+
+/// Turn heading text into an ID the way most Markdown renderers do, e.g.
+/// “Plans & Pricing” becomes `plans--pricing`.
+fn slug(text: &str) -> String {
+    text.trim()
+        .chars()
+        .filter_map(|c| {
+            if c.is_alphanumeric() || c == '-' || c == '_' {
+                Some(c.to_lowercase().next().unwrap_or(c))
+            } else if c.is_whitespace() {
+                Some('-')
+            } else {
+                None
+            }
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use assert2::check;
+
+    #[test]
+    fn test_apply() {
+        let html = r#"<ul>
+            <li class="x">One</li>
+            <li data-price="5">Two &amp; more</li>
+            <li>Three</li>
+        </ul>"#;
+
+        check!(
+            Extract::Css("li.x, li[data-price]").apply(html).unwrap()
+                == "<li class=\"x\">One</li>\n\
+                    <li data-price=\"5\">Two &amp; more</li>"
+        );
+        check!(
+            Extract::Xpath("//li[contains(text(), 'Two')]")
+                .apply(html)
+                .unwrap()
+                == "<li data-price=\"5\">Two &amp; more</li>"
+        );
+        check!(
+            Extract::Xpath("//li[3]/text()").apply(html).unwrap() == "Three"
+        );
+        check!(Extract::Xpath("//li/@data-price").apply(html).unwrap() == "5");
+        check!(Extract::Css("li[").apply(html).is_err());
+        check!(Extract::Xpath("//li[").apply(html).is_err());
+    }
+
+    #[test]
+    fn test_fragment() {
+        let html = "<h1>Product</h1><p>Intro</p>\
+            <h2 id=\"features\">Features</h2><p>Fast &amp; small</p>\
+            <h3>Details</h3><p>More</p>\
+            <h2><a name=\"faq\"></a>FAQ</h2><p>Questions</p>\
+            <h2>Plans &amp; Pricing</h2><p>$5</p>\
+            <div id=\"footer\"><p>Contact</p></div>";
+        let apply = |fragment| Extract::Fragment(fragment).apply(html).unwrap();
+
+        check!(
+            apply("features")
+                == "<h2 id=\"features\">Features</h2><p>Fast &amp; small</p>\
+                    <h3>Details</h3><p>More</p>"
+        );
+        check!(
+            apply("faq") == "<h2><a name=\"faq\"></a>FAQ</h2><p>Questions</p>"
+        );
+        check!(
+            apply("plans--pricing")
+                == "<h2>Plans &amp; Pricing</h2><p>$5</p>\
+                    <div id=\"footer\"><p>Contact</p></div>"
+        );
+        check!(apply("footer") == "<div id=\"footer\"><p>Contact</p></div>");
+        check!(apply("missing") == html);
+    }
+}
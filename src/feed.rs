@@ -0,0 +1,157 @@
+//! Atom feed of detected changes.
+//!
+//! Entries are stored in the state directory in `feed.ron`, and the Atom XML
+//! is regenerated from them after each run.
+
+use chrono::{DateTime, Utc};
+use std::fmt::Write as _;
+use std::fs;
+use std::io;
+use std::path::{Path, PathBuf};
+use url::Url;
+
+use crate::notify::{ChangeKind, Event, Notification};
+use crate::output::escape_html;
+use crate::state::{atomic_write, sha256_hex};
+
+/// Maximum number of entries to keep in the feed.
+const MAX_ENTRIES: usize = 100;
+
+/// An entry in the feed.
+#[derive(Clone, Debug, serde::Serialize, serde::Deserialize)]
+pub struct Entry {
+    /// Unique ID for the entry.
+    pub id: String,
+
+    /// Title of the entry.
+    pub title: String,
+
+    /// The URL that changed.
+    pub url: Url,
+
+    /// When the change was detected.
+    pub updated: DateTime<Utc>,
+
+    /// Plain text describing the change, e.g. a diff.
+    pub text: String,
+
+    /// What happened, e.g. `changed` or `failed`.
+    #[serde(default)]
+    pub event: Event,
+
+    /// What kind of change it was, if it was a change.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub kind: Option<ChangeKind>,
+}
+
+/// The feed of changes.
+#[derive(Clone, Debug, Default, serde::Serialize, serde::Deserialize)]
+pub struct Feed {
+    /// Entries, newest first.
+    pub entries: Vec<Entry>,
+}
+
+impl Feed {
+    /// Get the path to the stored feed entries within a state directory.
+    #[must_use]
+    pub fn state_path(state_dir: &Path) -> PathBuf {
+        state_dir.join("feed.ron")
+    }
+
+    /// Load the feed entries from the state directory.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if the entries exist but couldn’t be read or parsed.
+    pub fn load(state_dir: &Path) -> anyhow::Result<Self> {
+        match fs::read(Self::state_path(state_dir)) {
+            Ok(source) => Ok(ron::de::from_bytes(&source)?),
+            Err(error) if error.kind() == io::ErrorKind::NotFound => {
+                Ok(Self::default())
+            }
+            Err(error) => Err(error.into()),
+        }
+    }
+
+    /// Add an entry for a notification.
+    pub fn add(&mut self, notification: &Notification) {
+        let updated = notification.detected_at;
+        let id = sha256_hex(
+            format!("{}\n{}", notification.url, updated.to_rfc3339())
+                .as_bytes(),
+        );
+        self.entries.insert(
+            0,
+            Entry {
+                id: format!("urn:monitorbot:{id}"),
+                title: notification.summary(),
+                url: notification.url.clone(),
+                updated,
+                text: notification.text.clone(),
+                event: notification.event,
+                kind: notification.kind,
+            },
+        );
+        self.entries.truncate(MAX_ENTRIES);
+    }
+
+    /// Save the entries to the state directory, and write the Atom XML to
+    /// `output`.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if either file could not be written.
+    pub fn save(&self, state_dir: &Path, output: &Path) -> anyhow::Result<()> {
+        let contents = ron::ser::to_string_pretty(
+            self,
+            ron::ser::PrettyConfig::default(),
+        )?;
+        atomic_write(&Self::state_path(state_dir), contents.as_bytes())?;
+        atomic_write(output, self.to_atom().as_bytes())?;
+        Ok(())
+    }
+
+    /// Generate Atom XML for the feed.
+    pub fn to_atom(&self) -> String {
+        let updated = self
+            .entries
+            .first()
+            .map_or_else(Utc::now, |entry| entry.updated);
+
+        let mut xml = String::new();
+        xml.push_str(
+            "<?xml version=\"1.0\" encoding=\"utf-8\"?>\n\
+            <feed xmlns=\"http://www.w3.org/2005/Atom\">\n\
+            \x20 <id>urn:monitorbot:feed</id>\n\
+            \x20 <title>monitorbot changes</title>\n\
+            \x20 <author><name>monitorbot</name></author>\n",
+        );
+        let _ = writeln!(xml, "  <updated>{}</updated>", updated.to_rfc3339());
+
+        for entry in &self.entries {
+            let _ = write!(
+                xml,
+                "  <entry>\n\
+                \x20   <id>{}</id>\n\
+                \x20   <title>{}</title>\n\
+                \x20   <link href=\"{}\"/>\n\
+                \x20   <updated>{}</updated>\n\
+                \x20   <category term=\"{}\"/>\n\
+                \x20   <content type=\"html\">{}</content>\n\
+                \x20 </entry>\n",
+                escape_html(&entry.id),
+                escape_html(&entry.title),
+                escape_html(entry.url.as_str()),
+                entry.updated.to_rfc3339(),
+                entry.kind.map_or(entry.event.as_str(), ChangeKind::as_str),
+                escape_html(&format!(
+                    "<pre>{}</pre>",
+                    escape_html(&entry.text)
+                )),
+            );
+        }
+
+        xml.push_str("</feed>\n");
+        xml
+    }
+}
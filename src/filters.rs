@@ -0,0 +1,278 @@
+//! Configurable steps to normalize content before comparing it.
+//!
+//! A URL’s `filters` is a list of steps that are applied in order, e.g.
+//! `["selector:#main", "strip-regex:\\d{2}:\\d{2}", "lowercase"]`. Steps that
+//! work on HTML (`selector:`, `xpath:`, and `strip-boilerplate`) are applied to
+//! HTML pages before they’re rendered, so they must come before steps that work
+//! on the rendered text, which are applied to every kind of content.
+
+use anyhow::{anyhow, bail};
+use regex::Regex;
+use scraper::Selector;
+use std::borrow::Cow;
+
+use crate::boilerplate;
+use crate::extract::Extract;
+
+/// An ordered list of steps.
+#[derive(Clone, Debug, Default, serde::Deserialize, serde::Serialize)]
+#[serde(try_from = "Vec<String>", into = "Vec<String>")]
+pub struct Pipeline {
+    /// The source, for serializing.
+    source: Vec<String>,
+
+    /// Steps to apply to HTML before it’s rendered.
+    html: Vec<HtmlStep>,
+
+    /// Steps to apply to the rendered text.
+    text: Vec<TextStep>,
+}
+
+/// A step that works on HTML.
+#[derive(Clone, Debug)]
+enum HtmlStep {
+    /// `selector:CSS`: keep only the elements matching a CSS selector.
+    Selector(String),
+
+    /// `xpath:EXPRESSION`: keep only the matches for an `XPath` expression.
+    Xpath(String),
+
+    /// `strip-boilerplate`: remove cookie banners and consent dialogs.
+    StripBoilerplate,
+}
+
+/// A step that works on rendered text.
+#[derive(Clone, Debug)]
+enum TextStep {
+    /// `strip-regex:REGEX`: remove everything matching a regular expression.
+    StripRegex(Regex),
+
+    /// `lowercase`: make everything lowercase.
+    Lowercase,
+
+    /// `collapse-whitespace`: replace runs of whitespace within lines with a
+    /// single space, trim lines, and replace runs of blank lines with one.
+    CollapseWhitespace,
+}
+
+impl Pipeline {
+    /// Make an empty pipeline.
+    #[must_use]
+    pub const fn new() -> Self {
+        Self { source: Vec::new(), html: Vec::new(), text: Vec::new() }
+    }
+
+    /// Check if there are no steps.
+    #[must_use]
+    pub const fn is_empty(&self) -> bool {
+        self.source.is_empty()
+    }
+
+    /// Check if there’s a step that selects part of the page.
+    #[must_use]
+    pub fn has_extract(&self) -> bool {
+        self.html.iter().any(|step| {
+            matches!(step, HtmlStep::Selector(_) | HtmlStep::Xpath(_))
+        })
+    }
+
+    /// Apply the HTML steps to an HTML document.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if an extraction failed; see [`Extract::apply()`].
+    pub fn apply_html<'a>(
+        &self,
+        mut html: Cow<'a, str>,
+    ) -> anyhow::Result<Cow<'a, str>> {
+        for step in &self.html {
+            html = match step {
+                HtmlStep::Selector(source) => {
+                    Extract::Css(source).apply(&html)?.into()
+                }
+                HtmlStep::Xpath(source) => {
+                    Extract::Xpath(source).apply(&html)?.into()
+                }
+                HtmlStep::StripBoilerplate => boilerplate::strip(&html).into(),
+            };
+        }
+        Ok(html)
+    }
+
+    /// Apply the text steps to rendered text.
+    #[must_use]
+    pub fn apply_text(&self, mut text: String) -> String {
+        for step in &self.text {
+            text = match step {
+                TextStep::StripRegex(regex) => {
+                    regex.replace_all(&text, "").into_owned()
+                }
+                TextStep::Lowercase => text.to_lowercase(),
+                TextStep::CollapseWhitespace => collapse_whitespace(&text),
+            };
+        }
+        text
+    }
+}
+
+impl TryFrom<Vec<String>> for Pipeline {
+    type Error = String;
+
+    fn try_from(source: Vec<String>) -> Result<Self, Self::Error> {
+        let mut pipeline = Self::default();
+        for step in &source {
+            let (name, argument) = match step.split_once(':') {
+                Some((name, argument)) => (name, Some(argument)),
+                None => (step.as_str(), None),
+            };
+            let html = match (name, argument) {
+                ("selector", Some(source)) => {
+                    Selector::parse(source).map_err(|error| {
+                        format!("invalid selector {source:?}: {error}")
+                    })?;
+                    Some(HtmlStep::Selector(source.to_owned()))
+                }
+                ("xpath", Some(source)) => {
+                    skyscraper::xpath::parse(source).map_err(|error| {
+                        format!("invalid xpath {source:?}: {error}")
+                    })?;
+                    Some(HtmlStep::Xpath(source.to_owned()))
+                }
+                ("strip-boilerplate", None) => Some(HtmlStep::StripBoilerplate),
+                _ => None,
+            };
+            if let Some(html) = html {
+                if !pipeline.text.is_empty() {
+                    return Err(format!(
+                        "filter {step:?} works on HTML, so it must come \
+                        before filters that work on text"
+                    ));
+                }
+                pipeline.html.push(html);
+            } else {
+                pipeline.text.push(parse_text_step(name, argument).map_err(
+                    |error| format!("invalid filter {step:?}: {error}"),
+                )?);
+            }
+        }
+        pipeline.source = source;
+        Ok(pipeline)
+    }
+}
+
+impl From<Pipeline> for Vec<String> {
+    fn from(pipeline: Pipeline) -> Self {
+        pipeline.source
+    }
+}
+
+/// Parse a step that works on rendered text.
+///
+/// # Errors
+///
+/// Returns an error if the step is unknown, is missing its argument or has one
+/// it doesn’t need, or if its regular expression is invalid.
+fn parse_text_step(
+    name: &str,
+    argument: Option<&str>,
+) -> anyhow::Result<TextStep> {
+    match (name, argument) {
+        ("strip-regex", Some(source)) => Ok(TextStep::StripRegex(
+            Regex::new(source).map_err(|error| anyhow!("{error}"))?,
+        )),
+        ("lowercase", None) => Ok(TextStep::Lowercase),
+        ("collapse-whitespace", None) => Ok(TextStep::CollapseWhitespace),
+        (
+            "selector"
+            | "xpath"
+            | "strip-regex"
+            | "strip-boilerplate"
+            | "lowercase"
+            | "collapse-whitespace",
+            _,
+        ) => {
+            if argument.is_some() {
+                bail!("{name} doesn’t take an argument")
+            }
+            bail!("{name} needs an argument, e.g. {name}:…")
+        }
+        _ => bail!(
+            "unknown filter; expected selector:, xpath:, strip-boilerplate, \
+            strip-regex:, lowercase, or collapse-whitespace"
+        ),
+    }
+}
+
+/// Replace runs of whitespace within lines with a single space, trim lines,
+/// replace runs of blank lines with a single blank line, and remove blank lines
+/// at the start and end.
+fn collapse_whitespace(text: &str) -> String {
+    let mut collapsed = String::with_capacity(text.len());
+    let mut blank = false;
+    for line in text.lines() {
+        let mut words = line.split_whitespace().peekable();
+        if words.peek().is_none() {
+            blank = true;
+            continue;
+        }
+        if blank && !collapsed.is_empty() {
+            collapsed.push('\n');
+        }
+        blank = false;
+        for (i, word) in words.enumerate() {
+            if i > 0 {
+                collapsed.push(' ');
+            }
+            collapsed.push_str(word);
+        }
+        collapsed.push('\n');
+    }
+    collapsed
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use assert2::check;
+
+    /// Parse a pipeline.
+    fn pipeline(steps: &[&str]) -> Result<Pipeline, String> {
+        Pipeline::try_from(
+            steps
+                .iter()
+                .map(|&step| step.to_owned())
+                .collect::<Vec<_>>(),
+        )
+    }
+
+    #[test]
+    fn test_pipeline() {
+        let filters = pipeline(&[
+            "selector:#main",
+            "strip-regex:\\d{2}:\\d{2}",
+            "lowercase",
+            "collapse-whitespace",
+        ])
+        .unwrap();
+        check!(filters.has_extract());
+        check!(
+            filters
+                .apply_html("<p>Out</p><div id=\"main\">In</div>".into())
+                .unwrap()
+                == "<div id=\"main\">In</div>"
+        );
+        check!(
+            filters.apply_text(
+                "\nUpdated  at 12:34\n\n\n  Hello   World \n\n".into()
+            ) == "updated at\n\nhello world\n"
+        );
+
+        check!(pipeline(&[]).unwrap().is_empty());
+        check!(pipeline(&["lowercase", "selector:p"]).is_err());
+        check!(pipeline(&["selector:p["]).is_err());
+        check!(pipeline(&["strip-regex:("]).is_err());
+        check!(pipeline(&["lowercase:x"]).is_err());
+        check!(pipeline(&["strip-regex"]).is_err());
+        check!(pipeline(&["uppercase"]).is_err());
+    }
+}
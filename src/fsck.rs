@@ -0,0 +1,419 @@
+//! Checking the state directory for damage (`monitorbot fsck`).
+//!
+//! A file in the state directory that can’t be parsed, e.g. because the disk
+//! filled up while it was being copied, makes every run that loads it fail.
+//! This checks that:
+//!
+//!   * Every RON file parses, and the name of every history file decodes to a
+//!     URL.
+//!   * Links to the histories of final URLs resolve.
+//!   * Every body a history refers to is stored, and every stored body has the
+//!     SHA-256 in its name.
+//!   * The [`Redirects`](crate::state::Redirects) index agrees with the
+//!     histories and links.
+//!
+//! Damaged files can be moved to `quarantine/<time>/` within the state
+//! directory, so that monitorbot starts over for the URLs they affect.
+
+use chrono::{DateTime, Utc};
+use sha2::{Digest, Sha256};
+use std::fs;
+use std::io::{self, Read};
+use std::path::{Path, PathBuf};
+use url::Url;
+
+use crate::digest::Record;
+use crate::feed::Feed;
+use crate::notify::Outbox;
+use crate::state::{Redirects, State, fs_safe_url, hex};
+
+/// A problem found in the state directory.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct Problem {
+    /// The file with the problem.
+    pub path: PathBuf,
+
+    /// What’s wrong with it.
+    pub description: String,
+
+    /// Whether the file is damaged and should be quarantined. Other problems,
+    /// like a missing body, can’t be fixed by moving a file.
+    pub damaged: bool,
+}
+
+impl Problem {
+    /// A damaged file.
+    fn damaged(path: &Path, description: impl Into<String>) -> Self {
+        Self {
+            path: path.to_owned(),
+            description: description.into(),
+            damaged: true,
+        }
+    }
+
+    /// An inconsistency that can’t be fixed by quarantining `path`.
+    fn inconsistent(path: &Path, description: impl Into<String>) -> Self {
+        Self {
+            path: path.to_owned(),
+            description: description.into(),
+            damaged: false,
+        }
+    }
+}
+
+/// Check the state directory.
+///
+/// Returns the problems found, in the order of the files they’re in.
+///
+/// # Errors
+///
+/// Returns an error if the directory couldn’t be read.
+pub fn check(state: &State) -> io::Result<Vec<Problem>> {
+    let mut problems = Vec::new();
+    let mut names = fs::read_dir(state.dir())?
+        .map(|entry| Ok(entry?.file_name()))
+        .collect::<io::Result<Vec<_>>>()?;
+    names.sort();
+
+    let redirects = state.load_redirects();
+    if let Err(error) = &redirects {
+        problems.push(Problem::damaged(
+            &state.redirects_path(),
+            format!("could not load: {}", error.root_cause()),
+        ));
+    }
+    let redirects = redirects.unwrap_or_default();
+
+    for name in names {
+        let path = state.dir().join(&name);
+        let Some(stem) =
+            name.to_str().and_then(|name| name.strip_suffix(".ron"))
+        else {
+            continue;
+        };
+        let result = match stem {
+            "redirects" => continue,
+            "outbox" => Outbox::load(state.dir()).map(drop),
+            "feed" => Feed::load(state.dir()).map(drop),
+            "digest" => Record::load(state.dir()).map(drop),
+            _ => {
+                check_history(state, &path, stem, &redirects, &mut problems)?;
+                continue;
+            }
+        };
+        if let Err(error) = result {
+            problems.push(Problem::damaged(
+                &path,
+                format!("could not load: {}", error.root_cause()),
+            ));
+        }
+    }
+
+    for (request_url, final_url) in &redirects.urls {
+        if !state.history_path(final_url).exists() {
+            problems.push(Problem::inconsistent(
+                &state.redirects_path(),
+                format!(
+                    "{request_url} redirects to {final_url}, which has no \
+                    history"
+                ),
+            ));
+        }
+    }
+
+    check_bodies(state, &mut problems)?;
+    Ok(problems)
+}
+
+/// Check a history file, or a link to one.
+///
+/// # Errors
+///
+/// Returns an error if the file’s metadata couldn’t be read.
+fn check_history(
+    state: &State,
+    path: &Path,
+    stem: &str,
+    redirects: &Redirects,
+    problems: &mut Vec<Problem>,
+) -> io::Result<()> {
+    let Some(url) = url_from_file_stem(stem) else {
+        problems.push(Problem::damaged(path, "file name is not a URL"));
+        return Ok(());
+    };
+
+    if fs::symlink_metadata(path)?.is_symlink() {
+        let target = fs::read_link(path)?;
+        let expected = redirects.urls.get(&url).map(|final_url| {
+            PathBuf::from(State::history_file_name(final_url))
+        });
+        if fs::metadata(path).is_err() {
+            problems.push(Problem::damaged(
+                path,
+                format!("link to {} does not resolve", target.display()),
+            ));
+        } else if let Some(expected) = expected {
+            if target != expected {
+                problems.push(Problem::inconsistent(
+                    path,
+                    format!(
+                        "links to {}, but the redirects index says {}",
+                        target.display(),
+                        expected.display(),
+                    ),
+                ));
+            }
+        } else {
+            problems.push(Problem::inconsistent(
+                path,
+                "is a link, but the URL is not in the redirects index",
+            ));
+        }
+        // The file it links to is checked on its own.
+        return Ok(());
+    }
+
+    let history = match state.load_final_history(&url, true) {
+        Ok(history) => history.unwrap_or_default(),
+        Err(error) => {
+            problems.push(Problem::damaged(
+                path,
+                format!("could not load: {}", error.root_cause()),
+            ));
+            return Ok(());
+        }
+    };
+
+    if let Some(baseline) = history.baseline
+        && baseline >= history.revisions.len()
+    {
+        problems.push(Problem::inconsistent(
+            path,
+            format!("baseline {baseline} is not a stored revision"),
+        ));
+    }
+    for revision in &history.revisions {
+        if !state.body_path(&revision.body_sha256).exists() {
+            problems.push(Problem::inconsistent(
+                path,
+                format!(
+                    "body {} of the response fetched at {} is missing",
+                    revision.body_sha256,
+                    revision.fetched_at.to_rfc3339(),
+                ),
+            ));
+        }
+    }
+    Ok(())
+}
+
+/// Check that every stored body has the SHA-256 in its name.
+///
+/// # Errors
+///
+/// Returns an error if the directory couldn’t be read.
+fn check_bodies(state: &State, problems: &mut Vec<Problem>) -> io::Result<()> {
+    let dir = state.dir().join("bodies");
+    let mut paths = match fs::read_dir(&dir) {
+        Ok(entries) => entries
+            .map(|entry| Ok(entry?.path()))
+            .collect::<io::Result<Vec<_>>>()?,
+        Err(error) if error.kind() == io::ErrorKind::NotFound => {
+            return Ok(());
+        }
+        Err(error) => return Err(error),
+    };
+    paths.sort();
+
+    for path in paths {
+        match file_sha256(&path) {
+            Ok(sha256) => {
+                if path.file_name().and_then(|name| name.to_str())
+                    != Some(sha256.as_str())
+                {
+                    problems.push(Problem::damaged(
+                        &path,
+                        format!("content has SHA-256 {sha256}"),
+                    ));
+                }
+            }
+            Err(error) => {
+                problems.push(Problem::damaged(&path, error.to_string()));
+            }
+        }
+    }
+    Ok(())
+}
+
+/// Get the SHA-256 of a file without reading it all into memory.
+///
+/// # Errors
+///
+/// Returns an error if the file couldn’t be read.
+fn file_sha256(path: &Path) -> io::Result<String> {
+    let mut file = fs::File::open(path)?;
+    let mut hasher = Sha256::new();
+    let mut buffer = vec![0; 64 * 1024];
+    loop {
+        let len = file.read(&mut buffer)?;
+        let Some(chunk) = buffer.get(..len).filter(|chunk| !chunk.is_empty())
+        else {
+            break;
+        };
+        hasher.update(chunk);
+    }
+    Ok(hex(&hasher.finalize()))
+}
+
+/// Get the URL a history file is for from its name without `.ron`.
+///
+/// This reverses [`fs_safe_url()`]. Returns `None` if the name isn’t one that
+/// it could have produced.
+#[must_use]
+pub fn url_from_file_stem(stem: &str) -> Option<Url> {
+    let mut url = String::with_capacity(stem.len());
+    let mut chars = stem.chars();
+    while let Some(c) = chars.next() {
+        match c {
+            '\\' => url.push(chars.next()?),
+            '|' => url.push('/'),
+            c => url.push(c),
+        }
+    }
+    let url = Url::parse(&url).ok()?;
+    (fs_safe_url(&url) == stem).then_some(url)
+}
+
+/// Move damaged files into `quarantine/<time>/` within the state directory.
+///
+/// Returns where each file was moved.
+///
+/// # Errors
+///
+/// Returns an error if a file couldn’t be moved.
+pub fn quarantine(
+    state: &State,
+    problems: &[Problem],
+    now: DateTime<Utc>,
+) -> io::Result<Vec<(PathBuf, PathBuf)>> {
+    let dir = quarantine_dir(state, now);
+    let mut moved: Vec<(PathBuf, PathBuf)> = Vec::new();
+    for problem in problems.iter().filter(|problem| problem.damaged) {
+        if moved.iter().any(|(from, _)| *from == problem.path) {
+            continue;
+        }
+        let relative = problem
+            .path
+            .strip_prefix(state.dir())
+            .unwrap_or(&problem.path);
+        let destination = dir.join(relative);
+        if let Some(parent) = destination.parent() {
+            fs::create_dir_all(parent)?;
+        }
+        fs::rename(&problem.path, &destination)?;
+        moved.push((problem.path.clone(), destination));
+    }
+    Ok(moved)
+}
+
+/// Get the directory damaged files found at `now` are moved to.
+#[must_use]
+pub fn quarantine_dir(state: &State, now: DateTime<Utc>) -> PathBuf {
+    state
+        .dir()
+        .join("quarantine")
+        .join(now.format("%Y%m%dT%H%M%SZ").to_string())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::response::Response;
+    use crate::state::{History, Revision};
+    use assert2::check;
+
+    #[test]
+    fn test_check() {
+        let dir = std::env::temp_dir()
+            .join(format!("monitorbot-{}-fsck", std::process::id()));
+        let _ = fs::remove_dir_all(&dir);
+        let state = State::new(dir.clone());
+        state.create().unwrap();
+
+        let good: Url = "https://example.com/a|b".parse().unwrap();
+        check!(url_from_file_stem(&fs_safe_url(&good)) == Some(good.clone()));
+        let response = Response {
+            url: good.clone(),
+            version: http::Version::HTTP_11,
+            status: http::StatusCode::OK,
+            headers: http::HeaderMap::new(),
+            body: "body".into(),
+            spooled: None,
+            certificate_sha256: None,
+            peer_address: None,
+        };
+        let history = History {
+            revisions: vec![Revision::new(&response, b"body")],
+            ..History::default()
+        };
+        state.save_response_body(&response).unwrap();
+        state.save_history(&good, &history).unwrap();
+        check!(check(&state).unwrap() == []);
+
+        let old: Url = "http://example.com/".parse().unwrap();
+        state.record_redirect(&old, &good).unwrap();
+        check!(check(&state).unwrap() == []);
+
+        let truncated: Url = "https://example.com/truncated".parse().unwrap();
+        let truncated_path = state.history_path(&truncated);
+        fs::write(&truncated_path, "(revisions: [(fetched_at: ").unwrap();
+        let body_path = state.body_path(&history.revisions[0].body_sha256);
+        fs::write(&body_path, "changed").unwrap();
+        fs::write(dir.join("stray.ron"), "()").unwrap();
+
+        let problems = check(&state).unwrap();
+        let summary = problems
+            .iter()
+            .map(|problem| {
+                (
+                    problem
+                        .path
+                        .strip_prefix(&dir)
+                        .unwrap()
+                        .display()
+                        .to_string(),
+                    problem.damaged,
+                )
+            })
+            .collect::<Vec<_>>();
+        check!(
+            summary
+                == [
+                    ("https:||example.com|truncated.ron".to_owned(), true),
+                    ("stray.ron".to_owned(), true),
+                    (
+                        format!("bodies/{}", history.revisions[0].body_sha256),
+                        true
+                    ),
+                ]
+        );
+
+        let now = DateTime::from_timestamp(1_000, 0).unwrap();
+        let moved = quarantine(&state, &problems, now).unwrap();
+        check!(moved.len() == 3);
+        check!(!truncated_path.exists());
+        check!(
+            quarantine_dir(&state, now)
+                .join("https:||example.com|truncated.ron")
+                .exists()
+        );
+
+        // Now the body is missing.
+        let problems = check(&state).unwrap();
+        check!(problems.len() == 1);
+        check!(!problems[0].damaged);
+        check!(problems[0].description.contains("is missing"));
+
+        fs::remove_dir_all(&dir).unwrap();
+    }
+}
@@ -0,0 +1,280 @@
+//! Keeping rendered snapshots in a git repository.
+//!
+//! With a `[git]` section in the configuration, the rendered Markdown of each
+//! URL is written to a file in a git repository, and committed whenever it
+//! changes. This makes `git log`, `git diff`, and `git blame` work on the
+//! history of a page, and the repository can be pushed somewhere for backup:
+//!
+//! ```toml
+//! [git]
+//! remote = "origin"   # Push after each run.
+//! ```
+//!
+//! The repository is created if it doesn’t exist. It’s in addition to the
+//! normal state, not a replacement for it. This runs the `git` command, so it
+//! must be installed.
+
+use anyhow::{Context, bail};
+use chrono::{DateTime, Utc};
+use std::ffi::OsStr;
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::process::{Command, Stdio};
+use url::Url;
+
+use crate::state::{atomic_write, fs_safe_url};
+
+/// Name and email used for commits.
+const AUTHOR: (&str, &str) = ("monitorbot", "monitorbot@localhost");
+
+/// Configuration for the git repository.
+#[derive(Clone, Debug, Default, serde::Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct GitConfig {
+    /// Where the repository is. Defaults to `git` in the state directory.
+    #[serde(default)]
+    pub dir: Option<PathBuf>,
+
+    /// Remote to push to at the end of each run, if any.
+    #[serde(default)]
+    pub remote: Option<String>,
+}
+
+impl GitConfig {
+    /// Make a relative `dir` relative to `base_dir`.
+    pub fn resolve_paths(&mut self, base_dir: &Path) {
+        if let Some(dir) = &mut self.dir
+            && dir.is_relative()
+        {
+            *dir = base_dir.join(&*dir);
+        }
+    }
+
+    /// Get the path to the repository.
+    #[must_use]
+    pub fn dir(&self, state_dir: &Path) -> PathBuf {
+        self.dir.clone().unwrap_or_else(|| state_dir.join("git"))
+    }
+}
+
+/// A git repository of rendered snapshots.
+#[derive(Debug)]
+pub struct GitHistory {
+    /// The working tree of the repository.
+    dir: PathBuf,
+
+    /// Remote to push to, if any.
+    remote: Option<String>,
+
+    /// Whether anything was committed during this run, or would have been
+    /// with `--dry-run`.
+    committed: bool,
+}
+
+impl GitHistory {
+    /// Open the repository, creating it if necessary and not `dry_run`.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if the repository couldn’t be created.
+    pub fn open(
+        config: &GitConfig,
+        state_dir: &Path,
+        dry_run: bool,
+    ) -> anyhow::Result<Self> {
+        let dir = config.dir(state_dir);
+        if !dry_run && !dir.join(".git").exists() {
+            fs::create_dir_all(&dir).with_context(|| {
+                format!("could not create {}", dir.display())
+            })?;
+            git(&dir, ["init", "--quiet"])?;
+        }
+        Ok(Self { dir, remote: config.remote.clone(), committed: false })
+    }
+
+    /// Get the path to the repository.
+    #[must_use]
+    pub fn dir(&self) -> &Path {
+        &self.dir
+    }
+
+    /// Get the name of the file for a URL within the repository.
+    #[must_use]
+    pub fn file_name(url: &Url) -> String {
+        format!("{}.md", fs_safe_url(url))
+    }
+
+    /// Write the rendered content of a URL and commit it if it changed.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if the file couldn’t be written or committed.
+    pub fn record(
+        &mut self,
+        label: &str,
+        url: &Url,
+        rendered: &str,
+        fetched_at: DateTime<Utc>,
+    ) -> anyhow::Result<()> {
+        let name = Self::file_name(url);
+        let is_new = !self.dir.join(&name).exists();
+        atomic_write(&self.dir.join(&name), rendered.as_bytes())?;
+        git(&self.dir, ["add", "--", &name])?;
+
+        // `git diff --quiet` exits with 1 if there are differences.
+        let status = Command::new("git")
+            .current_dir(&self.dir)
+            .args(["diff", "--cached", "--quiet", "--", &name])
+            .status()
+            .context("could not run git")?;
+        if status.success() {
+            return Ok(());
+        }
+
+        let verb = if is_new { "Add" } else { "Update" };
+        let message = format!("{verb} {label}\n\n{url}\n");
+        git(
+            &self.dir,
+            [
+                "commit",
+                "--quiet",
+                "--no-verify",
+                "--date",
+                &fetched_at.to_rfc3339(),
+                "--message",
+                &message,
+                "--",
+                &name,
+            ],
+        )?;
+        self.committed = true;
+        Ok(())
+    }
+
+    /// Note that a snapshot would have been committed, for `--dry-run`.
+    pub const fn would_commit(&mut self) {
+        self.committed = true;
+    }
+
+    /// Get the remote to push to, if there is one and anything was
+    /// committed.
+    #[must_use]
+    pub fn push_remote(&self) -> Option<&str> {
+        self.remote.as_deref().filter(|_| self.committed)
+    }
+
+    /// Push to the remote, if there is one and anything was committed.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if the push failed.
+    pub fn push(&self) -> anyhow::Result<()> {
+        if let Some(remote) = self.push_remote() {
+            git(&self.dir, ["push", "--quiet", remote, "HEAD"])?;
+        }
+        Ok(())
+    }
+}
+
+/// Run a git command in a repository.
+///
+/// # Errors
+///
+/// Returns an error if git couldn’t be run or failed.
+fn git<I, S>(dir: &Path, args: I) -> anyhow::Result<()>
+where
+    I: IntoIterator<Item = S>,
+    S: AsRef<OsStr>,
+{
+    let args: Vec<_> = args.into_iter().collect();
+    let output = Command::new("git")
+        .current_dir(dir)
+        .args(&args)
+        .env("GIT_AUTHOR_NAME", AUTHOR.0)
+        .env("GIT_AUTHOR_EMAIL", AUTHOR.1)
+        .env("GIT_COMMITTER_NAME", AUTHOR.0)
+        .env("GIT_COMMITTER_EMAIL", AUTHOR.1)
+        .stdin(Stdio::null())
+        .output()
+        .context("could not run git")?;
+    if !output.status.success() {
+        let command = args
+            .first()
+            .map(|arg| arg.as_ref().to_string_lossy().into_owned())
+            .unwrap_or_default();
+        bail!(
+            "git {command} failed: {}",
+            String::from_utf8_lossy(&output.stderr).trim(),
+        );
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use assert2::check;
+
+    /// Get the subjects of the commits in a repository, newest first.
+    fn log(dir: &Path) -> Vec<String> {
+        let output = Command::new("git")
+            .current_dir(dir)
+            .args(["log", "--format=%s"])
+            .output()
+            .unwrap();
+        String::from_utf8(output.stdout)
+            .unwrap()
+            .lines()
+            .map(str::to_owned)
+            .collect()
+    }
+
+    #[test]
+    fn test_record() {
+        let state_dir = std::env::temp_dir()
+            .join(format!("monitorbot-{}-git", std::process::id()));
+        let _ = fs::remove_dir_all(&state_dir);
+        let mut history =
+            GitHistory::open(&GitConfig::default(), &state_dir, false).unwrap();
+        check!(history.dir() == state_dir.join("git"));
+
+        let url: Url = "https://example.com/page".parse().unwrap();
+        let now = Utc::now();
+        history.record("Example", &url, "# One\n", now).unwrap();
+        history.record("Example", &url, "# One\n", now).unwrap();
+        history.record("Example", &url, "# Two\n", now).unwrap();
+        check!(log(history.dir()) == ["Update Example", "Add Example"]);
+        check!(
+            fs::read_to_string(history.dir().join(GitHistory::file_name(&url)))
+                .unwrap()
+                == "# Two\n"
+        );
+
+        // Opening an existing repository keeps its history.
+        let history =
+            GitHistory::open(&GitConfig::default(), &state_dir, false).unwrap();
+        check!(log(history.dir()).len() == 2);
+
+        fs::remove_dir_all(state_dir).unwrap();
+    }
+
+    #[test]
+    fn test_dry_run() {
+        let state_dir = std::env::temp_dir()
+            .join(format!("monitorbot-{}-git-dry-run", std::process::id()));
+        let _ = fs::remove_dir_all(&state_dir);
+        let config = GitConfig { dir: None, remote: Some("origin".to_owned()) };
+        let mut history = GitHistory::open(&config, &state_dir, true).unwrap();
+        check!(!state_dir.exists());
+
+        // Nothing would be pushed unless something would be committed.
+        check!(history.push_remote() == None);
+        history.would_commit();
+        check!(history.push_remote() == Some("origin"));
+
+        let mut history =
+            GitHistory::open(&GitConfig::default(), &state_dir, true).unwrap();
+        history.would_commit();
+        check!(history.push_remote() == None);
+    }
+}
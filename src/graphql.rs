@@ -0,0 +1,196 @@
+//! Monitoring GraphQL endpoints.
+//!
+//! A URL with a `graphql` table is requested with a `POST` containing the query
+//! and variables as JSON. String variables may contain templates that are
+//! filled in at request time:
+//!
+//!   * `{{today}}`, `{{yesterday}}`, `{{tomorrow}}`: the date in UTC, e.g.
+//!     `2024-05-01`.
+//!   * `{{now}}`: the current time in RFC 3339 format.
+//!   * `{{timestamp}}`: the current time in seconds since the UNIX epoch.
+
+use anyhow::bail;
+use chrono::{DateTime, Days, SecondsFormat, Utc};
+use serde_json::Value as JsonValue;
+use std::collections::BTreeMap;
+
+use crate::response::Response;
+
+/// The `Accept` header to send with GraphQL requests.
+///
+/// This overrides the impersonation profile, but not `Accept` in the URL’s
+/// `headers`.
+pub static ACCEPT: &str = "application/graphql-response+json, application/json";
+
+/// A GraphQL query to send to a URL.
+#[derive(Clone, Debug, serde::Deserialize, serde::Serialize)]
+#[serde(deny_unknown_fields)]
+pub struct GraphQl {
+    /// The query document.
+    pub query: String,
+
+    /// Variables for the query. Strings may contain templates.
+    #[serde(default, skip_serializing_if = "BTreeMap::is_empty")]
+    pub variables: BTreeMap<String, JsonValue>,
+
+    /// The operation to run, if the query contains more than one.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub operation_name: Option<String>,
+}
+
+impl GraphQl {
+    /// Build the JSON request body, filling in templates in variables.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if a variable contains an unknown template.
+    pub fn body(&self, now: DateTime<Utc>) -> anyhow::Result<JsonValue> {
+        let mut variables = serde_json::Map::new();
+        for (name, value) in &self.variables {
+            variables.insert(name.clone(), substitute(value, now)?);
+        }
+
+        let mut body = serde_json::Map::new();
+        body.insert("query".to_owned(), self.query.clone().into());
+        body.insert("variables".to_owned(), variables.into());
+        if let Some(operation_name) = &self.operation_name {
+            body.insert(
+                "operationName".to_owned(),
+                operation_name.clone().into(),
+            );
+        }
+        Ok(body.into())
+    }
+}
+
+/// Check a GraphQL response for errors.
+///
+/// Responses with errors but no data are treated as failures. Partial data is
+/// rendered along with the errors.
+///
+/// # Errors
+///
+/// Returns an error containing the error messages if there’s no data.
+pub fn check_errors(response: &Response) -> anyhow::Result<()> {
+    let Ok(json) = serde_json::from_slice::<JsonValue>(&response.body) else {
+        return Ok(());
+    };
+    let Some(errors) = json.get("errors").and_then(JsonValue::as_array) else {
+        return Ok(());
+    };
+    if !json.get("data").is_none_or(JsonValue::is_null) {
+        return Ok(());
+    }
+
+    let messages: Vec<_> = errors
+        .iter()
+        .map(|error| {
+            error
+                .get("message")
+                .and_then(JsonValue::as_str)
+                .map_or_else(|| error.to_string(), ToOwned::to_owned)
+        })
+        .collect();
+    bail!("GraphQL errors: {}", messages.join("; "))
+}
+
+/// Fill in templates in strings within a JSON value.
+///
+/// # Errors
+///
+/// Returns an error if a string contains an unknown template.
+fn substitute(
+    value: &JsonValue,
+    now: DateTime<Utc>,
+) -> anyhow::Result<JsonValue> {
+    Ok(match value {
+        JsonValue::String(s) => JsonValue::String(fill(s, now)?),
+        JsonValue::Array(values) => values
+            .iter()
+            .map(|value| substitute(value, now))
+            .collect::<anyhow::Result<_>>()?,
+        JsonValue::Object(map) => map
+            .iter()
+            .map(|(key, value)| Ok((key.clone(), substitute(value, now)?)))
+            .collect::<anyhow::Result<serde_json::Map<_, _>>>()?
+            .into(),
+        other => other.clone(),
+    })
+}
+
+/// Fill in `{{name}}` templates in a string.
+///
+/// # Errors
+///
+/// Returns an error if the string contains an unknown template.
+fn fill(s: &str, now: DateTime<Utc>) -> anyhow::Result<String> {
+    let mut out = String::new();
+    let mut rest = s;
+    while let Some((before, after)) = rest.split_once("{{") {
+        let Some((name, after)) = after.split_once("}}") else {
+            break;
+        };
+        out.push_str(before);
+        out.push_str(&expand(name.trim(), now)?);
+        rest = after;
+    }
+    out.push_str(rest);
+    Ok(out)
+}
+
+/// Expand a single template.
+///
+/// # Errors
+///
+/// Returns an error if the template is unknown.
+fn expand(name: &str, now: DateTime<Utc>) -> anyhow::Result<String> {
+    let date = |date: Option<DateTime<Utc>>| {
+        date.map(|date| date.format("%Y-%m-%d").to_string())
+            .ok_or_else(|| anyhow::anyhow!("date out of range"))
+    };
+    match name {
+        "today" => date(Some(now)),
+        "yesterday" => date(now.checked_sub_days(Days::new(1))),
+        "tomorrow" => date(now.checked_add_days(Days::new(1))),
+        "now" => Ok(now.to_rfc3339_opts(SecondsFormat::Secs, true)),
+        "timestamp" => Ok(now.timestamp().to_string()),
+        _ => bail!("unknown template {{{{{name}}}}} in GraphQL variable"),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use assert2::check;
+
+    #[test]
+    fn test_body() {
+        let graphql: GraphQl = toml::from_str(
+            r#"
+            query = "query Releases($since: Date!) { releases(since: $since) }"
+            operation_name = "Releases"
+            variables = { since = "{{ yesterday }}", range = ["{{today}}", 5] }
+            "#,
+        )
+        .unwrap();
+        let now = "2024-03-01T12:30:00Z".parse().unwrap();
+
+        check!(
+            graphql.body(now).unwrap()
+                == serde_json::json!({
+                    "query": graphql.query,
+                    "operationName": "Releases",
+                    "variables": {
+                        "since": "2024-02-29",
+                        "range": ["2024-03-01", 5],
+                    },
+                })
+        );
+        check!(
+            fill("{{now}} {{timestamp}}", now).unwrap()
+                == "2024-03-01T12:30:00Z 1709296200"
+        );
+        check!(fill("{{ unknown }}", now).is_err());
+        check!(fill("{{today", now).unwrap() == "{{today");
+    }
+}
@@ -0,0 +1,132 @@
+//! Running a command for each notification (`--exec`).
+//!
+//! The command is run with `sh -c`. Details are passed in environment
+//! variables:
+//!
+//!   * `MONITORBOT_URL`: the URL.
+//!   * `MONITORBOT_LABEL`: the name of the URL, or the URL itself.
+//!   * `MONITORBOT_STATUS`: what happened: `changed`, `failed`, `recovered`,
+//!     `soft-error`, or `new`.
+//!   * `MONITORBOT_KIND`: for changes, what kind of change it was: `content`,
+//!     `removed`, `restored`, `redirect`, `certificate`, `headers`,
+//!     `connection`, or `language`.
+//!   * `MONITORBOT_SEVERITY`: how important the URL is: `low`, `normal`, or
+//!     `high`.
+//!   * `MONITORBOT_DIFF_FILE`: path to a file containing the diff or other
+//!     details. It’s deleted after the command exits.
+//!
+//! The notification is also written to the command’s stdin as JSON.
+
+use anyhow::{Context, bail};
+use std::fs;
+use std::io::Write;
+use std::path::PathBuf;
+use std::process::{Command, Stdio};
+use std::sync::atomic::{AtomicU64, Ordering};
+
+use crate::notify::Notification;
+
+/// Run the hook command for a notification.
+///
+/// # Errors
+///
+/// Returns an error if the command couldn’t be run or failed.
+pub fn run(command: &str, notification: &Notification) -> anyhow::Result<()> {
+    let diff_file = DiffFile::create(&notification.text)?;
+
+    let mut child = Command::new("sh");
+    child
+        .arg("-c")
+        .arg(command)
+        .env("MONITORBOT_URL", notification.url.as_str())
+        .env("MONITORBOT_LABEL", &notification.label)
+        .env("MONITORBOT_STATUS", notification.event.as_str())
+        .env("MONITORBOT_SEVERITY", notification.severity.as_str())
+        .env("MONITORBOT_DIFF_FILE", &diff_file.0)
+        .stdin(Stdio::piped());
+    if let Some(kind) = notification.kind {
+        child.env("MONITORBOT_KIND", kind.as_str());
+    }
+    let mut child = child
+        .spawn()
+        .with_context(|| format!("could not run {command:?}"))?;
+
+    if let Some(mut stdin) = child.stdin.take() {
+        // The command doesn’t have to read stdin, so ignore broken pipes.
+        let _ = serde_json::to_writer(&mut stdin, notification);
+        let _ = stdin.write_all(b"\n");
+    }
+
+    let status = child.wait()?;
+    if !status.success() {
+        bail!("{command:?} failed: {status}");
+    }
+    Ok(())
+}
+
+/// Temporary file containing the text of a notification.
+///
+/// The file is deleted when this is dropped.
+struct DiffFile(PathBuf);
+
+impl DiffFile {
+    /// Write `text` to a new temporary file.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if the file couldn’t be written.
+    fn create(text: &str) -> anyhow::Result<Self> {
+        /// Make file names unique within this process.
+        static COUNTER: AtomicU64 = AtomicU64::new(0);
+
+        let path = std::env::temp_dir().join(format!(
+            "monitorbot-{}-{}.diff",
+            std::process::id(),
+            COUNTER.fetch_add(1, Ordering::Relaxed),
+        ));
+        fs::write(&path, text)
+            .with_context(|| format!("could not write {}", path.display()))?;
+        Ok(Self(path))
+    }
+}
+
+impl Drop for DiffFile {
+    fn drop(&mut self) {
+        let _ = fs::remove_file(&self.0);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::notify::{Event, Severity};
+    use assert2::check;
+    use chrono::Utc;
+
+    #[test]
+    fn test_run() {
+        let notification = Notification {
+            label: "Example".to_owned(),
+            url: "https://example.com/".parse().unwrap(),
+            text: "-old\n+new\n".to_owned(),
+            detected_at: Utc::now(),
+            event: Event::Failed,
+            kind: None,
+            tags: Vec::new(),
+            severity: Severity::Normal,
+            diff_path: None,
+            status: None,
+        };
+
+        run(
+            r#"test "$MONITORBOT_URL" = https://example.com/ \
+                && test "$MONITORBOT_STATUS" = failed \
+                && test "$MONITORBOT_SEVERITY" = normal \
+                && test "$(cat "$MONITORBOT_DIFF_FILE")" = "$(printf -- '-old\n+new')" \
+                && grep -q '"label":"Example"'"#,
+            &notification,
+        )
+        .unwrap();
+        check!(run("exit 3", &notification).is_err());
+    }
+}
@@ -0,0 +1,309 @@
+//! Reading image metadata.
+//!
+//! Images are compared by size and hash like other binary content, which
+//! doesn’t say what changed. For formats that are recognized, the format,
+//! dimensions, and EXIF and XMP metadata are read so that changes to them can
+//! be reported, e.g. to notice an image that was swapped for another one.
+
+use exif::{In, Tag};
+use regex::Regex;
+use std::collections::BTreeMap;
+use std::fmt::Write as _;
+use std::io::Cursor;
+use std::sync::LazyLock;
+
+/// Maximum length of a value to report. Longer values are truncated.
+const MAX_VALUE_LEN: usize = 200;
+
+/// Matches a tag with a prefixed name in XMP, e.g. `<dc:creator>`.
+static XMP_TAG_PATTERN: LazyLock<Regex> = LazyLock::new(|| {
+    Regex::new(r"<(/?)([\w.-]+:[\w.-]+)((?:[^>/]|/[^>])*)(/?)>")
+        .expect("pattern is valid")
+});
+
+/// Matches an attribute with a prefixed name in XMP, e.g. `xmp:Rating="5"`.
+static XMP_ATTRIBUTE_PATTERN: LazyLock<Regex> = LazyLock::new(|| {
+    Regex::new(r#"([\w.-]+:[\w.-]+)\s*=\s*(?:"([^"]*)"|'([^']*)')"#)
+        .expect("pattern is valid")
+});
+
+/// Metadata about an image, as names and values.
+#[derive(Clone, Debug, Default, Eq, PartialEq)]
+pub struct Metadata {
+    /// Fields in the order they should be displayed.
+    pub fields: Vec<(String, String)>,
+}
+
+impl Metadata {
+    /// Read the metadata of an image.
+    ///
+    /// Returns `None` if the format of the image isn’t recognized. Missing or
+    /// invalid EXIF and XMP metadata is skipped.
+    #[must_use]
+    pub fn read(body: &[u8]) -> Option<Self> {
+        let format = imagesize::image_type(body).ok()?;
+        let mut fields =
+            vec![("Format".to_owned(), format!("{format:?}").to_uppercase())];
+        if let Ok(size) = imagesize::blob_size(body) {
+            fields.push((
+                "Dimensions".to_owned(),
+                format!("{} × {}", size.width, size.height),
+            ));
+        }
+        fields.extend(exif_fields(body));
+        fields.extend(xmp_fields(body));
+        Some(Self { fields })
+    }
+
+    /// Get the value of a field.
+    #[must_use]
+    pub fn get(&self, name: &str) -> Option<&str> {
+        self.fields
+            .iter()
+            .find(|(field, _)| field == name)
+            .map(|(_, value)| value.as_str())
+    }
+
+    /// Describe the metadata with one field per line.
+    #[must_use]
+    pub fn describe(&self) -> String {
+        self.fields
+            .iter()
+            .fold(String::new(), |mut out, (name, value)| {
+                let _ = writeln!(out, "{name}: {value}");
+                out
+            })
+    }
+
+    /// Describe the fields that changed since `old`, one per line.
+    ///
+    /// Returns an empty string if nothing changed.
+    #[must_use]
+    pub fn changes(&self, old: &Self) -> String {
+        let mut out = String::new();
+        for (name, value) in &self.fields {
+            match old.get(name) {
+                Some(old_value) if old_value == value => {}
+                old_value => {
+                    let old_value = old_value.unwrap_or("(none)");
+                    let _ = writeln!(out, "{name}: {old_value} → {value}");
+                }
+            }
+        }
+        for (name, old_value) in &old.fields {
+            if self.get(name).is_none() {
+                let _ = writeln!(out, "{name}: {old_value} → (none)");
+            }
+        }
+        out
+    }
+}
+
+/// Describe an image, or how it changed since `old`.
+///
+/// Returns `None` if the format of the image isn’t recognized.
+#[must_use]
+pub fn report(old: Option<&[u8]>, new: &[u8]) -> Option<String> {
+    let new = Metadata::read(new)?;
+    Some(match old.and_then(Metadata::read) {
+        Some(old) => {
+            let changes = new.changes(&old);
+            if changes.is_empty() {
+                "Image metadata is unchanged\n".to_owned()
+            } else {
+                changes
+            }
+        }
+        None => new.describe(),
+    })
+}
+
+/// Read EXIF fields for the primary image, sorted by name.
+///
+/// Pointers to other parts of the file are skipped, since they change when
+/// anything else does.
+fn exif_fields(body: &[u8]) -> Vec<(String, String)> {
+    let Ok(exif) =
+        exif::Reader::new().read_from_container(&mut Cursor::new(body))
+    else {
+        return Vec::new();
+    };
+    let fields: BTreeMap<_, _> = exif
+        .fields()
+        .filter(|field| field.ifd_num == In::PRIMARY)
+        .filter(|field| {
+            !matches!(
+                field.tag,
+                Tag::ExifIFDPointer
+                    | Tag::GPSInfoIFDPointer
+                    | Tag::InteropIFDPointer
+                    | Tag::MakerNote
+            )
+        })
+        .map(|field| {
+            (
+                format!("EXIF {}", field.tag),
+                truncate(field.display_value().with_unit(&exif).to_string()),
+            )
+        })
+        .collect();
+    fields.into_iter().collect()
+}
+
+/// Read properties from the XMP packet, if there is one, sorted by name.
+///
+/// This isn’t a full XML parser; it finds properties set as attributes and
+/// the text inside properties. Values in lists are joined with commas.
+fn xmp_fields(body: &[u8]) -> Vec<(String, String)> {
+    const START: &[u8] = b"<x:xmpmeta";
+    const END: &[u8] = b"</x:xmpmeta>";
+
+    let Some(start) = find(body, START) else {
+        return Vec::new();
+    };
+    let Some(len) = body.get(start..).and_then(|rest| find(rest, END)) else {
+        return Vec::new();
+    };
+    let packet = body
+        .get(start..)
+        .and_then(|rest| rest.get(..len))
+        .map(String::from_utf8_lossy)
+        .unwrap_or_default();
+
+    let mut properties: BTreeMap<String, Vec<String>> = BTreeMap::new();
+    // Properties that contain the current position.
+    let mut open: Vec<&str> = Vec::new();
+    let mut text_start = 0;
+    for captures in XMP_TAG_PATTERN.captures_iter(&packet) {
+        let (Some(whole), Some(name)) = (captures.get(0), captures.get(2))
+        else {
+            continue;
+        };
+        let name = name.as_str();
+        let text = packet.get(text_start..whole.start()).unwrap_or_default();
+        text_start = whole.end();
+        if let Some(&property) = open.last() {
+            let text = text.trim();
+            if !text.is_empty() {
+                properties
+                    .entry(property.to_owned())
+                    .or_default()
+                    .push(unescape(text));
+            }
+        }
+
+        let is_property = !name.starts_with("rdf:") && !name.starts_with("x:");
+        if captures.get(1).is_some_and(|slash| !slash.is_empty()) {
+            if is_property {
+                open.pop();
+            }
+            continue;
+        }
+
+        let attributes = captures.get(3).map_or("", |m| m.as_str());
+        for attribute in XMP_ATTRIBUTE_PATTERN.captures_iter(attributes) {
+            let (Some(key), Some(value)) = (
+                attribute.get(1),
+                attribute.get(2).or_else(|| attribute.get(3)),
+            ) else {
+                continue;
+            };
+            let key = key.as_str();
+            if !key.starts_with("xmlns:")
+                && !key.starts_with("rdf:")
+                && !key.starts_with("x:")
+                && !key.starts_with("xml:")
+            {
+                properties
+                    .entry(key.to_owned())
+                    .or_default()
+                    .push(unescape(value.as_str()));
+            }
+        }
+
+        let self_closing = captures.get(4).is_some_and(|m| !m.is_empty());
+        if is_property && !self_closing {
+            open.push(name);
+        }
+    }
+
+    properties
+        .into_iter()
+        .map(|(name, values)| {
+            (format!("XMP {name}"), truncate(values.join(", ")))
+        })
+        .collect()
+}
+
+/// Find the first occurrence of `needle` in `haystack`.
+fn find(haystack: &[u8], needle: &[u8]) -> Option<usize> {
+    haystack
+        .windows(needle.len())
+        .position(|window| window == needle)
+}
+
+/// Replace the predefined XML entities.
+fn unescape(text: &str) -> String {
+    text.replace("&lt;", "<")
+        .replace("&gt;", ">")
+        .replace("&quot;", "\"")
+        .replace("&apos;", "'")
+        .replace("&amp;", "&")
+}
+
+/// Truncate a value to [`MAX_VALUE_LEN`] characters.
+fn truncate(mut value: String) -> String {
+    if let Some((index, _)) = value.char_indices().nth(MAX_VALUE_LEN) {
+        value.truncate(index);
+        value.push('…');
+    }
+    value
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use assert2::check;
+
+    /// Make a PNG header with the given dimensions and extra chunks.
+    fn png(width: u32, height: u32, extra: &[u8]) -> Vec<u8> {
+        let mut png = b"\x89PNG\r\n\x1a\n\0\0\0\x0dIHDR".to_vec();
+        png.extend(width.to_be_bytes());
+        png.extend(height.to_be_bytes());
+        png.extend(b"\x08\x06\0\0\0\0\0\0\0");
+        png.extend(extra);
+        png
+    }
+
+    #[test]
+    fn test_metadata() {
+        let xmp = br#"<x:xmpmeta xmlns:x="adobe:ns:meta/"><rdf:RDF>
+            <rdf:Description xmp:Rating="5" xmlns:xmp="http://ns.adobe.com/xap/1.0/">
+            <dc:creator><rdf:Seq><rdf:li>Alice &amp; Bob</rdf:li>
+            <rdf:li>Carol</rdf:li></rdf:Seq></dc:creator>
+            <dc:rights/>
+            </rdf:Description></rdf:RDF></x:xmpmeta>"#;
+        let old = Metadata::read(&png(640, 480, xmp)).unwrap();
+        check!(
+            old.describe()
+                == "Format: PNG\nDimensions: 640 × 480\n\
+                    XMP dc:creator: Alice & Bob, Carol\nXMP xmp:Rating: 5\n"
+        );
+
+        let new = Metadata::read(&png(800, 480, b"")).unwrap();
+        check!(
+            new.changes(&old)
+                == "Dimensions: 640 × 480 → 800 × 480\n\
+                    XMP dc:creator: Alice & Bob, Carol → (none)\n\
+                    XMP xmp:Rating: 5 → (none)\n"
+        );
+        check!(new.changes(&new) == "");
+        check!(
+            report(Some(&png(800, 480, b"")), &png(800, 480, b"more")).unwrap()
+                == "Image metadata is unchanged\n"
+        );
+        check!(report(None, b"not an image") == None);
+
+        check!(truncate("é".repeat(300)).chars().count() == 201);
+    }
+}
@@ -0,0 +1,141 @@
+//! Comparing JSON embedded in HTML pages (`inline_json`).
+//!
+//! Many pages are rendered by JavaScript from state embedded in the page, e.g.
+//! `<script id="__NEXT_DATA__" type="application/json">`, or in a data
+//! attribute. The visible HTML often hides what actually changed, so with
+//! `inline_json` the embedded JSON is extracted and compared instead:
+//!
+//! ```toml
+//! [[url]]
+//! url = "https://shop.example.com/product/42"
+//! inline_json.selector = "script#__NEXT_DATA__"
+//! inline_json.pointer = "/props/pageProps"
+//! ```
+//!
+//! If the text isn’t JSON by itself, as in
+//! `<script>window.__STATE__ = {…};</script>`, the JSON from the first `{` or
+//! `[` to the last `}` or `]` is used.
+
+use anyhow::{anyhow, bail};
+use scraper::{Html, Selector};
+
+/// Where to find JSON in an HTML page.
+#[derive(Clone, Debug, Eq, PartialEq, serde::Deserialize, serde::Serialize)]
+#[serde(deny_unknown_fields)]
+pub struct InlineJson {
+    /// CSS selector for the elements containing the JSON, usually a
+    /// `<script>`. If several match, their JSON is compared as an array.
+    pub selector: String,
+
+    /// Attribute of the elements containing the JSON (default: their text).
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub attribute: Option<String>,
+
+    /// JSON pointer to the part of the JSON to compare, e.g.
+    /// `/props/pageProps`.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub pointer: Option<String>,
+}
+
+impl InlineJson {
+    /// Extract the JSON from an HTML document and render it pretty-printed,
+    /// with object keys sorted so that each value is on its own line.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if the selector is invalid, nothing matches it, a
+    /// match doesn’t contain JSON, or `pointer` isn’t found.
+    pub fn apply(&self, html: &str) -> anyhow::Result<String> {
+        let source = &self.selector;
+        let selector = Selector::parse(source).map_err(|error| {
+            anyhow!("invalid inline_json selector {source:?}: {error}")
+        })?;
+        let mut values = Html::parse_document(html)
+            .select(&selector)
+            .map(|element| {
+                let text = match &self.attribute {
+                    Some(attribute) => element
+                        .attr(attribute)
+                        .ok_or_else(|| {
+                            anyhow!("{source:?} match has no {attribute:?}")
+                        })?
+                        .to_owned(),
+                    None => element.text().collect(),
+                };
+                parse(&text)
+                    .ok_or_else(|| anyhow!("{source:?} match is not JSON"))
+            })
+            .collect::<anyhow::Result<Vec<_>>>()?;
+
+        let value = match values.len() {
+            0 => bail!("nothing matches inline_json selector {source:?}"),
+            1 => values.swap_remove(0),
+            _ => serde_json::Value::Array(values),
+        };
+        let value = match &self.pointer {
+            Some(pointer) => value.pointer(pointer).ok_or_else(|| {
+                anyhow!("inline_json pointer {pointer:?} not found")
+            })?,
+            None => &value,
+        };
+        Ok(serde_json::to_string_pretty(value)?)
+    }
+}
+
+/// Parse JSON, or JSON embedded in JavaScript, e.g. `var x = {…};`.
+fn parse(text: &str) -> Option<serde_json::Value> {
+    if let Ok(value) = serde_json::from_str(text) {
+        return Some(value);
+    }
+    let start = text.find(['{', '['])?;
+    let end = text.rfind(['}', ']'])?;
+    serde_json::from_str(text.get(start..=end)?).ok()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use assert2::check;
+
+    #[test]
+    fn test_inline_json() {
+        let html = r#"<p>Loading…</p>
+            <script id="data" type="application/json">
+                {"props": {"price": 5, "name": "Widget"}, "build": "a1"}
+            </script>
+            <script>window.__STATE__ = {"cart": [1, 2]};</script>
+            <div class="item" data-state='{"id": 1}'></div>
+            <div class="item" data-state='{"id": 2}'></div>"#;
+        let inline_json = |selector: &str, attribute, pointer| InlineJson {
+            selector: selector.to_owned(),
+            attribute,
+            pointer,
+        };
+
+        check!(
+            inline_json("#data", None, Some("/props".to_owned()))
+                .apply(html)
+                .unwrap()
+                == "{\n  \"name\": \"Widget\",\n  \"price\": 5\n}"
+        );
+        check!(
+            inline_json("script:not([id])", None, None)
+                .apply(html)
+                .unwrap()
+                == "{\n  \"cart\": [\n    1,\n    2\n  ]\n}"
+        );
+        check!(
+            inline_json(".item", Some("data-state".to_owned()), None)
+                .apply(html)
+                .unwrap()
+                == "[\n  {\n    \"id\": 1\n  },\n  {\n    \"id\": 2\n  }\n]"
+        );
+        check!(inline_json("#missing", None, None).apply(html).is_err());
+        check!(inline_json("p", None, None).apply(html).is_err());
+        check!(
+            inline_json("#data", None, Some("/nope".to_owned()))
+                .apply(html)
+                .is_err()
+        );
+    }
+}
@@ -0,0 +1,185 @@
+//! Reporting new items on list-like pages.
+//!
+//! On job boards, classifieds, and release indexes, new entries push old ones
+//! down or off the page, so a line diff shows every entry moving. With
+//! `items_selector`, each element matching the selector is an item, and only
+//! items that weren’t on the page before are reported:
+//!
+//! ```toml
+//! [[url]]
+//! url = "https://example.com/jobs"
+//! items_selector = "ul.jobs > li"
+//! ```
+//!
+//! Items are identified by their first link, or by their text if they don’t
+//! have one. Items that disappear aren’t reported.
+
+use scraper::{ElementRef, Html, Selector};
+use std::collections::HashSet;
+use std::fmt;
+use std::io;
+use std::sync::LazyLock;
+use termcolor::{Color, ColorSpec};
+use url::Url;
+
+use crate::state::sha256_hex;
+
+/// Selector for links within an item.
+static LINK_SELECTOR: LazyLock<Selector> =
+    LazyLock::new(|| Selector::parse("a[href]").expect("selector is valid"));
+
+/// An item on a list-like page.
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub struct Item {
+    /// What identifies the item: its link, or a hash of its text.
+    pub id: String,
+
+    /// The text of the item, with whitespace collapsed.
+    pub text: String,
+
+    /// The item’s first link, if it has one.
+    pub link: Option<Url>,
+}
+
+impl fmt::Display for Item {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "+ {}", self.text)?;
+        if let Some(link) = &self.link {
+            write!(f, " <{link}>")?;
+        }
+        Ok(())
+    }
+}
+
+/// Parse an `items_selector`.
+///
+/// # Errors
+///
+/// Returns an error if the selector is invalid.
+pub fn selector(source: &str) -> anyhow::Result<Selector> {
+    Selector::parse(source).map_err(|error| {
+        anyhow::anyhow!("invalid items_selector {source:?}: {error}")
+    })
+}
+
+/// Get the items on a page.
+///
+/// Relative links are resolved against `base_url`.
+#[must_use]
+pub fn extract(html: &str, base_url: &Url, selector: &Selector) -> Vec<Item> {
+    Html::parse_document(html)
+        .select(selector)
+        .map(|element| item(element, base_url))
+        .filter(|item| !item.text.is_empty() || item.link.is_some())
+        .collect()
+}
+
+/// Make an item from an element.
+fn item(element: ElementRef<'_>, base_url: &Url) -> Item {
+    let text = element.text().collect::<Vec<_>>().join(" ");
+    let text = text.split_whitespace().collect::<Vec<_>>().join(" ");
+    let href = if element.value().name() == "a" {
+        element.attr("href")
+    } else {
+        element
+            .select(&LINK_SELECTOR)
+            .next()
+            .and_then(|link| link.attr("href"))
+    };
+    let link = href
+        .and_then(|href| base_url.join(href).ok())
+        .map(|mut url| {
+            url.set_fragment(None);
+            url
+        });
+    let id = link.as_ref().map_or_else(
+        || sha256_hex(text.as_bytes()),
+        |link| link.as_str().to_owned(),
+    );
+    Item { id, text, link }
+}
+
+/// Get the items in `new` that aren’t in `old`, in the order they appear.
+#[must_use]
+pub fn new_items<'a>(old: &[Item], new: &'a [Item]) -> Vec<&'a Item> {
+    let old: HashSet<&str> = old.iter().map(|item| item.id.as_str()).collect();
+    let mut seen = HashSet::new();
+    new.iter()
+        .filter(|item| !old.contains(item.id.as_str()))
+        .filter(|item| seen.insert(item.id.as_str()))
+        .collect()
+}
+
+/// Print a list of new items.
+///
+/// # Panics
+///
+/// Panics if the output couldn’t be written, like `println!`.
+pub fn print_items<S>(out: &mut S, items: &[&Item])
+where
+    S: termcolor::WriteColor + io::Write + ?Sized,
+{
+    let mut added_color = ColorSpec::new();
+    added_color.set_fg(Some(Color::Green)).set_intense(true);
+
+    // Use `unwrap()` here because these would be IO errors, so we may as well
+    // act like `println!`.
+    let noun = if items.len() == 1 { "item" } else { "items" };
+    writeln!(out, "{} new {noun}:", items.len()).unwrap();
+    for item in items {
+        out.set_color(&added_color).unwrap();
+        writeln!(out, "{item}").unwrap();
+        out.reset().unwrap();
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use assert2::check;
+
+    #[test]
+    fn test_new_items() {
+        let base_url: Url = "https://example.com/jobs/".parse().unwrap();
+        let li = selector("li").unwrap();
+        let old = extract(
+            r#"<ul>
+                <li><a href="1">Baker</a></li>
+                <li><a href="2#apply">Cook</a></li>
+                <li>Call   us
+                    about dishwashing</li>
+            </ul>"#,
+            &base_url,
+            &li,
+        );
+        check!(old.len() == 3);
+        check!(old[2].text == "Call us about dishwashing");
+        check!(old[2].link == None);
+
+        let new = extract(
+            r#"<ul>
+                <li><a href="3">Waiter</a> <b>new!</b></li>
+                <li><a href="1">Baker</a></li>
+                <li><a href="2">Cook (updated)</a></li>
+                <li>Call us about dishwashing</li>
+                <li>Ask about <a href="/jobs/4">hosting</a></li>
+                <li></li>
+            </ul>"#,
+            &base_url,
+            &li,
+        );
+        let new_items: Vec<String> = new_items(&old, &new)
+            .iter()
+            .map(ToString::to_string)
+            .collect();
+        check!(
+            new_items
+                == [
+                    "+ Waiter new! <https://example.com/jobs/3>",
+                    "+ Ask about hosting <https://example.com/jobs/4>",
+                ]
+        );
+
+        check!(selector("li[").is_err());
+    }
+}
@@ -0,0 +1,187 @@
+//! `JUnit` XML reports (`--report junit=PATH`).
+//!
+//! Each URL checked is a test case, so CI systems can show which URLs changed
+//! or failed. URLs that were new or unchanged pass, URLs that changed or failed
+//! their expectations are failures, and URLs that couldn’t be checked are
+//! errors.
+
+use chrono::{DateTime, Utc};
+use std::io::{self, Write};
+use std::time::Duration;
+
+use crate::output::escape_html;
+use crate::progress::Outcome;
+use crate::timing::Timing;
+
+/// Name of the test suite.
+const SUITE: &str = "monitorbot";
+
+/// Write a `JUnit` XML report with a test case for each URL checked.
+///
+/// # Errors
+///
+/// Returns an error if the report could not be written.
+pub fn write(
+    out: &mut dyn Write,
+    timings: &[Timing],
+    timestamp: DateTime<Utc>,
+) -> io::Result<()> {
+    let count = |outcomes: &[Outcome]| {
+        timings
+            .iter()
+            .filter(|timing| {
+                timing
+                    .outcome
+                    .is_some_and(|outcome| outcomes.contains(&outcome))
+            })
+            .count()
+    };
+    let tests = timings.len();
+    let failures = count(&[Outcome::Changed, Outcome::Failed]);
+    let errors = count(&[Outcome::Error]);
+    let skipped = count(&[Outcome::Skipped]);
+    let time = seconds(timings.iter().map(|timing| timing.total).sum());
+
+    writeln!(out, r#"<?xml version="1.0" encoding="UTF-8"?>"#)?;
+    writeln!(
+        out,
+        r#"<testsuites name="{SUITE}" tests="{tests}" failures="{failures}" errors="{errors}" skipped="{skipped}" time="{time}">"#,
+    )?;
+    writeln!(
+        out,
+        r#"  <testsuite name="{SUITE}" tests="{tests}" failures="{failures}" errors="{errors}" skipped="{skipped}" time="{time}" timestamp="{}">"#,
+        timestamp.format("%Y-%m-%dT%H:%M:%S"),
+    )?;
+    for timing in timings {
+        write_case(out, timing)?;
+    }
+    writeln!(out, "  </testsuite>")?;
+    writeln!(out, "</testsuites>")?;
+    Ok(())
+}
+
+/// Write the test case for one URL.
+///
+/// # Errors
+///
+/// Returns an error if the test case could not be written.
+fn write_case(out: &mut dyn Write, timing: &Timing) -> io::Result<()> {
+    let name = timing.url.as_ref().map_or("", |url| url.as_str());
+    let classname = timing
+        .url
+        .as_ref()
+        .and_then(|url| url.host_str())
+        .unwrap_or(SUITE);
+    write!(
+        out,
+        r#"    <testcase name="{}" classname="{}" time="{}""#,
+        escape(name),
+        escape(classname),
+        seconds(timing.total),
+    )?;
+
+    let details = timing.notifications.join("\n");
+    match timing.outcome {
+        Some(outcome @ (Outcome::Changed | Outcome::Failed)) => {
+            let message = if outcome == Outcome::Changed {
+                "changed"
+            } else {
+                "failed expectations"
+            };
+            writeln!(out, ">")?;
+            writeln!(
+                out,
+                r#"      <failure message="{message}" type="{outcome}">{}</failure>"#,
+                escape(&details),
+            )?;
+        }
+        Some(Outcome::Error) => {
+            let (kind, message) =
+                timing.error.as_ref().map_or(("error", ""), |error| {
+                    (error.kind.as_str(), &*error.message)
+                });
+            writeln!(out, ">")?;
+            writeln!(
+                out,
+                r#"      <error message="{}" type="{kind}">{}</error>"#,
+                escape(message),
+                escape(message),
+            )?;
+        }
+        Some(Outcome::Skipped) => {
+            writeln!(out, ">")?;
+            writeln!(out, "      <skipped/>")?;
+        }
+        Some(Outcome::New | Outcome::Unchanged) | None => {
+            writeln!(out, "/>")?;
+            return Ok(());
+        }
+    }
+    writeln!(out, "    </testcase>")
+}
+
+/// Format a duration as fractional seconds.
+fn seconds(duration: Duration) -> String {
+    format!("{:.3}", duration.as_secs_f64())
+}
+
+/// Escape text for XML, dropping characters that XML can’t contain.
+fn escape(text: &str) -> String {
+    let text = text
+        .chars()
+        .filter(|&c| !c.is_control() || matches!(c, '\t' | '\n' | '\r'))
+        .collect::<String>();
+    escape_html(&text)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::error::{ErrorKind, ErrorReport};
+    use assert2::check;
+
+    #[test]
+    fn test_write() {
+        let timing = |url: &str, outcome| Timing {
+            url: Some(url.parse().unwrap()),
+            total: Duration::from_millis(250),
+            outcome: Some(outcome),
+            ..Timing::default()
+        };
+        let timings = [
+            timing("https://example.com/same", Outcome::Unchanged),
+            Timing {
+                notifications: vec!["-old\n+<new> & \u{1b}more".to_owned()],
+                ..timing("https://example.com/changed", Outcome::Changed)
+            },
+            Timing {
+                error: Some(ErrorReport {
+                    kind: ErrorKind::Network,
+                    retryable: true,
+                    message: "connection refused".to_owned(),
+                }),
+                ..timing("https://example.org/down", Outcome::Error)
+            },
+            timing("https://example.org/later", Outcome::Skipped),
+        ];
+
+        let mut out = Vec::new();
+        let timestamp = DateTime::from_timestamp(0, 0).unwrap();
+        write(&mut out, &timings, timestamp).unwrap();
+        let xml = String::from_utf8(out).unwrap();
+        check!(xml.contains(
+            r#"<testsuite name="monitorbot" tests="4" failures="1" errors="1" skipped="1" time="1.000" timestamp="1970-01-01T00:00:00">"#
+        ));
+        check!(xml.contains(
+            r#"<testcase name="https://example.com/same" classname="example.com" time="0.250"/>"#
+        ));
+        check!(xml.contains(
+            r#"<failure message="changed" type="changed">-old
++&lt;new&gt; &amp; more</failure>"#
+        ));
+        check!(xml.contains(
+            r#"<error message="connection refused" type="network">connection refused</error>"#
+        ));
+        check!(xml.contains("<skipped/>\n    </testcase>"));
+    }
+}
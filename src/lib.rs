@@ -0,0 +1,64 @@
+//! Monitor web pages for changes.
+
+pub mod archive;
+pub mod audit;
+pub mod backoff;
+pub mod boilerplate;
+pub mod cache;
+pub mod check;
+pub mod checksums;
+pub mod commands;
+pub mod config;
+pub mod config_file;
+pub mod credentials;
+pub mod csv_diff;
+pub mod daemon;
+pub mod dashboard;
+pub mod digest;
+pub mod dns;
+pub mod dom_diff;
+pub mod encoding;
+pub mod error;
+pub mod expect;
+pub mod export;
+pub mod extract;
+pub mod feed;
+pub mod filters;
+pub mod fsck;
+pub mod git;
+pub mod graphql;
+pub mod hook;
+pub mod image;
+pub mod inline_json;
+pub mod items;
+pub mod junit;
+pub mod logging;
+pub mod notify;
+pub mod oauth;
+pub mod output;
+pub mod paginate;
+pub mod params;
+pub mod profile;
+pub mod progress;
+pub mod refresh;
+pub mod render;
+pub mod request;
+pub mod response;
+pub mod review;
+pub mod robots;
+pub mod schedule;
+pub mod secret;
+pub mod soft_error;
+pub mod spool;
+pub mod state;
+pub mod stream;
+pub mod summarize;
+pub mod systemd;
+pub mod timing;
+pub mod tls;
+pub mod trace;
+pub mod translate;
+pub mod tunnel;
+pub mod values;
+pub mod vantage;
+pub mod wayback;
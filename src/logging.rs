@@ -1,11 +1,44 @@
 //! Various logging functions.
 
 use anyhow::bail;
+use chrono::{Local, SecondsFormat};
+use serde_json::{Map, Value};
+use std::fmt;
+use tracing::field::{Field, Visit};
+use tracing::span::Record;
+use tracing::{Event, Level, Subscriber};
+use tracing_subscriber::field::RecordFields;
 use tracing_subscriber::filter::LevelFilter;
+use tracing_subscriber::fmt::format::Writer;
+use tracing_subscriber::fmt::{
+    FmtContext, FormatEvent, FormatFields, FormattedFields,
+};
 use tracing_subscriber::prelude::*;
+use tracing_subscriber::registry::LookupSpan;
+
+/// Format of log messages.
+#[derive(Clone, Copy, Debug, Default, Eq, PartialEq, clap::ValueEnum)]
+pub enum LogFormat {
+    /// Human readable lines, or lines for the systemd journal if output goes
+    /// to it.
+    #[default]
+    Text,
+
+    /// One JSON object per line, with the fields of the message and the spans
+    /// it’s in, e.g. the URL being checked.
+    Json,
+}
 
 /// Initialize logging for the executable.
-pub fn init(verbose: u8) -> anyhow::Result<()> {
+///
+/// When output goes to the systemd journal, text log lines are prefixed with
+/// their priority instead of a timestamp.
+///
+/// # Errors
+///
+/// Returns an error if `verbose` is too high, or if logging was already
+/// initialized.
+pub fn init(verbose: u8, format: LogFormat) -> anyhow::Result<()> {
     let filter = match verbose {
         4.. => bail!("-v is only allowed up to 3 times."),
         3 => LevelFilter::TRACE,
@@ -14,11 +47,232 @@ pub fn init(verbose: u8) -> anyhow::Result<()> {
         0 => LevelFilter::WARN,
     };
 
-    let formatter = tracing_subscriber::fmt::layer()
-        .with_timer(tracing_subscriber::fmt::time::ChronoLocal::rfc_3339());
+    let json = format == LogFormat::Json;
+    let journal = !json && is_journal();
+    let formatter = (!json && !journal).then(|| {
+        tracing_subscriber::fmt::layer()
+            .with_timer(tracing_subscriber::fmt::time::ChronoLocal::rfc_3339())
+    });
+    let journal_formatter = journal
+        .then(|| tracing_subscriber::fmt::layer().event_format(JournalFormat));
+    let json_formatter = json.then(|| {
+        tracing_subscriber::fmt::layer()
+            .fmt_fields(JsonFields)
+            .event_format(JsonFormat)
+    });
     tracing::subscriber::set_global_default(
-        tracing_subscriber::registry().with(filter).with(formatter),
+        tracing_subscriber::registry()
+            .with(filter)
+            .with(formatter)
+            .with(journal_formatter)
+            .with(json_formatter),
     )?;
 
     Ok(())
 }
+
+/// Check if output goes to the systemd journal.
+///
+/// systemd sets `JOURNAL_STREAM` for services with output connected to the
+/// journal.
+#[must_use]
+pub fn is_journal() -> bool {
+    std::env::var_os("JOURNAL_STREAM").is_some()
+}
+
+/// Formats log lines for the systemd journal.
+///
+/// The journal adds its own timestamps, and reads the priority of each line
+/// from a prefix like `<4>`.
+#[derive(Clone, Copy, Debug)]
+struct JournalFormat;
+
+impl<S, N> FormatEvent<S, N> for JournalFormat
+where
+    S: Subscriber + for<'a> LookupSpan<'a>,
+    N: for<'a> FormatFields<'a> + 'static,
+{
+    fn format_event(
+        &self,
+        ctx: &FmtContext<'_, S, N>,
+        mut writer: Writer<'_>,
+        event: &Event<'_>,
+    ) -> fmt::Result {
+        let priority = match *event.metadata().level() {
+            Level::ERROR => 3,
+            Level::WARN => 4,
+            Level::INFO => 6,
+            Level::DEBUG | Level::TRACE => 7,
+        };
+        write!(writer, "<{priority}>")?;
+        ctx.field_format().format_fields(writer.by_ref(), event)?;
+        writeln!(writer)
+    }
+}
+
+/// Formats log lines as JSON objects.
+///
+/// Each object has `timestamp`, `level`, `target`, and `message`, followed by
+/// the other fields of the event and the fields of the spans it’s in, from the
+/// outermost. Fields of the event take precedence.
+#[derive(Clone, Copy, Debug)]
+struct JsonFormat;
+
+impl<S> FormatEvent<S, JsonFields> for JsonFormat
+where
+    S: Subscriber + for<'a> LookupSpan<'a>,
+{
+    fn format_event(
+        &self,
+        ctx: &FmtContext<'_, S, JsonFields>,
+        mut writer: Writer<'_>,
+        event: &Event<'_>,
+    ) -> fmt::Result {
+        let metadata = event.metadata();
+        let mut object = Map::new();
+        object.insert(
+            "timestamp".to_owned(),
+            Local::now()
+                .to_rfc3339_opts(SecondsFormat::Millis, false)
+                .into(),
+        );
+        object.insert("level".to_owned(), metadata.level().as_str().into());
+        object.insert("target".to_owned(), metadata.target().into());
+
+        let mut fields = JsonVisitor::default();
+        event.record(&mut fields);
+        if let Some(scope) = ctx.event_scope() {
+            for span in scope.from_root() {
+                if let Some(span_fields) =
+                    span.extensions().get::<FormattedFields<JsonFields>>()
+                {
+                    object.extend(parse_object(&span_fields.fields));
+                }
+            }
+        }
+        object.extend(fields.0);
+
+        let line = serde_json::to_string(&object).map_err(|_| fmt::Error)?;
+        writeln!(writer, "{line}")
+    }
+}
+
+/// Formats the fields of spans as JSON objects, so that [`JsonFormat`] can
+/// include them.
+#[derive(Clone, Copy, Debug)]
+struct JsonFields;
+
+impl<'writer> FormatFields<'writer> for JsonFields {
+    fn format_fields<R: RecordFields>(
+        &self,
+        mut writer: Writer<'writer>,
+        fields: R,
+    ) -> fmt::Result {
+        let mut visitor = JsonVisitor::default();
+        fields.record(&mut visitor);
+        write!(writer, "{}", Value::Object(visitor.0))
+    }
+
+    fn add_fields(
+        &self,
+        current: &'writer mut FormattedFields<Self>,
+        fields: &Record<'_>,
+    ) -> fmt::Result {
+        let mut visitor = JsonVisitor(parse_object(&current.fields));
+        fields.record(&mut visitor);
+        current.fields = Value::Object(visitor.0).to_string();
+        Ok(())
+    }
+}
+
+/// Parse fields formatted by [`JsonFields`].
+fn parse_object(fields: &str) -> Map<String, Value> {
+    serde_json::from_str(fields).unwrap_or_default()
+}
+
+/// Collects fields as JSON values.
+#[derive(Debug, Default)]
+struct JsonVisitor(Map<String, Value>);
+
+impl Visit for JsonVisitor {
+    fn record_f64(&mut self, field: &Field, value: f64) {
+        self.0.insert(field.name().to_owned(), value.into());
+    }
+
+    fn record_i64(&mut self, field: &Field, value: i64) {
+        self.0.insert(field.name().to_owned(), value.into());
+    }
+
+    fn record_u64(&mut self, field: &Field, value: u64) {
+        self.0.insert(field.name().to_owned(), value.into());
+    }
+
+    fn record_bool(&mut self, field: &Field, value: bool) {
+        self.0.insert(field.name().to_owned(), value.into());
+    }
+
+    fn record_str(&mut self, field: &Field, value: &str) {
+        self.0.insert(field.name().to_owned(), value.into());
+    }
+
+    fn record_debug(&mut self, field: &Field, value: &dyn fmt::Debug) {
+        self.0
+            .insert(field.name().to_owned(), format!("{value:?}").into());
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use assert2::check;
+    use std::io;
+    use std::sync::{Arc, Mutex, PoisonError};
+
+    /// Collects log output in memory.
+    #[derive(Clone, Default)]
+    struct Buffer(Arc<Mutex<Vec<u8>>>);
+
+    impl io::Write for Buffer {
+        fn write(&mut self, bytes: &[u8]) -> io::Result<usize> {
+            self.0
+                .lock()
+                .unwrap_or_else(PoisonError::into_inner)
+                .extend_from_slice(bytes);
+            Ok(bytes.len())
+        }
+
+        fn flush(&mut self) -> io::Result<()> {
+            Ok(())
+        }
+    }
+
+    #[test]
+    fn test_json_format() {
+        let buffer = Buffer::default();
+        let writer = buffer.clone();
+        let subscriber = tracing_subscriber::registry().with(
+            tracing_subscriber::fmt::layer()
+                .fmt_fields(JsonFields)
+                .event_format(JsonFormat)
+                .with_writer(move || writer.clone()),
+        );
+        tracing::subscriber::with_default(subscriber, || {
+            let span = tracing::info_span!(
+                "check",
+                url = "https://example.com/",
+                status = tracing::field::Empty,
+            );
+            let _entered = span.enter();
+            span.record("status", 200);
+            tracing::warn!(bytes = 12, "Slow \"response\"");
+        });
+
+        let output = buffer.0.lock().unwrap().clone();
+        let line: Value = serde_json::from_slice(&output).unwrap();
+        check!(line["level"] == "WARN");
+        check!(line["message"] == "Slow \"response\"");
+        check!(line["bytes"] == 12);
+        check!(line["url"] == "https://example.com/");
+        check!(line["status"] == 200);
+    }
+}
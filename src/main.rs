@@ -1,27 +1,28 @@
 //! monitorbot executable.
 
-use bytes::Bytes;
-use encoding_rs::Encoding;
-use htmd::HtmlToMarkdown;
-use mime::Mime;
-use std::borrow::Cow;
-use std::collections::vec_deque::VecDeque;
-use std::fs;
-use std::io::{self, Write};
-use std::path::{Path, PathBuf};
+use anyhow::Context;
+use std::collections::HashMap;
 use std::process::ExitCode;
-use termcolor::{Color, ColorSpec};
-use thiserror::Error;
-use url::Url;
-
-mod logging;
-mod params;
-
-use params::{Params, Parser};
-
-/// Default user agent to use when making HTTP requests.
-static USER_AGENT: &str =
-    concat!(env!("CARGO_PKG_NAME"), "/", env!("CARGO_PKG_VERSION"));
+use tokio::net::TcpListener;
+
+use monitorbot::audit::AuditLog;
+use monitorbot::check::Checker;
+use monitorbot::config::{Config, UrlConfig};
+use monitorbot::dashboard::Dashboard;
+use monitorbot::export::Export;
+use monitorbot::feed::Feed;
+use monitorbot::git::GitHistory;
+use monitorbot::oauth::TokenCache;
+use monitorbot::params::{Command, Params, Parser};
+use monitorbot::progress::Progress;
+use monitorbot::request::ClientOptions;
+use monitorbot::robots::RobotsCache;
+use monitorbot::state::State;
+use monitorbot::systemd::Systemd;
+use monitorbot::timing::Recorder;
+use monitorbot::trace::HttpTrace;
+use monitorbot::tunnel::Tunnels;
+use monitorbot::{commands, daemon, logging, request};
 
 /// Wrapper to handle errors.
 ///
@@ -35,112 +36,6 @@ async fn main() -> ExitCode {
     })
 }
 
-/// Errors resulting from processing an HTTP response.
-#[derive(Error, Debug)]
-pub enum ResponseError {
-    /// Could not convert header value to string.
-    #[error("could not convert header value to string")]
-    InvalidStr(#[from] http::header::ToStrError),
-
-    /// Could not convert header value to MIME media type.
-    #[error("could not convert header value to MIME media type")]
-    InvalidMediaType(#[from] mime::FromStrError),
-
-    /// Unknown charset in header.
-    #[error("unknown charset {0}")]
-    InvalidCharset(String),
-}
-
-/// An HTTP response that can be serialized.
-#[derive(serde::Serialize, serde::Deserialize)]
-struct Response {
-    /// The URL that actually produced the response.
-    pub url: Url,
-
-    /// The HTTP version of the response.
-    #[serde(with = "http_serde::version")]
-    pub version: http::Version,
-
-    /// The HTTP status code of the response.
-    #[serde(with = "http_serde::status_code")]
-    pub status: http::StatusCode,
-
-    /// The HTTP headers of the response.
-    #[serde(with = "http_serde::header_map")]
-    pub headers: http::HeaderMap,
-
-    /// The body returned by the response.
-    pub body: Bytes,
-}
-
-impl Response {
-    /// From [`reqwest::Response`].
-    pub async fn from_reqwest(
-        response: reqwest::Response,
-    ) -> reqwest::Result<Self> {
-        Ok(Self {
-            url: response.url().clone(),
-            version: response.version(),
-            status: response.status(),
-            headers: response.headers().clone(),
-            body: response.bytes().await?,
-        })
-    }
-
-    /// Get the content-type.
-    ///
-    /// Based on [`reqwest::Response::text_with_charset()`].
-    pub fn content_type(&self) -> Result<Option<Mime>, ResponseError> {
-        // FIXME? ignores multiple values
-        self.headers
-            .get(http::header::CONTENT_TYPE)
-            .map(|value| {
-                value.to_str().map_err(ResponseError::InvalidStr).and_then(
-                    |value| {
-                        value.parse().map_err(ResponseError::InvalidMediaType)
-                    },
-                )
-            })
-            .transpose()
-    }
-
-    /// Get the charset.
-    ///
-    /// Based on [`reqwest::Response::text_with_charset()`].
-    pub fn charset(&self) -> Result<Option<String>, ResponseError> {
-        // FIXME? return &str?
-        Ok(self.content_type()?.and_then(|media_type| {
-            media_type
-                .get_param(mime::CHARSET)
-                .map(|name| name.to_string())
-        }))
-    }
-
-    /// Get the charset.
-    ///
-    /// Based on [`reqwest::Response::text_with_charset()`].
-    pub fn charset_encoding(
-        &self,
-    ) -> Result<Option<&'static Encoding>, ResponseError> {
-        self.charset()?
-            .map(|charset| {
-                Encoding::for_label(charset.as_bytes())
-                    .ok_or(ResponseError::InvalidCharset(charset))
-            })
-            .transpose()
-    }
-
-    /// Get the response body as text.
-    ///
-    /// If the response does not specify a charset, this defaults to UTF-8.
-    pub fn text(&self) -> Result<Cow<'_, str>, ResponseError> {
-        // FIXME change fallback to Windows Latin 1?
-        let encoding = self.charset_encoding()?.unwrap_or(encoding_rs::UTF_8);
-        let (text, _actual_encoding, _mangled) = encoding.decode(&self.body);
-        Ok(text)
-    }
-}
-
 /// Do the actual work.
 ///
 /// Returns the exit code to use.
@@ -150,263 +45,174 @@ impl Response {
 /// This returns any errors encountered during the run so that they can be
 /// outputted nicely in [`main()`].
 async fn cli(params: &Params) -> anyhow::Result<ExitCode> {
-    logging::init(params.verbose)?;
-
-    let client = reqwest::Client::builder()
-        .user_agent(USER_AGENT)
-        .connection_verbose(true)
-        .build()?;
-
-    let state_dir_path = params.state_dir_path();
-    fs::DirBuilder::new()
-        .recursive(true)
-        .create(&state_dir_path)?;
-
-    for request_url in &params.urls {
-        let mut file_name = fs_safe_url(request_url);
-        file_name.push_str(".ron");
-        let request_path = state_dir_path.join(file_name);
-
-        let old_response: Option<Response> = if request_path.exists() {
-            match load_old_response(&request_path) {
-                Ok(response) => Some(response),
-                Err(error) => {
-                    tracing::warn!("Could not read old response: {error:?}");
-                    None
-                }
-            }
-        } else {
-            None
-        };
-
-        // FIXME use etag/last-modified to check if possible.
-        let response = Response::from_reqwest(
-            client.get(request_url.clone()).send().await?,
-        )
-        .await?;
-
-        let mut response_file_name = fs_safe_url(&response.url);
-        response_file_name.push_str(".ron");
-        let response_path = state_dir_path.join(&response_file_name);
-
-        // Atomic write.
-        let tmp_path = response_path.with_added_extension(".tmp");
-        let (tmp_path, mut file) = create_unique_file(&tmp_path)?;
-        file.write_all(
-            ron::ser::to_string_pretty(
-                &response,
-                ron::ser::PrettyConfig::default(),
-            )?
-            .as_bytes(),
-        )?;
-        std::fs::rename(&tmp_path, &response_path)?;
+    logging::init(params.verbose, params.log_format)?;
 
-        if response.url != *request_url {
-            // FIXME do this for any other steps in the redirect chain.
-
-            // FIXME make this atomic
-            if request_path.exists() {
-                fs::remove_file(&request_path)?;
-            }
-
-            // They’re in the same directory, so just link/copy to the response.
-            cache_redirect(&response_file_name, &request_path)?;
+    match &params.command {
+        Some(Command::Show(show)) => return commands::show::run(params, show),
+        Some(Command::History(history)) => {
+            return commands::history::run(params, history);
         }
-
-        let old_md = if let Some(old_response) = old_response {
-            // Shortcut
-            if old_response.body == response.body {
-                continue;
-            }
-
-            // FIXME check the content-type; handle non-HTML.
-            render_html(&old_response.text()?, &old_response.url)?
-        } else {
-            String::new()
-        };
-
-        // FIXME check the content-type; handle non-HTML.
-        let new_md = render_html(&response.text()?, &response.url)?;
-        if params.no_diff {
-            println!("{new_md}");
-        } else if new_md != old_md {
-            print_pretty_diff(&mut params.out_stream(), &old_md, &new_md);
+        Some(Command::Note(note)) => return commands::note::run(params, note),
+        Some(Command::Add(add)) => return commands::add::run(params, add),
+        Some(Command::Remove(remove)) => {
+            return commands::remove::run(params, remove);
+        }
+        Some(Command::List) => return commands::list::run(params),
+        Some(Command::Review(review)) => {
+            return commands::review::run(params, review);
+        }
+        Some(Command::Fsck(fsck)) => return commands::fsck::run(params, fsck),
+        Some(Command::Values(values)) => {
+            return commands::values::run(params, values);
+        }
+        Some(Command::Digest(digest)) => {
+            return commands::digest::run(params, digest).await;
+        }
+        Some(Command::Import(import)) => {
+            return commands::import::run(params, import);
+        }
+        Some(Command::Export(export)) => {
+            return commands::export::run(params, export);
         }
+        Some(Command::Restore(restore)) => {
+            return commands::restore::run(params, restore);
+        }
+        Some(Command::Baseline(baseline)) => {
+            return commands::baseline::run(params, baseline);
+        }
+        Some(Command::Profile(profile)) => {
+            return commands::profile::run(params, profile);
+        }
+        Some(Command::Audit(audit)) => {
+            return commands::audit::run(params, audit);
+        }
+        Some(Command::Completions(completions)) => {
+            return commands::completions::run(completions);
+        }
+        Some(Command::Manpage) => return commands::manpage::run(),
+        None => {}
     }
 
-    Ok(ExitCode::SUCCESS)
-}
-
-/// Load old response.
-///
-/// # Errors
-///
-/// May return an error from [`fs::read()`] or from [`ron::de::from_bytes()`].
-fn load_old_response(request_path: &Path) -> anyhow::Result<Response> {
-    Ok(ron::de::from_bytes(&std::fs::read(request_path)?)?)
-}
+    let recorder = Recorder::default();
+    let client =
+        request::client(params, &recorder, &ClientOptions::new(params, None))?;
 
-/// Deal with a caching a redirect.
-///
-/// This makes a symlink from `request_path` to `response_path` on UNIX, and
-/// copies the file on other platforms.
-///
-/// # Errors
-///
-/// Returns [`io::Error`] for errors.
-fn cache_redirect(
-    response_path: &impl AsRef<Path>,
-    request_path: &impl AsRef<Path>,
-) -> io::Result<()> {
-    if cfg!(all(not(target_os = "hermit"), unix)) {
-        // UNIX (platforms for which `std::os::unix::fs` exists)
-        std::os::unix::fs::symlink(response_path, request_path)
-    } else {
-        // Not UNIX
-        fs::copy(response_path, request_path).map(|_| ())
+    if !params.dry_run {
+        State::new(params.state_dir_path()).create()?;
     }
-}
-
-/// Make a filesystem-safe version of the URL.
-fn fs_safe_url(url: &Url) -> String {
-    // FIXME does not work on Windows.
-    let s = url.as_str();
-    assert_ne!(s, "");
-    assert_ne!(s, ".");
-    assert_ne!(s, "..");
-    s.replace('\\', "\\\\")
-        .replace('|', r"\|")
-        .replace('/', "|")
-}
 
-/// Create a unique file in a directory.
-///
-/// # Errors
-///
-/// This will return an error with [`io::ErrorKind::AlreadyExists`] if it can’t
-/// create a file. It will add a number to the file name and increase the
-/// number until it finds an available name, or after 100 tries.
-fn create_unique_file(base_name: &Path) -> io::Result<(PathBuf, fs::File)> {
-    const MAX_ATTEMPTS: usize = 100;
-
-    for i in 1..=MAX_ATTEMPTS {
-        let candidate = base_name.with_added_extension(format!(".{i}"));
-        match exclusive_create_file(&candidate) {
-            Err(error) if error.kind() == io::ErrorKind::AlreadyExists => {}
-            Err(error) => return Err(error),
-            Ok(file) => return Ok((candidate, file)),
+    let config = params.load_config()?;
+    let url_configs = params.url_configs(&config);
+    let systemd = Systemd::from_env();
+    let tokens = TokenCache::default();
+    let dashboard = params
+        .listen
+        .is_some()
+        .then(|| Dashboard::new(params, &url_configs));
+    let check = || {
+        check_all(
+            params,
+            &config,
+            &url_configs,
+            &client,
+            &recorder,
+            &tokens,
+            &systemd,
+            dashboard.as_ref(),
+        )
+    };
+
+    if let Some(interval) = params.daemon {
+        let daemon = daemon::run(interval, &systemd, check);
+        if let (Some(dashboard), Some(listen)) = (&dashboard, params.listen) {
+            let listener = TcpListener::bind(listen)
+                .await
+                .with_context(|| format!("could not listen on {listen}"))?;
+            tracing::info!("Serving dashboard on http://{listen}/");
+            return tokio::select! {
+                result = daemon => result,
+                never = dashboard.serve(listener) => never,
+            };
         }
+        return daemon.await;
     }
 
-    Err(io::ErrorKind::AlreadyExists.into())
+    if check().await? {
+        Ok(ExitCode::FAILURE)
+    } else {
+        Ok(ExitCode::SUCCESS)
+    }
 }
 
-/// Create a file; fail if it already exists.
+/// Check all the URLs once.
+///
+/// Returns whether any URL failed.
 ///
 /// # Errors
 ///
-/// It will return an error if the file already exists, or if there was some
-/// other reason it could not create the file.
-fn exclusive_create_file(path: &Path) -> io::Result<fs::File> {
-    fs::OpenOptions::new()
-        .write(true)
-        .create_new(true)
-        .open(path)
-}
-
-/// Render HTML as Markdown.
-fn render_html<S: AsRef<str>>(
-    html: S,
-    _base_url: &Url,
-) -> anyhow::Result<String> {
-    // FIXME output links relative to _base_url.
-    Ok(HtmlToMarkdown::builder().build().convert(html.as_ref())?)
-}
-
-/// Print a pretty diff.
-#[expect(clippy::iter_with_drain, reason = "lint is incorrect")]
-fn print_pretty_diff<S>(out: &mut S, old: &str, new: &str)
-where
-    S: termcolor::WriteColor + io::Write,
-{
-    const CONTEXT_LEN: usize = 2;
-
-    let mut context = VecDeque::new();
-    let mut lines_since_diff: Option<usize> = None;
-
-    let mut old_color = ColorSpec::new();
-    old_color.set_fg(Some(Color::Red)).set_intense(true);
-    let mut new_color = ColorSpec::new();
-    new_color.set_fg(Some(Color::Green)).set_intense(true);
-
-    for diff in diff::lines(old, new) {
-        match diff {
-            diff::Result::Left(old_line) => {
-                for line in context.drain(..) {
-                    println!(" {line}");
-                }
-                // Use `unwrap()` here because these would be IO errors, so we
-                // may as well act like `println!`.
-                out.set_color(&old_color).unwrap();
-                writeln!(out, "-{old_line}").unwrap();
-                out.reset().unwrap();
-                lines_since_diff = Some(0);
-            }
-            diff::Result::Right(new_line) => {
-                for line in context.drain(..) {
-                    println!(" {line}");
-                }
-                // Use `unwrap()` here because these would be IO errors, so we
-                // may as well act like `println!`.
-                out.set_color(&new_color).unwrap();
-                writeln!(out, "+{new_line}").unwrap();
-                out.reset().unwrap();
-                lines_since_diff = Some(0);
-            }
-            diff::Result::Both(line, _) => {
-                if let Some(count) = lines_since_diff {
-                    println!(" {line}");
-                    #[expect(
-                        clippy::arithmetic_side_effects,
-                        reason = "limited by CONTEXT_LEN"
-                    )]
-                    let count = count + 1;
-                    if count >= CONTEXT_LEN {
-                        lines_since_diff = None;
-                    } else {
-                        lines_since_diff = Some(count);
-                    }
-                } else {
-                    context.push_back(line);
-                    if context.len() > CONTEXT_LEN {
-                        context.pop_front();
-                    }
-                }
-            }
-        }
-    }
-}
-
-#[cfg(test)]
-mod tests {
-    use super::*;
-    use assert2::check;
-
-    /// Convert a `&str` to a `Url`.
-    fn u(s: &'static str) -> Url {
-        Url::parse(s).unwrap()
+/// Returns an error if the feed couldn’t be loaded, or if output,
+/// notifications, or the feed couldn’t be written.
+#[expect(
+    clippy::too_many_arguments,
+    reason = "these are shared between runs with --daemon"
+)]
+async fn check_all(
+    params: &Params,
+    config: &Config,
+    url_configs: &[UrlConfig],
+    client: &reqwest::Client,
+    recorder: &Recorder,
+    tokens: &TokenCache,
+    systemd: &Systemd,
+    dashboard: Option<&Dashboard<'_>>,
+) -> anyhow::Result<bool> {
+    let state = State::new(params.state_dir_path());
+    let feed = if params.feed_output.is_some() {
+        Some(Feed::load(state.dir())?)
+    } else {
+        None
+    };
+
+    let audit_log = config
+        .audit_log
+        .as_ref()
+        .map(|audit_log| AuditLog::open(audit_log, state.dir()))
+        .transpose()?;
+
+    let git = config
+        .git
+        .as_ref()
+        .map(|git| GitHistory::open(git, state.dir(), params.dry_run))
+        .transpose()?;
+
+    let mut checker = Checker {
+        params,
+        config,
+        client: client.clone(),
+        clients: HashMap::new(),
+        state,
+        robots: params.respect_robots.then(RobotsCache::default),
+        feed,
+        notifications: Vec::new(),
+        failed: false,
+        recorder: recorder.clone(),
+        timings: Vec::new(),
+        progress: Progress::new(params, url_configs.len()),
+        export: params.output_dir.as_deref().map(Export::new),
+        tokens: tokens.clone(),
+        dashboard,
+        audit_log,
+        git,
+        status: None,
+        trace: params.trace_http.as_deref().map(HttpTrace::new),
+        tunnels: Tunnels::default(),
+    };
+
+    for url_config in url_configs {
+        checker.check(url_config).await?;
+        systemd.ping();
     }
+    checker.finish().await?;
 
-    #[test]
-    fn test_fs_safe_url() {
-        check!(
-            fs_safe_url(&u("https://demon.horse/hireme/#fragment"))
-                == "https:||demon.horse|hireme|#fragment"
-        );
-        check!(fs_safe_url(&u("a://a/b")) == "a:||a|b");
-        check!(
-            fs_safe_url(&u(r"a://a/foo\back|pipe\|backpipe"))
-                == r"a:||a|foo\\back\|pipe\\\|backpipe"
-        );
-    }
+    Ok(checker.failed)
 }
@@ -0,0 +1,1249 @@
+//! Sending notifications about changes.
+//!
+//! Notifications are collected during a run and delivered at the end of it.
+//! Notifications that can’t be delivered yet, because it’s during quiet hours
+//! or because they’re being batched, are kept in the state directory in
+//! `outbox.ron` until a later run.
+//!
+//! Each notifier can have rules that select which notifications it gets, e.g.
+//! to page someone about URLs tagged `security` and send everything else in a
+//! daily digest.
+//!
+//! Messages can be formatted with [MiniJinja] templates, set in `[notify]` or
+//! for each notifier:
+//!
+//! ```toml
+//! [notify]
+//! title_template = "{{ label }}: {{ changed_lines }} lines changed"
+//! template = """
+//! {{ title }} ({{ status }})
+//! {{ url }}
+//!
+//! {{ diff }}"""
+//! ```
+//!
+//! See [`TemplateContext`] for the variables available.
+//!
+//! [MiniJinja]: https://docs.rs/minijinja
+
+use anyhow::Context;
+use chrono::{DateTime, Local, Utc};
+use regex::Regex;
+use serde::Deserialize as _;
+use std::borrow::Cow;
+use std::collections::BTreeMap;
+use std::fmt::Write as _;
+use std::fs;
+use std::io;
+use std::path::{Path, PathBuf};
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::time::Duration;
+use url::Url;
+
+use crate::output::escape_html;
+use crate::response::Response;
+use crate::schedule::TimeRange;
+use crate::secret::Secret;
+use crate::state::atomic_write;
+use crate::summarize::{self, Counts, Limits};
+
+/// A notification about a change to a URL.
+#[derive(Clone, Debug, serde::Serialize, serde::Deserialize)]
+pub struct Notification {
+    /// The label for the URL; see [`crate::config::UrlConfig::label()`].
+    pub label: String,
+
+    /// The URL that changed.
+    pub url: Url,
+
+    /// Plain text describing the change, e.g. a diff.
+    pub text: String,
+
+    /// When the change was detected.
+    pub detected_at: DateTime<Utc>,
+
+    /// What happened.
+    #[serde(default)]
+    pub event: Event,
+
+    /// What kind of change it was, if the event is [`Event::Changed`].
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub kind: Option<ChangeKind>,
+
+    /// The tags of the URL.
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub tags: Vec<String>,
+
+    /// How important changes to the URL are.
+    #[serde(default)]
+    pub severity: Severity,
+
+    /// Where the full text was saved, if it’s too long for some notifiers.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub diff_path: Option<PathBuf>,
+
+    /// The HTTP status of the response, if there was one.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub status: Option<u16>,
+}
+
+impl Notification {
+    /// A one line summary of the notification.
+    #[must_use]
+    pub fn summary(&self) -> String {
+        match (self.event, self.kind) {
+            (Event::Changed, None | Some(ChangeKind::Content)) => {
+                format!("{} changed", self.label)
+            }
+            (Event::Changed, Some(ChangeKind::Removed)) => {
+                format!("{} was removed", self.label)
+            }
+            (Event::Changed, Some(ChangeKind::Restored)) => {
+                format!("{} was restored", self.label)
+            }
+            (Event::Changed, Some(kind)) => {
+                format!("{} changed ({})", self.label, kind.as_str())
+            }
+            (Event::Failed, _) => format!("{} failed", self.label),
+            (Event::Recovered, _) => format!("{} recovered", self.label),
+            (Event::SoftError, _) => format!("{} looks broken", self.label),
+            (Event::New, _) => format!("Now monitoring {}", self.label),
+        }
+    }
+
+    /// The text shortened to fit within `limits`, with the number of lines
+    /// added and removed, and where to find the full text if it was shortened.
+    #[must_use]
+    pub fn summarized_text(&self, limits: Limits) -> String {
+        let mut text = String::new();
+        let counts = Counts::of(&self.text);
+        if self.event == Event::Changed && !counts.is_empty() {
+            let _ = writeln!(text, "{counts}");
+        }
+        let summary = summarize::summarize(&self.text, limits);
+        text.push_str(&summary);
+        if matches!(summary, Cow::Owned(_))
+            && let Some(path) = &self.diff_path
+        {
+            let _ = writeln!(text, "Full diff: {}", path.display());
+        }
+        text
+    }
+}
+
+/// What a notification is about.
+#[derive(
+    Clone,
+    Copy,
+    Debug,
+    Default,
+    Eq,
+    PartialEq,
+    serde::Serialize,
+    serde::Deserialize,
+)]
+#[serde(rename_all = "kebab-case")]
+pub enum Event {
+    /// The content changed.
+    #[default]
+    Changed,
+
+    /// The response failed expectations in the configuration.
+    Failed,
+
+    /// The response passed expectations after previously failing them, or
+    /// no longer looks like a soft error.
+    Recovered,
+
+    /// The response looks like a “not found” page, login page, or parked
+    /// domain even though it was successful.
+    SoftError,
+
+    /// The URL was checked for the first time, with `on_new = "notify"`.
+    New,
+}
+
+impl Event {
+    /// A short name for the event, e.g. `changed`.
+    #[must_use]
+    pub const fn as_str(self) -> &'static str {
+        match self {
+            Self::Changed => "changed",
+            Self::Failed => "failed",
+            Self::Recovered => "recovered",
+            Self::SoftError => "soft-error",
+            Self::New => "new",
+        }
+    }
+}
+
+/// What kind of change a notification is about.
+///
+/// This lets automation handle, say, a page being removed differently from an
+/// edit to it.
+#[derive(
+    Clone, Copy, Debug, Eq, PartialEq, serde::Serialize, serde::Deserialize,
+)]
+#[serde(rename_all = "kebab-case")]
+pub enum ChangeKind {
+    /// The content was edited.
+    Content,
+
+    /// The page started returning “not found” or “gone”.
+    Removed,
+
+    /// The page came back after being removed, or matches the pinned baseline
+    /// again.
+    Restored,
+
+    /// The page redirects somewhere else.
+    Redirect,
+
+    /// The TLS certificate changed.
+    Certificate,
+
+    /// Watched headers, like `Server`, changed.
+    Headers,
+
+    /// The HTTP version or resolved addresses changed.
+    Connection,
+
+    /// The language of the content changed.
+    Language,
+
+    /// An `alert` condition on an extracted value started to hold.
+    Value,
+}
+
+impl ChangeKind {
+    /// Classify a change in content between two responses.
+    ///
+    /// A page is removed when it starts returning 404 Not Found or 410 Gone,
+    /// and restored when it stops. Otherwise, the change is a redirect if the
+    /// final URL or the target of a redirect that wasn’t followed changed, or
+    /// an edit to the content.
+    pub fn between(old: &Response, new: &Response) -> Self {
+        match (old.is_removed(), new.is_removed()) {
+            (false, true) => Self::Removed,
+            (true, false) => Self::Restored,
+            _ if old.destination() != new.destination() => Self::Redirect,
+            _ => Self::Content,
+        }
+    }
+
+    /// A short name for the kind of change, e.g. `redirect`.
+    #[must_use]
+    pub const fn as_str(self) -> &'static str {
+        match self {
+            Self::Content => "content",
+            Self::Removed => "removed",
+            Self::Restored => "restored",
+            Self::Redirect => "redirect",
+            Self::Certificate => "certificate",
+            Self::Headers => "headers",
+            Self::Connection => "connection",
+            Self::Language => "language",
+            Self::Value => "value",
+        }
+    }
+}
+
+/// How important changes to a URL are, for routing notifications.
+#[derive(
+    Clone,
+    Copy,
+    Debug,
+    Default,
+    Eq,
+    Ord,
+    PartialEq,
+    PartialOrd,
+    serde::Serialize,
+    serde::Deserialize,
+)]
+#[serde(rename_all = "kebab-case")]
+pub enum Severity {
+    /// Changes that can wait, e.g. for a digest. Also called `info`.
+    #[serde(alias = "info")]
+    Low,
+
+    /// The default. Also called `warning`.
+    #[default]
+    #[serde(alias = "warning")]
+    Normal,
+
+    /// Changes that someone should look at right away. Also called
+    /// `critical`.
+    #[serde(alias = "critical")]
+    High,
+}
+
+impl Severity {
+    /// A short name for the severity, e.g. `high`.
+    #[must_use]
+    pub const fn as_str(self) -> &'static str {
+        match self {
+            Self::Low => "low",
+            Self::Normal => "normal",
+            Self::High => "high",
+        }
+    }
+}
+
+/// A rule selecting notifications for a notifier.
+///
+/// A notification matches if it matches every condition that’s set. A rule
+/// with no conditions matches everything.
+#[derive(Clone, Debug, Default, serde::Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct Rule {
+    /// Match URLs with any of these tags.
+    #[serde(default)]
+    pub tags: Vec<String>,
+
+    /// Match URLs that match this regular expression.
+    #[serde(default, deserialize_with = "deserialize_regex")]
+    pub url: Option<Regex>,
+
+    /// Match any of these events, e.g. `failed`.
+    #[serde(default)]
+    pub events: Vec<Event>,
+
+    /// Match any of these kinds of change, e.g. `certificate`.
+    #[serde(default)]
+    pub kinds: Vec<ChangeKind>,
+
+    /// Match notifications at least this severe.
+    #[serde(default)]
+    pub min_severity: Option<Severity>,
+}
+
+impl Rule {
+    /// Check if a notification matches the rule.
+    #[must_use]
+    pub fn matches(&self, notification: &Notification) -> bool {
+        (self.tags.is_empty()
+            || self.tags.iter().any(|tag| notification.tags.contains(tag)))
+            && self
+                .url
+                .as_ref()
+                .is_none_or(|url| url.is_match(notification.url.as_str()))
+            && (self.events.is_empty()
+                || self.events.contains(&notification.event))
+            && (self.kinds.is_empty()
+                || notification
+                    .kind
+                    .is_some_and(|kind| self.kinds.contains(&kind)))
+            && self
+                .min_severity
+                .is_none_or(|severity| notification.severity >= severity)
+    }
+}
+
+/// Deserialize an optional regular expression.
+fn deserialize_regex<'de, D>(deserializer: D) -> Result<Option<Regex>, D::Error>
+where
+    D: serde::Deserializer<'de>,
+{
+    Option::<String>::deserialize(deserializer)?
+        .map(|source| Regex::new(&source).map_err(serde::de::Error::custom))
+        .transpose()
+}
+
+/// Deserialize an optional template, checking its syntax.
+fn deserialize_template<'de, D>(
+    deserializer: D,
+) -> Result<Option<String>, D::Error>
+where
+    D: serde::Deserializer<'de>,
+{
+    let source = Option::<String>::deserialize(deserializer)?;
+    if let Some(source) = &source {
+        minijinja::Environment::new()
+            .template_from_str(source)
+            .map_err(serde::de::Error::custom)?;
+    }
+    Ok(source)
+}
+
+/// Templates for formatting messages.
+#[derive(Clone, Copy, Debug, Default)]
+pub struct Templates<'a> {
+    /// Template for the title of a message about one notification.
+    pub title: Option<&'a str>,
+
+    /// Template for the text about each notification.
+    pub text: Option<&'a str>,
+}
+
+/// The variables available to templates.
+#[derive(Debug, serde::Serialize)]
+pub struct TemplateContext<'a> {
+    /// The label for the URL.
+    pub label: &'a str,
+
+    /// The URL.
+    pub url: &'a str,
+
+    /// A one line summary, e.g. “Example changed”.
+    pub title: String,
+
+    /// The diff or other text, shortened to fit the notifier’s limits.
+    pub diff: Cow<'a, str>,
+
+    /// Where the full diff was saved, if it was.
+    pub diff_path: Option<Cow<'a, str>>,
+
+    /// The number of lines added plus the number removed.
+    pub changed_lines: usize,
+
+    /// The number of lines added.
+    pub added_lines: usize,
+
+    /// The number of lines removed.
+    pub removed_lines: usize,
+
+    /// The HTTP status code, if there was a response.
+    pub status: Option<u16>,
+
+    /// What happened, e.g. `changed` or `failed`.
+    pub event: &'static str,
+
+    /// What kind of change it was, e.g. `content`, if it was a change.
+    pub kind: Option<&'static str>,
+
+    /// How important changes to the URL are, e.g. `normal`.
+    pub severity: &'static str,
+
+    /// The tags of the URL.
+    pub tags: &'a [String],
+
+    /// When the change was detected, in RFC 3339 format.
+    pub detected_at: String,
+}
+
+impl<'a> TemplateContext<'a> {
+    /// Get the variables for a notification shortened to fit within `limits`.
+    #[must_use]
+    pub fn new(notification: &'a Notification, limits: Limits) -> Self {
+        let counts = Counts::of(&notification.text);
+        Self {
+            label: &notification.label,
+            url: notification.url.as_str(),
+            title: notification.summary(),
+            diff: summarize::summarize(&notification.text, limits),
+            diff_path: (notification.diff_path.as_deref())
+                .map(Path::to_string_lossy),
+            changed_lines: counts.added.saturating_add(counts.removed),
+            added_lines: counts.added,
+            removed_lines: counts.removed,
+            status: notification.status,
+            event: notification.event.as_str(),
+            kind: notification.kind.map(ChangeKind::as_str),
+            severity: notification.severity.as_str(),
+            tags: &notification.tags,
+            detected_at: notification.detected_at.to_rfc3339(),
+        }
+    }
+
+    /// Render a template with these variables.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if the template is invalid or couldn’t be rendered.
+    pub fn render(&self, source: &str) -> Result<String, minijinja::Error> {
+        minijinja::Environment::new()
+            .render_str(source, minijinja::value::Serde(self))
+    }
+}
+
+/// Render a template for a notification, or log a warning and return `None`
+/// if it fails so that the default format is used instead.
+fn render(
+    source: Option<&str>,
+    notification: &Notification,
+    limits: Limits,
+) -> Option<String> {
+    TemplateContext::new(notification, limits)
+        .render(source?)
+        .inspect_err(|error| {
+            tracing::warn!(
+                "Could not render template for {}: {error:#}",
+                notification.label,
+            );
+        })
+        .ok()
+}
+
+/// A message to send to a notifier, made from one or more notifications.
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub struct Message {
+    /// A one line summary.
+    pub title: String,
+
+    /// The full message as plain text.
+    pub text: String,
+
+    /// The full message as HTML.
+    pub html: String,
+
+    /// The highest severity of the notifications in the message.
+    pub severity: Severity,
+}
+
+impl Message {
+    /// Make a message about a single notification, shortened to fit within
+    /// `limits` and formatted with `templates`.
+    #[must_use]
+    pub fn single(
+        notification: &Notification,
+        limits: Limits,
+        templates: Templates<'_>,
+    ) -> Self {
+        let title = render(templates.title, notification, limits)
+            .unwrap_or_else(|| notification.summary());
+        let severity = notification.severity;
+        if let Some(text) = render(templates.text, notification, limits) {
+            return Self { title, html: text_html(&text), text, severity };
+        }
+
+        let text = notification.summarized_text(limits);
+        Self {
+            text: format!("{title}: {}\n\n{text}", notification.url),
+            html: html_section(notification, &text),
+            title,
+            severity,
+        }
+    }
+
+    /// Make a digest message combining several notifications, each shortened
+    /// to fit within `limits` and formatted with `templates`.
+    ///
+    /// The title template is only used if there is just one notification.
+    #[must_use]
+    pub fn digest(
+        notifications: &[Notification],
+        limits: Limits,
+        templates: Templates<'_>,
+    ) -> Self {
+        if let [notification] = notifications {
+            return Self::single(notification, limits, templates);
+        }
+
+        let title = format!("{} changes", notifications.len());
+        let mut text = format!("{title}\n");
+        let mut html = format!("<p><b>{}</b></p>", escape_html(&title));
+        for notification in notifications {
+            if let Some(section) = render(templates.text, notification, limits)
+            {
+                let _ = writeln!(text, "\n{}", section.trim_end());
+                html.push_str(&text_html(&section));
+                continue;
+            }
+
+            let summary = notification.summarized_text(limits);
+            let _ = write!(
+                text,
+                "\n=== {} ({})\n{summary}",
+                notification.label, notification.url,
+            );
+            html.push_str(&html_section(notification, &summary));
+        }
+
+        let severity = notifications
+            .iter()
+            .map(|notification| notification.severity)
+            .max()
+            .unwrap_or_default();
+        Self { title, text, html, severity }
+    }
+}
+
+/// Format a notification with its (summarized) text as HTML.
+fn html_section(notification: &Notification, text: &str) -> String {
+    format!(
+        "<p><a href=\"{}\">{}</a> {}</p>\
+        <pre><code class=\"language-diff\">{}</code></pre>",
+        escape_html(notification.url.as_str()),
+        escape_html(&notification.label),
+        match notification.event {
+            Event::Changed => "changed",
+            Event::Failed => "failed",
+            Event::Recovered => "recovered",
+            Event::SoftError => "looks broken",
+            Event::New => "is now monitored",
+        },
+        escape_html(text),
+    )
+}
+
+/// Format text from a template as HTML.
+fn text_html(text: &str) -> String {
+    format!(
+        "<p>{}</p>",
+        escape_html(text.trim_end()).replace('\n', "<br>")
+    )
+}
+
+/// When to deliver notifications, how long they may be, and how they’re
+/// formatted.
+#[derive(Clone, Debug, Default, serde::Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct Schedule {
+    /// Don’t deliver notifications during this time, e.g. `"23:00-07:00"`.
+    ///
+    /// Notifications are delivered as a digest after quiet hours end.
+    #[serde(default)]
+    pub quiet_hours: Option<TimeRange>,
+
+    /// Collect notifications for this long and deliver them as one digest,
+    /// e.g. `"1h"`.
+    #[serde(default, with = "humantime_serde")]
+    pub batch: Option<Duration>,
+
+    /// Shorten diffs in notifications to this many lines; defaults to
+    /// [`summarize::DEFAULT_MAX_LINES`].
+    #[serde(default)]
+    pub max_lines: Option<usize>,
+
+    /// Shorten diffs in notifications to this many bytes; defaults to
+    /// [`summarize::DEFAULT_MAX_BYTES`].
+    #[serde(default)]
+    pub max_bytes: Option<usize>,
+
+    /// Template for the title of messages; see [`TemplateContext`].
+    #[serde(default, deserialize_with = "deserialize_template")]
+    pub title_template: Option<String>,
+
+    /// Template for the text about each notification in messages; see
+    /// [`TemplateContext`].
+    #[serde(default, deserialize_with = "deserialize_template")]
+    pub template: Option<String>,
+}
+
+/// A configured notifier.
+#[derive(Clone, Debug, serde::Deserialize)]
+pub struct Notifier {
+    /// Name used to identify the notifier; defaults to its description.
+    #[serde(default)]
+    pub name: Option<String>,
+
+    /// Only send notifications that match at least one of these rules. If
+    /// there are none, all notifications are sent.
+    #[serde(default)]
+    pub rules: Vec<Rule>,
+
+    /// Don’t send notifications that match any of these rules.
+    #[serde(default)]
+    pub exclude: Vec<Rule>,
+
+    /// When to deliver notifications; overrides the global `[notify]`
+    /// settings.
+    #[serde(flatten)]
+    pub schedule: Schedule,
+
+    /// Where to send notifications.
+    #[serde(flatten)]
+    pub backend: Backend,
+}
+
+impl Notifier {
+    /// The name used to identify the notifier, e.g. in the outbox.
+    #[must_use]
+    pub fn name(&self) -> String {
+        self.name
+            .clone()
+            .unwrap_or_else(|| self.backend.description())
+    }
+
+    /// Check if a notification should be sent to this notifier.
+    #[must_use]
+    pub fn accepts(&self, notification: &Notification) -> bool {
+        (self.rules.is_empty()
+            || self.rules.iter().any(|rule| rule.matches(notification)))
+            && !self.exclude.iter().any(|rule| rule.matches(notification))
+    }
+
+    /// Templates for messages sent to this notifier.
+    #[must_use]
+    pub fn templates<'a>(&'a self, defaults: &'a Schedule) -> Templates<'a> {
+        Templates {
+            title: (self.schedule.title_template.as_deref())
+                .or(defaults.title_template.as_deref()),
+            text: (self.schedule.template.as_deref())
+                .or(defaults.template.as_deref()),
+        }
+    }
+
+    /// How long notifications sent to this notifier may be.
+    #[must_use]
+    pub fn limits(&self, defaults: &Schedule) -> Limits {
+        let default = Limits::default();
+        Limits {
+            lines: (self.schedule.max_lines)
+                .or(defaults.max_lines)
+                .unwrap_or(default.lines),
+            bytes: (self.schedule.max_bytes)
+                .or(defaults.max_bytes)
+                .unwrap_or(default.bytes),
+        }
+    }
+}
+
+/// A notification backend.
+#[derive(Clone, Debug, serde::Deserialize)]
+#[serde(tag = "type", rename_all = "kebab-case", deny_unknown_fields)]
+pub enum Backend {
+    /// Post a notice to a Matrix room.
+    Matrix {
+        /// Base URL of the homeserver, e.g. `https://matrix.org`.
+        homeserver: Url,
+
+        /// Access token for the user that posts the notices.
+        access_token: Secret,
+
+        /// Room to post in, e.g. `!abcdefg:matrix.org`.
+        room_id: String,
+    },
+}
+
+impl Backend {
+    /// A short description of the backend that doesn’t include secrets.
+    #[must_use]
+    pub fn description(&self) -> String {
+        match self {
+            Self::Matrix { room_id, .. } => format!("Matrix room {room_id}"),
+        }
+    }
+
+    /// Get mutable references to the secrets in the backend’s settings.
+    pub fn secrets_mut(&mut self) -> Vec<&mut Secret> {
+        match self {
+            Self::Matrix { access_token, .. } => vec![access_token],
+        }
+    }
+
+    /// Send a message.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if the message could not be sent.
+    pub async fn send(
+        &self,
+        client: &reqwest::Client,
+        message: &Message,
+    ) -> anyhow::Result<()> {
+        match self {
+            Self::Matrix { homeserver, access_token, room_id } => {
+                send_matrix(client, homeserver, access_token, room_id, message)
+                    .await
+            }
+        }
+    }
+}
+
+/// Notifications waiting to be delivered, by notifier name.
+#[derive(Clone, Debug, Default, serde::Serialize, serde::Deserialize)]
+pub struct Outbox {
+    /// Notifications waiting for each notifier.
+    pub pending: BTreeMap<String, Vec<Notification>>,
+}
+
+impl Outbox {
+    /// Get the path to the outbox within a state directory.
+    #[must_use]
+    pub fn path(state_dir: &Path) -> PathBuf {
+        state_dir.join("outbox.ron")
+    }
+
+    /// Load the outbox from the state directory.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if the outbox exists but couldn’t be read or parsed.
+    pub fn load(state_dir: &Path) -> anyhow::Result<Self> {
+        match fs::read(Self::path(state_dir)) {
+            Ok(source) => Ok(ron::de::from_bytes(&source)?),
+            Err(error) if error.kind() == io::ErrorKind::NotFound => {
+                Ok(Self::default())
+            }
+            Err(error) => Err(error.into()),
+        }
+    }
+
+    /// Save the outbox to the state directory.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if the outbox could not be written.
+    pub fn save(&self, state_dir: &Path) -> anyhow::Result<()> {
+        let contents = ron::ser::to_string_pretty(
+            self,
+            ron::ser::PrettyConfig::default(),
+        )?;
+        atomic_write(&Self::path(state_dir), contents.as_bytes())?;
+        Ok(())
+    }
+
+    /// Deliver notifications to a notifier, or queue them if it’s not time.
+    ///
+    /// `notifications` are the new notifications from this run. They are
+    /// delivered along with anything already waiting in the outbox.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if the notifications could not be sent. They remain in
+    /// the outbox to be retried on the next run.
+    pub async fn deliver(
+        &mut self,
+        client: &reqwest::Client,
+        notifier: &Notifier,
+        defaults: &Schedule,
+        notifications: Vec<Notification>,
+    ) -> anyhow::Result<()> {
+        let name = notifier.name();
+        let pending = self.pending.entry(name.clone()).or_default();
+        let deferred = !pending.is_empty();
+        pending.extend(notifications);
+        if pending.is_empty() {
+            return Ok(());
+        }
+
+        let quiet_hours =
+            notifier.schedule.quiet_hours.or(defaults.quiet_hours);
+        if quiet_hours.is_some_and(|quiet| quiet.contains(Local::now().time()))
+        {
+            tracing::info!("Quiet hours: holding notifications for {name}");
+            return Ok(());
+        }
+
+        let batch = notifier.schedule.batch.or(defaults.batch);
+        if let Some(batch) = batch {
+            let due = pending
+                .iter()
+                .map(|n| n.detected_at)
+                .min()
+                .zip(chrono::Duration::from_std(batch).ok())
+                .and_then(|(oldest, batch)| oldest.checked_add_signed(batch));
+            if due.is_some_and(|due| Utc::now() < due) {
+                tracing::info!("Batching notifications for {name}");
+                return Ok(());
+            }
+        }
+
+        let limits = notifier.limits(defaults);
+        let templates = notifier.templates(defaults);
+        if deferred || batch.is_some() {
+            let message = Message::digest(pending, limits, templates);
+            notifier.backend.send(client, &message).await?;
+        } else {
+            // Remove each notification once it’s sent, so that if sending a
+            // later one fails, only the rest are retried.
+            while let Some(notification) = pending.first() {
+                let message = Message::single(notification, limits, templates);
+                notifier.backend.send(client, &message).await?;
+                pending.remove(0);
+            }
+        }
+
+        self.pending.remove(&name);
+        Ok(())
+    }
+}
+
+/// Post a message to a Matrix room as an `m.notice`.
+///
+/// # Errors
+///
+/// Returns an error if the request fails.
+async fn send_matrix(
+    client: &reqwest::Client,
+    homeserver: &Url,
+    access_token: &Secret,
+    room_id: &str,
+    message: &Message,
+) -> anyhow::Result<()> {
+    /// Counter to make transaction IDs unique within this process.
+    static TRANSACTION: AtomicU64 = AtomicU64::new(0);
+
+    let transaction_id = format!(
+        "monitorbot-{}-{}-{}",
+        std::process::id(),
+        Utc::now().timestamp_micros(),
+        TRANSACTION.fetch_add(1, Ordering::Relaxed),
+    );
+
+    let mut url = homeserver.clone();
+    url.path_segments_mut()
+        .map_err(|()| anyhow::anyhow!("invalid homeserver URL {homeserver}"))?
+        .pop_if_empty()
+        .extend([
+            "_matrix",
+            "client",
+            "v3",
+            "rooms",
+            room_id,
+            "send",
+            "m.room.message",
+            &transaction_id,
+        ]);
+
+    // Clients don’t usually alert for notices, so only send high severity
+    // messages as normal text.
+    let msgtype = if message.severity == Severity::High {
+        "m.text"
+    } else {
+        "m.notice"
+    };
+    let body = serde_json::json!({
+        "msgtype": msgtype,
+        "body": message.text,
+        "format": "org.matrix.custom.html",
+        "formatted_body": message.html,
+    });
+
+    client
+        .put(url)
+        .bearer_auth(access_token.expose()?)
+        .json(&body)
+        .send()
+        .await
+        .and_then(reqwest::Response::error_for_status)
+        .context("could not post to Matrix")?;
+
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use assert2::check;
+    use chrono::NaiveTime;
+    use tokio::io::{AsyncBufReadExt, AsyncReadExt, AsyncWriteExt, BufReader};
+    use tokio::net::TcpListener;
+
+    /// Make a `NaiveTime`.
+    fn t(hour: u32, minute: u32) -> NaiveTime {
+        NaiveTime::from_hms_opt(hour, minute, 0).unwrap()
+    }
+
+    #[test]
+    fn test_quiet_hours() {
+        let quiet = TimeRange::try_from("23:00-07:00".to_owned()).unwrap();
+        check!(quiet.contains(t(23, 0)));
+        check!(quiet.contains(t(2, 30)));
+        check!(!quiet.contains(t(7, 0)));
+        check!(!quiet.contains(t(12, 0)));
+
+        let quiet = TimeRange::try_from("09:00 - 17:30".to_owned()).unwrap();
+        check!(quiet.contains(t(17, 29)));
+        check!(!quiet.contains(t(17, 30)));
+        check!(!quiet.contains(t(8, 59)));
+
+        check!(TimeRange::try_from("9-5".to_owned()).is_err());
+    }
+
+    #[test]
+    fn test_summarized_text() {
+        let mut notification = Notification {
+            label: "Example".to_owned(),
+            url: "https://example.com/".parse().unwrap(),
+            text: "-one\n+1\n+2\n+3\n+4\n".to_owned(),
+            detected_at: Utc::now(),
+            event: Event::Changed,
+            kind: Some(ChangeKind::Content),
+            tags: Vec::new(),
+            severity: Severity::Normal,
+            diff_path: Some(PathBuf::from("/state/diffs/example.diff")),
+            status: Some(200),
+        };
+        check!(
+            notification.summarized_text(Limits::default())
+                == "+4 -1 lines\n-one\n+1\n+2\n+3\n+4\n"
+        );
+        check!(
+            notification.summarized_text(Limits { lines: 3, bytes: 1000 })
+                == "+4 -1 lines\n-one\n+1\n… 3 more changed lines …\n\
+                    Full diff: /state/diffs/example.diff\n"
+        );
+
+        notification.event = Event::Failed;
+        notification.text = "Expected status 200\n".to_owned();
+        check!(
+            notification.summarized_text(Limits::default())
+                == "Expected status 200\n"
+        );
+    }
+    #[test]
+    fn test_templates() {
+        let schedule: Schedule = toml::from_str(
+            r#"
+            title_template = "{{ label }}: {{ changed_lines }} lines"
+            template = "{{ title }} ({{ status }}) {{ tags | join(', ') }}\n{{ diff }}"
+            "#,
+        )
+        .unwrap();
+        let templates = Templates {
+            title: schedule.title_template.as_deref(),
+            text: schedule.template.as_deref(),
+        };
+        let notification = Notification {
+            label: "Example".to_owned(),
+            url: "https://example.com/".parse().unwrap(),
+            text: "-one\n+1\n+2\n".to_owned(),
+            detected_at: Utc::now(),
+            event: Event::Changed,
+            kind: Some(ChangeKind::Content),
+            tags: vec!["docs".to_owned(), "security".to_owned()],
+            severity: Severity::Normal,
+            diff_path: None,
+            status: Some(200),
+        };
+
+        let message =
+            Message::single(&notification, Limits::default(), templates);
+        check!(message.title == "Example: 3 lines");
+        check!(
+            message.text
+                == "Example changed (200) docs, security\n-one\n+1\n+2\n"
+        );
+        check!(
+            message.html
+                == "<p>Example changed (200) docs, security<br>-one<br>+1<br>\
+                    +2</p>"
+        );
+
+        let message = Message::digest(
+            &[notification.clone(), notification],
+            Limits { lines: 1, bytes: 1000 },
+            templates,
+        );
+        check!(message.title == "2 changes");
+        let section = "Example changed (200) docs, security\n\
+            … 3 more changed lines …\n";
+        check!(message.text == format!("2 changes\n\n{section}\n{section}"));
+
+        check!(
+            toml::from_str::<Schedule>("template = '{{ diff'")
+                .unwrap_err()
+                .to_string()
+                .contains("syntax error")
+        );
+    }
+
+    #[test]
+    fn test_rules() {
+        let notifier: Notifier = toml::from_str(
+            r#"
+            type = "matrix"
+            homeserver = "https://matrix.org"
+            access_token = "token"
+            room_id = "!room:matrix.org"
+            exclude = [{ kinds = ["headers"] }]
+
+            [[rules]]
+            tags = ["security"]
+
+            [[rules]]
+            url = '^https://example\.com/status'
+            events = ["failed"]
+            min_severity = "high"
+            "#,
+        )
+        .unwrap();
+
+        let mut notification = Notification {
+            label: "Example".to_owned(),
+            url: "https://example.com/".parse().unwrap(),
+            text: String::new(),
+            detected_at: Utc::now(),
+            event: Event::Changed,
+            kind: Some(ChangeKind::Content),
+            tags: vec!["security".to_owned()],
+            severity: Severity::Normal,
+            diff_path: None,
+            status: None,
+        };
+        check!(notifier.accepts(&notification));
+        notification.kind = Some(ChangeKind::Headers);
+        check!(!notifier.accepts(&notification));
+
+        notification.tags.clear();
+        notification.kind = None;
+        notification.event = Event::Failed;
+        check!(!notifier.accepts(&notification));
+        notification.url = "https://example.com/status/db".parse().unwrap();
+        check!(!notifier.accepts(&notification));
+        notification.severity = Severity::High;
+        check!(notifier.accepts(&notification));
+
+        let matrix = r#"
+            type = "matrix"
+            homeserver = "https://matrix.org"
+            access_token = "token"
+            room_id = "!room:matrix.org"
+        "#;
+        let scheduled = format!(
+            "{matrix}\nname = \"ops\"\nbatch = \"1h\"\nmax_lines = 5\n\
+            quiet_hours = \"23:00-07:00\"\ntemplate = \"{{{{ label }}}}\""
+        );
+        check!(toml::from_str::<Notifier>(&scheduled).is_ok());
+        for typo in ["quiet_hour = \"23:00-07:00\"", "rule = []"] {
+            check!(
+                toml::from_str::<Notifier>(&format!("{matrix}\n{typo}"))
+                    .unwrap_err()
+                    .to_string()
+                    .contains("unknown field")
+            );
+        }
+
+        let rule: Rule =
+            toml::from_str(r#"min_severity = "critical""#).unwrap();
+        check!(rule.min_severity == Some(Severity::High));
+
+        check!(
+            toml::from_str::<Rule>(r#"url = "(""#)
+                .unwrap_err()
+                .to_string()
+                .contains("regex parse error")
+        );
+    }
+
+    #[tokio::test]
+    async fn test_deliver() {
+        // A homeserver that accepts a message, fails, then accepts another.
+        let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let address = listener.local_addr().unwrap();
+        tokio::spawn(async move {
+            for status in ["200 OK", "500 Internal Server Error", "200 OK"] {
+                let (socket, _) = listener.accept().await.unwrap();
+                let mut socket = BufReader::new(socket);
+                let mut length = 0;
+                let mut line = String::new();
+                while line != "\r\n" {
+                    line.clear();
+                    socket.read_line(&mut line).await.unwrap();
+                    if let Some(value) =
+                        line.to_lowercase().strip_prefix("content-length:")
+                    {
+                        length = value.trim().parse().unwrap();
+                    }
+                }
+                socket.read_exact(&mut vec![0; length]).await.unwrap();
+                let reply = format!(
+                    "HTTP/1.1 {status}\r\nContent-Length: 2\r\n\
+                    Connection: close\r\n\r\n{{}}"
+                );
+                socket
+                    .into_inner()
+                    .write_all(reply.as_bytes())
+                    .await
+                    .unwrap();
+            }
+        });
+
+        let notifier: Notifier = toml::from_str(&format!(
+            "type = \"matrix\"\nhomeserver = \"http://{address}/\"\n\
+            access_token = \"token\"\nroom_id = \"!room:matrix.org\""
+        ))
+        .unwrap();
+        let notification = |label: &str| Notification {
+            label: label.to_owned(),
+            url: "https://example.com/".parse().unwrap(),
+            text: String::new(),
+            detected_at: Utc::now(),
+            event: Event::Changed,
+            kind: Some(ChangeKind::Content),
+            tags: Vec::new(),
+            severity: Severity::Normal,
+            diff_path: None,
+            status: None,
+        };
+        let pending = |outbox: &Outbox| {
+            outbox
+                .pending
+                .values()
+                .flatten()
+                .map(|notification| notification.label.clone())
+                .collect::<Vec<_>>()
+        };
+
+        let client = reqwest::Client::new();
+        let defaults = Schedule::default();
+        let mut outbox = Outbox::default();
+        let notifications =
+            vec![notification("a"), notification("b"), notification("c")];
+        check!(
+            outbox
+                .deliver(&client, &notifier, &defaults, notifications)
+                .await
+                .is_err()
+        );
+        // “a” was sent, so only the rest are retried, as a digest.
+        check!(pending(&outbox) == ["b", "c"]);
+        outbox
+            .deliver(&client, &notifier, &defaults, Vec::new())
+            .await
+            .unwrap();
+        check!(outbox.pending.is_empty());
+    }
+
+    #[test]
+    fn test_change_kind() {
+        let response = |url: &str, status| Response {
+            url: url.parse().unwrap(),
+            version: http::Version::HTTP_11,
+            status,
+            headers: http::HeaderMap::new(),
+            body: bytes::Bytes::new(),
+            spooled: None,
+            certificate_sha256: None,
+            peer_address: None,
+        };
+        let ok = response("https://example.com/", http::StatusCode::OK);
+        let gone = response("https://example.com/", http::StatusCode::GONE);
+        let moved = response("https://example.com/new", http::StatusCode::OK);
+        check!(ChangeKind::between(&ok, &ok) == ChangeKind::Content);
+        check!(ChangeKind::between(&ok, &gone) == ChangeKind::Removed);
+        check!(ChangeKind::between(&gone, &moved) == ChangeKind::Restored);
+        check!(ChangeKind::between(&ok, &moved) == ChangeKind::Redirect);
+
+        let redirect = |location: &str| {
+            let mut redirect =
+                response("https://example.com/", http::StatusCode::FOUND);
+            redirect
+                .headers
+                .insert(http::header::LOCATION, location.parse().unwrap());
+            redirect
+        };
+        check!(
+            redirect("/a").redirect_target()
+                == Some("https://example.com/a".parse().unwrap())
+        );
+        check!(ok.redirect_target() == None);
+        check!(
+            ChangeKind::between(&redirect("/a"), &redirect("/a"))
+                == ChangeKind::Content
+        );
+        check!(
+            ChangeKind::between(&redirect("/a"), &redirect("https://other/"))
+                == ChangeKind::Redirect
+        );
+
+        let mut notification = Notification {
+            label: "Example".to_owned(),
+            url: "https://example.com/".parse().unwrap(),
+            text: String::new(),
+            detected_at: Utc::now(),
+            event: Event::Changed,
+            kind: Some(ChangeKind::Removed),
+            tags: Vec::new(),
+            severity: Severity::Normal,
+            diff_path: None,
+            status: None,
+        };
+        check!(notification.summary() == "Example was removed");
+        notification.kind = Some(ChangeKind::Certificate);
+        check!(notification.summary() == "Example changed (certificate)");
+    }
+}
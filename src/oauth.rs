@@ -0,0 +1,267 @@
+//! Getting access tokens for APIs protected by OAuth 2.0.
+//!
+//! A URL with `oauth2` gets an access token from the token endpoint with the
+//! client credentials grant ([RFC 6749 § 4.4]) and sends it in the
+//! `Authorization` header. Tokens are cached until shortly before they expire,
+//! so they’re reused between checks when running with `--daemon`.
+//!
+//! [RFC 6749 § 4.4]: https://www.rfc-editor.org/rfc/rfc6749#section-4.4
+
+use anyhow::{Context, bail};
+use http::header::{self, HeaderValue};
+use std::collections::HashMap;
+use std::sync::{Arc, Mutex, PoisonError};
+use std::time::Duration;
+use tokio::time::Instant;
+use url::Url;
+
+use crate::secret::Secret;
+
+/// How long before a token expires to stop using it.
+const EXPIRY_MARGIN: Duration = Duration::from_secs(30);
+
+/// OAuth 2.0 client credentials for a URL.
+#[derive(Clone, Debug, Eq, PartialEq, serde::Deserialize, serde::Serialize)]
+#[serde(deny_unknown_fields)]
+pub struct OAuth2 {
+    /// The token endpoint, e.g. `https://auth.example.com/oauth/token`.
+    pub token_url: Url,
+
+    /// The client ID.
+    pub client_id: String,
+
+    /// The client secret.
+    pub client_secret: Secret,
+
+    /// Scopes to request.
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub scopes: Vec<String>,
+
+    /// How to send the client ID and secret to the token endpoint.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub client_auth: Option<ClientAuth>,
+}
+
+/// How to authenticate to the token endpoint.
+#[derive(
+    Clone,
+    Copy,
+    Debug,
+    Default,
+    Eq,
+    PartialEq,
+    serde::Deserialize,
+    serde::Serialize,
+)]
+#[serde(rename_all = "kebab-case")]
+pub enum ClientAuth {
+    /// HTTP basic authentication (`client_secret_basic`).
+    #[default]
+    Basic,
+
+    /// Parameters in the request body (`client_secret_post`).
+    Post,
+}
+
+/// The parts of a token endpoint response that we use.
+#[derive(Clone, Debug, serde::Deserialize)]
+struct TokenResponse {
+    /// The access token.
+    access_token: String,
+
+    /// The type of the token; only `Bearer` is supported.
+    token_type: String,
+
+    /// How long the token is valid for in seconds, if it expires.
+    #[serde(default)]
+    expires_in: Option<u64>,
+}
+
+/// An access token.
+#[derive(Clone, Debug)]
+struct Token {
+    /// The access token.
+    access_token: Secret,
+
+    /// When to stop using the token, if it expires.
+    expires_at: Option<Instant>,
+}
+
+impl Token {
+    /// Make a token from a token endpoint response received at `now`.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if the token isn’t a bearer token.
+    fn new(response: TokenResponse, now: Instant) -> anyhow::Result<Self> {
+        if !response.token_type.eq_ignore_ascii_case("bearer") {
+            bail!("unsupported token type {:?}", response.token_type);
+        }
+        Ok(Self {
+            access_token: response.access_token.into(),
+            expires_at: response.expires_in.and_then(|seconds| {
+                now.checked_add(
+                    Duration::from_secs(seconds).saturating_sub(EXPIRY_MARGIN),
+                )
+            }),
+        })
+    }
+
+    /// Check if the token can still be used at `now`.
+    fn is_valid(&self, now: Instant) -> bool {
+        self.expires_at.is_none_or(|expires_at| now < expires_at)
+    }
+}
+
+/// Identifies the token for a set of credentials.
+type Key = (Url, String, Vec<String>);
+
+/// Access tokens that can be reused, shared between clones.
+#[derive(Clone, Debug, Default)]
+pub struct TokenCache {
+    /// The tokens by endpoint, client ID, and scopes.
+    tokens: Arc<Mutex<HashMap<Key, Token>>>,
+}
+
+impl TokenCache {
+    /// Get the `Authorization` header for a URL, requesting a new token if
+    /// there isn’t a valid one cached.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if a token couldn’t be requested.
+    pub async fn authorization(
+        &self,
+        client: &reqwest::Client,
+        oauth2: &OAuth2,
+    ) -> anyhow::Result<HeaderValue> {
+        let key = key(oauth2);
+        let cached = self
+            .lock()
+            .get(&key)
+            .filter(|token| token.is_valid(Instant::now()))
+            .cloned();
+        let token = if let Some(token) = cached {
+            token
+        } else {
+            let token =
+                request_token(client, oauth2).await.with_context(|| {
+                    format!("could not get token from {}", oauth2.token_url)
+                })?;
+            self.lock().insert(key, token.clone());
+            token
+        };
+
+        let mut value = HeaderValue::try_from(format!(
+            "Bearer {}",
+            token.access_token.expose()?,
+        ))
+        .context("invalid access token")?;
+        value.set_sensitive(true);
+        Ok(value)
+    }
+
+    /// Forget the token for a URL, e.g. because it was rejected.
+    pub fn invalidate(&self, oauth2: &OAuth2) {
+        self.lock().remove(&key(oauth2));
+    }
+
+    /// Lock the tokens.
+    fn lock(&self) -> std::sync::MutexGuard<'_, HashMap<Key, Token>> {
+        self.tokens.lock().unwrap_or_else(PoisonError::into_inner)
+    }
+}
+
+/// Get the key for the token for a set of credentials.
+fn key(oauth2: &OAuth2) -> Key {
+    (
+        oauth2.token_url.clone(),
+        oauth2.client_id.clone(),
+        oauth2.scopes.clone(),
+    )
+}
+
+/// Request a token from the token endpoint.
+///
+/// # Errors
+///
+/// Returns an error if the request failed or the response was invalid.
+async fn request_token(
+    client: &reqwest::Client,
+    oauth2: &OAuth2,
+) -> anyhow::Result<Token> {
+    let client_auth = oauth2.client_auth.unwrap_or_default();
+    let mut request = client
+        .post(oauth2.token_url.clone())
+        .header(header::ACCEPT, "application/json")
+        .header(header::CONTENT_TYPE, "application/x-www-form-urlencoded")
+        .body(request_body(oauth2, client_auth)?);
+    if client_auth == ClientAuth::Basic {
+        request = request.basic_auth(
+            &oauth2.client_id,
+            Some(oauth2.client_secret.expose()?),
+        );
+    }
+
+    let now = Instant::now();
+    let response: TokenResponse = request
+        .send()
+        .await
+        .and_then(reqwest::Response::error_for_status)?
+        .json()
+        .await
+        .context("invalid token response")?;
+    Token::new(response, now)
+}
+
+/// Make the form-encoded body of a token request.
+///
+/// # Errors
+///
+/// Returns an error if the client secret hasn’t been resolved.
+fn request_body(
+    oauth2: &OAuth2,
+    client_auth: ClientAuth,
+) -> anyhow::Result<String> {
+    let mut form = url::form_urlencoded::Serializer::new(String::new());
+    form.append_pair("grant_type", "client_credentials");
+    if !oauth2.scopes.is_empty() {
+        form.append_pair("scope", &oauth2.scopes.join(" "));
+    }
+    if client_auth == ClientAuth::Post {
+        form.append_pair("client_id", &oauth2.client_id);
+        form.append_pair("client_secret", oauth2.client_secret.expose()?);
+    }
+    Ok(form.finish())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use assert2::check;
+
+    #[test]
+    fn test_token() {
+        let now = Instant::now();
+        let after =
+            |seconds| now.checked_add(Duration::from_secs(seconds)).unwrap();
+        let response = |token_type: &str, expires_in| TokenResponse {
+            access_token: "abc".to_owned(),
+            token_type: token_type.to_owned(),
+            expires_in,
+        };
+
+        let token = Token::new(response("Bearer", Some(3600)), now).unwrap();
+        check!(token.access_token.expose().unwrap() == "abc");
+        check!(token.is_valid(after(3569)));
+        check!(!token.is_valid(after(3570)));
+
+        let token = Token::new(response("bearer", None), now).unwrap();
+        check!(token.is_valid(after(1_000_000)));
+
+        let token = Token::new(response("bearer", Some(10)), now).unwrap();
+        check!(!token.is_valid(now));
+
+        check!(Token::new(response("mac", None), now).is_err());
+    }
+}
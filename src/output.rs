@@ -0,0 +1,281 @@
+//! Output to the terminal.
+
+use std::borrow::Cow;
+use std::collections::vec_deque::VecDeque;
+use std::io;
+use termcolor::{Color, ColorSpec};
+
+use sections::Sections;
+
+mod sections;
+mod sentences;
+mod side_by_side;
+
+pub use sentences::{print_sentence_diff, split_sentences};
+pub use side_by_side::print_side_by_side;
+
+/// Width to use for side-by-side diffs if the terminal width is unknown.
+const DEFAULT_WIDTH: usize = 80;
+
+/// How to show diffs in the terminal.
+#[derive(Clone, Copy, Debug, Default, Eq, PartialEq, clap::ValueEnum)]
+pub enum DiffView {
+    /// Removed and added lines interleaved, marked with `-` and `+`.
+    #[default]
+    Unified,
+
+    /// Old and new versions in two columns, sized to the terminal.
+    SideBySide,
+}
+
+/// How finely to compare text in diffs.
+#[derive(Clone, Copy, Debug, Default, Eq, PartialEq, clap::ValueEnum)]
+pub enum DiffGranularity {
+    /// Whole lines.
+    #[default]
+    Line,
+
+    /// Sentences, with the words that changed within them highlighted.
+    Sentence,
+}
+
+impl DiffGranularity {
+    /// Prepare text to be diffed line by line at this granularity.
+    #[must_use]
+    pub fn prepare(self, text: &str) -> Cow<'_, str> {
+        match self {
+            Self::Line => Cow::Borrowed(text),
+            Self::Sentence => Cow::Owned(split_sentences(text)),
+        }
+    }
+}
+
+/// Get the width of the terminal on stdout.
+///
+/// If stdout isn’t a terminal, this uses `$COLUMNS` if it’s set, or 80.
+#[must_use]
+pub fn terminal_width() -> usize {
+    terminal_size::terminal_size()
+        .map(|(width, _)| usize::from(width.0))
+        .or_else(|| std::env::var("COLUMNS").ok()?.parse().ok())
+        .unwrap_or(DEFAULT_WIDTH)
+}
+
+/// Print a heading identifying the URL that output is about.
+///
+/// # Panics
+///
+/// Panics if the output couldn’t be written, like `println!`.
+pub fn print_heading<S>(out: &mut S, label: &str)
+where
+    S: termcolor::WriteColor + io::Write + ?Sized,
+{
+    // Use `unwrap()` here because these would be IO errors, so we may as well
+    // act like `println!`.
+    out.set_color(ColorSpec::new().set_bold(true)).unwrap();
+    writeln!(out, "=== {label}").unwrap();
+    out.reset().unwrap();
+}
+
+/// Print a pretty diff.
+///
+/// `context_len` is the number of unchanged lines to show around each change,
+/// or `None` to show the whole document.
+///
+/// # Panics
+///
+/// Panics if the output couldn’t be written, like `println!`.
+#[expect(clippy::iter_with_drain, reason = "lint is incorrect")]
+pub fn print_pretty_diff<S>(
+    out: &mut S,
+    old: &str,
+    new: &str,
+    context_len: Option<usize>,
+) where
+    S: termcolor::WriteColor + io::Write + ?Sized,
+{
+    let mut context = VecDeque::new();
+    let mut lines_since_diff: Option<usize> = None;
+    // Only show where hunks are when some lines are left out.
+    let mut sections = context_len.map(|_| Sections::new());
+
+    let mut old_color = ColorSpec::new();
+    old_color.set_fg(Some(Color::Red)).set_intense(true);
+    let mut new_color = ColorSpec::new();
+    new_color.set_fg(Some(Color::Green)).set_intense(true);
+
+    for diff in diff::lines(old, new) {
+        match diff {
+            diff::Result::Left(old_line) => {
+                if let Some(sections) = &mut sections {
+                    sections.print_header(out).unwrap();
+                }
+                for line in context.drain(..) {
+                    writeln!(out, " {line}").unwrap();
+                }
+                // Use `unwrap()` here because these would be IO errors, so we
+                // may as well act like `println!`.
+                out.set_color(&old_color).unwrap();
+                writeln!(out, "-{old_line}").unwrap();
+                out.reset().unwrap();
+                lines_since_diff = Some(0);
+            }
+            diff::Result::Right(new_line) => {
+                if let Some(sections) = &mut sections {
+                    sections.print_header(out).unwrap();
+                }
+                for line in context.drain(..) {
+                    writeln!(out, " {line}").unwrap();
+                }
+                // Use `unwrap()` here because these would be IO errors, so we
+                // may as well act like `println!`.
+                out.set_color(&new_color).unwrap();
+                writeln!(out, "+{new_line}").unwrap();
+                out.reset().unwrap();
+                if let Some(sections) = &mut sections {
+                    sections.update(new_line);
+                }
+                lines_since_diff = Some(0);
+            }
+            diff::Result::Both(line, _) => {
+                if let Some(sections) = &mut sections {
+                    sections.update(line);
+                }
+                let Some(context_len) = context_len else {
+                    writeln!(out, " {line}").unwrap();
+                    continue;
+                };
+
+                if let Some(count) = lines_since_diff
+                    && count < context_len
+                {
+                    writeln!(out, " {line}").unwrap();
+                    lines_since_diff = Some(count.saturating_add(1));
+                } else {
+                    lines_since_diff = None;
+                    context.push_back(line);
+                    if context.len() > context_len {
+                        context.pop_front();
+                        if let Some(sections) = &mut sections {
+                            sections.skip();
+                        }
+                    }
+                }
+            }
+        }
+    }
+}
+
+/// Print a unified diff at a granularity.
+///
+/// `context_len` is the number of unchanged lines or sentences to show around
+/// each change, or `None` to show the whole document.
+///
+/// # Errors
+///
+/// Returns an error if the output couldn’t be written.
+pub fn print_diff<S>(
+    out: &mut S,
+    old: &str,
+    new: &str,
+    context_len: Option<usize>,
+    granularity: DiffGranularity,
+) -> io::Result<()>
+where
+    S: termcolor::WriteColor + io::Write + ?Sized,
+{
+    match granularity {
+        DiffGranularity::Line => {
+            print_pretty_diff(out, old, new, context_len);
+            Ok(())
+        }
+        DiffGranularity::Sentence => {
+            print_sentence_diff(out, old, new, context_len)
+        }
+    }
+}
+
+/// Format a size in bytes for display, e.g. “1.2 MB”.
+#[must_use]
+pub fn format_size(bytes: usize) -> String {
+    const UNITS: [&str; 4] = ["kB", "MB", "GB", "TB"];
+
+    if bytes < 1000 {
+        return format!("{bytes} B");
+    }
+
+    #[expect(clippy::cast_precision_loss, reason = "only used for display")]
+    let mut size = bytes as f64;
+    let mut unit = "B";
+    for next in UNITS {
+        if size < 1000.0 {
+            break;
+        }
+        size /= 1000.0;
+        unit = next;
+    }
+    format!("{size:.1} {unit}")
+}
+
+/// Escape text for inclusion in HTML or XML.
+#[must_use]
+pub fn escape_html(text: &str) -> String {
+    let mut escaped = String::with_capacity(text.len());
+    for c in text.chars() {
+        match c {
+            '&' => escaped.push_str("&amp;"),
+            '<' => escaped.push_str("&lt;"),
+            '>' => escaped.push_str("&gt;"),
+            '"' => escaped.push_str("&quot;"),
+            '\'' => escaped.push_str("&#39;"),
+            c => escaped.push(c),
+        }
+    }
+    escaped
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use assert2::check;
+    use termcolor::NoColor;
+
+    /// Print a line diff without color.
+    fn diff(old: &str, new: &str, context_len: Option<usize>) -> String {
+        let mut out = NoColor::new(Vec::new());
+        print_pretty_diff(&mut out, old, new, context_len);
+        String::from_utf8(out.into_inner()).unwrap()
+    }
+
+    #[test]
+    fn test_print_pretty_diff() {
+        let old = "a\nb\nc\nd\ne\nf\ng\nh";
+        let new = "a\nB\nc\nd\ne\nf\nG\nh";
+        check!(diff(old, new, Some(0)) == "-b\n+B\n-g\n+G\n");
+        check!(diff(old, new, Some(1)) == " a\n-b\n+B\n c\n f\n-g\n+G\n h\n");
+        check!(
+            diff(old, new, Some(2))
+                == " a\n-b\n+B\n c\n d\n e\n f\n-g\n+G\n h\n"
+        );
+        check!(
+            diff(old, new, None) == " a\n-b\n+B\n c\n d\n e\n f\n-g\n+G\n h\n"
+        );
+    }
+
+    #[test]
+    fn test_format_size() {
+        check!(format_size(0) == "0 B");
+        check!(format_size(999) == "999 B");
+        check!(format_size(1000) == "1.0 kB");
+        check!(format_size(1_234_567) == "1.2 MB");
+        check!(format_size(1_300_000_000) == "1.3 GB");
+    }
+
+    #[test]
+    fn test_escape_html() {
+        check!(
+            escape_html(r#"<a href="x">'&'</a>"#)
+                == "&lt;a href=&quot;x&quot;&gt;&#39;&amp;&#39;&lt;/a&gt;"
+        );
+    }
+}
@@ -0,0 +1,99 @@
+//! Finding which section of a Markdown document a change is in.
+
+use std::io;
+use termcolor::{Color, ColorSpec};
+
+/// The Markdown headings enclosing the current line of a document.
+#[derive(Debug, Default)]
+pub struct Sections {
+    /// Heading lines with their levels, from the outermost.
+    headings: Vec<(usize, String)>,
+
+    /// Whether unchanged lines were left out since the last change, so the
+    /// next change starts a new hunk.
+    gap: bool,
+}
+
+impl Sections {
+    /// Start at the beginning of a document.
+    #[must_use]
+    pub const fn new() -> Self {
+        Self { headings: Vec::new(), gap: true }
+    }
+
+    /// Update the headings with a line of the new document.
+    pub fn update(&mut self, line: &str) {
+        let Some(level) = heading_level(line) else {
+            return;
+        };
+        self.headings.retain(|&(other, _)| other < level);
+        self.headings.push((level, line.trim().to_owned()));
+    }
+
+    /// Note that unchanged lines were left out.
+    pub const fn skip(&mut self) {
+        self.gap = true;
+    }
+
+    /// Print a hunk header with the enclosing headings, e.g.
+    /// `@@ ## Pricing › ### Plans`, if a change starts a new hunk and is in a
+    /// section.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if the output couldn’t be written.
+    pub fn print_header<S>(&mut self, out: &mut S) -> io::Result<()>
+    where
+        S: termcolor::WriteColor + io::Write + ?Sized,
+    {
+        if !std::mem::replace(&mut self.gap, false) || self.headings.is_empty()
+        {
+            return Ok(());
+        }
+        let path = self
+            .headings
+            .iter()
+            .map(|(_, heading)| heading.as_str())
+            .collect::<Vec<_>>()
+            .join(" › ");
+        out.set_color(ColorSpec::new().set_fg(Some(Color::Cyan)))?;
+        writeln!(out, "@@ {path}")?;
+        out.reset()
+    }
+}
+
+/// Get the level of a Markdown ATX heading, e.g. 2 for `## Pricing`.
+fn heading_level(line: &str) -> Option<usize> {
+    let line = line.trim_start();
+    let level = line.bytes().take_while(|&byte| byte == b'#').count();
+    let rest = line.get(level..)?;
+    ((1..=6).contains(&level) && rest.starts_with(' ')).then_some(level)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::output::print_pretty_diff;
+    use assert2::check;
+    use termcolor::NoColor;
+
+    #[test]
+    fn test_sections() {
+        check!(heading_level("## Pricing") == Some(2));
+        check!(heading_level("#hashtag") == None);
+        check!(heading_level("####### Seven") == None);
+
+        let old = "# Product\n\nIntro\n\n## Pricing\n\n### Plans\n\n\
+            Basic: $5\n\nPro: $10\n\n## Support\n\nEmail us\n";
+        let new = old.replace("$10", "$12").replace("Email", "Call");
+        let mut out = NoColor::new(Vec::new());
+        print_pretty_diff(&mut out, old, &new, Some(1));
+        check!(
+            String::from_utf8(out.into_inner()).unwrap()
+                == "@@ # Product › ## Pricing › ### Plans\n \n\
+                -Pro: $10\n+Pro: $12\n \n\
+                @@ # Product › ## Support\n \n\
+                -Email us\n+Call us\n \n"
+        );
+    }
+}
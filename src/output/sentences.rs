@@ -0,0 +1,166 @@
+//! Diffing prose sentence by sentence.
+//!
+//! A line of rendered Markdown is usually a whole paragraph, so a line diff
+//! shows the entire paragraph as changed when one word in it changes. With
+//! `--diff-granularity sentence`, lines are split into sentences using the
+//! Unicode sentence boundary rules ([UAX #29]), which handle punctuation in
+//! most scripts, so only the sentences that changed are shown. The words that
+//! changed within them are highlighted.
+//!
+//! [UAX #29]: https://www.unicode.org/reports/tr29/
+
+use std::io;
+use unicode_segmentation::UnicodeSegmentation;
+
+use super::sections::Sections;
+use super::side_by_side::{
+    Hunk, Segments, Style, hunks, is_context, pair, print_segments,
+};
+
+/// Split each line of text into sentences, one per line.
+///
+/// Sentences don’t continue across lines, so Markdown structure like headings
+/// and list items still starts on its own line. Blank lines and indentation
+/// are kept.
+#[must_use]
+pub fn split_sentences(text: &str) -> String {
+    let mut lines = Vec::new();
+    for line in text.lines() {
+        let trimmed = line.trim_start();
+        let indent = line
+            .get(..line.len().saturating_sub(trimmed.len()))
+            .unwrap_or_default();
+        let mut sentences = trimmed
+            .split_sentence_bounds()
+            .map(str::trim_end)
+            .filter(|sentence| !sentence.is_empty());
+        if let Some(first) = sentences.next() {
+            lines.push(format!("{indent}{first}"));
+            lines.extend(sentences.map(str::to_owned));
+        } else {
+            lines.push(String::new());
+        }
+    }
+    lines.join("\n")
+}
+
+/// Print a unified diff of the sentences in two texts.
+///
+/// Each removed sentence is shown just before the sentence that replaced it,
+/// with the words that differ highlighted. `context_len` is the number of
+/// unchanged sentences to show around each change, or `None` to show the
+/// whole document.
+///
+/// # Errors
+///
+/// Returns an error if the output couldn’t be written.
+pub fn print_sentence_diff<S>(
+    out: &mut S,
+    old: &str,
+    new: &str,
+    context_len: Option<usize>,
+) -> io::Result<()>
+where
+    S: termcolor::WriteColor + io::Write + ?Sized,
+{
+    let old = split_sentences(old);
+    let new = split_sentences(new);
+    let hunks = hunks(&old, &new);
+    // Only show where hunks are when some sentences are left out.
+    let mut sections = context_len.map(|_| Sections::new());
+    for (i, hunk) in hunks.iter().enumerate() {
+        match hunk {
+            Hunk::Same(sentence) => {
+                if let Some(sections) = &mut sections {
+                    sections.update(sentence);
+                }
+                if is_context(&hunks, i, context_len) {
+                    if let Some(sections) = &mut sections {
+                        sections.print_header(out)?;
+                    }
+                    writeln!(out, " {sentence}")?;
+                } else if let Some(sections) = &mut sections {
+                    sections.skip();
+                }
+            }
+            Hunk::Changed(removed, added) => {
+                if let Some(sections) = &mut sections {
+                    sections.print_header(out)?;
+                    for sentence in added {
+                        sections.update(sentence);
+                    }
+                }
+                for j in 0..removed.len().max(added.len()) {
+                    let (left, right) =
+                        pair(removed.get(j).copied(), added.get(j).copied());
+                    print_marked(out, "-", Style::Removed, left)?;
+                    print_marked(out, "+", Style::Added, right)?;
+                }
+            }
+        }
+    }
+    Ok(())
+}
+
+/// Print a changed sentence after a `-` or `+` marker, unless it’s empty.
+///
+/// # Errors
+///
+/// Returns an error if the output couldn’t be written.
+fn print_marked<S>(
+    out: &mut S,
+    marker: &str,
+    style: Style,
+    segments: Segments,
+) -> io::Result<()>
+where
+    S: termcolor::WriteColor + io::Write + ?Sized,
+{
+    if segments.is_empty() {
+        return Ok(());
+    }
+    let mut line = vec![(marker.to_owned(), style)];
+    line.extend(segments);
+    print_segments(out, Some(&line))?;
+    writeln!(out)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use assert2::check;
+    use termcolor::NoColor;
+
+    #[test]
+    fn test_split_sentences() {
+        check!(
+            split_sentences(
+                "# Title\n\nOne. Two? Three!  Four, e.g. five.\n  \
+                - Item one. Item two.\n\n\
+                これは日本語です。二つ目の文。"
+            ) == "# Title\n\nOne.\nTwo?\nThree!\nFour, e.g. five.\n  \
+                - Item one.\nItem two.\n\n\
+                これは日本語です。\n二つ目の文。"
+        );
+    }
+
+    #[test]
+    fn test_print_sentence_diff() {
+        let mut out = NoColor::new(Vec::new());
+        print_sentence_diff(
+            &mut out,
+            "Intro.\n\nFirst sentence. The price is $10. Last one.",
+            "Intro.\n\nFirst sentence. The price is now $12. Last one. New.",
+            Some(1),
+        )
+        .unwrap();
+        check!(
+            String::from_utf8(out.into_inner()).unwrap()
+                == " First sentence.\n\
+                    -The price is $10.\n\
+                    +The price is now $12.\n \
+                    Last one.\n\
+                    +New.\n"
+        );
+    }
+}
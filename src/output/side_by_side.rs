@@ -0,0 +1,320 @@
+//! Printing diffs with the old and new versions side by side.
+
+use std::io;
+use termcolor::{Color, ColorSpec};
+
+/// Separator between the columns.
+const SEPARATOR: &str = " │ ";
+
+/// How to show a piece of a line.
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+pub enum Style {
+    /// Unchanged.
+    Plain,
+
+    /// Part of a removed line.
+    Removed,
+
+    /// A removed word within a changed line.
+    RemovedWord,
+
+    /// Part of an added line.
+    Added,
+
+    /// An added word within a changed line.
+    AddedWord,
+}
+
+impl Style {
+    /// Get the colors for this style.
+    fn color_spec(self) -> ColorSpec {
+        let mut spec = ColorSpec::new();
+        match self {
+            Self::Plain => {}
+            Self::Removed => {
+                spec.set_fg(Some(Color::Red));
+            }
+            Self::RemovedWord => {
+                spec.set_fg(Some(Color::Red))
+                    .set_intense(true)
+                    .set_bold(true);
+            }
+            Self::Added => {
+                spec.set_fg(Some(Color::Green));
+            }
+            Self::AddedWord => {
+                spec.set_fg(Some(Color::Green))
+                    .set_intense(true)
+                    .set_bold(true);
+            }
+        }
+        spec
+    }
+}
+
+/// A line (or part of a wrapped line) as styled segments.
+pub type Segments = Vec<(String, Style)>;
+
+/// A piece of a diff.
+#[derive(Debug)]
+pub enum Hunk<'a> {
+    /// A line in both versions.
+    Same(&'a str),
+
+    /// Lines removed and added.
+    Changed(Vec<&'a str>, Vec<&'a str>),
+}
+
+/// Print a diff with the old version on the left and the new on the right.
+///
+/// `width` is the total width to fit the output in. Long lines are wrapped.
+/// `context_len` is the number of unchanged lines to show around each change,
+/// or `None` to show the whole document.
+///
+/// # Errors
+///
+/// Returns an error if the output couldn’t be written.
+pub fn print_side_by_side<S>(
+    out: &mut S,
+    old: &str,
+    new: &str,
+    context_len: Option<usize>,
+    width: usize,
+) -> io::Result<()>
+where
+    S: termcolor::WriteColor + io::Write + ?Sized,
+{
+    let column = width
+        .saturating_sub(SEPARATOR.chars().count())
+        .max(20)
+        .checked_div(2)
+        .unwrap_or_default();
+    let hunks = hunks(old, new);
+    for (i, hunk) in hunks.iter().enumerate() {
+        match hunk {
+            Hunk::Same(line) => {
+                if is_context(&hunks, i, context_len) {
+                    let segments = vec![((*line).to_owned(), Style::Plain)];
+                    print_row(out, &segments, &segments, column)?;
+                }
+            }
+            Hunk::Changed(removed, added) => {
+                for j in 0..removed.len().max(added.len()) {
+                    let (left, right) =
+                        pair(removed.get(j).copied(), added.get(j).copied());
+                    print_row(out, &left, &right, column)?;
+                }
+            }
+        }
+    }
+    Ok(())
+}
+
+/// Group a line diff into unchanged lines and blocks of changed lines.
+pub fn hunks<'a>(old: &'a str, new: &'a str) -> Vec<Hunk<'a>> {
+    let mut hunks = Vec::new();
+    for result in diff::lines(old, new) {
+        match (result, hunks.last_mut()) {
+            (diff::Result::Both(line, _), _) => hunks.push(Hunk::Same(line)),
+            (diff::Result::Left(line), Some(Hunk::Changed(removed, _))) => {
+                removed.push(line);
+            }
+            (diff::Result::Right(line), Some(Hunk::Changed(_, added))) => {
+                added.push(line);
+            }
+            (diff::Result::Left(line), _) => {
+                hunks.push(Hunk::Changed(vec![line], Vec::new()));
+            }
+            (diff::Result::Right(line), _) => {
+                hunks.push(Hunk::Changed(Vec::new(), vec![line]));
+            }
+        }
+    }
+    hunks
+}
+
+/// Check if the unchanged line at `index` is within `context_len` lines of a
+/// change.
+pub fn is_context(
+    hunks: &[Hunk<'_>],
+    index: usize,
+    context_len: Option<usize>,
+) -> bool {
+    let Some(context_len) = context_len else {
+        return true;
+    };
+    let is_change = |hunk: &Hunk<'_>| matches!(hunk, Hunk::Changed(..));
+    let start = index.saturating_sub(context_len);
+    let end = index.saturating_add(context_len).saturating_add(1);
+    hunks
+        .get(start..end.min(hunks.len()))
+        .is_some_and(|nearby| nearby.iter().any(is_change))
+}
+
+/// Style a removed and an added line shown next to each other.
+///
+/// If both are present, the words that differ are highlighted.
+pub fn pair(
+    removed: Option<&str>,
+    added: Option<&str>,
+) -> (Segments, Segments) {
+    let (Some(removed), Some(added)) = (removed, added) else {
+        return (
+            removed.map_or_else(Vec::new, |line| {
+                vec![(line.to_owned(), Style::Removed)]
+            }),
+            added.map_or_else(Vec::new, |line| {
+                vec![(line.to_owned(), Style::Added)]
+            }),
+        );
+    };
+
+    let old_words = words(removed);
+    let new_words = words(added);
+    let mut left = Vec::new();
+    let mut right = Vec::new();
+    for result in diff::slice(&old_words, &new_words) {
+        match result {
+            diff::Result::Both(word, _) => {
+                push(&mut left, word, Style::Removed);
+                push(&mut right, word, Style::Added);
+            }
+            diff::Result::Left(word) => {
+                push(&mut left, word, Style::RemovedWord);
+            }
+            diff::Result::Right(word) => {
+                push(&mut right, word, Style::AddedWord);
+            }
+        }
+    }
+    (left, right)
+}
+
+/// Split a line into words and the whitespace between them.
+fn words(line: &str) -> Vec<&str> {
+    let mut words = Vec::new();
+    let mut start = 0;
+    let mut in_space = None;
+    for (i, c) in line.char_indices() {
+        let space = c.is_whitespace();
+        if in_space.is_some_and(|in_space| in_space != space) {
+            words.push(&line[start..i]);
+            start = i;
+        }
+        in_space = Some(space);
+    }
+    if start < line.len() {
+        words.push(&line[start..]);
+    }
+    words
+}
+
+/// Add text to segments, merging it with the last one if it’s the same style.
+fn push(segments: &mut Segments, text: &str, style: Style) {
+    match segments.last_mut() {
+        Some((last, last_style)) if *last_style == style => last.push_str(text),
+        _ => segments.push((text.to_owned(), style)),
+    }
+}
+
+/// Wrap styled segments into rows no wider than `width` characters.
+fn wrap(segments: &Segments, width: usize) -> Vec<Segments> {
+    let mut rows = vec![Vec::new()];
+    let mut row_width = 0_usize;
+    for (text, style) in segments {
+        for c in text.chars() {
+            if row_width >= width {
+                rows.push(Vec::new());
+                row_width = 0;
+            }
+            if let Some(row) = rows.last_mut() {
+                push(row, c.encode_utf8(&mut [0; 4]), *style);
+            }
+            row_width = row_width.saturating_add(1);
+        }
+    }
+    rows
+}
+
+/// Print the left and right lines, wrapped to `column` characters each.
+///
+/// # Errors
+///
+/// Returns an error if the output couldn’t be written.
+fn print_row<S>(
+    out: &mut S,
+    left: &Segments,
+    right: &Segments,
+    column: usize,
+) -> io::Result<()>
+where
+    S: termcolor::WriteColor + io::Write + ?Sized,
+{
+    let left = wrap(left, column);
+    let right = wrap(right, column);
+    for i in 0..left.len().max(right.len()) {
+        let left_width = print_segments(out, left.get(i))?;
+        write!(out, "{}", " ".repeat(column.saturating_sub(left_width)))?;
+        write!(out, "{SEPARATOR}")?;
+        print_segments(out, right.get(i))?;
+        writeln!(out)?;
+    }
+    Ok(())
+}
+
+/// Print styled segments, returning the number of characters printed.
+///
+/// # Errors
+///
+/// Returns an error if the output couldn’t be written.
+pub fn print_segments<S>(
+    out: &mut S,
+    segments: Option<&Segments>,
+) -> io::Result<usize>
+where
+    S: termcolor::WriteColor + io::Write + ?Sized,
+{
+    let mut width = 0_usize;
+    for (text, style) in segments.into_iter().flatten() {
+        out.set_color(&style.color_spec())?;
+        write!(out, "{text}")?;
+        out.reset()?;
+        width = width.saturating_add(text.chars().count());
+    }
+    Ok(width)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use assert2::check;
+    use termcolor::NoColor;
+
+    #[test]
+    fn test_print_side_by_side() {
+        let mut out = NoColor::new(Vec::new());
+        print_side_by_side(
+            &mut out,
+            "a\nb\nc\nd\nsame words here\ne",
+            "a\nb\nc\nd\nsame other words here\nf\nlong line to wrap",
+            Some(1),
+            43,
+        )
+        .unwrap();
+        check!(
+            String::from_utf8(out.into_inner()).unwrap()
+                == "d                    │ d\n\
+                    same words here      │ same other words her\n\
+                    \x20                    │ e\n\
+                    e                    │ f\n\
+                    \x20                    │ long line to wrap\n"
+        );
+    }
+
+    #[test]
+    fn test_words() {
+        check!(words("a  bc d") == ["a", "  ", "bc", " ", "d"]);
+        check!(words(" a") == [" ", "a"]);
+        check!(words("").is_empty());
+    }
+}
@@ -0,0 +1,512 @@
+//! Following “next page” links so that paginated pages can be monitored as a
+//! whole.
+//!
+//! HTML pages are followed with a `rel="next"` link or a CSS selector, and
+//! their bodies are concatenated. JSON APIs are followed with a cursor or a
+//! next page URL in the body, and their items are combined into one document:
+//!
+//! ```toml
+//! [[url]]
+//! url = "https://api.example.com/releases"
+//! paginate = { cursor = "/meta/next_cursor", cursor_param = "after", items = "/data" }
+//! ```
+//!
+//! Either kind of page can also be followed with a `Link: <…>; rel="next"`
+//! header.
+
+use anyhow::Context;
+use bytes::Bytes;
+use http::HeaderMap;
+use scraper::{Html, Selector};
+use serde_json::Value as JsonValue;
+use std::collections::HashSet;
+use url::Url;
+
+use crate::response::Response;
+
+/// Selector for standard `rel="next"` links.
+static DEFAULT_SELECTOR: &str = r#"link[rel~="next"], a[rel~="next"]"#;
+
+/// How to follow pagination for a URL.
+#[derive(Clone, Debug, serde::Deserialize, serde::Serialize)]
+#[serde(deny_unknown_fields)]
+pub struct Paginate {
+    /// CSS selector for the link to the next page (default: links with
+    /// `rel="next"`).
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub selector: Option<String>,
+
+    /// Maximum number of pages to fetch, including the first.
+    #[serde(default = "default_max_pages")]
+    pub max_pages: usize,
+
+    /// JSON pointer to the URL of the next page in a JSON response, e.g.
+    /// `/links/next`.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub next: Option<String>,
+
+    /// JSON pointer to the cursor for the next page in a JSON response, e.g.
+    /// `/meta/next_cursor`. The last page has no cursor, or a null or empty
+    /// one.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub cursor: Option<String>,
+
+    /// Query parameter to pass the cursor to the next page in (default:
+    /// `cursor`).
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub cursor_param: Option<String>,
+
+    /// JSON pointer to the array of items in each page of a JSON response,
+    /// e.g. `/data`.
+    ///
+    /// The items from every page are combined into the first page. Without
+    /// this, pages that are arrays are concatenated, and other pages are
+    /// combined into an array.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub items: Option<String>,
+}
+
+impl Default for Paginate {
+    fn default() -> Self {
+        Self {
+            selector: None,
+            max_pages: default_max_pages(),
+            next: None,
+            cursor: None,
+            cursor_param: None,
+            items: None,
+        }
+    }
+}
+
+impl Paginate {
+    /// Parse the selector for the link to the next page.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if the selector is invalid.
+    pub fn selector(&self) -> anyhow::Result<Selector> {
+        let source = self.selector.as_deref().unwrap_or(DEFAULT_SELECTOR);
+        Selector::parse(source).map_err(|error| {
+            anyhow::anyhow!("invalid pagination selector {source:?}: {error}")
+        })
+    }
+
+    /// Find the URL of the next page of a JSON response.
+    ///
+    /// Cursors are passed to `first_url`, and next page URLs are resolved
+    /// against `page_url`.
+    fn next_json_page(
+        &self,
+        json: &JsonValue,
+        first_url: &Url,
+        page_url: &Url,
+    ) -> Option<Url> {
+        if let Some(pointer) = &self.cursor {
+            let cursor = match json.pointer(pointer)? {
+                JsonValue::String(cursor) if !cursor.is_empty() => {
+                    cursor.clone()
+                }
+                JsonValue::Number(cursor) => cursor.to_string(),
+                _ => return None,
+            };
+            let param = self.cursor_param.as_deref().unwrap_or("cursor");
+            let mut url = first_url.clone();
+            let pairs: Vec<(String, String)> = first_url
+                .query_pairs()
+                .filter(|(name, _)| name != param)
+                .map(|(name, value)| (name.into_owned(), value.into_owned()))
+                .collect();
+            url.query_pairs_mut()
+                .clear()
+                .extend_pairs(pairs)
+                .append_pair(param, &cursor);
+            return Some(url);
+        }
+        let pointer = self.next.as_ref()?;
+        let next = json.pointer(pointer)?.as_str()?;
+        http_url(page_url.join(next).ok()?)
+    }
+
+    /// Combine the pages of a JSON response into one document.
+    fn combine_json(&self, pages: Vec<JsonValue>) -> JsonValue {
+        let Some(pointer) = &self.items else {
+            if !pages.iter().all(JsonValue::is_array) {
+                return JsonValue::Array(pages);
+            }
+            return pages
+                .into_iter()
+                .flat_map(|page| match page {
+                    JsonValue::Array(items) => items,
+                    _ => Vec::new(),
+                })
+                .collect();
+        };
+
+        let mut pages = pages.into_iter();
+        let mut first = pages.next().unwrap_or_default();
+        let items: Vec<JsonValue> = first
+            .pointer(pointer)
+            .into_iter()
+            .chain(
+                pages
+                    .as_slice()
+                    .iter()
+                    .filter_map(|page| page.pointer(pointer)),
+            )
+            .filter_map(JsonValue::as_array)
+            .flatten()
+            .cloned()
+            .collect();
+        if let Some(value) = first.pointer_mut(pointer) {
+            *value = JsonValue::Array(items);
+        }
+        first
+    }
+}
+
+/// Pages of a paginated response fetched so far.
+#[derive(Debug)]
+pub struct Pages<'a> {
+    /// How to follow pagination.
+    paginate: &'a Paginate,
+
+    /// Selector for the link to the next HTML page.
+    selector: &'a Selector,
+
+    /// URL of the first page.
+    first_url: Url,
+
+    /// The pages’ bodies.
+    bodies: Bodies,
+
+    /// The URL of the next page, if there is one.
+    next: Option<Url>,
+
+    /// URLs that have been fetched, to avoid loops.
+    seen: HashSet<Url>,
+}
+
+/// The bodies of pages of a paginated response.
+#[derive(Debug)]
+enum Bodies {
+    /// HTML pages, concatenated.
+    Html(Vec<u8>),
+
+    /// Parsed JSON pages.
+    Json(Vec<JsonValue>),
+}
+
+impl<'a> Pages<'a> {
+    /// Start with the first page.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if the page couldn’t be decoded.
+    pub fn new(
+        paginate: &'a Paginate,
+        selector: &'a Selector,
+        first: &Response,
+    ) -> anyhow::Result<Self> {
+        let bodies = if first.is_json()? {
+            Bodies::Json(Vec::new())
+        } else {
+            Bodies::Html(Vec::new())
+        };
+        let mut pages = Self {
+            paginate,
+            selector,
+            first_url: first.url.clone(),
+            bodies,
+            next: None,
+            seen: HashSet::new(),
+        };
+        pages.add(first)?;
+        Ok(pages)
+    }
+
+    /// Get the URL of the next page, if there is one that hasn’t been
+    /// fetched.
+    #[must_use]
+    pub fn next_url(&self) -> Option<&Url> {
+        self.next.as_ref().filter(|url| !self.seen.contains(*url))
+    }
+
+    /// Add a page.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if the page couldn’t be decoded.
+    pub fn add(&mut self, page: &Response) -> anyhow::Result<()> {
+        self.seen.insert(page.url.clone());
+        let next = match &mut self.bodies {
+            Bodies::Html(body) => {
+                body.extend_from_slice(&page.body);
+                next_page(&page.text()?, &page.url, self.selector)
+            }
+            Bodies::Json(bodies) => {
+                let mut json: JsonValue = serde_json::from_slice(&page.body)
+                    .with_context(|| format!("invalid JSON in {}", page.url))?;
+                let next = self.paginate.next_json_page(
+                    &json,
+                    &self.first_url,
+                    &page.url,
+                );
+                // Cursors and next page URLs would make the combined document
+                // change whenever items are added.
+                let paginate = self.paginate;
+                for pointer in
+                    [&paginate.cursor, &paginate.next].into_iter().flatten()
+                {
+                    remove_pointer(&mut json, pointer);
+                }
+                bodies.push(json);
+                next
+            }
+        };
+        self.next = next.or_else(|| link_next(&page.headers, &page.url));
+        Ok(())
+    }
+
+    /// Combine the pages into one body.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if combined JSON couldn’t be serialized.
+    pub fn finish(self) -> anyhow::Result<Bytes> {
+        Ok(match self.bodies {
+            Bodies::Html(body) => body.into(),
+            Bodies::Json(pages) => {
+                serde_json::to_vec(&self.paginate.combine_json(pages))?.into()
+            }
+        })
+    }
+}
+
+/// Default for [`Paginate::max_pages`].
+const fn default_max_pages() -> usize {
+    10
+}
+
+/// Find the URL of the next page in an HTML page.
+///
+/// Relative links are resolved against `base_url`. Only HTTP(S) URLs are
+/// returned.
+#[must_use]
+pub fn next_page(
+    html: &str,
+    base_url: &Url,
+    selector: &Selector,
+) -> Option<Url> {
+    Html::parse_document(html)
+        .select(selector)
+        .filter_map(|element| element.attr("href"))
+        .filter_map(|href| base_url.join(href).ok())
+        .find_map(http_url)
+}
+
+/// Find the URL of the next page in a `Link` header.
+///
+/// Relative links are resolved against `base_url`. Only HTTP(S) URLs are
+/// returned.
+#[must_use]
+pub fn link_next(headers: &HeaderMap, base_url: &Url) -> Option<Url> {
+    headers
+        .get_all(http::header::LINK)
+        .iter()
+        .filter_map(|value| value.to_str().ok())
+        .flat_map(links)
+        .filter(|(_, params)| {
+            params.split(';').any(|param| {
+                let Some((name, value)) = param.split_once('=') else {
+                    return false;
+                };
+                name.trim().eq_ignore_ascii_case("rel")
+                    && value
+                        .trim()
+                        .trim_matches('"')
+                        .split_whitespace()
+                        .any(|rel| rel.eq_ignore_ascii_case("next"))
+            })
+        })
+        .find_map(|(target, _)| http_url(base_url.join(target).ok()?))
+}
+
+/// Split a `Link` header into targets and their parameters.
+fn links(value: &str) -> Vec<(&str, &str)> {
+    let mut links = Vec::new();
+    let mut rest = value;
+    while let Some((_, after)) = rest.split_once('<') {
+        let Some((target, after)) = after.split_once('>') else {
+            break;
+        };
+        let (params, after) = after
+            .find('<')
+            .and_then(|end| Some((after.get(..end)?, after.get(end..)?)))
+            .unwrap_or((after, ""));
+        links.push((target, params.trim_end().trim_end_matches(',')));
+        rest = after;
+    }
+    links
+}
+
+/// Return a URL if it’s HTTP(S).
+fn http_url(url: Url) -> Option<Url> {
+    matches!(url.scheme(), "http" | "https").then_some(url)
+}
+
+/// Remove the value at a JSON pointer, if there is one.
+fn remove_pointer(json: &mut JsonValue, pointer: &str) {
+    let Some((parent, key)) = pointer.rsplit_once('/') else {
+        return;
+    };
+    let key = key.replace("~1", "/").replace("~0", "~");
+    match json.pointer_mut(parent) {
+        Some(JsonValue::Object(map)) => {
+            map.remove(&key);
+        }
+        Some(JsonValue::Array(values)) => {
+            if let Ok(index) = key.parse::<usize>()
+                && index < values.len()
+            {
+                values.remove(index);
+            }
+        }
+        _ => {}
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use assert2::check;
+
+    #[test]
+    fn test_next_page() {
+        let base: Url = "https://example.com/changes/?page=2".parse().unwrap();
+        let default = Paginate::default();
+        let default = default.selector().unwrap();
+
+        let html = r#"<a href="?page=1">Prev</a>
+            <a rel="nofollow next" href="?page=3">Next</a>"#;
+        check!(
+            next_page(html, &base, &default).map(String::from)
+                == Some("https://example.com/changes/?page=3".to_owned())
+        );
+
+        let html = r#"<head><link rel="next" href="/p/3"></head>"#;
+        check!(
+            next_page(html, &base, &default).map(String::from)
+                == Some("https://example.com/p/3".to_owned())
+        );
+
+        let html = r#"<a href="javascript:next()" rel="next">Next</a>"#;
+        check!(next_page(html, &base, &default) == None);
+
+        let custom = Paginate {
+            selector: Some("nav .older a".to_owned()),
+            ..Paginate::default()
+        };
+        let html = r#"<nav><span class="older"><a href="old">Older</a></span>
+            </nav>"#;
+        check!(
+            next_page(html, &base, &custom.selector().unwrap())
+                .map(String::from)
+                == Some("https://example.com/changes/old".to_owned())
+        );
+
+        let invalid =
+            Paginate { selector: Some("[".to_owned()), ..Paginate::default() };
+        check!(invalid.selector().is_err());
+    }
+
+    #[test]
+    fn test_link_next() {
+        let base: Url = "https://api.example.com/items".parse().unwrap();
+        let mut headers = HeaderMap::new();
+        headers.insert(
+            http::header::LINK,
+            r#"<https://api.example.com/items?page=1>; rel="prev first", <?page=3&a=b,c>; rel="next", <javascript:x>; rel=last"#
+                .parse()
+                .unwrap(),
+        );
+        check!(
+            link_next(&headers, &base).map(String::from)
+                == Some(
+                    "https://api.example.com/items?page=3&a=b,c".to_owned()
+                )
+        );
+        check!(link_next(&HeaderMap::new(), &base) == None);
+    }
+
+    #[test]
+    fn test_json_pages() {
+        let page = |url: &str, body: &str| Response {
+            url: url.parse().unwrap(),
+            version: http::Version::HTTP_11,
+            status: http::StatusCode::OK,
+            headers: HeaderMap::from_iter([(
+                http::header::CONTENT_TYPE,
+                "application/json".parse().unwrap(),
+            )]),
+            body: body.to_owned().into(),
+            spooled: None,
+            certificate_sha256: None,
+            peer_address: None,
+        };
+        let selector = Paginate::default().selector().unwrap();
+
+        let paginate = Paginate {
+            cursor: Some("/meta/next".to_owned()),
+            cursor_param: Some("after".to_owned()),
+            items: Some("/data".to_owned()),
+            ..Paginate::default()
+        };
+        let mut pages = Pages::new(
+            &paginate,
+            &selector,
+            &page(
+                "https://api.example.com/items?limit=2&after=old",
+                r#"{"data": [1, 2], "meta": {"next": "c2", "total": 3}}"#,
+            ),
+        )
+        .unwrap();
+        check!(
+            pages.next_url().map(Url::as_str)
+                == Some("https://api.example.com/items?limit=2&after=c2")
+        );
+        pages
+            .add(&page(
+                "https://api.example.com/items?limit=2&after=c2",
+                r#"{"data": [3], "meta": {"next": null, "total": 3}}"#,
+            ))
+            .unwrap();
+        check!(pages.next_url() == None);
+        check!(
+            pages.finish().unwrap()
+                == &br#"{"data":[1,2,3],"meta":{"total":3}}"#[..]
+        );
+
+        let paginate =
+            Paginate { next: Some("/next".to_owned()), ..Paginate::default() };
+        let mut pages = Pages::new(
+            &paginate,
+            &selector,
+            &page("https://api.example.com/a", r#"{"a": 1, "next": "b"}"#),
+        )
+        .unwrap();
+        check!(
+            pages.next_url().map(Url::as_str)
+                == Some("https://api.example.com/b")
+        );
+        pages
+            .add(&page(
+                "https://api.example.com/b",
+                r#"{"b": 2, "next": "a"}"#,
+            ))
+            .unwrap();
+        // Already fetched.
+        check!(pages.next_url() == None);
+        check!(pages.finish().unwrap() == &br#"[{"a":1},{"b":2}]"#[..]);
+    }
+}
@@ -1,52 +1,367 @@
 //! Code to deal with executable parameters.
 
 use std::io::{self, IsTerminal, Write};
+use std::net::SocketAddr;
 use std::path::PathBuf;
+use std::time::Duration;
 use termcolor::{Color, ColorSpec, StandardStream, WriteColor};
 
+use crate::config::{self, Config, DiffMode, OnNew, UrlConfig};
+use crate::dns::{AddressFamily, Pin};
+use crate::logging::{self, LogFormat};
+use crate::output::{DiffGranularity, DiffView};
+use crate::profile;
+use crate::refresh;
+use crate::request::Impersonate;
+use crate::spool;
+use crate::timing::Report;
+
 pub use clap::Parser;
 
 /// Parameters to configure executable.
 #[derive(Debug, clap::Parser)]
 #[clap(version, about)]
+#[expect(
+    clippy::struct_excessive_bools,
+    reason = "command line flags are naturally bools"
+)]
 pub struct Params {
-    /// URLs to check to changes.
+    /// Subcommand to run instead of checking URLs.
+    #[clap(subcommand)]
+    pub command: Option<Command>,
+
+    /// URLs or local files to check for changes (default: all URLs in the
+    /// config file).
+    #[clap(value_parser = config::parse_url)]
     pub urls: Vec<url::Url>,
 
     /// Whether or not to just render the page ignoring changes.
     #[clap(long)]
     pub no_diff: bool,
 
-    /// Where to store state (default: ~/.monitorbot).
-    #[clap(short, long, value_hint=clap::ValueHint::DirPath)]
+    /// How to compare old and new versions of pages.
+    ///
+    /// This overrides `diff_mode` in the config file.
+    #[clap(long, value_name = "MODE")]
+    pub diff_mode: Option<DiffMode>,
+
+    /// What to do the first time a URL is checked.
+    ///
+    /// This overrides `on_new` in the config file.
+    #[clap(long, value_name = "ACTION")]
+    pub on_new: Option<OnNew>,
+
+    /// Number of unchanged lines to show around each change.
+    #[clap(short = 'C', long, value_name = "N", default_value_t = 2)]
+    pub context: usize,
+
+    /// Show the whole document with changes highlighted.
+    #[clap(long, conflicts_with = "context")]
+    pub full: bool,
+
+    /// How to show diffs in the terminal.
+    ///
+    /// Notifications always use unified diffs.
+    #[clap(long, value_name = "VIEW", default_value = "unified")]
+    pub diff_view: DiffView,
+
+    /// How finely to compare text.
+    ///
+    /// `sentence` splits paragraphs into sentences, so that a changed word
+    /// doesn’t mark the whole paragraph as changed.
+    #[clap(long, value_name = "UNIT", default_value = "line")]
+    pub diff_granularity: DiffGranularity,
+
+    /// User agent to send with requests.
+    ///
+    /// This overrides `user_agent` in the config file.
+    #[clap(short = 'A', long, value_name = "USER_AGENT")]
+    pub user_agent: Option<String>,
+
+    /// Send headers like this browser to avoid being blocked.
+    ///
+    /// This overrides `impersonate` in the config file.
+    #[clap(long, value_name = "BROWSER")]
+    pub impersonate: Option<Impersonate>,
+
+    /// Ask for responses without compression (`Accept-Encoding: identity`).
+    #[clap(long)]
+    pub identity_encoding: bool,
+
+    /// Only use HTTP/1.
+    #[clap(long)]
+    pub http1_only: bool,
+
+    /// Use HTTP/2 without negotiating it first.
+    ///
+    /// Requests to servers that don’t support HTTP/2 will fail.
+    #[clap(long, conflicts_with = "http1_only")]
+    pub http2_prior_knowledge: bool,
+
+    /// Use HTTP/3 without negotiating it first.
+    ///
+    /// Requests to servers that don’t support HTTP/3 will fail.
+    #[cfg(feature = "http3")]
+    #[clap(long, conflicts_with_all = ["http1_only", "http2_prior_knowledge"])]
+    pub http3_prior_knowledge: bool,
+
+    /// Connect to ADDR for HOST, like curl’s `--resolve`.
+    ///
+    /// This is useful for monitoring an origin server behind a CDN. PORT is
+    /// accepted for compatibility with curl, but the pin applies to HOST on
+    /// any port. May be passed more than once.
+    #[clap(long, value_name = "HOST:PORT:ADDR")]
+    pub resolve: Vec<Pin>,
+
+    /// Only connect over IPv4.
+    ///
+    /// This overrides `address_family` in the config file.
+    #[clap(short = '4', long, conflicts_with = "ipv6")]
+    pub ipv4: bool,
+
+    /// Only connect over IPv6.
+    ///
+    /// This overrides `address_family` in the config file.
+    #[clap(short = '6', long)]
+    pub ipv6: bool,
+
+    /// Close idle connections after this long, e.g. "30s" (default: 90s).
+    #[clap(long, value_name = "DURATION", value_parser = humantime::parse_duration)]
+    pub pool_idle_timeout: Option<Duration>,
+
+    /// Maximum number of idle connections to keep open to each host.
+    #[clap(long, value_name = "N")]
+    pub pool_max_per_host: Option<usize>,
+
+    /// Send TCP keepalive probes on idle connections at this interval, e.g.
+    /// "60s".
+    #[clap(long, value_name = "DURATION", value_parser = humantime::parse_duration)]
+    pub tcp_keepalive: Option<Duration>,
+
+    /// Trust the CA certificates in this PEM file in addition to the system’s.
+    ///
+    /// This applies to all URLs, along with any `tls.ca_certificate` in the
+    /// config file.
+    #[clap(long, value_name = "PATH")]
+    pub ca_certificate: Option<PathBuf>,
+
+    /// Don’t verify TLS certificates, e.g. for self-signed internal services.
+    ///
+    /// This is only safe on networks you trust.
+    #[clap(long)]
+    pub insecure: bool,
+
+    /// Save new versions of binary content (e.g. images) in this directory.
+    #[clap(long, value_name = "DIR", value_hint=clap::ValueHint::DirPath)]
+    pub download_dir: Option<PathBuf>,
+
+    /// Write every HTTP request and response, and equivalent `curl` commands,
+    /// to files in this directory for debugging.
+    ///
+    /// The files include secrets like `Authorization` headers.
+    #[clap(long, value_name = "DIR", value_hint=clap::ValueHint::DirPath)]
+    pub trace_http: Option<PathBuf>,
+
+    /// Write each change to a Markdown file and an HTML report in a new
+    /// directory for the run inside this directory.
+    #[clap(long, value_name = "DIR", value_hint=clap::ValueHint::DirPath)]
+    pub output_dir: Option<PathBuf>,
+
+    /// Run a command for each change or failure.
+    ///
+    /// The command is run with `sh -c`. The URL, what happened, and a file
+    /// containing the diff are passed in the environment variables
+    /// `MONITORBOT_URL`, `MONITORBOT_STATUS`, and `MONITORBOT_DIFF_FILE`. The
+    /// details are also written to stdin as JSON.
+    #[clap(long, value_name = "COMMAND")]
+    pub exec: Option<String>,
+
+    /// Maintain an Atom feed of changes at this path.
+    #[clap(long, value_name = "PATH", value_hint=clap::ValueHint::FilePath)]
+    pub feed_output: Option<PathBuf>,
+
+    /// Always download pages, even if they haven’t changed.
+    ///
+    /// Normally pages aren’t fetched while the server says the stored
+    /// response is still fresh, and requests include `If-None-Match` and
+    /// `If-Modified-Since` headers so that the server can skip sending
+    /// unchanged pages.
+    #[clap(short, long)]
+    pub force: bool,
+
+    /// Fetch pages even if `Cache-Control` or `Expires` says the stored
+    /// response is still fresh.
+    ///
+    /// Unlike `--force`, requests still ask the server to skip sending
+    /// unchanged pages.
+    #[clap(long)]
+    pub no_cache: bool,
+
+    /// Skip URLs that were successfully checked less than this long ago, e.g.
+    /// "30m".
+    ///
+    /// This overrides `interval` in the config file, so pass "0s" to check
+    /// every URL.
+    #[clap(long, value_name = "DURATION", value_parser = humantime::parse_duration)]
+    pub max_age: Option<Duration>,
+
+    /// Stop downloading responses larger than this many bytes and report an
+    /// error.
+    ///
+    /// This overrides `max_download_size` in the config file.
+    #[clap(long, value_name = "BYTES")]
+    pub max_download_size: Option<u64>,
+
+    /// Follow redirects done with a `Refresh` header, a meta refresh tag, or
+    /// a simple script that sets `location`, like HTTP redirects.
+    ///
+    /// This applies to all URLs, like `follow_refresh` in the config file.
+    #[clap(long)]
+    pub follow_refresh: bool,
+
+    /// Maximum number of client-side redirects to follow for a URL.
+    #[clap(long, value_name = "N", default_value_t = refresh::DEFAULT_MAX_HOPS)]
+    pub max_refresh_hops: usize,
+
+    /// Maximum number of HTTP redirects to follow for a URL (default: 10).
+    ///
+    /// This overrides `max_redirects` in the config file.
+    #[clap(long, value_name = "N")]
+    pub max_redirects: Option<usize>,
+
+    /// Don’t follow HTTP redirects; compare the redirect responses instead.
+    ///
+    /// This applies to all URLs, like `follow_redirects = false` in the config
+    /// file.
+    #[clap(long, conflicts_with = "max_redirects")]
+    pub no_follow_redirects: bool,
+
+    /// Write response bodies larger than this many bytes to disk while they’re
+    /// downloaded rather than keeping them in memory.
+    ///
+    /// These are compared by size and hash rather than rendered.
+    #[clap(
+        long,
+        value_name = "BYTES",
+        default_value_t = spool::DEFAULT_MAX_MEMORY
+    )]
+    pub max_body_memory: usize,
+
+    /// Keep running and check URLs every DURATION, e.g. "15m".
+    ///
+    /// URLs with a longer `interval` in the config file are only checked once
+    /// it has passed. Under systemd, this supports `Type=notify` and
+    /// `WatchdogSec`.
+    #[clap(long, value_name = "DURATION", value_parser = humantime::parse_duration)]
+    pub daemon: Option<Duration>,
+
+    /// Serve a read-only dashboard of results at this address while running
+    /// with `--daemon`, e.g. `127.0.0.1:8080`.
+    ///
+    /// There’s no authentication, so only listen on a trusted network.
+    #[clap(long, value_name = "ADDR", requires = "daemon")]
+    pub listen: Option<SocketAddr>,
+
+    /// Print a report on each URL checked at the end of the run.
+    ///
+    /// FORMAT is `text` or `json` for how long each part of checking each URL
+    /// took, or `junit` for a `JUnit` XML report for CI systems. The report is
+    /// printed to stderr, or written to PATH. May be passed more than once.
+    /// Timings are also logged with `-v`.
+    #[clap(long, value_name = "FORMAT[=PATH]")]
+    pub report: Vec<Report>,
+
+    /// Fetch and diff, but never write to the state directory.
+    #[clap(short = 'n', long)]
+    pub dry_run: bool,
+
+    /// Where to store state (default: ~/.monitorbot, or the profile’s
+    /// directory).
+    #[clap(short, long, global = true, value_hint=clap::ValueHint::DirPath)]
     pub state_dir: Option<PathBuf>,
 
+    /// Configuration file (default: config.toml in the state directory).
+    #[clap(short, long, global = true, value_hint=clap::ValueHint::FilePath)]
+    pub config: Option<PathBuf>,
+
+    /// Use a separate configuration file and state directory in
+    /// ~/.monitorbot/profiles/NAME.
+    ///
+    /// This keeps sets of URLs, e.g. for work and personal use, isolated
+    /// without passing `--state-dir` and `--config`. Those still override the
+    /// profile’s paths.
+    #[clap(long, global = true, value_name = "NAME", value_parser = profile::parse_name)]
+    pub profile: Option<String>,
+
+    /// Only check URLs with this tag (may be repeated).
+    #[clap(short, long, value_name = "TAG")]
+    pub tag: Vec<String>,
+
+    /// Remove cookie banners and consent dialogs from pages before comparing
+    /// them.
+    ///
+    /// This can also be turned on for individual URLs with
+    /// `strip_boilerplate = true`.
+    #[clap(long)]
+    pub strip_boilerplate: bool,
+
+    /// Fetch robots.txt for each host and skip URLs it disallows.
+    ///
+    /// This also waits between requests to a host if its robots.txt specifies
+    /// a `Crawl-delay`.
+    #[clap(long)]
+    pub respect_robots: bool,
+
+    /// Don’t show a progress bar and summary when checking several URLs.
+    ///
+    /// They’re only shown when stderr is a terminal.
+    #[clap(long)]
+    pub no_progress: bool,
+
     /// Whether or not to output in color.
-    #[clap(long, default_value = "auto", value_name = "WHEN")]
+    #[clap(long, global = true, default_value = "auto", value_name = "WHEN")]
     pub color: ColorChoice,
 
     /// Verbosity (may be repeated up to three times).
-    #[clap(short, long, action = clap::ArgAction::Count)]
+    #[clap(short, long, global = true, action = clap::ArgAction::Count)]
     pub verbose: u8,
+
+    /// Format of log messages.
+    #[clap(long, global = true, default_value = "text", value_name = "FORMAT")]
+    pub log_format: LogFormat,
 }
 
 impl Params {
     /// Print a warning message in error color to `err_stream()`.
+    ///
+    /// If stderr goes to the systemd journal, each line is marked as an error.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if stderr couldn’t be written.
     pub fn warn<S: AsRef<str>>(&self, message: S) -> io::Result<()> {
         let mut err_out = self.err_stream();
         err_out.set_color(&error_color())?;
-        err_out.write_all(message.as_ref().as_bytes())?;
+        if logging::is_journal() {
+            for line in message.as_ref().lines() {
+                writeln!(err_out, "<3>{line}")?;
+            }
+        } else {
+            err_out.write_all(message.as_ref().as_bytes())?;
+        }
         err_out.reset()?;
 
         Ok(())
     }
 
     /// Get stream to use for standard output.
+    #[must_use]
     pub fn out_stream(&self) -> StandardStream {
         StandardStream::stdout(self.color_choice(&io::stdout()))
     }
 
     /// Get stream to use for errors.
+    #[must_use]
     pub fn err_stream(&self) -> StandardStream {
         StandardStream::stderr(self.color_choice(&io::stderr()))
     }
@@ -68,13 +383,471 @@ impl Params {
     /// Get the directory to store state in.
     ///
     /// Clap’s `default_value` functionality doesn’t support dynamic values.
+    #[must_use]
     pub fn state_dir_path(&self) -> PathBuf {
-        self.state_dir.clone().unwrap_or_else(|| {
-            std::env::home_dir()
-                .unwrap_or_else(|| PathBuf::from("."))
-                .join(".monitorbot")
-        })
+        self.state_dir.clone().unwrap_or_else(|| self.profile_dir())
+    }
+
+    /// Get the path to the configuration file.
+    #[must_use]
+    pub fn config_path(&self) -> PathBuf {
+        self.config
+            .clone()
+            .unwrap_or_else(|| self.profile_dir().join("config.toml"))
+    }
+
+    /// Get the directory for the profile passed with `--profile`, or the
+    /// default directory.
+    #[must_use]
+    pub fn profile_dir(&self) -> PathBuf {
+        match &self.profile {
+            Some(name) => profile::dir(&default_dir(), name),
+            None => default_dir(),
+        }
+    }
+
+    /// Load the configuration file.
+    ///
+    /// It’s fine for the default configuration file not to exist, but it’s an
+    /// error if a file passed with `--config` doesn’t exist.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if the file could not be read or parsed.
+    pub fn load_config(&self) -> anyhow::Result<Config> {
+        Config::load(&self.config_path(), self.config.is_some())
+    }
+
+    /// Get the address family passed with `--ipv4` or `--ipv6`, if any.
+    #[must_use]
+    pub const fn address_family(&self) -> Option<AddressFamily> {
+        if self.ipv4 {
+            Some(AddressFamily::Ipv4)
+        } else if self.ipv6 {
+            Some(AddressFamily::Ipv6)
+        } else {
+            None
+        }
+    }
+
+    /// Get the address family to connect to a URL over, if it’s restricted.
+    #[must_use]
+    pub fn url_address_family(
+        &self,
+        url_config: &UrlConfig,
+    ) -> Option<AddressFamily> {
+        self.address_family().or(url_config.address_family)
+    }
+
+    /// Get the diff mode to use for a URL.
+    #[must_use]
+    pub fn diff_mode(&self, url_config: &UrlConfig) -> DiffMode {
+        self.diff_mode.or(url_config.diff_mode).unwrap_or_default()
+    }
+
+    /// Get what to do the first time a URL is checked.
+    #[must_use]
+    pub fn on_new(&self, url_config: &UrlConfig) -> OnNew {
+        self.on_new.or(url_config.on_new).unwrap_or_default()
+    }
+
+    /// Get the number of unchanged lines to show around each change.
+    ///
+    /// Returns `None` if the whole document should be shown.
+    #[must_use]
+    pub const fn diff_context(&self) -> Option<usize> {
+        if self.full { None } else { Some(self.context) }
     }
+
+    /// Get the URLs to check.
+    ///
+    /// If URLs were passed on the command line, only those are checked (using
+    /// settings from the config file if they are present there). Otherwise,
+    /// all URLs from the config file are checked. Either way, they are
+    /// filtered by `--tag`.
+    #[must_use]
+    pub fn url_configs(&self, config: &Config) -> Vec<UrlConfig> {
+        let urls: Vec<UrlConfig> = if self.urls.is_empty() {
+            config.urls.clone()
+        } else {
+            self.urls
+                .iter()
+                .map(|url| {
+                    config
+                        .find_url(url)
+                        .cloned()
+                        .unwrap_or_else(|| UrlConfig::new(url.clone()))
+                })
+                .collect()
+        };
+
+        urls.into_iter()
+            .filter(|url_config| url_config.matches_tags(&self.tag))
+            .collect()
+    }
+}
+
+/// Subcommands.
+#[derive(Debug, clap::Subcommand)]
+pub enum Command {
+    /// Print a stored response without fetching it.
+    Show(ShowParams),
+
+    /// List the stored responses for a URL, with their notes.
+    History(HistoryParams),
+
+    /// Add, show, or delete a note on a stored response.
+    ///
+    /// Notes are shown by `history` and `show`, e.g. to record why a page
+    /// changed.
+    Note(NoteParams),
+
+    /// Add URLs to the configuration file.
+    ///
+    /// Comments and formatting in the file are kept. The URLs’ content is
+    /// saved the next time they’re checked.
+    Add(AddParams),
+
+    /// Remove URLs from the configuration file and delete their history.
+    Remove(RemoveParams),
+
+    /// List the configured URLs with when they were last checked and how
+    /// often checks have failed.
+    List,
+
+    /// Review changes since they were last acknowledged.
+    ///
+    /// Shows each URL that changed with a diff from the content last
+    /// acknowledged. Press `a` to acknowledge a URL’s changes, so they aren’t
+    /// shown again, `o` to open it in a browser, and `q` to quit.
+    Review(ReviewParams),
+
+    /// Check the state directory for damaged or inconsistent files.
+    ///
+    /// Checks that every stored file parses, links resolve, bodies match
+    /// their SHA-256, and the redirects index agrees with the histories.
+    /// Exits with an error if there are problems.
+    Fsck(FsckParams),
+
+    /// Print the values extracted from a URL with `extract` rules.
+    ///
+    /// Shows a table of the values extracted from each stored response,
+    /// oldest first.
+    Values(ValuesParams),
+
+    /// Summarize the changes since the last digest, grouped by tag.
+    ///
+    /// Prints a diff for each URL that changed since the last digest, or in
+    /// the last day if there hasn’t been one. Meant to be run once a day,
+    /// e.g. from cron, instead of getting a notification for each change.
+    Digest(DigestParams),
+
+    /// Convert another tool’s configuration to monitorbot’s.
+    ///
+    /// This prints `[[url]]` entries to add to the configuration file.
+    Import(ImportParams),
+
+    /// Write the configuration file and state directory to an archive.
+    ///
+    /// The archive is a zstd-compressed tar file that can be restored with
+    /// `restore`, e.g. on another machine.
+    Export(ExportParams),
+
+    /// Restore the configuration file and state directory from an archive
+    /// written by `export`.
+    Restore(RestoreParams),
+
+    /// Pin a stored revision as the baseline to compare new responses to.
+    ///
+    /// While a baseline is pinned, changes are reported when the response
+    /// changes, but shown relative to the baseline instead of the previous
+    /// response. This shows how far a page has drifted from a known good
+    /// state.
+    #[clap(subcommand)]
+    Baseline(BaselineCommand),
+
+    /// Manage profiles, which have separate configuration files and state
+    /// directories.
+    #[clap(subcommand)]
+    Profile(ProfileCommand),
+
+    /// Check the audit log configured with `[audit_log]`.
+    #[clap(subcommand)]
+    Audit(AuditCommand),
+
+    /// Print a shell completion script.
+    ///
+    /// For example, for bash, add `source <(monitorbot completions bash)` to
+    /// `~/.bashrc`.
+    Completions(CompletionsParams),
+
+    /// Print a man page in roff format.
+    ///
+    /// For example, `monitorbot manpage > monitorbot.1`.
+    Manpage,
+}
+
+/// Parameters for the `add` subcommand.
+#[derive(Debug, clap::Args)]
+pub struct AddParams {
+    /// URLs to add.
+    #[clap(required = true, value_name = "URL", value_parser = config::parse_url)]
+    pub urls: Vec<url::Url>,
+
+    /// Name to use for the URL in output.
+    #[clap(long, value_name = "NAME")]
+    pub name: Option<String>,
+
+    /// CSS selector for the part of the page to monitor.
+    #[clap(long, visible_alias = "selector", value_name = "SELECTOR")]
+    pub select: Option<String>,
+
+    /// Tag for the URLs (may be repeated).
+    #[clap(short, long, value_name = "TAG")]
+    pub tag: Vec<String>,
+}
+
+/// Parameters for the `remove` subcommand.
+#[derive(Debug, clap::Args)]
+pub struct RemoveParams {
+    /// URLs to remove.
+    #[clap(required = true, value_name = "URL", value_parser = config::parse_url)]
+    pub urls: Vec<url::Url>,
+
+    /// Keep the URLs’ history in the state directory.
+    #[clap(long)]
+    pub keep_history: bool,
+}
+
+/// Parameters for the `review` subcommand.
+#[derive(Debug, clap::Args)]
+pub struct ReviewParams {
+    /// Print the URLs with changes to review instead of showing them.
+    #[clap(long)]
+    pub list: bool,
+}
+
+/// Parameters for the `fsck` subcommand.
+#[derive(Debug, clap::Args)]
+pub struct FsckParams {
+    /// Move damaged files to `quarantine/` in the state directory, so that
+    /// runs stop failing on them.
+    #[clap(long)]
+    pub quarantine: bool,
+}
+
+/// Parameters for the `values` subcommand.
+#[derive(Debug, clap::Args)]
+pub struct ValuesParams {
+    /// URL to show values for.
+    #[clap(value_parser = config::parse_url)]
+    pub url: url::Url,
+
+    /// Value to show (may be repeated; default: all of them).
+    #[clap(short, long, value_name = "NAME")]
+    pub name: Vec<String>,
+
+    /// Print CSV instead of a table.
+    #[clap(long)]
+    pub csv: bool,
+}
+
+/// Parameters for the `digest` subcommand.
+#[derive(Debug, clap::Args)]
+pub struct DigestParams {
+    /// Include changes in this long before now instead of since the last
+    /// digest, e.g. `"2 days"`.
+    #[clap(long, value_name = "DURATION", value_parser = humantime::parse_duration)]
+    pub since: Option<Duration>,
+
+    /// Also write the digest as an HTML page.
+    #[clap(long, value_name = "PATH")]
+    pub html: Option<PathBuf>,
+
+    /// Also send the digest to the notifiers in the configuration file.
+    #[clap(long)]
+    pub notify: bool,
+}
+
+/// Parameters for the `completions` subcommand.
+#[derive(Debug, clap::Args)]
+pub struct CompletionsParams {
+    /// The shell to print completions for.
+    pub shell: clap_complete::Shell,
+}
+
+/// Subcommands of the `profile` subcommand.
+#[derive(Debug, clap::Subcommand)]
+pub enum ProfileCommand {
+    /// List profiles.
+    List,
+}
+
+/// Subcommands of the `audit` subcommand.
+#[derive(Debug, clap::Subcommand)]
+pub enum AuditCommand {
+    /// Verify that no entries in the audit log were changed or removed.
+    ///
+    /// This checks the chain of hashes, and the signatures if `--public-key`
+    /// is passed.
+    Verify(AuditVerifyParams),
+}
+
+/// Parameters for `audit verify`.
+#[derive(Debug, clap::Args)]
+pub struct AuditVerifyParams {
+    /// The log to verify (default: the one in the configuration file).
+    #[clap(value_hint = clap::ValueHint::FilePath)]
+    pub file: Option<PathBuf>,
+
+    /// minisign public key to verify signatures with.
+    #[clap(long, value_name = "PATH", value_hint = clap::ValueHint::FilePath)]
+    pub public_key: Option<PathBuf>,
+}
+
+/// Subcommands of the `baseline` subcommand.
+#[derive(Debug, clap::Subcommand)]
+pub enum BaselineCommand {
+    /// Pin a revision as the baseline.
+    Set(BaselineSetParams),
+
+    /// Unpin the baseline, so that responses are compared to the previous
+    /// response again.
+    Clear(BaselineParams),
+
+    /// Show which revision is pinned as the baseline.
+    Show(BaselineParams),
+}
+
+/// Parameters for `baseline set`.
+#[derive(Debug, clap::Args)]
+pub struct BaselineSetParams {
+    /// URL to pin the baseline for.
+    #[clap(value_parser = config::parse_url)]
+    pub url: url::Url,
+
+    /// Revision to pin, where 1 is the oldest (default: the latest).
+    #[clap(short, long, value_name = "N")]
+    pub rev: Option<usize>,
+}
+
+/// Parameters for `baseline clear` and `baseline show`.
+#[derive(Debug, clap::Args)]
+pub struct BaselineParams {
+    /// URL of the baseline.
+    #[clap(value_parser = config::parse_url)]
+    pub url: url::Url,
+}
+
+/// Parameters for the `import` subcommand.
+#[derive(Debug, clap::Args)]
+pub struct ImportParams {
+    /// The tool to import from.
+    pub from: ImportFormat,
+
+    /// The file to import: `urls.yaml` for urlwatch, or `url-watches.json`
+    /// from a changedetection.io backup.
+    #[clap(value_hint = clap::ValueHint::FilePath)]
+    pub path: PathBuf,
+}
+
+/// Parameters for the `export` subcommand.
+#[derive(Debug, clap::Args)]
+pub struct ExportParams {
+    /// The archive to write, e.g. `state.tar.zst`.
+    #[clap(short, long, value_hint = clap::ValueHint::FilePath)]
+    pub output: PathBuf,
+}
+
+/// Parameters for the `restore` subcommand.
+#[derive(Debug, clap::Args)]
+pub struct RestoreParams {
+    /// The archive to restore, e.g. `state.tar.zst`.
+    #[clap(value_hint = clap::ValueHint::FilePath)]
+    pub archive: PathBuf,
+
+    /// Replace existing history and configuration.
+    #[clap(long)]
+    pub overwrite: bool,
+}
+
+/// Tools to import configuration from.
+#[derive(Clone, Copy, Debug, Eq, PartialEq, clap::ValueEnum)]
+pub enum ImportFormat {
+    /// urlwatch `urls.yaml`.
+    Urlwatch,
+
+    /// changedetection.io `url-watches.json`.
+    Changedetection,
+}
+
+/// Parameters for the `show` subcommand.
+#[derive(Debug, clap::Args)]
+pub struct ShowParams {
+    /// URL to show.
+    #[clap(value_parser = config::parse_url)]
+    pub url: url::Url,
+
+    /// Revision to show, where 1 is the oldest (default: the latest).
+    #[clap(short, long, value_name = "N")]
+    pub rev: Option<usize>,
+
+    /// What to show.
+    #[clap(flatten)]
+    pub format: ShowFormat,
+}
+
+/// Parameters for the `history` subcommand.
+#[derive(Debug, clap::Args)]
+pub struct HistoryParams {
+    /// URL to list responses for.
+    #[clap(value_parser = config::parse_url)]
+    pub url: url::Url,
+}
+
+/// Parameters for the `note` subcommand.
+#[derive(Debug, clap::Args)]
+pub struct NoteParams {
+    /// URL of the response.
+    #[clap(value_parser = config::parse_url)]
+    pub url: url::Url,
+
+    /// Revision to note, where 1 is the oldest (default: the latest).
+    #[clap(short, long, value_name = "N")]
+    pub rev: Option<usize>,
+
+    /// The note (default: show the current note).
+    #[clap(conflicts_with = "delete")]
+    pub text: Option<String>,
+
+    /// Delete the note.
+    #[clap(long)]
+    pub delete: bool,
+}
+
+/// What to output for the `show` subcommand.
+#[derive(Debug, clap::Args)]
+#[group(multiple = false)]
+pub struct ShowFormat {
+    /// Show the raw body.
+    #[clap(long)]
+    pub raw: bool,
+
+    /// Show the body rendered as Markdown (the default).
+    #[clap(long)]
+    pub markdown: bool,
+
+    /// Show the status and headers.
+    #[clap(long)]
+    pub headers: bool,
+}
+
+/// The default directory for state and configuration (~/.monitorbot).
+#[must_use]
+pub fn default_dir() -> PathBuf {
+    std::env::home_dir()
+        .unwrap_or_else(|| PathBuf::from("."))
+        .join(".monitorbot")
 }
 
 /// Whether or not to output in color.
@@ -102,6 +875,7 @@ impl From<ColorChoice> for termcolor::ColorChoice {
 }
 
 /// Returns color used to output errors.
+#[must_use]
 pub fn error_color() -> ColorSpec {
     let mut color = ColorSpec::new();
     color.set_fg(Some(Color::Red));
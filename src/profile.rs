@@ -0,0 +1,98 @@
+//! Profiles: separate sets of URLs with their own configuration and state.
+//!
+//! The profile `NAME` lives in `~/.monitorbot/profiles/NAME`, which contains
+//! its `config.toml` and its state, just like `~/.monitorbot` does for the
+//! default profile.
+
+use std::fs;
+use std::io;
+use std::path::{Path, PathBuf};
+
+/// Directory containing profiles within the default directory.
+const PROFILES_DIR: &str = "profiles";
+
+/// Check that a profile name can be used as a directory name.
+///
+/// This is used to parse `--profile`.
+///
+/// # Errors
+///
+/// Returns an error if the name is empty, starts with `.`, or contains a path
+/// separator.
+pub fn parse_name(name: &str) -> Result<String, String> {
+    if name.is_empty() || name.starts_with('.') || name.contains(['/', '\\']) {
+        Err(format!(
+            "invalid profile name {name:?}: must not be empty, start with \
+            “.”, or contain “/” or “\\”"
+        ))
+    } else {
+        Ok(name.to_owned())
+    }
+}
+
+/// Get the directory containing all profiles.
+#[must_use]
+pub fn profiles_dir(default_dir: &Path) -> PathBuf {
+    default_dir.join(PROFILES_DIR)
+}
+
+/// Get the directory for a profile.
+#[must_use]
+pub fn dir(default_dir: &Path, name: &str) -> PathBuf {
+    profiles_dir(default_dir).join(name)
+}
+
+/// List the names of the profiles, sorted.
+///
+/// # Errors
+///
+/// Returns an error if the profiles directory exists but couldn’t be read.
+pub fn list(default_dir: &Path) -> io::Result<Vec<String>> {
+    let entries = match fs::read_dir(profiles_dir(default_dir)) {
+        Ok(entries) => entries,
+        Err(error) if error.kind() == io::ErrorKind::NotFound => {
+            return Ok(Vec::new());
+        }
+        Err(error) => return Err(error),
+    };
+
+    let mut names = Vec::new();
+    for entry in entries {
+        let entry = entry?;
+        if entry.file_type()?.is_dir()
+            && let Ok(name) = entry.file_name().into_string()
+            && parse_name(&name).is_ok()
+        {
+            names.push(name);
+        }
+    }
+    names.sort();
+    Ok(names)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use assert2::check;
+
+    #[test]
+    fn test_list() {
+        let dir = std::env::temp_dir()
+            .join(format!("monitorbot-{}-profiles", std::process::id()));
+        let _ = fs::remove_dir_all(&dir);
+        check!(list(&dir).unwrap().is_empty());
+
+        fs::create_dir_all(self::dir(&dir, "work")).unwrap();
+        fs::create_dir_all(self::dir(&dir, "personal")).unwrap();
+        fs::create_dir_all(self::dir(&dir, ".hidden")).unwrap();
+        fs::write(profiles_dir(&dir).join("file"), "").unwrap();
+        check!(list(&dir).unwrap() == ["personal", "work"]);
+
+        check!(parse_name("work").is_ok());
+        check!(parse_name("").is_err());
+        check!(parse_name("..").is_err());
+        check!(parse_name("a/b").is_err());
+
+        fs::remove_dir_all(dir).unwrap();
+    }
+}
@@ -0,0 +1,256 @@
+//! Showing progress while checking many URLs.
+//!
+//! The progress bar is drawn on stderr, and only when stderr is a terminal and
+//! more than one URL is being checked. It’s cleared before anything else is
+//! output so that diffs aren’t mixed up with it.
+
+use std::cell::Cell;
+use std::fmt;
+use std::io::{self, IsTerminal, Write};
+use std::time::Duration;
+use termcolor::{Color, ColorSpec, WriteColor};
+
+use crate::output::format_size;
+use crate::params::Params;
+
+/// Width of the bar itself, in characters.
+const BAR_WIDTH: usize = 20;
+
+/// Maximum width of the whole progress line.
+const LINE_WIDTH: usize = 79;
+
+/// What happened when checking a URL.
+#[derive(Clone, Copy, Debug, Eq, PartialEq, serde::Serialize)]
+#[serde(rename_all = "lowercase")]
+pub enum Outcome {
+    /// The URL was checked for the first time.
+    New,
+
+    /// The URL hasn’t changed.
+    Unchanged,
+
+    /// The URL changed.
+    Changed,
+
+    /// The URL failed its expectations or looks like a soft error.
+    Failed,
+
+    /// The URL wasn’t fetched, e.g. because of `--max-age` or robots.txt.
+    Skipped,
+
+    /// There was an error checking the URL.
+    Error,
+}
+
+impl Outcome {
+    /// The color to show the outcome in, if any.
+    const fn color(self) -> Option<Color> {
+        match self {
+            Self::Changed | Self::New => Some(Color::Green),
+            Self::Failed => Some(Color::Yellow),
+            Self::Error => Some(Color::Red),
+            Self::Unchanged | Self::Skipped => None,
+        }
+    }
+}
+
+impl fmt::Display for Outcome {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.pad(match self {
+            Self::New => "new",
+            Self::Unchanged => "unchanged",
+            Self::Changed => "changed",
+            Self::Failed => "failed",
+            Self::Skipped => "skipped",
+            Self::Error => "error",
+        })
+    }
+}
+
+/// Progress through the URLs being checked.
+#[derive(Debug)]
+pub struct Progress {
+    /// Whether to show the progress bar and summary.
+    enabled: bool,
+
+    /// Whether to use color.
+    color: termcolor::ColorChoice,
+
+    /// The number of URLs to check.
+    total: usize,
+
+    /// The label, outcome, duration, and bytes downloaded for each URL
+    /// checked so far.
+    results: Vec<(String, Outcome, Duration, usize)>,
+
+    /// Whether the progress bar is currently drawn.
+    drawn: Cell<bool>,
+}
+
+impl Progress {
+    /// Prepare to show progress through `total` URLs.
+    #[must_use]
+    pub fn new(params: &Params, total: usize) -> Self {
+        Self {
+            enabled: !params.no_progress
+                && total > 1
+                && io::stderr().is_terminal(),
+            color: params.color_choice(&io::stderr()),
+            total,
+            results: Vec::new(),
+            drawn: Cell::new(false),
+        }
+    }
+
+    /// Show that a URL is being checked.
+    pub fn start(&self, label: &str) {
+        if self.enabled {
+            // Errors drawing the progress bar aren’t important.
+            let _ = self.draw(label);
+        }
+    }
+
+    /// Record the outcome of checking a URL.
+    pub fn finish(
+        &mut self,
+        label: &str,
+        outcome: Outcome,
+        took: Duration,
+        bytes: usize,
+    ) {
+        self.results.push((label.to_owned(), outcome, took, bytes));
+    }
+
+    /// Clear the progress bar so that something else can be output.
+    pub fn clear(&self) {
+        if self.drawn.replace(false) {
+            let mut err = io::stderr().lock();
+            let _ = write!(err, "\r\x1b[2K");
+            let _ = err.flush();
+        }
+    }
+
+    /// Count the URLs checked so far with an outcome.
+    fn count(&self, outcome: Outcome) -> usize {
+        self.results
+            .iter()
+            .filter(|(_, o, _, _)| *o == outcome)
+            .count()
+    }
+
+    /// Draw the progress bar.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if stderr couldn’t be written.
+    fn draw(&self, label: &str) -> io::Result<()> {
+        let done = self.results.len();
+        let filled = done
+            .saturating_mul(BAR_WIDTH)
+            .checked_div(self.total)
+            .unwrap_or_default()
+            .min(BAR_WIDTH);
+
+        let mut err = termcolor::StandardStream::stderr(self.color);
+        write!(err, "\r\x1b[2K[")?;
+        err.set_color(ColorSpec::new().set_fg(Some(Color::Cyan)))?;
+        write!(err, "{}", "#".repeat(filled))?;
+        err.reset()?;
+        let status = format!(
+            "{}] {done}/{} checked, {} changed, {} errored: ",
+            " ".repeat(BAR_WIDTH.saturating_sub(filled)),
+            self.total,
+            self.count(Outcome::Changed),
+            self.count(Outcome::Error),
+        );
+        let room = LINE_WIDTH
+            .saturating_sub(filled.saturating_add(1))
+            .saturating_sub(status.chars().count());
+        write!(err, "{status}{}", truncate(label, room))?;
+        err.flush()?;
+        self.drawn.set(true);
+        Ok(())
+    }
+
+    /// Clear the progress bar and print a summary of the run.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if stderr couldn’t be written.
+    pub fn print_summary(&self) -> io::Result<()> {
+        self.clear();
+        if !self.enabled {
+            return Ok(());
+        }
+
+        let width = self
+            .results
+            .iter()
+            .map(|(label, _, _, _)| label.chars().count())
+            .max()
+            .unwrap_or_default()
+            .min(60);
+
+        let mut err = termcolor::StandardStream::stderr(self.color);
+        err.set_color(ColorSpec::new().set_bold(true))?;
+        writeln!(
+            err,
+            "\n{:width$}  {:9}  {:>8}  Time",
+            "URL", "Result", "Size",
+        )?;
+        err.reset()?;
+        let mut total_bytes = 0_usize;
+        for (label, outcome, took, bytes) in &self.results {
+            write!(err, "{:width$}  ", truncate(label, width))?;
+            err.set_color(ColorSpec::new().set_fg(outcome.color()))?;
+            write!(err, "{outcome:9}")?;
+            err.reset()?;
+            writeln!(
+                err,
+                "  {:>8}  {:.0}ms",
+                format_size(*bytes),
+                took.as_secs_f64() * 1000.0,
+            )?;
+            total_bytes = total_bytes.saturating_add(*bytes);
+        }
+
+        writeln!(
+            err,
+            "\n{} checked: {} changed, {} new, {} unchanged, {} failed, {} \
+            skipped, {} errored; downloaded {}",
+            self.results.len(),
+            self.count(Outcome::Changed),
+            self.count(Outcome::New),
+            self.count(Outcome::Unchanged),
+            self.count(Outcome::Failed),
+            self.count(Outcome::Skipped),
+            self.count(Outcome::Error),
+            format_size(total_bytes),
+        )
+    }
+}
+
+/// Truncate a string to `width` characters, using “…” if it’s too long.
+fn truncate(s: &str, width: usize) -> String {
+    if s.chars().count() <= width {
+        s.to_owned()
+    } else {
+        let mut truncated: String =
+            s.chars().take(width.saturating_sub(1)).collect();
+        truncated.push('…');
+        truncated
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use assert2::check;
+
+    #[test]
+    fn test_truncate() {
+        check!(truncate("example.com", 20) == "example.com");
+        check!(truncate("https://example.com/", 10) == "https://e…");
+        check!(truncate("abc", 1) == "…");
+    }
+}
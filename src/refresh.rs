@@ -0,0 +1,177 @@
+//! Detecting client-side redirects.
+//!
+//! Some pages “redirect” with a `Refresh` header, a `<meta
+//! http-equiv="refresh">` tag, or a script that sets `location`, rather than an
+//! HTTP redirect. Without following them, the interstitial page is what gets
+//! stored and compared.
+//!
+//! With `follow_refresh`, these redirects are followed like HTTP redirects, up
+//! to `--max-refresh-hops` times. Only simple scripts that assign a quoted URL
+//! to `location` are recognized; nothing is executed.
+
+use regex::Regex;
+use scraper::{Html, Selector};
+use std::sync::LazyLock;
+use url::Url;
+
+use crate::response::Response;
+
+/// Default for `--max-refresh-hops`.
+pub const DEFAULT_MAX_HOPS: usize = 5;
+
+/// Selector for `<meta http-equiv="refresh">` tags.
+static META_SELECTOR: LazyLock<Selector> = LazyLock::new(|| {
+    Selector::parse(r#"meta[http-equiv="refresh" i]"#)
+        .expect("selector is valid")
+});
+
+/// Selector for inline scripts.
+static SCRIPT_SELECTOR: LazyLock<Selector> = LazyLock::new(|| {
+    Selector::parse("script:not([src])").expect("selector is valid")
+});
+
+/// Matches simple assignments to `location`, like `location.href = "/new"`
+/// or `window.location.replace('/new')`.
+static LOCATION_PATTERN: LazyLock<Regex> = LazyLock::new(|| {
+    Regex::new(
+        r#"(?:\b(?:window|document|top|self)\.)?\blocation(?:\.href\s*=|\s*=|\.(?:replace|assign)\s*\()\s*["']([^"']+)["']"#,
+    )
+    .expect("pattern is valid")
+});
+
+/// Find where a response redirects to on the client side, if anywhere.
+///
+/// The `Refresh` header is checked first, then `<meta http-equiv="refresh">`,
+/// then scripts. Refreshes without a URL, which just reload the page, are
+/// ignored, as are targets that aren’t HTTP(S) or are the page itself.
+///
+/// # Errors
+///
+/// Returns an error if the response couldn’t be decoded.
+pub fn target(response: &Response) -> anyhow::Result<Option<Url>> {
+    let header = response
+        .headers
+        .get(http::header::REFRESH)
+        .and_then(|value| value.to_str().ok())
+        .and_then(refresh_url);
+    let target = if let Some(header) = header {
+        Some(header.to_owned())
+    } else if response.spooled.is_some() || !response.is_html()? {
+        None
+    } else {
+        html_target(&response.text()?)
+    };
+
+    Ok(target
+        .and_then(|target| response.url.join(&target).ok())
+        .filter(|url| matches!(url.scheme(), "http" | "https"))
+        .filter(|url| !is_same_page(url, &response.url)))
+}
+
+/// Find a client-side redirect in an HTML document.
+fn html_target(html: &str) -> Option<String> {
+    let document = Html::parse_document(html);
+    document
+        .select(&META_SELECTOR)
+        .filter_map(|element| element.attr("content"))
+        .find_map(refresh_url)
+        .map(str::to_owned)
+        .or_else(|| {
+            document.select(&SCRIPT_SELECTOR).find_map(|element| {
+                let script = element.text().collect::<String>();
+                LOCATION_PATTERN
+                    .captures(&script)
+                    .and_then(|captures| captures.get(1))
+                    .map(|target| target.as_str().to_owned())
+            })
+        })
+}
+
+/// Get the URL from the value of a `Refresh` header or meta tag, e.g.
+/// `0; url=https://example.com/`.
+fn refresh_url(content: &str) -> Option<&str> {
+    let (_delay, rest) = content.split_once([';', ','])?;
+    let rest = rest.trim_start();
+    let url = rest
+        .get(..3)
+        .filter(|prefix| prefix.eq_ignore_ascii_case("url"))
+        .and_then(|_| rest.get(3..))
+        .and_then(|after| after.trim_start().strip_prefix('='))
+        .unwrap_or(rest)
+        .trim();
+    let url = url
+        .strip_prefix(['"', '\''])
+        .and_then(|url| url.strip_suffix(['"', '\'']))
+        .unwrap_or(url);
+    (!url.is_empty()).then_some(url)
+}
+
+/// Check if two URLs are the same page, ignoring fragments.
+fn is_same_page(a: &Url, b: &Url) -> bool {
+    a.as_str().split('#').next() == b.as_str().split('#').next()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use assert2::check;
+
+    #[test]
+    fn test_target() {
+        let response = |headers: &[(&str, &str)], body: &str| Response {
+            url: "https://example.com/old/".parse().unwrap(),
+            version: http::Version::HTTP_11,
+            status: http::StatusCode::OK,
+            headers: headers
+                .iter()
+                .map(|(name, value)| {
+                    (name.parse().unwrap(), value.parse().unwrap())
+                })
+                .collect(),
+            body: body.to_owned().into(),
+            spooled: None,
+            certificate_sha256: None,
+            peer_address: None,
+        };
+        let target = |headers, body| {
+            target(&response(headers, body)).unwrap().map(String::from)
+        };
+
+        check!(
+            target(&[("Refresh", "0; url=/new")], "")
+                == Some("https://example.com/new".to_owned())
+        );
+        check!(
+            target(
+                &[],
+                r#"<meta http-equiv="Refresh" content="5;URL='../new/'">"#
+            ) == Some("https://example.com/new/".to_owned())
+        );
+        check!(
+            target(
+                &[],
+                r#"<script>window.location.href = "https://other.example/";
+                </script>"#
+            ) == Some("https://other.example/".to_owned())
+        );
+        check!(
+            target(&[], "<script>location.replace('new')</script>")
+                == Some("https://example.com/old/new".to_owned())
+        );
+
+        // Reloads, the page itself, and other schemes aren’t followed.
+        check!(target(&[("Refresh", "300")], "") == None);
+        check!(
+            target(&[], r#"<meta http-equiv="refresh" content="60">"#) == None
+        );
+        check!(
+            target(&[], r#"<meta http-equiv="refresh" content="0; url=#top">"#)
+                == None
+        );
+        check!(
+            target(&[], "<script>location = 'javascript:void(0)'</script>")
+                == None
+        );
+        check!(target(&[], "<p>location = 'not a script'</p>") == None);
+    }
+}
@@ -0,0 +1,226 @@
+//! Rendering content for display and diffing.
+
+use htmd::element_handler::Handlers;
+use htmd::{Element, HtmlToMarkdown};
+use url::Url;
+
+use crate::extract::Filter;
+use crate::response::Response;
+
+mod handler;
+mod ical;
+mod markdown;
+mod table;
+
+pub use handler::{Content, ContentHandler, find, register};
+pub use table::TableMode;
+
+/// Render a response as text based on its content type.
+///
+/// Content types with a registered [`ContentHandler`] are rendered by it, and
+/// anything else is rendered as HTML. HTML responses are filtered with `filter`
+/// first, and if it has `inline_json`, the JSON embedded in them is rendered
+/// instead. Then the rendered text is filtered with the text steps in
+/// `filter`.
+///
+/// # Errors
+///
+/// Returns an error if the response could not be decoded, filtered, or
+/// rendered.
+pub fn render(
+    response: &Response,
+    tables: TableMode,
+    filter: Filter<'_>,
+) -> anyhow::Result<String> {
+    let handler = response.media_type()?.as_deref().and_then(handler::find);
+    let text = match handler {
+        Some(handler) => handler.render(response)?.into_text(),
+        None if response.is_html()? => {
+            let html = filter.apply(response.text()?)?;
+            match filter.inline_json {
+                Some(inline_json) => inline_json.apply(&html)?,
+                None => render_html(html, &response.url, tables)?,
+            }
+        }
+        None => render_html(response.text()?, &response.url, tables)?,
+    };
+    Ok(filter.apply_text(text))
+}
+
+/// Render HTML as Markdown.
+///
+/// The Markdown is normalized so that insignificant differences in the HTML
+/// don’t change it; see [`markdown`].
+///
+/// # Errors
+///
+/// Returns an error if the HTML couldn’t be converted.
+pub fn render_html<S: AsRef<str>>(
+    html: S,
+    _base_url: &Url,
+    tables: TableMode,
+) -> anyhow::Result<String> {
+    // FIXME output links relative to _base_url.
+    let markdown = HtmlToMarkdown::builder()
+        .add_handler(
+            vec!["table"],
+            move |handlers: &dyn Handlers, element: Element| {
+                table::handle(tables, handlers, &element)
+            },
+        )
+        .build()
+        .convert(html.as_ref())?;
+    Ok(markdown::normalize(&markdown))
+}
+
+/// Render JSON pretty-printed, so that each value is on its own line.
+///
+/// Invalid JSON is rendered as is.
+///
+/// # Errors
+///
+/// Returns an error if the response could not be decoded.
+fn render_json(response: &Response) -> anyhow::Result<String> {
+    let text = response.text()?;
+    Ok(serde_json::from_str::<serde_json::Value>(&text)
+        .and_then(|json| serde_json::to_string_pretty(&json))
+        .unwrap_or_else(|_| text.into_owned()))
+}
+
+/// Extract the text from a PDF.
+///
+/// If the PDF has no text layer, or the text could not be extracted, this
+/// returns a note saying so that includes the size and hash of the PDF so that
+/// changes are still detected.
+#[cfg(feature = "pdf")]
+fn render_pdf(body: &[u8]) -> String {
+    // pdf-extract may panic on malformed PDFs.
+    match std::panic::catch_unwind(|| pdf_extract::extract_text_from_mem(body))
+    {
+        Ok(Ok(text)) if !text.trim().is_empty() => text,
+        Ok(Ok(_)) => pdf_note(body, "PDF has no text layer"),
+        Ok(Err(error)) => {
+            pdf_note(body, &format!("Could not extract text from PDF: {error}"))
+        }
+        Err(_) => pdf_note(body, "Could not extract text from PDF"),
+    }
+}
+
+/// Extract the text from a PDF.
+///
+/// Support for PDFs was not enabled at compile time, so this just returns a
+/// note including the size and hash of the PDF.
+#[cfg(not(feature = "pdf"))]
+fn render_pdf(body: &[u8]) -> String {
+    pdf_note(body, "Support for PDFs is not enabled")
+}
+
+/// Make a note about a PDF that includes its size and hash.
+fn pdf_note(body: &[u8], message: &str) -> String {
+    format!(
+        "*{message}* ({} bytes, SHA-256 {})",
+        body.len(),
+        crate::state::sha256_hex(body),
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use assert2::check;
+
+    /// A pricing table with row headers, a `colspan`, and a multi-line cell.
+    static TABLE: &str = "<h1>Pricing</h1><table><caption>Plans</caption>
+        <thead><tr><th>Plan</th><th>Price</th><th>Seats</th></tr></thead>
+        <tr><th>Free</th><td>$0</td><td>1</td></tr>
+        <tr><th>Team</th><td><p>$20</p><p>a | b</p></td><td>20</td></tr>
+        <tr><td colspan=2>Enterprise</td><td>Call</td></tr>
+        </table>";
+
+    #[test]
+    fn test_render_tables() {
+        let url = "https://example.com/".parse().unwrap();
+        check!(
+            render_html(TABLE, &url, TableMode::Markdown).unwrap()
+                == "# Pricing\n\n\
+                    Plans\n\n\
+                    | Plan       | Price      | Seats |\n\
+                    | ---------- | ---------- | ----- |\n\
+                    | Free       | $0         | 1     |\n\
+                    | Team       | $20 a \\| b | 20    |\n\
+                    | Enterprise |            | Call  |"
+        );
+        check!(
+            render_html(TABLE, &url, TableMode::Cells).unwrap()
+                == "# Pricing\n\n\
+                    Plans\n\n\
+                    Free › Price: $0\n\
+                    Free › Seats: 1\n\
+                    Team › Price: $20 a \\| b\n\
+                    Team › Seats: 20\n\
+                    Enterprise › Seats: Call"
+        );
+        check!(
+            render_html(
+                "<table><tr><td>a<td>b</table>",
+                &url,
+                TableMode::Markdown
+            )
+            .unwrap()
+                == "| a   | b   |\n| --- | --- |"
+        );
+    }
+
+    /// Make a one page PDF showing `text`, with `title` in its metadata.
+    #[cfg(feature = "pdf")]
+    fn pdf(text: &str, title: &str) -> Vec<u8> {
+        let stream = format!("BT /F1 12 Tf 72 720 Td ({text}) Tj ET");
+        let objects = [
+            "<< /Type /Catalog /Pages 2 0 R >>".to_owned(),
+            "<< /Type /Pages /Kids [3 0 R] /Count 1 >>".to_owned(),
+            "<< /Type /Page /Parent 2 0 R /MediaBox [0 0 612 792] \
+                /Resources << /Font << /F1 5 0 R >> >> /Contents 4 0 R >>"
+                .to_owned(),
+            format!(
+                "<< /Length {} >>\nstream\n{stream}\nendstream",
+                stream.len()
+            ),
+            "<< /Type /Font /Subtype /Type1 /BaseFont /Helvetica \
+                /Encoding /WinAnsiEncoding >>"
+                .to_owned(),
+            format!("<< /Title ({title}) >>"),
+        ];
+        let size = objects.len().saturating_add(1);
+        let mut pdf = b"%PDF-1.4\n".to_vec();
+        let mut offsets = Vec::new();
+        for (number, object) in (1..).zip(&objects) {
+            offsets.push(pdf.len());
+            pdf.extend(format!("{number} 0 obj\n{object}\nendobj\n").bytes());
+        }
+        let xref = pdf.len();
+        pdf.extend(format!("xref\n0 {size}\n0000000000 65535 f \n").bytes());
+        for offset in offsets {
+            pdf.extend(format!("{offset:010} 00000 n \n").bytes());
+        }
+        pdf.extend(
+            format!(
+                "trailer\n<< /Size {size} /Root 1 0 R /Info 6 0 R >>\n\
+                startxref\n{xref}\n%%EOF\n",
+            )
+            .bytes(),
+        );
+        pdf
+    }
+
+    #[cfg(feature = "pdf")]
+    #[test]
+    fn test_render_pdf() {
+        let a = pdf("Price: $5", "Generated 1");
+        let b = pdf("Price: $5", "Generated 2");
+        check!(a != b);
+        check!(render_pdf(&a).trim() == "Price: $5");
+        check!(render_pdf(&a) == render_pdf(&b));
+        check!(render_pdf(&pdf("Price: $6", "Generated 1")) != render_pdf(&a));
+        check!(render_pdf(b"not a PDF").starts_with("*Could not extract"));
+    }
+}
@@ -0,0 +1,243 @@
+//! Rendering content types other than HTML.
+//!
+//! Each non-HTML content type is rendered by a [`ContentHandler`]. Handlers are
+//! kept in a process-wide registry, so programs using this crate can add
+//! handlers for their own formats with [`register()`]:
+//!
+//! ```
+//! use monitorbot::render::{self, Content, ContentHandler};
+//! use monitorbot::response::Response;
+//!
+//! struct Yaml;
+//!
+//! impl ContentHandler for Yaml {
+//!     fn matches(&self, media_type: &str) -> bool {
+//!         media_type == "application/yaml"
+//!     }
+//!
+//!     fn render(&self, response: &Response) -> anyhow::Result<Content> {
+//!         Ok(Content::Text(response.text()?.trim().to_owned()))
+//!     }
+//! }
+//!
+//! render::register(Yaml);
+//! ```
+//!
+//! Handlers registered later take precedence, so a handler can replace one of
+//! the built-in handlers. Responses that no handler matches are rendered as
+//! HTML.
+
+use std::sync::{Arc, LazyLock, PoisonError, RwLock};
+
+use crate::items::Item;
+use crate::response::Response;
+use crate::stream;
+
+/// The handlers for all content types.
+static REGISTRY: LazyLock<RwLock<Vec<Arc<dyn ContentHandler>>>> =
+    LazyLock::new(|| {
+        RwLock::new(vec![
+            Arc::new(Json),
+            Arc::new(Csv),
+            Arc::new(Calendar),
+            Arc::new(Pdf),
+            Arc::new(Messages),
+        ])
+    });
+
+/// Renders responses of particular content types for diffing.
+pub trait ContentHandler: Send + Sync {
+    /// Check if this handles responses with `media_type`, e.g.
+    /// `application/json`. It’s always lowercase, and has no parameters.
+    fn matches(&self, media_type: &str) -> bool;
+
+    /// Render a response.
+    ///
+    /// The result should be normalized, so that it only changes when the
+    /// content changes in a way that matters.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if the response couldn’t be decoded or rendered.
+    fn render(&self, response: &Response) -> anyhow::Result<Content>;
+}
+
+/// Rendered content.
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub enum Content {
+    /// Normalized text, like Markdown.
+    Text(String),
+
+    /// Structured items, like entries in a feed. They’re rendered one per
+    /// line so that each added or removed item shows up on its own.
+    Items(Vec<Item>),
+}
+
+impl Content {
+    /// Get the content as text for diffing.
+    #[must_use]
+    pub fn into_text(self) -> String {
+        match self {
+            Self::Text(text) => text,
+            Self::Items(items) => items
+                .iter()
+                .map(|item| match &item.link {
+                    Some(link) => format!("* {} <{link}>", item.text),
+                    None => format!("* {}", item.text),
+                })
+                .collect::<Vec<_>>()
+                .join("\n"),
+        }
+    }
+}
+
+/// Add a handler, taking precedence over those already registered.
+pub fn register<H: ContentHandler + 'static>(handler: H) {
+    REGISTRY
+        .write()
+        .unwrap_or_else(PoisonError::into_inner)
+        .push(Arc::new(handler));
+}
+
+/// Find the handler for `media_type`, if there is one.
+#[must_use]
+pub fn find(media_type: &str) -> Option<Arc<dyn ContentHandler>> {
+    REGISTRY
+        .read()
+        .unwrap_or_else(PoisonError::into_inner)
+        .iter()
+        .rev()
+        .find(|handler| handler.matches(media_type))
+        .cloned()
+}
+
+/// Renders JSON pretty-printed; see [`super::render_json()`].
+struct Json;
+
+impl ContentHandler for Json {
+    fn matches(&self, media_type: &str) -> bool {
+        media_type == "application/json" || media_type.ends_with("+json")
+    }
+
+    fn render(&self, response: &Response) -> anyhow::Result<Content> {
+        Ok(Content::Text(super::render_json(response)?))
+    }
+}
+
+/// Renders CSV and TSV as is.
+struct Csv;
+
+impl ContentHandler for Csv {
+    fn matches(&self, media_type: &str) -> bool {
+        matches!(
+            media_type,
+            "text/csv" | "application/csv" | "text/tab-separated-values"
+        )
+    }
+
+    fn render(&self, response: &Response) -> anyhow::Result<Content> {
+        Ok(Content::Text(response.text()?.into_owned()))
+    }
+}
+
+/// Renders iCalendar files as a list of events; see [`super::ical`].
+struct Calendar;
+
+impl ContentHandler for Calendar {
+    fn matches(&self, media_type: &str) -> bool {
+        media_type == "text/calendar"
+    }
+
+    fn render(&self, response: &Response) -> anyhow::Result<Content> {
+        Ok(Content::Text(super::ical::render(&response.text()?)))
+    }
+}
+
+/// Renders the text of PDFs; see [`super::render_pdf()`].
+struct Pdf;
+
+impl ContentHandler for Pdf {
+    fn matches(&self, media_type: &str) -> bool {
+        media_type == "application/pdf"
+    }
+
+    fn render(&self, response: &Response) -> anyhow::Result<Content> {
+        Ok(Content::Text(super::render_pdf(&response.body)))
+    }
+}
+
+/// Renders messages captured from a stream as is; see [`crate::stream`].
+struct Messages;
+
+impl ContentHandler for Messages {
+    fn matches(&self, media_type: &str) -> bool {
+        media_type == stream::MEDIA_TYPE
+    }
+
+    fn render(&self, response: &Response) -> anyhow::Result<Content> {
+        Ok(Content::Text(response.text()?.into_owned()))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::extract::Filter;
+    use crate::render::{TableMode, render};
+    use assert2::check;
+
+    /// Renders each line of a response as an item.
+    struct Lines;
+
+    impl ContentHandler for Lines {
+        fn matches(&self, media_type: &str) -> bool {
+            media_type == "application/x-monitorbot-test"
+        }
+
+        fn render(&self, response: &Response) -> anyhow::Result<Content> {
+            Ok(Content::Items(
+                response
+                    .text()?
+                    .lines()
+                    .map(|line| Item {
+                        id: line.to_owned(),
+                        text: line.to_owned(),
+                        link: None,
+                    })
+                    .collect(),
+            ))
+        }
+    }
+
+    #[test]
+    fn test_register() {
+        let response = |content_type: &str, body: &str| {
+            let mut headers = http::HeaderMap::new();
+            headers.insert(
+                http::header::CONTENT_TYPE,
+                content_type.parse().unwrap(),
+            );
+            Response {
+                url: "https://example.com/".parse().unwrap(),
+                version: http::Version::HTTP_11,
+                status: http::StatusCode::OK,
+                headers,
+                body: body.to_owned().into(),
+                spooled: None,
+                certificate_sha256: None,
+                peer_address: None,
+            }
+        };
+        let render = |response: &Response| {
+            render(response, TableMode::default(), Filter::default()).unwrap()
+        };
+
+        let custom = response("application/x-monitorbot-test", "a\n<b>");
+        check!(find("application/x-monitorbot-test").is_none());
+        register(Lines);
+        check!(render(&custom) == "* a\n* <b>");
+
+        check!(find("application/ld+json").is_some());
+        check!(render(&response("application/json", "[1]")) == "[\n  1\n]");
+    }
+}
@@ -0,0 +1,201 @@
+//! Rendering iCalendar (`.ics`) files.
+//!
+//! Calendars are rendered as a list of events, sorted by start time, with only
+//! the fields people care about. Fields that change whenever the calendar is
+//! generated, like `DTSTAMP`, are ignored so that they don’t show up as
+//! changes.
+
+use std::fmt::Write as _;
+
+/// An event from a calendar.
+#[derive(Debug, Default, Eq, Ord, PartialEq, PartialOrd)]
+struct Event {
+    /// When the event starts.
+    start: String,
+
+    /// When the event ends.
+    end: String,
+
+    /// The title of the event.
+    summary: String,
+
+    /// Where the event is.
+    location: String,
+}
+
+/// Render the events in an iCalendar file as Markdown.
+pub fn render(source: &str) -> String {
+    let mut events = Vec::new();
+    let mut event: Option<Event> = None;
+    for line in unfold(source) {
+        let Some((name, params, value)) = parse_line(&line) else {
+            continue;
+        };
+        match (name.as_str(), &mut event) {
+            ("BEGIN", None) if value.eq_ignore_ascii_case("VEVENT") => {
+                event = Some(Event::default());
+            }
+            ("END", Some(_)) if value.eq_ignore_ascii_case("VEVENT") => {
+                events.extend(event.take());
+            }
+            ("SUMMARY", Some(event)) => event.summary = unescape(value),
+            ("LOCATION", Some(event)) => event.location = unescape(value),
+            ("DTSTART", Some(event)) => {
+                event.start = format_time(params, value);
+            }
+            ("DTEND", Some(event)) => event.end = format_time(params, value),
+            _ => {}
+        }
+    }
+    events.sort();
+
+    let mut out = String::new();
+    for event in events {
+        let summary = if event.summary.is_empty() {
+            "(untitled)"
+        } else {
+            &event.summary
+        };
+        let _ = writeln!(out, "## {summary}\n");
+        if !event.start.is_empty() {
+            let _ = writeln!(out, "Start: {}  ", event.start);
+        }
+        if !event.end.is_empty() {
+            let _ = writeln!(out, "End: {}  ", event.end);
+        }
+        if !event.location.is_empty() {
+            let _ = writeln!(out, "Location: {}  ", event.location);
+        }
+        out.push('\n');
+    }
+    out.trim_end().to_owned()
+}
+
+/// Join folded lines, which continue on lines starting with a space or tab.
+fn unfold(source: &str) -> Vec<String> {
+    let mut lines: Vec<String> = Vec::new();
+    for line in source.lines() {
+        match (line.strip_prefix([' ', '\t']), lines.last_mut()) {
+            (Some(rest), Some(last)) => last.push_str(rest),
+            _ => lines.push(line.to_owned()),
+        }
+    }
+    lines
+}
+
+/// Parse a content line into its uppercase name, its parameters, and its
+/// value.
+fn parse_line(line: &str) -> Option<(String, &str, &str)> {
+    let (head, value) = line.split_once(':')?;
+    let (name, params) = head.split_once(';').unwrap_or((head, ""));
+    Some((name.to_ascii_uppercase(), params, value))
+}
+
+/// Unescape a text value.
+fn unescape(value: &str) -> String {
+    let mut out = String::with_capacity(value.len());
+    let mut chars = value.chars();
+    while let Some(c) = chars.next() {
+        if c != '\\' {
+            out.push(c);
+            continue;
+        }
+        match chars.next() {
+            Some('n' | 'N') => out.push(' '),
+            Some(c) => out.push(c),
+            None => out.push('\\'),
+        }
+    }
+    out.trim().to_owned()
+}
+
+/// Format a `DATE` or `DATE-TIME` value for display.
+///
+/// Values that can’t be parsed are returned as is.
+fn format_time(params: &str, value: &str) -> String {
+    let tzid = params.split(';').find_map(|param| {
+        let (name, value) = param.split_once('=')?;
+        name.eq_ignore_ascii_case("TZID")
+            .then(|| value.trim_matches('"'))
+    });
+
+    let (Some(date), time) = (
+        format_digits(value.get(..8), &[4, 2, 2], "-"),
+        value.get(8..),
+    ) else {
+        return value.to_owned();
+    };
+    let Some(time) = time.filter(|time| !time.is_empty()) else {
+        return date;
+    };
+    let (time, utc) = match time.strip_suffix(['Z', 'z']) {
+        Some(time) => (time, true),
+        None => (time, false),
+    };
+    let Some(time) = time
+        .strip_prefix(['T', 't'])
+        .and_then(|time| format_digits(time.get(..4), &[2, 2], ":"))
+    else {
+        return value.to_owned();
+    };
+
+    match (utc, tzid) {
+        (true, _) => format!("{date} {time} UTC"),
+        (false, Some(tzid)) => format!("{date} {time} ({tzid})"),
+        (false, None) => format!("{date} {time}"),
+    }
+}
+
+/// Split a string of ASCII digits into groups of `widths` joined by
+/// `separator`, e.g. `20240501` into `2024-05-01`.
+fn format_digits(
+    digits: Option<&str>,
+    widths: &[usize],
+    separator: &str,
+) -> Option<String> {
+    let digits =
+        digits.filter(|digits| digits.bytes().all(|b| b.is_ascii_digit()))?;
+    let mut groups = Vec::new();
+    let mut rest = digits;
+    for &width in widths {
+        let (group, after) = rest.split_at_checked(width)?;
+        groups.push(group);
+        rest = after;
+    }
+    Some(groups.join(separator))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use assert2::check;
+
+    #[test]
+    fn test_render() {
+        let ics = "BEGIN:VCALENDAR\r\n\
+            BEGIN:VEVENT\r\n\
+            DTSTAMP:20240101T000000Z\r\n\
+            SUMMARY:Second\\, with a long\r\n  folded title\r\n\
+            DTSTART;TZID=Europe/Paris:20240502T093000\r\n\
+            DTEND;TZID=Europe/Paris:20240502T103000\r\n\
+            LOCATION:Room 1\r\n\
+            END:VEVENT\r\n\
+            BEGIN:VEVENT\r\n\
+            DTSTAMP:20240101T000000Z\r\n\
+            SUMMARY:First\r\n\
+            DTSTART;VALUE=DATE:20240501\r\n\
+            DTEND:20240501T170000Z\r\n\
+            END:VEVENT\r\n\
+            END:VCALENDAR\r\n";
+        check!(
+            render(ics)
+                == "## First\n\n\
+                    Start: 2024-05-01  \n\
+                    End: 2024-05-01 17:00 UTC  \n\n\
+                    ## Second, with a long folded title\n\n\
+                    Start: 2024-05-02 09:30 (Europe/Paris)  \n\
+                    End: 2024-05-02 10:30 (Europe/Paris)  \n\
+                    Location: Room 1"
+        );
+    }
+}
@@ -0,0 +1,184 @@
+//! Normalizing rendered Markdown.
+//!
+//! `htmd` isn’t entirely consistent about whitespace and escaping, between
+//! versions and between inputs that differ in insignificant ways. Those
+//! differences would be reported as changes, so rendered Markdown is put in a
+//! canonical form before it’s compared:
+//!
+//!   * Trailing whitespace is removed, and runs of blank lines are collapsed.
+//!   * List items are marked with `-`, and thematic breaks are `* * *`.
+//!   * Underlined (setext) headings become `#` (ATX) headings.
+//!   * Common HTML entities like `&amp;` are decoded, and non-breaking spaces
+//!     become normal spaces.
+//!
+//! Fenced code blocks are left as they are.
+
+/// Put Markdown in canonical form.
+#[must_use]
+pub fn normalize(markdown: &str) -> String {
+    let mut lines: Vec<String> = Vec::new();
+    let mut fence: Option<&str> = None;
+    for line in markdown.lines() {
+        if let Some(marker) = fence {
+            if line.trim_start().starts_with(marker) {
+                fence = None;
+            }
+            lines.push(line.to_owned());
+            continue;
+        }
+
+        let line = decode_entities(line.trim_end());
+        if line.is_empty() {
+            if lines.last().is_some_and(|last| !last.is_empty()) {
+                lines.push(String::new());
+            }
+        } else if let Some(marker) = fence_marker(&line) {
+            fence = Some(marker);
+            lines.push(line);
+        } else if let Some(level) = setext_level(&line)
+            && lines.last().is_some_and(|last| is_paragraph(last))
+        {
+            let mut paragraph = Vec::new();
+            while let Some(last) = lines.pop_if(|last| is_paragraph(last)) {
+                paragraph.push(last);
+            }
+            paragraph.reverse();
+            lines.push(format!(
+                "{} {}",
+                "#".repeat(level),
+                paragraph.join(" ")
+            ));
+        } else if is_thematic_break(&line) {
+            lines.push("* * *".to_owned());
+        } else {
+            lines.push(normalize_bullet(line));
+        }
+    }
+
+    if lines.last().is_some_and(String::is_empty) {
+        lines.pop();
+    }
+    lines.join("\n")
+}
+
+/// Get the marker of a line that opens a fenced code block, if it is one.
+fn fence_marker(line: &str) -> Option<&'static str> {
+    let line = line.trim_start();
+    ["```", "~~~"]
+        .into_iter()
+        .find(|marker| line.starts_with(marker))
+}
+
+/// Get the heading level of a setext heading underline, if it is one.
+fn setext_level(line: &str) -> Option<usize> {
+    let line = line.trim();
+    if line.chars().all(|c| c == '=') {
+        Some(1)
+    } else if line.chars().all(|c| c == '-') {
+        Some(2)
+    } else {
+        None
+    }
+}
+
+/// Check if a line is part of a paragraph, and so could be underlined to make
+/// it a setext heading.
+fn is_paragraph(line: &str) -> bool {
+    let trimmed = line.trim_start();
+    !trimmed.is_empty()
+        && line.len().saturating_sub(trimmed.len()) < 4
+        && !trimmed.starts_with(['#', '>', '|', '-', '*', '+'])
+        && !trimmed.starts_with(|c: char| c.is_ascii_digit())
+}
+
+/// Check if a line is a thematic break, like `---` or `* * *`.
+fn is_thematic_break(line: &str) -> bool {
+    let mut chars = line.chars().filter(|c| !c.is_whitespace());
+    let Some(first) = chars.next() else {
+        return false;
+    };
+    ['*', '-', '_'].contains(&first)
+        && chars.clone().all(|c| c == first)
+        && chars.count() >= 2
+}
+
+/// Mark a list item with `-` if it’s marked with `*` or `+`.
+fn normalize_bullet(line: String) -> String {
+    let trimmed = line.trim_start();
+    let indent = line.len().saturating_sub(trimmed.len());
+    match trimmed.strip_prefix(['*', '+']) {
+        Some(rest) if rest.starts_with(' ') => {
+            format!("{}-{rest}", line.get(..indent).unwrap_or_default())
+        }
+        _ => line,
+    }
+}
+
+/// Decode common HTML entities, and replace non-breaking spaces with spaces.
+fn decode_entities(line: &str) -> String {
+    let mut decoded = String::with_capacity(line.len());
+    let mut rest = line;
+    while let Some(start) = rest.find('&') {
+        let (before, after) = rest.split_at(start);
+        decoded.push_str(before);
+        let entity = after
+            .get(1..)
+            .and_then(|after| after.split_once(';'))
+            .and_then(|(name, after)| Some((decode_entity(name)?, after)));
+        if let Some((c, after)) = entity {
+            decoded.push(c);
+            rest = after;
+        } else {
+            decoded.push('&');
+            rest = after.get(1..).unwrap_or_default();
+        }
+    }
+    decoded.push_str(rest);
+    decoded.replace('\u{a0}', " ")
+}
+
+/// Decode the name of an HTML entity, e.g. `amp` or `#x26`.
+fn decode_entity(name: &str) -> Option<char> {
+    match name {
+        "amp" => Some('&'),
+        "lt" => Some('<'),
+        "gt" => Some('>'),
+        "quot" => Some('"'),
+        "apos" => Some('\''),
+        "nbsp" => Some(' '),
+        _ => {
+            let number = name.strip_prefix('#')?;
+            let code = if let Some(hex) = number.strip_prefix(['x', 'X']) {
+                u32::from_str_radix(hex, 16).ok()?
+            } else {
+                number.parse().ok()?
+            };
+            char::from_u32(code)
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use assert2::check;
+
+    #[test]
+    fn test_normalize() {
+        check!(
+            normalize(
+                "\n\nTitle\n=====\n\n\n\nSome  \ntext\n-------\n\
+                * one &amp; two&nbsp;&#8212; &#x26;lt;\n  \
+                + nested\n\n\
+                *emphasis*, & more; &unknown;\n\
+                - - -\n\n\
+                ```\n* code\n\n\n&amp;\n```\n\n"
+            ) == "# Title\n\n## Some text\n\
+                - one & two — &lt;\n  \
+                - nested\n\n\
+                *emphasis*, & more; &unknown;\n\
+                * * *\n\n\
+                ```\n* code\n\n\n&amp;\n```"
+        );
+    }
+}
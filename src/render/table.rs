@@ -0,0 +1,257 @@
+//! Rendering HTML tables.
+
+use htmd::Element;
+use htmd::element_handler::{HandlerResult, Handlers};
+use markup5ever_rcdom::{Handle, NodeData};
+use std::fmt::Write as _;
+
+/// How to render tables.
+#[derive(
+    Clone,
+    Copy,
+    Debug,
+    Default,
+    Eq,
+    PartialEq,
+    serde::Deserialize,
+    serde::Serialize,
+)]
+#[serde(rename_all = "kebab-case")]
+pub enum TableMode {
+    /// Aligned GitHub-style Markdown tables.
+    #[default]
+    Markdown,
+
+    /// One line per cell, labeled with its row and column, so that a change
+    /// to one cell only changes one line.
+    Cells,
+}
+
+/// The maximum `colspan` to honor.
+const MAX_COLSPAN: usize = 100;
+
+/// A table extracted from HTML, with each cell rendered as Markdown.
+#[derive(Debug, Default)]
+struct Table {
+    /// The caption, if any.
+    caption: Option<String>,
+
+    /// The header row, if any.
+    header: Option<Vec<String>>,
+
+    /// The other rows.
+    rows: Vec<Vec<String>>,
+}
+
+/// Handle a `<table>` element.
+pub fn handle(
+    mode: TableMode,
+    handlers: &dyn Handlers,
+    element: &Element,
+) -> Option<HandlerResult> {
+    let mut table = Table::default();
+    for child in element.node.children.borrow().iter() {
+        match tag_name(child).as_deref() {
+            Some("caption") => {
+                table.caption = handlers
+                    .handle(child)
+                    .map(|result| normalize(&result.content));
+            }
+            Some("thead") => {
+                for row in child_rows(child) {
+                    table.add_row(handlers, &row, true);
+                }
+            }
+            Some("tbody" | "tfoot") => {
+                for row in child_rows(child) {
+                    table.add_row(handlers, &row, false);
+                }
+            }
+            Some("tr") => table.add_row(handlers, child, false),
+            _ => {}
+        }
+    }
+
+    if table.header.is_none() && table.rows.is_empty() {
+        return None;
+    }
+
+    let content = match mode {
+        TableMode::Markdown => table.to_markdown(),
+        TableMode::Cells => table.to_cells(),
+    };
+    Some(format!("\n\n{}\n\n", content.trim_end()).into())
+}
+
+impl Table {
+    /// Add a row to the table.
+    ///
+    /// The first row is used as the header if it’s in `<thead>` or all of
+    /// its cells are `<th>`.
+    fn add_row(&mut self, handlers: &dyn Handlers, row: &Handle, head: bool) {
+        let mut cells = Vec::new();
+        let mut all_th = true;
+        for cell in row.children.borrow().iter() {
+            let tag = tag_name(cell);
+            if !matches!(tag.as_deref(), Some("td" | "th")) {
+                continue;
+            }
+            all_th &= tag.as_deref() == Some("th");
+
+            let content = handlers
+                .handle(cell)
+                .map(|result| normalize(&result.content))
+                .unwrap_or_default();
+            cells.push(content);
+            for _ in 1..colspan(cell) {
+                cells.push(String::new());
+            }
+        }
+
+        if cells.is_empty() {
+            return;
+        }
+        if self.header.is_none() && self.rows.is_empty() && (head || all_th) {
+            self.header = Some(cells);
+        } else {
+            self.rows.push(cells);
+        }
+    }
+
+    /// The number of columns in the widest row.
+    fn columns(&self) -> usize {
+        self.header
+            .iter()
+            .chain(&self.rows)
+            .map(Vec::len)
+            .max()
+            .unwrap_or_default()
+    }
+
+    /// Render as an aligned GitHub-style Markdown table.
+    ///
+    /// If there’s no header row the first row is used, since Markdown tables
+    /// require one.
+    fn to_markdown(&self) -> String {
+        let columns = self.columns();
+        let (header, rows) = match (&self.header, self.rows.split_first()) {
+            (Some(header), _) => (header, &self.rows[..]),
+            (None, Some((first, rest))) => (first, rest),
+            (None, None) => return String::new(),
+        };
+
+        let mut widths = vec![3; columns];
+        for row in std::iter::once(header).chain(rows) {
+            for (width, cell) in widths.iter_mut().zip(row) {
+                *width = (*width).max(cell.chars().count());
+            }
+        }
+
+        let mut out = String::new();
+        if let Some(caption) = &self.caption {
+            let _ = writeln!(out, "{caption}\n");
+        }
+        write_row(&mut out, header, &widths);
+        let separator: Vec<_> =
+            widths.iter().map(|&width| "-".repeat(width)).collect();
+        write_row(&mut out, &separator, &widths);
+        for row in rows {
+            write_row(&mut out, row, &widths);
+        }
+        out
+    }
+
+    /// Render with one line per cell, labeled with the first cell in the row
+    /// and the column header.
+    fn to_cells(&self) -> String {
+        let mut out = String::new();
+        if let Some(caption) = &self.caption {
+            let _ = writeln!(out, "{caption}\n");
+        }
+
+        for row in &self.rows {
+            let Some((label, cells)) = row.split_first() else {
+                continue;
+            };
+            if cells.is_empty() {
+                let _ = writeln!(out, "{label}");
+                continue;
+            }
+
+            for (i, cell) in cells.iter().enumerate() {
+                if cell.is_empty() {
+                    continue;
+                }
+                let column = i.saturating_add(1);
+                let heading = self
+                    .header
+                    .as_ref()
+                    .and_then(|header| header.get(column))
+                    .filter(|heading| !heading.is_empty());
+                if let Some(heading) = heading {
+                    let _ = writeln!(out, "{label} › {heading}: {cell}");
+                } else {
+                    let _ = writeln!(
+                        out,
+                        "{label} › column {}: {cell}",
+                        column.saturating_add(1),
+                    );
+                }
+            }
+        }
+        out
+    }
+}
+
+/// Write a row of a Markdown table, padding cells to `widths`.
+fn write_row(out: &mut String, row: &[String], widths: &[usize]) {
+    out.push('|');
+    for (i, width) in widths.iter().enumerate() {
+        let cell = row.get(i).map(String::as_str).unwrap_or_default();
+        let padding = width.saturating_sub(cell.chars().count());
+        let _ = write!(out, " {cell}{} |", " ".repeat(padding));
+    }
+    out.push('\n');
+}
+
+/// Collapse whitespace (including line breaks) and escape pipes so that cell
+/// content fits on one line.
+fn normalize(content: &str) -> String {
+    content
+        .split_whitespace()
+        .collect::<Vec<_>>()
+        .join(" ")
+        .replace('|', "\\|")
+}
+
+/// Get the `<tr>` children of a node.
+fn child_rows(node: &Handle) -> Vec<Handle> {
+    node.children
+        .borrow()
+        .iter()
+        .filter(|child| tag_name(child).as_deref() == Some("tr"))
+        .cloned()
+        .collect()
+}
+
+/// Get the tag name of a node, if it’s an element.
+fn tag_name(node: &Handle) -> Option<String> {
+    match &node.data {
+        NodeData::Element { name, .. } => Some(name.local.to_string()),
+        _ => None,
+    }
+}
+
+/// Get the `colspan` of a cell.
+fn colspan(cell: &Handle) -> usize {
+    let NodeData::Element { attrs, .. } = &cell.data else {
+        return 1;
+    };
+    attrs
+        .borrow()
+        .iter()
+        .find(|attr| &*attr.name.local == "colspan")
+        .and_then(|attr| attr.value.trim().parse().ok())
+        .unwrap_or(1_usize)
+        .clamp(1, MAX_COLSPAN)
+}
@@ -0,0 +1,906 @@
+//! HTTP responses.
+
+use bytes::Bytes;
+use chardetng::{EncodingDetector, Iso2022JpDetection, Utf8Detection};
+use encoding_rs::Encoding;
+use mime::Mime;
+use sha2::{Digest, Sha256};
+use std::borrow::Cow;
+use std::fmt::Write as _;
+use std::fs;
+use std::io::{self, Read as _, Write as _};
+use std::net::IpAddr;
+use std::path::{Path, PathBuf};
+use std::sync::Arc;
+use thiserror::Error;
+use url::Url;
+
+use crate::encoding::Decoder;
+use crate::spool::{Spool, Spooled};
+use crate::state::{hex, sha256_hex};
+
+/// Errors resulting from processing an HTTP response.
+#[derive(Error, Debug)]
+pub enum ResponseError {
+    /// Could not convert header value to string.
+    #[error("could not convert header value to string")]
+    InvalidStr(#[from] http::header::ToStrError),
+
+    /// Could not convert header value to MIME media type.
+    #[error("could not convert header value to MIME media type")]
+    InvalidMediaType(#[from] mime::FromStrError),
+
+    /// Unknown charset in header.
+    #[error("unknown charset {0}")]
+    InvalidCharset(String),
+}
+
+/// Errors downloading an HTTP response.
+#[derive(Error, Debug)]
+pub enum DownloadError {
+    /// The request failed or the connection was interrupted.
+    #[error(transparent)]
+    Request(#[from] reqwest::Error),
+
+    /// The body was larger than the maximum, so downloading was stopped.
+    #[error(
+        "stopped downloading after {received} bytes: larger than \
+        max_download_size of {max} bytes"
+    )]
+    TooLarge {
+        /// The maximum size of the body in bytes.
+        max: u64,
+
+        /// How many bytes were downloaded before stopping.
+        received: usize,
+    },
+
+    /// The body was too large to keep in memory, and couldn’t be written to
+    /// disk.
+    #[error("could not write body to disk")]
+    Spool(#[from] io::Error),
+
+    /// The body couldn’t be decoded according to its `Content-Encoding`.
+    #[error("could not decode body")]
+    Decode(#[source] io::Error),
+
+    /// A local file for a `file://` URL couldn’t be read.
+    #[error("could not read {}", path.display())]
+    File {
+        /// The path to the file.
+        path: PathBuf,
+
+        /// The error reading it.
+        source: io::Error,
+    },
+}
+
+impl DownloadError {
+    /// How many bytes of the body were downloaded before the error.
+    #[must_use]
+    pub const fn received(&self) -> usize {
+        match self {
+            Self::Request(_)
+            | Self::Spool(_)
+            | Self::Decode(_)
+            | Self::File { .. } => 0,
+            Self::TooLarge { received, .. } => *received,
+        }
+    }
+}
+
+/// Collects a body as it’s downloaded, writing it to the spool if it’s too
+/// large to keep in memory.
+struct BodyWriter<'a> {
+    /// The URL the body is from, for logging.
+    url: &'a Url,
+
+    /// The maximum size of the body, if any.
+    max: Option<u64>,
+
+    /// Where to write the body if it’s large.
+    spool: &'a Spool,
+
+    /// The body so far, if it’s being kept in memory.
+    body: Vec<u8>,
+
+    /// The file the body is being written to, if it’s too large for memory.
+    file: Option<(fs::File, Spooled)>,
+
+    /// Hash of the body so far.
+    hasher: Sha256,
+
+    /// Number of bytes so far.
+    received: usize,
+}
+
+impl<'a> BodyWriter<'a> {
+    /// Start collecting a body.
+    fn new(url: &'a Url, max: Option<u64>, spool: &'a Spool) -> Self {
+        Self {
+            url,
+            max,
+            spool,
+            body: Vec::new(),
+            file: None,
+            hasher: Sha256::new(),
+            received: 0,
+        }
+    }
+
+    /// Add a chunk of the body.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if the body is now larger than `max`, or couldn’t be
+    /// written to the spool.
+    fn write(&mut self, chunk: &[u8]) -> Result<(), DownloadError> {
+        self.received = self.received.saturating_add(chunk.len());
+        if let Some(max) = self.max
+            && u64::try_from(self.received).unwrap_or(u64::MAX) > max
+        {
+            return Err(DownloadError::TooLarge {
+                max,
+                received: self.received,
+            });
+        }
+        self.hasher.update(chunk);
+
+        if self.file.is_none() && self.received > self.spool.max_memory {
+            tracing::debug!(
+                "Writing body of {} to disk: larger than {} bytes",
+                self.url,
+                self.spool.max_memory,
+            );
+            let (mut spool_file, spooled) = self.spool.create()?;
+            spool_file.write_all(&self.body)?;
+            self.body = Vec::new();
+            self.file = Some((spool_file, spooled));
+        }
+        if let Some((spool_file, _)) = &mut self.file {
+            spool_file.write_all(chunk)?;
+        } else {
+            self.body.extend_from_slice(chunk);
+        }
+        Ok(())
+    }
+
+    /// Get the body, which is empty if it was written to the spool, and the
+    /// spooled file, if any.
+    fn finish(self) -> (Bytes, Option<Arc<Spooled>>) {
+        let Self { body, file, hasher, received, .. } = self;
+        let spooled = file.map(|(_, mut spooled)| {
+            spooled.len = received;
+            spooled.sha256 = hex(&hasher.finalize());
+            Arc::new(spooled)
+        });
+        (body.into(), spooled)
+    }
+}
+
+/// Guess the media type of a local file from its extension.
+fn guess_media_type(path: &Path) -> Option<&'static str> {
+    let extension = path.extension()?.to_str()?.to_ascii_lowercase();
+    Some(match extension.as_str() {
+        "html" | "htm" => "text/html; charset=utf-8",
+        "xhtml" => "application/xhtml+xml",
+        "json" => "application/json",
+        "csv" => "text/csv",
+        "tsv" => "text/tab-separated-values",
+        "ics" => "text/calendar",
+        "pdf" => "application/pdf",
+        "xml" => "application/xml",
+        "txt" | "md" | "log" | "toml" | "yaml" | "yml" | "ini" | "conf" => {
+            "text/plain"
+        }
+        _ => return None,
+    })
+}
+
+/// An HTTP response that can be serialized.
+#[derive(Clone, Debug, serde::Serialize, serde::Deserialize)]
+pub struct Response {
+    /// The URL that actually produced the response.
+    pub url: Url,
+
+    /// The HTTP version of the response.
+    #[serde(with = "http_serde::version")]
+    pub version: http::Version,
+
+    /// The HTTP status code of the response.
+    #[serde(with = "http_serde::status_code")]
+    pub status: http::StatusCode,
+
+    /// The HTTP headers of the response.
+    #[serde(with = "http_serde::header_map")]
+    pub headers: http::HeaderMap,
+
+    /// The body returned by the response. This is empty if the body was too
+    /// large to keep in memory; see `spooled`.
+    pub body: Bytes,
+
+    /// The body, if it was too large to keep in memory.
+    #[serde(skip)]
+    pub spooled: Option<Arc<Spooled>>,
+
+    /// SHA-256 of the server’s TLS certificate, if the URL uses HTTPS.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub certificate_sha256: Option<String>,
+
+    /// The address of the server that sent the response, if known.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub peer_address: Option<IpAddr>,
+}
+
+impl Response {
+    /// From [`reqwest::Response`], downloading at most `max` bytes of body.
+    ///
+    /// The body is decoded according to its `Content-Encoding`, which is kept
+    /// in the headers. `max` applies to the decoded body.
+    ///
+    /// Bodies larger than the spool’s `max_memory` are written to a file as
+    /// they’re downloaded rather than kept in memory.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if the body couldn’t be downloaded or written to disk,
+    /// or if it’s larger than `max`. Downloading stops as soon as the body is
+    /// too large.
+    pub async fn from_reqwest(
+        mut response: reqwest::Response,
+        max: Option<u64>,
+        spool: &Spool,
+    ) -> Result<Self, DownloadError> {
+        let certificate_sha256 = response
+            .extensions()
+            .get::<reqwest::tls::TlsInfo>()
+            .and_then(reqwest::tls::TlsInfo::peer_certificate)
+            .map(sha256_hex);
+        let url = response.url().clone();
+        let mut decoder =
+            Decoder::new(response.headers()).map_err(DownloadError::Decode)?;
+        let mut body = BodyWriter::new(&url, max, spool);
+        while let Some(chunk) = response.chunk().await? {
+            body.write(
+                &decoder.decode(&chunk).map_err(DownloadError::Decode)?,
+            )?;
+        }
+        body.write(&decoder.finish().map_err(DownloadError::Decode)?)?;
+
+        let (body, spooled) = body.finish();
+        Ok(Self {
+            url,
+            version: response.version(),
+            status: response.status(),
+            headers: response.headers().clone(),
+            body,
+            spooled,
+            certificate_sha256,
+            peer_address: response.remote_addr().map(|addr| addr.ip()),
+        })
+    }
+
+    /// Read a local file for a `file://` URL, reading at most `max` bytes.
+    ///
+    /// The response is always `200 OK`. Its `Content-Type` is guessed from
+    /// the file’s extension, and its `Last-Modified` is the file’s
+    /// modification time. Large files are written to the spool like large
+    /// bodies.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if the URL isn’t a local path, or the file couldn’t be
+    /// read or is larger than `max`.
+    pub fn from_file(
+        url: &Url,
+        max: Option<u64>,
+        spool: &Spool,
+    ) -> Result<Self, DownloadError> {
+        let path = url.to_file_path().map_err(|()| DownloadError::File {
+            path: PathBuf::from(url.path()),
+            source: io::Error::new(
+                io::ErrorKind::InvalidInput,
+                "not a local path",
+            ),
+        })?;
+        let file_error =
+            |source| DownloadError::File { path: path.clone(), source };
+
+        let mut file = fs::File::open(&path).map_err(file_error)?;
+        let mut headers = http::HeaderMap::new();
+        if let Ok(modified) = file.metadata().and_then(|meta| meta.modified()) {
+            let modified = chrono::DateTime::<chrono::Utc>::from(modified)
+                .format("%a, %d %b %Y %H:%M:%S GMT")
+                .to_string();
+            if let Ok(value) = modified.parse() {
+                headers.insert(http::header::LAST_MODIFIED, value);
+            }
+        }
+        if let Some(media_type) = guess_media_type(&path) {
+            headers.insert(
+                http::header::CONTENT_TYPE,
+                http::HeaderValue::from_static(media_type),
+            );
+        }
+
+        let mut body = BodyWriter::new(url, max, spool);
+        let mut chunk = vec![0; 64 * 1024];
+        loop {
+            let len = file.read(&mut chunk).map_err(file_error)?;
+            let Some(chunk) =
+                chunk.get(..len).filter(|chunk| !chunk.is_empty())
+            else {
+                break;
+            };
+            body.write(chunk)?;
+        }
+
+        let (body, spooled) = body.finish();
+        Ok(Self {
+            url: url.clone(),
+            version: http::Version::default(),
+            status: http::StatusCode::OK,
+            headers,
+            body,
+            spooled,
+            certificate_sha256: None,
+            peer_address: None,
+        })
+    }
+
+    /// Get the size of the body in bytes, even if it’s stored on disk.
+    pub fn body_len(&self) -> usize {
+        self.spooled
+            .as_ref()
+            .map_or(self.body.len(), |spooled| spooled.len)
+    }
+
+    /// Get the SHA-256 of the body, even if it’s stored on disk.
+    pub fn body_sha256(&self) -> String {
+        self.spooled.as_ref().map_or_else(
+            || sha256_hex(&self.body),
+            |spooled| spooled.sha256.clone(),
+        )
+    }
+
+    /// Get the content-type.
+    ///
+    /// Based on [`reqwest::Response::text_with_charset()`].
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if the `Content-Type` header is invalid.
+    pub fn content_type(&self) -> Result<Option<Mime>, ResponseError> {
+        // FIXME? ignores multiple values
+        self.headers
+            .get(http::header::CONTENT_TYPE)
+            .map(|value| {
+                value.to_str().map_err(ResponseError::InvalidStr).and_then(
+                    |value| {
+                        value.parse().map_err(ResponseError::InvalidMediaType)
+                    },
+                )
+            })
+            .transpose()
+    }
+
+    /// Get the essence of the content-type, e.g. `text/html`.
+    ///
+    /// This is the type and subtype without parameters, in lowercase.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if the `Content-Type` header is invalid.
+    pub fn media_type(&self) -> Result<Option<String>, ResponseError> {
+        Ok(self
+            .content_type()?
+            .map(|media_type| media_type.essence_str().to_ascii_lowercase()))
+    }
+
+    /// Whether the response says the page was removed.
+    #[must_use]
+    pub const fn is_removed(&self) -> bool {
+        is_removed(self.status)
+    }
+
+    /// Whether the response is HTML.
+    ///
+    /// Responses without a content-type are assumed to be HTML.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if the `Content-Type` header is invalid.
+    pub fn is_html(&self) -> Result<bool, ResponseError> {
+        Ok(matches!(
+            self.media_type()?.as_deref(),
+            None | Some("text/html" | "application/xhtml+xml")
+        ))
+    }
+
+    /// Whether the response is JSON.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if the `Content-Type` header is invalid.
+    pub fn is_json(&self) -> Result<bool, ResponseError> {
+        Ok(self.media_type()?.is_some_and(|media_type| {
+            media_type == "application/json" || media_type.ends_with("+json")
+        }))
+    }
+
+    /// Whether the response is binary content that can’t be rendered as text.
+    ///
+    /// PDFs are not considered binary since text can be extracted from them.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if the `Content-Type` header is invalid.
+    pub fn is_binary(&self) -> Result<bool, ResponseError> {
+        let Some(media_type) = self.media_type()? else {
+            return Ok(false);
+        };
+        let Some((top, sub)) = media_type.split_once('/') else {
+            return Ok(false);
+        };
+
+        Ok(match top {
+            "text" => false,
+            "image" | "audio" | "video" | "font" => !sub.ends_with("+xml"),
+            "application" => {
+                !matches!(
+                    sub,
+                    "pdf"
+                        | "json"
+                        | "xml"
+                        | "javascript"
+                        | "ecmascript"
+                        | "csv"
+                ) && !sub.ends_with("+json")
+                    && !sub.ends_with("+xml")
+            }
+            _ => true,
+        })
+    }
+
+    /// Get where the response redirects to, if it’s a redirect that wasn’t
+    /// followed.
+    #[must_use]
+    pub fn redirect_target(&self) -> Option<Url> {
+        if !self.status.is_redirection()
+            || self.status == http::StatusCode::NOT_MODIFIED
+        {
+            return None;
+        }
+        let location = self.headers.get(http::header::LOCATION)?;
+        self.url.join(location.to_str().ok()?).ok()
+    }
+
+    /// Get where the response leads: the target if it’s a redirect that wasn’t
+    /// followed, and otherwise its own URL.
+    #[must_use]
+    pub fn destination(&self) -> Url {
+        self.redirect_target().unwrap_or_else(|| self.url.clone())
+    }
+
+    /// Describe a redirect that wasn’t followed, for comparison.
+    #[must_use]
+    pub fn redirect_summary(&self) -> Option<String> {
+        self.redirect_target()
+            .map(|target| format!("Redirects to {target} ({})\n", self.status))
+    }
+
+    /// Get the charset.
+    ///
+    /// Based on [`reqwest::Response::text_with_charset()`].
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if the `Content-Type` header is invalid.
+    pub fn charset(&self) -> Result<Option<String>, ResponseError> {
+        // FIXME? return &str?
+        Ok(self.content_type()?.and_then(|media_type| {
+            media_type
+                .get_param(mime::CHARSET)
+                .map(|name| name.to_string())
+        }))
+    }
+
+    /// Get the charset.
+    ///
+    /// Based on [`reqwest::Response::text_with_charset()`].
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if the `Content-Type` header is invalid or has an
+    /// unknown charset.
+    pub fn charset_encoding(
+        &self,
+    ) -> Result<Option<&'static Encoding>, ResponseError> {
+        self.charset()?
+            .map(|charset| {
+                Encoding::for_label(charset.as_bytes())
+                    .ok_or(ResponseError::InvalidCharset(charset))
+            })
+            .transpose()
+    }
+
+    /// Determine the encoding of the body.
+    ///
+    /// This follows the order browsers use: a byte order mark, then the
+    /// charset in the content-type, then (for HTML) a `<meta>` tag in the
+    /// first 1024 bytes. If none of those are present, the body is assumed to
+    /// be UTF-8 if it’s valid UTF-8, and otherwise the encoding is guessed
+    /// from its contents.
+    ///
+    /// This is the encoding the response claims to be in; see
+    /// [`Self::actual_encoding()`] for the one used to decode it.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if the `Content-Type` header is invalid or has an
+    /// unknown charset.
+    pub fn encoding(&self) -> Result<&'static Encoding, ResponseError> {
+        if let Some((encoding, _)) = Encoding::for_bom(&self.body) {
+            return Ok(encoding);
+        }
+
+        if let Some(encoding) = self.charset_encoding()? {
+            return Ok(encoding);
+        }
+
+        if self.is_html()?
+            && let Some(encoding) = sniff_meta_charset(&self.body)
+        {
+            return Ok(encoding);
+        }
+
+        if str::from_utf8(&self.body).is_ok() {
+            Ok(encoding_rs::UTF_8)
+        } else {
+            Ok(self.guess_encoding())
+        }
+    }
+
+    /// Determine the encoding to decode the body with.
+    ///
+    /// Some servers label pages with the wrong encoding, e.g. UTF-8 for pages
+    /// in windows-1252, which makes every non-ASCII character decode as a
+    /// replacement character. If the body isn’t valid in the encoding from
+    /// [`Self::encoding()`], the encoding is guessed from its contents.
+    /// Similarly, a body labeled with a legacy single byte encoding that’s
+    /// valid UTF-8 is decoded as UTF-8, since that’s very unlikely to happen
+    /// by accident. Byte order marks are always trusted.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if the `Content-Type` header is invalid or has an
+    /// unknown charset.
+    pub fn actual_encoding(&self) -> Result<&'static Encoding, ResponseError> {
+        let encoding = self.encoding()?;
+        if Encoding::for_bom(&self.body).is_some() {
+            return Ok(encoding);
+        }
+
+        if encoding.is_single_byte() {
+            return Ok(
+                if !self.body.is_ascii() && str::from_utf8(&self.body).is_ok() {
+                    encoding_rs::UTF_8
+                } else {
+                    encoding
+                },
+            );
+        }
+
+        let mangled = encoding
+            .decode_without_bom_handling_and_without_replacement(&self.body)
+            .is_none();
+        Ok(if !mangled {
+            encoding
+        } else if str::from_utf8(&self.body).is_ok() {
+            encoding_rs::UTF_8
+        } else {
+            self.guess_encoding()
+        })
+    }
+
+    /// Guess the encoding of a body that isn’t valid UTF-8 from its contents
+    /// and the top level domain of the URL.
+    fn guess_encoding(&self) -> &'static Encoding {
+        let mut detector = EncodingDetector::new(Iso2022JpDetection::Deny);
+        detector.feed(&self.body, true);
+        let tld = self
+            .url
+            .host_str()
+            .and_then(|host| host.rsplit('.').next())
+            .map(str::as_bytes);
+        detector.guess(tld, Utf8Detection::Deny)
+    }
+
+    /// Get the response body as text.
+    ///
+    /// See [`Self::actual_encoding()`] for how the encoding is determined.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if the encoding couldn’t be determined; see
+    /// [`Self::encoding()`].
+    pub fn text(&self) -> Result<Cow<'_, str>, ResponseError> {
+        let (text, _actual_encoding, _mangled) =
+            self.actual_encoding()?.decode(&self.body);
+        Ok(text)
+    }
+}
+
+/// Whether a status means the page was removed, i.e. 404 Not Found or
+/// 410 Gone.
+#[must_use]
+pub const fn is_removed(status: http::StatusCode) -> bool {
+    matches!(status, http::StatusCode::NOT_FOUND | http::StatusCode::GONE)
+}
+
+/// Format the headers named in `watch_headers` as lines for comparison.
+///
+/// Headers are listed in the order of `names`, with one line per value. Only
+/// the names of cookies in `Set-Cookie` are included, since their values
+/// usually change on every request.
+#[must_use]
+pub fn watched_headers(headers: &http::HeaderMap, names: &[String]) -> String {
+    let mut lines = String::new();
+    for name in names {
+        let mut values: Vec<_> = headers
+            .get_all(name.as_str())
+            .iter()
+            .map(|value| String::from_utf8_lossy(value.as_bytes()).into_owned())
+            .collect();
+        if name.eq_ignore_ascii_case("set-cookie") {
+            for value in &mut values {
+                let cookie_name =
+                    value.split(['=', ';']).next().unwrap_or_default();
+                *value = cookie_name.trim().to_owned();
+            }
+            values.sort_unstable();
+            values.dedup();
+        }
+        for value in values {
+            let _ = writeln!(lines, "{name}: {value}");
+        }
+    }
+    lines
+}
+
+/// Headers that show whether a file changed without downloading it.
+pub static HEAD_HEADERS: &[&str] = &["Content-Length", "Last-Modified", "ETag"];
+
+/// Format the headers that show whether a file changed, for URLs that are only
+/// fetched with `HEAD`.
+#[must_use]
+pub fn head_summary(headers: &http::HeaderMap) -> String {
+    let mut lines = String::new();
+    for name in HEAD_HEADERS {
+        for value in headers.get_all(*name) {
+            let _ = writeln!(
+                lines,
+                "{name}: {}",
+                String::from_utf8_lossy(value.as_bytes()),
+            );
+        }
+    }
+    lines
+}
+
+/// Find the charset declared in a `<meta>` tag near the start of an HTML
+/// document.
+///
+/// This handles both `<meta charset="…">` and `<meta http-equiv="Content-Type"
+/// content="text/html; charset=…">`. It’s a simplification of the [prescan
+/// algorithm] in the HTML standard.
+///
+/// [prescan algorithm]: https://html.spec.whatwg.org/multipage/parsing.html#prescan-a-byte-stream-to-determine-its-encoding
+fn sniff_meta_charset(body: &[u8]) -> Option<&'static Encoding> {
+    let head = body.get(..1024).unwrap_or(body).to_ascii_lowercase();
+
+    let mut rest = head.as_slice();
+    while let Some(start) = find(rest, b"<meta") {
+        rest = rest.get(start.saturating_add(5)..)?;
+        let tag = rest.split(|&b| b == b'>').next().unwrap_or_default();
+        let Some(position) = find(tag, b"charset") else {
+            continue;
+        };
+
+        let value = tag
+            .get(position.saturating_add(7)..)?
+            .trim_ascii_start()
+            .strip_prefix(b"=")?
+            .trim_ascii_start();
+        let value = value
+            .strip_prefix(b"\"")
+            .or_else(|| value.strip_prefix(b"'"))
+            .unwrap_or(value);
+        let label = value
+            .split(|b| b"\"'; \t\r\n/".contains(b))
+            .next()
+            .unwrap_or_default();
+
+        if let Some(encoding) = Encoding::for_label(label) {
+            // A document that can be read this way can’t actually be UTF-16.
+            return Some(
+                if encoding == encoding_rs::UTF_16BE
+                    || encoding == encoding_rs::UTF_16LE
+                {
+                    encoding_rs::UTF_8
+                } else if encoding == encoding_rs::X_USER_DEFINED {
+                    encoding_rs::WINDOWS_1252
+                } else {
+                    encoding
+                },
+            );
+        }
+    }
+
+    None
+}
+
+/// Find the first position of `needle` in `haystack`.
+fn find(haystack: &[u8], needle: &[u8]) -> Option<usize> {
+    haystack
+        .windows(needle.len())
+        .position(|window| window == needle)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use assert2::check;
+
+    /// Make an HTML response.
+    fn response(content_type: Option<&str>, body: &[u8]) -> Response {
+        let mut headers = http::HeaderMap::new();
+        if let Some(content_type) = content_type {
+            headers.insert(
+                http::header::CONTENT_TYPE,
+                content_type.parse().unwrap(),
+            );
+        }
+        Response {
+            url: "https://example.com/".parse().unwrap(),
+            version: http::Version::HTTP_11,
+            status: http::StatusCode::OK,
+            headers,
+            body: Bytes::copy_from_slice(body),
+            spooled: None,
+            certificate_sha256: None,
+            peer_address: None,
+        }
+    }
+
+    #[test]
+    fn test_watched_headers() {
+        let mut headers = http::HeaderMap::new();
+        headers.append("server", "nginx".parse().unwrap());
+        headers.append("set-cookie", "b=2; Path=/".parse().unwrap());
+        headers.append("set-cookie", "a=1".parse().unwrap());
+        headers.append("x-other", "ignored".parse().unwrap());
+
+        let names = ["Set-Cookie", "Server", "CSP"].map(str::to_owned);
+        check!(
+            watched_headers(&headers, &names)
+                == "Set-Cookie: a\nSet-Cookie: b\nServer: nginx\n"
+        );
+    }
+
+    #[test]
+    fn test_head_summary() {
+        let mut headers = http::HeaderMap::new();
+        headers.append("etag", "\"abc\"".parse().unwrap());
+        headers.append("content-length", "1024".parse().unwrap());
+        headers.append("date", "ignored".parse().unwrap());
+        check!(
+            head_summary(&headers) == "Content-Length: 1024\nETag: \"abc\"\n"
+        );
+    }
+
+    #[test]
+    fn test_from_file() {
+        let dir = std::env::temp_dir()
+            .join(format!("monitorbot-{}-from-file", std::process::id()));
+        let _ = fs::remove_dir_all(&dir);
+        fs::create_dir_all(&dir).unwrap();
+        let spool = Spool { dir: dir.join("spool"), max_memory: 1 << 20 };
+        let url = |name: &str| Url::from_file_path(dir.join(name)).unwrap();
+        fs::write(dir.join("page.html"), "<p>Hello</p>").unwrap();
+        let file_error_kind = |error: &DownloadError| match error {
+            DownloadError::File { source, .. } => Some(source.kind()),
+            _ => None,
+        };
+
+        let response =
+            Response::from_file(&url("page.html"), None, &spool).unwrap();
+        check!(response.status == http::StatusCode::OK);
+        check!(response.body == "<p>Hello</p>");
+        check!(
+            response.headers[http::header::CONTENT_TYPE]
+                == "text/html; charset=utf-8"
+        );
+        check!(response.headers.contains_key(http::header::LAST_MODIFIED));
+
+        // The file is read up to the cap, like a download.
+        let error = Response::from_file(&url("page.html"), Some(5), &spool)
+            .unwrap_err();
+        check!(let DownloadError::TooLarge { max: 5, received: 12 } = error);
+        check!(
+            Response::from_file(&url("page.html"), Some(12), &spool).is_ok()
+        );
+
+        let error = Response::from_file(&url("missing.html"), None, &spool)
+            .unwrap_err();
+        check!(
+            error.to_string()
+                == format!(
+                    "could not read {}",
+                    dir.join("missing.html").display()
+                )
+        );
+        check!(file_error_kind(&error) == Some(io::ErrorKind::NotFound));
+        check!(error.received() == 0);
+
+        let error = Response::from_file(&url(""), None, &spool).unwrap_err();
+        check!(file_error_kind(&error) == Some(io::ErrorKind::IsADirectory));
+
+        let remote = "file://example.com/page.html".parse().unwrap();
+        let error = Response::from_file(&remote, None, &spool).unwrap_err();
+        check!(file_error_kind(&error) == Some(io::ErrorKind::InvalidInput));
+        fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    fn test_text() {
+        let text = |content_type, body| {
+            response(content_type, body).text().unwrap().into_owned()
+        };
+
+        check!(text(None, "café".as_bytes()) == "café");
+        check!(text(None, b"caf\xe9") == "café");
+        check!(text(Some("text/html"), b"\xef\xbb\xbfcaf\xc3\xa9") == "café");
+        check!(text(Some("text/html; charset=utf-8"), b"\xff\xfec\x00") == "c");
+        check!(text(Some("text/plain; charset=iso-8859-5"), b"\xd0") == "а");
+        check!(
+            text(None, b"<meta charset='koi8-r'>\xc1")
+                == "<meta charset='koi8-r'>а"
+        );
+        check!(
+            text(
+                Some("text/html"),
+                b"<META HTTP-EQUIV=Content-Type \
+                CONTENT=\"text/html; charset=Shift_JIS\">\x82\xa0",
+            )
+            .ends_with('あ')
+        );
+        check!(
+            text(None, b"<meta charset=utf-16>caf\xc3\xa9").ends_with("café")
+        );
+        check!(
+            text(Some("text/plain"), b"<meta charset=koi8-r>caf\xe9")
+                == "<meta charset=koi8-r>café"
+        );
+
+        // Mislabeled encodings are repaired.
+        check!(text(Some("text/html; charset=utf-8"), b"caf\xe9") == "café");
+        check!(
+            text(Some("text/plain; charset=windows-1252"), "café".as_bytes())
+                == "café"
+        );
+        check!(
+            text(Some("text/plain; charset=windows-1252"), b"caf\xe9")
+                == "café"
+        );
+        check!(
+            text(Some("text/plain; charset=shift_jis"), "あ".as_bytes())
+                == "あ"
+        );
+        let response = response(Some("text/html; charset=utf-8"), b"caf\xe9");
+        check!(response.encoding().unwrap() == encoding_rs::UTF_8);
+        check!(
+            response.actual_encoding().unwrap() == encoding_rs::WINDOWS_1252
+        );
+    }
+}
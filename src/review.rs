@@ -0,0 +1,192 @@
+//! Finding changes that haven’t been reviewed.
+//!
+//! `monitorbot review` shows how each URL changed since its changes were last
+//! acknowledged. Acknowledging a URL records the time of the latest response
+//! that was shown as `reviewed_at` in its history, so those changes aren’t
+//! shown again. URLs that have never been reviewed are compared to the first
+//! response stored for them.
+
+use anyhow::bail;
+use chrono::{DateTime, Utc};
+
+use crate::check::render_url;
+use crate::config::UrlConfig;
+use crate::extract::Filter;
+use crate::output::print_pretty_diff;
+use crate::params::Params;
+use crate::state::{History, Revision, State};
+
+/// Changes to a URL that haven’t been acknowledged.
+#[derive(Clone, Copy, Debug)]
+pub struct Unreviewed<'a> {
+    /// The response that was current when the URL was last reviewed.
+    pub base: &'a Revision,
+
+    /// The latest response.
+    pub latest: &'a Revision,
+
+    /// Number of times the content changed since `base`.
+    pub count: usize,
+}
+
+/// Find the changes to a URL since it was last reviewed.
+///
+/// Returns `None` if the content hasn’t changed, including if it changed and
+/// then changed back.
+#[must_use]
+pub fn unreviewed(history: &History) -> Option<Unreviewed<'_>> {
+    changes_since(history, history.reviewed_at)
+}
+
+/// Find the changes to a URL since `since` that haven’t been reviewed.
+///
+/// Returns `None` if the content hasn’t changed since then, or since the URL
+/// was last reviewed if that was later.
+#[must_use]
+pub fn unreviewed_since(
+    history: &History,
+    since: DateTime<Utc>,
+) -> Option<Unreviewed<'_>> {
+    let since = history
+        .reviewed_at
+        .map_or(since, |reviewed| reviewed.max(since));
+    changes_since(history, Some(since))
+}
+
+/// Find the changes to a URL since the response current at `time`, or since
+/// the first response if `time` is `None` or before it.
+///
+/// Returns `None` if the content hasn’t changed, including if it changed and
+/// then changed back.
+#[must_use]
+pub fn changes_since(
+    history: &History,
+    time: Option<DateTime<Utc>>,
+) -> Option<Unreviewed<'_>> {
+    let first = history.revisions.first()?;
+    let base = time
+        .and_then(|time| {
+            history
+                .revisions
+                .iter()
+                .rev()
+                .find(|revision| revision.fetched_at <= time)
+        })
+        .unwrap_or(first);
+    let latest = history.latest()?;
+    if latest.content_sha256 == base.content_sha256 {
+        return None;
+    }
+
+    let count = history
+        .changes()
+        .iter()
+        .filter(|(_, revision)| revision.fetched_at > base.fetched_at)
+        .count();
+    Some(Unreviewed { base, latest, count })
+}
+
+/// Acknowledge the changes to a URL up to and including the response fetched
+/// at `fetched_at`.
+pub const fn acknowledge(history: &mut History, fetched_at: DateTime<Utc>) {
+    history.reviewed_at = Some(fetched_at);
+}
+
+/// Render the diff between two revisions as lines prefixed with ` `, `-`,
+/// or `+`.
+///
+/// # Errors
+///
+/// Returns an error if either revision couldn’t be loaded or rendered.
+pub fn render_diff(
+    params: &Params,
+    state: &State,
+    url_config: &UrlConfig,
+    old: &Revision,
+    new: &Revision,
+) -> anyhow::Result<Vec<String>> {
+    let filter = Filter::new(params, url_config)?;
+    let render = |revision: &Revision| -> anyhow::Result<String> {
+        let response = state.load_response(revision, params.max_body_memory)?;
+        if response.spooled.is_some() {
+            bail!("body is too large to render");
+        }
+        render_url(url_config, &response, filter)
+    };
+    let mut out = termcolor::NoColor::new(Vec::new());
+    print_pretty_diff(
+        &mut out,
+        &render(old)?,
+        &render(new)?,
+        params.diff_context(),
+    );
+    Ok(String::from_utf8_lossy(&out.into_inner())
+        .lines()
+        .map(str::to_owned)
+        .collect())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::response::Response;
+    use assert2::check;
+
+    #[test]
+    fn test_unreviewed() {
+        let revision = |body: &'static [u8], seconds| {
+            let response = Response {
+                url: "https://example.com/".parse().unwrap(),
+                version: http::Version::HTTP_11,
+                status: http::StatusCode::OK,
+                headers: http::HeaderMap::new(),
+                body: body.into(),
+                spooled: None,
+                certificate_sha256: None,
+                peer_address: None,
+            };
+            let mut revision = Revision::new(&response, body);
+            revision.fetched_at = DateTime::from_timestamp(seconds, 0).unwrap();
+            revision
+        };
+
+        let mut history = History {
+            revisions: vec![revision(b"a", 1_000)],
+            ..History::default()
+        };
+        check!(unreviewed(&history).is_none());
+
+        history.revisions.push(revision(b"b", 2_000));
+        history.revisions.push(revision(b"b", 3_000));
+        history.revisions.push(revision(b"c", 4_000));
+        let changes = unreviewed(&history).unwrap();
+        check!(changes.base.fetched_at.timestamp() == 1_000);
+        check!(changes.latest.fetched_at.timestamp() == 4_000);
+        check!(changes.count == 2);
+
+        acknowledge(&mut history, DateTime::from_timestamp(3_000, 0).unwrap());
+        let changes = unreviewed(&history).unwrap();
+        check!(changes.base.fetched_at.timestamp() == 3_000);
+        check!(changes.count == 1);
+
+        let latest = changes.latest.fetched_at;
+        acknowledge(&mut history, latest);
+        check!(unreviewed(&history).is_none());
+
+        // Changing back to the reviewed content isn’t a change to review.
+        history.revisions.push(revision(b"b", 5_000));
+        history.revisions.push(revision(b"c", 6_000));
+        check!(unreviewed(&history).is_none());
+
+        // Changes since a time exclude reviewed ones.
+        let time = |seconds| DateTime::from_timestamp(seconds, 0).unwrap();
+        check!(unreviewed_since(&history, time(4_500)).is_none());
+        history.revisions.push(revision(b"d", 7_000));
+        let changes = unreviewed_since(&history, time(2_500)).unwrap();
+        check!(changes.base.fetched_at.timestamp() == 4_000);
+        check!(changes.count == 3);
+        let changes = unreviewed_since(&history, time(5_500)).unwrap();
+        check!(changes.base.fetched_at.timestamp() == 5_000);
+        check!(changes.count == 2);
+    }
+}
@@ -0,0 +1,287 @@
+//! Support for respecting robots.txt.
+//!
+//! See [RFC 9309](https://www.rfc-editor.org/rfc/rfc9309.html).
+
+use std::collections::HashMap;
+use std::time::Duration;
+use tokio::time::Instant;
+use url::Url;
+
+use crate::stream;
+
+/// The product token we look for in `User-agent` lines.
+static PRODUCT_TOKEN: &str = env!("CARGO_PKG_NAME");
+
+/// Rules from a robots.txt file that apply to us.
+#[derive(Clone, Debug, Default, Eq, PartialEq)]
+pub struct Robots {
+    /// `Allow` (true) and `Disallow` (false) rules with their path patterns.
+    rules: Vec<(bool, String)>,
+
+    /// Delay to wait between requests to the host.
+    crawl_delay: Option<Duration>,
+}
+
+impl Robots {
+    /// Robots that allows everything, e.g. when robots.txt doesn’t exist.
+    #[must_use]
+    pub fn allow_all() -> Self {
+        Self::default()
+    }
+
+    /// Robots that disallows everything, e.g. when robots.txt is unreachable.
+    #[must_use]
+    pub fn disallow_all() -> Self {
+        Self { rules: vec![(false, "/".to_owned())], crawl_delay: None }
+    }
+
+    /// Parse a robots.txt file, keeping only the rules that apply to us.
+    ///
+    /// Rules in groups specifically for our product token take precedence over
+    /// rules in `*` groups.
+    #[must_use]
+    pub fn parse(source: &str) -> Self {
+        let mut ours = Self::default();
+        let mut any = Self::default();
+        let mut found_ours = false;
+
+        // User agents for the current group.
+        let mut agents: Vec<String> = Vec::new();
+        // Whether the last line was a rule; a `User-agent` after a rule starts
+        // a new group.
+        let mut in_rules = false;
+
+        for line in source.lines() {
+            let line = line.split('#').next().unwrap_or("").trim();
+            let Some((key, value)) = line.split_once(':') else {
+                continue;
+            };
+            let key = key.trim().to_ascii_lowercase();
+            let value = value.trim();
+
+            if key == "user-agent" {
+                if in_rules {
+                    agents.clear();
+                    in_rules = false;
+                }
+                agents.push(value.to_ascii_lowercase());
+                continue;
+            }
+
+            in_rules = true;
+            let is_ours = agents.iter().any(|agent| agent == PRODUCT_TOKEN);
+            let is_any = agents.iter().any(|agent| agent == "*");
+            let target = if is_ours {
+                found_ours = true;
+                &mut ours
+            } else if is_any {
+                &mut any
+            } else {
+                continue;
+            };
+
+            match key.as_str() {
+                "allow" | "disallow" if !value.is_empty() => {
+                    target.rules.push((key == "allow", value.to_owned()));
+                }
+                "crawl-delay" => {
+                    if let Ok(seconds) = value.parse::<f64>()
+                        && let Ok(delay) = Duration::try_from_secs_f64(seconds)
+                    {
+                        target.crawl_delay = Some(delay);
+                    }
+                }
+                _ => {}
+            }
+        }
+
+        if found_ours { ours } else { any }
+    }
+
+    /// Check if a URL is allowed by these rules.
+    ///
+    /// The longest matching rule wins. If an `Allow` and a `Disallow` rule are
+    /// equally long, `Allow` wins.
+    #[must_use]
+    pub fn is_allowed(&self, url: &Url) -> bool {
+        let mut path = url.path().to_owned();
+        if let Some(query) = url.query() {
+            path.push('?');
+            path.push_str(query);
+        }
+
+        self.rules
+            .iter()
+            .filter(|(_, pattern)| pattern_matches(pattern, &path))
+            .max_by_key(|(allow, pattern)| (pattern.len(), *allow))
+            .is_none_or(|(allow, _)| *allow)
+    }
+
+    /// The delay to wait between requests, if any.
+    #[must_use]
+    pub const fn crawl_delay(&self) -> Option<Duration> {
+        self.crawl_delay
+    }
+}
+
+/// Check if a robots.txt path pattern matches a path.
+///
+/// Supports `*` (any sequence of characters) and a trailing `$` (end of path).
+fn pattern_matches(pattern: &str, path: &str) -> bool {
+    let (pattern, anchored) = match pattern.strip_suffix('$') {
+        Some(pattern) => (pattern, true),
+        None => (pattern, false),
+    };
+
+    let mut parts = pattern.split('*');
+    let first = parts.next().unwrap_or("");
+    let Some(mut rest) = path.strip_prefix(first) else {
+        return false;
+    };
+
+    let parts: Vec<&str> = parts.collect();
+    for (i, part) in parts.iter().enumerate() {
+        if anchored && i.checked_add(1) == Some(parts.len()) {
+            // The last part must match the end of the path.
+            return rest.ends_with(part);
+        }
+        let Some(index) = rest.find(part) else {
+            return false;
+        };
+        rest = rest.get(index.saturating_add(part.len())..).unwrap_or("");
+    }
+
+    !anchored || rest.is_empty()
+}
+
+/// Cache of robots.txt rules for each origin, plus crawl delay tracking.
+#[derive(Debug, Default)]
+pub struct RobotsCache {
+    /// Parsed robots.txt for each origin.
+    robots: HashMap<String, Robots>,
+
+    /// When we last made a request to each origin.
+    last_request: HashMap<String, Instant>,
+}
+
+impl RobotsCache {
+    /// Check if `url` may be fetched, fetching robots.txt if necessary.
+    pub async fn is_allowed(
+        &mut self,
+        client: &reqwest::Client,
+        url: &Url,
+    ) -> bool {
+        self.robots(client, url).await.is_allowed(url)
+    }
+
+    /// Wait until the crawl delay for the origin of `url` has passed, then
+    /// record that a request is about to be made.
+    pub async fn wait(&mut self, url: &Url) {
+        let origin = url.origin().ascii_serialization();
+        let delay = self
+            .robots
+            .get(&origin)
+            .and_then(Robots::crawl_delay)
+            .unwrap_or_default();
+        if let Some(next) = self
+            .last_request
+            .get(&origin)
+            .and_then(|last| last.checked_add(delay))
+        {
+            tokio::time::sleep_until(next).await;
+        }
+        self.last_request.insert(origin, Instant::now());
+    }
+
+    /// Get the robots.txt rules for the origin of `url`.
+    async fn robots(&mut self, client: &reqwest::Client, url: &Url) -> &Robots {
+        let origin = url.origin().ascii_serialization();
+        if !self.robots.contains_key(&origin) {
+            let robots = fetch(client, url).await;
+            self.last_request.insert(origin.clone(), Instant::now());
+            self.robots.insert(origin.clone(), robots);
+        }
+        &self.robots[&origin]
+    }
+}
+
+/// Fetch and parse robots.txt for the origin of `url`.
+///
+/// Per RFC 9309, if robots.txt is unavailable (4xx) everything is allowed, and
+/// if it is unreachable (5xx or network error) everything is disallowed.
+async fn fetch(client: &reqwest::Client, url: &Url) -> Robots {
+    // Local files don’t have a robots.txt.
+    if url.scheme() == "file" {
+        return Robots::allow_all();
+    }
+    let Ok(robots_url) = stream::http_url(url).join("/robots.txt") else {
+        return Robots::allow_all();
+    };
+
+    let response = match client.get(robots_url.clone()).send().await {
+        Ok(response) => response,
+        Err(error) => {
+            tracing::warn!("Could not fetch {robots_url}: {error}");
+            return Robots::disallow_all();
+        }
+    };
+
+    let status = response.status();
+    if status.is_client_error() {
+        Robots::allow_all()
+    } else if !status.is_success() {
+        tracing::warn!("Could not fetch {robots_url}: {status}");
+        Robots::disallow_all()
+    } else {
+        match response.text().await {
+            Ok(text) => Robots::parse(&text),
+            Err(error) => {
+                tracing::warn!("Could not read {robots_url}: {error}");
+                Robots::disallow_all()
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use assert2::check;
+
+    /// Convert a `&str` to a `Url`.
+    fn u(s: &'static str) -> Url {
+        Url::parse(s).unwrap()
+    }
+
+    #[test]
+    fn test_parse_and_match() {
+        let robots = Robots::parse(
+            "User-agent: *\n\
+             Disallow: /\n\
+             \n\
+             User-agent: googlebot\n\
+             User-agent: monitorbot\n\
+             Disallow: /private/\n\
+             Allow: /private/public$\n\
+             Disallow: /*.pdf$\n\
+             Crawl-delay: 1.5 # seconds\n",
+        );
+
+        check!(robots.is_allowed(&u("https://a/")));
+        check!(!robots.is_allowed(&u("https://a/private/")));
+        check!(!robots.is_allowed(&u("https://a/private/x")));
+        check!(robots.is_allowed(&u("https://a/private/public")));
+        check!(!robots.is_allowed(&u("https://a/private/public/x")));
+        check!(!robots.is_allowed(&u("https://a/docs/a.pdf")));
+        check!(robots.is_allowed(&u("https://a/docs/a.pdf?x")));
+        check!(robots.crawl_delay() == Some(Duration::from_millis(1500)));
+    }
+
+    #[test]
+    fn test_parse_fallback_to_any() {
+        let robots = Robots::parse("User-agent: *\nDisallow: /admin\n");
+        check!(robots.is_allowed(&u("https://a/")));
+        check!(!robots.is_allowed(&u("https://a/admin/x")));
+        check!(robots.crawl_delay() == None);
+    }
+}
@@ -0,0 +1,227 @@
+//! Restricting when things happen to certain days and times.
+//!
+//! A URL’s `schedule` is a list of windows separated by `;`, e.g.
+//! `"Mon-Fri 09:00-18:00; Sat 10:00-12:00"`. Each window has days, a time
+//! range in local time, or both. The URL is only checked within one of its
+//! windows.
+
+use chrono::{DateTime, Datelike, Local, NaiveTime, Weekday};
+
+/// A daily period of time in local time, e.g. `23:00-07:00`.
+#[derive(Clone, Copy, Debug, Eq, PartialEq, serde::Deserialize)]
+#[serde(try_from = "String")]
+pub struct TimeRange {
+    /// When the period starts.
+    pub start: NaiveTime,
+
+    /// When the period ends.
+    pub end: NaiveTime,
+}
+
+impl TimeRange {
+    /// Check if a time is within the period.
+    #[must_use]
+    pub fn contains(&self, time: NaiveTime) -> bool {
+        if self.wraps() {
+            self.start <= time || time < self.end
+        } else {
+            self.start <= time && time < self.end
+        }
+    }
+
+    /// Check if the period wraps around midnight.
+    fn wraps(&self) -> bool {
+        self.start > self.end
+    }
+}
+
+impl TryFrom<String> for TimeRange {
+    type Error = String;
+
+    fn try_from(value: String) -> Result<Self, Self::Error> {
+        let parse = |time: &str| {
+            NaiveTime::parse_from_str(time.trim(), "%H:%M")
+                .map_err(|error| format!("invalid time {time:?}: {error}"))
+        };
+        let (start, end) = value.split_once('-').ok_or_else(|| {
+            format!("expected a time range like \"23:00-07:00\", got {value:?}")
+        })?;
+        Ok(Self { start: parse(start)?, end: parse(end)? })
+    }
+}
+
+/// When a URL may be checked, e.g. `"Mon-Fri 09:00-18:00; Sat"`.
+#[derive(Clone, Debug, Eq, PartialEq, serde::Deserialize, serde::Serialize)]
+#[serde(try_from = "String", into = "String")]
+pub struct Windows {
+    /// The source, for serializing.
+    source: String,
+
+    /// The parsed windows.
+    windows: Vec<Window>,
+}
+
+impl Windows {
+    /// Check if a time is within any of the windows.
+    #[must_use]
+    pub fn contains(&self, time: DateTime<Local>) -> bool {
+        self.windows
+            .iter()
+            .any(|window| window.contains(time.weekday(), time.time()))
+    }
+}
+
+impl TryFrom<String> for Windows {
+    type Error = String;
+
+    fn try_from(source: String) -> Result<Self, Self::Error> {
+        let windows = source
+            .split(';')
+            .map(Window::parse)
+            .collect::<Result<_, _>>()?;
+        Ok(Self { source, windows })
+    }
+}
+
+impl From<Windows> for String {
+    fn from(windows: Windows) -> Self {
+        windows.source
+    }
+}
+
+/// A window of days and times, e.g. `Mon-Fri 09:00-18:00`.
+#[derive(Clone, Debug, Eq, PartialEq)]
+struct Window {
+    /// The days the window is open, or all days if empty.
+    days: Vec<Weekday>,
+
+    /// The time the window is open, or all day if `None`.
+    ///
+    /// If this wraps around midnight, the window stays open past midnight
+    /// after each day in `days`.
+    hours: Option<TimeRange>,
+}
+
+impl Window {
+    /// Parse a window.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if the days or time range are invalid.
+    fn parse(source: &str) -> Result<Self, String> {
+        let source = source.trim();
+        let (days, hours) = if source.starts_with(|c: char| c.is_ascii_digit())
+        {
+            ("", source)
+        } else {
+            source
+                .split_once(char::is_whitespace)
+                .unwrap_or((source, ""))
+        };
+        if days.is_empty() && hours.trim().is_empty() {
+            return Err(format!(
+                "expected a window like \"Mon-Fri 09:00-18:00\", got \
+                {source:?}"
+            ));
+        }
+
+        Ok(Self {
+            days: parse_days(days)?,
+            hours: if hours.trim().is_empty() {
+                None
+            } else {
+                Some(TimeRange::try_from(hours.to_owned())?)
+            },
+        })
+    }
+
+    /// Check if the window is open on `weekday` at `time`.
+    fn contains(&self, weekday: Weekday, time: NaiveTime) -> bool {
+        let has_day = |day| self.days.is_empty() || self.days.contains(&day);
+        match self.hours {
+            None => has_day(weekday),
+            Some(hours) if hours.wraps() => {
+                (time >= hours.start && has_day(weekday))
+                    || (time < hours.end && has_day(weekday.pred()))
+            }
+            Some(hours) => has_day(weekday) && hours.contains(time),
+        }
+    }
+}
+
+/// Parse days like `Mon-Fri` or `Sat,Sun` into a list of weekdays.
+///
+/// Ranges may wrap around the end of the week, e.g. `Fri-Mon`.
+///
+/// # Errors
+///
+/// Returns an error if a day is invalid.
+fn parse_days(source: &str) -> Result<Vec<Weekday>, String> {
+    let parse = |day: &str| {
+        day.trim()
+            .parse::<Weekday>()
+            .map_err(|_| format!("invalid day {day:?}"))
+    };
+    let mut days = Vec::new();
+    for part in source.split(',').filter(|part| !part.trim().is_empty()) {
+        if let Some((first, last)) = part.split_once('-') {
+            let (mut day, last) = (parse(first)?, parse(last)?);
+            days.push(day);
+            while day != last {
+                day = day.succ();
+                days.push(day);
+            }
+        } else {
+            days.push(parse(part)?);
+        }
+    }
+    Ok(days)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use assert2::check;
+    use chrono::TimeZone;
+
+    /// Make a local time on 2024-05-06, a Monday, plus `days`.
+    fn at(days: u32, hour: u32, minute: u32) -> DateTime<Local> {
+        Local
+            .with_ymd_and_hms(
+                2024,
+                5,
+                6_u32.saturating_add(days),
+                hour,
+                minute,
+                0,
+            )
+            .unwrap()
+    }
+
+    #[test]
+    fn test_windows() {
+        let windows =
+            Windows::try_from("Mon-Fri 09:00-18:00; Sat".to_owned()).unwrap();
+        check!(windows.contains(at(0, 9, 0)));
+        check!(!windows.contains(at(0, 18, 0)));
+        check!(!windows.contains(at(4, 8, 59)));
+        check!(windows.contains(at(5, 3, 0)));
+        check!(!windows.contains(at(6, 12, 0)));
+
+        // Wraps around midnight and the end of the week.
+        let windows =
+            Windows::try_from("Sat-Sun 22:00-02:00".to_owned()).unwrap();
+        check!(windows.contains(at(5, 23, 0)));
+        check!(windows.contains(at(6, 1, 0)));
+        check!(windows.contains(at(7, 1, 0)));
+        check!(!windows.contains(at(5, 1, 0)));
+
+        let windows = Windows::try_from("00:00-06:00".to_owned()).unwrap();
+        check!(windows.contains(at(2, 5, 59)));
+        check!(!windows.contains(at(2, 6, 0)));
+
+        check!(Windows::try_from("Mon-Fry".to_owned()).is_err());
+        check!(Windows::try_from("Mon 9-5".to_owned()).is_err());
+        check!(Windows::try_from("Mon;".to_owned()).is_err());
+    }
+}
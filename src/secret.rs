@@ -0,0 +1,305 @@
+//! Secrets in the configuration file, like tokens and passwords.
+//!
+//! Rather than putting a secret directly in the configuration file, it can be
+//! read from an environment variable, the output of a command, or an
+//! age-encrypted secrets file:
+//!
+//! ```toml
+//! [secrets]
+//! file = "secrets.toml.age"   # Relative to the configuration file.
+//! identity = "key.txt"
+//!
+//! [[url]]
+//! url = "https://example.com/"
+//! headers.Authorization = { env = "EXAMPLE_AUTH" }
+//! headers.X-Token = { command = "pass show example.com/token" }
+//! headers.X-Other = { age = "other_token" }
+//! ```
+//!
+//! Secrets are resolved when the configuration is loaded.
+
+use anyhow::{Context, bail};
+use std::collections::BTreeMap;
+use std::fmt;
+use std::path::{Path, PathBuf};
+use std::process::{Command, Stdio};
+
+/// A secret value in the configuration.
+#[derive(Clone, Eq, Hash, PartialEq, serde::Deserialize, serde::Serialize)]
+#[serde(untagged, deny_unknown_fields)]
+pub enum Secret {
+    /// The secret itself.
+    Plain(String),
+
+    /// Read the secret from an environment variable.
+    Env {
+        /// Name of the environment variable.
+        env: String,
+    },
+
+    /// Run a command with `sh -c` and use the first line of its output.
+    Command {
+        /// The command to run.
+        command: String,
+    },
+
+    /// Look up the secret in the age-encrypted secrets file.
+    Age {
+        /// The key of the secret in the secrets file.
+        age: String,
+    },
+}
+
+impl Secret {
+    /// Get the value of the secret.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if the secret hasn’t been resolved.
+    pub fn expose(&self) -> anyhow::Result<&str> {
+        match self {
+            Self::Plain(value) => Ok(value),
+            _ => bail!("secret {self:?} was not resolved"),
+        }
+    }
+
+    /// Resolve the secret in place so that it can be used with
+    /// [`Self::expose()`].
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if the secret couldn’t be found.
+    pub fn resolve(&mut self, resolver: &mut Resolver) -> anyhow::Result<()> {
+        let value = match self {
+            Self::Plain(_) => return Ok(()),
+            Self::Env { env } => std::env::var(&*env).with_context(|| {
+                format!("could not read environment variable {env:?}")
+            })?,
+            Self::Command { command } => run_command(command)?,
+            Self::Age { age } => resolver.age_secret(age)?,
+        };
+        *self = Self::Plain(value);
+        Ok(())
+    }
+}
+
+impl fmt::Debug for Secret {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::Plain(_) => f.write_str("Secret(***)"),
+            Self::Env { env } => write!(f, "Secret(env = {env:?})"),
+            Self::Command { command } => {
+                write!(f, "Secret(command = {command:?})")
+            }
+            Self::Age { age } => write!(f, "Secret(age = {age:?})"),
+        }
+    }
+}
+
+impl From<String> for Secret {
+    fn from(value: String) -> Self {
+        Self::Plain(value)
+    }
+}
+
+/// Where to find the age-encrypted secrets file.
+#[derive(Clone, Debug, serde::Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct SecretsConfig {
+    /// Age-encrypted TOML file mapping names to secrets.
+    pub file: PathBuf,
+
+    /// Age identity file to decrypt `file` with.
+    pub identity: PathBuf,
+}
+
+/// Resolves secrets, decrypting the secrets file if needed.
+#[derive(Debug, Default)]
+pub struct Resolver {
+    /// The secrets file configuration, with paths made absolute.
+    config: Option<SecretsConfig>,
+
+    /// The decrypted secrets file, once it’s been decrypted.
+    secrets: Option<BTreeMap<String, String>>,
+}
+
+impl Resolver {
+    /// Create a resolver.
+    ///
+    /// Relative paths in `config` are relative to `base_dir`.
+    #[must_use]
+    pub fn new(config: Option<&SecretsConfig>, base_dir: &Path) -> Self {
+        Self {
+            config: config.map(|config| SecretsConfig {
+                file: base_dir.join(&config.file),
+                identity: base_dir.join(&config.identity),
+            }),
+            secrets: None,
+        }
+    }
+
+    /// Look up a secret in the age-encrypted secrets file.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if there is no secrets file, it couldn’t be decrypted,
+    /// or it doesn’t contain the secret.
+    fn age_secret(&mut self, name: &str) -> anyhow::Result<String> {
+        if self.secrets.is_none() {
+            let Some(config) = &self.config else {
+                bail!(
+                    "secret {name:?} is in age file, but [secrets] is not set"
+                );
+            };
+            self.secrets = Some(decrypt_file(config)?);
+        }
+
+        self.secrets
+            .as_ref()
+            .and_then(|secrets| secrets.get(name))
+            .cloned()
+            .with_context(|| format!("secret {name:?} not found in age file"))
+    }
+}
+
+/// Run a command with `sh -c` and return the first line of its output.
+///
+/// # Errors
+///
+/// Returns an error if the command couldn’t be run or failed.
+fn run_command(command: &str) -> anyhow::Result<String> {
+    // Inherit stdin and stderr so that password managers can prompt.
+    let output = Command::new("sh")
+        .arg("-c")
+        .arg(command)
+        .stdin(Stdio::inherit())
+        .stderr(Stdio::inherit())
+        .output()
+        .with_context(|| format!("could not run {command:?}"))?;
+    if !output.status.success() {
+        bail!("{command:?} failed: {}", output.status);
+    }
+
+    let stdout = String::from_utf8(output.stdout)
+        .with_context(|| format!("{command:?} output is not UTF-8"))?;
+    Ok(stdout.lines().next().unwrap_or_default().to_owned())
+}
+
+/// Decrypt the secrets file.
+///
+/// # Errors
+///
+/// Returns an error if the file couldn’t be read, decrypted, or parsed.
+#[cfg(feature = "age")]
+fn decrypt_file(
+    config: &SecretsConfig,
+) -> anyhow::Result<BTreeMap<String, String>> {
+    let identity = std::fs::read(&config.identity).with_context(|| {
+        format!("could not read {}", config.identity.display())
+    })?;
+    let encrypted = std::fs::read(&config.file)
+        .with_context(|| format!("could not read {}", config.file.display()))?;
+    decrypt(&identity, &encrypted).with_context(|| {
+        format!("could not decrypt secrets in {}", config.file.display())
+    })
+}
+
+/// Decrypt the secrets file.
+///
+/// Support for age was not enabled at compile time, so this returns an error.
+///
+/// # Errors
+///
+/// Always returns an error.
+#[cfg(not(feature = "age"))]
+fn decrypt_file(
+    _config: &SecretsConfig,
+) -> anyhow::Result<BTreeMap<String, String>> {
+    bail!("support for age-encrypted secrets is not enabled")
+}
+
+/// Decrypt a TOML secrets file with an identity file.
+///
+/// The secrets file may be binary or ASCII armored.
+///
+/// # Errors
+///
+/// Returns an error if it couldn’t be decrypted or parsed.
+#[cfg(feature = "age")]
+fn decrypt(
+    identity: &[u8],
+    encrypted: &[u8],
+) -> anyhow::Result<BTreeMap<String, String>> {
+    use std::io::Read;
+
+    let identities =
+        age::IdentityFile::from_buffer(identity)?.into_identities()?;
+    let decryptor =
+        age::Decryptor::new(age::armor::ArmoredReader::new(encrypted))?;
+    let mut reader = decryptor.decrypt(
+        identities
+            .iter()
+            .map(|identity| &**identity as &dyn age::Identity),
+    )?;
+    let mut source = String::new();
+    reader.read_to_string(&mut source)?;
+    Ok(toml::from_str(&source)?)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use assert2::check;
+
+    /// Parse a secret from a TOML value.
+    fn parse(source: &str) -> Secret {
+        #[derive(serde::Deserialize)]
+        struct Wrapper {
+            secret: Secret,
+        }
+        toml::from_str::<Wrapper>(&format!("secret = {source}"))
+            .unwrap()
+            .secret
+    }
+
+    #[test]
+    fn test_resolve() {
+        let mut resolver = Resolver::default();
+
+        let mut secret = parse(r#""plain""#);
+        secret.resolve(&mut resolver).unwrap();
+        check!(secret.expose().unwrap() == "plain");
+        check!(format!("{secret:?}") == "Secret(***)");
+
+        let mut secret = parse(r#"{ env = "PATH" }"#);
+        check!(secret.expose().is_err());
+        secret.resolve(&mut resolver).unwrap();
+        check!(secret.expose().unwrap() == std::env::var("PATH").unwrap());
+
+        let mut secret = parse(r#"{ command = "printf 'one\\ntwo\\n'" }"#);
+        secret.resolve(&mut resolver).unwrap();
+        check!(secret.expose().unwrap() == "one");
+
+        let mut secret = parse(r#"{ command = "false" }"#);
+        check!(secret.resolve(&mut resolver).is_err());
+
+        let mut secret = parse(r#"{ age = "token" }"#);
+        check!(secret.resolve(&mut resolver).is_err());
+    }
+
+    #[cfg(feature = "age")]
+    #[test]
+    fn test_decrypt() {
+        use age::secrecy::ExposeSecret;
+
+        let identity = age::x25519::Identity::generate();
+        let encrypted =
+            age::encrypt(&identity.to_public(), b"token = \"abc\"\n").unwrap();
+        let secrets = decrypt(
+            identity.to_string().expose_secret().as_bytes(),
+            &encrypted,
+        )
+        .unwrap();
+        check!(secrets["token"] == "abc");
+    }
+}
@@ -0,0 +1,320 @@
+//! Detecting pages that return success but aren’t really the page.
+//!
+//! Many sites return `200 OK` for missing pages, login walls, or (after a
+//! domain expires) a parking page. Diffing those against the real page just
+//! produces a giant, useless diff, so they’re reported as a soft error
+//! instead.
+
+use scraper::{ElementRef, Html, Node, Selector};
+use std::fmt;
+use url::Url;
+
+use crate::response::Response;
+
+/// Phrases in the title or main heading of a “not found” page.
+static NOT_FOUND_PHRASES: &[&str] = &[
+    "404",
+    "not found",
+    "page doesn’t exist",
+    "page doesn't exist",
+    "page does not exist",
+    "no longer available",
+    "could not be found",
+];
+
+/// Phrases in the title of a login page.
+static LOGIN_PHRASES: &[&str] = &[
+    "log in",
+    "login",
+    "sign in",
+    "signin",
+    "authentication required",
+];
+
+/// Phrases on a parked domain page.
+static PARKED_PHRASES: &[&str] = &[
+    "domain is for sale",
+    "domain may be for sale",
+    "buy this domain",
+    "domain is parked",
+    "parked free",
+    "parked domain",
+    "domain has expired",
+];
+
+/// Domains that parked pages redirect to.
+static PARKED_DOMAINS: &[&str] = &[
+    "above.com",
+    "afternic.com",
+    "bodis.com",
+    "dan.com",
+    "hugedomains.com",
+    "parkingcrew.net",
+    "sedo.com",
+    "sedoparking.com",
+];
+
+/// What kind of soft error a page looks like.
+#[derive(
+    Clone, Copy, Debug, Eq, PartialEq, serde::Serialize, serde::Deserialize,
+)]
+#[serde(rename_all = "kebab-case")]
+pub enum SoftError {
+    /// A “not found” page.
+    NotFound,
+
+    /// A page asking to log in.
+    LoginWall,
+
+    /// A domain parking page.
+    Parked,
+}
+
+impl fmt::Display for SoftError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(match self {
+            Self::NotFound => "a “not found” page",
+            Self::LoginWall => "a login page",
+            Self::Parked => "a parked domain",
+        })
+    }
+}
+
+/// Configuration for soft error detection.
+///
+/// Phrases and domains are used in addition to the built in ones.
+#[derive(Clone, Debug, serde::Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct SoftErrorConfig {
+    /// Pages with more text than this are never considered “not found” or
+    /// login pages based only on their body text.
+    pub max_text_length: usize,
+
+    /// Phrases in the title or main heading of a “not found” page.
+    pub not_found_phrases: Vec<String>,
+
+    /// Phrases in the title of a login page.
+    pub login_phrases: Vec<String>,
+
+    /// Phrases on a parked domain page.
+    pub parked_phrases: Vec<String>,
+
+    /// Domains that parked pages redirect to.
+    pub parked_domains: Vec<String>,
+}
+
+impl Default for SoftErrorConfig {
+    fn default() -> Self {
+        Self {
+            max_text_length: 1000,
+            not_found_phrases: Vec::new(),
+            login_phrases: Vec::new(),
+            parked_phrases: Vec::new(),
+            parked_domains: Vec::new(),
+        }
+    }
+}
+
+/// Check if a successful HTML response looks like a soft error.
+///
+/// # Errors
+///
+/// Returns an error if the response couldn’t be decoded.
+pub fn detect(
+    config: &SoftErrorConfig,
+    response: &Response,
+) -> anyhow::Result<Option<SoftError>> {
+    if !response.status.is_success() || !response.is_html()? {
+        return Ok(None);
+    }
+
+    let html = Html::parse_document(&response.text()?);
+    let title = select_text(&html, "title");
+    let heading = select_text(&html, "h1");
+    let text = visible_text(&html);
+    let tiny = text.chars().count() <= config.max_text_length;
+
+    if contains_any(&text, PARKED_PHRASES, &config.parked_phrases)
+        || meta_refresh(&html, &response.url).is_some_and(|url| {
+            url.host_str().is_some_and(|host| {
+                is_parked_domain(host, &config.parked_domains)
+            })
+        })
+    {
+        return Ok(Some(SoftError::Parked));
+    }
+
+    let not_found = |text: &str| {
+        contains_any(text, NOT_FOUND_PHRASES, &config.not_found_phrases)
+    };
+    if not_found(&title) || not_found(&heading) || (tiny && not_found(&text)) {
+        return Ok(Some(SoftError::NotFound));
+    }
+
+    let has_password = html
+        .select(&selector("input[type=password i]"))
+        .next()
+        .is_some();
+    if has_password
+        && (tiny || contains_any(&title, LOGIN_PHRASES, &config.login_phrases))
+    {
+        return Ok(Some(SoftError::LoginWall));
+    }
+
+    Ok(None)
+}
+
+/// Parse a hard coded selector.
+fn selector(source: &str) -> Selector {
+    Selector::parse(source).unwrap()
+}
+
+/// Get the text of the first element matching `source`.
+fn select_text(html: &Html, source: &str) -> String {
+    html.select(&selector(source))
+        .next()
+        .map(|element| element.text().collect())
+        .unwrap_or_default()
+}
+
+/// Get the text of the body, excluding scripts and styles, with whitespace
+/// collapsed.
+fn visible_text(html: &Html) -> String {
+    let Some(body) = html.select(&selector("body")).next() else {
+        return String::new();
+    };
+
+    let mut text = String::new();
+    for node in body.descendants() {
+        let Node::Text(node_text) = node.value() else {
+            continue;
+        };
+        let hidden =
+            node.parent()
+                .and_then(ElementRef::wrap)
+                .is_some_and(|parent| {
+                    matches!(
+                        parent.value().name(),
+                        "script" | "style" | "noscript"
+                    )
+                });
+        if !hidden {
+            for word in node_text.split_whitespace() {
+                if !text.is_empty() {
+                    text.push(' ');
+                }
+                text.push_str(word);
+            }
+        }
+    }
+    text
+}
+
+/// Get the URL a `<meta http-equiv="refresh">` tag redirects to.
+fn meta_refresh(html: &Html, base_url: &Url) -> Option<Url> {
+    let content = html
+        .select(&selector("meta[http-equiv=refresh i]"))
+        .next()?
+        .attr("content")?;
+    let (_, target) = content.split_once(';')?;
+    let target = target.trim_start();
+    let target = target
+        .get(..4)
+        .filter(|prefix| prefix.eq_ignore_ascii_case("url="))
+        .and_then(|_| target.get(4..))
+        .unwrap_or(target)
+        .trim_matches(|c: char| c == '\'' || c == '"' || c.is_whitespace());
+    base_url.join(target).ok()
+}
+
+/// Check if `host` is or is in one of the parking domains.
+fn is_parked_domain(host: &str, extra: &[String]) -> bool {
+    let host = host.to_ascii_lowercase();
+    PARKED_DOMAINS
+        .iter()
+        .copied()
+        .chain(extra.iter().map(String::as_str))
+        .any(|domain| {
+            host == domain
+                || host
+                    .strip_suffix(domain)
+                    .is_some_and(|prefix| prefix.ends_with('.'))
+        })
+}
+
+/// Check if `text` contains any of the phrases, ignoring case.
+fn contains_any(text: &str, phrases: &[&str], extra: &[String]) -> bool {
+    let text = text.to_lowercase();
+    phrases
+        .iter()
+        .copied()
+        .chain(extra.iter().map(String::as_str))
+        .any(|phrase| text.contains(&phrase.to_lowercase()))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use assert2::check;
+
+    /// Detect a soft error in an HTML page with the default configuration.
+    fn detect_html(html: &str) -> Option<SoftError> {
+        let response = Response {
+            url: "https://example.com/page".parse().unwrap(),
+            version: http::Version::HTTP_11,
+            status: http::StatusCode::OK,
+            headers: http::HeaderMap::new(),
+            body: html.to_owned().into(),
+            spooled: None,
+            certificate_sha256: None,
+            peer_address: None,
+        };
+        detect(&SoftErrorConfig::default(), &response).unwrap()
+    }
+
+    #[test]
+    fn test_detect() {
+        check!(detect_html("<title>Changelog</title><h1>Changes</h1>") == None);
+        check!(
+            detect_html("<title>Page Not Found – Example</title>")
+                == Some(SoftError::NotFound)
+        );
+        check!(
+            detect_html("<h1>Oops</h1><p>That page doesn’t exist.</p>")
+                == Some(SoftError::NotFound)
+        );
+        check!(
+            detect_html(&format!(
+                "<h1>Bugs</h1><p>Fixed “page not found” error.</p><p>{}</p>",
+                "More text. ".repeat(100),
+            )) == None
+        );
+        check!(
+            detect_html(
+                "<title>Sign in</title><form><input type=PASSWORD></form>"
+            ) == Some(SoftError::LoginWall)
+        );
+        check!(
+            detect_html(
+                "<meta http-equiv=Refresh \
+                content=\"0; URL='https://ww1.sedoparking.com/example.com'\">"
+            ) == Some(SoftError::Parked)
+        );
+        check!(
+            detect_html("<p>This domain is for sale!</p>")
+                == Some(SoftError::Parked)
+        );
+        check!(detect_html("<script>'not found'</script><p>Hello</p>") == None);
+    }
+
+    #[test]
+    fn test_is_parked_domain() {
+        check!(is_parked_domain("sedo.com", &[]));
+        check!(is_parked_domain("WWW.Sedo.com", &[]));
+        check!(!is_parked_domain("notsedo.com", &[]));
+        check!(is_parked_domain(
+            "parked.example",
+            &["parked.example".into()]
+        ));
+    }
+}
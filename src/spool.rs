@@ -0,0 +1,145 @@
+//! Writing large response bodies to disk instead of keeping them in memory.
+//!
+//! Bodies larger than `--max-body-memory` are written to a temporary file in
+//! the state directory as they’re downloaded, and hashed at the same time.
+//! They’re compared by size and hash like binary content, and the file is
+//! moved into the store of bodies when the response is saved.
+
+use std::fs;
+use std::io;
+use std::path::{Path, PathBuf};
+use std::sync::atomic::{AtomicU64, Ordering};
+
+/// Default maximum size of a body to keep in memory.
+pub const DEFAULT_MAX_MEMORY: usize = 16_000_000;
+
+/// Counter to make temporary file names unique within the process.
+static COUNTER: AtomicU64 = AtomicU64::new(0);
+
+/// Where and when to write bodies to disk.
+#[derive(Clone, Debug)]
+pub struct Spool {
+    /// Directory for temporary files. It’s created when it’s needed.
+    pub dir: PathBuf,
+
+    /// Maximum size of a body in bytes to keep in memory.
+    pub max_memory: usize,
+}
+
+impl Spool {
+    /// Create a temporary file to write a body to.
+    ///
+    /// The file is deleted when the returned [`Spooled`] is dropped, unless
+    /// it’s been moved with [`Spooled::persist()`].
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if the file couldn’t be created.
+    pub fn create(&self) -> io::Result<(fs::File, Spooled)> {
+        fs::create_dir_all(&self.dir)?;
+        let path = self.dir.join(format!(
+            "body-{}-{}",
+            std::process::id(),
+            COUNTER.fetch_add(1, Ordering::Relaxed),
+        ));
+        let file = fs::File::create(&path)?;
+        Ok((
+            file,
+            Spooled { path, len: 0, sha256: String::new(), temporary: true },
+        ))
+    }
+}
+
+/// A body stored in a file rather than in memory.
+#[derive(Debug)]
+pub struct Spooled {
+    /// The file containing the body.
+    path: PathBuf,
+
+    /// The size of the body in bytes.
+    pub len: usize,
+
+    /// SHA-256 of the body.
+    pub sha256: String,
+
+    /// Whether to delete the file when this is dropped.
+    temporary: bool,
+}
+
+impl Spooled {
+    /// Refer to a body that’s already stored, e.g. in the state directory.
+    ///
+    /// The file won’t be deleted.
+    #[must_use]
+    pub const fn stored(path: PathBuf, len: usize, sha256: String) -> Self {
+        Self { path, len, sha256, temporary: false }
+    }
+
+    /// Get the path to the file containing the body.
+    #[must_use]
+    pub fn path(&self) -> &Path {
+        &self.path
+    }
+
+    /// Move the body to `path`, or copy it if it isn’t temporary.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if the file couldn’t be moved or copied.
+    pub fn persist(&self, path: &Path) -> io::Result<()> {
+        if let Some(parent) = path.parent() {
+            fs::create_dir_all(parent)?;
+        }
+        if self.temporary {
+            fs::rename(&self.path, path)
+        } else {
+            fs::copy(&self.path, path).map(|_| ())
+        }
+    }
+}
+
+impl Drop for Spooled {
+    fn drop(&mut self) {
+        if self.temporary {
+            // The file is gone if it was persisted.
+            let _ = fs::remove_file(&self.path);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use assert2::check;
+    use std::io::Write as _;
+
+    #[test]
+    fn test_spool() {
+        let dir = std::env::temp_dir()
+            .join(format!("monitorbot-{}-spool", std::process::id()));
+        let _ = fs::remove_dir_all(&dir);
+        let spool = Spool { dir: dir.join("tmp"), max_memory: 10 };
+
+        let (mut file, spooled) = spool.create().unwrap();
+        file.write_all(b"discarded").unwrap();
+        let path = spooled.path().to_owned();
+        check!(path.exists());
+        drop(spooled);
+        check!(!path.exists());
+
+        let (mut file, spooled) = spool.create().unwrap();
+        file.write_all(b"kept").unwrap();
+        let kept = dir.join("bodies").join("kept");
+        spooled.persist(&kept).unwrap();
+        drop(spooled);
+        check!(fs::read(&kept).unwrap() == b"kept");
+
+        let stored = Spooled::stored(kept.clone(), 4, String::new());
+        stored.persist(&dir.join("copy")).unwrap();
+        drop(stored);
+        check!(kept.exists());
+        check!(fs::read(dir.join("copy")).unwrap() == b"kept");
+
+        fs::remove_dir_all(dir).unwrap();
+    }
+}
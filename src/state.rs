@@ -0,0 +1,1280 @@
+//! Storing state between runs.
+//!
+//! The state directory contains:
+//!
+//!   * `<url>.ron`: the [`History`] of responses for a URL, where `<url>` is
+//!     the result of [`fs_safe_url()`]. If the URL redirected, this is a link
+//!     to the file for the final URL on UNIX.
+//!   * `redirects.ron`: the [`Redirects`] index of which final URL each request
+//!     URL redirected to. This, not the links, is what’s used to find the
+//!     history for a URL that redirected.
+//!   * `bodies/<sha256>`: response bodies, stored by the SHA-256 of their
+//!     content so that identical bodies are only stored once.
+
+use anyhow::Context;
+use bytes::Bytes;
+use chrono::{DateTime, TimeDelta, Utc};
+use sha2::{Digest, Sha256};
+use std::collections::BTreeMap;
+use std::fmt::Write as _;
+use std::fs;
+use std::io::{self, Write};
+use std::net::IpAddr;
+use std::path::{Path, PathBuf};
+use std::sync::Arc;
+use std::time::Duration;
+use url::Url;
+
+use crate::response::Response;
+use crate::soft_error::SoftError;
+use crate::spool::Spooled;
+use crate::values::Value;
+
+/// The history of responses for a URL.
+#[derive(Clone, Debug, Default, serde::Serialize, serde::Deserialize)]
+pub struct History {
+    /// Every response fetched, oldest first.
+    pub revisions: Vec<Revision>,
+
+    /// When the URL was last checked successfully, including checks where it
+    /// was not modified.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub checked_at: Option<DateTime<Utc>>,
+
+    /// Index in `revisions` of the revision pinned as the baseline, if any.
+    ///
+    /// New responses are compared to the baseline instead of the previous
+    /// response.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub baseline: Option<usize>,
+
+    /// How often checks of the URL have failed.
+    #[serde(default, skip_serializing_if = "Reliability::is_empty")]
+    pub reliability: Reliability,
+
+    /// When changes to the URL were last acknowledged with `review`.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub reviewed_at: Option<DateTime<Utc>>,
+}
+
+/// Counts of successful and failed checks of a URL.
+///
+/// A check fails if the URL couldn’t be fetched at all, e.g. because of a
+/// timeout or a refused connection. Responses that fail expectations still
+/// count as successful, since the site was reachable.
+#[derive(
+    Clone,
+    Copy,
+    Debug,
+    Default,
+    Eq,
+    PartialEq,
+    serde::Serialize,
+    serde::Deserialize,
+)]
+pub struct Reliability {
+    /// Total number of checks.
+    pub checks: u64,
+
+    /// Number of checks that failed.
+    pub errors: u64,
+
+    /// Number of checks that have failed in a row, up to the latest one.
+    pub consecutive_errors: u64,
+
+    /// When the current run of failed checks started, if the latest check
+    /// failed.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub failing_since: Option<DateTime<Utc>>,
+
+    /// When the latest failed check was, if the latest check failed.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub last_failure: Option<DateTime<Utc>>,
+
+    /// When the URL was disabled for failing too long; see
+    /// [`crate::backoff`]. It’s enabled again by a successful check.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub disabled_at: Option<DateTime<Utc>>,
+}
+
+impl Reliability {
+    /// Check if no checks have been recorded.
+    #[must_use]
+    pub const fn is_empty(&self) -> bool {
+        self.checks == 0
+    }
+
+    /// Record the result of a check made at `now`.
+    pub const fn record(&mut self, success: bool, now: DateTime<Utc>) {
+        self.checks = self.checks.saturating_add(1);
+        if success {
+            self.consecutive_errors = 0;
+            self.failing_since = None;
+            self.last_failure = None;
+            self.disabled_at = None;
+        } else {
+            self.errors = self.errors.saturating_add(1);
+            self.consecutive_errors = self.consecutive_errors.saturating_add(1);
+            if self.failing_since.is_none() {
+                self.failing_since = Some(now);
+            }
+            self.last_failure = Some(now);
+        }
+    }
+
+    /// Get the percentage of checks that succeeded, if there were any.
+    #[must_use]
+    #[expect(clippy::cast_precision_loss, reason = "only used for display")]
+    pub fn uptime(&self) -> Option<f64> {
+        if self.is_empty() {
+            return None;
+        }
+        let successes = self.checks.saturating_sub(self.errors);
+        Some(successes as f64 * 100.0 / self.checks as f64)
+    }
+}
+
+/// The final URL that each request URL redirected to when it was last checked.
+///
+/// Several request URLs may redirect to the same final URL, in which case
+/// they share its history.
+#[derive(
+    Clone, Debug, Default, Eq, PartialEq, serde::Serialize, serde::Deserialize,
+)]
+pub struct Redirects {
+    /// Final URLs by request URL. This is sorted so that the file only changes
+    /// when a redirect does.
+    pub urls: BTreeMap<Url, Url>,
+}
+
+impl Redirects {
+    /// Get the URL whose history should be used for `url`.
+    #[must_use]
+    pub fn resolve<'a>(&'a self, url: &'a Url) -> &'a Url {
+        self.urls.get(url).unwrap_or(url)
+    }
+
+    /// Get the request URLs that redirected to `final_url`, in order.
+    pub fn requests_for<'a>(
+        &'a self,
+        final_url: &'a Url,
+    ) -> impl Iterator<Item = &'a Url> {
+        self.urls
+            .iter()
+            .filter(move |(_, to)| *to == final_url)
+            .map(|(from, _)| from)
+    }
+}
+
+impl History {
+    /// Get the most recent revision.
+    #[must_use]
+    pub fn latest(&self) -> Option<&Revision> {
+        self.revisions.last()
+    }
+
+    /// Add a new revision.
+    ///
+    /// If the content and outcome are the same as the latest revision, the
+    /// latest revision is updated to say when it was last seen instead, so
+    /// that checking a page that doesn’t change doesn’t grow its history.
+    /// Its body and note are kept, but the headers and connection details
+    /// are taken from the new revision.
+    ///
+    /// Returns whether a revision was added, i.e. whether its body needs to
+    /// be saved.
+    pub fn push(&mut self, revision: Revision) -> bool {
+        let Some(latest) = self.revisions.last_mut() else {
+            self.revisions.push(revision);
+            return true;
+        };
+        if !latest.same_as(&revision) {
+            self.revisions.push(revision);
+            return true;
+        }
+        *latest = Revision {
+            fetched_at: latest.fetched_at,
+            last_seen: Some(revision.fetched_at),
+            body_sha256: std::mem::take(&mut latest.body_sha256),
+            note: latest.note.take(),
+            ..revision
+        };
+        false
+    }
+
+    /// Get the most recent revision that wasn’t a soft error.
+    #[must_use]
+    pub fn latest_valid(&self) -> Option<&Revision> {
+        self.revisions
+            .iter()
+            .rev()
+            .find(|revision| revision.soft_error.is_none())
+    }
+
+    /// Find when the page was removed, if the latest revision that wasn’t a
+    /// soft error says it was (404 Not Found or 410 Gone).
+    #[must_use]
+    pub fn removal(&self) -> Option<Removal> {
+        let mut valid = self
+            .revisions
+            .iter()
+            .rev()
+            .filter(|revision| revision.soft_error.is_none())
+            .peekable();
+        let mut removed_at = None;
+        while let Some(revision) =
+            valid.next_if(|revision| revision.is_removed())
+        {
+            removed_at = Some(revision.fetched_at);
+        }
+        Some(Removal { removed_at: removed_at?, before: valid.next().cloned() })
+    }
+
+    /// Find when content was last fetched, if it was within `window` before
+    /// `now`.
+    #[must_use]
+    pub fn seen_within(
+        &self,
+        content_sha256: &str,
+        now: DateTime<Utc>,
+        window: Duration,
+    ) -> Option<DateTime<Utc>> {
+        let since =
+            now.checked_sub_signed(TimeDelta::from_std(window).ok()?)?;
+        self.revisions
+            .iter()
+            .rev()
+            .take_while(|revision| revision.last_fetched() >= since)
+            .find(|revision| revision.content_sha256 == content_sha256)
+            .map(Revision::last_fetched)
+    }
+
+    /// Find when the content was first fetched in the run of revisions, up to
+    /// the latest one, that had this content.
+    ///
+    /// Errors are skipped, so they don’t interrupt the run. Returns `None` if
+    /// the latest revision that wasn’t an error had different content.
+    #[must_use]
+    pub fn unchanged_since(
+        &self,
+        content_sha256: &str,
+    ) -> Option<DateTime<Utc>> {
+        self.revisions
+            .iter()
+            .rev()
+            .filter(|revision| !revision.is_error())
+            .take_while(|revision| revision.content_sha256 == content_sha256)
+            .last()
+            .map(|revision| revision.fetched_at)
+    }
+
+    /// Find the revisions where the content changed, with their numbers.
+    ///
+    /// Revisions are numbered from 1 for the oldest, which is always included.
+    #[must_use]
+    pub fn changes(&self) -> Vec<(usize, &Revision)> {
+        let mut previous: Option<&Revision> = None;
+        let mut changes = Vec::new();
+        for (rev, revision) in (1..).zip(&self.revisions) {
+            if previous.is_none_or(|previous| {
+                previous.content_sha256 != revision.content_sha256
+            }) {
+                changes.push((rev, revision));
+            }
+            previous = Some(revision);
+        }
+        changes
+    }
+
+    /// Get the revision pinned as the baseline, if any.
+    #[must_use]
+    pub fn baseline(&self) -> Option<&Revision> {
+        self.revisions.get(self.baseline?)
+    }
+
+    /// Get the index in `revisions` of a revision number, where 1 is the
+    /// oldest revision, or of the latest revision if `rev` is `None`.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if the revision doesn’t exist.
+    pub fn index(&self, rev: Option<usize>) -> anyhow::Result<usize> {
+        let count = self.revisions.len();
+        match rev {
+            Some(0) => anyhow::bail!("Revisions start at 1"),
+            Some(rev) if rev <= count => Ok(rev.saturating_sub(1)),
+            None if count > 0 => Ok(count.saturating_sub(1)),
+            _ => anyhow::bail!(
+                "Revision {} does not exist; there are {count} revisions",
+                rev.unwrap_or(count),
+            ),
+        }
+    }
+
+    /// When the URL was last checked successfully.
+    ///
+    /// Older state files don’t have `checked_at`, so this falls back to the
+    /// most recent successful revision.
+    #[must_use]
+    pub fn last_checked(&self) -> Option<DateTime<Utc>> {
+        self.checked_at.or_else(|| {
+            self.revisions
+                .iter()
+                .rev()
+                .find(|revision| revision.status.is_success())
+                .map(|revision| revision.fetched_at)
+        })
+    }
+}
+
+/// When a URL’s page was removed, and what it was before.
+#[derive(Clone, Debug)]
+pub struct Removal {
+    /// When the page first said it was removed.
+    pub removed_at: DateTime<Utc>,
+
+    /// The last response before the page was removed, if any.
+    pub before: Option<Revision>,
+}
+
+/// A response as stored in the state directory.
+///
+/// The body is stored separately; see [`State::load_body()`].
+#[derive(Clone, Debug, serde::Serialize, serde::Deserialize)]
+pub struct Revision {
+    /// When the response was first fetched.
+    pub fetched_at: DateTime<Utc>,
+
+    /// When the same response was last fetched, if it was fetched again
+    /// after `fetched_at`; see [`History::push()`].
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub last_seen: Option<DateTime<Utc>>,
+
+    /// The URL that actually produced the response.
+    pub url: Url,
+
+    /// The HTTP version of the response.
+    #[serde(with = "http_serde::version")]
+    pub version: http::Version,
+
+    /// The HTTP status code of the response.
+    #[serde(with = "http_serde::status_code")]
+    pub status: http::StatusCode,
+
+    /// The HTTP headers of the response.
+    #[serde(with = "http_serde::header_map")]
+    pub headers: http::HeaderMap,
+
+    /// SHA-256 of the body, which is what it’s stored under.
+    pub body_sha256: String,
+
+    /// SHA-256 of the content that’s diffed, e.g. the text of a PDF or the
+    /// rendered part of a page that’s monitored, rather than the raw body.
+    ///
+    /// If this matches between revisions there’s no need to render or diff.
+    pub content_sha256: String,
+
+    /// Addresses the host resolved to when the response was fetched.
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub addresses: Vec<IpAddr>,
+
+    /// SHA-256 of the server’s TLS certificate, if the URL uses HTTPS.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub certificate_sha256: Option<String>,
+
+    /// The address of the server that sent the response, if known.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub peer_address: Option<IpAddr>,
+
+    /// The kind of soft error the response looked like, if any.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub soft_error: Option<SoftError>,
+
+    /// Expectations from the configuration that the response failed.
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub failures: Vec<String>,
+
+    /// SHA-256 of the rendered content seen through each vantage point that
+    /// saw something different.
+    #[serde(default, skip_serializing_if = "BTreeMap::is_empty")]
+    pub vantages: BTreeMap<String, String>,
+
+    /// Values of the request headers named by the response’s `Vary`, so
+    /// that it’s only reused from the cache for requests that match.
+    #[serde(default, skip_serializing_if = "BTreeMap::is_empty")]
+    pub vary: BTreeMap<String, String>,
+
+    /// Values extracted by the URL’s `extract` rules.
+    #[serde(default, skip_serializing_if = "BTreeMap::is_empty")]
+    pub values: BTreeMap<String, Value>,
+
+    /// A note about the revision, added with `monitorbot note`.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub note: Option<String>,
+}
+
+impl Revision {
+    /// Create a revision from a response that was just fetched.
+    ///
+    /// Bodies that were too large to keep in memory are compared as a whole,
+    /// so `content` is ignored for them.
+    pub fn new(response: &Response, content: &[u8]) -> Self {
+        let body_sha256 = response.body_sha256();
+        let content_sha256 = if response.spooled.is_some() {
+            body_sha256.clone()
+        } else {
+            sha256_hex(content)
+        };
+        Self {
+            fetched_at: Utc::now(),
+            last_seen: None,
+            url: response.url.clone(),
+            version: response.version,
+            status: response.status,
+            headers: response.headers.clone(),
+            body_sha256,
+            content_sha256,
+            addresses: Vec::new(),
+            certificate_sha256: response.certificate_sha256.clone(),
+            peer_address: response.peer_address,
+            soft_error: None,
+            failures: Vec::new(),
+            vantages: BTreeMap::new(),
+            vary: BTreeMap::new(),
+            values: BTreeMap::new(),
+            note: None,
+        }
+    }
+
+    /// When the response was last fetched.
+    #[must_use]
+    pub fn last_fetched(&self) -> DateTime<Utc> {
+        self.last_seen.unwrap_or(self.fetched_at)
+    }
+
+    /// Whether a new revision is the same as this one apart from when it was
+    /// fetched and how it was served.
+    fn same_as(&self, other: &Self) -> bool {
+        self.content_sha256 == other.content_sha256
+            && self.url == other.url
+            && self.status == other.status
+            && self.soft_error == other.soft_error
+            && self.failures == other.failures
+            && self.vantages == other.vantages
+            && self.values == other.values
+    }
+
+    /// Whether the response was an error, i.e. it had an error status or
+    /// looked like a soft error.
+    #[must_use]
+    pub fn is_error(&self) -> bool {
+        self.status.is_client_error()
+            || self.status.is_server_error()
+            || self.soft_error.is_some()
+    }
+
+    /// Whether the response said the page was removed.
+    #[must_use]
+    pub const fn is_removed(&self) -> bool {
+        crate::response::is_removed(self.status)
+    }
+}
+
+/// The state directory.
+#[derive(Clone, Debug)]
+pub struct State {
+    /// Path to the directory.
+    dir: PathBuf,
+}
+
+impl State {
+    /// Use the state directory at `dir`.
+    #[must_use]
+    pub const fn new(dir: PathBuf) -> Self {
+        Self { dir }
+    }
+
+    /// Get the path to the state directory.
+    #[must_use]
+    pub fn dir(&self) -> &Path {
+        &self.dir
+    }
+
+    /// Create the state directory if it doesn’t exist.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if the directory could not be created.
+    pub fn create(&self) -> io::Result<()> {
+        fs::DirBuilder::new()
+            .recursive(true)
+            .create(self.dir.join("bodies"))
+    }
+
+    /// Get the file name for a URL’s history within the state directory.
+    #[must_use]
+    pub fn history_file_name(url: &Url) -> String {
+        let mut file_name = fs_safe_url(url);
+        file_name.push_str(".ron");
+        file_name
+    }
+
+    /// Get the path to a URL’s history.
+    #[must_use]
+    pub fn history_path(&self, url: &Url) -> PathBuf {
+        self.dir.join(Self::history_file_name(url))
+    }
+
+    /// Get the path to a stored body.
+    #[must_use]
+    pub fn body_path(&self, sha256: &str) -> PathBuf {
+        self.dir.join("bodies").join(sha256)
+    }
+
+    /// Get the path to the [`Redirects`] index.
+    #[must_use]
+    pub fn redirects_path(&self) -> PathBuf {
+        self.dir.join("redirects.ron")
+    }
+
+    /// Get the directory for temporary files, e.g. bodies being downloaded.
+    #[must_use]
+    pub fn tmp_dir(&self) -> PathBuf {
+        self.dir.join("tmp")
+    }
+
+    /// Get the path to save the full text of a notification about a URL.
+    #[must_use]
+    pub fn diff_path(&self, url: &Url, detected_at: DateTime<Utc>) -> PathBuf {
+        self.dir.join("diffs").join(format!(
+            "{}-{}.diff",
+            fs_safe_url(url),
+            detected_at.format("%Y%m%dT%H%M%SZ"),
+        ))
+    }
+
+    /// Save the full text of a notification.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if the file could not be written.
+    pub fn save_diff(&self, path: &Path, text: &str) -> io::Result<()> {
+        fs::create_dir_all(self.dir.join("diffs"))?;
+        atomic_write(path, text.as_bytes())
+    }
+
+    /// Load a URL’s history, if it exists.
+    ///
+    /// If the URL redirected when it was last checked, this loads the history
+    /// of the URL it redirected to.
+    ///
+    /// This also understands the old format, which stored a single
+    /// [`Response`] including its body. If it finds a file in the old format,
+    /// it converts it to a [`History`] and stores the body separately (unless
+    /// `read_only` is set).
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if the history could not be read or parsed.
+    pub fn load_history(
+        &self,
+        url: &Url,
+        read_only: bool,
+    ) -> anyhow::Result<Option<History>> {
+        let redirects = self.load_redirects()?;
+        self.load_final_history(redirects.resolve(url), read_only)
+    }
+
+    /// Load the history stored for a URL without looking up redirects, e.g.
+    /// for the final URL of a response.
+    ///
+    /// See [`Self::load_history()`].
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if the history could not be read or parsed.
+    pub fn load_final_history(
+        &self,
+        url: &Url,
+        read_only: bool,
+    ) -> anyhow::Result<Option<History>> {
+        let path = self.history_path(url);
+        let source = match fs::read(&path) {
+            Ok(source) => source,
+            Err(error) if error.kind() == io::ErrorKind::NotFound => {
+                return Ok(None);
+            }
+            Err(error) => return Err(error.into()),
+        };
+
+        let error = match ron::de::from_bytes(&source) {
+            Ok(history) => return Ok(Some(history)),
+            Err(error) => error,
+        };
+
+        // Try the old format.
+        let Ok(response) = ron::de::from_bytes::<Response>(&source) else {
+            return Err(error).with_context(|| {
+                format!("could not parse {}", path.display())
+            });
+        };
+
+        let mut revision = Revision::new(&response, &response.body);
+        revision.fetched_at = fs::metadata(&path)?.modified()?.into();
+        if !read_only {
+            self.save_body(&response.body)?;
+        }
+        Ok(Some(History {
+            revisions: vec![revision],
+            checked_at: None,
+            baseline: None,
+            reliability: Reliability::default(),
+            reviewed_at: None,
+        }))
+    }
+
+    /// Save a URL’s history atomically.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if the history could not be serialized or written.
+    pub fn save_history(
+        &self,
+        url: &Url,
+        history: &History,
+    ) -> anyhow::Result<()> {
+        let contents = ron::ser::to_string_pretty(
+            history,
+            ron::ser::PrettyConfig::default(),
+        )?;
+        atomic_write(&self.history_path(url), contents.as_bytes())?;
+        Ok(())
+    }
+
+    /// Load a body by its SHA-256.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if the body could not be read.
+    pub fn load_body(&self, sha256: &str) -> io::Result<Bytes> {
+        fs::read(self.body_path(sha256)).map(Bytes::from)
+    }
+
+    /// Store a body by its SHA-256, unless it’s already stored.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if the body could not be written.
+    pub fn save_body(&self, body: &[u8]) -> io::Result<()> {
+        let path = self.body_path(&sha256_hex(body));
+        if path.exists() {
+            return Ok(());
+        }
+        atomic_write(&path, body)
+    }
+
+    /// Store the body of a response by its SHA-256, unless it’s already
+    /// stored.
+    ///
+    /// Bodies that were written to disk because they were too large to keep in
+    /// memory are moved into place.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if the body could not be written.
+    pub fn save_response_body(&self, response: &Response) -> io::Result<()> {
+        let Some(spooled) = &response.spooled else {
+            return self.save_body(&response.body);
+        };
+        let path = self.body_path(&spooled.sha256);
+        if path.exists() {
+            return Ok(());
+        }
+        spooled.persist(&path)
+    }
+
+    /// Load a stored revision along with its body as a [`Response`].
+    ///
+    /// Bodies larger than `max_memory` bytes are left on disk; see
+    /// [`Response::spooled`].
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if the body could not be read.
+    pub fn load_response(
+        &self,
+        revision: &Revision,
+        max_memory: usize,
+    ) -> io::Result<Response> {
+        let path = self.body_path(&revision.body_sha256);
+        let len =
+            usize::try_from(fs::metadata(&path)?.len()).unwrap_or(usize::MAX);
+        let (body, spooled) = if len > max_memory {
+            let spooled =
+                Spooled::stored(path, len, revision.body_sha256.clone());
+            (Bytes::new(), Some(Arc::new(spooled)))
+        } else {
+            (self.load_body(&revision.body_sha256)?, None)
+        };
+        Ok(Response {
+            url: revision.url.clone(),
+            version: revision.version,
+            status: revision.status,
+            headers: revision.headers.clone(),
+            body,
+            spooled,
+            certificate_sha256: revision.certificate_sha256.clone(),
+            peer_address: revision.peer_address,
+        })
+    }
+
+    /// Load the [`Redirects`] index. It’s empty if it doesn’t exist yet.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if the index could not be read or parsed.
+    pub fn load_redirects(&self) -> anyhow::Result<Redirects> {
+        let path = self.redirects_path();
+        match fs::read(&path) {
+            Ok(source) => ron::de::from_bytes(&source)
+                .with_context(|| format!("could not parse {}", path.display())),
+            Err(error) if error.kind() == io::ErrorKind::NotFound => {
+                Ok(Redirects::default())
+            }
+            Err(error) => Err(error.into()),
+        }
+    }
+
+    /// Record the final URL `request_url` resolved to when it was checked.
+    ///
+    /// This updates the [`Redirects`] index atomically, removing `request_url`
+    /// if it no longer redirects. On UNIX it also links the history file for
+    /// `request_url` to the one for `final_url`, for people browsing the state
+    /// directory. Nothing is written if the redirect hasn’t changed.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if the index could not be read or written.
+    pub fn record_redirect(
+        &self,
+        request_url: &Url,
+        final_url: &Url,
+    ) -> anyhow::Result<()> {
+        let mut redirects = self.load_redirects()?;
+        let changed = if request_url == final_url {
+            redirects.urls.remove(request_url).is_some()
+        } else {
+            redirects
+                .urls
+                .insert(request_url.clone(), final_url.clone())
+                != Some(final_url.clone())
+        };
+        if changed {
+            self.save_redirects(&redirects)?;
+        }
+
+        if request_url != final_url {
+            self.link_history(request_url, final_url)?;
+        }
+        Ok(())
+    }
+
+    /// Delete the history for a URL, e.g. after it’s removed from the
+    /// configuration.
+    ///
+    /// If the URL redirected, the history of the final URL is also deleted,
+    /// unless another URL redirects to it or `in_use` returns true for it.
+    /// Bodies are left in place, since other URLs may share them.
+    ///
+    /// Returns the paths of the files deleted.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if the redirects index couldn’t be read or written, or
+    /// a file couldn’t be deleted.
+    pub fn remove_history<F>(
+        &self,
+        url: &Url,
+        in_use: F,
+    ) -> anyhow::Result<Vec<PathBuf>>
+    where
+        F: Fn(&Url) -> bool,
+    {
+        let mut redirects = self.load_redirects()?;
+        let final_url = redirects.urls.remove(url);
+        if final_url.is_some() {
+            self.save_redirects(&redirects)?;
+        }
+
+        let mut urls = vec![url];
+        if let Some(final_url) = &final_url
+            && !in_use(final_url)
+            && !redirects.urls.values().any(|other| other == final_url)
+        {
+            urls.push(final_url);
+        }
+
+        let mut removed = Vec::new();
+        for url in urls {
+            let path = self.history_path(url);
+            match fs::remove_file(&path) {
+                Ok(()) => removed.push(path),
+                Err(error) if error.kind() == io::ErrorKind::NotFound => {}
+                Err(error) => {
+                    return Err(error).with_context(|| {
+                        format!("could not delete {}", path.display())
+                    });
+                }
+            }
+        }
+        Ok(removed)
+    }
+
+    /// Save the [`Redirects`] index atomically.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if the index could not be serialized or written.
+    fn save_redirects(&self, redirects: &Redirects) -> anyhow::Result<()> {
+        let contents = ron::ser::to_string_pretty(
+            redirects,
+            ron::ser::PrettyConfig::default(),
+        )?;
+        atomic_write(&self.redirects_path(), contents.as_bytes())?;
+        Ok(())
+    }
+
+    /// Link the history file for `request_url` to the one for `final_url`.
+    ///
+    /// This never replaces a regular file, since that’s the history from
+    /// before `request_url` started redirecting, and it replaces an old link
+    /// atomically.
+    ///
+    /// # Errors
+    ///
+    /// Returns [`io::Error`] for errors.
+    #[cfg(all(not(target_os = "hermit"), unix))]
+    fn link_history(
+        &self,
+        request_url: &Url,
+        final_url: &Url,
+    ) -> io::Result<()> {
+        let path = self.history_path(request_url);
+        // They’re in the same directory, so just link to the file name.
+        let target = PathBuf::from(Self::history_file_name(final_url));
+        match fs::symlink_metadata(&path) {
+            Ok(metadata) if !metadata.is_symlink() => return Ok(()),
+            Ok(_) if fs::read_link(&path)? == target => return Ok(()),
+            Ok(_) => {}
+            Err(error) if error.kind() == io::ErrorKind::NotFound => {}
+            Err(error) => return Err(error),
+        }
+
+        let tmp_path =
+            path.with_added_extension(format!(".{}.tmp", std::process::id()));
+        if tmp_path.symlink_metadata().is_ok() {
+            fs::remove_file(&tmp_path)?;
+        }
+        std::os::unix::fs::symlink(target, &tmp_path)?;
+        fs::rename(&tmp_path, &path)
+    }
+
+    /// Links are only a convenience, so they’re skipped on other platforms.
+    #[cfg(not(all(not(target_os = "hermit"), unix)))]
+    #[expect(
+        clippy::unnecessary_wraps,
+        clippy::unused_self,
+        reason = "matches the UNIX version"
+    )]
+    fn link_history(
+        &self,
+        _request_url: &Url,
+        _final_url: &Url,
+    ) -> io::Result<()> {
+        Ok(())
+    }
+}
+
+/// Get the SHA-256 of some data as a hex string.
+#[must_use]
+pub fn sha256_hex(data: &[u8]) -> String {
+    hex(&Sha256::digest(data))
+}
+
+/// Format bytes, e.g. a digest, as a hex string.
+#[must_use]
+pub fn hex(bytes: &[u8]) -> String {
+    bytes.iter().fold(String::new(), |mut hex, byte| {
+        let _ = write!(hex, "{byte:02x}");
+        hex
+    })
+}
+
+/// Write a file atomically.
+///
+/// This writes to a temporary file in the same directory and then renames it.
+///
+/// # Errors
+///
+/// Returns an error if the file could not be written.
+pub fn atomic_write(path: &Path, contents: &[u8]) -> io::Result<()> {
+    let tmp_path = path.with_added_extension(".tmp");
+    let (tmp_path, mut file) = create_unique_file(&tmp_path)?;
+    file.write_all(contents)?;
+    fs::rename(&tmp_path, path)
+}
+
+/// Make a filesystem-safe version of the URL.
+///
+/// # Panics
+///
+/// Panics if the URL is empty, `.`, or `..`, which can’t happen for a parsed
+/// URL.
+#[must_use]
+pub fn fs_safe_url(url: &Url) -> String {
+    // FIXME does not work on Windows.
+    let s = url.as_str();
+    assert_ne!(s, "");
+    assert_ne!(s, ".");
+    assert_ne!(s, "..");
+    s.replace('\\', "\\\\")
+        .replace('|', r"\|")
+        .replace('/', "|")
+}
+
+/// Create a unique file in a directory.
+///
+/// # Errors
+///
+/// This will return an error with [`io::ErrorKind::AlreadyExists`] if it can’t
+/// create a file. It will add a number to the file name and increase the
+/// number until it finds an available name, or after 100 tries.
+fn create_unique_file(base_name: &Path) -> io::Result<(PathBuf, fs::File)> {
+    const MAX_ATTEMPTS: usize = 100;
+
+    for i in 1..=MAX_ATTEMPTS {
+        let candidate = base_name.with_added_extension(format!(".{i}"));
+        match exclusive_create_file(&candidate) {
+            Err(error) if error.kind() == io::ErrorKind::AlreadyExists => {}
+            Err(error) => return Err(error),
+            Ok(file) => return Ok((candidate, file)),
+        }
+    }
+
+    Err(io::ErrorKind::AlreadyExists.into())
+}
+
+/// Create a file; fail if it already exists.
+///
+/// # Errors
+///
+/// It will return an error if the file already exists, or if there was some
+/// other reason it could not create the file.
+fn exclusive_create_file(path: &Path) -> io::Result<fs::File> {
+    fs::OpenOptions::new()
+        .write(true)
+        .create_new(true)
+        .open(path)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use assert2::check;
+
+    /// Convert a `&str` to a `Url`.
+    fn u(s: &'static str) -> Url {
+        Url::parse(s).unwrap()
+    }
+
+    #[test]
+    fn test_fs_safe_url() {
+        check!(
+            fs_safe_url(&u("https://demon.horse/hireme/#fragment"))
+                == "https:||demon.horse|hireme|#fragment"
+        );
+        check!(fs_safe_url(&u("a://a/b")) == "a:||a|b");
+        check!(
+            fs_safe_url(&u(r"a://a/foo\back|pipe\|backpipe"))
+                == r"a:||a|foo\\back\|pipe\\\|backpipe"
+        );
+    }
+
+    #[test]
+    fn test_sha256_hex() {
+        check!(
+            sha256_hex(b"abc")
+                == "ba7816bf8f01cfea414140de5dae2223b00361a396177a9cb410ff61f20015ad"
+        );
+    }
+
+    #[test]
+    fn test_last_checked() {
+        let response = |status| Response {
+            url: u("https://example.com/"),
+            version: http::Version::HTTP_11,
+            status,
+            headers: http::HeaderMap::new(),
+            body: Bytes::new(),
+            spooled: None,
+            certificate_sha256: None,
+            peer_address: None,
+        };
+        let mut ok = Revision::new(&response(http::StatusCode::OK), b"");
+        ok.fetched_at = DateTime::from_timestamp(1_000, 0).unwrap();
+        let mut error = Revision::new(
+            &response(http::StatusCode::INTERNAL_SERVER_ERROR),
+            b"",
+        );
+        error.fetched_at = DateTime::from_timestamp(2_000, 0).unwrap();
+
+        let mut history = History {
+            revisions: vec![ok.clone(), error],
+            checked_at: None,
+            baseline: None,
+            reliability: Reliability::default(),
+            reviewed_at: None,
+        };
+        check!(history.last_checked() == Some(ok.fetched_at));
+
+        let checked_at = DateTime::from_timestamp(3_000, 0).unwrap();
+        history.checked_at = Some(checked_at);
+        check!(history.last_checked() == Some(checked_at));
+
+        check!(History::default().last_checked() == None);
+
+        check!(history.removal().is_none());
+        let mut removed =
+            Revision::new(&response(http::StatusCode::NOT_FOUND), b"gone");
+        removed.fetched_at = DateTime::from_timestamp(4_000, 0).unwrap();
+        history.revisions.push(removed.clone());
+        removed.fetched_at = DateTime::from_timestamp(5_000, 0).unwrap();
+        history.revisions.push(removed);
+        let removal = history.removal().unwrap();
+        check!(removal.removed_at.timestamp() == 4_000);
+        check!(removal.before.unwrap().fetched_at.timestamp() == 2_000);
+        history.revisions.truncate(2);
+
+        check!(history.index(None).unwrap() == 1);
+        check!(history.index(Some(1)).unwrap() == 0);
+        check!(history.index(Some(0)).is_err());
+        check!(history.index(Some(3)).is_err());
+        check!(History::default().index(None).is_err());
+    }
+
+    /// Make a history of successful responses with `contents`, fetched 1,000
+    /// seconds apart starting at 1,000 seconds after the epoch.
+    fn history(contents: &[&[u8]]) -> History {
+        let response = Response {
+            url: u("https://example.com/"),
+            version: http::Version::HTTP_11,
+            status: http::StatusCode::OK,
+            headers: http::HeaderMap::new(),
+            body: Bytes::new(),
+            spooled: None,
+            certificate_sha256: None,
+            peer_address: None,
+        };
+        let revisions = (1_000..)
+            .step_by(1_000)
+            .zip(contents)
+            .map(|(seconds, content)| {
+                let mut revision = Revision::new(&response, content);
+                revision.fetched_at =
+                    DateTime::from_timestamp(seconds, 0).unwrap();
+                revision
+            })
+            .collect();
+        History { revisions, ..History::default() }
+    }
+
+    #[test]
+    fn test_seen_within() {
+        let history = history(&[b"a", b"b", b"a", b"c"]);
+        let at = |seconds| DateTime::from_timestamp(seconds, 0).unwrap();
+        let window = |seconds| Duration::from_secs(seconds);
+        let sha256 = |content: &[u8]| sha256_hex(content);
+        check!(
+            history.seen_within(&sha256(b"a"), at(4_500), window(2_000))
+                == Some(at(3_000))
+        );
+        check!(
+            history.seen_within(&sha256(b"b"), at(4_500), window(3_000))
+                == Some(at(2_000))
+        );
+        check!(
+            history.seen_within(&sha256(b"b"), at(4_500), window(2_000))
+                == None
+        );
+        check!(
+            history.seen_within(&sha256(b"d"), at(4_500), window(9_000))
+                == None
+        );
+    }
+
+    #[test]
+    fn test_unchanged_since() {
+        let mut history = history(&[b"a", b"b", b"error", b"b", b"login"]);
+        history.revisions[2].status = http::StatusCode::BAD_GATEWAY;
+        history.revisions[4].soft_error = Some(SoftError::LoginWall);
+        let sha256 = |content: &[u8]| sha256_hex(content);
+        check!(
+            history.unchanged_since(&sha256(b"b"))
+                == DateTime::from_timestamp(2_000, 0)
+        );
+        check!(history.unchanged_since(&sha256(b"a")) == None);
+        check!(history.unchanged_since(&sha256(b"error")) == None);
+    }
+
+    #[test]
+    fn test_push() {
+        let response = Response {
+            url: u("https://example.com/"),
+            version: http::Version::HTTP_11,
+            status: http::StatusCode::OK,
+            headers: http::HeaderMap::new(),
+            body: Bytes::from_static(b"<p>a</p>"),
+            spooled: None,
+            certificate_sha256: None,
+            peer_address: None,
+        };
+        let at = |seconds| DateTime::from_timestamp(seconds, 0).unwrap();
+        let revision = |content: &[u8], seconds| {
+            let mut revision = Revision::new(&response, content);
+            revision.fetched_at = at(seconds);
+            revision
+        };
+
+        let mut history = History::default();
+        check!(history.push(revision(b"a", 1_000)));
+        history.revisions[0].note = Some("first".to_owned());
+
+        let mut same = revision(b"a", 2_000);
+        same.body_sha256 = "other body".to_owned();
+        same.peer_address = Some(IpAddr::from([192, 0, 2, 1]));
+        check!(!history.push(same));
+        check!(!history.push(revision(b"a", 3_000)));
+        let latest = history.latest().unwrap();
+        check!(history.revisions.len() == 1);
+        check!(latest.fetched_at == at(1_000));
+        check!(latest.last_fetched() == at(3_000));
+        check!(latest.body_sha256 == response.body_sha256());
+        check!(latest.note.as_deref() == Some("first"));
+        check!(latest.peer_address == None);
+        check!(
+            history.unchanged_since(&latest.content_sha256) == Some(at(1_000))
+        );
+
+        let mut failed = revision(b"a", 4_000);
+        failed.failures.push("missing text".to_owned());
+        check!(history.push(failed));
+        check!(history.push(revision(b"b", 5_000)));
+        check!(history.revisions.len() == 3);
+
+        let content_sha256 = history.revisions[0].content_sha256.clone();
+        let window = Duration::from_secs(2_500);
+        check!(
+            history.seen_within(&content_sha256, at(5_000), window)
+                == Some(at(4_000))
+        );
+    }
+
+    #[test]
+    fn test_reliability() {
+        let mut reliability = Reliability::default();
+        check!(reliability.uptime() == None);
+
+        let time = |seconds| DateTime::from_timestamp(seconds, 0).unwrap();
+        reliability.record(true, time(1));
+        reliability.record(false, time(2));
+        reliability.record(false, time(3));
+        check!(
+            reliability
+                == Reliability {
+                    checks: 3,
+                    errors: 2,
+                    consecutive_errors: 2,
+                    failing_since: Some(time(2)),
+                    last_failure: Some(time(3)),
+                    disabled_at: None,
+                }
+        );
+
+        reliability.disabled_at = Some(time(3));
+        reliability.record(true, time(4));
+        check!(reliability.consecutive_errors == 0);
+        check!(reliability.failing_since == None);
+        check!(reliability.disabled_at == None);
+        check!(reliability.uptime() == Some(50.0));
+    }
+
+    #[test]
+    fn test_record_redirect() {
+        let dir = std::env::temp_dir()
+            .join(format!("monitorbot-{}-redirects", std::process::id()));
+        let _ = fs::remove_dir_all(&dir);
+        let state = State::new(dir.clone());
+        state.create().unwrap();
+
+        let a = u("https://example.com/a");
+        let b = u("https://example.com/b");
+        let target = u("https://example.com/target");
+        let history = |url: &Url| History {
+            checked_at: Some(DateTime::from_timestamp(1_000, 0).unwrap()),
+            revisions: vec![Revision::new(
+                &Response {
+                    url: url.clone(),
+                    version: http::Version::HTTP_11,
+                    status: http::StatusCode::OK,
+                    headers: http::HeaderMap::new(),
+                    body: Bytes::new(),
+                    spooled: None,
+                    certificate_sha256: None,
+                    peer_address: None,
+                },
+                b"",
+            )],
+            baseline: None,
+            reliability: Reliability::default(),
+            reviewed_at: None,
+        };
+        state.save_history(&target, &history(&target)).unwrap();
+        // `a` has history from before it redirected.
+        state.save_history(&a, &history(&a)).unwrap();
+
+        state.record_redirect(&b, &target).unwrap();
+        state.record_redirect(&a, &target).unwrap();
+        state.record_redirect(&target, &target).unwrap();
+        let redirects = state.load_redirects().unwrap();
+        check!(redirects.requests_for(&target).collect::<Vec<_>>() == [&a, &b]);
+        check!(redirects.resolve(&target) == &target);
+
+        let loaded = |url| state.load_history(url, true).unwrap().unwrap();
+        check!(loaded(&a).revisions[0].url == target);
+        check!(loaded(&b).revisions[0].url == target);
+        check!(
+            state
+                .load_final_history(&a, true)
+                .unwrap()
+                .unwrap()
+                .revisions[0]
+                .url
+                == a
+        );
+        #[cfg(unix)]
+        {
+            check!(!state.history_path(&a).is_symlink());
+            check!(
+                fs::read_link(state.history_path(&b)).unwrap()
+                    == Path::new(&State::history_file_name(&target))
+            );
+        }
+
+        // `a` stops redirecting.
+        state.record_redirect(&a, &a).unwrap();
+        check!(loaded(&a).revisions[0].url == a);
+        check!(state.load_redirects().unwrap().urls.len() == 1);
+
+        fs::remove_dir_all(dir).unwrap();
+    }
+}
@@ -0,0 +1,749 @@
+//! Monitoring WebSocket and server-sent event streams (`stream`).
+//!
+//! URLs with `ws://` and `wss://` schemes are WebSocket endpoints. Other URLs
+//! with a `stream` table are read as server-sent events (`text/event-stream`).
+//!
+//! Messages are collected for `duration`, or until `count` messages have been
+//! received, and stored one per line like any other content. Each message is
+//! trimmed, and JSON messages are reformatted compactly with their keys sorted,
+//! so that changes in formatting aren’t reported. Binary WebSocket messages are
+//! stored in base64.
+
+use anyhow::bail;
+use base64::Engine as _;
+use base64::engine::general_purpose::STANDARD as BASE64;
+use bytes::Bytes;
+use http::{HeaderMap, HeaderValue, StatusCode, header};
+use sha1::{Digest, Sha1};
+use std::time::Duration;
+use tokio::io::{AsyncReadExt, AsyncWriteExt};
+use tokio::time::Instant;
+use url::Url;
+
+use crate::error::MonitorError;
+use crate::response::{DownloadError, Response};
+use crate::spool::Spool;
+use crate::state::sha256_hex;
+
+/// The media type of captured messages, so that they’re rendered as is.
+pub const MEDIA_TYPE: &str = "text/x-monitorbot-stream";
+
+/// How long to collect messages for if `duration` isn’t set.
+pub const DEFAULT_DURATION: Duration = Duration::from_secs(10);
+
+/// Appended to the key to get `Sec-WebSocket-Accept` (RFC 6455).
+const WEBSOCKET_GUID: &str = "258EAFA5-E914-47DA-95CA-C5AB0DC85B11";
+
+/// Largest WebSocket frame to accept if there’s no `max_download_size`.
+const MAX_FRAME: u64 = 16 * 1024 * 1024;
+
+/// How to collect messages from a stream.
+#[derive(
+    Clone, Debug, Default, Eq, PartialEq, serde::Deserialize, serde::Serialize,
+)]
+#[serde(deny_unknown_fields)]
+pub struct Stream {
+    /// How long to collect messages for, e.g. "30s" (default: 10 seconds).
+    #[serde(
+        default,
+        with = "humantime_serde",
+        skip_serializing_if = "Option::is_none"
+    )]
+    pub duration: Option<Duration>,
+
+    /// Stop after this many messages, even if `duration` hasn’t passed.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub count: Option<usize>,
+
+    /// Message to send after connecting to a WebSocket, e.g. to subscribe to
+    /// a channel.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub send: Option<String>,
+
+    /// Sort the messages, for streams that send them in no particular order.
+    #[serde(default, skip_serializing_if = "std::ops::Not::not")]
+    pub sort: bool,
+}
+
+/// Check if a URL is a WebSocket endpoint.
+#[must_use]
+pub fn is_websocket(url: &Url) -> bool {
+    matches!(url.scheme(), "ws" | "wss")
+}
+
+/// Get the HTTP URL to request a WebSocket URL with, or `url` itself if it
+/// isn’t one.
+#[must_use]
+pub fn http_url(url: &Url) -> Url {
+    let mut url = url.clone();
+    let scheme = match url.scheme() {
+        "ws" => "http",
+        "wss" => "https",
+        _ => return url,
+    };
+    // Both are special schemes, so this can’t fail.
+    let _ = url.set_scheme(scheme);
+    url
+}
+
+/// Connect to a stream and collect its messages into a response.
+///
+/// If the server doesn’t accept the connection as a stream, e.g. because it
+/// returned 404, its response is returned as is.
+///
+/// # Errors
+///
+/// Returns an error if the connection failed, the server broke the protocol,
+/// or the messages were larger than `max` bytes.
+pub async fn capture(
+    client: &reqwest::Client,
+    url: &Url,
+    mut headers: HeaderMap,
+    stream: &Stream,
+    max: Option<u64>,
+    spool: &Spool,
+) -> Result<Response, MonitorError> {
+    let websocket = is_websocket(url);
+    let key = if websocket {
+        let key = websocket_key().map_err(MonitorError::Network)?;
+        headers.insert(header::CONNECTION, HeaderValue::from_static("upgrade"));
+        headers.insert(header::UPGRADE, HeaderValue::from_static("websocket"));
+        headers.insert(
+            header::SEC_WEBSOCKET_VERSION,
+            HeaderValue::from_static("13"),
+        );
+        headers.insert(
+            header::SEC_WEBSOCKET_KEY,
+            HeaderValue::from_str(&key)
+                .map_err(|error| MonitorError::Network(error.into()))?,
+        );
+        Some(key)
+    } else {
+        headers.insert(
+            header::ACCEPT,
+            HeaderValue::from_static("text/event-stream"),
+        );
+        headers.insert(
+            header::ACCEPT_ENCODING,
+            HeaderValue::from_static("identity"),
+        );
+        None
+    };
+
+    let response = client
+        .get(http_url(url))
+        .version(http::Version::HTTP_11)
+        .headers(headers)
+        .send()
+        .await?;
+    let accepted = match &key {
+        Some(_) => response.status() == StatusCode::SWITCHING_PROTOCOLS,
+        None => response.status().is_success(),
+    };
+    if !accepted {
+        return Ok(Response::from_reqwest(response, max, spool).await?);
+    }
+
+    let mut template = Response {
+        url: url.clone(),
+        version: response.version(),
+        // The messages are the content, not the handshake.
+        status: StatusCode::OK,
+        headers: response.headers().clone(),
+        body: Bytes::new(),
+        spooled: None,
+        certificate_sha256: response
+            .extensions()
+            .get::<reqwest::tls::TlsInfo>()
+            .and_then(reqwest::tls::TlsInfo::peer_certificate)
+            .map(sha256_hex),
+        peer_address: response.remote_addr().map(|addr| addr.ip()),
+    };
+    template.headers.insert(
+        header::CONTENT_TYPE,
+        HeaderValue::from_static("text/x-monitorbot-stream; charset=utf-8"),
+    );
+
+    let mut source = match key {
+        Some(key) => {
+            check_accept(response.headers(), &key)
+                .map_err(MonitorError::Network)?;
+            let mut websocket = WebSocket {
+                io: response.upgrade().await?,
+                max: max.unwrap_or(MAX_FRAME),
+            };
+            if let Some(message) = &stream.send {
+                websocket
+                    .write_frame(OPCODE_TEXT, message.as_bytes())
+                    .await
+                    .map_err(|error| MonitorError::Network(error.into()))?;
+            }
+            Source::WebSocket(websocket)
+        }
+        None => Source::Events(Events::new(response)),
+    };
+
+    let messages = collect(&mut source, stream, max).await?;
+    if let Source::WebSocket(websocket) = &mut source {
+        // Say goodbye, but don’t wait for the server to.
+        let _ = websocket.write_frame(OPCODE_CLOSE, &[]).await;
+    }
+    template.body = Bytes::from(messages.join("\n"));
+    Ok(template)
+}
+
+/// Collect normalized messages until `duration` passes, `count` messages have
+/// been received, or the stream ends.
+///
+/// # Errors
+///
+/// Returns an error if reading the stream failed, or the messages were larger
+/// than `max` bytes.
+async fn collect(
+    source: &mut Source,
+    stream: &Stream,
+    max: Option<u64>,
+) -> Result<Vec<String>, MonitorError> {
+    let duration = stream.duration.unwrap_or(DEFAULT_DURATION);
+    let deadline = Instant::now().checked_add(duration);
+    let mut messages = Vec::new();
+    let mut received = 0_usize;
+    while stream.count.is_none_or(|count| messages.len() < count) {
+        let next = source.next();
+        let message = match deadline {
+            Some(deadline) => {
+                match tokio::time::timeout_at(deadline, next).await {
+                    Ok(message) => message,
+                    Err(_) => break,
+                }
+            }
+            None => next.await,
+        };
+        let Some(message) = message.map_err(MonitorError::Network)? else {
+            break;
+        };
+
+        received = received.saturating_add(message.len());
+        if let Some(max) = max
+            && u64::try_from(received).unwrap_or(u64::MAX) > max
+        {
+            return Err(DownloadError::TooLarge { max, received }.into());
+        }
+        messages.push(normalize(&message));
+    }
+    if stream.sort {
+        messages.sort();
+    }
+    Ok(messages)
+}
+
+/// Normalize a message so that only changes in its content show up.
+///
+/// Messages are trimmed. JSON is reformatted compactly with its keys sorted.
+/// Line breaks in other messages are escaped, so that each message is on one
+/// line.
+#[must_use]
+pub fn normalize(message: &str) -> String {
+    let message = message.trim();
+    match serde_json::from_str::<serde_json::Value>(message) {
+        Ok(json) => json.to_string(),
+        Err(_) => message.replace('\n', "\\n"),
+    }
+}
+
+/// Generate a random `Sec-WebSocket-Key`.
+///
+/// # Errors
+///
+/// Returns an error if no randomness was available.
+fn websocket_key() -> anyhow::Result<String> {
+    let mut key = [0; 16];
+    getrandom::fill(&mut key)?;
+    Ok(BASE64.encode(key))
+}
+
+/// Check that the server accepted the WebSocket `key` (RFC 6455 §4.2.2).
+///
+/// # Errors
+///
+/// Returns an error if `Sec-WebSocket-Accept` is missing or wrong.
+fn check_accept(headers: &HeaderMap, key: &str) -> anyhow::Result<()> {
+    let mut hash = Sha1::new();
+    hash.update(key.as_bytes());
+    hash.update(WEBSOCKET_GUID.as_bytes());
+    let expected = BASE64.encode(hash.finalize());
+    match headers.get(header::SEC_WEBSOCKET_ACCEPT) {
+        Some(accept) if accept.as_bytes() == expected.as_bytes() => Ok(()),
+        Some(_) => bail!("server sent the wrong Sec-WebSocket-Accept"),
+        None => bail!("server didn’t send Sec-WebSocket-Accept"),
+    }
+}
+
+/// Where messages come from.
+enum Source {
+    /// A WebSocket connection.
+    WebSocket(WebSocket),
+
+    /// A server-sent event stream.
+    Events(Events),
+}
+
+impl Source {
+    /// Get the next message, or `None` if the stream ended.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if the stream couldn’t be read.
+    async fn next(&mut self) -> anyhow::Result<Option<String>> {
+        match self {
+            Self::WebSocket(websocket) => websocket.next().await,
+            Self::Events(events) => events.next().await,
+        }
+    }
+}
+
+/// Continues a fragmented WebSocket message.
+const OPCODE_CONTINUATION: u8 = 0x0;
+
+/// A WebSocket text message.
+const OPCODE_TEXT: u8 = 0x1;
+
+/// A WebSocket binary message.
+const OPCODE_BINARY: u8 = 0x2;
+
+/// Closes a WebSocket connection.
+const OPCODE_CLOSE: u8 = 0x8;
+
+/// A WebSocket ping, which must be answered with a pong.
+const OPCODE_PING: u8 = 0x9;
+
+/// A WebSocket pong.
+const OPCODE_PONG: u8 = 0xa;
+
+/// The client side of a WebSocket connection (RFC 6455).
+struct WebSocket {
+    /// The upgraded connection.
+    io: reqwest::Upgraded,
+
+    /// The largest message to accept.
+    max: u64,
+}
+
+impl WebSocket {
+    /// Get the next message, or `None` if the connection was closed.
+    ///
+    /// Pings are answered, and fragmented messages are put back together.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if the connection failed, or the server sent an
+    /// invalid or overly large frame.
+    async fn next(&mut self) -> anyhow::Result<Option<String>> {
+        let mut message = Vec::new();
+        let mut binary = false;
+        let mut fragmented = false;
+        loop {
+            let (fin, opcode, payload) = match self.read_frame().await {
+                Ok(frame) => frame,
+                Err(error)
+                    if error.kind() == std::io::ErrorKind::UnexpectedEof =>
+                {
+                    return Ok(None);
+                }
+                Err(error) => return Err(error.into()),
+            };
+            // Control frames can come between fragments, but can’t be
+            // fragmented themselves (RFC 6455 §5.5).
+            if opcode & 0x8 != 0 && (!fin || payload.len() > 125) {
+                bail!("server sent an invalid WebSocket control frame");
+            }
+            match opcode {
+                OPCODE_TEXT | OPCODE_BINARY if !fragmented => {
+                    binary = opcode == OPCODE_BINARY;
+                    message = payload;
+                }
+                OPCODE_CONTINUATION if fragmented => {
+                    message.extend_from_slice(&payload);
+                }
+                OPCODE_TEXT | OPCODE_BINARY | OPCODE_CONTINUATION => {
+                    bail!("server sent a WebSocket fragment out of order");
+                }
+                OPCODE_CLOSE => return Ok(None),
+                OPCODE_PING => {
+                    self.write_frame(OPCODE_PONG, &payload).await?;
+                    continue;
+                }
+                OPCODE_PONG => continue,
+                _ => bail!("server sent unknown WebSocket opcode {opcode}"),
+            }
+            if u64::try_from(message.len()).unwrap_or(u64::MAX) > self.max {
+                bail!("WebSocket message is larger than {} bytes", self.max);
+            }
+            if fin {
+                return Ok(Some(if binary {
+                    BASE64.encode(message)
+                } else {
+                    String::from_utf8(message)?
+                }));
+            }
+            fragmented = true;
+        }
+    }
+
+    /// Read a frame, returning whether it’s the last of its message, its
+    /// opcode, and its unmasked payload.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if the frame couldn’t be read or was too large.
+    async fn read_frame(&mut self) -> std::io::Result<(bool, u8, Vec<u8>)> {
+        let mut head = [0; 2];
+        self.io.read_exact(&mut head).await?;
+        let fin = head[0] & 0x80 != 0;
+        let opcode = head[0] & 0x0f;
+        let masked = head[1] & 0x80 != 0;
+        let len = match head[1] & 0x7f {
+            0x7e => u64::from(self.io.read_u16().await?),
+            0x7f => self.io.read_u64().await?,
+            len => u64::from(len),
+        };
+        if len > self.max {
+            return Err(std::io::Error::other(format!(
+                "WebSocket frame is larger than {} bytes",
+                self.max,
+            )));
+        }
+        let mut mask = [0; 4];
+        if masked {
+            self.io.read_exact(&mut mask).await?;
+        }
+        let mut payload =
+            vec![0; usize::try_from(len).map_err(std::io::Error::other)?];
+        self.io.read_exact(&mut payload).await?;
+        for (byte, mask) in payload.iter_mut().zip(mask.iter().cycle()) {
+            *byte ^= mask;
+        }
+        Ok((fin, opcode, payload))
+    }
+
+    /// Write a whole message in one masked frame, as clients must.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if the frame couldn’t be written.
+    async fn write_frame(
+        &mut self,
+        opcode: u8,
+        payload: &[u8],
+    ) -> std::io::Result<()> {
+        let mut mask = [0; 4];
+        getrandom::fill(&mut mask).map_err(std::io::Error::other)?;
+
+        let mut frame = vec![0x80 | opcode];
+        let len = payload.len();
+        if let Ok(len @ 0..=125) = u8::try_from(len) {
+            frame.push(0x80 | len);
+        } else if let Ok(len) = u16::try_from(len) {
+            // Masked, with a 16-bit length.
+            frame.push(0xfe);
+            frame.extend_from_slice(&len.to_be_bytes());
+        } else {
+            // Masked, with a 64-bit length.
+            frame.push(0xff);
+            frame.extend_from_slice(&(len as u64).to_be_bytes());
+        }
+        frame.extend_from_slice(&mask);
+        frame.extend(
+            payload
+                .iter()
+                .zip(mask.iter().cycle())
+                .map(|(byte, mask)| byte ^ mask),
+        );
+        self.io.write_all(&frame).await?;
+        self.io.flush().await
+    }
+}
+
+/// A server-sent event stream (the HTML `EventSource` format).
+struct Events {
+    /// The response being read.
+    response: reqwest::Response,
+
+    /// Received bytes that aren’t a whole line yet.
+    buffer: Vec<u8>,
+
+    /// The type of the current event, if it isn’t `message`.
+    event: Option<String>,
+
+    /// The `data` lines of the current event.
+    data: Vec<String>,
+}
+
+impl Events {
+    /// Start reading events from a response.
+    const fn new(response: reqwest::Response) -> Self {
+        Self { response, buffer: Vec::new(), event: None, data: Vec::new() }
+    }
+
+    /// Get the data of the next event, or `None` if the stream ended.
+    ///
+    /// Events with a type other than `message` are prefixed with their type
+    /// in square brackets. Comments and other fields are ignored.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if the stream couldn’t be read.
+    async fn next(&mut self) -> anyhow::Result<Option<String>> {
+        loop {
+            while let Some(end) =
+                self.buffer.iter().position(|&byte| byte == b'\n')
+            {
+                let line = self.buffer.drain(..=end).collect::<Vec<_>>();
+                let line = String::from_utf8_lossy(&line);
+                let line = line.trim_end_matches(['\n', '\r']);
+                if let Some(event) = self.line(line) {
+                    return Ok(Some(event));
+                }
+            }
+            match self.response.chunk().await? {
+                Some(chunk) => self.buffer.extend_from_slice(&chunk),
+                None => return Ok(None),
+            }
+        }
+    }
+
+    /// Process a line, returning an event if it ended one.
+    fn line(&mut self, line: &str) -> Option<String> {
+        if line.is_empty() {
+            let event = self.event.take();
+            if self.data.is_empty() {
+                return None;
+            }
+            let data = self.data.join("\n");
+            self.data.clear();
+            return Some(match event {
+                Some(event) => format!("[{event}] {data}"),
+                None => data,
+            });
+        }
+
+        let (field, value) = line.split_once(':').unwrap_or((line, ""));
+        let value = value.strip_prefix(' ').unwrap_or(value);
+        match field {
+            "data" => self.data.push(value.to_owned()),
+            "event" if value != "message" => {
+                self.event = Some(value.to_owned());
+            }
+            _ => {}
+        }
+        None
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use assert2::check;
+    use tokio::io::AsyncBufReadExt;
+    use tokio::net::TcpListener;
+
+    /// Serve one connection, replying with `reply` once the request headers
+    /// have been read, then calling `then` with the connection.
+    async fn serve<R, F, Fut>(reply: R, then: F) -> Url
+    where
+        R: Fn(&str) -> String + Send + 'static,
+        F: FnOnce(tokio::net::TcpStream) -> Fut + Send + 'static,
+        Fut: Future<Output = ()> + Send,
+    {
+        let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let address = listener.local_addr().unwrap();
+        tokio::spawn(async move {
+            let (socket, _) = listener.accept().await.unwrap();
+            let mut socket = tokio::io::BufReader::new(socket);
+            let mut request = String::new();
+            while !request.ends_with("\r\n\r\n") {
+                socket.read_line(&mut request).await.unwrap();
+            }
+            let mut socket = socket.into_inner();
+            socket.write_all(reply(&request).as_bytes()).await.unwrap();
+            then(socket).await;
+        });
+        format!("http://{address}/").parse().unwrap()
+    }
+
+    /// Accept a WebSocket handshake.
+    fn accept(request: &str) -> String {
+        let key = request
+            .lines()
+            .find_map(|line| line.strip_prefix("sec-websocket-key: "))
+            .unwrap();
+        let mut hash = Sha1::new();
+        hash.update(key.as_bytes());
+        hash.update(WEBSOCKET_GUID.as_bytes());
+        format!(
+            "HTTP/1.1 101 Switching Protocols\r\n\
+            Connection: Upgrade\r\nUpgrade: websocket\r\n\
+            Sec-WebSocket-Accept: {}\r\n\r\n",
+            BASE64.encode(hash.finalize()),
+        )
+    }
+
+    /// Capture from a WebSocket server that sends `frames`, then waits for
+    /// the client to hang up. Returns the capture and what the client sent.
+    async fn websocket(
+        frames: &'static [u8],
+        duration: Duration,
+        max: Option<u64>,
+    ) -> (Result<String, String>, Vec<u8>) {
+        let (sent_tx, sent_rx) = tokio::sync::oneshot::channel();
+        let mut url = serve(accept, move |mut socket| async move {
+            socket.write_all(frames).await.unwrap();
+            let mut sent = Vec::new();
+            let _ = socket.read_to_end(&mut sent).await;
+            let _ = sent_tx.send(sent);
+        })
+        .await;
+        url.set_scheme("ws").unwrap();
+
+        let client = reqwest::Client::builder().http1_only().build().unwrap();
+        let spool = Spool { dir: std::env::temp_dir(), max_memory: 1 << 20 };
+        let stream = Stream { duration: Some(duration), ..Stream::default() };
+        let result =
+            capture(&client, &url, HeaderMap::new(), &stream, max, &spool)
+                .await
+                .map(|response| {
+                    String::from_utf8(response.body.to_vec()).unwrap()
+                })
+                .map_err(|error| format!("{error:#}"));
+        drop(client);
+        (result, sent_rx.await.unwrap_or_default())
+    }
+
+    /// Strip the masks from frames sent by the client.
+    fn unmask(mut sent: &[u8]) -> Vec<(u8, Vec<u8>)> {
+        let mut frames = Vec::new();
+        while let [head, len, a, b, c, d, rest @ ..] = sent {
+            let len = usize::from(len & 0x7f);
+            let payload = rest[..len]
+                .iter()
+                .zip([a, b, c, d].into_iter().cycle())
+                .map(|(byte, mask)| byte ^ mask)
+                .collect();
+            frames.push((*head, payload));
+            sent = &rest[len..];
+        }
+        frames
+    }
+
+    #[tokio::test]
+    async fn test_websocket() {
+        let second = Duration::from_secs(1);
+
+        // A ping between fragments is answered without breaking the message.
+        let (result, sent) =
+            websocket(b"\x01\x01a\x89\x01p\x80\x01b\x88\x00", second, None)
+                .await;
+        check!(result.as_deref() == Ok("ab"));
+        check!(unmask(&sent) == [(0x8a, b"p".to_vec()), (0x88, vec![])]);
+
+        // The server closing ends the messages, and the client replies.
+        let (result, sent) = websocket(
+            b"\x81\x01a\x88\x02\x03\xe8\x81\x01b",
+            Duration::from_secs(30),
+            None,
+        )
+        .await;
+        check!(result.as_deref() == Ok("a"));
+        check!(unmask(&sent) == [(0x88, vec![])]);
+
+        // Running out of time mid-frame keeps the messages so far.
+        let start = Instant::now();
+        let (result, _) =
+            websocket(b"\x81\x01a\x81\x05he", Duration::from_millis(200), None)
+                .await;
+        check!(result.as_deref() == Ok("a"));
+        check!(start.elapsed() < Duration::from_secs(5));
+
+        // Frames and messages larger than `max` are refused before they’re
+        // read.
+        let (result, _) = websocket(b"\x81\x05hello", second, Some(4)).await;
+        check!(result.unwrap_err().contains("frame is larger than 4 bytes"));
+        let (result, _) =
+            websocket(b"\x01\x03hel\x80\x02lo", second, Some(4)).await;
+        check!(
+            result
+                .unwrap_err()
+                .contains("message is larger than 4 bytes")
+        );
+        let (result, _) = websocket(
+            b"\x81\x7f\xff\xff\xff\xff\xff\xff\xff\xff",
+            second,
+            None,
+        )
+        .await;
+        check!(result.unwrap_err().contains("frame is larger than"));
+
+        // Invalid text and out of order fragments are errors.
+        let (result, _) = websocket(b"\x81\x02\xff\xfe", second, None).await;
+        check!(result.unwrap_err().contains("invalid utf-8"));
+        let (result, _) = websocket(b"\x01\x01a\x81\x01b", second, None).await;
+        check!(result.unwrap_err().contains("out of order"));
+        let (result, _) = websocket(b"\x80\x01a", second, None).await;
+        check!(result.unwrap_err().contains("out of order"));
+        let (result, _) = websocket(b"\x09\x01p", second, None).await;
+        check!(result.unwrap_err().contains("invalid WebSocket control"));
+    }
+
+    #[tokio::test]
+    async fn test_capture() {
+        let client = reqwest::Client::builder().http1_only().build().unwrap();
+        let spool = Spool { dir: std::env::temp_dir(), max_memory: 1 << 20 };
+
+        let url = serve(
+            |_| {
+                "HTTP/1.1 200 OK\r\nContent-Type: text/event-stream\r\n\r\n\
+                : comment\r\ndata: {\"b\": 1,\r\ndata:  \"a\": 2}\r\n\r\n\
+                event: status\r\ndata: up\r\n\r\ndata: later\r\n\r\n"
+                    .to_owned()
+            },
+            |_| async {},
+        )
+        .await;
+        let stream = Stream { count: Some(2), ..Stream::default() };
+        let response =
+            capture(&client, &url, HeaderMap::new(), &stream, None, &spool)
+                .await
+                .unwrap();
+        check!(response.body == "{\"a\":2,\"b\":1}\n[status] up");
+
+        let url = serve(accept, |mut socket| async move {
+            // A ping, then “bc” split in two frames, then “a”.
+            socket
+                .write_all(b"\x89\x00\x01\x01b\x80\x01c\x81\x01a")
+                .await
+                .unwrap();
+            // The subscription message, then the pong, both masked.
+            let mut frames = [0; 15 + 6];
+            socket.read_exact(&mut frames).await.unwrap();
+            check!(frames[..2] == [0x81, 0x80 | 9]);
+            check!(frames[15..17] == [0x8a, 0x80]);
+            // Wait for the close frame.
+            let _ = socket.read(&mut [0; 6]).await;
+        })
+        .await;
+        let mut url = url;
+        url.set_scheme("ws").unwrap();
+        let stream = Stream {
+            count: Some(2),
+            send: Some("subscribe".to_owned()),
+            sort: true,
+            ..Stream::default()
+        };
+        let response =
+            capture(&client, &url, HeaderMap::new(), &stream, None, &spool)
+                .await
+                .unwrap();
+        check!(response.url.scheme() == "ws");
+        check!(response.body == "a\nbc");
+    }
+}
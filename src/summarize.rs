@@ -0,0 +1,244 @@
+//! Shortening diffs so they fit in notifications.
+//!
+//! Chat services and mail servers limit the size of messages, and a diff of a
+//! rewritten page isn’t readable in a notification anyway. Long runs of changed
+//! lines are collapsed into a line like `… 87 more changed lines …`, and the
+//! diff is cut off once it reaches the limits.
+
+use std::borrow::Cow;
+use std::fmt;
+use std::fmt::Write as _;
+
+/// Default maximum number of lines in a notification.
+pub const DEFAULT_MAX_LINES: usize = 100;
+
+/// Default maximum size of a notification in bytes.
+pub const DEFAULT_MAX_BYTES: usize = 16_000;
+
+/// Minimum number of changed lines to show in a row.
+const MIN_RUN: usize = 3;
+
+/// Minimum length of a line in bytes before it’s shortened.
+const MIN_LINE_BYTES: usize = 80;
+
+/// Bytes to leave for the final `… N more changed lines …`.
+const MARKER_BYTES: usize = 48;
+
+/// How long a notification may be.
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+pub struct Limits {
+    /// Maximum number of lines.
+    pub lines: usize,
+
+    /// Maximum size in bytes.
+    pub bytes: usize,
+}
+
+impl Default for Limits {
+    fn default() -> Self {
+        Self { lines: DEFAULT_MAX_LINES, bytes: DEFAULT_MAX_BYTES }
+    }
+}
+
+impl Limits {
+    /// Check if text is within the limits.
+    #[must_use]
+    pub fn fits(&self, text: &str) -> bool {
+        text.len() <= self.bytes && text.lines().count() <= self.lines
+    }
+}
+
+/// The number of lines added and removed in a diff.
+#[derive(Clone, Copy, Debug, Default, Eq, PartialEq)]
+pub struct Counts {
+    /// Lines starting with `+`.
+    pub added: usize,
+
+    /// Lines starting with `-`.
+    pub removed: usize,
+}
+
+impl Counts {
+    /// Count the lines added and removed in a diff.
+    #[must_use]
+    pub fn of(diff: &str) -> Self {
+        let mut counts = Self::default();
+        for line in diff.lines() {
+            if line.starts_with('+') {
+                counts.added = counts.added.saturating_add(1);
+            } else if line.starts_with('-') {
+                counts.removed = counts.removed.saturating_add(1);
+            }
+        }
+        counts
+    }
+
+    /// Check if nothing was added or removed.
+    #[must_use]
+    pub const fn is_empty(&self) -> bool {
+        self.added == 0 && self.removed == 0
+    }
+}
+
+impl fmt::Display for Counts {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "+{} -{} lines", self.added, self.removed)
+    }
+}
+
+/// A line of a summarized diff.
+#[derive(Clone, Copy, Debug)]
+enum Piece<'a> {
+    /// A line from the diff.
+    Line(&'a str),
+
+    /// Changed lines that were left out.
+    Collapsed(usize),
+}
+
+impl Piece<'_> {
+    /// The number of lines from the diff this represents.
+    const fn lines(&self) -> usize {
+        match self {
+            Self::Line(_) => 1,
+            Self::Collapsed(count) => *count,
+        }
+    }
+
+    /// The number of changed lines from the diff this represents.
+    fn changed(&self) -> usize {
+        match self {
+            Self::Line(line) => usize::from(is_changed(line)),
+            Self::Collapsed(count) => *count,
+        }
+    }
+}
+
+/// Shorten a diff to fit within `limits`.
+///
+/// Text that already fits is returned unchanged. Otherwise, runs of changed
+/// lines longer than a quarter of the line limit are collapsed, very long lines
+/// are shortened, and the diff is cut off with a line saying how many changed
+/// lines were left out.
+pub fn summarize(diff: &str, limits: Limits) -> Cow<'_, str> {
+    if limits.fits(diff) {
+        return Cow::Borrowed(diff);
+    }
+
+    let max_run = limits.lines.checked_div(4).unwrap_or(0).max(MIN_RUN);
+    let mut pieces = Vec::new();
+    let mut run = 0_usize;
+    for line in diff.lines() {
+        if !is_changed(line) {
+            run = 0;
+        } else if run < max_run {
+            run = run.saturating_add(1);
+        } else {
+            if let Some(Piece::Collapsed(count)) = pieces.last_mut() {
+                *count = count.saturating_add(1);
+            } else {
+                pieces.push(Piece::Collapsed(1));
+            }
+            continue;
+        }
+        pieces.push(Piece::Line(line));
+    }
+
+    let max_bytes = limits.bytes.saturating_sub(MARKER_BYTES);
+    let max_line_bytes = limits
+        .bytes
+        .checked_div(4)
+        .unwrap_or(0)
+        .max(MIN_LINE_BYTES)
+        .min(max_bytes.saturating_sub(1));
+    let max_lines = limits.lines.saturating_sub(1);
+    let mut summary = String::new();
+    for (i, piece) in pieces.iter().enumerate() {
+        let line = match piece {
+            Piece::Line(line) => shorten(line, max_line_bytes),
+            Piece::Collapsed(count) => Cow::Owned(marker(*count, *count)),
+        };
+        if i >= max_lines
+            || summary.len().saturating_add(line.len()) >= max_bytes
+        {
+            let rest = pieces.get(i..).unwrap_or_default();
+            let _ = writeln!(
+                summary,
+                "{}",
+                marker(
+                    rest.iter().map(Piece::changed).sum(),
+                    rest.iter().map(Piece::lines).sum(),
+                ),
+            );
+            break;
+        }
+        let _ = writeln!(summary, "{line}");
+    }
+    Cow::Owned(summary)
+}
+
+/// Check if a line of a diff was added or removed.
+fn is_changed(line: &str) -> bool {
+    line.starts_with(['+', '-'])
+}
+
+/// Make the line that replaces lines that were left out.
+fn marker(changed: usize, lines: usize) -> String {
+    if changed == 0 {
+        format!("… {lines} more lines …")
+    } else {
+        format!("… {changed} more changed lines …")
+    }
+}
+
+/// Shorten a line to at most `max` bytes, ending it with `…` if it was cut.
+fn shorten(line: &str, max: usize) -> Cow<'_, str> {
+    if line.len() <= max {
+        Cow::Borrowed(line)
+    } else {
+        let end = line.floor_char_boundary(max.saturating_sub('…'.len_utf8()));
+        Cow::Owned(format!("{}…", line.get(..end).unwrap_or_default()))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use assert2::check;
+
+    #[test]
+    fn test_summarize() {
+        let limits = Limits { lines: 12, bytes: 1000 };
+        check!(summarize(" a\n+b\n", limits) == " a\n+b\n");
+
+        let mut diff = String::from(" context\n");
+        for i in 0..20 {
+            let _ = writeln!(diff, "-old {i}");
+        }
+        diff.push_str(" middle\n+new\n end\n");
+        check!(Counts::of(&diff) == Counts { added: 1, removed: 20 });
+        check!(Counts::of(&diff).to_string() == "+1 -20 lines");
+
+        check!(
+            summarize(&diff, limits)
+                == " context\n-old 0\n-old 1\n-old 2\n\
+                    … 17 more changed lines …\n middle\n+new\n end\n"
+        );
+
+        let limits = Limits { lines: 4, bytes: 1000 };
+        check!(
+            summarize(&diff, limits)
+                == " context\n-old 0\n-old 1\n… 19 more changed lines …\n"
+        );
+
+        let limits = Limits { lines: 4, bytes: 400 };
+        check!(
+            summarize(&format!("+{}\n", "é".repeat(300)), limits)
+                == format!("+{}…\n", "é".repeat(48))
+        );
+        check!(
+            summarize(&" x\n".repeat(10), limits)
+                == " x\n x\n x\n… 7 more lines …\n"
+        );
+    }
+}
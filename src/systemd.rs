@@ -0,0 +1,189 @@
+//! Integration with systemd services of `Type=notify`.
+//!
+//! systemd passes the path of a socket in `NOTIFY_SOCKET`, and the watchdog
+//! interval in `WATCHDOG_USEC` if `WatchdogSec` is set for the unit. Messages
+//! like `READY=1` are sent to the socket as datagrams. When not running under
+//! systemd, all of this does nothing.
+
+use std::env;
+use std::time::Duration;
+
+/// Where to send notifications to the service manager.
+#[derive(Clone, Debug, Default)]
+pub struct Systemd {
+    /// The notification socket, if running under systemd.
+    socket: Option<String>,
+
+    /// How often the watchdog must be pinged, if it’s enabled.
+    watchdog: Option<Duration>,
+}
+
+impl Systemd {
+    /// Read the notification socket and watchdog interval from the
+    /// environment.
+    #[must_use]
+    pub fn from_env() -> Self {
+        let socket = env::var("NOTIFY_SOCKET")
+            .ok()
+            .filter(|socket| !socket.is_empty());
+        let watchdog = socket.as_ref().and_then(|_| {
+            parse_watchdog(
+                env::var("WATCHDOG_USEC").ok().as_deref(),
+                env::var("WATCHDOG_PID").ok().as_deref(),
+                std::process::id(),
+            )
+        });
+        Self { socket, watchdog }
+    }
+
+    /// How often to ping the watchdog, if it’s enabled.
+    ///
+    /// This is half the interval systemd expects, to leave room for delays.
+    #[must_use]
+    pub fn watchdog_interval(&self) -> Option<Duration> {
+        self.watchdog.and_then(|watchdog| watchdog.checked_div(2))
+    }
+
+    /// Tell systemd that startup is finished.
+    pub fn ready(&self) {
+        self.notify("READY=1");
+    }
+
+    /// Tell systemd that the service is still alive.
+    pub fn ping(&self) {
+        if self.watchdog.is_some() {
+            self.notify("WATCHDOG=1");
+        }
+    }
+
+    /// Set the status shown by `systemctl status`.
+    pub fn status(&self, status: &str) {
+        self.notify(&format!("STATUS={}", status.replace('\n', " ")));
+    }
+
+    /// Tell systemd that the service is shutting down.
+    pub fn stopping(&self) {
+        self.notify("STOPPING=1");
+    }
+
+    /// Send a message to the notification socket.
+    ///
+    /// Errors are logged rather than returned, since the service should keep
+    /// working even if systemd can’t be notified.
+    fn notify(&self, message: &str) {
+        let Some(socket) = &self.socket else {
+            return;
+        };
+        if let Err(error) = send(socket, message) {
+            tracing::warn!("Could not notify systemd at {socket:?}: {error}");
+        } else {
+            tracing::trace!("Notified systemd: {message}");
+        }
+    }
+}
+
+/// Send a datagram to a notification socket.
+///
+/// Sockets starting with `@` are in the abstract namespace.
+///
+/// # Errors
+///
+/// Returns an error if the message couldn’t be sent.
+#[cfg(unix)]
+fn send(socket: &str, message: &str) -> std::io::Result<()> {
+    use std::os::unix::net::UnixDatagram;
+
+    let sender = UnixDatagram::unbound()?;
+    #[cfg(any(target_os = "linux", target_os = "android"))]
+    if let Some(name) = socket.strip_prefix('@') {
+        #[cfg(target_os = "android")]
+        use std::os::android::net::SocketAddrExt as _;
+        #[cfg(target_os = "linux")]
+        use std::os::linux::net::SocketAddrExt as _;
+
+        let address = std::os::unix::net::SocketAddr::from_abstract_name(name)?;
+        sender.send_to_addr(message.as_bytes(), &address)?;
+        return Ok(());
+    }
+    sender.send_to(message.as_bytes(), socket)?;
+    Ok(())
+}
+
+/// Send a datagram to a notification socket.
+///
+/// # Errors
+///
+/// Always returns an error, since notification sockets are Unix sockets.
+#[cfg(not(unix))]
+fn send(_socket: &str, _message: &str) -> std::io::Result<()> {
+    Err(std::io::ErrorKind::Unsupported.into())
+}
+
+/// Parse the watchdog interval from `WATCHDOG_USEC`.
+///
+/// If `WATCHDOG_PID` is set, the watchdog is only enabled if it’s `pid`, since
+/// otherwise it was meant for another process.
+fn parse_watchdog(
+    usec: Option<&str>,
+    watchdog_pid: Option<&str>,
+    pid: u32,
+) -> Option<Duration> {
+    if watchdog_pid.is_some_and(|watchdog_pid| watchdog_pid != pid.to_string())
+    {
+        return None;
+    }
+    usec.and_then(|usec| usec.parse().ok())
+        .filter(|usec| *usec > 0)
+        .map(Duration::from_micros)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use assert2::check;
+
+    #[test]
+    fn test_parse_watchdog() {
+        check!(
+            parse_watchdog(Some("30000000"), None, 5)
+                == Some(Duration::from_secs(30))
+        );
+        check!(
+            parse_watchdog(Some("30000000"), Some("5"), 5)
+                == Some(Duration::from_secs(30))
+        );
+        check!(parse_watchdog(Some("30000000"), Some("6"), 5).is_none());
+        check!(parse_watchdog(Some("0"), None, 5).is_none());
+        check!(parse_watchdog(Some("soon"), None, 5).is_none());
+        check!(parse_watchdog(None, None, 5).is_none());
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn test_notify() {
+        use std::os::unix::net::UnixDatagram;
+
+        let path = env::temp_dir()
+            .join(format!("monitorbot-{}-notify", std::process::id()));
+        let _ = std::fs::remove_file(&path);
+        let receiver = UnixDatagram::bind(&path).unwrap();
+        let systemd = Systemd {
+            socket: Some(path.to_str().unwrap().to_owned()),
+            watchdog: Some(Duration::from_secs(10)),
+        };
+
+        systemd.ready();
+        systemd.ping();
+        systemd.status("Checked 2 URLs\nall good");
+        let mut buffer = [0; 64];
+        for expected in
+            ["READY=1", "WATCHDOG=1", "STATUS=Checked 2 URLs all good"]
+        {
+            let len = receiver.recv(&mut buffer).unwrap();
+            check!(&buffer[..len] == expected.as_bytes());
+        }
+        check!(systemd.watchdog_interval() == Some(Duration::from_secs(5)));
+
+        std::fs::remove_file(path).unwrap();
+    }
+}
@@ -0,0 +1,515 @@
+//! Measuring how long each part of checking a URL takes.
+//!
+//! DNS lookups and connection setup happen inside reqwest, so they are
+//! measured with a custom DNS resolver and a connector layer that record into
+//! a shared [`Recorder`]. The connector layer wraps reqwest’s TLS connector,
+//! which connects and does the TLS handshake in one call, so TLS can’t be
+//! timed separately from connecting. URLs are checked one at a time, so
+//! everything recorded between [`Recorder::start()`] and [`Recorder::finish()`]
+//! belongs to one URL.
+
+use chrono::Utc;
+use clap::ValueEnum;
+use std::collections::BTreeMap;
+use std::fmt;
+use std::fs;
+use std::future::Future;
+use std::io::{self, Write};
+use std::net::IpAddr;
+use std::path::{Path, PathBuf};
+use std::pin::Pin;
+use std::str::FromStr;
+use std::sync::{Arc, Mutex, MutexGuard, PoisonError};
+use std::task::{Context, Poll};
+use std::time::{Duration, Instant};
+use url::Url;
+
+use crate::dns::AddressFamily;
+use crate::error::ErrorReport;
+use crate::junit;
+use crate::output::format_size;
+use crate::progress::Outcome;
+
+/// Format of the report printed at the end of a run.
+#[derive(Clone, Copy, Debug, Eq, PartialEq, clap::ValueEnum)]
+pub enum ReportFormat {
+    /// One line per URL.
+    Text,
+
+    /// A JSON array with an object for each URL.
+    Json,
+
+    /// A `JUnit` XML report with a test case for each URL, for CI systems.
+    Junit,
+}
+
+/// A report to print at the end of a run, and where to write it.
+///
+/// Parsed from `FORMAT` or `FORMAT=PATH`. Without a path the report is
+/// printed to stderr.
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub struct Report {
+    /// The format of the report.
+    pub format: ReportFormat,
+
+    /// The file to write the report to, if any.
+    pub path: Option<PathBuf>,
+}
+
+impl FromStr for Report {
+    type Err = String;
+
+    fn from_str(input: &str) -> Result<Self, Self::Err> {
+        let (format, path) = match input.split_once('=') {
+            Some((format, path)) if !path.is_empty() => {
+                (format, Some(PathBuf::from(path)))
+            }
+            Some(_) => return Err("expected a path after =".to_owned()),
+            None => (input, None),
+        };
+        Ok(Self { format: ReportFormat::from_str(format, true)?, path })
+    }
+}
+
+/// How long each part of checking a URL took.
+#[derive(Clone, Debug, Default, serde::Serialize)]
+pub struct Timing {
+    /// The URL that was checked.
+    pub url: Option<Url>,
+
+    /// The HTTP version of the response, e.g. `HTTP/2.0`.
+    pub http_version: Option<String>,
+
+    /// The address of the server that sent the response, if known.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub peer_address: Option<IpAddr>,
+
+    /// Total time to check the URL.
+    #[serde(serialize_with = "as_millis")]
+    pub total: Duration,
+
+    /// Time spent resolving host names.
+    #[serde(serialize_with = "as_millis")]
+    pub dns: Duration,
+
+    /// Time spent establishing connections, including TLS handshakes but not
+    /// DNS. This is zero if an existing connection was reused.
+    ///
+    /// There’s no separate time for TLS; see the [module docs](self).
+    #[serde(serialize_with = "as_millis")]
+    pub connect: Duration,
+
+    /// Number of new connections opened. Zero means existing connections
+    /// were reused.
+    pub connections: usize,
+
+    /// Time from sending the request to receiving the response headers. This
+    /// includes `dns` and `connect`.
+    #[serde(serialize_with = "as_millis")]
+    pub ttfb: Duration,
+
+    /// Time spent downloading the body.
+    #[serde(serialize_with = "as_millis")]
+    pub download: Duration,
+
+    /// Time spent rendering the old and new versions.
+    #[serde(serialize_with = "as_millis")]
+    pub render: Duration,
+
+    /// Time spent diffing and outputting changes.
+    #[serde(serialize_with = "as_millis")]
+    pub diff: Duration,
+
+    /// Number of bytes of response bodies downloaded, including pages and
+    /// artifacts.
+    pub bytes: usize,
+
+    /// The error checking the URL, if it failed.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub error: Option<ErrorReport>,
+
+    /// What happened when checking the URL.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub outcome: Option<Outcome>,
+
+    /// The text of the notifications sent about the URL, used to explain
+    /// failures in the `JUnit` report.
+    #[serde(skip)]
+    pub notifications: Vec<String>,
+}
+
+impl fmt::Display for Timing {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        if let Some(url) = &self.url {
+            write!(f, "{url}: ")?;
+        }
+        if let Some(http_version) = &self.http_version {
+            write!(f, "{http_version}, ")?;
+            if let Some(addr) = self.peer_address {
+                write!(f, "{} {addr}, ", AddressFamily::of(addr))?;
+            }
+            match self.connections {
+                0 => write!(f, "reused connection, ")?,
+                1 => write!(f, "new connection, ")?,
+                n => write!(f, "{n} new connections, ")?,
+            }
+        }
+        write!(
+            f,
+            "total {} (dns {}, connect {}, ttfb {}, download {}, render {}, \
+            diff {}), {}",
+            Millis(self.total),
+            Millis(self.dns),
+            Millis(self.connect),
+            Millis(self.ttfb),
+            Millis(self.download),
+            Millis(self.render),
+            Millis(self.diff),
+            format_size(self.bytes),
+        )?;
+        if let Some(error) = &self.error {
+            write!(f, ", failed with {} error", error.kind)?;
+        }
+        Ok(())
+    }
+}
+
+/// Display a duration in milliseconds.
+struct Millis(Duration);
+
+impl fmt::Display for Millis {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{:.1}ms", self.0.as_secs_f64() * 1000.0)
+    }
+}
+
+/// Serialize a duration as fractional milliseconds.
+fn as_millis<S: serde::Serializer>(
+    duration: &Duration,
+    serializer: S,
+) -> Result<S::Ok, S::Error> {
+    serializer.serialize_f64(duration.as_secs_f64() * 1000.0)
+}
+
+/// Print a report of timings for a run.
+///
+/// # Errors
+///
+/// Returns an error if the report could not be written.
+pub fn print_report(
+    out: &mut dyn Write,
+    format: ReportFormat,
+    timings: &[Timing],
+) -> io::Result<()> {
+    match format {
+        ReportFormat::Text => {
+            for timing in timings {
+                writeln!(out, "{timing}")?;
+            }
+            for (host, (urls, connections)) in connections_by_host(timings) {
+                writeln!(
+                    out,
+                    "{host}: {urls} URLs, {connections} new connections",
+                )?;
+            }
+            writeln!(
+                out,
+                "Downloaded {} in total",
+                format_size(total_bytes(timings)),
+            )?;
+        }
+        ReportFormat::Json => {
+            serde_json::to_writer_pretty(&mut *out, timings)?;
+            writeln!(out)?;
+        }
+        ReportFormat::Junit => {
+            junit::write(out, timings, Utc::now())?;
+        }
+    }
+    Ok(())
+}
+
+/// Write a report of timings for a run to a file.
+///
+/// # Errors
+///
+/// Returns an error if the file could not be written.
+pub fn write_report(
+    path: &Path,
+    format: ReportFormat,
+    timings: &[Timing],
+) -> io::Result<()> {
+    let mut out = io::BufWriter::new(fs::File::create(path)?);
+    print_report(&mut out, format, timings)?;
+    out.flush()
+}
+
+/// Add up the bytes downloaded for all URLs.
+#[must_use]
+pub fn total_bytes(timings: &[Timing]) -> usize {
+    timings
+        .iter()
+        .fold(0, |total, timing| total.saturating_add(timing.bytes))
+}
+
+/// Count the URLs checked and new connections opened for each host.
+fn connections_by_host(timings: &[Timing]) -> BTreeMap<&str, (usize, usize)> {
+    let mut by_host = BTreeMap::<&str, (usize, usize)>::new();
+    for timing in timings {
+        let Some(host) = timing.url.as_ref().and_then(Url::host_str) else {
+            continue;
+        };
+        let (urls, connections) = by_host.entry(host).or_default();
+        *urls = urls.saturating_add(1);
+        *connections = connections.saturating_add(timing.connections);
+    }
+    by_host
+}
+
+/// Shared place to record timings for the URL currently being checked.
+#[derive(Clone, Debug, Default)]
+pub struct Recorder {
+    /// The timing being recorded, and when checking the URL started.
+    inner: Arc<Mutex<(Timing, Option<Instant>)>>,
+}
+
+impl Recorder {
+    /// Start recording timings for a URL.
+    pub fn start(&self, url: &Url) {
+        *self.lock() = (
+            Timing { url: Some(url.clone()), ..Timing::default() },
+            Some(Instant::now()),
+        );
+    }
+
+    /// Stop recording timings for the current URL and return them.
+    #[must_use]
+    pub fn finish(&self) -> Timing {
+        let (mut timing, started) = std::mem::take(&mut *self.lock());
+        timing.total = started.map(|s| s.elapsed()).unwrap_or_default();
+        // The connector does DNS resolution too.
+        timing.connect = timing.connect.saturating_sub(timing.dns);
+        timing
+    }
+
+    /// Add a duration to one of the timings.
+    pub fn add(
+        &self,
+        field: fn(&mut Timing) -> &mut Duration,
+        elapsed: Duration,
+    ) {
+        let mut inner = self.lock();
+        let duration = field(&mut inner.0);
+        *duration = duration.saturating_add(elapsed);
+        drop(inner);
+    }
+
+    /// Record that a new connection was opened.
+    pub fn add_connection(&self) {
+        let mut inner = self.lock();
+        inner.0.connections = inner.0.connections.saturating_add(1);
+        drop(inner);
+    }
+
+    /// Record that part of a response body was downloaded.
+    pub fn add_bytes(&self, bytes: usize) {
+        let mut inner = self.lock();
+        inner.0.bytes = inner.0.bytes.saturating_add(bytes);
+        drop(inner);
+    }
+
+    /// Record the HTTP version of the response and the address of the server
+    /// that sent it.
+    pub fn set_response(
+        &self,
+        version: http::Version,
+        peer_address: Option<IpAddr>,
+    ) {
+        let mut inner = self.lock();
+        inner.0.http_version = Some(format!("{version:?}"));
+        inner.0.peer_address = peer_address;
+        drop(inner);
+    }
+
+    /// Lock the timing being recorded.
+    ///
+    /// Nothing panics while holding the lock, so a poisoned lock is fine.
+    fn lock(&self) -> MutexGuard<'_, (Timing, Option<Instant>)> {
+        self.inner.lock().unwrap_or_else(PoisonError::into_inner)
+    }
+
+    /// Run a function and add how long it took to one of the timings.
+    pub fn time<T, F: FnOnce() -> T>(
+        &self,
+        field: fn(&mut Timing) -> &mut Duration,
+        f: F,
+    ) -> T {
+        let started = Instant::now();
+        let result = f();
+        self.add(field, started.elapsed());
+        result
+    }
+}
+
+/// DNS resolver that records how long lookups take.
+///
+/// It can also restrict addresses to one family, for `--ipv4` and `--ipv6`.
+#[derive(Clone, Debug)]
+pub struct Resolver {
+    /// Where to record timings.
+    pub recorder: Recorder,
+
+    /// Only return addresses in this family, if set.
+    pub family: Option<AddressFamily>,
+}
+
+impl reqwest::dns::Resolve for Resolver {
+    fn resolve(&self, name: reqwest::dns::Name) -> reqwest::dns::Resolving {
+        let recorder = self.recorder.clone();
+        let family = self.family;
+        Box::pin(async move {
+            let started = Instant::now();
+            let result =
+                tokio::net::lookup_host((name.as_str().to_owned(), 0)).await;
+            recorder.add(|t| &mut t.dns, started.elapsed());
+            let Some(family) = family else {
+                return Ok(Box::new(result?) as reqwest::dns::Addrs);
+            };
+            let addrs: Vec<_> =
+                result?.filter(|addr| family.contains(addr.ip())).collect();
+            if addrs.is_empty() {
+                return Err(io::Error::new(
+                    io::ErrorKind::NotFound,
+                    format!("no {family} addresses for {}", name.as_str()),
+                )
+                .into());
+            }
+            Ok(Box::new(addrs.into_iter()) as reqwest::dns::Addrs)
+        })
+    }
+}
+
+/// Connector layer that records how long it takes to establish connections,
+/// and how many are opened.
+#[derive(Clone, Debug)]
+pub struct ConnectLayer {
+    /// Where to record timings.
+    pub recorder: Recorder,
+}
+
+impl<S> tower_layer::Layer<S> for ConnectLayer {
+    type Service = ConnectService<S>;
+
+    fn layer(&self, inner: S) -> Self::Service {
+        ConnectService { inner, recorder: self.recorder.clone() }
+    }
+}
+
+/// Connector service created by [`ConnectLayer`].
+#[derive(Clone, Debug)]
+pub struct ConnectService<S> {
+    /// The wrapped connector.
+    inner: S,
+
+    /// Where to record timings.
+    recorder: Recorder,
+}
+
+impl<S, R> tower_service::Service<R> for ConnectService<S>
+where
+    S: tower_service::Service<R>,
+    S::Future: Send + 'static,
+{
+    type Response = S::Response;
+    type Error = S::Error;
+    type Future = Pin<
+        Box<dyn Future<Output = Result<Self::Response, Self::Error>> + Send>,
+    >;
+
+    fn poll_ready(
+        &mut self,
+        cx: &mut Context<'_>,
+    ) -> Poll<Result<(), Self::Error>> {
+        self.inner.poll_ready(cx)
+    }
+
+    fn call(&mut self, request: R) -> Self::Future {
+        let recorder = self.recorder.clone();
+        let started = Instant::now();
+        let future = self.inner.call(request);
+        Box::pin(async move {
+            let result = future.await;
+            recorder.add(|t| &mut t.connect, started.elapsed());
+            if result.is_ok() {
+                tracing::debug!("Opened new connection");
+                recorder.add_connection();
+            }
+            result
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use assert2::check;
+
+    #[test]
+    fn test_recorder() {
+        let recorder = Recorder::default();
+        recorder.start(&"https://example.com/".parse().unwrap());
+        recorder.add(|t| &mut t.dns, Duration::from_millis(5));
+        recorder.add(|t| &mut t.connect, Duration::from_millis(20));
+        recorder.add(|t| &mut t.render, Duration::from_millis(1));
+        recorder.add(|t| &mut t.render, Duration::from_millis(2));
+        recorder.add_bytes(1000);
+        recorder.add_bytes(234);
+
+        let timing = recorder.finish();
+        check!(timing.dns == Duration::from_millis(5));
+        check!(timing.connect == Duration::from_millis(15));
+        check!(timing.render == Duration::from_millis(3));
+        check!(timing.bytes == 1234);
+        check!(total_bytes(&[timing.clone(), timing.clone()]) == 2468);
+        check!(
+            timing.to_string()
+                == format!(
+                    "https://example.com/: total {} (dns 5.0ms, connect \
+                    15.0ms, ttfb 0.0ms, download 0.0ms, render 3.0ms, diff \
+                    0.0ms), 1.2 kB",
+                    Millis(timing.total),
+                )
+        );
+
+        check!(recorder.finish().url.is_none());
+    }
+
+    #[test]
+    fn test_connections() {
+        let recorder = Recorder::default();
+        recorder.start(&"https://example.com/a".parse().unwrap());
+        recorder.set_response(
+            http::Version::HTTP_11,
+            Some("2001:db8::1".parse().unwrap()),
+        );
+        recorder.add_connection();
+        let first = recorder.finish();
+        check!(
+            first
+                .to_string()
+                .contains("HTTP/1.1, IPv6 2001:db8::1, new connection, ")
+        );
+
+        recorder.start(&"https://example.com/b".parse().unwrap());
+        recorder.set_response(http::Version::HTTP_11, None);
+        let second = recorder.finish();
+        check!(second.to_string().contains("HTTP/1.1, reused connection, "));
+
+        let mut out = Vec::new();
+        print_report(&mut out, ReportFormat::Text, &[first, second]).unwrap();
+        check!(String::from_utf8(out).unwrap().ends_with(
+            "\nexample.com: 2 URLs, 1 new connections\n\
+                    Downloaded 0 B in total\n"
+        ));
+    }
+}
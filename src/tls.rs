@@ -0,0 +1,245 @@
+//! TLS options for URLs: client certificates, extra CA certificates, and
+//! turning off verification.
+//!
+//! Internal services often require mutual TLS, or use certificates signed by
+//! a private CA:
+//!
+//! ```toml
+//! [[url]]
+//! url = "https://dashboard.internal/"
+//! tls.client_certificate = "client.p12"   # Relative to the config file.
+//! tls.password = { env = "DASHBOARD_P12_PASSWORD" }
+//! tls.ca_certificate = "internal-ca.pem"
+//! ```
+//!
+//! URLs with TLS options get their own HTTP client, since reqwest configures
+//! TLS for a whole client.
+
+use anyhow::{Context, bail};
+use base64::Engine as _;
+use base64::engine::general_purpose::STANDARD as BASE64;
+use p12_keystore::{KeyStore, Pkcs12ImportPolicy};
+use std::fmt::Write as _;
+use std::fs;
+use std::path::{Path, PathBuf};
+
+use crate::secret::Secret;
+
+/// TLS options for a URL.
+#[derive(
+    Clone,
+    Debug,
+    Default,
+    Eq,
+    Hash,
+    PartialEq,
+    serde::Deserialize,
+    serde::Serialize,
+)]
+#[serde(deny_unknown_fields)]
+pub struct Tls {
+    /// Client certificate to present to the server.
+    ///
+    /// This is either a PEM file containing the certificate chain, or a
+    /// PKCS#12 file (`.p12` or `.pfx`) containing the certificate chain and
+    /// private key.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub client_certificate: Option<PathBuf>,
+
+    /// PEM file containing the private key for `client_certificate`, if it
+    /// isn’t in the same file.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub client_key: Option<PathBuf>,
+
+    /// Password for a PKCS#12 `client_certificate`.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub password: Option<Secret>,
+
+    /// PEM file of CA certificates to trust in addition to the system’s.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub ca_certificate: Option<PathBuf>,
+
+    /// Don’t verify the server’s certificate. This is only safe on networks
+    /// you trust.
+    #[serde(default, skip_serializing_if = "std::ops::Not::not")]
+    pub insecure: bool,
+}
+
+impl Tls {
+    /// Make relative paths relative to `base_dir`.
+    pub fn resolve_paths(&mut self, base_dir: &Path) {
+        for path in [
+            &mut self.client_certificate,
+            &mut self.client_key,
+            &mut self.ca_certificate,
+        ]
+        .into_iter()
+        .flatten()
+        {
+            if path.is_relative() {
+                *path = base_dir.join(&*path);
+            }
+        }
+    }
+
+    /// Configure a client builder with these options.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if a certificate or key couldn’t be loaded.
+    pub fn apply(
+        &self,
+        mut builder: reqwest::ClientBuilder,
+    ) -> anyhow::Result<reqwest::ClientBuilder> {
+        if let Some(certificate) = &self.client_certificate {
+            let password = self.password.as_ref().map(Secret::expose);
+            builder = builder.identity(
+                identity(
+                    certificate,
+                    self.client_key.as_deref(),
+                    password.transpose()?,
+                )
+                .with_context(|| {
+                    format!(
+                        "could not load client certificate {}",
+                        certificate.display(),
+                    )
+                })?,
+            );
+        } else if self.client_key.is_some() {
+            bail!("tls.client_key requires tls.client_certificate");
+        }
+        if let Some(path) = &self.ca_certificate {
+            builder = add_ca_certificates(builder, path)?;
+        }
+        Ok(builder.danger_accept_invalid_certs(self.insecure))
+    }
+}
+
+/// Trust the CA certificates in a PEM file in addition to the system’s.
+///
+/// # Errors
+///
+/// Returns an error if the file couldn’t be read or parsed.
+pub fn add_ca_certificates(
+    mut builder: reqwest::ClientBuilder,
+    path: &Path,
+) -> anyhow::Result<reqwest::ClientBuilder> {
+    let certificates = fs::read(path)
+        .map_err(anyhow::Error::from)
+        .and_then(|pem| Ok(reqwest::Certificate::from_pem_bundle(&pem)?))
+        .with_context(|| {
+            format!("could not load CA certificates {}", path.display())
+        })?;
+    for certificate in certificates {
+        builder = builder.add_root_certificate(certificate);
+    }
+    Ok(builder)
+}
+
+/// Load a client certificate and its private key.
+///
+/// # Errors
+///
+/// Returns an error if a file couldn’t be read or parsed, or doesn’t contain
+/// a private key.
+fn identity(
+    certificate: &Path,
+    key: Option<&Path>,
+    password: Option<&str>,
+) -> anyhow::Result<reqwest::Identity> {
+    let data = fs::read(certificate)?;
+    let mut pem = if is_pkcs12(certificate) {
+        if key.is_some() {
+            bail!("tls.client_key can’t be used with a PKCS#12 file");
+        }
+        pkcs12_to_pem(&data, password.unwrap_or_default())?.into_bytes()
+    } else {
+        data
+    };
+    if let Some(key) = key {
+        pem.push(b'\n');
+        pem.extend(fs::read(key).with_context(|| {
+            format!("could not read client key {}", key.display())
+        })?);
+    }
+    Ok(reqwest::Identity::from_pem(&pem)?)
+}
+
+/// Check if a file is PKCS#12 based on its extension.
+fn is_pkcs12(path: &Path) -> bool {
+    path.extension().is_some_and(|extension| {
+        extension.eq_ignore_ascii_case("p12")
+            || extension.eq_ignore_ascii_case("pfx")
+    })
+}
+
+/// Convert the private key and certificate chain in a PKCS#12 file to PEM.
+///
+/// rustls can only load client certificates from PEM.
+///
+/// # Errors
+///
+/// Returns an error if the file couldn’t be decrypted or parsed, or doesn’t
+/// contain a private key.
+fn pkcs12_to_pem(data: &[u8], password: &str) -> anyhow::Result<String> {
+    let store =
+        KeyStore::from_pkcs12(data, password, Pkcs12ImportPolicy::Strict)
+            .context("could not read PKCS#12 file")?;
+    let Some((_, chain)) = store.private_key_chain() else {
+        bail!("PKCS#12 file doesn’t contain a private key");
+    };
+    let mut pem = String::new();
+    write_pem(&mut pem, "PRIVATE KEY", chain.key().as_der());
+    for certificate in chain.certs() {
+        write_pem(&mut pem, "CERTIFICATE", certificate.as_der());
+    }
+    Ok(pem)
+}
+
+/// Append a PEM block to `out`.
+fn write_pem(out: &mut String, label: &str, der: &[u8]) {
+    let encoded = BASE64.encode(der);
+    let _ = writeln!(out, "-----BEGIN {label}-----");
+    // Base64 is ASCII, so splitting it into bytes keeps it valid UTF-8.
+    for line in encoded.as_bytes().chunks(64) {
+        out.push_str(&String::from_utf8_lossy(line));
+        out.push('\n');
+    }
+    let _ = writeln!(out, "-----END {label}-----");
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use assert2::check;
+
+    #[test]
+    fn test_write_pem() {
+        let mut pem = String::new();
+        write_pem(&mut pem, "CERTIFICATE", &[0; 51]);
+        check!(
+            pem == format!(
+                "-----BEGIN CERTIFICATE-----\n{}\nAAAA\n\
+                -----END CERTIFICATE-----\n",
+                "A".repeat(64),
+            )
+        );
+
+        check!(is_pkcs12(Path::new("client.p12")));
+        check!(is_pkcs12(Path::new("client.PFX")));
+        check!(!is_pkcs12(Path::new("client.pem")));
+
+        let mut tls = Tls {
+            client_certificate: Some("client.pem".into()),
+            ca_certificate: Some("/etc/ca.pem".into()),
+            ..Tls::default()
+        };
+        tls.resolve_paths(Path::new("/config"));
+        check!(
+            tls.client_certificate.as_deref()
+                == Some(Path::new("/config/client.pem"))
+        );
+        check!(tls.ca_certificate.as_deref() == Some(Path::new("/etc/ca.pem")));
+    }
+}
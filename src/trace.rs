@@ -0,0 +1,195 @@
+//! Writing HTTP requests and responses to files (`--trace-http`).
+//!
+//! When a site serves monitorbot different content than a browser, it helps to
+//! see exactly what was sent and received. For each request, this writes:
+//!
+//!   * `<prefix>.request`: the request line, headers, and body.
+//!   * `<prefix>.response`: the status line, headers, and body.
+//!   * `<prefix>.curl`: an equivalent `curl` command line.
+//!
+//! The prefix is the time of the request, a counter, and the URL. Headers
+//! added by the HTTP library, like `Host`, aren’t included, and the response
+//! body is after decompression. The files include secrets like
+//! `Authorization` headers.
+
+use std::fmt::Write as _;
+use std::fs;
+use std::io::{self, Write as _};
+use std::path::{Path, PathBuf};
+use std::sync::atomic::{AtomicUsize, Ordering};
+
+use crate::request::USER_AGENT;
+use crate::response::Response;
+use crate::state::fs_safe_url;
+
+/// A directory to write requests and responses to.
+#[derive(Debug)]
+pub struct HttpTrace {
+    /// Where to write the files.
+    dir: PathBuf,
+
+    /// Number of requests written, to keep file names unique.
+    count: AtomicUsize,
+}
+
+/// A request that was written, so that its response can be written next to it.
+#[derive(Debug)]
+pub struct Exchange {
+    /// The path of the files without the extension.
+    prefix: PathBuf,
+}
+
+impl HttpTrace {
+    /// Write files to `dir`, which is created if it doesn’t exist.
+    #[must_use]
+    pub fn new(dir: &Path) -> Self {
+        Self { dir: dir.to_owned(), count: AtomicUsize::new(0) }
+    }
+
+    /// Write a request and the equivalent `curl` command.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if the files couldn’t be written.
+    pub fn request(&self, request: &reqwest::Request) -> io::Result<Exchange> {
+        fs::create_dir_all(&self.dir)?;
+        let count = self.count.fetch_add(1, Ordering::Relaxed);
+        let prefix = self.dir.join(format!(
+            "{}-{count:03}-{}",
+            chrono::Utc::now().format("%Y%m%dT%H%M%S%.3fZ"),
+            fs_safe_url(request.url()),
+        ));
+
+        let mut head = format!(
+            "{} {} {:?}\n",
+            request.method(),
+            request.url(),
+            request.version(),
+        );
+        if !request.headers().contains_key(http::header::USER_AGENT) {
+            let _ = writeln!(head, "user-agent: {USER_AGENT}");
+        }
+        write_headers(&mut head, request.headers());
+        let body = request.body().and_then(reqwest::Body::as_bytes);
+        write_message(&prefix.with_added_extension("request"), &head, body)?;
+
+        fs::write(prefix.with_added_extension("curl"), curl_command(request))?;
+        Ok(Exchange { prefix })
+    }
+}
+
+impl Exchange {
+    /// Write the response to the request.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if the file couldn’t be written.
+    pub fn response(&self, response: &Response) -> io::Result<()> {
+        let mut head = format!("{:?} {}\n", response.version, response.status);
+        write_headers(&mut head, &response.headers);
+        let path = self.prefix.with_added_extension("response");
+        if let Some(spooled) = &response.spooled {
+            write_message(&path, &head, None)?;
+            let mut file = fs::OpenOptions::new().append(true).open(&path)?;
+            io::copy(&mut fs::File::open(spooled.path())?, &mut file)?;
+            Ok(())
+        } else {
+            write_message(&path, &head, Some(&response.body))
+        }
+    }
+}
+
+/// Add headers to the head of a message, one per line.
+fn write_headers(head: &mut String, headers: &http::HeaderMap) {
+    for (name, value) in headers {
+        let _ = writeln!(
+            head,
+            "{name}: {}",
+            String::from_utf8_lossy(value.as_bytes())
+        );
+    }
+}
+
+/// Write a message: its head, a blank line, and its body.
+fn write_message(
+    path: &Path,
+    head: &str,
+    body: Option<&[u8]>,
+) -> io::Result<()> {
+    let mut file = fs::File::create(path)?;
+    file.write_all(head.as_bytes())?;
+    file.write_all(b"\n")?;
+    file.write_all(body.unwrap_or_default())
+}
+
+/// Get a `curl` command line that makes the same request.
+#[must_use]
+pub fn curl_command(request: &reqwest::Request) -> String {
+    let mut command = String::from("curl --include --compressed");
+    match *request.method() {
+        http::Method::GET => {}
+        http::Method::HEAD => command.push_str(" --head"),
+        ref method => {
+            let _ = write!(command, " --request {}", method.as_str());
+        }
+    }
+    if !request.headers().contains_key(http::header::USER_AGENT) {
+        let _ = write!(command, " \\\n  --user-agent {}", quote(USER_AGENT));
+    }
+    for (name, value) in request.headers() {
+        let header =
+            format!("{name}: {}", String::from_utf8_lossy(value.as_bytes()));
+        let _ = write!(command, " \\\n  --header {}", quote(&header));
+    }
+    if let Some(body) = request.body().and_then(reqwest::Body::as_bytes) {
+        let _ = write!(
+            command,
+            " \\\n  --data-binary {}",
+            quote(&String::from_utf8_lossy(body)),
+        );
+    }
+    let _ = writeln!(command, " \\\n  {}", quote(request.url().as_str()));
+    command
+}
+
+/// Quote a string for the shell.
+fn quote(s: &str) -> String {
+    format!("'{}'", s.replace('\'', r"'\''"))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use assert2::check;
+
+    #[test]
+    fn test_curl_command() {
+        let client = reqwest::Client::new();
+        let request = client
+            .post("https://example.com/graphql?q=1")
+            .header("Authorization", "Bearer it's")
+            .body(r#"{"query":"{ a }"}"#)
+            .build()
+            .unwrap();
+        check!(
+            curl_command(&request)
+                == format!(
+                    "curl --include --compressed --request POST \\\n  \
+                    --user-agent '{USER_AGENT}' \\\n  \
+                    --header 'authorization: Bearer it'\\''s' \\\n  \
+                    --data-binary '{{\"query\":\"{{ a }}\"}}' \\\n  \
+                    'https://example.com/graphql?q=1'\n"
+                )
+        );
+
+        let request = client.head("https://example.com/").build().unwrap();
+        check!(
+            curl_command(&request)
+                == format!(
+                    "curl --include --compressed --head \\\n  \
+                    --user-agent '{USER_AGENT}' \\\n  \
+                    'https://example.com/'\n"
+                )
+        );
+    }
+}
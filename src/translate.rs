@@ -0,0 +1,353 @@
+//! Detecting the language of content and translating changes (`translate`).
+//!
+//! The language is guessed from the script the text is written in and, for
+//! Latin scripts, from common words. It only needs to be good enough to tell
+//! whether a change needs translating.
+//!
+//! Translation is done by a command run with `sh -c`. The added lines are
+//! written to its stdin, and it should write the translation to stdout.
+//! Details are passed in environment variables:
+//!
+//!   * `MONITORBOT_URL`: the URL.
+//!   * `MONITORBOT_LANGUAGE`: the detected language of the content, e.g. `de`.
+//!   * `MONITORBOT_TARGET_LANGUAGE`: the language to translate to, e.g. `en`.
+
+use anyhow::{Context, bail};
+use std::collections::HashSet;
+use std::process::Stdio;
+use tokio::io::AsyncWriteExt;
+use tokio::process::Command;
+use url::Url;
+
+/// Common words in languages written in the Latin script.
+///
+/// Each word should be rare in the other languages listed, so words like
+/// “de”, “que”, and “en” that are common in several aren’t.
+const STOPWORDS: &[(&str, &[&str])] = &[
+    (
+        "en",
+        &[
+            "the", "and", "of", "is", "that", "with", "for", "this", "are",
+            "was", "you", "have", "from", "which", "would",
+        ],
+    ),
+    (
+        "de",
+        &[
+            "der", "und", "ist", "nicht", "mit", "sich", "auf", "für", "ein",
+            "eine", "werden", "von", "dem", "auch", "wird",
+        ],
+    ),
+    (
+        "fr",
+        &[
+            "le", "les", "et", "des", "est", "une", "pour", "dans", "qui",
+            "sur", "pas", "au", "avec", "sont", "cette",
+        ],
+    ),
+    (
+        "es",
+        &[
+            "el", "los", "las", "y", "como", "más", "pero", "este", "esta",
+            "fue", "también", "muy", "sus", "hay", "cuando",
+        ],
+    ),
+    (
+        "it",
+        &[
+            "gli", "della", "di", "che", "è", "per", "sono", "nel", "alla",
+            "dei", "anche", "questo", "questa", "più", "delle",
+        ],
+    ),
+    (
+        "pt",
+        &[
+            "os", "não", "uma", "com", "em", "é", "dos", "das", "pelo", "pela",
+            "ao", "também", "são", "isso", "seu",
+        ],
+    ),
+    (
+        "nl",
+        &[
+            "het", "een", "van", "niet", "zijn", "voor", "dat", "met", "op",
+            "ook", "wordt", "bij", "naar", "worden", "deze",
+        ],
+    ),
+    (
+        "sv",
+        &[
+            "och", "att", "det", "som", "är", "på", "för", "med", "inte", "av",
+            "till", "har", "också", "ett", "vid",
+        ],
+    ),
+    (
+        "pl",
+        &[
+            "w", "nie", "na", "się", "jest", "z", "że", "jak", "oraz", "dla",
+            "przez", "od", "są", "tak", "być",
+        ],
+    ),
+];
+
+/// Minimum number of common words needed to decide on a Latin language.
+const MIN_STOPWORDS: usize = 3;
+
+/// How to translate changes.
+#[derive(Clone, Debug, Eq, PartialEq, serde::Deserialize, serde::Serialize)]
+#[serde(deny_unknown_fields)]
+pub struct Translate {
+    /// Command to translate text with; see the [module docs](self).
+    pub command: String,
+
+    /// Language to translate to (default: `en`). Changes that are already
+    /// in this language aren’t translated.
+    #[serde(default = "default_target", skip_serializing_if = "is_default")]
+    pub to: String,
+}
+
+/// Get the default target language.
+fn default_target() -> String {
+    "en".to_owned()
+}
+
+/// Check if `to` is the default target language.
+fn is_default(to: &str) -> bool {
+    to == "en"
+}
+
+impl Translate {
+    /// Check if content in `language` needs translating.
+    #[must_use]
+    pub fn wants(&self, language: &str) -> bool {
+        let target = self.to.split(['-', '_']).next().unwrap_or_default();
+        !target.eq_ignore_ascii_case(language)
+    }
+
+    /// Translate `text` from `language` with the command.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if the command couldn’t be run, failed, or didn’t
+    /// output UTF-8.
+    pub async fn run(
+        &self,
+        url: &Url,
+        language: &str,
+        text: &str,
+    ) -> anyhow::Result<String> {
+        let command = &self.command;
+        let mut child = Command::new("sh")
+            .arg("-c")
+            .arg(command)
+            .env("MONITORBOT_URL", url.as_str())
+            .env("MONITORBOT_LANGUAGE", language)
+            .env("MONITORBOT_TARGET_LANGUAGE", &self.to)
+            .stdin(Stdio::piped())
+            .stdout(Stdio::piped())
+            .kill_on_drop(true)
+            .spawn()
+            .with_context(|| format!("could not run {command:?}"))?;
+
+        // Write stdin while reading stdout, so that neither pipe fills up
+        // while the other side waits.
+        let write = child.stdin.take().map(|mut stdin| {
+            let text = text.to_owned();
+            tokio::spawn(async move {
+                // The command doesn’t have to read all of stdin.
+                let _ = stdin.write_all(text.as_bytes()).await;
+            })
+        });
+        let output = child.wait_with_output().await?;
+        if let Some(write) = write {
+            let _ = write.await;
+        }
+        if !output.status.success() {
+            bail!("{command:?} failed: {}", output.status);
+        }
+        String::from_utf8(output.stdout)
+            .with_context(|| format!("{command:?} output invalid UTF-8"))
+    }
+}
+
+/// Guess the language of `text`, returning an ISO 639-1 code like `de`.
+///
+/// Returns `None` if there isn’t enough text to tell.
+#[must_use]
+pub fn detect(text: &str) -> Option<&'static str> {
+    if let Some(language) = detect_script(text) {
+        return Some(language);
+    }
+
+    let mut counts = vec![0_usize; STOPWORDS.len()];
+    let words = text
+        .split(|c: char| !c.is_alphabetic())
+        .filter(|word| !word.is_empty())
+        .map(str::to_lowercase);
+    for word in words {
+        for (count, (_, stopwords)) in counts.iter_mut().zip(STOPWORDS) {
+            if stopwords.contains(&word.as_str()) {
+                *count = count.saturating_add(1);
+            }
+        }
+    }
+
+    let (best, count) =
+        counts.iter().enumerate().max_by_key(|&(_, count)| count)?;
+    let runner_up = counts
+        .iter()
+        .enumerate()
+        .filter(|&(index, _)| index != best)
+        .map(|(_, count)| *count)
+        .max()
+        .unwrap_or_default();
+    (*count >= MIN_STOPWORDS && *count > runner_up)
+        .then(|| STOPWORDS.get(best).map(|(language, _)| *language))
+        .flatten()
+}
+
+/// Guess the language of `text` from the script most of its letters are
+/// written in, unless that’s Latin.
+fn detect_script(text: &str) -> Option<&'static str> {
+    let mut latin = 0_usize;
+    let mut other = Vec::<(&'static str, usize)>::new();
+    for c in text.chars().filter(|c| c.is_alphabetic()) {
+        let language = match c {
+            'і' | 'ї' | 'є' | 'ґ' | 'І' | 'Ї' | 'Є' | 'Ґ' => "uk",
+            '\u{0400}'..='\u{04ff}' => "ru",
+            '\u{0370}'..='\u{03ff}' => "el",
+            '\u{0590}'..='\u{05ff}' => "he",
+            '\u{0600}'..='\u{06ff}' => "ar",
+            '\u{0900}'..='\u{097f}' => "hi",
+            '\u{0e00}'..='\u{0e7f}' => "th",
+            '\u{3040}'..='\u{30ff}' => "ja",
+            '\u{ac00}'..='\u{d7af}' | '\u{1100}'..='\u{11ff}' => "ko",
+            '\u{4e00}'..='\u{9fff}' => "zh",
+            _ => {
+                latin = latin.saturating_add(1);
+                continue;
+            }
+        };
+        match other.iter_mut().find(|(other, _)| *other == language) {
+            Some((_, count)) => *count = count.saturating_add(1),
+            None => other.push((language, 1)),
+        }
+    }
+
+    let count = |language| {
+        other
+            .iter()
+            .find(|(other, _)| *other == language)
+            .map_or(0, |(_, count)| *count)
+    };
+    let total = other
+        .iter()
+        .fold(0_usize, |total, (_, count)| total.saturating_add(*count));
+    if total <= latin {
+        return None;
+    }
+    // Japanese mixes kana with Chinese characters; Ukrainian and Russian
+    // share most letters.
+    if count("ja") > 0 {
+        return Some("ja");
+    }
+    if count("uk") > 0 {
+        return Some("uk");
+    }
+    other
+        .iter()
+        .max_by_key(|(_, count)| *count)
+        .map(|(language, _)| *language)
+}
+
+/// Get the lines of `new` that aren’t in `old`, in order.
+#[must_use]
+pub fn added_lines(old: &str, new: &str) -> String {
+    let old = old.lines().collect::<HashSet<_>>();
+    new.lines()
+        .filter(|line| !line.trim().is_empty() && !old.contains(line))
+        .collect::<Vec<_>>()
+        .join("\n")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use assert2::check;
+
+    #[test]
+    fn test_detect() {
+        check!(
+            detect("The rules for the use of this form are below.")
+                == Some("en")
+        );
+        check!(
+            detect(
+                "Die Änderungen an der Verordnung sind nicht mit den Regeln vereinbar."
+            ) == Some("de")
+        );
+        check!(
+            detect(
+                "Les modifications de la loi sont publiées dans le journal et sur le site."
+            ) == Some("fr")
+        );
+        check!(detect("Изменения в правилах вступают в силу.") == Some("ru"));
+        check!(detect("Зміни до правил набувають чинності.") == Some("uk"));
+        check!(detect("規則の変更は来月から適用されます。") == Some("ja"));
+        check!(detect("Hello") == None);
+    }
+
+    #[test]
+    fn test_stopwords() {
+        let mut seen = HashSet::new();
+        for (_, stopwords) in STOPWORDS {
+            for word in *stopwords {
+                check!(seen.insert(word), "{word:?} is in more than one list");
+            }
+        }
+
+        check!(
+            detect("Los cambios en el reglamento son más claros y muy útiles.")
+                == Some("es")
+        );
+        check!(
+            detect("Le modifiche della legge sono anche nel testo per tutti.")
+                == Some("it")
+        );
+        check!(
+            detect("As mudanças da lei não são claras e isso é um problema.")
+                == Some("pt")
+        );
+        check!(
+            detect("De wijzigingen van het reglement zijn niet voor iedereen.")
+                == Some("nl")
+        );
+        // Only words shared between languages isn’t enough to tell.
+        check!(detect("que de en una para con") == None);
+    }
+
+    #[tokio::test]
+    async fn test_translate() {
+        let translate = Translate {
+            command: r#"test "$MONITORBOT_LANGUAGE" = de \
+                && test "$MONITORBOT_TARGET_LANGUAGE" = en-GB \
+                && tr a-z A-Z"#
+                .to_owned(),
+            to: "en-GB".to_owned(),
+        };
+        check!(translate.wants("de"));
+        check!(!translate.wants("en"));
+
+        let url = "https://example.com/".parse().unwrap();
+        let added = added_lines("a\nb\n", "a\nneu\n\nb\nauch neu\n");
+        check!(added == "neu\nauch neu");
+        check!(
+            translate.run(&url, "de", &added).await.unwrap() == "NEU\nAUCH NEU"
+        );
+        check!(translate.run(&url, "fr", &added).await.is_err());
+
+        // Large text doesn’t fill up the pipes.
+        let cat = Translate { command: "cat".to_owned(), to: "en".to_owned() };
+        let large = "neu\n".repeat(1 << 20);
+        check!(cat.run(&url, "de", &large).await.unwrap() == large);
+    }
+}
@@ -0,0 +1,200 @@
+//! Fetching URLs through SSH tunnels (`ssh_tunnel`).
+//!
+//! Pages that are only reachable from inside a network can be monitored by
+//! tunneling through a bastion host:
+//!
+//! ```toml
+//! ssh_tunnel = "user@bastion.example.com"  # for every URL
+//!
+//! [[url]]
+//! url = "http://wiki.internal/"
+//! ssh_tunnel = "user@other-bastion.example.com"  # for just this URL
+//! ```
+//!
+//! The first time a URL needs a tunnel, `ssh -N -D` is started with a SOCKS
+//! proxy on a free local port, and requests are sent through it. Names are
+//! resolved on the far side, so internal names work. The tunnel is shared by
+//! every URL with the same destination, and closed at the end of the run.
+//!
+//! ssh runs without prompting, so it has to be able to log in with a key or an
+//! agent. Other options, like the port or a jump host, can be set for the host
+//! in `~/.ssh/config`.
+
+use anyhow::{Context, bail};
+use std::collections::HashMap;
+use std::fmt;
+use std::net::{Ipv4Addr, TcpListener};
+use std::process::Stdio;
+use std::time::{Duration, Instant};
+use tokio::net::TcpStream;
+use tokio::process::{Child, Command};
+use url::Url;
+
+/// How long to wait for ssh to connect and start the proxy.
+const CONNECT_TIMEOUT: Duration = Duration::from_secs(30);
+
+/// How often to check whether the proxy has started.
+const POLL_INTERVAL: Duration = Duration::from_millis(100);
+
+/// Where to connect with ssh, e.g. `user@bastion.example.com`.
+#[derive(
+    Clone, Debug, Eq, Hash, PartialEq, serde::Deserialize, serde::Serialize,
+)]
+#[serde(try_from = "String", into = "String")]
+pub struct Destination(String);
+
+impl Destination {
+    /// Get the destination as it’s passed to ssh.
+    #[must_use]
+    pub fn as_str(&self) -> &str {
+        &self.0
+    }
+}
+
+impl TryFrom<String> for Destination {
+    type Error = anyhow::Error;
+
+    fn try_from(source: String) -> anyhow::Result<Self> {
+        if source.is_empty() {
+            bail!("ssh_tunnel must not be empty");
+        }
+        // ssh would take a leading - as an option.
+        if source.starts_with('-')
+            || source.contains(|c: char| c.is_whitespace() || c.is_control())
+        {
+            bail!(
+                "invalid ssh_tunnel {source:?}; expected a destination like \
+                \"user@host\""
+            );
+        }
+        Ok(Self(source))
+    }
+}
+
+impl From<Destination> for String {
+    fn from(destination: Destination) -> Self {
+        destination.0
+    }
+}
+
+impl fmt::Display for Destination {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(&self.0)
+    }
+}
+
+/// An open SSH tunnel.
+#[derive(Debug)]
+struct Tunnel {
+    /// The ssh process. It’s killed when this is dropped.
+    child: Child,
+
+    /// URL of the SOCKS proxy ssh is running.
+    proxy: Url,
+}
+
+/// SSH tunnels opened so far in this run, by destination.
+#[derive(Debug, Default)]
+pub struct Tunnels(HashMap<Destination, Tunnel>);
+
+impl Tunnels {
+    /// Get the proxy to send requests to `destination` through, opening a
+    /// tunnel if there isn’t one already.
+    ///
+    /// If ssh has exited since the tunnel was opened, e.g. because the
+    /// connection dropped, a new tunnel is opened.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if ssh couldn’t be run, or didn’t start the proxy.
+    pub async fn proxy(
+        &mut self,
+        destination: &Destination,
+    ) -> anyhow::Result<Url> {
+        if let Some(tunnel) = self.0.get_mut(destination) {
+            if tunnel.child.try_wait()?.is_none() {
+                return Ok(tunnel.proxy.clone());
+            }
+            tracing::warn!("SSH tunnel to {destination} closed; reopening it");
+        }
+
+        let tunnel = open("ssh", destination).await?;
+        let proxy = tunnel.proxy.clone();
+        self.0.insert(destination.clone(), tunnel);
+        Ok(proxy)
+    }
+}
+
+/// Start `program` (ssh) with a SOCKS proxy and wait until it’s listening.
+///
+/// # Errors
+///
+/// Returns an error if ssh couldn’t be run, exited, or didn’t start the proxy
+/// within [`CONNECT_TIMEOUT`].
+async fn open(
+    program: &str,
+    destination: &Destination,
+) -> anyhow::Result<Tunnel> {
+    let port = free_port().context("could not find a free port")?;
+    tracing::info!("Opening SSH tunnel to {destination} on port {port}");
+    let mut child = Command::new(program)
+        .args(["-N", "-D", &format!("127.0.0.1:{port}")])
+        .args(["-o", "BatchMode=yes", "-o", "ExitOnForwardFailure=yes"])
+        .arg("--")
+        .arg(destination.as_str())
+        .stdin(Stdio::null())
+        .kill_on_drop(true)
+        .spawn()
+        .with_context(|| format!("could not run {program}"))?;
+
+    let started = Instant::now();
+    loop {
+        if let Some(status) = child.try_wait()? {
+            bail!("ssh to {destination} exited with {status}");
+        }
+        if TcpStream::connect((Ipv4Addr::LOCALHOST, port))
+            .await
+            .is_ok()
+        {
+            break;
+        }
+        if started.elapsed() > CONNECT_TIMEOUT {
+            bail!("timed out waiting for ssh to {destination}");
+        }
+        tokio::time::sleep(POLL_INTERVAL).await;
+    }
+
+    Ok(Tunnel { child, proxy: format!("socks5h://127.0.0.1:{port}").parse()? })
+}
+
+/// Find a local port that nothing is listening on.
+///
+/// # Errors
+///
+/// Returns an error if a port couldn’t be bound.
+fn free_port() -> std::io::Result<u16> {
+    Ok(TcpListener::bind((Ipv4Addr::LOCALHOST, 0))?
+        .local_addr()?
+        .port())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use assert2::check;
+
+    #[tokio::test]
+    async fn test_open() {
+        let destination =
+            |source: &str| Destination::try_from(source.to_owned());
+        check!(destination("user@bastion").is_ok());
+        check!(destination("").is_err());
+        check!(destination("-oProxyCommand=true").is_err());
+        check!(destination("user@bastion true").is_err());
+
+        let error = open("false", &destination("user@bastion").unwrap())
+            .await
+            .unwrap_err();
+        check!(error.to_string().starts_with("ssh to user@bastion exited"));
+    }
+}
@@ -0,0 +1,664 @@
+//! Extracting structured values from pages and alerting on them.
+//!
+//! A URL’s `extract` rules pull values like a price or a version number out of
+//! each response, either with the named groups of a regular expression or
+//! from the elements matching a CSS selector. The values are stored with the
+//! response, as numbers when they look like numbers, so that `alert`
+//! conditions like `price < 100` can compare them. An alert is notified when
+//! its condition starts to hold.
+
+use anyhow::{anyhow, bail};
+use regex::Regex;
+use scraper::{Html, Selector};
+use std::collections::BTreeMap;
+use std::fmt;
+
+use crate::config::UrlConfig;
+use crate::response::Response;
+
+/// A rule for extracting values from a page.
+#[derive(Clone, Debug, serde::Deserialize, serde::Serialize)]
+#[serde(deny_unknown_fields)]
+pub struct ValueRule {
+    /// Name of the value. With `regex`, this is the first group or the whole
+    /// match; without it, each named group is a value.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub name: Option<String>,
+
+    /// Regular expression to match against the body.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub regex: Option<String>,
+
+    /// CSS selector for the element containing the value. The first match is
+    /// used.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub selector: Option<String>,
+
+    /// Attribute of the element containing the value (default: its text).
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub attribute: Option<String>,
+
+    /// How to store the value.
+    #[serde(
+        default,
+        rename = "type",
+        skip_serializing_if = "ValueType::is_auto"
+    )]
+    pub value_type: ValueType,
+
+    /// Notify when the value goes above this.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub above: Option<f64>,
+
+    /// Notify when the value goes below this.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub below: Option<f64>,
+
+    /// Notify when the value changes by more than this between checks, e.g.
+    /// `"5%"` or `10`.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub changed_by_more_than: Option<Threshold>,
+}
+
+impl ValueRule {
+    /// Get the names of the values the rule extracts.
+    ///
+    /// Returns nothing if the rule is invalid; see [`extract()`].
+    #[must_use]
+    pub fn names(&self) -> Vec<String> {
+        if let Some(name) = &self.name {
+            return vec![name.clone()];
+        }
+        self.regex
+            .as_deref()
+            .and_then(|source| Regex::new(source).ok())
+            .map(|regex| {
+                regex.capture_names().flatten().map(str::to_owned).collect()
+            })
+            .unwrap_or_default()
+    }
+
+    /// Check a value against the rule’s thresholds.
+    ///
+    /// `above` and `below` are only reported when the value crosses them, so
+    /// they aren’t reported again on every check. Returns a message for each
+    /// threshold that was crossed.
+    #[must_use]
+    pub fn crossed(
+        &self,
+        name: &str,
+        old: Option<&Value>,
+        new: Option<&Value>,
+    ) -> Vec<String> {
+        let (old, new) =
+            (old.and_then(Value::number), new.and_then(Value::number));
+        let Some(new) = new else {
+            return Vec::new();
+        };
+        let mut crossed = Vec::new();
+        if let Some(above) = self.above
+            && new > above
+            && !old.is_some_and(|old| old > above)
+        {
+            crossed.push(format!("{name} went above {above}: {new}"));
+        }
+        if let Some(below) = self.below
+            && new < below
+            && !old.is_some_and(|old| old < below)
+        {
+            crossed.push(format!("{name} went below {below}: {new}"));
+        }
+        if let Some(threshold) = self.changed_by_more_than
+            && let Some(old) = old
+            && threshold.exceeded(old, new)
+        {
+            crossed.push(format!(
+                "{name} changed by more than {threshold}: {old} → {new}"
+            ));
+        }
+        crossed
+    }
+}
+
+/// How much a value may change between checks without a notification.
+#[derive(
+    Clone, Copy, Debug, PartialEq, serde::Deserialize, serde::Serialize,
+)]
+#[serde(try_from = "Value", into = "Value")]
+pub enum Threshold {
+    /// An absolute amount.
+    Absolute(f64),
+
+    /// A percentage of the old value.
+    Percent(f64),
+}
+
+impl Threshold {
+    /// Check if the change from `old` to `new` is more than the threshold.
+    ///
+    /// Any change from zero is more than a percentage of it.
+    #[must_use]
+    pub fn exceeded(self, old: f64, new: f64) -> bool {
+        let change = (new - old).abs();
+        match self {
+            Self::Absolute(amount) => change > amount,
+            Self::Percent(_) if old == 0.0 => change > 0.0,
+            Self::Percent(percent) => change / old.abs() * 100.0 > percent,
+        }
+    }
+}
+
+impl fmt::Display for Threshold {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::Absolute(amount) => write!(f, "{amount}"),
+            Self::Percent(percent) => write!(f, "{percent}%"),
+        }
+    }
+}
+
+impl TryFrom<Value> for Threshold {
+    type Error = anyhow::Error;
+
+    fn try_from(value: Value) -> anyhow::Result<Self> {
+        let threshold = match &value {
+            Value::Number(amount) => Some(Self::Absolute(*amount)),
+            Value::Text(text) => text
+                .trim()
+                .strip_suffix('%')
+                .and_then(|percent| percent.trim().parse().ok())
+                .map(Self::Percent),
+        };
+        threshold
+            .filter(|threshold| match threshold {
+                Self::Absolute(amount) | Self::Percent(amount) => {
+                    *amount >= 0.0
+                }
+            })
+            .ok_or_else(|| {
+                anyhow!("invalid threshold {value}; expected e.g. 10 or \"5%\"")
+            })
+    }
+}
+
+impl From<Threshold> for Value {
+    fn from(threshold: Threshold) -> Self {
+        match threshold {
+            Threshold::Absolute(amount) => Self::Number(amount),
+            Threshold::Percent(_) => Self::Text(threshold.to_string()),
+        }
+    }
+}
+
+/// How to store an extracted value.
+#[derive(
+    Clone,
+    Copy,
+    Debug,
+    Default,
+    Eq,
+    PartialEq,
+    serde::Deserialize,
+    serde::Serialize,
+)]
+#[serde(rename_all = "kebab-case")]
+pub enum ValueType {
+    /// A number if it looks like one, otherwise text.
+    #[default]
+    Auto,
+
+    /// A number, ignoring anything but digits, `.`, and `-`, so that
+    /// `$1,299.00` is 1299.
+    Number,
+
+    /// Text.
+    Text,
+}
+
+impl ValueType {
+    /// Check if this is the default, [`ValueType::Auto`].
+    #[must_use]
+    pub const fn is_auto(&self) -> bool {
+        matches!(self, Self::Auto)
+    }
+
+    /// Convert extracted text to a value.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if a [`ValueType::Number`] doesn’t contain a number.
+    pub fn convert(self, text: &str) -> anyhow::Result<Value> {
+        let text = text.split_whitespace().collect::<Vec<_>>().join(" ");
+        match self {
+            Self::Auto => Ok(Value::parse(&text)),
+            Self::Number => text
+                .chars()
+                .filter(|c| c.is_ascii_digit() || matches!(c, '.' | '-'))
+                .collect::<String>()
+                .parse()
+                .map(Value::Number)
+                .map_err(|_| anyhow!("{text:?} is not a number")),
+            Self::Text => Ok(Value::Text(text)),
+        }
+    }
+}
+
+/// A value extracted from a page.
+#[derive(Clone, Debug, PartialEq, serde::Deserialize, serde::Serialize)]
+#[serde(untagged)]
+pub enum Value {
+    /// A number.
+    Number(f64),
+
+    /// Anything else.
+    Text(String),
+}
+
+impl Value {
+    /// Parse a number if the text looks like one, or keep it as text.
+    #[must_use]
+    pub fn parse(text: &str) -> Self {
+        let numeric = text.chars().any(|c| c.is_ascii_digit())
+            && text
+                .chars()
+                .all(|c| c.is_ascii_digit() || matches!(c, '.' | '-' | '+'));
+        text.parse()
+            .ok()
+            .filter(|_| numeric)
+            .map_or_else(|| Self::Text(text.to_owned()), Self::Number)
+    }
+
+    /// Get the value as a number, if it is one.
+    #[must_use]
+    pub const fn number(&self) -> Option<f64> {
+        match self {
+            Self::Number(number) => Some(*number),
+            Self::Text(_) => None,
+        }
+    }
+}
+
+impl fmt::Display for Value {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::Number(number) => write!(f, "{number}"),
+            Self::Text(text) => write!(f, "{text:?}"),
+        }
+    }
+}
+
+/// Extract the values from a response.
+///
+/// Values that aren’t found are left out.
+///
+/// # Errors
+///
+/// Returns an error if a rule is invalid, a value isn’t the configured type,
+/// or the response couldn’t be decoded.
+pub fn extract(
+    rules: &[ValueRule],
+    response: &Response,
+) -> anyhow::Result<BTreeMap<String, Value>> {
+    let mut values = BTreeMap::new();
+    if rules.is_empty() || response.spooled.is_some() {
+        return Ok(values);
+    }
+    let text = response.text()?;
+    let mut html = None;
+    for rule in rules {
+        let found = match (&rule.regex, &rule.selector) {
+            (Some(source), None) => extract_regex(rule, source, &text)?,
+            (None, Some(source)) => {
+                let html =
+                    html.get_or_insert_with(|| Html::parse_document(&text));
+                extract_selector(rule, source, html)?
+            }
+            _ => bail!("extract rules need either regex or selector"),
+        };
+        for (name, text) in found {
+            values.insert(name, rule.value_type.convert(&text)?);
+        }
+    }
+    Ok(values)
+}
+
+/// Extract values with a regular expression.
+///
+/// # Errors
+///
+/// Returns an error if the regular expression is invalid, or if there’s no
+/// `name` and it has no named groups.
+fn extract_regex(
+    rule: &ValueRule,
+    source: &str,
+    text: &str,
+) -> anyhow::Result<Vec<(String, String)>> {
+    let regex = Regex::new(source).map_err(|error| anyhow!("{error}"))?;
+    let names = regex.capture_names().flatten().collect::<Vec<_>>();
+    if rule.name.is_none() && names.is_empty() {
+        bail!("extract regex {source:?} needs a name or named groups");
+    }
+    let Some(captures) = regex.captures(text) else {
+        return Ok(Vec::new());
+    };
+    if let Some(name) = &rule.name {
+        let found = captures.get(1).or_else(|| captures.get(0));
+        return Ok(found
+            .map(|found| (name.clone(), found.as_str().to_owned()))
+            .into_iter()
+            .collect());
+    }
+    Ok(names
+        .into_iter()
+        .filter_map(|name| {
+            Some((name.to_owned(), captures.name(name)?.as_str().to_owned()))
+        })
+        .collect())
+}
+
+/// Extract a value from the first element matching a selector.
+///
+/// # Errors
+///
+/// Returns an error if the selector is invalid, or there’s no `name`.
+fn extract_selector(
+    rule: &ValueRule,
+    source: &str,
+    html: &Html,
+) -> anyhow::Result<Vec<(String, String)>> {
+    let Some(name) = &rule.name else {
+        bail!("extract selector {source:?} needs a name");
+    };
+    let selector = Selector::parse(source)
+        .map_err(|error| anyhow!("invalid extract selector: {error}"))?;
+    let Some(element) = html.select(&selector).next() else {
+        return Ok(Vec::new());
+    };
+    let text = match &rule.attribute {
+        Some(attribute) => element.attr(attribute).map(str::to_owned),
+        None => Some(element.text().collect()),
+    };
+    Ok(text.map(|text| (name.clone(), text)).into_iter().collect())
+}
+
+/// Find the `alert` conditions that started to hold and the thresholds that
+/// were crossed between the values of two checks.
+///
+/// Returns a message for each one.
+#[must_use]
+pub fn alerts(
+    url_config: &UrlConfig,
+    old: Option<&BTreeMap<String, Value>>,
+    new: &BTreeMap<String, Value>,
+) -> Vec<String> {
+    let mut alerts = Vec::new();
+    for condition in &url_config.alert {
+        // Conditions only hold for values that were found.
+        if let Some(value) = new.get(condition.name())
+            && condition.holds(new)
+            && !old.is_some_and(|old| condition.holds(old))
+        {
+            alerts
+                .push(format!("{condition} ({} is {value})", condition.name()));
+        }
+    }
+    for rule in &url_config.extract {
+        for name in rule.names() {
+            alerts.extend(rule.crossed(
+                &name,
+                old.and_then(|old| old.get(&name)),
+                new.get(&name),
+            ));
+        }
+    }
+    alerts
+}
+
+/// A condition on an extracted value, e.g. `price < 100`.
+#[derive(Clone, Debug, PartialEq, serde::Deserialize, serde::Serialize)]
+#[serde(try_from = "String", into = "String")]
+pub struct Condition {
+    /// The source, for serializing and output.
+    source: String,
+
+    /// The name of the value.
+    name: String,
+
+    /// How to compare the value.
+    operator: Operator,
+
+    /// What to compare the value to.
+    operand: Value,
+}
+
+/// How to compare a value in a [`Condition`].
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+enum Operator {
+    /// Less than (`<`).
+    Less,
+
+    /// Less than or equal to (`<=`).
+    LessOrEqual,
+
+    /// Greater than (`>`).
+    Greater,
+
+    /// Greater than or equal to (`>=`).
+    GreaterOrEqual,
+
+    /// Equal to (`==`).
+    Equal,
+
+    /// Not equal to (`!=`).
+    NotEqual,
+}
+
+impl Condition {
+    /// Check if the condition holds for a set of values.
+    ///
+    /// Conditions on values that weren’t found, or on a number and text,
+    /// never hold.
+    #[must_use]
+    pub fn holds(&self, values: &BTreeMap<String, Value>) -> bool {
+        let ordering = match (values.get(&self.name), &self.operand) {
+            (Some(Value::Number(value)), Value::Number(operand)) => {
+                value.partial_cmp(operand)
+            }
+            (Some(Value::Text(value)), Value::Text(operand)) => {
+                Some(value.cmp(operand))
+            }
+            _ => None,
+        };
+        let Some(ordering) = ordering else {
+            return false;
+        };
+        match self.operator {
+            Operator::Less => ordering.is_lt(),
+            Operator::LessOrEqual => ordering.is_le(),
+            Operator::Greater => ordering.is_gt(),
+            Operator::GreaterOrEqual => ordering.is_ge(),
+            Operator::Equal => ordering.is_eq(),
+            Operator::NotEqual => ordering.is_ne(),
+        }
+    }
+
+    /// The name of the value the condition is on.
+    #[must_use]
+    pub fn name(&self) -> &str {
+        &self.name
+    }
+}
+
+impl fmt::Display for Condition {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(&self.source)
+    }
+}
+
+impl TryFrom<String> for Condition {
+    type Error = anyhow::Error;
+
+    fn try_from(source: String) -> anyhow::Result<Self> {
+        const OPERATORS: [(&str, Operator); 6] = [
+            ("<=", Operator::LessOrEqual),
+            (">=", Operator::GreaterOrEqual),
+            ("==", Operator::Equal),
+            ("!=", Operator::NotEqual),
+            ("<", Operator::Less),
+            (">", Operator::Greater),
+        ];
+        let Some((name, operator, operand)) =
+            OPERATORS.iter().find_map(|(symbol, operator)| {
+                let (name, operand) = source.split_once(symbol)?;
+                Some((name.trim(), *operator, operand.trim()))
+            })
+        else {
+            bail!(
+                "invalid alert {source:?}; expected e.g. \"price < 100\" with \
+                <, <=, >, >=, ==, or !="
+            );
+        };
+        if name.is_empty() || name.contains(char::is_whitespace) {
+            bail!(
+                "invalid alert {source:?}; expected a value name before {operator:?}"
+            );
+        }
+        let operand = operand
+            .strip_prefix('"')
+            .and_then(|operand| operand.strip_suffix('"'))
+            .map_or_else(
+                || Value::parse(operand),
+                |text| Value::Text(text.to_owned()),
+            );
+        Ok(Self { name: name.to_owned(), operator, operand, source })
+    }
+}
+
+impl From<Condition> for String {
+    fn from(condition: Condition) -> Self {
+        condition.source
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use assert2::check;
+
+    #[test]
+    fn test_extract() {
+        let response = Response {
+            url: "https://example.com/".parse().unwrap(),
+            version: http::Version::HTTP_11,
+            status: http::StatusCode::OK,
+            headers: http::HeaderMap::new(),
+            body: r#"<p>Version 1.2.3 <span class="price">$1,299.00</span></p>
+                <meta itemprop="stock" content=" 7 ">"#
+                .into(),
+            spooled: None,
+            certificate_sha256: None,
+            peer_address: None,
+        };
+        let rule =
+            |source: &str| -> ValueRule { toml::from_str(source).unwrap() };
+        let values = extract(
+            &[
+                rule(r"regex = 'Version (?<version>[\d.]+)'"),
+                rule(
+                    "name = \"price\"\nselector = \".price\"\n\
+                    type = \"number\"",
+                ),
+                rule(
+                    "name = \"stock\"\nselector = \"meta\"\n\
+                    attribute = \"content\"",
+                ),
+                rule("name = \"missing\"\nselector = \"table\""),
+            ],
+            &response,
+        )
+        .unwrap();
+        check!(
+            values
+                == BTreeMap::from([
+                    ("price".to_owned(), Value::Number(1299.0)),
+                    ("stock".to_owned(), Value::Number(7.0)),
+                    ("version".to_owned(), Value::Text("1.2.3".to_owned())),
+                ])
+        );
+
+        let stored = ron::to_string(&values).unwrap();
+        check!(
+            ron::from_str::<BTreeMap<String, Value>>(&stored).unwrap()
+                == values
+        );
+
+        check!(extract(&[rule("regex = 'Version'")], &response).is_err());
+
+        let condition = |source: &str| {
+            Condition::try_from(source.to_owned())
+                .unwrap()
+                .holds(&values)
+        };
+        check!(condition("price < 1300"));
+        check!(!condition("price>=1300"));
+        check!(condition("stock != 0"));
+        check!(condition("version == \"1.2.3\""));
+        check!(!condition("version < 2"));
+        check!(!condition("missing == 0"));
+        check!(Condition::try_from("price".to_owned()).is_err());
+        check!(Condition::try_from("< 100".to_owned()).is_err());
+    }
+
+    #[test]
+    fn test_alerts() {
+        let url_config: UrlConfig = toml::from_str(
+            r#"
+            url = "https://example.com/"
+            extract = [
+                { regex = 'v(?<version>\S+)', type = "text" },
+                { name = "price", regex = '\$(\S+)', below = 100, changed_by_more_than = "10%" },
+                { name = "stock", regex = 'stock (\d+)', above = 5, changed_by_more_than = 2 },
+            ]
+            alert = ['version == "2"']
+            "#,
+        )
+        .unwrap();
+        let values = |price: f64, stock: f64, version: &str| {
+            BTreeMap::from([
+                ("price".to_owned(), Value::Number(price)),
+                ("stock".to_owned(), Value::Number(stock)),
+                ("version".to_owned(), Value::Text(version.to_owned())),
+            ])
+        };
+
+        check!(
+            alerts(&url_config, None, &values(120.0, 3.0, "1"))
+                == Vec::<String>::new()
+        );
+        check!(
+            alerts(
+                &url_config,
+                Some(&values(120.0, 3.0, "1")),
+                &values(99.0, 6.0, "2")
+            ) == [
+                r#"version == "2" (version is "2")"#,
+                "price went below 100: 99",
+                "price changed by more than 10%: 120 → 99",
+                "stock went above 5: 6",
+                "stock changed by more than 2: 3 → 6",
+            ]
+        );
+        check!(
+            alerts(
+                &url_config,
+                Some(&values(99.0, 6.0, "2")),
+                &values(95.0, 7.0, "2")
+            ) == Vec::<String>::new()
+        );
+
+        check!(Threshold::Percent(10.0).exceeded(0.0, 1.0));
+        check!(!Threshold::Percent(10.0).exceeded(-100.0, -91.0));
+        check!(Threshold::try_from(Value::Text("5".to_owned())).is_err());
+        check!(Threshold::try_from(Value::Number(-1.0)).is_err());
+    }
+}
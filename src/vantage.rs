@@ -0,0 +1,136 @@
+//! Fetching URLs from several vantage points.
+//!
+//! CDNs take a while to propagate changes, and some sites show different
+//! content depending on where a visitor is. To catch that, URLs can also be
+//! fetched through proxies, e.g. in other regions or SSH SOCKS tunnels, and
+//! compared to the normal response:
+//!
+//! ```toml
+//! [[vantage]]
+//! name = "eu"
+//! proxy = "socks5h://localhost:1080"  # ssh -D 1080 eu.example.com
+//!
+//! [[url]]
+//! url = "https://example.com/"
+//! vantages = ["eu"]
+//! ```
+//!
+//! A notification is sent when a vantage point sees different content, and
+//! again when they all agree.
+
+use anyhow::bail;
+use std::collections::HashSet;
+use url::Url;
+
+use crate::config::UrlConfig;
+
+/// A proxy to fetch URLs through.
+#[derive(Clone, Debug, serde::Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct Vantage {
+    /// Name used in `vantages` and in output.
+    pub name: String,
+
+    /// URL of the proxy: `http`, `https`, `socks5`, or `socks5h` to resolve
+    /// names through the proxy.
+    pub proxy: Url,
+}
+
+/// Find a vantage point by name.
+#[must_use]
+pub fn find<'a>(vantages: &'a [Vantage], name: &str) -> Option<&'a Vantage> {
+    vantages.iter().find(|vantage| vantage.name == name)
+}
+
+/// Check that vantage point names are unique, and that every name in a URL’s
+/// `vantages` is defined.
+///
+/// # Errors
+///
+/// Returns an error describing the first problem found.
+pub fn validate(
+    vantages: &[Vantage],
+    urls: &[UrlConfig],
+) -> anyhow::Result<()> {
+    let mut names = HashSet::new();
+    for vantage in vantages {
+        if !names.insert(vantage.name.as_str()) {
+            bail!("vantage {:?} is defined more than once", vantage.name);
+        }
+        if !matches!(
+            vantage.proxy.scheme(),
+            "http" | "https" | "socks5" | "socks5h"
+        ) {
+            bail!(
+                "proxy for vantage {:?} must be http, https, socks5, or socks5h",
+                vantage.name,
+            );
+        }
+    }
+    for url_config in urls {
+        if let Some(name) = url_config
+            .vantages
+            .iter()
+            .find(|name| !names.contains(name.as_str()))
+        {
+            bail!("unknown vantage {name:?} for {}", url_config.url);
+        }
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::config::Config;
+    use assert2::check;
+
+    /// Parse a configuration and validate its vantage points.
+    fn load(source: &str) -> anyhow::Result<Config> {
+        let config: Config = toml::from_str(source)?;
+        validate(&config.vantages, &config.urls)?;
+        Ok(config)
+    }
+
+    #[test]
+    fn test_validate() {
+        let config = load(
+            r#"
+            [[vantage]]
+            name = "eu"
+            proxy = "socks5h://localhost:1080"
+
+            [[url]]
+            url = "https://example.com/"
+            vantages = ["eu"]
+            "#,
+        )
+        .unwrap();
+        check!(find(&config.vantages, "eu").is_some());
+        check!(find(&config.vantages, "us").is_none());
+
+        let error = load(
+            r#"
+            [[url]]
+            url = "https://example.com/"
+            vantages = ["us"]
+            "#,
+        )
+        .unwrap_err();
+        check!(
+            error.to_string()
+                == "unknown vantage \"us\" for https://example.com/"
+        );
+
+        check!(
+            load(
+                r#"
+                [[vantage]]
+                name = "eu"
+                proxy = "ftp://localhost/"
+                "#,
+            )
+            .is_err()
+        );
+    }
+}
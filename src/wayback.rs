@@ -0,0 +1,86 @@
+//! Archiving changed pages in the Internet Archive’s Wayback Machine.
+//!
+//! URLs with `wayback = true` are submitted to [Save Page Now] when a change
+//! is detected. The service redirects to the new snapshot once it’s been
+//! captured, so its URL can be included with the change as an independent
+//! record of what the page looked like.
+//!
+//! [Save Page Now]: https://web.archive.org/save
+
+use anyhow::{Context, bail};
+use url::Url;
+
+/// Where to submit URLs to be saved.
+const SAVE_URL: &str = "https://web.archive.org/save/";
+
+/// Ask the Wayback Machine to save a snapshot of `url`.
+///
+/// Returns the URL of the snapshot.
+///
+/// # Errors
+///
+/// Returns an error if the request failed, or the response didn’t identify the
+/// snapshot.
+pub async fn save(client: &reqwest::Client, url: &Url) -> anyhow::Result<Url> {
+    let mut url = url.clone();
+    url.set_fragment(None);
+    let save_url = Url::parse(&format!("{SAVE_URL}{url}"))?;
+    let response = client
+        .get(save_url)
+        .send()
+        .await
+        .and_then(reqwest::Response::error_for_status)
+        .context("could not save to the Wayback Machine")?;
+    if let Some(snapshot) = snapshot_url(response.url(), response.headers()) {
+        return Ok(snapshot);
+    }
+    bail!("the Wayback Machine didn’t return a snapshot URL");
+}
+
+/// Find the URL of the snapshot in a response from Save Page Now.
+///
+/// The service usually redirects to the snapshot, but sometimes it responds
+/// directly with the path of the snapshot in `Content-Location`.
+fn snapshot_url(response_url: &Url, headers: &http::HeaderMap) -> Option<Url> {
+    if response_url.path().starts_with("/web/") {
+        return Some(response_url.clone());
+    }
+    headers
+        .get(http::header::CONTENT_LOCATION)
+        .and_then(|value| value.to_str().ok())
+        .filter(|location| location.starts_with("/web/"))
+        .and_then(|location| response_url.join(location).ok())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use assert2::check;
+
+    #[test]
+    fn test_snapshot_url() {
+        let snapshot: Url =
+            "https://web.archive.org/web/20250102030405/https://example.com/"
+                .parse()
+                .unwrap();
+        check!(
+            snapshot_url(&snapshot, &http::HeaderMap::new()) == Some(snapshot)
+        );
+
+        let save: Url = "https://web.archive.org/save/https://example.com/"
+            .parse()
+            .unwrap();
+        let mut headers = http::HeaderMap::new();
+        check!(snapshot_url(&save, &headers) == None);
+
+        headers.insert(
+            http::header::CONTENT_LOCATION,
+            "/web/20250102030405/https://example.com/".parse().unwrap(),
+        );
+        check!(
+            snapshot_url(&save, &headers).unwrap().as_str()
+                == "https://web.archive.org/web/20250102030405/\
+                    https://example.com/"
+        );
+    }
+}